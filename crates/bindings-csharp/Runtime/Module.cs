@@ -21,6 +21,53 @@ public partial struct IndexDef
     }
 }
 
+[SpacetimeDB.Type]
+public enum ReferentialAction : byte
+{
+    Restrict,
+    Cascade,
+}
+
+[SpacetimeDB.Type]
+public partial struct ForeignKeyDef
+{
+    string Name;
+    byte ColumnId;
+    string Table;
+    string Column;
+    ReferentialAction OnDelete;
+
+    public ForeignKeyDef(
+        string name,
+        byte columnId,
+        string table,
+        string column,
+        ReferentialAction onDelete
+    )
+    {
+        Name = name;
+        ColumnId = columnId;
+        Table = table;
+        Column = column;
+        OnDelete = onDelete;
+    }
+}
+
+[SpacetimeDB.Type]
+public partial struct CheckDef
+{
+    string Name;
+
+    // The SQL predicate that every row of the table must satisfy.
+    string Expr;
+
+    public CheckDef(string name, string expr)
+    {
+        Name = name;
+        Expr = expr;
+    }
+}
+
 [SpacetimeDB.Type]
 public partial struct TableDef
 {
@@ -36,6 +83,12 @@ public partial struct TableDef
     // "public" | "private"
     string TableAccess;
 
+    ForeignKeyDef[] ForeignKeys;
+    CheckDef[] Checks;
+
+    // The SQL predicate restricting the rows visible to the identities other than the owner.
+    string? RowPolicy;
+
     public TableDef(
         string name,
         AlgebraicTypeRef type,
@@ -49,6 +102,9 @@ public partial struct TableDef
         Indices = indices;
         TableType = "user";
         TableAccess = name.StartsWith('_') ? "private" : "public";
+        ForeignKeys = new ForeignKeyDef[] { };
+        Checks = new CheckDef[] { };
+        RowPolicy = null;
     }
 }
 
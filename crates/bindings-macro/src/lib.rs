@@ -33,18 +33,42 @@ mod sym {
     /// Matches `autoinc`.
     pub const AUTOINC: Symbol = Symbol("autoinc");
 
+    /// Matches `cascade`.
+    pub const CASCADE: Symbol = Symbol("cascade");
+
+    /// Matches `check`.
+    pub const CHECK: Symbol = Symbol("check");
+
+    /// Matches `column`.
+    pub const COLUMN: Symbol = Symbol("column");
+
     /// Matches `crate`.
     pub const CRATE: Symbol = Symbol("crate");
 
+    /// Matches `foreign_key`.
+    pub const FOREIGN_KEY: Symbol = Symbol("foreign_key");
+
     /// Matches `name`.
     pub const NAME: Symbol = Symbol("name");
 
+    /// Matches `on_delete`.
+    pub const ON_DELETE: Symbol = Symbol("on_delete");
+
     /// Matches `primarykey`.
     pub const PRIMARYKEY: Symbol = Symbol("primarykey");
 
+    /// Matches `restrict`.
+    pub const RESTRICT: Symbol = Symbol("restrict");
+
+    /// Matches `row_policy`.
+    pub const ROW_POLICY: Symbol = Symbol("row_policy");
+
     /// Matches `sats`.
     pub const SATS: Symbol = Symbol("sats");
 
+    /// Matches `table`.
+    pub const TABLE: Symbol = Symbol("table");
+
     /// Matches `unique`.
     pub const UNIQUE: Symbol = Symbol("unique");
 
@@ -75,11 +99,26 @@ mod sym {
 /// The macro takes this `input`, which defines what the attribute does,
 /// and it is structured roughly like so:
 /// ```ignore
-/// input = table | init | connect | disconnect | migrate
-///       | reducer [, repeat = Duration]
+/// input = table [, policy = string] | init | connect | disconnect | migrate
+///       | reducer [, repeat = Duration | cron = string | rate = Duration | delay = Duration]
+///                 [, catch_up = skip | once | all]
 ///       | index(btree | hash [, name = string] [, field_name:ident]*)
 /// ```
 ///
+/// An index on several fields, e.g. `#[spacetimedb(index(btree, owner, timestamp))]`,
+/// generates a `filter_by_owner_and_timestamp` accessor on the table.
+/// Lookups on leading fields of the index, e.g. `filter_by_owner`, also use it.
+///
+/// A reducer with a `cron`, `rate` or `delay` schedule is called by the host on its own:
+/// at the times matching the `cron` expression in UTC, e.g. `cron = "0 */5 * * * *"`,
+/// every `rate`, or `delay` after each call ends. It takes no arguments, or the `Timestamp` of the call.
+/// `catch_up` chooses which of the calls missed while the database was down are made,
+/// and defaults to `skip`.
+///
+/// The `policy` of a table, e.g. `#[spacetimedb(table, policy = "owner = :sender")]`,
+/// is a `SQL` predicate restricting the rows visible to the identities other than the owner,
+/// where `:sender` is the identity of the client.
+///
 /// For description of the field attributes on `#[spacetimedb(table)]` structs,
 /// see [`TableType`](spacetimedb_tabletype).
 #[proc_macro_attribute]
@@ -100,9 +139,13 @@ pub fn spacetimedb(macro_args: proc_macro::TokenStream, item: proc_macro::TokenS
 /// On `item`, route the macro `input` to the various interpretations.
 fn route_input(input: MacroInput, item: TokenStream) -> syn::Result<TokenStream> {
     match input {
-        MacroInput::Table => spacetimedb_table(item),
+        MacroInput::Table { policy } => spacetimedb_table(policy, item),
         MacroInput::Init => spacetimedb_init(item),
-        MacroInput::Reducer { repeat } => spacetimedb_reducer(repeat, item),
+        MacroInput::Reducer {
+            repeat,
+            recurrence,
+            catch_up,
+        } => spacetimedb_reducer(repeat, recurrence, catch_up, item),
         MacroInput::Connect => spacetimedb_special_reducer("__identity_connected__", item),
         MacroInput::Disconnect => spacetimedb_special_reducer("__identity_disconnected__", item),
         MacroInput::Migrate => spacetimedb_special_reducer("__migrate__", item),
@@ -122,10 +165,14 @@ fn duration_totokens(dur: Duration) -> TokenStream {
 
 /// Defines the input space of the `spacetimedb` macro.
 enum MacroInput {
-    Table,
+    Table {
+        policy: Option<syn::LitStr>,
+    },
     Init,
     Reducer {
         repeat: Option<Duration>,
+        recurrence: Option<Recurrence>,
+        catch_up: Option<CatchUp>,
     },
     Connect,
     Disconnect,
@@ -174,12 +221,29 @@ fn check_duplicate_meta<T>(x: &Option<T>, meta: &syn::meta::ParseNestedMeta<'_>)
 impl syn::parse::Parse for MacroInput {
     fn parse(input: ParseStream) -> syn::Result<Self> {
         Ok(match_tok!(match input {
-            kw::table => Self::Table,
+            kw::table => {
+                // Eat an optional comma, and then if anything follows,
+                // it has to be `policy = string`.
+                let mut policy = None;
+                comma_then_comma_delimited(input, || {
+                    match_tok!(match input {
+                        tok @ kw::policy => {
+                            check_duplicate(&policy, tok.span)?;
+                            input.parse::<Token![=]>()?;
+                            policy = Some(input.parse::<syn::LitStr>()?);
+                        }
+                    });
+                    Ok(())
+                })?;
+                Self::Table { policy }
+            }
             kw::init => Self::Init,
             kw::reducer => {
                 // Eat an optional comma, and then if anything follows,
-                // it has to be `repeat = Duration`.
+                // it has to be `repeat = Duration`, a recurrence, or `catch_up = CatchUp`.
                 let mut repeat = None;
+                let mut recurrence = None;
+                let mut catch_up = None;
                 comma_then_comma_delimited(input, || {
                     match_tok!(match input {
                         tok @ kw::repeat => {
@@ -187,10 +251,40 @@ impl syn::parse::Parse for MacroInput {
                             input.parse::<Token![=]>()?;
                             repeat = Some(input.call(parse_duration)?);
                         }
+                        tok @ kw::cron => {
+                            check_duplicate(&recurrence, tok.span)?;
+                            input.parse::<Token![=]>()?;
+                            recurrence = Some(Recurrence::Cron(input.parse()?));
+                        }
+                        tok @ kw::rate => {
+                            check_duplicate(&recurrence, tok.span)?;
+                            input.parse::<Token![=]>()?;
+                            recurrence = Some(Recurrence::FixedRate(input.call(parse_duration)?));
+                        }
+                        tok @ kw::delay => {
+                            check_duplicate(&recurrence, tok.span)?;
+                            input.parse::<Token![=]>()?;
+                            recurrence = Some(Recurrence::FixedDelay(input.call(parse_duration)?));
+                        }
+                        tok @ kw::catch_up => {
+                            check_duplicate(&catch_up, tok.span)?;
+                            input.parse::<Token![=]>()?;
+                            catch_up = Some(input.parse()?);
+                        }
                     });
                     Ok(())
                 })?;
-                Self::Reducer { repeat }
+                if repeat.is_some() && recurrence.is_some() {
+                    return Err(input.error("`repeat` can't be combined with `cron`, `rate` or `delay`"));
+                }
+                if catch_up.is_some() && recurrence.is_none() {
+                    return Err(input.error("`catch_up` requires `cron`, `rate` or `delay`"));
+                }
+                Self::Reducer {
+                    repeat,
+                    recurrence,
+                    catch_up,
+                }
             }
             kw::connect => Self::Connect,
             kw::disconnect => Self::Disconnect,
@@ -247,6 +341,52 @@ impl quote::ToTokens for IndexType {
     }
 }
 
+/// When the host calls a reducer on its own.
+enum Recurrence {
+    Cron(syn::LitStr),
+    FixedRate(Duration),
+    FixedDelay(Duration),
+}
+
+impl quote::ToTokens for Recurrence {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        tokens.extend(match self {
+            Self::Cron(cron) => quote!(spacetimedb::ScheduleKind::Cron(#cron)),
+            Self::FixedRate(dur) => {
+                let dur = duration_totokens(*dur);
+                quote!(spacetimedb::ScheduleKind::FixedRate(#dur))
+            }
+            Self::FixedDelay(dur) => {
+                let dur = duration_totokens(*dur);
+                quote!(spacetimedb::ScheduleKind::FixedDelay(#dur))
+            }
+        })
+    }
+}
+
+#[derive(Debug)]
+enum CatchUp {
+    Skip,
+    Once,
+    All,
+}
+
+impl syn::parse::Parse for CatchUp {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        Ok(match_tok!(match input {
+            kw::skip => Self::Skip,
+            kw::once => Self::Once,
+            kw::all => Self::All,
+        }))
+    }
+}
+
+impl quote::ToTokens for CatchUp {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        tokens.append(Ident::new(&format!("{self:?}"), Span::call_site()))
+    }
+}
+
 mod kw {
     syn::custom_keyword!(table);
     syn::custom_keyword!(init);
@@ -258,13 +398,30 @@ mod kw {
     syn::custom_keyword!(btree);
     syn::custom_keyword!(hash);
     syn::custom_keyword!(name);
+    syn::custom_keyword!(policy);
     syn::custom_keyword!(repeat);
+    syn::custom_keyword!(cron);
+    syn::custom_keyword!(rate);
+    syn::custom_keyword!(delay);
+    syn::custom_keyword!(catch_up);
+    syn::custom_keyword!(skip);
+    syn::custom_keyword!(once);
+    syn::custom_keyword!(all);
     syn::custom_keyword!(update);
 }
 
 /// Generates a reducer in place of `item`.
-fn spacetimedb_reducer(repeat: Option<Duration>, item: TokenStream) -> syn::Result<TokenStream> {
-    let repeat_dur = repeat.map_or(ReducerExtra::Schedule, ReducerExtra::Repeat);
+fn spacetimedb_reducer(
+    repeat: Option<Duration>,
+    recurrence: Option<Recurrence>,
+    catch_up: Option<CatchUp>,
+    item: TokenStream,
+) -> syn::Result<TokenStream> {
+    let repeat_dur = match (repeat, recurrence) {
+        (Some(repeat), _) => ReducerExtra::Repeat(repeat),
+        (None, Some(recurrence)) => ReducerExtra::Recurring(recurrence, catch_up.unwrap_or(CatchUp::Skip)),
+        (None, None) => ReducerExtra::Schedule,
+    };
     let original_function = syn::parse2::<ItemFn>(item)?;
 
     // Extract reducer name, making sure it's not `__XXX__` as that's the form we reserve for special reducers.
@@ -290,6 +447,7 @@ enum ReducerExtra {
     None,
     Schedule,
     Repeat(Duration),
+    Recurring(Recurrence, CatchUp),
 }
 
 fn gen_reducer(original_function: ItemFn, reducer_name: &str, extra: ReducerExtra) -> syn::Result<TokenStream> {
@@ -368,6 +526,21 @@ fn gen_reducer(original_function: ItemFn, reducer_name: &str, extra: ReducerExtr
         }));
     }
 
+    let mut register_extra = TokenStream::new();
+    if let ReducerExtra::Recurring(recurrence, catch_up) = &extra {
+        register_extra.extend(quote! {
+            spacetimedb::rt::register_schedule::<_, _, #func_name>(#func_name);
+        });
+        extra_impls.extend(quote! {
+            impl spacetimedb::rt::ScheduleInfo for #func_name {
+                const SCHEDULE: spacetimedb::ScheduleDef<'static> = spacetimedb::ScheduleDef {
+                    kind: #recurrence,
+                    catch_up: spacetimedb::CatchUp::#catch_up,
+                };
+            }
+        });
+    }
+
     if let ReducerExtra::Repeat(repeat_dur) = &extra {
         let repeat_dur = duration_totokens(*repeat_dur);
         epilogue.extend(quote! {
@@ -405,7 +578,8 @@ fn gen_reducer(original_function: ItemFn, reducer_name: &str, extra: ReducerExtr
     let generated_describe_function = quote! {
         #[export_name = #register_describer_symbol]
         pub extern "C" fn __register_describer() {
-            spacetimedb::rt::register_reducer::<_, _, #func_name>(#func_name)
+            spacetimedb::rt::register_reducer::<_, _, #func_name>(#func_name);
+            #register_extra
         }
     };
 
@@ -433,6 +607,16 @@ struct Column<'a> {
     index: u8,
     field: &'a module::SatsField<'a>,
     attr: ColumnIndexAttribute,
+    foreign_key: Option<ForeignKey>,
+}
+
+/// The column of another table referenced by a `#[foreign_key(..)]` column.
+struct ForeignKey {
+    /// The type of the referenced table.
+    table: syn::Path,
+    column: Ident,
+    /// `Restrict` or `Cascade`.
+    on_delete: Ident,
 }
 
 // TODO: any way to avoid duplication with same structure in bindings crate? Extra crate?
@@ -452,12 +636,18 @@ bitflags! {
         const PRIMARY_KEY = Self::UNIQUE.bits() | 0b1000;
         /// PrimaryKey + AutoInc
         const PRIMARY_KEY_AUTO = Self::PRIMARY_KEY.bits() | Self::AUTO_INC.bits();
+        /// References the column of another table
+        const FOREIGN_KEY = 0b1_0000;
+        /// Restricts the rows to those satisfying a predicate
+        const CHECK = 0b10_0000;
     }
 }
 
-fn spacetimedb_table(item: TokenStream) -> syn::Result<TokenStream> {
+fn spacetimedb_table(policy: Option<syn::LitStr>, item: TokenStream) -> syn::Result<TokenStream> {
+    let policy = policy.map(|policy| quote!(#[row_policy(#policy)]));
     Ok(quote! {
         #[derive(spacetimedb::TableType)]
+        #policy
         #item
     })
 }
@@ -487,7 +677,25 @@ fn spacetimedb_table(item: TokenStream) -> syn::Result<TokenStream> {
 /// * `#[primarykey]`
 ///
 ///    Similar to `#[unique]`, but generates additional CRUD methods.
-#[proc_macro_derive(TableType, attributes(sats, unique, autoinc, primarykey))]
+///
+/// * `#[foreign_key(table = Other, column = field)]`
+///
+///    Requires the values of the annotated field to be present in the column `field` of the table `Other`,
+///    which must be `#[unique]` or `#[primarykey]`.
+///    Checked when the transaction commits.
+///
+///    Deleting a row of `Other` that is still referenced fails the transaction,
+///    unless `on_delete = cascade` is given, in which case the referencing rows are deleted too.
+///
+/// The struct itself may be annotated with `#[row_policy("owner = :sender")]`,
+/// the row-level security policy that `#[spacetimedb(table, policy = ...)]` expands to,
+/// and with any number of `#[check(amount >= 0)]`, a `SQL` predicate that every row must satisfy.
+/// It is checked on every insert and update, including those made through `SQL`.
+/// A predicate that isn't valid Rust tokens, like `name <> ''`, can be given as a string literal.
+#[proc_macro_derive(
+    TableType,
+    attributes(sats, unique, autoinc, primarykey, foreign_key, row_policy, check)
+)]
 pub fn spacetimedb_tabletype(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let item = syn::parse_macro_input!(item as syn::DeriveInput);
     spacetimedb_tabletype_impl(item)
@@ -499,6 +707,7 @@ enum ColumnAttr {
     Unique(Span),
     Autoinc(Span),
     Primarykey(Span),
+    ForeignKey(Span, ForeignKey),
 }
 
 impl ColumnAttr {
@@ -515,6 +724,41 @@ impl ColumnAttr {
         } else if ident == sym::PRIMARYKEY {
             attr.meta.require_path_only()?;
             Some(ColumnAttr::Primarykey(ident.span()))
+        } else if ident == sym::FOREIGN_KEY {
+            let mut table = None;
+            let mut column = None;
+            let mut on_delete = None;
+            attr.parse_nested_meta(|meta| {
+                if meta.path == sym::TABLE {
+                    check_duplicate_meta(&table, &meta)?;
+                    table = Some(meta.value()?.parse::<syn::Path>()?);
+                } else if meta.path == sym::COLUMN {
+                    check_duplicate_meta(&column, &meta)?;
+                    column = Some(meta.value()?.parse::<Ident>()?);
+                } else if meta.path == sym::ON_DELETE {
+                    check_duplicate_meta(&on_delete, &meta)?;
+                    let action = meta.value()?.parse::<Ident>()?;
+                    on_delete = Some(if action == sym::CASCADE {
+                        Ident::new("Cascade", action.span())
+                    } else if action == sym::RESTRICT {
+                        Ident::new("Restrict", action.span())
+                    } else {
+                        return Err(syn::Error::new(action.span(), "expected `cascade` or `restrict`"));
+                    });
+                } else {
+                    return Err(meta.error("unknown foreign_key attribute"));
+                }
+                Ok(())
+            })?;
+            let missing = |arg| syn::Error::new(ident.span(), format!("missing `{arg} = ...` in foreign_key"));
+            Some(ColumnAttr::ForeignKey(
+                ident.span(),
+                ForeignKey {
+                    table: table.ok_or_else(|| missing("table"))?,
+                    column: column.ok_or_else(|| missing("column"))?,
+                    on_delete: on_delete.unwrap_or_else(|| Ident::new("Restrict", ident.span())),
+                },
+            ))
         } else {
             None
         })
@@ -547,6 +791,7 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
             .map_err(|_| syn::Error::new_spanned(field.ident, "too many columns; the most a table can have is 256"))?;
 
         let mut col_attr = ColumnIndexAttribute::UNSET;
+        let mut foreign_key = None;
         for attr in field.original_attrs {
             let Some(attr) = ColumnAttr::parse(attr)? else { continue };
             let duplicate = |span| syn::Error::new(span, "duplicate attribute");
@@ -554,6 +799,11 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
                 ColumnAttr::Unique(span) => (ColumnIndexAttribute::UNIQUE, span),
                 ColumnAttr::Autoinc(span) => (ColumnIndexAttribute::AUTO_INC, span),
                 ColumnAttr::Primarykey(span) => (ColumnIndexAttribute::PRIMARY_KEY, span),
+                ColumnAttr::ForeignKey(span, fk) => {
+                    check_duplicate(&foreign_key, span)?;
+                    foreign_key = Some(fk);
+                    continue;
+                }
             };
             // do those attributes intersect (not counting the INDEXED bit which is present in all attributes)?
             // this will check that no two attributes both have UNIQUE, AUTOINC or PRIMARY_KEY bits set
@@ -585,12 +835,14 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
             index: col_num,
             field,
             attr: col_attr,
+            foreign_key,
         };
 
         columns.push(column);
     }
 
     let mut indexes = vec![];
+    let mut multi_column_filter_funcs = vec![];
 
     for attr in sats_ty.original_attrs {
         if attr.path().segments.last().unwrap().ident != "spacetimedb" {
@@ -600,16 +852,40 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
         let MacroInput::Index { ty, name, field_names } = args else {
             continue;
         };
-        let col_ids = field_names
+        let index_columns = field_names
             .iter()
             .map(|ident| {
-                let col = columns
+                columns
                     .iter()
                     .find(|col| col.field.ident == Some(ident))
-                    .ok_or_else(|| syn::Error::new(ident.span(), "not a column of the table"))?;
-                Ok(col.index)
+                    .ok_or_else(|| syn::Error::new(ident.span(), "not a column of the table"))
             })
             .collect::<syn::Result<Vec<_>>>()?;
+        let col_ids = index_columns.iter().map(|col| col.index).collect::<Vec<_>>();
+
+        // Single-column indexes are served by `filter_by_{field}`.
+        if index_columns.len() > 1 {
+            let vis = index_columns[0].field.vis;
+            let column_idents = index_columns
+                .iter()
+                .map(|col| col.field.ident.unwrap())
+                .collect::<Vec<_>>();
+            let column_types = index_columns.iter().map(|col| col.field.ty);
+            let filter_func_ident = format_ident!(
+                "filter_by_{}",
+                column_idents
+                    .iter()
+                    .map(|ident| ident.to_string())
+                    .collect::<Vec<_>>()
+                    .join("_and_")
+            );
+            multi_column_filter_funcs.push(quote! {
+                #vis fn #filter_func_ident(#(#column_idents: &#column_types),*) -> impl Iterator<Item = Self> {
+                    spacetimedb::query::filter_by_fields::<Self>(&[#(#col_ids),*], &(#(#column_idents,)*))
+                }
+            });
+        }
+
         let name = name.as_deref().unwrap_or("default_index");
         indexes.push(quote!(spacetimedb::IndexDef {
             name: #name,
@@ -690,6 +966,37 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
     });
     let non_primary_filter_func = non_primary_filter_func.collect::<Vec<_>>();
 
+    let range_filter_func = columns.iter().filter_map(|column| {
+        let vis = column.field.vis;
+        let column_ident = column.field.ident.unwrap();
+        let column_type = column.field.ty;
+        let column_index = column.index;
+
+        let filter_func_ident = format_ident!("filter_by_{}_range", column_ident);
+
+        // Only offer range scans for types whose order is meaningful.
+        let skip = if let syn::Type::Path(p) = column_type {
+            // TODO: this is janky as heck
+            !matches!(
+                &*p.path.segments.last().unwrap().ident.to_string(),
+                "u8" | "i8" | "u16" | "i16" | "u32" | "i32" | "u64" | "i64" | "u128" | "i128" | "String"
+            )
+        } else {
+            true
+        };
+
+        if skip {
+            return None;
+        }
+
+        Some(quote! {
+            #vis fn #filter_func_ident(range: impl std::ops::RangeBounds<#column_type>) -> impl Iterator<Item = Self> {
+                spacetimedb::query::filter_by_field_range::<Self, #column_type, #column_index>(range)
+            }
+        })
+    });
+    let range_filter_func = range_filter_func.collect::<Vec<_>>();
+
     let insert_result = if has_unique {
         quote!(std::result::Result<Self, spacetimedb::UniqueConstraintViolation<Self>>)
     } else {
@@ -722,6 +1029,45 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
             Span::call_site(),
         )
     });
+    let foreign_keys = columns.iter().filter_map(|col| {
+        let ForeignKey {
+            table,
+            column,
+            on_delete,
+        } = col.foreign_key.as_ref()?;
+        let name = format!("{table_name}_{}_fkey", col.field.ident.unwrap());
+        let col_id = col.index;
+        let column = column.to_string();
+        Some(quote!(spacetimedb::ForeignKeyDef {
+            name: #name,
+            col_id: #col_id,
+            table: <#table as spacetimedb::TableType>::TABLE_NAME,
+            column: #column,
+            on_delete: spacetimedb::spacetimedb_lib::ReferentialAction::#on_delete,
+        }))
+    });
+    let mut row_policy = None;
+    let mut checks = Vec::new();
+    for attr in &item.attrs {
+        if attr.path() == sym::ROW_POLICY {
+            check_duplicate(&row_policy, attr.span())?;
+            row_policy = Some(attr.parse_args::<syn::LitStr>()?);
+        } else if attr.path() == sym::CHECK {
+            let expr = match attr.parse_args::<syn::LitStr>() {
+                Ok(expr) => expr.value(),
+                Err(_) => attr.parse_args::<TokenStream>()?.to_string(),
+            };
+            let name = format!("{table_name}_check_{}", checks.len());
+            checks.push(quote!(spacetimedb::CheckDef {
+                name: #name,
+                expr: #expr,
+            }));
+        }
+    }
+    let row_policy = match row_policy {
+        Some(policy) => quote!(Some(#policy)),
+        None => quote!(None),
+    };
     let tabletype_impl = quote! {
         impl spacetimedb::TableType for #original_struct_ident {
             const TABLE_NAME: &'static str = #table_name;
@@ -729,6 +1075,9 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
                 #(spacetimedb::spacetimedb_lib::ColumnIndexAttribute::#column_attrs),*
             ];
             const INDEXES: &'static [spacetimedb::IndexDef<'static>] = &[#(#indexes),*];
+            const FOREIGN_KEYS: &'static [spacetimedb::ForeignKeyDef<'static>] = &[#(#foreign_keys),*];
+            const CHECKS: &'static [spacetimedb::CheckDef<'static>] = &[#(#checks),*];
+            const ROW_POLICY: Option<&'static str> = #row_policy;
             type InsertResult = #insert_result;
             #get_table_id_func
         }
@@ -812,6 +1161,8 @@ fn spacetimedb_tabletype_impl(item: syn::DeriveInput) -> syn::Result<TokenStream
 
             #db_iter
             #(#non_primary_filter_func)*
+            #(#range_filter_func)*
+            #(#multi_column_filter_funcs)*
         }
 
         #schema_impl
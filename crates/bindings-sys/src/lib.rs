@@ -59,16 +59,16 @@ pub mod raw {
         /// Here `index_name` points to a UTF-8 slice in WASM memory
         /// and `col_ids` points to a byte slice in WASM memory with each element being a column.
         ///
-        /// Currently only single-column-indices are supported
-        /// and they may only be of the btree index type.
+        /// The `index_type` is `0` for a btree index and `1` for a hash index.
+        /// A hash index only supports seeking a single value, not a range of values.
         ///
-        /// Returns an error if
-        /// - a table with the provided `table_id` doesn't exist
+        /// Returns an error if a table with the provided `table_id` doesn't exist.
+        ///
+        /// Traps if
+        /// - `index_type > 1`
+        /// - `col_ids.len() == 0`, or a column of `col_ids` doesn't exist in the table
         /// - the slice `(index_name, index_name_len)` is not valid UTF-8
         /// - `index_name + index_name_len` or `col_ids + col_len` overflow a 64-bit integer
-        /// - `index_type > 1`
-        ///
-        /// Traps if `index_type == 1` or `col_ids.len() != 1`.
         pub fn _create_index(
             index_name: *const u8,
             index_name_len: usize,
@@ -290,6 +290,104 @@ pub mod raw {
         pub fn _span_end(span_id: u32);
     }
 
+    #[link(wasm_import_module = "spacetime_7.1")]
+    extern "C" {
+        /// Finds all rows in the table identified by `table_id`,
+        /// where the row has a column, identified by `col_id`,
+        /// with a value within the range, in WASM memory, pointed to at by `range`.
+        ///
+        /// The range is encoded as its start bound followed by its end bound.
+        /// Each bound is a tag byte, [`BOUND_INCLUDED`], [`BOUND_EXCLUDED`] or [`BOUND_UNBOUNDED`],
+        /// followed by the BSATN-encoded endpoint unless the bound is unbounded.
+        /// The endpoints are decoded to an `AlgebraicValue` according to the column's schema
+        /// and compared with `Ord for AlgebraicValue`.
+        ///
+        /// The rows found are BSATN encoded and then concatenated.
+        /// The resulting byte string from the concatenation is written
+        /// to a fresh buffer with the buffer's identifier written to the WASM pointer `out`.
+        ///
+        /// Returns an error if
+        /// - a table with the provided `table_id` doesn't exist
+        /// - `col_id` does not identify a column of the table,
+        /// - `(range, range_len)` cannot be decoded to a range of `AlgebraicValue`s
+        ///   typed at the `AlgebraicType` of the column,
+        /// - `range + range_len` overflows a 64-bit integer
+        pub fn _iter_by_col_range(
+            table_id: TableId,
+            col_id: ColId,
+            range: *const u8,
+            range_len: usize,
+            out: *mut Buffer,
+        ) -> u16;
+
+        /// Finds all rows in the table identified by `table_id`,
+        /// where the row has columns, identified by the byte slice `(col_ids, col_len)`,
+        /// with data matching the byte string, in WASM memory, pointed to at by `val`.
+        ///
+        /// The byte string is the concatenation of the BSATN-encoded value of each column.
+        /// Matching is defined by decoding these to `AlgebraicValue`s
+        /// according to the columns' schemas and then `Ord for AlgebraicValue`.
+        /// An index whose leading columns are `col_ids` is used for the lookup, if there is one.
+        ///
+        /// The rows found are BSATN encoded and then concatenated.
+        /// The resulting byte string from the concatenation is written
+        /// to a fresh buffer with the buffer's identifier written to the WASM pointer `out`.
+        ///
+        /// Returns an error if
+        /// - a table with the provided `table_id` doesn't exist
+        /// - `(col_ids, col_len)` is empty or an element does not identify a column of the table,
+        /// - `(val, val_len)` cannot be decoded to a sequence of `AlgebraicValue`s
+        ///   typed at the `AlgebraicType`s of the columns,
+        /// - `col_ids + col_len` or `val + val_len` overflow a 64-bit integer
+        pub fn _iter_by_cols_eq(
+            table_id: TableId,
+            col_ids: *const u8,
+            col_len: usize,
+            val: *const u8,
+            val_len: usize,
+            out: *mut Buffer,
+        ) -> u16;
+
+        /// Replaces the row in the table identified by `table_id`
+        /// where the column identified by `col_id` matches the byte string,
+        /// in WASM memory, pointed to at by `value`,
+        /// with the BSATN-encoded row, in WASM memory, pointed to at by `row`.
+        ///
+        /// Matching is defined by BSATN-decoding `value` to an `AlgebraicValue`
+        /// according to the column's schema and then `Ord for AlgebraicValue`.
+        ///
+        /// The row is updated in place: auto-incrementing columns of `row` are kept as they are,
+        /// and subscribers are informed of a single update rather than a delete and an insert.
+        ///
+        /// Returns an error if
+        /// - a table with the provided `table_id` doesn't exist
+        /// - no row matches `value`
+        /// - `col_id` does not identify a column of the table,
+        /// - `(value, value_len)` doesn't decode from BSATN to an `AlgebraicValue`
+        ///   according to the `AlgebraicType` that the table's schema specifies for `col_id`.
+        /// - `(row, row_len)` doesn't decode from BSATN to a `ProductValue`
+        ///   according to the `ProductType` that the table's schema specifies.
+        /// - there were unique constraint violations
+        /// - `value + value_len` or `row + row_len` overflow a 64-bit integer
+        ///
+        /// Traps if more than one row matches `value`.
+        pub fn _update_by_col_eq(
+            table_id: TableId,
+            col_id: ColId,
+            value: *const u8,
+            value_len: usize,
+            row: *const u8,
+            row_len: usize,
+        ) -> u16;
+    }
+
+    /// The tag of an inclusive bound of a range. See [`_iter_by_col_range`].
+    pub const BOUND_INCLUDED: u8 = 0;
+    /// The tag of an exclusive bound of a range. See [`_iter_by_col_range`].
+    pub const BOUND_EXCLUDED: u8 = 1;
+    /// The tag of the absence of a bound of a range. See [`_iter_by_col_range`].
+    pub const BOUND_UNBOUNDED: u8 = 2;
+
     /// What strategy does the database index use?
     ///
     /// See also: https://www.postgresql.org/docs/current/sql-createindex.html
@@ -381,7 +479,8 @@ pub mod raw {
             /// Required. id is an index into the `ModuleDef.reducers` returned from `__describe_module__`.
             /// args is a bsatn-encoded product value defined by the schema at `reducers[id]`.
             fn __call_reducer__(id: usize, sender: Identity, timestamp: Timestamp, args: Buffer) -> Result;
-            /// Currently unused?
+            /// Unused. Tables with incompatible schema changes are instead migrated by the
+            /// `__migrate__` reducer, which is called within the transaction updating the database.
             fn __migrate_database__XXXX(sender: Identity, timestamp: Timestamp, something: Buffer) -> Result;
         }
     }
@@ -499,14 +598,14 @@ pub fn get_table_id(name: &str) -> Result<TableId, Errno> {
 /// on a product of the given columns ids in `col_ids`,
 /// identifying columns in the table identified by `table_id`.
 ///
-/// Currently only single-column-indices are supported
-/// and they may only be of the btree index type.
+/// The `index_type` is `0` for a btree index and `1` for a hash index.
+/// A hash index only supports seeking a single value, not a range of values.
 ///
-/// Returns an error if
-/// - a table with the provided `table_id` doesn't exist
-/// - `index_type > 1`
+/// Returns an error if a table with the provided `table_id` doesn't exist.
 ///
-/// Traps if `index_type == 1` or `col_ids.len() != 1`.
+/// Traps if
+/// - `index_type > 1`
+/// - `col_ids.len() == 0`, or a column of `col_ids` doesn't exist in the table
 #[inline]
 pub fn create_index(index_name: &str, table_id: TableId, index_type: u8, col_ids: &[u8]) -> Result<(), Errno> {
     cvt(unsafe {
@@ -542,6 +641,46 @@ pub fn iter_by_col_eq(table_id: TableId, col_id: ColId, val: &[u8]) -> Result<Bu
     unsafe { call(|out| raw::_iter_by_col_eq(table_id, col_id, val.as_ptr(), val.len(), out)) }
 }
 
+/// Finds all rows in the table identified by `table_id`,
+/// where the row has a column, identified by `col_id`,
+/// with a value within the encoded `range`.
+///
+/// See [`raw::_iter_by_col_range`] for the encoding of `range`.
+///
+/// The rows found are BSATN encoded and then concatenated.
+/// The resulting byte string from the concatenation is written
+/// to a fresh buffer with a handle to it returned as a `Buffer`.
+///
+/// Returns an error if
+/// - a table with the provided `table_id` doesn't exist
+/// - `col_id` does not identify a column of the table
+/// - `range` cannot be decoded to a range of `AlgebraicValue`s
+///   typed at the `AlgebraicType` of the column
+#[inline]
+pub fn iter_by_col_range(table_id: TableId, col_id: ColId, range: &[u8]) -> Result<Buffer, Errno> {
+    unsafe { call(|out| raw::_iter_by_col_range(table_id, col_id, range.as_ptr(), range.len(), out)) }
+}
+
+/// Finds all rows in the table identified by `table_id`,
+/// where the row has columns, identified by `col_ids`,
+/// with data matching `val`, the concatenation of the BSATN-encoded value of each column.
+///
+/// The rows found are BSATN encoded and then concatenated.
+/// The resulting byte string from the concatenation is written
+/// to a fresh buffer with a handle to it returned as a `Buffer`.
+///
+/// Returns an error if
+/// - a table with the provided `table_id` doesn't exist
+/// - `col_ids` is empty or an element does not identify a column of the table
+/// - `val` cannot be BSATN-decoded to a sequence of `AlgebraicValue`s
+///   typed at the `AlgebraicType`s of the columns
+#[inline]
+pub fn iter_by_cols_eq(table_id: TableId, col_ids: &[u8], val: &[u8]) -> Result<Buffer, Errno> {
+    unsafe {
+        call(|out| raw::_iter_by_cols_eq(table_id, col_ids.as_ptr(), col_ids.len(), val.as_ptr(), val.len(), out))
+    }
+}
+
 /// Inserts a row into the table identified by `table_id`,
 /// where the row is a BSATN-encoded `ProductValue`
 /// matching the table's `ProductType` row-schema.
@@ -576,6 +715,27 @@ pub fn delete_by_col_eq(table_id: TableId, col_id: ColId, value: &[u8]) -> Resul
     unsafe { call(|out| raw::_delete_by_col_eq(table_id, col_id, value.as_ptr(), value.len(), out)) }
 }
 
+/// Replaces the row in the table identified by `table_id`
+/// where the column identified by `col_id` matches `value`
+/// with `row`, a BSATN-encoded `ProductValue`, in place.
+///
+/// Matching is defined by BSATN-decoding `value` to an `AlgebraicValue`
+/// according to the column's schema and then `Ord for AlgebraicValue`.
+///
+/// Returns an error if
+/// - a table with the provided `table_id` doesn't exist
+/// - no row matches `value`
+/// - `col_id` does not identify a column of the table
+/// - there were unique constraint violations
+/// - `row` doesn't decode from BSATN to a `ProductValue`
+///   according to the `ProductType` that the table's schema specifies.
+///
+/// Traps if more than one row matches `value`.
+#[inline]
+pub fn update_by_col_eq(table_id: TableId, col_id: ColId, value: &[u8], row: &[u8]) -> Result<(), Errno> {
+    cvt(unsafe { raw::_update_by_col_eq(table_id, col_id, value.as_ptr(), value.len(), row.as_ptr(), row.len()) })
+}
+
 /// Returns an iterator for each row, as bytes, of a table identified by `table_id`.
 /// The rows can be put through an optional `filter`,
 /// which is encoded in the embedded language defined by `spacetimedb_lib::filter::Expr`.
@@ -14,9 +14,12 @@ use spacetimedb_lib::buffer::{BufReader, BufWriter, Cursor, DecodeError};
 pub use spacetimedb_lib::de::{Deserialize, DeserializeOwned};
 use spacetimedb_lib::sats::{impl_deserialize, impl_serialize, impl_st};
 pub use spacetimedb_lib::ser::Serialize;
-use spacetimedb_lib::{bsatn, ColumnIndexAttribute, IndexType, PrimaryKey, ProductType, ProductValue};
+use spacetimedb_lib::{
+    bsatn, ColumnIndexAttribute, IndexType, PrimaryKey, ProductType, ProductValue, ReferentialAction,
+};
 use std::cell::RefCell;
 use std::marker::PhantomData;
+use std::ops::{Bound, RangeBounds};
 use std::{fmt, panic};
 
 pub use spacetimedb_bindings_macro::{duration, query, spacetimedb, TableType};
@@ -26,7 +29,9 @@ pub use spacetimedb_lib;
 pub use spacetimedb_lib::sats;
 pub use spacetimedb_lib::Address;
 pub use spacetimedb_lib::AlgebraicValue;
+pub use spacetimedb_lib::CatchUp;
 pub use spacetimedb_lib::Identity;
+pub use spacetimedb_lib::MIGRATE_TABLE_PREFIX;
 pub use spacetimedb_primitives::TableId;
 pub use timestamp::Timestamp;
 
@@ -174,6 +179,62 @@ pub fn iter_by_col_eq(table_id: TableId, col_id: u8, val: &impl Serialize) -> Re
     })
 }
 
+/// Finds all rows in the table identified by `table_id`,
+/// where the row has a column, identified by `col_id`,
+/// with data within `range`, whose endpoints can be serialized.
+///
+/// The endpoints are decoded to an `AlgebraicValue`
+/// according to the column's schema and compared with `Ord for AlgebraicValue`.
+///
+/// The rows found are BSATN encoded and then concatenated.
+/// The resulting byte string from the concatenation is written
+/// to a fresh buffer with a handle to it returned as a `Buffer`.
+///
+/// Panics if BSATN serialization fails.
+pub fn iter_by_col_range<T: Serialize>(table_id: TableId, col_id: u8, range: &impl RangeBounds<T>) -> Result<Buffer> {
+    fn encode_bound<T: Serialize>(bytes: &mut Vec<u8>, bound: Bound<&T>) {
+        match bound {
+            Bound::Included(val) => {
+                bytes.push(sys::raw::BOUND_INCLUDED);
+                bsatn::to_writer(bytes, val).unwrap();
+            }
+            Bound::Excluded(val) => {
+                bytes.push(sys::raw::BOUND_EXCLUDED);
+                bsatn::to_writer(bytes, val).unwrap();
+            }
+            Bound::Unbounded => bytes.push(sys::raw::BOUND_UNBOUNDED),
+        }
+    }
+
+    with_row_buf(|bytes| {
+        // Encode the bounds of `range` into `bytes` and then use that.
+        encode_bound(bytes, range.start_bound());
+        encode_bound(bytes, range.end_bound());
+        sys::iter_by_col_range(table_id, col_id.into(), bytes)
+    })
+}
+
+/// Finds all rows in the table identified by `table_id`,
+/// where the row has columns, identified by `col_ids`,
+/// with data matching `vals`, a tuple holding a value for each column, in order.
+///
+/// Matching is defined by decoding of `vals` to `AlgebraicValue`s
+/// according to the columns' schemas and then `Ord for AlgebraicValue`.
+///
+/// The rows found are BSATN encoded and then concatenated.
+/// The resulting byte string from the concatenation is written
+/// to a fresh buffer with a handle to it returned as a `Buffer`.
+///
+/// Panics if BSATN serialization fails.
+pub fn iter_by_cols_eq(table_id: TableId, col_ids: &[u8], vals: &impl Serialize) -> Result<Buffer> {
+    with_row_buf(|bytes| {
+        // Encode `vals` as BSATN into `bytes` and then use that.
+        // A tuple is encoded as the concatenation of its elements.
+        bsatn::to_writer(bytes, vals).unwrap();
+        sys::iter_by_cols_eq(table_id, col_ids, bytes)
+    })
+}
+
 /// Deletes all rows in the table identified by `table_id`
 /// where the column identified by `col_id` matches a `value` that can be serialized.
 ///
@@ -198,6 +259,31 @@ pub fn delete_by_col_eq(table_id: TableId, col_id: u8, value: &impl Serialize) -
     })
 }
 
+/// Replaces the row in the table identified by `table_id`
+/// where the column identified by `col_id` matches a `value` that can be serialized
+/// with `row`, in place.
+///
+/// Matching is defined by decoding of `value` to an `AlgebraicValue`
+/// according to the column's schema and then `Ord for AlgebraicValue`.
+///
+/// Returns an error if
+/// - a table with the provided `table_id` doesn't exist
+/// - no row matches `value`
+/// - `col_id` does not identify a column of the table,
+/// - there were unique constraint violations
+///
+/// Panics when serialization fails.
+pub fn update_by_col_eq(table_id: TableId, col_id: u8, value: &impl Serialize, row: &impl Serialize) -> Result<()> {
+    with_row_buf(|bytes| {
+        // Encode `value` and `row` as BSATN into `bytes` and then use that.
+        bsatn::to_writer(bytes, value).unwrap();
+        let value_len = bytes.len();
+        bsatn::to_writer(bytes, row).unwrap();
+        let (value, row) = bytes.split_at(value_len);
+        sys::update_by_col_eq(table_id, col_id.into(), value, row)
+    })
+}
+
 /// A table iterator which yields values of the `TableType` corresponding to the table.
 type TableTypeTableIter<T> = RawTableIter<TableTypeBufferDeserialize<T>>;
 
@@ -217,6 +303,22 @@ fn table_iter<T: TableType>(table_id: TableId, filter: Option<spacetimedb_lib::f
     Ok(RawTableIter::new(iter, deserializer).into())
 }
 
+/// Returns an iterator over the rows which the table named `table_name` held
+/// before the module was updated, deserialized as `T`.
+///
+/// This is intended to be called from the `#[spacetimedb(migrate)]` reducer,
+/// which is invoked when the stored schema of one or more tables is
+/// incompatible with the updated module.
+/// While the reducer runs, the old rows are kept in a table prefixed with
+/// [`MIGRATE_TABLE_PREFIX`], so `T` must match the *old* layout of the table.
+///
+/// Panics if there are no rows to migrate from a table named `table_name`.
+pub fn migrate_rows<T: DeserializeOwned>(table_name: &str) -> impl Iterator<Item = T> {
+    let table_id = get_table_id(&format!("{MIGRATE_TABLE_PREFIX}{table_name}"));
+    let iter = sys::iter(table_id, None).expect("Failed to iterate over the table to migrate");
+    RawTableIter::new(iter, TableTypeBufferDeserialize::new())
+}
+
 /// A trait for deserializing mulitple items out of a single `BufReader`.
 ///
 /// Each `BufReader` holds a number of concatenated serialized objects.
@@ -228,7 +330,7 @@ trait BufferDeserialize {
     fn deserialize<'de>(&mut self, reader: impl BufReader<'de>) -> Self::Item;
 }
 
-/// Deserialize bsatn values to a particular `T` where `T: TableType`.
+/// Deserialize bsatn values to a particular `T` where `T: DeserializeOwned`.
 struct TableTypeBufferDeserialize<T> {
     _marker: PhantomData<T>,
 }
@@ -239,7 +341,7 @@ impl<T> TableTypeBufferDeserialize<T> {
     }
 }
 
-impl<T: TableType> BufferDeserialize for TableTypeBufferDeserialize<T> {
+impl<T: DeserializeOwned> BufferDeserialize for TableTypeBufferDeserialize<T> {
     type Item = T;
 
     fn deserialize<'de>(&mut self, mut reader: impl BufReader<'de>) -> Self::Item {
@@ -299,6 +401,50 @@ pub struct IndexDef<'a> {
     pub col_ids: &'a [u8],
 }
 
+/// Defines a foreign key from a column to the column of another table.
+#[derive(Clone, Copy)]
+pub struct ForeignKeyDef<'a> {
+    /// The name of the constraint.
+    pub name: &'a str,
+    /// The identifier of the referencing column.
+    pub col_id: u8,
+    /// The name of the referenced table.
+    pub table: &'a str,
+    /// The name of the referenced column.
+    pub column: &'a str,
+    /// What happens to the referencing rows when a referenced row is deleted.
+    pub on_delete: ReferentialAction,
+}
+
+/// Defines a check constraint that every row of a table must satisfy.
+#[derive(Clone, Copy)]
+pub struct CheckDef<'a> {
+    /// The name of the constraint.
+    pub name: &'a str,
+    /// The `SQL` predicate over the columns of the table.
+    pub expr: &'a str,
+}
+
+/// Defines when the host calls a reducer on its own.
+#[derive(Clone, Copy)]
+pub struct ScheduleDef<'a> {
+    /// The times of the calls.
+    pub kind: ScheduleKind<'a>,
+    /// Which of the calls missed while the database was down are made.
+    pub catch_up: CatchUp,
+}
+
+/// The times at which the host calls a reducer.
+#[derive(Clone, Copy)]
+pub enum ScheduleKind<'a> {
+    /// The times matching a cron expression, in UTC.
+    Cron(&'a str),
+    /// Every so often, however long each call takes.
+    FixedRate(std::time::Duration),
+    /// So long after each call ends.
+    FixedDelay(std::time::Duration),
+}
+
 /// A table iterator which yields values of the `TableType` corresponding to the table.
 #[derive(derive_more::From)]
 pub struct TableIter<T: TableType> {
@@ -315,11 +461,15 @@ impl<T: TableType> Iterator for TableIter<T> {
 
 /// A trait for the set of types serializable, deserializable, and convertible to `AlgebraicType`.
 ///
-/// Additionally, the type knows its own table name, its column attributes, and indices.
+/// Additionally, the type knows its own table name, its column attributes, indices, foreign keys,
+/// check constraints, and row-level security policy.
 pub trait TableType: SpacetimeType + DeserializeOwned + Serialize {
     const TABLE_NAME: &'static str;
     const COLUMN_ATTRS: &'static [ColumnIndexAttribute];
     const INDEXES: &'static [IndexDef<'static>];
+    const FOREIGN_KEYS: &'static [ForeignKeyDef<'static>] = &[];
+    const CHECKS: &'static [CheckDef<'static>] = &[];
+    const ROW_POLICY: Option<&'static str> = None;
     type InsertResult: sealed::InsertResult<T = Self>;
 
     /// Returns the ID of this table.
@@ -469,6 +619,44 @@ pub mod query {
         }
     }
 
+    /// Finds all rows of `Table` where the column at `COL_IDX` lies within `range`,
+    /// as defined by decoding the endpoints to an `AlgebraicValue`
+    /// according to the column's schema and then `Ord for AlgebraicValue`.
+    ///
+    /// **NOTE:** Do not use directly.
+    /// This is exposed as `filter_by_{$field_name}_range` on types with `#[spacetimedb(table)]`.
+    #[doc(hidden)]
+    pub fn filter_by_field_range<Table: TableType, T: FilterableValue, const COL_IDX: u8>(
+        range: impl RangeBounds<T>,
+    ) -> FilterByIter<Table> {
+        let rows = iter_by_col_range(Table::table_id(), COL_IDX, &range)
+            .expect("iter_by_col_range failed")
+            .read();
+        FilterByIter {
+            cursor: Cursor::new(rows),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Finds all rows of `Table` where the columns at `col_ids` match `vals`,
+    /// a tuple holding a value for each column, in order,
+    /// as defined by decoding to `AlgebraicValue`s
+    /// according to the columns' schemas and then `Ord for AlgebraicValue`.
+    ///
+    /// **NOTE:** Do not use directly.
+    /// This is exposed as `filter_by_{$field_name}_and_{$field_name}`
+    /// for multi-column indexes on types with `#[spacetimedb(table)]`.
+    #[doc(hidden)]
+    pub fn filter_by_fields<Table: TableType>(col_ids: &[u8], vals: &impl Serialize) -> FilterByIter<Table> {
+        let rows = iter_by_cols_eq(Table::table_id(), col_ids, vals)
+            .expect("iter_by_cols_eq failed")
+            .read();
+        FilterByIter {
+            cursor: Cursor::new(rows),
+            _phantom: PhantomData,
+        }
+    }
+
     /// Deletes the row of `Table` where the column at `COL_IDX` matches `val`,
     /// as defined by decoding to an `AlgebraicValue`
     /// according to the column's schema and then `Ord for AlgebraicValue`.
@@ -500,30 +688,32 @@ pub mod query {
     /// Matching is defined by decoding to an `AlgebraicValue`
     /// according to the column's schema and then `Ord for AlgebraicValue`.
     ///
+    /// Returns whether a row was updated, that is `false` when no row matches `old`.
+    ///
+    /// Panics if `new` violates a unique constraint, like `insert` does,
+    /// or if the update fails for any other reason.
+    ///
     /// **NOTE:** Do not use directly.
     /// This is exposed as `update_by_{$field_name}` on types with `#[spacetimedb(table)]`.
     #[doc(hidden)]
     pub fn update_by_field<Table: TableType, T: UniqueValue, const COL_IDX: u8>(old: &T, new: Table) -> bool {
-        // Delete the existing row, if any.
-        delete_by_field::<Table, T, COL_IDX>(old);
-
-        // Insert the new row.
-        Table::insert(new);
-
-        // TODO: For now this is always successful.
-        //       In the future, this could return what `delete_by_field` returns?
-        true
+        match update_by_col_eq(Table::table_id(), COL_IDX, old, &new) {
+            Ok(()) => true,
+            Err(Errno::LOOKUP_NOT_FOUND) => false,
+            Err(e) => panic!("unexpected error from update_by_field(): {e}"),
+        }
     }
 
-    /// An iterator returned by `filter_by_field`,
+    /// An iterator returned by `filter_by_field`, `filter_by_field_range` and `filter_by_fields`,
     /// which yields all of the rows of a table where a particular column's value
-    /// matches a given target value.
+    /// matches a given target value, or lies within a given range,
+    /// or where several columns match given target values, respectively.
     ///
     /// Matching is defined by decoding to an `AlgebraicValue`
     /// according to the column's schema and then `Ord for AlgebraicValue`.
     #[doc(hidden)]
     pub struct FilterByIter<Table: TableType> {
-        /// The buffer of rows returned by `iter_by_col_eq`.
+        /// The buffer of rows returned by `iter_by_col_eq`, `iter_by_col_range` or `iter_by_cols_eq`.
         cursor: Cursor<Box<[u8]>>,
 
         _phantom: PhantomData<Table>,
@@ -14,7 +14,9 @@ use spacetimedb_lib::de::{self, Deserialize, SeqProductAccess};
 use spacetimedb_lib::sats::typespace::TypespaceBuilder;
 use spacetimedb_lib::sats::{impl_deserialize, impl_serialize, AlgebraicType, AlgebraicTypeRef, ProductTypeElement};
 use spacetimedb_lib::ser::{Serialize, SerializeSeqProduct};
-use spacetimedb_lib::{bsatn, Address, Identity, MiscModuleExport, ModuleDef, ReducerDef, TableDef, TypeAlias};
+use spacetimedb_lib::{
+    bsatn, Address, Identity, MiscModuleExport, ModuleDef, ReducerDef, ScheduleDef, ScheduleKind, TableDef, TypeAlias,
+};
 use spacetimedb_primitives::TableId;
 use sys::Buffer;
 
@@ -55,10 +57,8 @@ pub fn invoke_reducer<'a, A: Args<'a>, T>(
 /// on a product of the given columns ids in `col_ids`,
 /// identifying columns in the table identified by `table_id`.
 ///
-/// Currently only single-column-indices are supported
-/// and they may only be of the btree index type.
-/// Attempting to create a multi-column index will result in a panic.
-/// Attempting to use an index type other than btree, meanwhile, will return an error.
+/// The index may be a btree or a hash index,
+/// the latter only supporting seeking a single value, not a range of values.
 ///
 /// Returns an invalid buffer on success
 /// and otherwise the error is written into the fresh one returned
@@ -137,6 +137,12 @@ pub trait RepeaterInfo: ReducerInfo {
     const REPEAT_INTERVAL: Duration;
 }
 
+/// A trait for reducer types that the host calls on a recurring schedule.
+pub trait ScheduleInfo: ReducerInfo {
+    /// When does the host call this reducer?
+    const SCHEDULE: crate::ScheduleDef<'static>;
+}
+
 /// A trait of types representing the arguments of a reducer.
 pub trait Args<'de>: Sized {
     /// How many arguments does the reducer accept?
@@ -408,6 +414,9 @@ pub fn register_table<T: TableType>() {
             indexes: T::INDEXES.iter().copied().map(Into::into).collect(),
             table_type: StTableType::User,
             table_access: StAccess::for_name(T::TABLE_NAME),
+            foreign_keys: T::FOREIGN_KEYS.iter().copied().map(Into::into).collect(),
+            checks: T::CHECKS.iter().copied().map(Into::into).collect(),
+            row_policy: T::ROW_POLICY.map(Into::into),
         };
         module.module.tables.push(schema)
     })
@@ -423,6 +432,27 @@ impl From<crate::IndexDef<'_>> for spacetimedb_lib::IndexDef {
     }
 }
 
+impl From<crate::ForeignKeyDef<'_>> for spacetimedb_lib::ForeignKeyDef {
+    fn from(fk: crate::ForeignKeyDef<'_>) -> spacetimedb_lib::ForeignKeyDef {
+        spacetimedb_lib::ForeignKeyDef {
+            name: fk.name.to_owned(),
+            col_id: fk.col_id,
+            table: fk.table.to_owned(),
+            column: fk.column.to_owned(),
+            on_delete: fk.on_delete,
+        }
+    }
+}
+
+impl From<crate::CheckDef<'_>> for spacetimedb_lib::CheckDef {
+    fn from(check: crate::CheckDef<'_>) -> spacetimedb_lib::CheckDef {
+        spacetimedb_lib::CheckDef {
+            name: check.name.to_owned(),
+            expr: check.expr.to_owned(),
+        }
+    }
+}
+
 /// Registers a describer for the reducer `I` with arguments `A`.
 pub fn register_reducer<'a, A: Args<'a>, T, I: ReducerInfo>(_: impl Reducer<'a, A, T>) {
     register_describer(|module| {
@@ -432,6 +462,24 @@ pub fn register_reducer<'a, A: Args<'a>, T, I: ReducerInfo>(_: impl Reducer<'a,
     })
 }
 
+/// Registers a describer for the recurring schedule of the reducer `I` with repeater args `A`.
+pub fn register_schedule<A: RepeaterArgs, T, I: ScheduleInfo>(_: impl for<'de> Reducer<'de, A, T>) {
+    register_describer(|module| {
+        let crate::ScheduleDef { kind, catch_up } = I::SCHEDULE;
+        let kind = match kind {
+            crate::ScheduleKind::Cron(cron) => ScheduleKind::Cron(cron.to_owned()),
+            crate::ScheduleKind::FixedRate(dur) => ScheduleKind::FixedRate(dur.as_micros() as u64),
+            crate::ScheduleKind::FixedDelay(dur) => ScheduleKind::FixedDelay(dur.as_micros() as u64),
+        };
+        let schedule = ScheduleDef {
+            reducer: I::NAME.into(),
+            kind,
+            catch_up,
+        };
+        module.module.misc_exports.push(MiscModuleExport::Schedule(schedule))
+    })
+}
+
 /// A builder for a module.
 #[derive(Default)]
 struct ModuleBuilder {
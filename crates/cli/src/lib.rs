@@ -19,6 +19,7 @@ pub fn get_subcommands() -> Vec<Command> {
         version::cli(),
         publish::cli(),
         delete::cli(),
+        compact::cli(),
         logs::cli(),
         call::cli(),
         describe::cli(),
@@ -45,6 +46,7 @@ pub async fn exec_subcommand(config: Config, cmd: &str, args: &ArgMatches) -> Re
         "energy" => energy::exec(config, args).await,
         "publish" => publish::exec(config, args).await,
         "delete" => delete::exec(config, args).await,
+        "compact" => compact::exec(config, args).await,
         "logs" => logs::exec(config, args).await,
         "sql" => sql::exec(config, args).await,
         "dns" => dns::exec(config, args).await,
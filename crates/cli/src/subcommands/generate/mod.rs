@@ -6,7 +6,7 @@ use clap::ArgAction::SetTrue;
 use convert_case::{Case, Casing};
 use duct::cmd;
 use spacetimedb_lib::sats::{AlgebraicType, Typespace};
-use spacetimedb_lib::{bsatn, MiscModuleExport, ModuleDef, ReducerDef, TableDef, TypeAlias, MODULE_ABI_MAJOR_VERSION};
+use spacetimedb_lib::{MiscModuleExport, ModuleDef, ReducerDef, TableDef, TypeAlias, MODULE_ABI_MAJOR_VERSION};
 use wasmtime::{AsContext, Caller};
 
 mod code_indenter;
@@ -183,9 +183,10 @@ pub fn extract_from_moduledef(module: ModuleDef) -> (GenCtx, impl Iterator<Item
     let mut names = vec![None; typespace.types.len()];
     let name_info = itertools::chain!(
         tables.iter().map(|t| (t.data, &t.name)),
-        misc_exports
-            .iter()
-            .map(|MiscModuleExport::TypeAlias(a)| (a.ty, &a.name)),
+        misc_exports.iter().filter_map(|exp| match exp {
+            MiscModuleExport::TypeAlias(a) => Some((a.ty, &a.name)),
+            MiscModuleExport::Schedule(_) => None,
+        }),
     );
     for (typeref, name) in name_info {
         names[typeref.idx()] = Some(name.clone())
@@ -193,7 +194,7 @@ pub fn extract_from_moduledef(module: ModuleDef) -> (GenCtx, impl Iterator<Item
 
     let ctx = GenCtx { typespace, names };
     let iter = itertools::chain!(
-        misc_exports.into_iter().map(GenItem::from_misc_export),
+        misc_exports.into_iter().filter_map(GenItem::from_misc_export),
         tables.into_iter().map(GenItem::Table),
         reducers
             .into_iter()
@@ -210,9 +211,13 @@ pub enum GenItem {
 }
 
 impl GenItem {
-    fn from_misc_export(exp: MiscModuleExport) -> Self {
+    /// Returns the item to generate for `exp`, if any.
+    ///
+    /// Schedules only concern the host, so clients get nothing for them.
+    fn from_misc_export(exp: MiscModuleExport) -> Option<Self> {
         match exp {
-            MiscModuleExport::TypeAlias(a) => Self::TypeAlias(a),
+            MiscModuleExport::TypeAlias(a) => Some(Self::TypeAlias(a)),
+            MiscModuleExport::Schedule(_) => None,
         }
     }
 
@@ -385,7 +390,7 @@ fn extract_descriptions(wasm_file: &Path) -> anyhow::Result<ModuleDef> {
         Some(f) => {
             let buf: u32 = f.typed(&store)?.call(&mut store, ()).unwrap();
             let slice = store.data_mut().buffers.remove(buf as usize);
-            bsatn::from_slice(&slice)?
+            ModuleDef::decode(&slice)?
         }
         None => ModuleDef::default(),
     };
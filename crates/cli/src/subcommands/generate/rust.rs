@@ -763,6 +763,9 @@ pub fn autogen_rust_reducer(ctx: &GenCtx, reducer: &ReducerDef) -> String {
 ///    to connect to a remote database, and passes the `handle_row_update`
 ///    and `handle_event` functions so the `BackgroundDbConnection` can spawn workers
 ///    which use those functions to dispatch on the content of messages.
+///
+/// 5. `fn connect_with`, which does the same for a caller-owned
+///    `spacetimedb_sdk::DbConnection` rather than the default connection.
 pub fn autogen_rust_globals(ctx: &GenCtx, items: &[GenItem]) -> Vec<Vec<(String, String)>> {
     let mut output = CodeIndenter::new(String::new());
     let out = &mut output;
@@ -800,6 +803,11 @@ pub fn autogen_rust_globals(ctx: &GenCtx, items: &[GenItem]) -> Vec<Vec<(String,
     // Define `fn connect`.
     print_connect_defn(out);
 
+    out.newline();
+
+    // Define `fn connect_with`.
+    print_connect_with_defn(out);
+
     vec![vec![("mod.rs".to_string(), output.into_inner())]]
 }
 
@@ -1064,6 +1072,42 @@ where
     );
 }
 
+/// Define the `connect_with` wrapper,
+/// which passes all the autogenerated dispatch functions to `DbConnection::connect`
+/// for a connection other than the default one.
+fn print_connect_with_defn(out: &mut Indenter) {
+    print_connect_docstring(out);
+    print_lines(
+        out,
+        &[
+            "///",
+            "/// Unlike `connect`, which uses the default connection,",
+            "/// this connects the caller-owned `connection`.",
+        ],
+    );
+    writeln!(out, "{}", ALLOW_UNUSED).unwrap();
+    out.delimited_block(
+        "pub fn connect_with<IntoUri>(
+	connection: &spacetimedb_sdk::DbConnection,
+	spacetimedb_uri: IntoUri,
+	db_name: &str,
+	credentials: Option<Credentials>,
+) -> Result<()>
+where
+	IntoUri: TryInto<spacetimedb_sdk::http::Uri>,
+	<IntoUri as TryInto<spacetimedb_sdk::http::Uri>>::Error: std::error::Error + Send + Sync + 'static,
+{",
+        |out| {
+            writeln!(
+                out,
+                "connection.connect(spacetimedb_uri, db_name, credentials, Arc::new(Module))"
+            )
+            .unwrap()
+        },
+        "}\n",
+    );
+}
+
 fn print_reducer_event_defn(out: &mut Indenter, items: &[GenItem]) {
     writeln!(out, "{}", ALLOW_UNUSED).unwrap();
 
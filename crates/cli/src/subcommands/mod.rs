@@ -1,5 +1,6 @@
 pub mod build;
 pub mod call;
+pub mod compact;
 pub mod delete;
 pub mod describe;
 pub mod dns;
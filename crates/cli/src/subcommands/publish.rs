@@ -1,6 +1,7 @@
 use anyhow::bail;
 use clap::Arg;
 use clap::ArgAction::SetTrue;
+use clap::ArgGroup;
 use clap::ArgMatches;
 use reqwest::{StatusCode, Url};
 use spacetimedb_lib::name::PublishOp;
@@ -31,6 +32,14 @@ pub fn cli() -> clap::Command {
                 .action(SetTrue)
                 .help("When publishing a new module to an existing address, also delete all tables associated with the database"),
         )
+        .arg(
+            Arg::new("drop_orphaned_tables")
+                .long("drop-orphaned-tables")
+                .action(SetTrue)
+                .conflicts_with("clear_database")
+                .help("When updating a database, drop the tables which are no longer part of the module, even if they still hold rows")
+                .long_help("When updating a database, drop the tables which are no longer part of the module, even if they still hold rows. Without this flag, the update is rejected if such a table is not empty after the `__migrate__` reducer ran."),
+        )
         .arg(
             Arg::new("path_to_project")
                 .value_parser(clap::value_parser!(PathBuf))
@@ -83,6 +92,33 @@ pub fn cli() -> clap::Command {
                 .action(SetTrue)
                 .help("Builds the module using debug instead of release (intended to speed up local iteration, not recommended for CI)"),
         )
+        .arg(
+            Arg::new("restore_from")
+                .long("restore-from")
+                .requires("restore_point")
+                .help("The domain or address of a database to create this database from")
+                .long_help("Create the new database with the state of the given database as of the commit given by --commit-offset or --timestamp. The source database is left unchanged."),
+        )
+        .arg(
+            Arg::new("commit_offset")
+                .long("commit-offset")
+                .requires("restore_from")
+                .value_parser(clap::value_parser!(u64))
+                .help("The offset of the last commit to restore when using --restore-from"),
+        )
+        .arg(
+            Arg::new("timestamp")
+                .long("timestamp")
+                .requires("restore_from")
+                .value_parser(clap::value_parser!(u64))
+                .help("Restore the last commit made at or before this time when using --restore-from")
+                .long_help("Restore the last commit made at or before this time, in microseconds since the Unix epoch, when using --restore-from. Commits made by versions of SpacetimeDB which did not record when they were made can only be restored this way if a later commit can."),
+        )
+        .group(
+            ArgGroup::new("restore_point")
+                .args(["commit_offset", "timestamp"])
+                .multiple(false)
+        )
         .arg(
             Arg::new("name|address")
                 .help("A valid domain or address for this database"),
@@ -103,10 +139,14 @@ pub async fn exec(mut config: Config, args: &ArgMatches) -> Result<(), anyhow::E
     let path_to_project = args.get_one::<PathBuf>("path_to_project").unwrap();
     let host_type = args.get_one::<String>("host_type").unwrap();
     let clear_database = args.get_flag("clear_database");
+    let drop_orphaned_tables = args.get_flag("drop_orphaned_tables");
     let trace_log = args.get_flag("trace_log");
     let anon_identity = args.get_flag("anon_identity");
     let skip_clippy = args.get_flag("skip_clippy");
     let build_debug = args.get_flag("debug");
+    let restore_from = args.get_one::<String>("restore_from");
+    let commit_offset = args.get_one::<u64>("commit_offset").map(u64::to_string);
+    let timestamp = args.get_one::<u64>("timestamp").map(u64::to_string);
 
     let mut query_params = Vec::<(&str, &str)>::new();
     query_params.push(("host_type", host_type.as_str()));
@@ -132,10 +172,24 @@ pub async fn exec(mut config: Config, args: &ArgMatches) -> Result<(), anyhow::E
         query_params.push(("clear", "true"));
     }
 
+    if drop_orphaned_tables {
+        query_params.push(("drop_orphaned_tables", "true"));
+    }
+
     if trace_log {
         query_params.push(("trace_log", "true"));
     }
 
+    if let Some(restore_from) = restore_from {
+        query_params.push(("restore_from", restore_from.as_str()));
+        if let Some(commit_offset) = &commit_offset {
+            query_params.push(("commit_offset", commit_offset.as_str()));
+        }
+        if let Some(timestamp) = &timestamp {
+            query_params.push(("timestamp", timestamp.as_str()));
+        }
+    }
+
     let path_to_wasm = crate::tasks::build(path_to_project, skip_clippy, build_debug)?;
     let program_bytes = fs::read(path_to_wasm)?;
 
@@ -174,7 +228,12 @@ pub async fn exec(mut config: Config, args: &ArgMatches) -> Result<(), anyhow::E
 
     let response: PublishResult = serde_json::from_slice(&bytes[..]).unwrap();
     match response {
-        PublishResult::Success { domain, address, op } => {
+        PublishResult::Success {
+            domain,
+            address,
+            op,
+            migrated_tables,
+        } => {
             let op = match op {
                 PublishOp::Created => "Created new",
                 PublishOp::Updated => "Updated",
@@ -184,6 +243,21 @@ pub async fn exec(mut config: Config, args: &ArgMatches) -> Result<(), anyhow::E
             } else {
                 println!("{} database with address: {}", op, address);
             }
+            for table in migrated_tables {
+                let mut changes = Vec::new();
+                if !table.added_columns.is_empty() {
+                    changes.push(format!("added columns: {}", table.added_columns.join(", ")));
+                }
+                if !table.altered_columns.is_empty() {
+                    changes.push(format!("altered columns: {}", table.altered_columns.join(", ")));
+                }
+                println!(
+                    "Migrated table {} ({}), rewriting {} rows",
+                    table.table_name,
+                    changes.join("; "),
+                    table.rows_migrated
+                );
+            }
         }
         PublishResult::TldNotRegistered { domain } => {
             return Err(anyhow::anyhow!(
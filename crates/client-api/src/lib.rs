@@ -8,6 +8,7 @@ use spacetimedb::address::Address;
 use spacetimedb::auth::identity::{DecodingKey, EncodingKey};
 use spacetimedb::client::ClientActorIndex;
 use spacetimedb::database_instance_context_controller::DatabaseInstanceContextController;
+use spacetimedb::host::Timestamp;
 use spacetimedb::host::UpdateDatabaseResult;
 use spacetimedb::host::{EnergyQuanta, HostController};
 use spacetimedb::identity::Identity;
@@ -68,6 +69,38 @@ pub struct DatabaseDef {
     pub program_bytes: Vec<u8>,
     /// The desired number of replicas the database shall have.
     pub num_replicas: u32,
+    /// Create the database from the history of an existing database, instead
+    /// of starting out empty.
+    ///
+    /// Only valid if the database does not exist yet.
+    pub restore_from: Option<RestorePoint>,
+    /// When updating the database, drop the tables which are no longer part
+    /// of the module even if they still hold rows, instead of rejecting the
+    /// update.
+    pub drop_orphaned_tables: bool,
+}
+
+/// A point in the history of a database to create a new database from.
+///
+/// See [`DatabaseDef::restore_from`].
+#[derive(Clone, Copy, Debug)]
+pub struct RestorePoint {
+    /// The [`Address`] of the database to restore.
+    pub address: Address,
+    /// The last commit to restore.
+    pub target: RestoreTarget,
+}
+
+/// The last commit to restore of a [`RestorePoint`].
+#[derive(Clone, Copy, Debug)]
+pub enum RestoreTarget {
+    /// The commit at this offset.
+    CommitOffset(u64),
+    /// The last commit made at or before this point in time.
+    ///
+    /// Commits written before commits recorded when they were made can only
+    /// be restored this way if a later commit can.
+    Timestamp(Timestamp),
 }
 
 /// API of the SpacetimeDB control plane.
@@ -135,6 +168,11 @@ pub trait ControlStateWriteAccess: Send + Sync {
     ///
     /// Otherwise, `None` is returned meaning that the database was freshly
     /// initialized.
+    ///
+    /// If [`DatabaseDef::restore_from`] is given, the new database is created
+    /// with the state of the source database as of the given commit, and runs
+    /// the module it ran at that point. If `program_bytes` differs from that
+    /// module, the database is subsequently updated.
     async fn publish_database(
         &self,
         identity: &Identity,
@@ -16,6 +16,7 @@ use spacetimedb::host::EntityDef;
 use spacetimedb::host::ReducerArgs;
 use spacetimedb::host::ReducerCallError;
 use spacetimedb::host::ReducerOutcome;
+use spacetimedb::host::Timestamp;
 use spacetimedb::host::UpdateDatabaseSuccess;
 use spacetimedb::identity::Identity;
 use spacetimedb::json::client_api::StmtResultJson;
@@ -26,6 +27,7 @@ use spacetimedb_lib::identity::AuthCtx;
 use spacetimedb_lib::name::{self, DnsLookupResponse, DomainName, DomainParsingError, PublishOp, PublishResult};
 use spacetimedb_lib::recovery::{RecoveryCode, RecoveryCodeResponse};
 use spacetimedb_lib::sats::WithTypespace;
+use spacetimedb_lib::{CatchUp, ScheduleDef, ScheduleKind};
 use std::collections::HashMap;
 use std::convert::From;
 
@@ -36,7 +38,7 @@ use crate::auth::{
 };
 use crate::routes::subscribe::generate_random_address;
 use crate::util::{ByteStringBody, NameOrAddress};
-use crate::{log_and_500, ControlStateDelegate, DatabaseDef, NodeDelegate};
+use crate::{log_and_500, ControlStateDelegate, DatabaseDef, NodeDelegate, RestorePoint, RestoreTarget};
 
 #[derive(derive_more::From)]
 pub(crate) struct DomainParsingRejection(pub(crate) DomainParsingError);
@@ -220,12 +222,37 @@ async fn extract_db_call_info(
     })
 }
 
-fn entity_description_json(description: WithTypespace<EntityDef>, expand: bool) -> Option<Value> {
+fn entity_description_json(
+    description: WithTypespace<EntityDef>,
+    schedule: Option<&ScheduleDef>,
+    expand: bool,
+) -> Option<Value> {
     let typ = DescribedEntityType::from_entitydef(description.ty()).as_str();
     let len = match description.ty() {
         EntityDef::Table(t) => description.resolve(t.data).ty().as_product()?.elements.len(),
         EntityDef::Reducer(r) => r.args.len(),
     };
+    let mut json = entity_json(description, typ, len, expand)?;
+    if let Some(schedule) = schedule {
+        json["schedule"] = schedule_json(schedule);
+    }
+    Some(json)
+}
+
+fn schedule_json(schedule: &ScheduleDef) -> Value {
+    let catch_up = match schedule.catch_up {
+        CatchUp::Skip => "skip",
+        CatchUp::Once => "once",
+        CatchUp::All => "all",
+    };
+    match &schedule.kind {
+        ScheduleKind::Cron(cron) => json!({ "cron": cron, "catch_up": catch_up }),
+        ScheduleKind::FixedRate(micros) => json!({ "fixed_rate_micros": micros, "catch_up": catch_up }),
+        ScheduleKind::FixedDelay(micros) => json!({ "fixed_delay_micros": micros, "catch_up": catch_up }),
+    }
+}
+
+fn entity_json(description: WithTypespace<EntityDef>, typ: &str, len: usize, expand: bool) -> Option<Value> {
     if expand {
         // TODO(noa): make this less hacky; needs coordination w/ spacetime-web
         let schema = match description.ty() {
@@ -309,7 +336,8 @@ where
         .ok_or_else(|| (StatusCode::NOT_FOUND, format!("{entity_type} {entity:?} not found")))?;
 
     let expand = expand.unwrap_or(true);
-    let response_json = json!({ entity: entity_description_json(description, expand) });
+    let schedule = catalog.get_schedule(&entity);
+    let response_json = json!({ entity: entity_description_json(description, schedule, expand) });
 
     Ok((
         StatusCode::OK,
@@ -355,7 +383,12 @@ where
     let expand = expand.unwrap_or(false);
     let response_catalog: HashMap<_, _> = catalog
         .iter()
-        .map(|(name, entity)| (name, entity_description_json(entity, expand)))
+        .map(|(name, entity)| {
+            (
+                name,
+                entity_description_json(entity, catalog.get_schedule(name), expand),
+            )
+        })
         .collect();
     let response_json = json!({
         "entities": response_catalog,
@@ -519,12 +552,15 @@ pub struct SqlParams {
 }
 
 #[derive(Deserialize)]
-pub struct SqlQueryParams {}
+pub struct SqlQueryParams {
+    /// The values of the `$1`, `$2`, ... placeholders of the query, as a JSON array.
+    params: Option<String>,
+}
 
 pub async fn sql<S>(
     State(worker_ctx): State<S>,
     Path(SqlParams { name_or_address }): Path<SqlParams>,
-    Query(SqlQueryParams {}): Query<SqlQueryParams>,
+    Query(SqlQueryParams { params }): Query<SqlQueryParams>,
     auth: SpacetimeAuthHeader,
     body: String,
 ) -> axum::response::Result<impl IntoResponse>
@@ -535,6 +571,13 @@ where
     // which queries this identity is allowed to execute against the database.
     let auth = auth.get().ok_or((StatusCode::UNAUTHORIZED, "Invalid credentials."))?;
 
+    let params = match params {
+        Some(params) => serde_json::from_str(&params)
+            .map(spacetimedb::sql::params::SqlParams::Json)
+            .map_err(|err| (StatusCode::BAD_REQUEST, format!("Invalid params: {err}")))?,
+        None => Default::default(),
+    };
+
     let address = name_or_address.resolve(&worker_ctx).await?.into();
     let database = worker_ctx_find_database(&worker_ctx, &address)
         .await?
@@ -562,12 +605,16 @@ where
         }
     };
 
-    let results = match execute(
-        worker_ctx.database_instance_context_controller(),
-        instance_id,
-        body,
-        auth,
-    ) {
+    let (dbic, _) = worker_ctx
+        .database_instance_context_controller()
+        .get(instance_id)
+        .ok_or((StatusCode::NOT_FOUND, "Database instance not found."))?;
+
+    // The query waits for the transaction in progress, if there's one.
+    let results = tokio::task::spawn_blocking(move || execute(&dbic, body, params, auth))
+        .await
+        .map_err(log_and_500)?;
+    let results = match results {
         Ok(results) => results,
         Err(err) => {
             log::warn!("{}", err);
@@ -592,6 +639,68 @@ where
     Ok((StatusCode::OK, axum::Json(json)))
 }
 
+#[derive(Deserialize)]
+pub struct CompactParams {
+    name_or_address: NameOrAddress,
+}
+
+/// Reclaim the disk space used by the history of a database.
+///
+/// The history before the snapshot taken by the compaction is discarded, so
+/// the database can no longer be forked at an earlier commit.
+///
+/// Only the owner of the database may compact it.
+pub async fn compact<S>(
+    State(worker_ctx): State<S>,
+    Path(CompactParams { name_or_address }): Path<CompactParams>,
+    auth: SpacetimeAuthHeader,
+) -> axum::response::Result<impl IntoResponse>
+where
+    S: NodeDelegate + ControlStateDelegate,
+{
+    let auth = auth_or_unauth(auth)?;
+
+    let address = name_or_address.resolve(&worker_ctx).await?.into();
+    let database = worker_ctx_find_database(&worker_ctx, &address)
+        .await?
+        .ok_or((StatusCode::NOT_FOUND, "No such database."))?;
+
+    if database.identity != auth.identity {
+        return Err((StatusCode::UNAUTHORIZED, "Identity does not own database.").into());
+    }
+
+    let database_instance = worker_ctx
+        .get_leader_database_instance_by_database(database.id)
+        .ok_or((
+            StatusCode::NOT_FOUND,
+            "Database instance not scheduled to this node yet.",
+        ))?;
+    let instance_id = database_instance.id;
+
+    let host = worker_ctx.host_controller();
+    if host.get_module_host(instance_id).is_err() {
+        let dbic = worker_ctx
+            .load_module_host_context(database, instance_id)
+            .await
+            .map_err(log_and_500)?;
+        host.spawn_module_host(dbic).await.map_err(log_and_500)?;
+    }
+
+    let (dbic, _) = worker_ctx
+        .database_instance_context_controller()
+        .get(instance_id)
+        .ok_or((StatusCode::NOT_FOUND, "Database instance not found."))?;
+
+    // Compaction briefly blocks the transactions of the database, and does a
+    // fair amount of IO.
+    let compaction = tokio::task::spawn_blocking(move || dbic.relational_db.compact())
+        .await
+        .map_err(log_and_500)?
+        .map_err(log_and_500)?;
+
+    Ok(axum::Json(compaction))
+}
+
 #[derive(Deserialize)]
 pub struct DNSParams {
     database_name: String,
@@ -763,8 +872,19 @@ pub struct PublishDatabaseParams {}
 pub struct PublishDatabaseQueryParams {
     #[serde(default)]
     clear: bool,
+    /// Drop the tables which are no longer part of the module, even if they still hold rows.
+    #[serde(default)]
+    drop_orphaned_tables: bool,
     name_or_address: Option<NameOrAddress>,
     client_address: Option<AddressForUrl>,
+    /// Create the database from the history of this database.
+    restore_from: Option<NameOrAddress>,
+    /// The offset of the last commit of `restore_from` to restore.
+    commit_offset: Option<u64>,
+    /// Restore the last commit of `restore_from` made at or before this point
+    /// in time, in microseconds since the Unix epoch, instead of the one at
+    /// `commit_offset`.
+    timestamp: Option<u64>,
 }
 
 pub async fn publish<S: NodeDelegate + ControlStateDelegate>(
@@ -777,10 +897,40 @@ pub async fn publish<S: NodeDelegate + ControlStateDelegate>(
     let PublishDatabaseQueryParams {
         name_or_address,
         clear,
+        drop_orphaned_tables,
         client_address,
+        restore_from,
+        commit_offset,
+        timestamp,
     } = query_params;
 
     let client_address = client_address.map(Address::from);
+    let target = match (commit_offset, timestamp) {
+        (None, None) => None,
+        (Some(commit_offset), None) => Some(RestoreTarget::CommitOffset(commit_offset)),
+        (None, Some(timestamp)) => Some(RestoreTarget::Timestamp(Timestamp(timestamp))),
+        (Some(_), Some(_)) => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Only one of `commit_offset` and `timestamp` may be given",
+            )
+                .into())
+        }
+    };
+    let restore_from = match (restore_from, target) {
+        (None, None) => None,
+        (Some(noa), Some(target)) => Some(RestorePoint {
+            address: noa.resolve(&ctx).await?.into(),
+            target,
+        }),
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "`restore_from` must be given together with either `commit_offset` or `timestamp`",
+            )
+                .into())
+        }
+    };
 
     // You should not be able to publish to a database that you do not own
     // so, unless you are the owner, this will fail.
@@ -810,6 +960,15 @@ pub async fn publish<S: NodeDelegate + ControlStateDelegate>(
     let op = {
         let exists = ctx.get_database_by_address(&db_addr).map_err(log_and_500)?.is_some();
 
+        if let Some(restore_from) = &restore_from {
+            if restore_from.address == db_addr {
+                return Err((StatusCode::BAD_REQUEST, "Cannot restore a database into itself").into());
+            }
+            if exists && !clear {
+                return Err((StatusCode::BAD_REQUEST, "Cannot restore into an existing database").into());
+            }
+        }
+
         if clear && exists {
             ctx.delete_database(&auth.identity, &db_addr)
                 .await
@@ -831,26 +990,30 @@ pub async fn publish<S: NodeDelegate + ControlStateDelegate>(
                 address: db_addr,
                 program_bytes: body.into(),
                 num_replicas: 1,
+                restore_from,
+                drop_orphaned_tables,
             },
         )
         .await
         .map_err(log_and_500)?;
 
+    let mut migrated_tables = Vec::new();
     if let Some(updated) = maybe_updated {
         match updated {
-            Ok(success) => {
-                if let UpdateDatabaseSuccess {
-                    // An update reducer was defined, and it was run
-                    update_result: Some(update_result),
-                    // Not yet implemented
-                    migrate_results: _,
-                } = success
-                {
+            Ok(UpdateDatabaseSuccess {
+                update_result,
+                // Not yet implemented
+                migrate_results: _,
+                migrated_tables: migrated,
+            }) => {
+                // An update reducer was defined, and it was run
+                if let Some(update_result) = update_result {
                     let ror = reducer_outcome_response(&auth.identity, "update", update_result.outcome);
                     if !matches!(ror, (StatusCode::OK, _)) {
                         return Err(ror.into());
                     }
                 }
+                migrated_tables = migrated;
             }
             Err(e) => return Err((StatusCode::BAD_REQUEST, format!("Database update rejected: {e}")).into()),
         }
@@ -860,6 +1023,7 @@ pub async fn publish<S: NodeDelegate + ControlStateDelegate>(
         domain: db_name.as_ref().map(ToString::to_string),
         address: db_addr,
         op,
+        migrated_tables,
     }))
 }
 
@@ -959,4 +1123,5 @@ where
         .route("/info/:name_or_address", get(info::<S>))
         .route("/logs/:name_or_address", get(logs::<S>))
         .route("/sql/:name_or_address", post(sql::<S>))
+        .route("/compact/:name_or_address", post(compact::<S>))
 }
@@ -35,6 +35,10 @@ pub struct SubscribeParams {
 #[derive(Deserialize)]
 pub struct SubscribeQueryParams {
     pub client_address: Option<AddressForUrl>,
+    /// Whether the client understands update operations in subscription updates.
+    /// Otherwise, each update is sent as the deletion of the old row followed by the insertion of the new one.
+    #[serde(default)]
+    pub update_ops: bool,
 }
 
 // TODO: is this a reasonable way to generate client addresses?
@@ -48,7 +52,10 @@ pub fn generate_random_address() -> Address {
 pub async fn handle_websocket<S>(
     State(ctx): State<S>,
     Path(SubscribeParams { name_or_address }): Path<SubscribeParams>,
-    Query(SubscribeQueryParams { client_address }): Query<SubscribeQueryParams>,
+    Query(SubscribeQueryParams {
+        client_address,
+        update_ops,
+    }): Query<SubscribeQueryParams>,
     forwarded_for: Option<TypedHeader<XForwardedFor>>,
     auth: SpacetimeAuthHeader,
     ws: WebSocketUpgrade,
@@ -134,7 +141,7 @@ where
         }
 
         let actor = |client, sendrx| ws_client_actor(client, ws, sendrx);
-        let client = match ClientConnection::spawn(client_id, protocol, instance_id, module, actor).await {
+        let client = match ClientConnection::spawn(client_id, protocol, update_ops, instance_id, module, actor).await {
             Ok(s) => s,
             Err(e) => {
                 log::warn!("ModuleHost died while we were connecting: {e:#}");
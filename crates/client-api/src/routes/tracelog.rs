@@ -84,7 +84,8 @@ pub async fn perform_tracelog_replay(body: Bytes) -> axum::response::Result<impl
         db_path.to_path_buf(),
         logger_path,
     );
-    let iv = InstanceEnv::new(dbic, Scheduler::dummy(&tmp_dir.path().join("scheduler")), None);
+    let scheduler = Scheduler::dummy(dbic.relational_db.clone());
+    let iv = InstanceEnv::new(dbic, scheduler, None);
 
     let tx = iv.dbic.relational_db.begin_tx();
 
@@ -1,12 +1,18 @@
 use std::ops::Deref;
+use std::sync::Arc;
+use std::time::Instant;
 
+use crate::error::SessionError;
 use crate::host::{ModuleHost, NoSuchModule, ReducerArgs, ReducerCallError, ReducerCallResult};
 use crate::protobuf::client_api::Subscribe;
+use crate::sql::params::SqlParams;
+use crate::sql::session::SqlSession;
 use crate::util::prometheus_handle::IntGaugeExt;
 use crate::worker_metrics::WORKER_METRICS;
 use derive_more::From;
 use futures::prelude::*;
-use tokio::sync::mpsc;
+use spacetimedb_lib::relation::MemTable;
+use tokio::sync::{mpsc, Mutex};
 
 use super::messages::{OneOffQueryResponseMessage, ServerMessage};
 use super::{message_handlers, ClientActorId, MessageHandleError};
@@ -21,6 +27,9 @@ pub enum Protocol {
 pub struct ClientConnectionSender {
     pub id: ClientActorId,
     pub protocol: Protocol,
+    /// Whether the client asked to receive the replacement of a row as a single update operation,
+    /// rather than as the deletion of the old row followed by the insertion of the new one.
+    pub update_ops: bool,
     sendtx: mpsc::Sender<DataMessage>,
 }
 
@@ -31,7 +40,12 @@ pub struct ClientClosed;
 impl ClientConnectionSender {
     pub fn dummy(id: ClientActorId, protocol: Protocol) -> Self {
         let (sendtx, _) = mpsc::channel(1);
-        Self { id, protocol, sendtx }
+        Self {
+            id,
+            protocol,
+            update_ops: false,
+            sendtx,
+        }
     }
 
     pub fn send_message(&self, message: impl ServerMessage) -> impl Future<Output = Result<(), ClientClosed>> + '_ {
@@ -60,6 +74,7 @@ pub struct ClientConnection {
     sender: ClientConnectionSender,
     pub database_instance_id: u64,
     pub module: ModuleHost,
+    sql_session: Arc<Mutex<SqlSession>>,
 }
 
 impl Deref for ClientConnection {
@@ -94,6 +109,7 @@ impl ClientConnection {
     pub async fn spawn<F, Fut>(
         id: ClientActorId,
         protocol: Protocol,
+        update_ops: bool,
         database_instance_id: u64,
         module: ModuleHost,
         actor: F,
@@ -113,11 +129,17 @@ impl ClientConnection {
         // Buffer up to 64 client messages
         let (sendtx, sendrx) = mpsc::channel::<DataMessage>(64);
 
-        let sender = ClientConnectionSender { id, protocol, sendtx };
+        let sender = ClientConnectionSender {
+            id,
+            protocol,
+            update_ops,
+            sendtx,
+        };
         let this = Self {
             sender,
             database_instance_id,
             module,
+            sql_session: Default::default(),
         };
 
         let actor_fut = actor(this.clone(), sendrx);
@@ -132,6 +154,7 @@ impl ClientConnection {
             sender: ClientConnectionSender::dummy(id, protocol),
             database_instance_id,
             module,
+            sql_session: Default::default(),
         }
     }
 
@@ -159,12 +182,62 @@ impl ClientConnection {
             .await
     }
 
-    pub fn subscribe(&self, subscription: Subscribe) -> Result<(), NoSuchModule> {
-        self.module.subscription().add_subscriber(self.sender(), subscription)
+    pub fn subscribe(&self, subscription: Subscribe, params: SqlParams) -> Result<(), NoSuchModule> {
+        self.module
+            .subscription()
+            .add_subscriber(self.sender(), subscription, params)
+    }
+
+    pub async fn one_off_query(&self, query: &str, params: SqlParams, message_id: &[u8]) -> Result<(), anyhow::Error> {
+        let result = match self.check_no_open_session().await {
+            Ok(()) => {
+                self.module
+                    .one_off_query(self.id.identity, query.to_owned(), params)
+                    .await
+            }
+            Err(e) => Err(e.into()),
+        };
+        self.send_query_response(message_id, result).await
+    }
+
+    /// Returns an error if the `SQL` session of this connection has a transaction open.
+    ///
+    /// Every other transaction of the database waits for it,
+    /// so the other messages of the connection are rejected until the session ends it.
+    pub async fn check_no_open_session(&self) -> Result<(), SessionError> {
+        match self.sql_session.lock().await.deadline() {
+            Some(_) => Err(SessionError::Busy),
+            None => Ok(()),
+        }
     }
 
-    pub async fn one_off_query(&self, query: &str, message_id: &[u8]) -> Result<(), anyhow::Error> {
-        let result = self.module.one_off_query(self.id.identity, query.to_owned()).await;
+    /// Runs `query` in the `SQL` session of this connection, see [SqlSession],
+    /// rolling back the transaction it opens once its deadline passes.
+    pub async fn session_query(&self, query: &str, params: SqlParams, message_id: &[u8]) -> Result<(), anyhow::Error> {
+        let session = self.sql_session.clone().lock_owned().await;
+        let was_open = session.deadline().is_some();
+        let (session, result) = self
+            .module
+            .sql_session_query(self.id.identity, session, query.to_owned(), params)
+            .await;
+        if let Some(deadline) = session.deadline().filter(|_| !was_open) {
+            let session = Arc::downgrade(&self.sql_session);
+            tokio::spawn(async move {
+                tokio::time::sleep_until(deadline.into()).await;
+                if let Some(session) = session.upgrade() {
+                    session.lock().await.expire(Instant::now());
+                }
+            });
+        }
+        drop(session);
+        self.send_query_response(message_id, result).await
+    }
+
+    async fn send_query_response(
+        &self,
+        message_id: &[u8],
+        result: Result<Vec<MemTable>, anyhow::Error>,
+    ) -> Result<(), anyhow::Error> {
         let message_id = message_id.to_owned();
         let response = match result {
             Ok(results) => OneOffQueryResponseMessage {
@@ -4,6 +4,7 @@ use crate::host::module_host::{EventStatus, ModuleEvent, ModuleFunctionCall};
 use crate::host::{EnergyDiff, ReducerArgs, Timestamp};
 use crate::identity::Identity;
 use crate::protobuf::client_api::{message, FunctionCall, Message, Subscribe};
+use crate::sql::params::SqlParams;
 use crate::worker_metrics::WORKER_METRICS;
 use base64::Engine;
 use bytes::Bytes;
@@ -52,17 +53,26 @@ pub async fn handle(client: &ClientConnection, message: DataMessage) -> Result<(
 }
 
 async fn handle_binary(client: &ClientConnection, message_buf: Vec<u8>) -> Result<(), MessageHandleError> {
-    let message = Message::decode(Bytes::from(message_buf))?;
+    let mut message = Message::decode(Bytes::from(message_buf))?;
     let message = match message.r#type {
         Some(message::Type::FunctionCall(FunctionCall { ref reducer, arg_bytes })) => {
             let args = ReducerArgs::Bsatn(arg_bytes.into());
             DecodedMessage::Call { reducer, args }
         }
-        Some(message::Type::Subscribe(subscription)) => DecodedMessage::Subscribe(subscription),
-        Some(message::Type::OneOffQuery(ref oneoff)) => DecodedMessage::OneOffQuery {
+        Some(message::Type::Subscribe(mut subscription)) => {
+            let params = SqlParams::Bsatn(std::mem::take(&mut subscription.params));
+            DecodedMessage::Subscribe(subscription, params)
+        }
+        Some(message::Type::OneOffQuery(ref mut oneoff)) => DecodedMessage::OneOffQuery {
+            params: SqlParams::Bsatn(std::mem::take(&mut oneoff.params)),
             query_string: &oneoff.query_string[..],
             message_id: &oneoff.message_id[..],
         },
+        Some(message::Type::SessionQuery(ref mut query)) => DecodedMessage::SessionQuery {
+            params: SqlParams::Bsatn(std::mem::take(&mut query.params)),
+            query_string: &query.query_string[..],
+            message_id: &query.message_id[..],
+        },
         _ => return Err(MessageHandleError::InvalidMessage),
     };
 
@@ -80,12 +90,34 @@ enum RawJsonMessage<'a> {
         args: &'a serde_json::value::RawValue,
     },
     #[serde(rename = "subscribe")]
-    Subscribe { query_strings: Vec<String> },
+    Subscribe {
+        query_strings: Vec<String>,
+        /// The values of the `$1`, `$2`, ... placeholders of the queries.
+        #[serde(default)]
+        params: Vec<serde_json::Value>,
+    },
     #[serde(rename = "one_off_query")]
     OneOffQuery {
         #[serde(borrow)]
         query_string: std::borrow::Cow<'a, str>,
 
+        /// The values of the `$1`, `$2`, ... placeholders of the query.
+        #[serde(default)]
+        params: Vec<serde_json::Value>,
+
+        /// A base64-encoded string of bytes.
+        #[serde(borrow)]
+        message_id: std::borrow::Cow<'a, str>,
+    },
+    #[serde(rename = "session_query")]
+    SessionQuery {
+        #[serde(borrow)]
+        query_string: std::borrow::Cow<'a, str>,
+
+        /// The values of the `$1`, `$2`, ... placeholders of the statements.
+        #[serde(default)]
+        params: Vec<serde_json::Value>,
+
         /// A base64-encoded string of bytes.
         #[serde(borrow)]
         message_id: std::borrow::Cow<'a, str>,
@@ -101,9 +133,16 @@ async fn handle_text(client: &ClientConnection, message: String) -> Result<(), M
             let args = ReducerArgs::Json(message.slice_ref(args.get()));
             DecodedMessage::Call { reducer: func, args }
         }
-        RawJsonMessage::Subscribe { query_strings } => DecodedMessage::Subscribe(Subscribe { query_strings }),
+        RawJsonMessage::Subscribe { query_strings, params } => DecodedMessage::Subscribe(
+            Subscribe {
+                query_strings,
+                params: Vec::new(),
+            },
+            SqlParams::Json(params),
+        ),
         RawJsonMessage::OneOffQuery {
             query_string: ref query,
+            params,
             message_id,
         } => {
             let _ = std::mem::replace(
@@ -112,6 +151,22 @@ async fn handle_text(client: &ClientConnection, message: String) -> Result<(), M
             );
             DecodedMessage::OneOffQuery {
                 query_string: &query[..],
+                params: SqlParams::Json(params),
+                message_id: &message_id_[..],
+            }
+        }
+        RawJsonMessage::SessionQuery {
+            query_string: ref query,
+            params,
+            message_id,
+        } => {
+            let _ = std::mem::replace(
+                &mut message_id_,
+                base64::engine::general_purpose::STANDARD.decode(&message_id[..])?,
+            );
+            DecodedMessage::SessionQuery {
+                query_string: &query[..],
+                params: SqlParams::Json(params),
                 message_id: &message_id_[..],
             }
         }
@@ -127,9 +182,15 @@ enum DecodedMessage<'a> {
         reducer: &'a str,
         args: ReducerArgs,
     },
-    Subscribe(Subscribe),
+    Subscribe(Subscribe, SqlParams),
     OneOffQuery {
         query_string: &'a str,
+        params: SqlParams,
+        message_id: &'a [u8],
+    },
+    SessionQuery {
+        query_string: &'a str,
+        params: SqlParams,
         message_id: &'a [u8],
     },
 }
@@ -137,15 +198,33 @@ enum DecodedMessage<'a> {
 impl DecodedMessage<'_> {
     async fn handle(self, client: &ClientConnection) -> Result<(), MessageExecutionError> {
         let res = match self {
-            DecodedMessage::Call { reducer, args } => {
-                let res = client.call_reducer(reducer, args).await;
-                res.map(drop).map_err(|e| (Some(reducer), e.into()))
-            }
-            DecodedMessage::Subscribe(subscription) => client.subscribe(subscription).map_err(|e| (None, e.into())),
+            DecodedMessage::Call { reducer, args } => match client.check_no_open_session().await {
+                Ok(()) => {
+                    let res = client.call_reducer(reducer, args).await;
+                    res.map(drop).map_err(|e| (Some(reducer), e.into()))
+                }
+                Err(e) => Err((Some(reducer), e.into())),
+            },
+            DecodedMessage::Subscribe(subscription, params) => match client.check_no_open_session().await {
+                Ok(()) => client.subscribe(subscription, params).map_err(|e| (None, e.into())),
+                Err(e) => Err((None, e.into())),
+            },
             DecodedMessage::OneOffQuery {
                 query_string: query,
+                params,
+                message_id,
+            } => client
+                .one_off_query(query, params, message_id)
+                .await
+                .map_err(|err| (None, err)),
+            DecodedMessage::SessionQuery {
+                query_string: query,
+                params,
                 message_id,
-            } => client.one_off_query(query, message_id).await.map_err(|err| (None, err)),
+            } => client
+                .session_query(query, params, message_id)
+                .await
+                .map_err(|err| (None, err)),
         };
         res.map_err(|(reducer, err)| MessageExecutionError {
             reducer: reducer.map(str::to_owned),
@@ -213,10 +292,31 @@ mod tests {
 
         if let RawJsonMessage::OneOffQuery {
             query_string: query,
+            params,
             message_id,
         } = parsed
         {
             assert_eq!(query, "SELECT * FROM User WHERE name != 'bananas'");
+            assert!(params.is_empty());
+            assert_eq!(message_id, "ywS3WFquDECZQ0UdLZN1IA==");
+        } else {
+            panic!("wrong variant")
+        }
+    }
+
+    #[test]
+    fn parse_session_query() {
+        let message = r#"{ "session_query": { "message_id": "ywS3WFquDECZQ0UdLZN1IA==", "query_string": "DELETE FROM User WHERE id = $1", "params": [1] } }"#;
+        let parsed = serde_json::from_str::<RawJsonMessage>(message).unwrap();
+
+        if let RawJsonMessage::SessionQuery {
+            query_string: query,
+            params,
+            message_id,
+        } = parsed
+        {
+            assert_eq!(query, "DELETE FROM User WHERE id = $1");
+            assert_eq!(params, vec![serde_json::json!(1)]);
             assert_eq!(message_id, "ywS3WFquDECZQ0UdLZN1IA==");
         } else {
             panic!("wrong variant")
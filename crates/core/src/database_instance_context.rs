@@ -6,8 +6,10 @@ use crate::db::ostorage::sled_object_db::SledObjectDB;
 use crate::db::ostorage::ObjectDB;
 use crate::db::relational_db::RelationalDB;
 use crate::db::{Config, FsyncPolicy, Storage};
+use crate::error::{DBError, DatabaseError};
 use crate::identity::Identity;
 use crate::messages::control_db::Database;
+use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
@@ -24,10 +26,7 @@ pub struct DatabaseInstanceContext {
 
 impl DatabaseInstanceContext {
     pub fn from_database(config: Config, database: &Database, instance_id: u64, root_db_path: PathBuf) -> Arc<Self> {
-        let mut db_path = root_db_path;
-        db_path.extend([&*database.address.to_hex(), &*instance_id.to_string()]);
-        db_path.push("database");
-
+        let db_path = Self::db_path(database, instance_id, root_db_path);
         let log_path = DatabaseLogger::filepath(&database.address, instance_id);
 
         Self::new(
@@ -42,11 +41,53 @@ impl DatabaseInstanceContext {
         )
     }
 
-    pub fn scheduler_db_path(&self, root_db_path: PathBuf) -> PathBuf {
-        let mut scheduler_db_path = root_db_path;
-        scheduler_db_path.extend([&*self.address.to_hex(), &*self.database_instance_id.to_string()]);
-        scheduler_db_path.push("scheduler");
-        scheduler_db_path
+    /// Prepare the storage of the new database instance `instance_id` of
+    /// `database`, such that it has the state `source` had right after the
+    /// commit at `commit_offset`.
+    ///
+    /// The instance can then be opened via [`Self::from_database`].
+    /// See [`RelationalDB::fork`] for details.
+    ///
+    /// The storage is written to a temporary directory, which is renamed to
+    /// the one of the instance once complete. If forking fails, nothing is
+    /// left on disk.
+    pub fn fork(
+        config: Config,
+        database: &Database,
+        instance_id: u64,
+        root_db_path: PathBuf,
+        source: &RelationalDB,
+        commit_offset: u64,
+    ) -> Result<(), DBError> {
+        if matches!(config.storage, Storage::Memory) {
+            return Err(DatabaseError::HistoryUnavailable(commit_offset).into());
+        }
+        let db_path = Self::db_path(database, instance_id, root_db_path);
+        let fork_path = db_path.with_extension("fork");
+        let res = (|| -> Result<(), DBError> {
+            let mut odb = Self::make_default_ostorage(fork_path.join("odb"));
+            source.fork(commit_offset, &fork_path, fork_path.join("mlog"), &mut *odb)?;
+            drop(odb);
+            Ok(fs::rename(&fork_path, &db_path)?)
+        })();
+        if res.is_err() {
+            // The instance is new, so its directory only holds what was forked.
+            // The directory of the database is only removed if it has no other instance.
+            if let Some(instance_path) = db_path.parent() {
+                let _ = fs::remove_dir_all(instance_path);
+                if let Some(database_path) = instance_path.parent() {
+                    let _ = fs::remove_dir(database_path);
+                }
+            }
+        }
+        res
+    }
+
+    fn db_path(database: &Database, instance_id: u64, root_db_path: PathBuf) -> PathBuf {
+        let mut db_path = root_db_path;
+        db_path.extend([&*database.address.to_hex(), &*instance_id.to_string()]);
+        db_path.push("database");
+        db_path
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -94,3 +135,42 @@ impl DatabaseInstanceContext {
         Box::new(SledObjectDB::open(path).unwrap())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::relational_db::tests_utils::make_test_db;
+    use crate::hash::Hash;
+    use crate::messages::control_db::HostType;
+    use spacetimedb_lib::error::ResultTest;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_fork_failure_leaves_nothing() -> ResultTest<()> {
+        let (source, _source_dir) = make_test_db()?;
+        let root = TempDir::new("stdb_test")?;
+        let database = Database {
+            id: 1,
+            address: Address::zero(),
+            identity: Identity::__dummy(),
+            host_type: HostType::Wasmer,
+            num_replicas: 1,
+            program_bytes_address: Hash::ZERO,
+            publisher_address: None,
+        };
+        let config = Config {
+            fsync: FsyncPolicy::Never,
+            storage: Storage::Disk,
+        };
+
+        // The commit doesn't exist (yet).
+        let res = DatabaseInstanceContext::fork(config, &database, 1, root.path().to_path_buf(), &source, 1000);
+        assert!(matches!(
+            res,
+            Err(DBError::Database(DatabaseError::CommitOffsetOutOfRange(1000)))
+        ));
+        assert_eq!(fs::read_dir(root.path())?.count(), 0);
+
+        Ok(())
+    }
+}
@@ -3,6 +3,7 @@ use super::{
     message_log::{self, MessageLog},
     messages::commit::Commit,
     ostorage::ObjectDB,
+    snapshot::SnapshotOffset,
 };
 use crate::{
     db::{
@@ -13,18 +14,34 @@ use crate::{
         },
     },
     error::DBError,
+    host::Timestamp,
 };
 
 use anyhow::Context;
+use serde::Serialize;
 use spacetimedb_lib::{
     hash::{hash_bytes, Hash},
     DataKey,
 };
 
+use std::collections::HashSet;
 use std::io;
 use std::sync::Arc;
 use std::sync::Mutex;
 
+/// The outcome of [`RelationalDB::compact`].
+///
+/// [`RelationalDB::compact`]: crate::db::relational_db::RelationalDB::compact
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct Compaction {
+    /// The number of message log segments removed.
+    pub segments_removed: usize,
+    /// The total size in bytes of the removed segments.
+    pub segment_bytes_removed: u64,
+    /// The number of objects removed from the object DB.
+    pub objects_removed: usize,
+}
+
 #[derive(Clone)]
 pub struct CommitLog {
     mlog: Option<Arc<Mutex<MessageLog>>>,
@@ -48,6 +65,82 @@ impl CommitLog {
         }
     }
 
+    /// The position in the log a snapshot of the datastore taken now would
+    /// be tagged with.
+    ///
+    /// Note that the datastore state may already reflect commits which have
+    /// not been appended to the log yet, unless the caller prevents commits
+    /// from being made concurrently.
+    pub fn snapshot_offset(&self) -> SnapshotOffset {
+        let unwritten_commit = self.unwritten_commit.lock().unwrap();
+        SnapshotOffset {
+            commit_offset: unwritten_commit.commit_offset,
+            tx_offset: unwritten_commit.min_tx_offset,
+            parent_commit_hash: unwritten_commit.parent_commit_hash,
+        }
+    }
+
+    /// Remove the message log segments containing only commits older than
+    /// `offset`.
+    ///
+    /// The caller must ensure the state of the database up to `offset` is
+    /// persisted elsewhere, e.g. in a snapshot.
+    ///
+    /// Returns the number of segments removed and their total size in bytes.
+    pub fn remove_segments_before(&self, offset: u64) -> Result<(usize, u64), DBError> {
+        let Some(mlog) = &self.mlog else {
+            return Ok((0, 0));
+        };
+        let mut mlog = mlog.lock().unwrap();
+        mlog.flush()?;
+        mlog.remove_segments_before(offset)
+    }
+
+    /// Add the hashes of the objects referenced from the commits at or after
+    /// `offset` to `live`.
+    ///
+    /// The log is read from a snapshot of its segments, without holding its
+    /// lock, so commits appended concurrently may or may not be visited.
+    /// Returns the offset of the commit following the last one visited, from
+    /// which a subsequent call can pick up.
+    pub fn referenced_objects(&self, offset: u64, live: &mut HashSet<Hash>) -> Result<u64, DBError> {
+        let Some(mlog) = &self.mlog else {
+            return Ok(offset);
+        };
+        let segments = mlog.lock().unwrap().segments_from(offset);
+
+        let mut next = offset;
+        for commit in Iter::from(segments) {
+            let commit = commit?;
+            // The first segment may contain commits older than `offset`.
+            if commit.commit_offset < offset {
+                continue;
+            }
+            next = commit.commit_offset + 1;
+            for transaction in commit.transactions {
+                live.extend(transaction.writes.iter().filter_map(|write| match write.data_key {
+                    DataKey::Hash(hash) => Some(hash),
+                    DataKey::Data(_) => None,
+                }));
+            }
+        }
+        Ok(next)
+    }
+
+    /// Remove the objects not contained in `live` from the object DB.
+    ///
+    /// The caller must ensure `live` holds the objects referenced from the
+    /// log and from the datastore, and that no commit adds objects which are
+    /// not accounted for while this is in progress.
+    ///
+    /// Returns the number of objects removed.
+    pub fn retain_objects(&self, live: &HashSet<Hash>) -> Result<usize, DBError> {
+        let mut odb = self.odb.lock().unwrap();
+        let objects_removed = odb.retain(live)?;
+        odb.sync_all()?;
+        Ok(objects_removed)
+    }
+
     /// Persist to disk the [Tx] result into the [MessageLog].
     ///
     /// Returns `Some(n_bytes_written)` if `commit_result` was persisted, `None` if it doesn't have bytes to write.
@@ -96,18 +189,29 @@ impl CommitLog {
         }
 
         let mut unwritten_commit = self.unwritten_commit.lock().unwrap();
-        let writes = tx_data
-            .records
-            .iter()
-            .map(|record| Write {
-                operation: match record.op {
-                    TxOp::Insert(_) => Operation::Insert,
-                    TxOp::Delete => Operation::Delete,
-                },
-                set_id: record.table_id.0,
+        let mut writes = Vec::with_capacity(tx_data.records.len());
+        for record in &tx_data.records {
+            let set_id = record.table_id.0;
+            let operation = match &record.op {
+                TxOp::Insert(_) => Operation::Insert,
+                TxOp::Delete => Operation::Delete,
+                // The commit log has no notion of updates,
+                // so an update is recorded as a delete of the old row followed by an insert.
+                TxOp::Update { old_key, .. } => {
+                    writes.push(Write {
+                        operation: Operation::Delete,
+                        set_id,
+                        data_key: *old_key,
+                    });
+                    Operation::Insert
+                }
+            };
+            writes.push(Write {
+                operation,
+                set_id,
                 data_key: record.key,
-            })
-            .collect();
+            });
+        }
         let transaction = Transaction { writes };
         unwritten_commit.transactions.push(Arc::new(transaction));
 
@@ -118,7 +222,7 @@ impl CommitLog {
                 let mut guard = self.odb.lock().unwrap();
                 for record in &tx_data.records {
                     match &record.op {
-                        TxOp::Insert(bytes) => {
+                        TxOp::Insert(bytes) | TxOp::Update { bytes, .. } => {
                             guard.add(Vec::clone(bytes));
                         }
                         TxOp::Delete => continue,
@@ -127,6 +231,7 @@ impl CommitLog {
             }
 
             let mut bytes = Vec::new();
+            unwritten_commit.timestamp = Some(Timestamp::now());
             unwritten_commit.encode(&mut bytes);
 
             unwritten_commit.parent_commit_hash = Some(hash_bytes(&bytes));
@@ -168,6 +273,19 @@ impl CommitLogView {
         }
     }
 
+    /// Obtain an iterator over the closed message log segments containing
+    /// only messages older than `offset`.
+    ///
+    /// See [`MessageLog::segments_before`] for more information.
+    pub fn message_log_segments_before(&self, offset: u64) -> message_log::Segments {
+        if let Some(mlog) = &self.mlog {
+            let mlog = mlog.lock().unwrap();
+            mlog.segments_before(offset)
+        } else {
+            message_log::Segments::empty()
+        }
+    }
+
     /// Obtain an iterator over the [`Commit`]s in the log.
     ///
     /// The iterator represents a snapshot of the log.
@@ -323,6 +441,7 @@ mod tests {
             parent_commit_hash: None,
             commit_offset: 0,
             min_tx_offset: 0,
+            timestamp: None,
             transactions: vec![Arc::new(tx)],
         };
         let mut commit_bytes = Vec::new();
@@ -345,6 +464,7 @@ mod tests {
                 parent_commit_hash: None,
                 commit_offset: 0,
                 min_tx_offset: 0,
+                timestamp: None,
                 transactions: Vec::new(),
             },
             true, // fsync
@@ -0,0 +1,109 @@
+//! The predicates of check constraints.
+//!
+//! A predicate is compiled once against its table, like the `WHERE` clause of a query,
+//! when the constraint is created or the schema of the table is loaded,
+//! and is then evaluated over every row written to the table.
+
+use crate::db::datastore::traits::{CheckSchema, TableSchema};
+use crate::error::ConstraintError;
+use crate::sql::ast::compile_standalone_where;
+use crate::sql::params::SqlParams;
+use spacetimedb_lib::relation::{Header, RelValueRef};
+use spacetimedb_sats::ProductValue;
+use spacetimedb_vm::expr::ColumnOp;
+
+/// A check constraint, along with its predicate compiled over the columns of its table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompiledCheck {
+    pub(crate) check: CheckSchema,
+    pub(crate) predicate: ColumnOp,
+    /// The header of the rows of the table, which the fields of `predicate` are resolved against.
+    header: Header,
+}
+
+impl CompiledCheck {
+    pub fn new(table: &TableSchema, check: CheckSchema) -> Result<Self, ConstraintError> {
+        let predicate = compile_standalone_where(table, &SqlParams::None, &check.expr)
+            .map_err(|e| ConstraintError::InvalidCheck(check.constraint_name.clone(), e.to_string()))?
+            .clause;
+        Ok(Self {
+            check,
+            predicate,
+            header: table.into(),
+        })
+    }
+
+    /// Returns whether `row` satisfies the predicate.
+    pub fn eval(&self, row: &ProductValue) -> bool {
+        // A predicate that can't be evaluated over the row is not satisfied.
+        matches!(self.predicate.compare(RelValueRef::new(row), &self.header), Ok(true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::datastore::traits::ColumnSchema;
+    use spacetimedb_lib::auth::{StAccess, StTableType};
+    use spacetimedb_sats::AlgebraicType;
+
+    fn table() -> TableSchema {
+        let columns = [
+            ("id", AlgebraicType::U32),
+            ("name", AlgebraicType::String),
+            ("score", AlgebraicType::I64),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(col_id, (col_name, col_type))| ColumnSchema {
+            table_id: 0.into(),
+            col_id: col_id.into(),
+            col_name: col_name.into(),
+            col_type,
+            is_autoinc: false,
+        })
+        .collect();
+        TableSchema {
+            table_id: 0.into(),
+            table_name: "Foo".into(),
+            columns,
+            indexes: vec![],
+            constraints: vec![],
+            foreign_keys: vec![],
+            checks: vec![],
+            table_type: StTableType::User,
+            table_access: StAccess::Public,
+        }
+    }
+
+    fn compile(sql: &str) -> Result<CompiledCheck, ConstraintError> {
+        let check = CheckSchema {
+            constraint_id: 0.into(),
+            constraint_name: "check".into(),
+            table_id: 0.into(),
+            expr: sql.into(),
+        };
+        CompiledCheck::new(&table(), check)
+    }
+
+    #[test]
+    fn test_eval() -> Result<(), ConstraintError> {
+        let row = || spacetimedb_sats::product![3u32, "Foo", -5i64];
+        assert!(compile("id >= 1 AND name <> ''")?.eval(&row()));
+        assert!(!compile("id > 3 OR name = 'Bar'")?.eval(&row()));
+        assert!(compile("id BETWEEN 1 AND 3")?.eval(&row()));
+        assert!(!compile("id NOT BETWEEN 1 AND 3")?.eval(&row()));
+        assert!(compile("name IN ('Bar', 'Foo')")?.eval(&row()));
+        assert!(compile("name LIKE 'F%'")?.eval(&row()));
+        assert!(!compile("score >= 0")?.eval(&row()));
+        assert!(compile("Foo.id = 3")?.eval(&row()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        for sql in ["height >= 18", "id IS NULL", "id > 1 id", "id * 2 > 1"] {
+            assert!(compile(sql).is_err(), "{sql}");
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use super::RowId;
-use crate::{db::datastore::traits::IndexSchema, error::DBError};
+use crate::error::DBError;
 use nonempty::NonEmpty;
 use spacetimedb_lib::{data_key::ToDataKey, DataKey};
 use spacetimedb_primitives::{ColId, IndexId, TableId};
@@ -61,7 +61,34 @@ impl Iterator for BTreeIndexIter<'_> {
 /// An iterator for the rows that match a value [AlgebraicValue] on the
 /// [BTreeIndex]
 pub struct BTreeIndexRangeIter<'a> {
-    range_iter: btree_set::Range<'a, IndexKey>,
+    /// `None` when the range is empty.
+    range_iter: Option<btree_set::Range<'a, IndexKey>>,
+    /// Set when seeking on a prefix of the indexed columns,
+    /// in which case the keys visited by `range_iter` must be filtered further.
+    prefix: Option<PrefixRange>,
+}
+
+impl<'a> BTreeIndexRangeIter<'a> {
+    /// Advances the iterator, returning the next `RowId` along with its value in the index.
+    pub(crate) fn next_entry(&mut self) -> Option<(&'a AlgebraicValue, &'a RowId)> {
+        let range_iter = self.range_iter.as_mut()?;
+        let Some(prefix) = &self.prefix else {
+            return range_iter.next().map(|key| (&key.value, &key.row_id));
+        };
+        for key in range_iter {
+            let AlgebraicValue::Product(value) = &key.value else {
+                unreachable!("keys of a multi-column index are products")
+            };
+            let value = &value.elements[..prefix.len];
+            if prefix.is_past_end(value) {
+                return None;
+            }
+            if prefix.contains(value) {
+                return Some((&key.value, &key.row_id));
+            }
+        }
+        None
+    }
 }
 
 impl<'a> Iterator for BTreeIndexRangeIter<'a> {
@@ -69,7 +96,54 @@ impl<'a> Iterator for BTreeIndexRangeIter<'a> {
 
     #[tracing::instrument(skip_all)]
     fn next(&mut self) -> Option<Self::Item> {
-        self.range_iter.next().map(|key| &key.row_id)
+        self.next_entry().map(|(_, row_id)| row_id)
+    }
+}
+
+/// A range of values of the leading `len` columns of a multi-column [BTreeIndex].
+///
+/// As keys are ordered lexicographically, the keys whose prefix lies within
+/// the range are contiguous, save for those equal to an excluded start bound.
+struct PrefixRange {
+    len: usize,
+    start: Bound<Vec<AlgebraicValue>>,
+    end: Bound<Vec<AlgebraicValue>>,
+}
+
+impl PrefixRange {
+    /// Returns the endpoints of `range` as sequences of `len` column values.
+    fn new(len: usize, range: &impl RangeBounds<AlgebraicValue>) -> Self {
+        let elements = |value: &AlgebraicValue| match value {
+            AlgebraicValue::Product(value) if len > 1 => value.elements.clone(),
+            value => vec![value.clone()],
+        };
+        let map = |bound| match bound {
+            Bound::Included(x) => Bound::Included(elements(x)),
+            Bound::Excluded(x) => Bound::Excluded(elements(x)),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        Self {
+            len,
+            start: map(range.start_bound()),
+            end: map(range.end_bound()),
+        }
+    }
+
+    fn contains(&self, prefix: &[AlgebraicValue]) -> bool {
+        let start = match &self.start {
+            Bound::Included(start) => &start[..] <= prefix,
+            Bound::Excluded(start) => &start[..] < prefix,
+            Bound::Unbounded => true,
+        };
+        start && !self.is_past_end(prefix)
+    }
+
+    fn is_past_end(&self, prefix: &[AlgebraicValue]) -> bool {
+        match &self.end {
+            Bound::Included(end) => prefix > &end[..],
+            Bound::Excluded(end) => prefix >= &end[..],
+            Bound::Unbounded => false,
+        }
     }
 }
 
@@ -119,6 +193,16 @@ impl BTreeIndex {
         self.idx.remove(&key);
     }
 
+    /// Points the entry of `row_id` under `col_value` to `new_row_id`.
+    ///
+    /// As the keys are ordered by `RowId` after the value, the entry is moved within the value.
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn replace_row_id(&mut self, col_value: &AlgebraicValue, row_id: &RowId, new_row_id: RowId) {
+        if self.idx.remove(&IndexKey::from_row(col_value, row_id.0)) {
+            self.idx.insert(IndexKey::from_row(col_value, new_row_id.0));
+        }
+    }
+
     #[tracing::instrument(skip_all)]
     pub(crate) fn violates_unique_constraint(&self, row: &ProductValue) -> bool {
         if self.is_unique {
@@ -152,15 +236,57 @@ impl BTreeIndex {
     /// that fall within the specified `range`.
     #[tracing::instrument(skip_all)]
     pub(crate) fn seek<'a>(&'a self, range: &impl RangeBounds<AlgebraicValue>) -> BTreeIndexRangeIter<'a> {
-        let map = |bound, datakey| match bound {
-            Bound::Included(x) => Bound::Included(IndexKey::from_row(x, datakey)),
-            Bound::Excluded(x) => Bound::Excluded(IndexKey::from_row(x, datakey)),
+        // An included bound takes in every row with the value, an excluded one none of them.
+        let start = match range.start_bound() {
+            Bound::Included(x) => Bound::Included(IndexKey::from_row(x, DataKey::min_datakey())),
+            Bound::Excluded(x) => Bound::Excluded(IndexKey::from_row(x, DataKey::max_datakey())),
             Bound::Unbounded => Bound::Unbounded,
         };
-        let start = map(range.start_bound(), DataKey::min_datakey());
-        let end = map(range.end_bound(), DataKey::max_datakey());
+        let end = match range.end_bound() {
+            Bound::Included(x) => Bound::Included(IndexKey::from_row(x, DataKey::max_datakey())),
+            Bound::Excluded(x) => Bound::Excluded(IndexKey::from_row(x, DataKey::min_datakey())),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        // `BTreeSet::range` panics when `start` lies after `end`.
+        let is_empty = match (&start, &end) {
+            (Bound::Included(start), Bound::Included(end)) => start > end,
+            (Bound::Included(start) | Bound::Excluded(start), Bound::Included(end) | Bound::Excluded(end)) => {
+                start >= end
+            }
+            _ => false,
+        };
         BTreeIndexRangeIter {
-            range_iter: self.idx.range((start, end)),
+            range_iter: (!is_empty).then(|| self.idx.range((start, end))),
+            prefix: None,
+        }
+    }
+
+    /// Returns an iterator over the [BTreeIndex] that yields all the `RowId`s
+    /// where the values of the first `prefix_len` indexed columns fall within `range`.
+    ///
+    /// The endpoints of `range` are single values when `prefix_len == 1`,
+    /// and products of `prefix_len` values otherwise,
+    /// just like the keys of an index on those columns would be.
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn seek_prefix<'a>(
+        &'a self,
+        prefix_len: usize,
+        range: &impl RangeBounds<AlgebraicValue>,
+    ) -> BTreeIndexRangeIter<'a> {
+        debug_assert!(prefix_len < self.cols.len());
+        let prefix = PrefixRange::new(prefix_len, range);
+        // A product sorts before all longer products it is a prefix of,
+        // so this is the first key whose prefix may lie within `range`.
+        let start = match &prefix.start {
+            Bound::Included(start) | Bound::Excluded(start) => Bound::Included(IndexKey::from_row(
+                &AlgebraicValue::Product(ProductValue::new(start)),
+                DataKey::min_datakey(),
+            )),
+            Bound::Unbounded => Bound::Unbounded,
+        };
+        BTreeIndexRangeIter {
+            range_iter: Some(self.idx.range((start, Bound::Unbounded))),
+            prefix: Some(prefix),
         }
     }
 
@@ -173,15 +299,3 @@ impl BTreeIndex {
         Ok(())
     }
 }
-
-impl From<&BTreeIndex> for IndexSchema {
-    fn from(x: &BTreeIndex) -> Self {
-        IndexSchema {
-            index_id: x.index_id,
-            table_id: x.table_id,
-            cols: x.cols.clone(),
-            is_unique: x.is_unique,
-            index_name: x.name.clone(),
-        }
-    }
-}
@@ -0,0 +1,136 @@
+use super::RowId;
+use crate::error::DBError;
+use nonempty::NonEmpty;
+use spacetimedb_lib::data_key::ToDataKey;
+use spacetimedb_primitives::{ColId, IndexId, TableId};
+use spacetimedb_sats::{AlgebraicValue, ProductValue};
+use std::collections::{btree_set, BTreeSet, HashMap};
+
+/// An iterator for the rows that match a value [AlgebraicValue] on the
+/// [HashIndex]
+pub struct HashIndexIter<'a> {
+    entry: Option<(&'a AlgebraicValue, btree_set::Iter<'a, RowId>)>,
+}
+
+impl<'a> HashIndexIter<'a> {
+    /// Advances the iterator, returning the next `RowId` along with its value in the index.
+    pub(crate) fn next_entry(&mut self) -> Option<(&'a AlgebraicValue, &'a RowId)> {
+        let (value, row_ids) = self.entry.as_mut()?;
+        row_ids.next().map(|row_id| (*value, row_id))
+    }
+}
+
+impl<'a> Iterator for HashIndexIter<'a> {
+    type Item = &'a RowId;
+
+    #[tracing::instrument(skip_all)]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().map(|(_, row_id)| row_id)
+    }
+}
+
+/// An index that only supports equality lookups.
+///
+/// Unlike a [super::btree_index::BTreeIndex], each distinct value is stored once,
+/// along with the `RowId`s of all the rows that have it,
+/// and seeking a value doesn't pay for keeping the values ordered.
+pub(crate) struct HashIndex {
+    pub(crate) index_id: IndexId,
+    pub(crate) table_id: TableId,
+    pub(crate) cols: NonEmpty<ColId>,
+    pub(crate) name: String,
+    pub(crate) is_unique: bool,
+    idx: HashMap<AlgebraicValue, BTreeSet<RowId>>,
+}
+
+impl HashIndex {
+    pub(crate) fn new(
+        index_id: IndexId,
+        table_id: TableId,
+        cols: NonEmpty<ColId>,
+        name: String,
+        is_unique: bool,
+    ) -> Self {
+        Self {
+            index_id,
+            table_id,
+            cols,
+            name,
+            is_unique,
+            idx: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn get_fields(&self, row: &ProductValue) -> Result<AlgebraicValue, DBError> {
+        let fields = row.project_not_empty(&self.cols)?;
+        Ok(fields)
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn insert(&mut self, row: &ProductValue) -> Result<(), DBError> {
+        let col_value = self.get_fields(row)?;
+        let row_id = RowId(row.to_data_key());
+        self.idx.entry(col_value).or_default().insert(row_id);
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn delete(&mut self, col_value: &AlgebraicValue, row_id: &RowId) {
+        let Some(row_ids) = self.idx.get_mut(col_value) else {
+            return;
+        };
+        row_ids.remove(row_id);
+        if row_ids.is_empty() {
+            self.idx.remove(col_value);
+        }
+    }
+
+    /// Points the entry of `row_id` under `col_value` to `new_row_id`,
+    /// leaving the value in place.
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn replace_row_id(&mut self, col_value: &AlgebraicValue, row_id: &RowId, new_row_id: RowId) {
+        if let Some(row_ids) = self.idx.get_mut(col_value) {
+            if row_ids.remove(row_id) {
+                row_ids.insert(new_row_id);
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn violates_unique_constraint(&self, row: &ProductValue) -> bool {
+        if self.is_unique {
+            let col_value = self.get_fields(row).unwrap();
+            return self.idx.contains_key(&col_value);
+        }
+        false
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn get_rows_that_violate_unique_constraint<'a>(
+        &'a self,
+        value: &AlgebraicValue,
+    ) -> Option<HashIndexIter<'a>> {
+        self.is_unique.then(|| self.seek(value))
+    }
+
+    /// Returns an iterator over the [HashIndex] that yields all the `RowId`s
+    /// whose value is equal to `value`.
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn seek<'a>(&'a self, value: &AlgebraicValue) -> HashIndexIter<'a> {
+        HashIndexIter {
+            entry: self
+                .idx
+                .get_key_value(value)
+                .map(|(value, row_ids)| (value, row_ids.iter())),
+        }
+    }
+
+    /// Construct the [HashIndex] from the rows.
+    #[tracing::instrument(skip_all)]
+    pub(crate) fn build_from_rows<'a>(&mut self, rows: impl Iterator<Item = &'a ProductValue>) -> Result<(), DBError> {
+        for row in rows {
+            self.insert(row)?;
+        }
+        Ok(())
+    }
+}
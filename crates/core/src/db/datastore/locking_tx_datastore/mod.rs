@@ -1,16 +1,20 @@
 mod btree_index;
+mod hash_index;
 mod sequence;
 mod table;
+mod table_index;
 
 use self::{
-    btree_index::{BTreeIndex, BTreeIndexRangeIter},
     sequence::Sequence,
     table::Table,
+    table_index::{as_point, IndexSeekEntries, IndexSeekIter, TableIndex},
 };
+use bytes::Bytes;
 use nonempty::NonEmpty;
 use std::{
     borrow::Cow,
-    collections::{BTreeMap, BTreeSet, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    iter::Peekable,
     ops::{Deref, RangeBounds},
     sync::Arc,
     vec,
@@ -23,14 +27,18 @@ use super::{
         ST_SEQUENCES_ID, ST_SEQUENCE_ROW_TYPE, ST_TABLES_ID, ST_TABLE_ROW_TYPE, TABLE_ID_SEQUENCE_ID, WASM_MODULE,
     },
     traits::{
-        self, DataRow, IndexDef, IndexSchema, MutTx, MutTxDatastore, SequenceDef, TableDef, TableSchema, TxData,
-        TxDatastore,
+        self, CheckDef, CheckSchema, ColumnDef, DataRow, ForeignKeyDef, ForeignKeySchema, IndexDef, IndexSchema, MutTx,
+        MutTxDatastore, SequenceDef, TableDef, TableSchema, TxData, TxDatastore,
     },
 };
 
+use crate::db::datastore::check::CompiledCheck;
 use crate::db::datastore::system_tables::{
-    st_constraints_schema, st_module_schema, table_name_is_system, StConstraintRow, SystemTables,
-    CONSTRAINT_ID_SEQUENCE_ID, ST_CONSTRAINTS_ID, ST_CONSTRAINT_ROW_TYPE, ST_MODULE_ROW_TYPE,
+    decode_legacy_row, st_constraints_schema, st_module_schema, st_row_policies_schema, st_scheduled_schema,
+    table_id_is_system, table_name_is_system, StConstraintFields, StConstraintRow, StForeignKey, StRowPolicyFields,
+    StRowPolicyRow, SystemTables, CONSTRAINT_ID_SEQUENCE_ID, SCHEDULED_ID_SEQUENCE_ID, ST_CONSTRAINTS_ID,
+    ST_CONSTRAINT_ROW_TYPE, ST_MODULE_ROW_TYPE, ST_RESERVED_IDS_START, ST_ROW_POLICIES_ID, ST_ROW_POLICY_ROW_TYPE,
+    ST_SCHEDULED_ID, ST_SCHEDULED_ROW_TYPE,
 };
 use crate::{
     db::datastore::traits::{TxOp, TxRecord},
@@ -39,10 +47,14 @@ use crate::{
             system_tables::{st_columns_schema, st_indexes_schema, st_sequences_schema, st_table_schema},
             traits::ColumnSchema,
         },
-        messages::{transaction::Transaction, write::Operation},
+        messages::{
+            transaction::Transaction,
+            write::{Operation, Write},
+        },
         ostorage::ObjectDB,
+        snapshot::Snapshot,
     },
-    error::{DBError, IndexError, TableError},
+    error::{ConstraintError, DBError, IndexError, TableError},
 };
 
 use anyhow::anyhow;
@@ -51,7 +63,7 @@ use spacetimedb_lib::{
     auth::{StAccess, StTableType},
     data_key::ToDataKey,
     relation::RelValue,
-    DataKey, Hash,
+    ColumnIndexAttribute, DataKey, Hash, IndexType, ReferentialAction,
 };
 use spacetimedb_primitives::{ColId, IndexId, SequenceId, TableId};
 use spacetimedb_sats::{AlgebraicType, AlgebraicValue, ProductType, ProductValue};
@@ -77,6 +89,8 @@ pub enum SequenceError {
     NotInteger { col: String, found: AlgebraicType },
     #[error("Sequence ID `{0}` still had no values left after allocation.")]
     UnableToAllocate(SequenceId),
+    #[error("Sequence ID `{0}` reached the ids reserved for the system tables.")]
+    Reserved(SequenceId),
 }
 
 const SEQUENCE_PREALLOCATION_AMOUNT: i128 = 4_096;
@@ -117,11 +131,16 @@ pub struct MutTxId {
 
 struct CommittedState {
     tables: HashMap<TableId, Table>,
+    /// Counts the committed transactions that changed the schema, see [TxState::changes_schema].
+    schema_version: u64,
 }
 
 impl CommittedState {
     fn new() -> Self {
-        Self { tables: HashMap::new() }
+        Self {
+            tables: HashMap::new(),
+            schema_version: 0,
+        }
     }
 
     fn get_or_create_table(&mut self, table_id: TableId, row_type: &ProductType, schema: &TableSchema) -> &mut Table {
@@ -136,35 +155,59 @@ impl CommittedState {
 
     fn merge(&mut self, tx_state: TxState, memory: BTreeMap<DataKey, Arc<Vec<u8>>>) -> TxData {
         let mut tx_data = TxData { records: vec![] };
-        for (table_id, table) in tx_state.insert_tables {
+        let TxState {
+            insert_tables,
+            mut delete_tables,
+            mut updates,
+            ..
+        } = tx_state;
+        for (table_id, table) in insert_tables {
             let commit_table = self.get_or_create_table(table_id, &table.row_type, &table.schema);
             // The schema may have been modified in the transaction.
             commit_table.row_type = table.row_type;
             commit_table.schema = table.schema;
 
+            let mut updates = updates.remove(&table_id).unwrap_or_default();
+            let mut deletes = delete_tables.get_mut(&table_id);
+
             tx_data.records.extend(table.rows.into_iter().map(|(row_id, row)| {
-                commit_table.insert(row_id, row.clone());
-                let pv = row;
                 let bytes = match row_id.0 {
                     DataKey::Data(data) => Arc::new(data.to_vec()),
                     DataKey::Hash(_) => memory.get(&row_id.0).unwrap().clone(),
                 };
+                // If the row replaced a row which is still to be deleted,
+                // update that row in place and report both as a single update.
+                let old = updates
+                    .remove(&row_id)
+                    .filter(|old_id| deletes.as_mut().map_or(false, |deletes| deletes.remove(old_id)))
+                    .and_then(|old_id| Some((old_id, commit_table.update(&old_id, row_id, row.clone())?)));
+                if old.is_none() {
+                    commit_table.insert(row_id, row.clone());
+                }
+                let op = match old {
+                    Some((old_id, old_row)) => TxOp::Update {
+                        bytes,
+                        old_key: old_id.0,
+                        old_row,
+                    },
+                    None => TxOp::Insert(bytes),
+                };
                 TxRecord {
-                    op: TxOp::Insert(bytes),
+                    op,
                     table_id,
                     key: row_id.0,
-                    product_value: pv,
+                    product_value: row,
                 }
             }));
 
             // Add all newly created indexes to the committed state
             for (_, index) in table.indexes {
-                if !commit_table.indexes.contains_key(&index.cols) {
+                if !commit_table.indexes.contains_key(index.cols()) {
                     commit_table.insert_index(index);
                 }
             }
         }
-        for (table_id, row_ids) in tx_state.delete_tables {
+        for (table_id, row_ids) in delete_tables {
             // NOTE: it is possible that the delete_tables contain a row in a table
             // that was created in the current transaction and not committed yet.
             // These delete row operations should be skipped here. e.g.
@@ -195,7 +238,7 @@ impl CommittedState {
         table_id: &TableId,
         cols: &NonEmpty<ColId>,
         range: &impl RangeBounds<AlgebraicValue>,
-    ) -> Option<BTreeIndexRangeIter<'a>> {
+    ) -> Option<IndexSeekIter<'a>> {
         if let Some(table) = self.tables.get(table_id) {
             table.index_seek(cols, range)
         } else {
@@ -235,6 +278,15 @@ struct TxState {
     /// For each table,  additions have
     insert_tables: BTreeMap<TableId, Table>,
     delete_tables: BTreeMap<TableId, BTreeSet<RowId>>,
+    /// For each table, maps the rows inserted by an update in this transaction
+    /// to the committed rows they replace, so the two can be reported as a single update.
+    updates: BTreeMap<TableId, BTreeMap<RowId, RowId>>,
+    /// The committed tables dropped in this transaction,
+    /// which are restored to the `CommittedState` if it is rolled back.
+    dropped_tables: BTreeMap<TableId, Table>,
+    /// The sequences dropped in this transaction,
+    /// which are restored to the `SequencesState` if it is rolled back.
+    dropped_sequences: Vec<(SequenceId, Sequence)>,
 }
 
 /// Represents whether a row has been previously committed, inserted
@@ -258,6 +310,9 @@ impl TxState {
         Self {
             insert_tables: BTreeMap::new(),
             delete_tables: BTreeMap::new(),
+            updates: BTreeMap::new(),
+            dropped_tables: BTreeMap::new(),
+            dropped_sequences: Vec::new(),
         }
     }
 
@@ -296,8 +351,24 @@ impl TxState {
         self.delete_tables.entry(table_id).or_insert_with(BTreeSet::new)
     }
 
+    /// Returns whether this transaction writes to the system tables describing the schema,
+    /// which are the ones the plan of a query depends on.
+    pub fn changes_schema(&self) -> bool {
+        const SCHEMA_TABLES: [TableId; 5] = [
+            ST_TABLES_ID,
+            ST_COLUMNS_ID,
+            ST_INDEXES_ID,
+            ST_CONSTRAINTS_ID,
+            ST_ROW_POLICIES_ID,
+        ];
+        !self.dropped_tables.is_empty()
+            || SCHEMA_TABLES
+                .iter()
+                .any(|table_id| self.insert_tables.contains_key(table_id) || self.delete_tables.contains_key(table_id))
+    }
+
     /// When there's an index on `cols`,
-    /// returns an iterator over the [BTreeIndex] that yields all the `RowId`s
+    /// returns an iterator over the [TableIndex] that yields all the `RowId`s
     /// that match the specified `value` in the indexed column.
     ///
     /// Matching is defined by `Ord for AlgebraicValue`.
@@ -309,7 +380,7 @@ impl TxState {
         table_id: &TableId,
         cols: &NonEmpty<ColId>,
         range: &impl RangeBounds<AlgebraicValue>,
-    ) -> Option<BTreeIndexRangeIter<'a>> {
+    ) -> Option<IndexSeekIter<'a>> {
         self.insert_tables.get(table_id)?.index_seek(cols, range)
     }
 }
@@ -401,7 +472,12 @@ impl Inner {
                         INDEX_ID_SEQUENCE_ID,
                     ),
                     ST_SEQUENCES_ID => (SystemTables::total_sequences() as i128, SEQUENCE_ID_SEQUENCE_ID),
-                    ST_CONSTRAINTS_ID => (SystemTables::total_constraints() as i128, CONSTRAINT_ID_SEQUENCE_ID),
+                    // The constraints of the system tables share the ids of the indexes they create.
+                    ST_CONSTRAINTS_ID => (
+                        (SystemTables::total_indexes() + SystemTables::total_constraints_indexes()) as i128,
+                        CONSTRAINT_ID_SEQUENCE_ID,
+                    ),
+                    ST_SCHEDULED_ID => (1, SCHEDULED_ID_SEQUENCE_ID),
                     _ => unreachable!(),
                 };
                 let st_sequences = self.committed_state.get_or_create_table(
@@ -446,6 +522,8 @@ impl Inner {
                 kind: constraint.kind,
                 table_id,
                 columns: constraint.columns,
+                foreign_key: None,
+                check: None,
             };
             let row = ProductValue::from(row);
             let data_key = row.to_data_key();
@@ -459,6 +537,7 @@ impl Inner {
                     cols: NonEmpty::new(first_col_id),
                     index_name,
                     is_unique: true,
+                    index_type: IndexType::BTree,
                 },
                 x if x.is_indexed() => IndexSchema {
                     index_id: constraint.constraint_id,
@@ -466,6 +545,7 @@ impl Inner {
                     cols: NonEmpty::new(first_col_id),
                     index_name,
                     is_unique: false,
+                    index_type: IndexType::BTree,
                 },
                 x => panic!("Adding constraint of kind `{x:?}` is not supported yet."),
             }
@@ -482,6 +562,7 @@ impl Inner {
                 cols: index.cols,
                 index_name: index.index_name,
                 is_unique: index.is_unique,
+                index_type: index.index_type,
             };
             let row = ProductValue::from(row);
             let data_key = row.to_data_key();
@@ -497,11 +578,13 @@ impl Inner {
             let sequence = StSequenceRow::try_from(row)?;
             // TODO: The system tables have initialized their value already, but this is wrong:
             // If we exceed  `SEQUENCE_PREALLOCATION_AMOUNT` we will get a unique violation
-            let is_system_table = self
-                .committed_state
-                .tables
-                .get(&sequence.table_id)
-                .map_or(false, |x| x.schema.table_type == StTableType::System);
+            // The module keeps scheduling reducers, so `st_scheduled` recovers its allocation like a user table.
+            let is_system_table = sequence.table_id != ST_SCHEDULED_ID
+                && self
+                    .committed_state
+                    .tables
+                    .get(&sequence.table_id)
+                    .map_or(false, |x| x.schema.table_type == StTableType::System);
 
             let schema = sequence.to_owned().into();
 
@@ -522,7 +605,8 @@ impl Inner {
         for row in rows {
             let index_row = StIndexRow::try_from(&row)?;
             let table = self.committed_state.get_table(&index_row.table_id).unwrap();
-            let mut index = BTreeIndex::new(
+            let mut index = TableIndex::new(
+                index_row.index_type,
                 index_row.index_id,
                 index_row.table_id,
                 index_row.cols.clone(),
@@ -535,6 +619,27 @@ impl Inner {
         Ok(())
     }
 
+    /// Caches the foreign keys and the compiled check constraints in the schemas of the tables they concern,
+    /// as these were only read once when the tables were created in memory.
+    fn build_constraints(&mut self) -> super::Result<()> {
+        let foreign_keys = self.foreign_keys()?;
+        let checks = self.checks()?;
+        for table in self.committed_state.tables.values_mut() {
+            let table_id = table.schema.table_id;
+            table.schema.foreign_keys = foreign_keys
+                .iter()
+                .filter(|fk| fk.table_id == table_id || fk.ref_table_id == table_id)
+                .cloned()
+                .collect();
+            table.schema.checks = checks
+                .iter()
+                .filter(|check| check.table_id == table_id)
+                .map(|check| CompiledCheck::new(&table.schema, check.clone()))
+                .collect::<Result<_, _>>()?;
+        }
+        Ok(())
+    }
+
     /// After replaying all old transactions, tables which have rows will
     /// have been created in memory, but tables with no rows will not have
     /// been created. This function ensures that they are created.
@@ -665,7 +770,10 @@ impl Inner {
             .data;
         let old_seq_row_id = RowId(old_seq_row.to_data_key());
         self.delete(&ST_SEQUENCES_ID, [old_seq_row_id]);
-        self.sequence_state.sequences.remove(&seq_id);
+        if let Some(sequence) = self.sequence_state.sequences.remove(&seq_id) {
+            let tx_state = self.tx_state.as_mut().unwrap();
+            tx_state.dropped_sequences.push((seq_id, sequence));
+        }
         Ok(())
     }
 
@@ -852,22 +960,70 @@ impl Inner {
                 index_name: el.index_name.into(),
                 is_unique: el.is_unique,
                 index_id: el.index_id,
+                index_type: el.index_type,
             };
             indexes.push(index_schema);
         }
 
-        Ok(Cow::Owned(TableSchema {
+        let mut schema = TableSchema {
             columns,
             table_id,
             table_name,
             indexes,
             constraints: vec![],
+            foreign_keys: vec![],
+            checks: vec![],
             table_type: el.table_type,
             table_access: el.table_access,
-        }))
+        };
+
+        // Look up the foreign keys of the table, and those referencing it,
+        // and compile its check constraints against the schema.
+        // `st_constraints` doesn't exist yet while bootstrapping.
+        if self.table_exists(&ST_CONSTRAINTS_ID) {
+            schema.foreign_keys = self
+                .foreign_keys()?
+                .into_iter()
+                .filter(|fk| fk.table_id == table_id || fk.ref_table_id == table_id)
+                .collect();
+            schema.checks = self
+                .checks()?
+                .into_iter()
+                .filter(|check| check.table_id == table_id)
+                .map(|check| CompiledCheck::new(&schema, check))
+                .collect::<Result<_, _>>()?;
+        }
+
+        Ok(Cow::Owned(schema))
     }
 
     fn drop_table(&mut self, table_id: TableId) -> super::Result<()> {
+        // Drop the foreign keys of the table, and those referencing it.
+        self.schema_for_table(table_id)?
+            .foreign_keys
+            .iter()
+            .map(|fk| fk.constraint_id)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .try_for_each(|constraint_id| self.drop_constraint(constraint_id))?;
+        self.schema_for_table(table_id)?
+            .checks
+            .iter()
+            .map(|compiled| compiled.check.constraint_id)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .try_for_each(|constraint_id| self.drop_constraint(constraint_id))?;
+        self.set_row_policy(table_id, None)?;
+
+        // Delete the table and its rows and indexes from memory,
+        // keeping the committed table around in case the transaction is rolled back.
+        let tx_state = self.tx_state.as_mut().unwrap();
+        if let Some(table) = self.committed_state.tables.remove(&table_id) {
+            tx_state.dropped_tables.insert(table_id, table);
+        }
+        // Otherwise the commit would create the table again.
+        tx_state.insert_tables.remove(&table_id);
+
         // First drop the tables indexes.
         const ST_INDEXES_TABLE_ID_COL: ColId = ColId(1);
         self.iter_by_col_eq(&ST_INDEXES_ID, ST_INDEXES_TABLE_ID_COL, table_id.into())?
@@ -889,11 +1045,6 @@ impl Inner {
 
         // Remove the table from st_tables.
         self.drop_table_from_st_tables(table_id)?;
-
-        // Delete the table and its rows and indexes from memory.
-        // TODO: This needs to not remove it from the committed state, because it can still be rolled back.
-        // We will have to store the deletion in the TxState and then apply it to the CommittedState in commit.
-        self.committed_state.tables.remove(&table_id);
         Ok(())
     }
 
@@ -918,6 +1069,112 @@ impl Inner {
         Ok(())
     }
 
+    fn rename_index(&mut self, index_id: IndexId, new_name: &str) -> super::Result<()> {
+        // Update the index's name in st_indexes.
+        const ST_INDEXES_INDEX_ID_COL: ColId = ColId(0);
+        let row = self
+            .iter_by_col_eq(&ST_INDEXES_ID, ST_INDEXES_INDEX_ID_COL, index_id.into())?
+            .last()
+            .ok_or_else(|| IndexError::NotFound(index_id))?;
+        let row_id = RowId(*row.id);
+        let mut el = StIndexRow::try_from(row.view())?;
+        el.index_name = new_name;
+        let new_row = el.to_owned().into();
+
+        self.delete(&ST_INDEXES_ID, [row_id]);
+        self.insert(ST_INDEXES_ID, new_row)?;
+        Ok(())
+    }
+
+    fn rename_sequence(&mut self, seq_id: SequenceId, new_name: &str) -> super::Result<()> {
+        // Update the sequence's name in st_sequences.
+        const ST_SEQUENCES_SEQUENCE_ID_COL: ColId = ColId(0);
+        let row = self
+            .iter_by_col_eq(&ST_SEQUENCES_ID, ST_SEQUENCES_SEQUENCE_ID_COL, seq_id.into())?
+            .last()
+            .ok_or_else(|| SequenceError::NotFound(seq_id))?;
+        let row_id = RowId(*row.id);
+        let mut el = StSequenceRow::try_from(row.view())?;
+        el.sequence_name = new_name;
+        let new_row = el.to_owned().into();
+
+        self.delete(&ST_SEQUENCES_ID, [row_id]);
+        self.insert(ST_SEQUENCES_ID, new_row)?;
+        Ok(())
+    }
+
+    fn alter_table_columns(
+        &mut self,
+        table_id: TableId,
+        columns: Vec<ColumnDef>,
+        mut migrate_row: impl FnMut(ProductValue) -> ProductValue,
+    ) -> super::Result<u32> {
+        let table_name = self.schema_for_table(table_id)?.table_name.clone();
+        log::trace!("TABLE ALTERING: {table_name}");
+
+        if table_name_is_system(&table_name) {
+            return Err(TableError::System(table_name).into());
+        }
+
+        // Take out all rows, so they can be reinserted with the new row type.
+        let rows = self
+            .iter(&table_id)?
+            .map(|row| (RowId(*row.id()), row.view().clone()))
+            .collect::<Vec<_>>();
+        let row_count = self.delete(&table_id, rows.iter().map(|(row_id, _)| *row_id));
+
+        // Replace the table's columns in st_columns.
+        // NOTE: Sequences are keyed by column id, and are thus retained.
+        self.drop_table_from_st_columns(table_id)?;
+        let mut col_schemas = Vec::with_capacity(columns.len());
+        for (i, col) in columns.into_iter().enumerate() {
+            let row = StColumnRow {
+                table_id,
+                col_id: i.into(),
+                col_name: col.col_name,
+                col_type: col.col_type,
+                is_autoinc: col.is_autoinc,
+            };
+            let row = StColumnRow::try_from(&self.insert(ST_COLUMNS_ID, row.into())?)?.to_owned();
+            col_schemas.push(ColumnSchema {
+                table_id,
+                col_id: row.col_id,
+                col_name: row.col_name,
+                col_type: row.col_type,
+                is_autoinc: row.is_autoinc,
+            });
+        }
+
+        // Update the in memory representation of the table for this
+        // transaction. It replaces the committed schema and row type on commit.
+        let tx_state = self.tx_state.as_mut().unwrap();
+        if tx_state.get_insert_table(&table_id).is_none() {
+            let Some(committed_table) = self.committed_state.tables.get(&table_id) else {
+                return Err(TableError::IdNotFound(table_id).into());
+            };
+            tx_state.insert_tables.insert(table_id, committed_table.empty_like());
+        }
+        let insert_table = tx_state.get_insert_table_mut(&table_id).unwrap();
+        insert_table.row_type = ProductType::from_iter(col_schemas.iter().map(|col| col.col_type.clone()));
+        insert_table.schema.columns = col_schemas;
+        // The predicates of the check constraints are typed after the columns.
+        let checks = std::mem::take(&mut insert_table.schema.checks);
+        insert_table.schema.checks = checks
+            .into_iter()
+            .map(|compiled| CompiledCheck::new(&insert_table.schema, compiled.check))
+            .collect::<Result<_, _>>()?;
+
+        // Reinsert the rows, bypassing sequences, as the values of auto_inc
+        // columns have already been generated.
+        for (_, row) in rows {
+            self.insert_row_internal(table_id, migrate_row(row))?;
+        }
+
+        log::trace!("TABLE ALTERED: {table_name}, table_id:{table_id}");
+
+        Ok(row_count)
+    }
+
     fn table_id_from_name(&self, table_name: &str) -> super::Result<Option<TableId>> {
         let table_name_col: ColId = 1.into();
         self.iter_by_col_eq(
@@ -957,6 +1214,7 @@ impl Inner {
             cols: index.cols.clone(),
             index_name: index.name.clone(),
             is_unique: index.is_unique,
+            index_type: index.index_type,
         };
         let index_id = StIndexRow::try_from(&self.insert(ST_INDEXES_ID, row.into())?)?.index_id;
 
@@ -970,26 +1228,32 @@ impl Inner {
         Ok(index_id)
     }
 
+    /// Returns the insert table of `table_id`, creating it in the transaction if needed,
+    /// so that the schema of the table can be modified.
+    fn ensure_insert_table(&mut self, table_id: TableId) -> super::Result<&mut Table> {
+        if self.tx_state.as_ref().unwrap().get_insert_table(&table_id).is_none() {
+            let row_type = self.row_type_for_table(table_id)?.into_owned();
+            let schema = self.schema_for_table(table_id)?.into_owned();
+            self.tx_state
+                .as_mut()
+                .unwrap()
+                .insert_tables
+                .insert(table_id, Table::new(row_type, schema));
+        }
+        Ok(self.tx_state.as_mut().unwrap().get_insert_table_mut(&table_id).unwrap())
+    }
+
     fn create_index_internal(&mut self, index_id: IndexId, index: IndexDef) -> super::Result<()> {
-        let insert_table =
-            if let Some(insert_table) = self.tx_state.as_mut().unwrap().get_insert_table_mut(&index.table_id) {
-                insert_table
-            } else {
-                let row_type = self.row_type_for_table(index.table_id)?.into_owned();
-                let schema = self.schema_for_table(index.table_id)?.into_owned();
-                self.tx_state
-                    .as_mut()
-                    .unwrap()
-                    .insert_tables
-                    .insert(index.table_id, Table::new(row_type, schema));
-                self.tx_state
-                    .as_mut()
-                    .unwrap()
-                    .get_insert_table_mut(&index.table_id)
-                    .unwrap()
-            };
+        self.ensure_insert_table(index.table_id)?;
+        let insert_table = self
+            .tx_state
+            .as_mut()
+            .unwrap()
+            .get_insert_table_mut(&index.table_id)
+            .unwrap();
 
-        let mut insert_index = BTreeIndex::new(
+        let mut insert_index = TableIndex::new(
+            index.index_type,
             index_id,
             index.table_id,
             index.cols.clone(),
@@ -1009,6 +1273,7 @@ impl Inner {
             index_name: index.name,
             is_unique: index.is_unique,
             index_id,
+            index_type: index.index_type,
         });
 
         insert_table.indexes.insert(index.cols, insert_index);
@@ -1038,8 +1303,8 @@ impl Inner {
         for (_, table) in self.committed_state.tables.iter_mut() {
             let mut cols = vec![];
             for index in table.indexes.values_mut() {
-                if index.index_id == *index_id {
-                    cols.push(index.cols.clone());
+                if index.index_id() == *index_id {
+                    cols.push(index.cols().clone());
                 }
             }
             for col in cols {
@@ -1055,8 +1320,8 @@ impl Inner {
         {
             let mut cols = vec![];
             for index in insert_table.indexes.values_mut() {
-                if index.index_id == *index_id {
-                    cols.push(index.cols.clone());
+                if index.index_id() == *index_id {
+                    cols.push(index.cols().clone());
                 }
             }
             for col in cols {
@@ -1079,6 +1344,366 @@ impl Inner {
         })
     }
 
+    fn create_foreign_key(&mut self, fk: ForeignKeyDef) -> super::Result<IndexId> {
+        log::trace!(
+            "FOREIGN KEY CREATING: {} for table: {} and col(s): {:?}",
+            fk.constraint_name,
+            fk.table_id,
+            fk.columns
+        );
+        let invalid = |reason: String| ConstraintError::InvalidForeignKey(fk.constraint_name.clone(), reason);
+
+        if self
+            .foreign_keys()?
+            .iter()
+            .any(|x| x.constraint_name == fk.constraint_name)
+        {
+            return Err(ConstraintError::Exists(fk.constraint_name).into());
+        }
+        let schema = self.schema_for_table(fk.table_id)?;
+        let ref_schema = self.schema_for_table(fk.ref_table_id)?;
+        if fk.columns.len() != fk.ref_columns.len() {
+            return Err(invalid("the number of columns differs from the referenced columns".into()).into());
+        }
+        for (col_id, ref_col_id) in fk.columns.iter().zip(fk.ref_columns.iter()) {
+            let col = schema
+                .get_column(col_id.idx())
+                .ok_or(TableError::ColumnNotFound(*col_id))?;
+            let ref_col = ref_schema
+                .get_column(ref_col_id.idx())
+                .ok_or(TableError::ColumnNotFound(*ref_col_id))?;
+            if col.col_type != ref_col.col_type {
+                return Err(invalid(format!(
+                    "column `{}` and the referenced column `{}.{}` have different types",
+                    col.col_name, ref_schema.table_name, ref_col.col_name
+                ))
+                .into());
+            }
+        }
+        // The referenced rows are looked up with a unique index,
+        // so that a deleted row can't be replaced by another with the same value.
+        if !ref_schema
+            .indexes
+            .iter()
+            .any(|index| index.is_unique && index.cols == fk.ref_columns)
+        {
+            return Err(invalid(format!(
+                "the referenced column(s) of `{}` are not unique",
+                ref_schema.table_name
+            ))
+            .into());
+        }
+        // The referencing rows of a deleted row are looked up with an index too.
+        if !schema.indexes.iter().any(|index| index.cols == fk.columns) {
+            return Err(invalid(format!("the column(s) of `{}` are not indexed", schema.table_name)).into());
+        }
+
+        // The rows already in the table are checked when the transaction commits,
+        // as the row of the constraint is new.
+        let row = StConstraintRow {
+            constraint_id: 0.into(), // Autogen'd
+            constraint_name: fk.constraint_name,
+            kind: ColumnIndexAttribute::FOREIGN_KEY,
+            table_id: fk.table_id,
+            columns: fk.columns.into(),
+            foreign_key: Some(StForeignKey {
+                ref_table_id: fk.ref_table_id,
+                ref_columns: fk.ref_columns.into(),
+                on_delete: fk.on_delete,
+            }),
+            check: None,
+        };
+        let row = self.insert(ST_CONSTRAINTS_ID, row.into())?;
+        let row = StConstraintRow::try_from(&row)?;
+        self.cache_constraint(&row, true)?;
+        let constraint_id = row.constraint_id;
+
+        log::trace!("FOREIGN KEY CREATED: id = {}", constraint_id);
+        Ok(constraint_id)
+    }
+
+    fn drop_constraint(&mut self, constraint_id: IndexId) -> super::Result<()> {
+        log::trace!("CONSTRAINT DROPPING: {}", constraint_id.0);
+
+        let (row_id, row) = self
+            .iter_by_col_eq(
+                &ST_CONSTRAINTS_ID,
+                StConstraintFields::ConstraintId.col_id(),
+                constraint_id.into(),
+            )?
+            .next()
+            .map(|row| (RowId(*row.id()), row.view().clone()))
+            .ok_or(ConstraintError::NotFound(constraint_id))?;
+        self.delete(&ST_CONSTRAINTS_ID, [row_id]);
+        self.cache_constraint(&StConstraintRow::try_from(&row)?, false)?;
+
+        log::trace!("CONSTRAINT DROPPED: {}", constraint_id.0);
+        Ok(())
+    }
+
+    /// Adds the constraint `row` to the schemas of the tables it concerns, or removes it if `!add`.
+    fn cache_constraint(&mut self, row: &StConstraintRow<&str>, add: bool) -> super::Result<()> {
+        if let Some(check) = row.to_check() {
+            let schema = &mut self.ensure_insert_table(check.table_id)?.schema;
+            schema.checks.retain(|x| x.check.constraint_id != check.constraint_id);
+            if add {
+                let compiled = CompiledCheck::new(schema, check)?;
+                schema.checks.push(compiled);
+            }
+        }
+        if let Some(fk) = row.to_foreign_key() {
+            let mut table_ids = vec![fk.table_id, fk.ref_table_id];
+            table_ids.dedup();
+            for table_id in table_ids {
+                let foreign_keys = &mut self.ensure_insert_table(table_id)?.schema.foreign_keys;
+                foreign_keys.retain(|x| x.constraint_id != fk.constraint_id);
+                if add {
+                    foreign_keys.push(fk.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn foreign_keys(&self) -> super::Result<Vec<ForeignKeySchema>> {
+        self.iter(&ST_CONSTRAINTS_ID)?
+            .map(|row| Ok(StConstraintRow::try_from(row.view())?.to_foreign_key()))
+            .filter_map(Result::transpose)
+            .collect()
+    }
+
+    fn create_check(&mut self, check: CheckDef) -> super::Result<IndexId> {
+        log::trace!(
+            "CHECK CONSTRAINT CREATING: {} for table: {}",
+            check.constraint_name,
+            check.table_id
+        );
+        if self
+            .checks()?
+            .iter()
+            .any(|x| x.constraint_name == check.constraint_name)
+        {
+            return Err(ConstraintError::Exists(check.constraint_name).into());
+        }
+
+        // The rows already in the table must satisfy the new constraint.
+        let schema = self.schema_for_table(check.table_id)?;
+        let compiled = CompiledCheck::new(
+            &schema,
+            CheckSchema {
+                constraint_id: 0.into(), // Not yet known
+                constraint_name: check.constraint_name.clone(),
+                table_id: check.table_id,
+                expr: check.expr.clone(),
+            },
+        )?;
+        for row in self.iter(&check.table_id)? {
+            Self::eval_check(&schema, &compiled, row.view())?;
+        }
+
+        let row = StConstraintRow {
+            constraint_id: 0.into(), // Autogen'd
+            constraint_name: check.constraint_name,
+            kind: ColumnIndexAttribute::CHECK,
+            table_id: check.table_id,
+            columns: vec![],
+            foreign_key: None,
+            check: Some(check.expr),
+        };
+        let row = self.insert(ST_CONSTRAINTS_ID, row.into())?;
+        let row = StConstraintRow::try_from(&row)?;
+        self.cache_constraint(&row, true)?;
+        let constraint_id = row.constraint_id;
+
+        log::trace!("CHECK CONSTRAINT CREATED: id = {}", constraint_id);
+        Ok(constraint_id)
+    }
+
+    fn checks(&self) -> super::Result<Vec<CheckSchema>> {
+        self.iter(&ST_CONSTRAINTS_ID)?
+            .map(|row| Ok(StConstraintRow::try_from(row.view())?.to_check()))
+            .filter_map(Result::transpose)
+            .collect()
+    }
+
+    /// Fails unless `row` satisfies the check constraint `compiled` of the table `schema`.
+    fn eval_check(schema: &TableSchema, compiled: &CompiledCheck, row: &ProductValue) -> super::Result<()> {
+        if compiled.eval(row) {
+            return Ok(());
+        }
+        Err(ConstraintError::CheckViolation {
+            constraint_name: compiled.check.constraint_name.clone(),
+            table_name: schema.table_name.clone(),
+            expr: compiled.check.expr.clone(),
+            row: row.clone(),
+        }
+        .into())
+    }
+
+    /// Fails unless `row`, about to be written to the table `table_id`,
+    /// satisfies every check constraint of the table.
+    fn check_row(&self, table_id: TableId, row: &ProductValue) -> super::Result<()> {
+        let schema = self.schema_for_table(table_id)?;
+        for compiled in &schema.checks {
+            Self::eval_check(&schema, compiled, row)?;
+        }
+        Ok(())
+    }
+
+    fn set_row_policy(&mut self, table_id: TableId, policy: Option<String>) -> super::Result<()> {
+        if !self.table_exists(&table_id) {
+            return Err(TableError::IdNotFound(table_id).into());
+        }
+        let row_ids = self
+            .iter_by_col_eq(
+                &ST_ROW_POLICIES_ID,
+                StRowPolicyFields::TableId.col_id(),
+                table_id.into(),
+            )?
+            .map(|row| RowId(*row.id()))
+            .collect::<Vec<_>>();
+        self.delete(&ST_ROW_POLICIES_ID, row_ids);
+        if let Some(policy) = policy {
+            self.insert(ST_ROW_POLICIES_ID, StRowPolicyRow { table_id, policy }.into())?;
+        }
+        Ok(())
+    }
+
+    fn row_policy(&self, table_id: TableId) -> super::Result<Option<String>> {
+        self.iter_by_col_eq(
+            &ST_ROW_POLICIES_ID,
+            StRowPolicyFields::TableId.col_id(),
+            table_id.into(),
+        )?
+        .next()
+        .map(|row| Ok(StRowPolicyRow::try_from(row.view())?.policy.to_owned()))
+        .transpose()
+    }
+
+    /// Enforces the foreign keys over the rows inserted and deleted by the transaction.
+    ///
+    /// The rows referencing a deleted row through a [ReferentialAction::Cascade] key are deleted in turn.
+    /// A key created by the transaction is checked against every row of its table.
+    fn check_foreign_keys(&mut self) -> super::Result<()> {
+        let tx_state = self.tx_state.as_ref().unwrap();
+        let mut created = HashSet::new();
+        if let Some(table) = tx_state.get_insert_table(&ST_CONSTRAINTS_ID) {
+            for row in table.scan_rows() {
+                created.extend(
+                    StConstraintRow::try_from(row)?
+                        .to_foreign_key()
+                        .map(|fk| fk.constraint_id),
+                );
+            }
+        }
+        // Only the keys of the tables written by the transaction need to be checked.
+        let mut foreign_keys = Vec::<ForeignKeySchema>::new();
+        for table_id in tx_state.insert_tables.keys().chain(tx_state.delete_tables.keys()) {
+            for fk in self
+                .get_schema(table_id)
+                .into_iter()
+                .flat_map(|schema| &schema.foreign_keys)
+            {
+                if !foreign_keys.iter().any(|x| x.constraint_id == fk.constraint_id) {
+                    foreign_keys.push(fk.clone());
+                }
+            }
+        }
+        if foreign_keys.is_empty() {
+            return Ok(());
+        }
+
+        // The committed rows deleted from a referenced table.
+        let mut deleted = Vec::new();
+        for (table_id, row_ids) in &tx_state.delete_tables {
+            if !foreign_keys.iter().any(|fk| fk.ref_table_id == *table_id) {
+                continue;
+            }
+            let Some(table) = self.committed_state.tables.get(table_id) else {
+                continue;
+            };
+            deleted.extend(
+                row_ids
+                    .iter()
+                    .filter_map(|row_id| Some((*table_id, table.get_row(row_id)?.clone()))),
+            );
+        }
+
+        while let Some((table_id, row)) = deleted.pop() {
+            // A cascade may reach tables the transaction didn't write.
+            let referencing_keys = self
+                .get_schema(&table_id)
+                .into_iter()
+                .flat_map(|schema| &schema.foreign_keys)
+                .filter(|fk| fk.ref_table_id == table_id)
+                .cloned()
+                .collect::<Vec<_>>();
+            for fk in &referencing_keys {
+                let value = row.project_not_empty(&fk.ref_columns)?;
+                // The row was replaced by one with the same value.
+                if self
+                    .iter_by_col_eq(&table_id, fk.ref_columns.clone(), value.clone())?
+                    .next()
+                    .is_some()
+                {
+                    continue;
+                }
+                let referencing = self
+                    .iter_by_col_eq(&fk.table_id, fk.columns.clone(), value.clone())?
+                    .map(|row| (RowId(*row.id()), row.view().clone()))
+                    .collect::<Vec<_>>();
+                if referencing.is_empty() {
+                    continue;
+                }
+                match fk.on_delete {
+                    ReferentialAction::Restrict => {
+                        return Err(ConstraintError::ForeignKeyRestrict {
+                            constraint_name: fk.constraint_name.clone(),
+                            table_name: self.schema_for_table(fk.table_id)?.table_name.clone(),
+                            ref_table_name: self.schema_for_table(table_id)?.table_name.clone(),
+                            value,
+                        }
+                        .into())
+                    }
+                    ReferentialAction::Cascade => {
+                        for (row_id, row) in referencing {
+                            self.delete_row_internal(&fk.table_id, &row_id);
+                            deleted.push((fk.table_id, row));
+                        }
+                    }
+                }
+            }
+        }
+
+        let tx_state = self.tx_state.as_ref().unwrap();
+        for fk in &foreign_keys {
+            let rows: Box<dyn Iterator<Item = &ProductValue>> = if created.contains(&fk.constraint_id) {
+                Box::new(self.iter(&fk.table_id)?.map(|row| row.view()))
+            } else if let Some(table) = tx_state.get_insert_table(&fk.table_id) {
+                Box::new(table.scan_rows())
+            } else {
+                continue;
+            };
+            for row in rows {
+                let value = row.project_not_empty(&fk.columns)?;
+                if self
+                    .iter_by_col_eq(&fk.ref_table_id, fk.ref_columns.clone(), value.clone())?
+                    .next()
+                    .is_none()
+                {
+                    return Err(ConstraintError::ForeignKeyViolation {
+                        constraint_name: fk.constraint_name.clone(),
+                        table_name: self.schema_for_table(fk.table_id)?.table_name.clone(),
+                        ref_table_name: self.schema_for_table(fk.ref_table_id)?.table_name.clone(),
+                        value,
+                    }
+                    .into());
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn contains_row(&self, table_id: &TableId, row_id: &RowId) -> RowState<'_> {
         match self.tx_state.as_ref().unwrap().get_row_op(table_id, row_id) {
             RowState::Committed(_) => unreachable!("a row cannot be committed in a tx state"),
@@ -1105,6 +1730,26 @@ impl Inner {
             || self.committed_state.tables.contains_key(table_id)
     }
 
+    /// Returns the number of rows visible to this transaction in the table,
+    /// or `None` if the table does not exist.
+    fn table_row_count(&self, table_id: &TableId) -> Option<u64> {
+        let committed = self.committed_state.tables.get(table_id).map(|table| table.rows.len());
+        let (inserted, deleted) = self
+            .tx_state
+            .as_ref()
+            .map(|tx_state| {
+                (
+                    tx_state.insert_tables.get(table_id).map(|table| table.rows.len()),
+                    tx_state.delete_tables.get(table_id).map_or(0, |row_ids| row_ids.len()),
+                )
+            })
+            .unwrap_or((None, 0));
+        if committed.is_none() && inserted.is_none() {
+            return None;
+        }
+        Some((committed.unwrap_or(0) + inserted.unwrap_or(0) - deleted) as u64)
+    }
+
     fn algebraic_type_is_numeric(ty: &AlgebraicType) -> bool {
         matches!(*ty, |AlgebraicType::I8| AlgebraicType::U8
             | AlgebraicType::I16
@@ -1172,9 +1817,18 @@ impl Inner {
             // At this point, we know this will be essentially a cheap copy.
             let col_ty = col.col_type.clone();
             let seq_val = self.get_next_sequence_value(sequence_id)?;
+            // The ids of the system tables added since the first release are taken from the top.
+            let allocates_ids = matches!(
+                table_id,
+                ST_TABLES_ID | ST_INDEXES_ID | ST_SEQUENCES_ID | ST_CONSTRAINTS_ID
+            );
+            if allocates_ids && seq_val >= ST_RESERVED_IDS_START as i128 {
+                return Err(SequenceError::Reserved(sequence_id).into());
+            }
             row.elements[col_idx] = Self::sequence_value_to_algebraic_value(&col_ty, seq_val);
         }
 
+        self.check_row(table_id, &row)?;
         self.insert_row_internal(table_id, row.clone())?;
         Ok(row)
     }
@@ -1186,96 +1840,8 @@ impl Inner {
         let data_key = DataKey::from_data(&bytes);
         let row_id = RowId(data_key);
 
-        // If the table does exist in the tx state, we need to create it based on the table in the
-        // committed state. If the table does not exist in the committed state, it doesn't exist
-        // in the database.
-        let insert_table = if let Some(table) = self.tx_state.as_ref().unwrap().get_insert_table(&table_id) {
-            table
-        } else {
-            let Some(committed_table) = self.committed_state.tables.get(&table_id) else {
-                return Err(TableError::IdNotFound(table_id).into());
-            };
-            let table = Table {
-                row_type: committed_table.row_type.clone(),
-                schema: committed_table.get_schema().clone(),
-                indexes: committed_table
-                    .indexes
-                    .iter()
-                    .map(|(cols, index)| {
-                        (
-                            cols.clone(),
-                            BTreeIndex::new(
-                                index.index_id,
-                                index.table_id,
-                                index.cols.clone(),
-                                index.name.clone(),
-                                index.is_unique,
-                            ),
-                        )
-                    })
-                    .collect(),
-                rows: Default::default(),
-            };
-            self.tx_state.as_mut().unwrap().insert_tables.insert(table_id, table);
-            self.tx_state.as_ref().unwrap().get_insert_table(&table_id).unwrap()
-        };
-
-        // Check unique constraints
-        for index in insert_table.indexes.values() {
-            if index.violates_unique_constraint(&row) {
-                let value = row.project_not_empty(&index.cols).unwrap();
-                return Err(IndexError::UniqueConstraintViolation {
-                    constraint_name: index.name.clone(),
-                    table_name: insert_table.schema.table_name.clone(),
-                    col_names: index
-                        .cols
-                        .iter()
-                        .map(|&x| insert_table.schema.columns[x.idx()].col_name.clone())
-                        .collect(),
-                    value,
-                }
-                .into());
-            }
-        }
-        if let Some(table) = self.committed_state.tables.get_mut(&table_id) {
-            for index in table.indexes.values() {
-                let value = index.get_fields(&row)?;
-                let Some(violators) = index.get_rows_that_violate_unique_constraint(&value) else {
-                    continue;
-                };
-                for row_id in violators {
-                    if let Some(delete_table) = self.tx_state.as_ref().unwrap().delete_tables.get(&table_id) {
-                        if !delete_table.contains(row_id) {
-                            let value = row.project_not_empty(&index.cols)?;
-                            return Err(IndexError::UniqueConstraintViolation {
-                                constraint_name: index.name.clone(),
-                                table_name: table.schema.table_name.clone(),
-                                col_names: index
-                                    .cols
-                                    .iter()
-                                    .map(|&x| insert_table.schema.columns[x.idx()].col_name.clone())
-                                    .collect(),
-                                value,
-                            }
-                            .into());
-                        }
-                    } else {
-                        let value = row.project_not_empty(&index.cols)?;
-                        return Err(IndexError::UniqueConstraintViolation {
-                            constraint_name: index.name.clone(),
-                            table_name: table.schema.table_name.clone(),
-                            col_names: index
-                                .cols
-                                .iter()
-                                .map(|&x| insert_table.schema.columns[x.idx()].col_name.clone())
-                                .collect(),
-                            value,
-                        }
-                        .into());
-                    }
-                }
-            }
-        }
+        self.get_or_create_insert_table(table_id)?;
+        self.check_unique_constraints(table_id, &row, |_| true)?;
 
         // Now that we have checked all the constraints, we can perform the actual insertion.
         {
@@ -1320,6 +1886,69 @@ impl Inner {
         Ok(())
     }
 
+    /// Returns the table of the rows inserted into `table_id` in this transaction.
+    ///
+    /// If it doesn't exist in the tx state yet, it is created based on the table in the committed state.
+    /// If the table does not exist in the committed state, it doesn't exist in the database.
+    fn get_or_create_insert_table(&mut self, table_id: TableId) -> super::Result<&mut Table> {
+        let tx_state = self.tx_state.as_mut().unwrap();
+        if tx_state.get_insert_table(&table_id).is_none() {
+            let Some(committed_table) = self.committed_state.tables.get(&table_id) else {
+                return Err(TableError::IdNotFound(table_id).into());
+            };
+            tx_state.insert_tables.insert(table_id, committed_table.empty_like());
+        }
+        Ok(tx_state.get_insert_table_mut(&table_id).unwrap())
+    }
+
+    /// Fails if writing `row` to the table `table_id` would violate one of its unique constraints,
+    /// only checking the indexes on the columns for which `check_cols` returns `true`.
+    ///
+    /// The insert table of `table_id` must exist.
+    fn check_unique_constraints(
+        &self,
+        table_id: TableId,
+        row: &ProductValue,
+        check_cols: impl Fn(&NonEmpty<ColId>) -> bool,
+    ) -> super::Result<()> {
+        let tx_state = self.tx_state.as_ref().unwrap();
+        let insert_table = tx_state.get_insert_table(&table_id).unwrap();
+        let violation = |index: &TableIndex| -> DBError {
+            IndexError::UniqueConstraintViolation {
+                constraint_name: index.name().to_owned(),
+                table_name: insert_table.schema.table_name.clone(),
+                col_names: index
+                    .cols()
+                    .iter()
+                    .map(|&x| insert_table.schema.columns[x.idx()].col_name.clone())
+                    .collect(),
+                value: row.project_not_empty(index.cols()).unwrap(),
+            }
+            .into()
+        };
+
+        for index in insert_table.indexes.values().filter(|index| check_cols(index.cols())) {
+            if index.violates_unique_constraint(row) {
+                return Err(violation(index));
+            }
+        }
+        let Some(table) = self.committed_state.tables.get(&table_id) else {
+            return Ok(());
+        };
+        // The committed rows deleted in this transaction don't count.
+        let deletes = tx_state.delete_tables.get(&table_id);
+        for index in table.indexes.values().filter(|index| check_cols(index.cols())) {
+            let value = index.get_fields(row)?;
+            let Some(mut violators) = index.get_rows_that_violate_unique_constraint(&value) else {
+                continue;
+            };
+            if violators.any(|row_id| deletes.map_or(true, |deletes| !deletes.contains(row_id))) {
+                return Err(violation(index));
+            }
+        }
+        Ok(())
+    }
+
     fn get<'a>(&'a self, table_id: &TableId, row_id: &'a RowId) -> super::Result<Option<DataRef<'a>>> {
         if !self.table_exists(table_id) {
             return Err(TableError::IdNotFound(*table_id).into());
@@ -1395,8 +2024,12 @@ impl Inner {
             RowState::Insert(_) => {
                 // If the row is present because of a an insertion in this transaction,
                 // we need to remove it from the appropriate insert_table.
-                let insert_table = self.tx_state.as_mut().unwrap().get_insert_table_mut(table_id).unwrap();
-                insert_table.delete(row_id);
+                let tx_state = self.tx_state.as_mut().unwrap();
+                tx_state.get_insert_table_mut(table_id).unwrap().delete(row_id);
+                // If the row was inserted by an update, the row it replaced stays deleted.
+                if let Some(updates) = tx_state.updates.get_mut(table_id) {
+                    updates.remove(row_id);
+                }
                 // True because we did delete a row.
                 true
             }
@@ -1411,6 +2044,115 @@ impl Inner {
         self.delete(table_id, relation.into_iter().map(|pv| RowId(pv.to_data_key())))
     }
 
+    /// Replaces the row identified by `row_id` with `row`.
+    ///
+    /// The row is updated in place: only the indexes whose key changes are rewritten,
+    /// and only their unique constraints are checked.
+    /// A committed row is replaced when the transaction is committed, see [CommittedState::merge].
+    ///
+    /// Returns `false` if there is no such row.
+    fn update(&mut self, table_id: TableId, row_id: RowId, row: ProductValue) -> super::Result<bool> {
+        let (old_row, original, is_committed) = match self.contains_row(&table_id, &row_id) {
+            RowState::Committed(pv) => (pv.clone(), Some(row_id), true),
+            RowState::Insert(pv) => {
+                let original = self
+                    .tx_state
+                    .as_ref()
+                    .unwrap()
+                    .updates
+                    .get(&table_id)
+                    .and_then(|updates| updates.get(&row_id))
+                    .copied();
+                (pv.clone(), original, false)
+            }
+            RowState::Delete | RowState::Absent => return Ok(false),
+        };
+        if old_row == row {
+            return Ok(true);
+        }
+        self.check_row(table_id, &row)?;
+
+        let mut bytes = Vec::new();
+        row.encode(&mut bytes);
+        let data_key = DataKey::from_data(&bytes);
+        let new_row_id = RowId(data_key);
+        if !matches!(self.contains_row(&table_id, &new_row_id), RowState::Absent) {
+            // The new row is already in the table, or was deleted in this transaction,
+            // which `insert` resolves.
+            return self.replace_row(table_id, row_id, old_row, row, original);
+        }
+
+        let insert_table = self.get_or_create_insert_table(table_id)?;
+        if insert_table.row_type.elements.len() != row.elements.len() {
+            return Err(TableError::RowInvalidType { table_id, row }.into());
+        }
+        let key_changed =
+            |cols: &NonEmpty<ColId>| old_row.project_not_empty(cols).ok() != row.project_not_empty(cols).ok();
+        self.check_unique_constraints(table_id, &row, key_changed)?;
+
+        let tx_state = self.tx_state.as_mut().unwrap();
+        if is_committed {
+            tx_state.get_or_create_delete_table(table_id).insert(row_id);
+            tx_state
+                .get_insert_table_mut(&table_id)
+                .unwrap()
+                .insert(new_row_id, row);
+        } else {
+            tx_state
+                .get_insert_table_mut(&table_id)
+                .unwrap()
+                .update(&row_id, new_row_id, row);
+            if let Some(updates) = tx_state.updates.get_mut(&table_id) {
+                updates.remove(&row_id);
+            }
+        }
+        if let DataKey::Hash(_) = data_key {
+            self.memory.insert(data_key, Arc::new(bytes));
+        }
+        if let Some(original) = original {
+            self.record_update(table_id, new_row_id, original);
+        }
+        Ok(true)
+    }
+
+    /// Replaces the row `row_id` with `row` by deleting the former and inserting the latter,
+    /// putting the old row back if the insertion fails.
+    fn replace_row(
+        &mut self,
+        table_id: TableId,
+        row_id: RowId,
+        old_row: ProductValue,
+        row: ProductValue,
+        original: Option<RowId>,
+    ) -> super::Result<bool> {
+        self.delete_row_internal(&table_id, &row_id);
+        let new_row_id = RowId(row.to_data_key());
+        if let Err(e) = self.insert_row_internal(table_id, row) {
+            // Put the old row back, so that a failed update leaves the table as it was.
+            self.insert_row_internal(table_id, old_row)
+                .expect("re-inserting a row removed by a failed update");
+            if let (Some(original), RowState::Insert(_)) = (original, self.contains_row(&table_id, &row_id)) {
+                self.record_update(table_id, row_id, original);
+            }
+            return Err(e);
+        }
+        if let Some(original) = original.filter(|original| *original != new_row_id) {
+            self.record_update(table_id, new_row_id, original);
+        }
+        Ok(true)
+    }
+
+    /// Records that the row `row_id` was inserted to replace the committed row `original`.
+    fn record_update(&mut self, table_id: TableId, row_id: RowId, original: RowId) {
+        self.tx_state
+            .as_mut()
+            .unwrap()
+            .updates
+            .entry(table_id)
+            .or_default()
+            .insert(row_id, original);
+    }
+
     fn iter(&self, table_id: &TableId) -> super::Result<Iter> {
         if self.table_exists(table_id) {
             return Ok(Iter::new(*table_id, self));
@@ -1439,6 +2181,24 @@ impl Inner {
         cols: NonEmpty<ColId>,
         range: R,
     ) -> super::Result<IterByColRange<'a, R>> {
+        // A hash index can't answer a range, and silently scanning the whole table instead
+        // would hide that the index doesn't fit the lookup.
+        if as_point(&range).is_none() {
+            let committed_table = self.committed_state.tables.get(table_id);
+            let inserted_table = self
+                .tx_state
+                .as_ref()
+                .and_then(|tx_state| tx_state.get_insert_table(table_id));
+            let index = [committed_table, inserted_table]
+                .into_iter()
+                .flatten()
+                .find_map(|table| table.indexes.get(&cols))
+                .filter(|index| index.index_type() == IndexType::Hash);
+            if let Some(index) = index {
+                return Err(IndexError::HashIndexRange(index.name().to_owned()).into());
+            }
+        }
+
         // We have to index_seek in both the committed state and the current tx state.
         // First, we will check modifications in the current tx. It may be that the table
         // has not been modified yet in the current tx, in which case we will only search
@@ -1460,8 +2220,11 @@ impl Inner {
             Ok(IterByColRange::Index(IndexSeekIterInner {
                 table_id: *table_id,
                 tx_state,
-                inserted_rows,
-                committed_rows: self.committed_state.index_seek(table_id, &cols, &range),
+                inserted_rows: inserted_rows.with_values().peekable(),
+                committed_rows: self
+                    .committed_state
+                    .index_seek(table_id, &cols, &range)
+                    .map(|rows| rows.with_values().peekable()),
                 committed_state: &self.committed_state,
             }))
         } else {
@@ -1492,15 +2255,39 @@ impl Inner {
     }
 
     fn commit(&mut self) -> super::Result<Option<TxData>> {
+        if let Err(e) = self.check_foreign_keys() {
+            self.rollback();
+            return Err(e);
+        }
         let tx_state = self.tx_state.take().unwrap();
+        if tx_state.changes_schema() {
+            self.committed_state.schema_version += 1;
+        }
         let memory = std::mem::take(&mut self.memory);
         let tx_data = self.committed_state.merge(tx_state, memory);
         Ok(Some(tx_data))
     }
 
     fn rollback(&mut self) {
-        self.tx_state = None;
-        // TODO: Check that no sequences exceed their allocation after the rollback.
+        let Some(tx_state) = self.tx_state.take() else {
+            return;
+        };
+        self.committed_state.tables.extend(tx_state.dropped_tables);
+        self.sequence_state.sequences.extend(tx_state.dropped_sequences);
+        // The allocations made by the transaction are rolled back along with their rows in `st_sequences`,
+        // so those sequences must allocate again, and persist it, before handing out their next value.
+        // Otherwise they would hand out values beyond the committed allocation, which are reused after a restart.
+        let Some(inserted) = tx_state.insert_tables.get(&ST_SEQUENCES_ID) else {
+            return;
+        };
+        for row in inserted.scan_rows() {
+            let Ok(row) = StSequenceRow::try_from(row) else {
+                continue;
+            };
+            if let Some(sequence) = self.sequence_state.get_sequence_mut(row.sequence_id) {
+                sequence.set_allocation(sequence.value);
+            }
+        }
     }
 }
 
@@ -1541,6 +2328,16 @@ impl Locking {
             .committed_state
             .get_or_create_table(ST_MODULE_ID, &ST_MODULE_ROW_TYPE, &st_module_schema());
         datastore.bootstrap_system_table(st_module_schema())?;
+        datastore.committed_state.get_or_create_table(
+            ST_ROW_POLICIES_ID,
+            &ST_ROW_POLICY_ROW_TYPE,
+            &st_row_policies_schema(),
+        );
+        datastore.bootstrap_system_table(st_row_policies_schema())?;
+        datastore
+            .committed_state
+            .get_or_create_table(ST_SCHEDULED_ID, &ST_SCHEDULED_ROW_TYPE, &st_scheduled_schema());
+        datastore.bootstrap_system_table(st_scheduled_schema())?;
 
         // The database tables are now initialized with the correct data.
         // Now we have to build our in memory structures.
@@ -1567,7 +2364,60 @@ impl Locking {
         // See John Carmack's philosophy on this.
         inner.build_missing_tables()?;
         inner.build_indexes()?;
+        inner.build_constraints()?;
         inner.build_sequence_state()?;
+        // The schema may have changed with the replayed transactions.
+        inner.committed_state.schema_version += 1;
+
+        Ok(())
+    }
+
+    /// Returns the version of the schema seen by `tx`, which changes whenever a change to the schema is committed,
+    /// or `None` if `tx` itself changes the schema.
+    pub fn schema_version(&self, tx: &MutTxId) -> Option<u64> {
+        match &tx.lock.tx_state {
+            Some(tx_state) if tx_state.changes_schema() => None,
+            _ => Some(tx.lock.committed_state.schema_version),
+        }
+    }
+
+    /// Copy the rows of every table in the committed state,
+    /// to be turned into a [`Snapshot`] by [`Snapshot::from_rows`].
+    ///
+    /// Holding `tx` guarantees that no transaction commits while the rows are
+    /// copied. Any uncommitted changes made in `tx` are not included.
+    pub fn committed_rows_mut_tx(&self, tx: &MutTxId) -> Vec<(TableId, Vec<ProductValue>)> {
+        tx.lock
+            .committed_state
+            .tables
+            .iter()
+            .map(|(table_id, table)| (*table_id, table.scan_rows().cloned().collect()))
+            .collect()
+    }
+
+    /// Replace the committed state with the contents of `snapshot`.
+    ///
+    /// Like [`Self::replay_transaction`], this only restores the rows of each
+    /// table, so [`Self::rebuild_state_after_replay`] must be called
+    /// afterwards.
+    pub fn restore_snapshot(&self, snapshot: &Snapshot) -> Result<(), DBError> {
+        let mut inner = self.inner.lock();
+
+        // The system tables come first, so the schema of every user table is
+        // known by the time its rows are restored.
+        for table in &snapshot.tables {
+            let schema = inner.schema_for_table(table.table_id)?.into_owned();
+            let row_type = inner.row_type_for_table(table.table_id)?.into_owned();
+            let rows = Self::table_rows(&mut inner, table.table_id, schema, row_type.clone());
+            rows.clear();
+            for bytes in &table.rows {
+                let row = match ProductValue::decode(&row_type, &mut &bytes[..]) {
+                    Ok(row) => row,
+                    Err(e) => decode_legacy_row(table.table_id, bytes).ok_or(e)?,
+                };
+                rows.insert(RowId(row.to_data_key()), row);
+            }
+        }
 
         Ok(())
     }
@@ -1586,41 +2436,106 @@ impl Locking {
             .rows
     }
 
-    pub fn replay_transaction(
-        &self,
-        transaction: &Transaction,
-        odb: Arc<std::sync::Mutex<Box<dyn ObjectDB + Send>>>,
+    pub fn replay_transaction(
+        &self,
+        transaction: &Transaction,
+        odb: Arc<std::sync::Mutex<Box<dyn ObjectDB + Send>>>,
+    ) -> Result<(), DBError> {
+        let mut inner = self.inner.lock();
+
+        // Replay writes to the system tables first, so that rows of tables
+        // whose columns were altered in this transaction are decoded using the
+        // new row type.
+        let (system_writes, user_writes): (Vec<&Write>, Vec<&Write>) = transaction
+            .writes
+            .iter()
+            .partition(|write| table_id_is_system(TableId(write.set_id)));
+        // The tables whose columns were altered, whether or not any of their
+        // rows are written in this transaction.
+        let altered_tables = system_writes
+            .iter()
+            .filter(|write| TableId(write.set_id) == ST_COLUMNS_ID)
+            .filter_map(|write| Self::row_data(write.data_key, &odb))
+            .map(|data| {
+                let row = ProductValue::decode(&ST_COLUMNS_ROW_TYPE, &mut &data[..])?;
+                Ok(StColumnRow::try_from(&row)?.table_id)
+            })
+            .collect::<Result<BTreeSet<_>, DBError>>()?;
+
+        Self::replay_writes(&mut inner, system_writes, &odb)?;
+        for table_id in altered_tables {
+            Self::refresh_table_schema(&mut inner, table_id)?;
+        }
+        Self::replay_writes(&mut inner, user_writes, &odb)
+    }
+
+    /// Re-read the schema and row type of the in memory table `table_id` from
+    /// the system tables, if the table exists.
+    fn refresh_table_schema(inner: &mut Inner, table_id: TableId) -> Result<(), DBError> {
+        let Some(mut table) = inner.committed_state.tables.remove(&table_id) else {
+            return Ok(());
+        };
+        // The table may have been dropped in the same transaction.
+        if let Ok(schema) = inner.schema_for_table(table_id) {
+            table.schema = schema.into_owned();
+            table.row_type = inner.row_type_for_table(table_id)?.into_owned();
+        }
+        inner.committed_state.tables.insert(table_id, table);
+        Ok(())
+    }
+
+    fn replay_writes<'a>(
+        inner: &mut Inner,
+        writes: impl IntoIterator<Item = &'a Write>,
+        odb: &Arc<std::sync::Mutex<Box<dyn ObjectDB + Send>>>,
     ) -> Result<(), DBError> {
-        let mut inner = self.inner.lock();
-        for write in &transaction.writes {
+        for write in writes {
             let table_id = TableId(write.set_id);
             let schema = inner.schema_for_table(table_id)?.into_owned();
             let row_type = inner.row_type_for_table(table_id)?.into_owned();
             match write.operation {
                 Operation::Delete => {
-                    Self::table_rows(&mut inner, table_id, schema, row_type).remove(&RowId(write.data_key));
+                    let rows = Self::table_rows(inner, table_id, schema, row_type);
+                    if rows.remove(&RowId(write.data_key)).is_none() {
+                        // The row may have been upgraded from an earlier layout when inserted,
+                        // in which case it's kept under the key of its upgraded value.
+                        let upgraded =
+                            Self::row_data(write.data_key, odb).and_then(|data| decode_legacy_row(table_id, &data));
+                        if let Some(row) = upgraded {
+                            rows.remove(&RowId(row.to_data_key()));
+                        }
+                    }
                 }
                 Operation::Insert => {
-                    let product_value = match write.data_key {
-                        DataKey::Data(data) => ProductValue::decode(&row_type, &mut &data[..]).unwrap_or_else(|_| {
-                            panic!("Couldn't decode product value to {:?} from message log", row_type)
-                        }),
-                        DataKey::Hash(hash) => {
-                            let data = odb.lock().unwrap().get(hash).unwrap_or_else(|| {
-                                panic!("Object {hash} referenced from transaction not present in object DB");
-                            });
-                            ProductValue::decode(&row_type, &mut &data[..]).unwrap_or_else(|_| {
+                    let data = Self::row_data(write.data_key, odb).unwrap_or_else(|| {
+                        panic!(
+                            "Object {:?} referenced from transaction not present in object DB",
+                            write.data_key
+                        );
+                    });
+                    let (row_id, product_value) = match ProductValue::decode(&row_type, &mut &data[..]) {
+                        Ok(row) => (RowId(write.data_key), row),
+                        Err(_) => {
+                            let row = decode_legacy_row(table_id, &data).unwrap_or_else(|| {
                                 panic!("Couldn't decode product value to {:?} from message log", row_type)
-                            })
+                            });
+                            (RowId(row.to_data_key()), row)
                         }
                     };
-                    Self::table_rows(&mut inner, table_id, schema, row_type)
-                        .insert(RowId(write.data_key), product_value);
+                    Self::table_rows(inner, table_id, schema, row_type).insert(row_id, product_value);
                 }
             }
         }
         Ok(())
     }
+
+    /// Returns the encoded row `data_key` refers to, if it's still around.
+    fn row_data(data_key: DataKey, odb: &Arc<std::sync::Mutex<Box<dyn ObjectDB + Send>>>) -> Option<Bytes> {
+        match data_key {
+            DataKey::Data(data) => Some(Bytes::copy_from_slice(&data)),
+            DataKey::Hash(hash) => odb.lock().unwrap().get(hash),
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
@@ -1744,12 +2659,14 @@ impl<'a> Iterator for Iter<'a> {
     }
 }
 
+/// Yields the rows inserted by the current transaction
+/// and the committed rows it didn't delete, in the order of the index.
 pub struct IndexSeekIterInner<'a> {
     table_id: TableId,
     tx_state: &'a TxState,
     committed_state: &'a CommittedState,
-    inserted_rows: BTreeIndexRangeIter<'a>,
-    committed_rows: Option<BTreeIndexRangeIter<'a>>,
+    inserted_rows: Peekable<IndexSeekEntries<'a>>,
+    committed_rows: Option<Peekable<IndexSeekEntries<'a>>>,
 }
 
 impl<'a> Iterator for IndexSeekIterInner<'a> {
@@ -1757,26 +2674,32 @@ impl<'a> Iterator for IndexSeekIterInner<'a> {
 
     #[tracing::instrument(skip_all)]
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(row_id) = self.inserted_rows.next() {
+        let deleted = self.tx_state.delete_tables.get(&self.table_id);
+        let committed = self.committed_rows.as_mut().and_then(|rows| {
+            while let Some((_, row_id)) = rows.peek() {
+                if !deleted.map_or(false, |table| table.contains(row_id)) {
+                    return rows.peek();
+                }
+                rows.next();
+            }
+            None
+        });
+
+        // Merge both sides, so the rows come in the order of the index.
+        let take_inserted = match (self.inserted_rows.peek(), committed) {
+            (Some((inserted, _)), Some((committed, _))) => inserted <= committed,
+            (inserted, _) => inserted.is_some(),
+        };
+        if take_inserted {
+            let (_, row_id) = self.inserted_rows.next()?;
             return Some(DataRef::new(
                 row_id,
                 self.tx_state.get_row(&self.table_id, row_id).unwrap(),
             ));
         }
 
-        if let Some(row_id) = self.committed_rows.as_mut().and_then(|i| {
-            i.find(|row_id| {
-                !self
-                    .tx_state
-                    .delete_tables
-                    .get(&self.table_id)
-                    .map_or(false, |table| table.contains(row_id))
-            })
-        }) {
-            return Some(get_committed_row(self.committed_state, &self.table_id, row_id));
-        }
-
-        None
+        let (_, row_id) = self.committed_rows.as_mut()?.next()?;
+        Some(get_committed_row(self.committed_state, &self.table_id, row_id))
     }
 }
 
@@ -1784,7 +2707,7 @@ pub struct CommittedIndexIter<'a> {
     table_id: TableId,
     tx_state: &'a TxState,
     committed_state: &'a CommittedState,
-    committed_rows: BTreeIndexRangeIter<'a>,
+    committed_rows: IndexSeekIter<'a>,
 }
 
 impl<'a> Iterator for CommittedIndexIter<'a> {
@@ -1976,10 +2899,32 @@ impl MutTxDatastore for Locking {
         tx.lock.rename_table(table_id, new_name)
     }
 
+    fn rename_index_mut_tx(&self, tx: &mut Self::MutTxId, index_id: IndexId, new_name: &str) -> super::Result<()> {
+        tx.lock.rename_index(index_id, new_name)
+    }
+
+    fn rename_sequence_mut_tx(&self, tx: &mut Self::MutTxId, seq_id: SequenceId, new_name: &str) -> super::Result<()> {
+        tx.lock.rename_sequence(seq_id, new_name)
+    }
+
+    fn alter_table_columns_mut_tx(
+        &self,
+        tx: &mut Self::MutTxId,
+        table_id: TableId,
+        columns: Vec<ColumnDef>,
+        migrate_row: impl FnMut(ProductValue) -> ProductValue,
+    ) -> super::Result<u32> {
+        tx.lock.alter_table_columns(table_id, columns, migrate_row)
+    }
+
     fn table_id_exists(&self, tx: &Self::MutTxId, table_id: &TableId) -> bool {
         tx.lock.table_exists(table_id)
     }
 
+    fn table_row_count_mut_tx(&self, tx: &Self::MutTxId, table_id: TableId) -> Option<u64> {
+        tx.lock.table_row_count(&table_id)
+    }
+
     fn table_id_from_name_mut_tx(&self, tx: &Self::MutTxId, table_name: &str) -> super::Result<Option<TableId>> {
         tx.lock.table_id_from_name(table_name)
     }
@@ -2000,6 +2945,43 @@ impl MutTxDatastore for Locking {
         tx.lock.drop_index(index_id)
     }
 
+    fn create_foreign_key_mut_tx(&self, tx: &mut Self::MutTxId, fk: ForeignKeyDef) -> super::Result<IndexId> {
+        tx.lock.create_foreign_key(fk)
+    }
+
+    fn drop_foreign_key_mut_tx(&self, tx: &mut Self::MutTxId, constraint_id: IndexId) -> super::Result<()> {
+        tx.lock.drop_constraint(constraint_id)
+    }
+
+    fn foreign_keys_mut_tx(&self, tx: &Self::MutTxId) -> super::Result<Vec<ForeignKeySchema>> {
+        tx.lock.foreign_keys()
+    }
+
+    fn create_check_mut_tx(&self, tx: &mut Self::MutTxId, check: CheckDef) -> super::Result<IndexId> {
+        tx.lock.create_check(check)
+    }
+
+    fn drop_check_mut_tx(&self, tx: &mut Self::MutTxId, constraint_id: IndexId) -> super::Result<()> {
+        tx.lock.drop_constraint(constraint_id)
+    }
+
+    fn checks_mut_tx(&self, tx: &Self::MutTxId) -> super::Result<Vec<CheckSchema>> {
+        tx.lock.checks()
+    }
+
+    fn set_row_policy_mut_tx(
+        &self,
+        tx: &mut Self::MutTxId,
+        table_id: TableId,
+        policy: Option<String>,
+    ) -> super::Result<()> {
+        tx.lock.set_row_policy(table_id, policy)
+    }
+
+    fn row_policy_mut_tx(&self, tx: &Self::MutTxId, table_id: TableId) -> super::Result<Option<String>> {
+        tx.lock.row_policy(table_id)
+    }
+
     fn index_id_from_name_mut_tx(&self, tx: &Self::MutTxId, index_name: &str) -> super::Result<Option<IndexId>> {
         tx.lock.index_id_from_name(index_name)
     }
@@ -2083,6 +3065,16 @@ impl MutTxDatastore for Locking {
     ) -> super::Result<ProductValue> {
         tx.lock.insert(table_id, row)
     }
+
+    fn update_mut_tx(
+        &self,
+        tx: &mut Self::MutTxId,
+        table_id: TableId,
+        row_id: Self::RowId,
+        row: ProductValue,
+    ) -> super::Result<bool> {
+        tx.lock.update(table_id, row_id, row)
+    }
 }
 
 impl traits::Programmable for Locking {
@@ -2124,26 +3116,39 @@ impl traits::MutProgrammable for Locking {
 
 #[cfg(test)]
 mod tests {
-    use super::{ColId, Locking, MutTxId, StTableRow};
+    use super::{ColId, IterByColRange, Locking, MutTxId, RowId, StTableRow, TableIndex};
     use crate::db::datastore::system_tables::{StConstraintRow, ST_CONSTRAINTS_ID};
+    use crate::db::messages::{
+        transaction::Transaction,
+        write::{Operation, Write},
+    };
+    use crate::db::ostorage::{memory_object_db::MemoryObjectDB, ObjectDB};
     use crate::{
         db::datastore::{
             locking_tx_datastore::{
                 StColumnRow, StIndexRow, StSequenceRow, ST_COLUMNS_ID, ST_INDEXES_ID, ST_SEQUENCES_ID, ST_TABLES_ID,
             },
-            traits::{ColumnDef, ColumnSchema, IndexDef, IndexSchema, MutTx, MutTxDatastore, TableDef, TableSchema},
+            traits::{
+                CheckDef, ColumnDef, ColumnSchema, ForeignKeyDef, IndexDef, IndexSchema, MutTx, MutTxDatastore,
+                TableDef, TableSchema, TxOp,
+            },
         },
-        error::{DBError, IndexError},
+        error::{ConstraintError, DBError, IndexError},
     };
     use itertools::Itertools;
     use nonempty::NonEmpty;
+    use spacetimedb_lib::DataKey;
     use spacetimedb_lib::{
         auth::{StAccess, StTableType},
+        data_key::ToDataKey,
         error::ResultTest,
-        ColumnIndexAttribute,
+        ColumnIndexAttribute, IndexType, ReferentialAction,
     };
     use spacetimedb_primitives::{IndexId, TableId};
+    use spacetimedb_sats::ArrayValue;
     use spacetimedb_sats::{product, AlgebraicType, AlgebraicValue, ProductValue};
+    use std::ops::Bound;
+    use std::sync::Arc;
 
     fn u32_str_u32(a: u32, b: &str, c: u32) -> ProductValue {
         product![a, b, c]
@@ -2160,6 +3165,7 @@ mod tests {
             cols: NonEmpty::new(ColId(col_id)),
             index_name: name.into(),
             is_unique,
+            index_type: IndexType::BTree,
         }
     }
 
@@ -2210,6 +3216,7 @@ mod tests {
             cols: NonEmpty::new(ColId(col_id)),
             index_name: name.to_string(),
             is_unique,
+            index_type: IndexType::BTree,
         }
     }
 
@@ -2288,6 +3295,8 @@ mod tests {
                 table_row(3, "st_indexes", StTableType::System, StAccess::Public),
                 table_row(4, "st_constraints", StTableType::System, StAccess::Public),
                 table_row(5, "st_module", StTableType::System, StAccess::Public),
+                table_row(u32::MAX - 1, "st_scheduled", StTableType::System, StAccess::Public),
+                table_row(u32::MAX, "st_row_policy", StTableType::System, StAccess::Public),
             ]
         );
         let column_rows = datastore
@@ -2326,16 +3335,30 @@ mod tests {
                 column_row(3, 2, "cols", AlgebraicType::array(AlgebraicType::U32), false),
                 column_row(3, 3, "index_name", AlgebraicType::String, false),
                 column_row(3, 4, "is_unique", AlgebraicType::Bool, false),
+                column_row(3, 5, "index_type", AlgebraicType::U8, false),
 
                 column_row(4, 0, "constraint_id", AlgebraicType::U32, true),
                 column_row(4, 1, "constraint_name", AlgebraicType::String, false),
                 column_row(4, 2, "kind", AlgebraicType::U32, false),
                 column_row(4, 3, "table_id", AlgebraicType::U32, false),
                 column_row(4, 4, "columns", AlgebraicType::array(AlgebraicType::U32), false),
+                column_row(4, 5, "ref_table_id", AlgebraicType::U32, false),
+                column_row(4, 6, "ref_columns", AlgebraicType::array(AlgebraicType::U32), false),
+                column_row(4, 7, "on_delete", AlgebraicType::U8, false),
+                column_row(4, 8, "check", AlgebraicType::String, false),
 
                 column_row(5, 0, "program_hash", AlgebraicType::array(AlgebraicType::U8), false),
                 column_row(5, 1, "kind", AlgebraicType::U8, false),
                 column_row(5, 2, "epoch", AlgebraicType::U128, false),
+
+                column_row(u32::MAX - 1, 0, "scheduled_id", AlgebraicType::U64, true),
+                column_row(u32::MAX - 1, 1, "reducer", AlgebraicType::String, false),
+                column_row(u32::MAX - 1, 2, "args", AlgebraicType::bytes(), false),
+                column_row(u32::MAX - 1, 3, "scheduled_at", AlgebraicType::U64, false),
+                column_row(u32::MAX - 1, 4, "recurring", AlgebraicType::Bool, false),
+
+                column_row(u32::MAX, 0, "table_id", AlgebraicType::U32, false),
+                column_row(u32::MAX, 1, "policy", AlgebraicType::String, false),
             ]
         );
         let index_rows = datastore
@@ -2354,6 +3377,7 @@ mod tests {
                 index_row(3, 0, 1, "table_name_idx", true),
                 index_row(4, 4, 0, "constraint_id_idx", true),
                 index_row(5, 1, 0, "idx_ct_columns_table_id", false),
+                index_row(u32::MAX, u32::MAX - 1, 0, "scheduled_id_idx", true),
             ]
         );
         let sequence_rows = datastore
@@ -2368,7 +3392,8 @@ mod tests {
                 StSequenceRow { sequence_id: 0.into(), sequence_name: "table_id_seq".to_string(), table_id: 0.into(), col_id: 0.into(), increment: 1, start: 6, min_value: 1, max_value: 4294967295, allocated: 4096 },
                 StSequenceRow { sequence_id: 1.into(), sequence_name: "sequence_id_seq".to_string(), table_id: 2.into(), col_id: 0.into(), increment: 1, start: 4, min_value: 1, max_value: 4294967295, allocated: 4096 },
                 StSequenceRow { sequence_id: 2.into(), sequence_name: "index_id_seq".to_string(), table_id: 3.into(), col_id: 0.into(), increment: 1, start: 6, min_value: 1, max_value: 4294967295, allocated: 4096 },
-                StSequenceRow { sequence_id: 3.into(), sequence_name: "constraint_id_seq".to_string(), table_id: 4.into(), col_id: 0.into(), increment: 1, start: 1, min_value: 1, max_value: 4294967295, allocated: 4096 },
+                StSequenceRow { sequence_id: 3.into(), sequence_name: "constraint_id_seq".to_string(), table_id: 4.into(), col_id: 0.into(), increment: 1, start: 6, min_value: 1, max_value: 4294967295, allocated: 4096 },
+                StSequenceRow { sequence_id: u32::MAX.into(), sequence_name: "scheduled_id_seq".to_string(), table_id: (u32::MAX - 1).into(), col_id: 0.into(), increment: 1, start: 1, min_value: 1, max_value: 4294967295, allocated: 4096 },
             ]
         );
         let constraints_rows = datastore
@@ -2381,7 +3406,7 @@ mod tests {
         assert_eq!(
             constraints_rows,
             vec![
-                StConstraintRow{ constraint_id: 5.into(), constraint_name: "ct_columns_table_id".to_string(), kind:  ColumnIndexAttribute::INDEXED, table_id: 1.into(), columns: vec![0.into()] },
+                StConstraintRow{ constraint_id: 5.into(), constraint_name: "ct_columns_table_id".to_string(), kind:  ColumnIndexAttribute::INDEXED, table_id: 1.into(), columns: vec![0.into()], foreign_key: None, check: None },
             ]
         );
         datastore.rollback_mut_tx(tx);
@@ -2498,6 +3523,8 @@ mod tests {
                 index_schema(7, 6, 1, "name_idx", true),
             ],
             constraints: vec![],
+            foreign_keys: vec![],
+            checks: vec![],
             table_type: StTableType::User,
             table_access: StAccess::Public,
         });
@@ -2526,6 +3553,8 @@ mod tests {
                 index_schema(7, 6, 1, "name_idx", true),
             ],
             constraints: vec![],
+            foreign_keys: vec![],
+            checks: vec![],
             table_type: StTableType::User,
             table_access: StAccess::Public,
         });
@@ -2729,6 +3758,252 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_index_seek_merges_inserted_and_committed_rows_in_order() -> ResultTest<()> {
+        let (datastore, mut tx, table_id) = setup_table()?;
+        for name in ["Bob", "Dave"] {
+            datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, name, 18))?;
+        }
+        datastore.commit_mut_tx(tx)?;
+        let mut tx = datastore.begin_mut_tx();
+        for name in ["Eve", "Alice", "Carol"] {
+            datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, name, 18))?;
+        }
+        let names = datastore
+            .iter_by_col_range_mut_tx(&tx, table_id, ColId(1), ..)?
+            .map(|row| row.view().elements[1].as_string().unwrap().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["Alice", "Bob", "Carol", "Dave", "Eve"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_seek_empty_range() -> ResultTest<()> {
+        let (datastore, mut tx, table_id) = setup_table()?;
+        for name in ["Bob", "Dave"] {
+            datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, name, 18))?;
+        }
+        datastore.commit_mut_tx(tx)?;
+        let mut tx = datastore.begin_mut_tx();
+        datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Carol", 18))?;
+
+        let names = |start: Bound<&str>, end: Bound<&str>| -> ResultTest<Vec<String>> {
+            let map = |bound| match bound {
+                Bound::Included(name) => Bound::Included(AlgebraicValue::String(String::from(name))),
+                Bound::Excluded(name) => Bound::Excluded(AlgebraicValue::String(String::from(name))),
+                Bound::Unbounded => Bound::Unbounded,
+            };
+            Ok(datastore
+                .iter_by_col_range_mut_tx(&tx, table_id, ColId(1), (map(start), map(end)))?
+                .map(|row| row.view().elements[1].as_string().unwrap().clone())
+                .collect())
+        };
+        // Inverted ranges are empty.
+        assert!(names(Bound::Included("Dave"), Bound::Included("Bob"))?.is_empty());
+        assert!(names(Bound::Excluded("Dave"), Bound::Excluded("Bob"))?.is_empty());
+        // As are ranges that exclude their only value.
+        assert!(names(Bound::Excluded("Bob"), Bound::Excluded("Bob"))?.is_empty());
+        assert!(names(Bound::Included("Bob"), Bound::Excluded("Bob"))?.is_empty());
+        assert!(names(Bound::Excluded("Carol"), Bound::Included("Carol"))?.is_empty());
+        // Excluded bounds leave out every row with their value.
+        assert_eq!(names(Bound::Excluded("Bob"), Bound::Excluded("Dave"))?, vec!["Carol"]);
+        assert_eq!(names(Bound::Included("Bob"), Bound::Included("Bob"))?, vec!["Bob"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hash_index() -> ResultTest<()> {
+        let (datastore, mut tx, table_id) = setup_table()?;
+        let index_def =
+            IndexDef::new("age_hash_idx".into(), table_id, 2.into(), false).with_index_type(IndexType::Hash);
+        datastore.create_index_mut_tx(&mut tx, index_def)?;
+        for (name, age) in [("Alice", 18), ("Bob", 20), ("Carol", 18)] {
+            datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, name, age))?;
+        }
+        datastore.commit_mut_tx(tx)?;
+
+        let mut tx = datastore.begin_mut_tx();
+        let index_row = datastore
+            .iter_mut_tx(&tx, ST_INDEXES_ID)?
+            .map(|x| StIndexRow::try_from(x.view()).unwrap().to_owned())
+            .find(|x| x.index_name == "age_hash_idx")
+            .unwrap();
+        assert_eq!(index_row.index_type, IndexType::Hash);
+
+        let names = |tx: &MutTxId, age: u32| -> ResultTest<Vec<String>> {
+            let iter = datastore.iter_by_col_eq_mut_tx(tx, table_id, ColId(2), age.into())?;
+            assert!(!matches!(iter, IterByColRange::Scan(_)));
+            Ok(iter
+                .map(|row| row.view().elements[1].as_string().unwrap().clone())
+                .sorted()
+                .collect())
+        };
+        assert_eq!(names(&tx, 18)?, vec!["Alice", "Carol"]);
+        assert_eq!(names(&tx, 19)?, Vec::<String>::new());
+
+        // The rows inserted and deleted by the transaction are accounted for.
+        datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Dave", 18))?;
+        datastore.delete_by_rel_mut_tx(&mut tx, table_id, [u32_str_u32(1, "Alice", 18)]);
+        assert_eq!(names(&tx, 18)?, vec!["Carol", "Dave"]);
+
+        // A hash index can't seek a range.
+        let range = AlgebraicValue::U32(18)..AlgebraicValue::U32(20);
+        let result = datastore.iter_by_col_range_mut_tx(&tx, table_id, ColId(2), range);
+        assert!(matches!(
+            result,
+            Err(DBError::Index(IndexError::HashIndexRange(name))) if name == "age_hash_idx"
+        ));
+
+        // A unique hash index enforces its constraint.
+        let index_def =
+            IndexDef::new("name_hash_idx".into(), table_id, 1.into(), true).with_index_type(IndexType::Hash);
+        datastore.create_index_mut_tx(&mut tx, index_def)?;
+        let result = datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Carol", 30));
+        assert!(matches!(
+            result,
+            Err(DBError::Index(IndexError::UniqueConstraintViolation { .. }))
+        ));
+        datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Eve", 30))?;
+
+        datastore.commit_mut_tx(tx)?;
+        let tx = datastore.begin_mut_tx();
+        assert_eq!(names(&tx, 18)?, vec!["Carol", "Dave"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_in_place() -> ResultTest<()> {
+        // Only the indexes whose key changes are rewritten.
+        let (old, new) = (u32_str_u32(1, "Foo", 18), u32_str_u32(1, "Bar", 18));
+        let (old_id, new_id) = (RowId(old.to_data_key()), RowId(new.to_data_key()));
+        let index = |col_id: u32, index_type| {
+            let cols = NonEmpty::new(ColId(col_id));
+            let mut index = TableIndex::new(index_type, IndexId(0), TableId(0), cols, "".into(), true);
+            index.insert(&old).map(|_| index)
+        };
+        let mut age_index = index(2, IndexType::Hash)?;
+        assert!(
+            !age_index.update(&old, &old_id, &new, new_id)?,
+            "The age index is not rebuilt"
+        );
+        let seek = |index: &TableIndex, value: AlgebraicValue| index.seek(&value).unwrap().copied().collect_vec();
+        assert_eq!(seek(&age_index, 18u32.into()), [new_id]);
+        let mut name_index = index(1, IndexType::BTree)?;
+        assert!(name_index.update(&old, &old_id, &new, new_id)?);
+        assert!(seek(&name_index, "Foo".into()).is_empty());
+        assert_eq!(seek(&name_index, "Bar".into()), [new_id]);
+
+        // A committed row is updated in place at commit, and reported as an update.
+        let (datastore, mut tx, table_id) = setup_table()?;
+        let index_def = IndexDef::new("age_idx".into(), table_id, 2.into(), true).with_index_type(IndexType::Hash);
+        datastore.create_index_mut_tx(&mut tx, index_def)?;
+        let row = datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Foo", 18))?;
+        datastore.commit_mut_tx(tx)?;
+        let mut tx = datastore.begin_mut_tx();
+        // The unchanged unique `id` and `age` don't conflict with the row they come from.
+        assert!(datastore.update_mut_tx(&mut tx, table_id, RowId(row.to_data_key()), new.clone())?);
+        let tx_data = datastore.commit_mut_tx(tx)?.unwrap();
+        assert_eq!(tx_data.records.len(), 1);
+        assert!(matches!(tx_data.records[0].op, TxOp::Update { ref old_row, .. } if *old_row == row));
+
+        let tx = datastore.begin_mut_tx();
+        assert_eq!(all_rows(&datastore, &tx, table_id), [new]);
+        let rows_eq = |col_id: u32, value: AlgebraicValue| -> ResultTest<Vec<ProductValue>> {
+            Ok(datastore
+                .iter_by_col_eq_mut_tx(&tx, table_id, ColId(col_id), value)?
+                .map(|row| row.view().clone())
+                .collect())
+        };
+        assert_eq!(rows_eq(2, 18u32.into())?.len(), 1);
+        assert!(rows_eq(1, "Foo".into())?.is_empty());
+        assert_eq!(rows_eq(1, "Bar".into())?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay_legacy_system_rows() -> ResultTest<()> {
+        let datastore = get_datastore()?;
+        let odb: Arc<std::sync::Mutex<Box<dyn ObjectDB + Send>>> =
+            Arc::new(std::sync::Mutex::new(Box::<MemoryObjectDB>::default()));
+        let write = |operation, table_id: TableId, row: ProductValue| {
+            let mut bytes = Vec::new();
+            row.encode(&mut bytes);
+            let data_key = DataKey::from_data(&bytes);
+            if let DataKey::Hash(_) = data_key {
+                odb.lock().unwrap().add(bytes);
+            }
+            Write {
+                operation,
+                set_id: table_id.0,
+                data_key,
+            }
+        };
+
+        // The rows of `st_indexes` and `st_constraints` as written before their columns were added.
+        let legacy_index = |index_id: u32, col_id: u32, name: &str| {
+            product![index_id, 6u32, ArrayValue::from(vec![col_id]), name, false]
+        };
+        let legacy_constraint = product![
+            6u32,
+            "ct_Foo_id",
+            u32::from(ColumnIndexAttribute::INDEXED.bits()),
+            6u32,
+            ArrayValue::from(vec![0u32])
+        ];
+        let transaction = Transaction {
+            writes: vec![
+                write(
+                    Operation::Insert,
+                    ST_TABLES_ID,
+                    table_row(6, "Foo", StTableType::User, StAccess::Public).into(),
+                ),
+                write(
+                    Operation::Insert,
+                    ST_COLUMNS_ID,
+                    column_row(6, 0, "id", AlgebraicType::U32, false).into(),
+                ),
+                write(
+                    Operation::Insert,
+                    ST_COLUMNS_ID,
+                    column_row(6, 1, "age", AlgebraicType::U32, false).into(),
+                ),
+                write(Operation::Insert, ST_INDEXES_ID, legacy_index(6, 0, "id_idx")),
+                write(Operation::Insert, ST_INDEXES_ID, legacy_index(7, 1, "age_idx")),
+                write(Operation::Insert, ST_CONSTRAINTS_ID, legacy_constraint),
+                write(Operation::Insert, TableId(6), product![1u32, 18u32]),
+            ],
+        };
+        datastore.replay_transaction(&transaction, odb.clone())?;
+        let transaction = Transaction {
+            writes: vec![write(Operation::Delete, ST_INDEXES_ID, legacy_index(7, 1, "age_idx"))],
+        };
+        datastore.replay_transaction(&transaction, odb.clone())?;
+        datastore.rebuild_state_after_replay()?;
+
+        let tx = datastore.begin_mut_tx();
+        let index_rows = datastore
+            .iter_by_col_eq_mut_tx(&tx, ST_INDEXES_ID, ColId(1), AlgebraicValue::U32(6))?
+            .map(|x| StIndexRow::try_from(x.view()).unwrap().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(index_rows, vec![index_row(6, 6, 0, "id_idx", false)]);
+        let constraint_rows = datastore
+            .iter_by_col_eq_mut_tx(&tx, ST_CONSTRAINTS_ID, ColId(3), AlgebraicValue::U32(6))?
+            .map(|x| StConstraintRow::try_from(x.view()).unwrap().to_owned())
+            .collect::<Vec<_>>();
+        #[rustfmt::skip]
+        assert_eq!(constraint_rows, vec![
+            StConstraintRow{ constraint_id: 6.into(), constraint_name: "ct_Foo_id".to_string(), kind: ColumnIndexAttribute::INDEXED, table_id: 6.into(), columns: vec![0.into()], foreign_key: None, check: None },
+        ]);
+        let iter = datastore.iter_by_col_eq_mut_tx(&tx, TableId(6), ColId(0), AlgebraicValue::U32(1))?;
+        assert!(!matches!(iter, IterByColRange::Scan(_)));
+        assert_eq!(
+            iter.map(|row| row.view().clone()).collect::<Vec<_>>(),
+            [product![1u32, 18u32]]
+        );
+        datastore.rollback_mut_tx(tx);
+        Ok(())
+    }
+
     #[test]
     fn test_create_index_pre_commit() -> ResultTest<()> {
         let (datastore, tx, table_id) = setup_table()?;
@@ -2757,6 +4032,7 @@ mod tests {
             index_row(6, 6, 0, "id_idx", true),
             index_row(7, 6, 1, "name_idx", true),
             index_row(8, 6, 2, "age_idx", true),
+            index_row(u32::MAX, u32::MAX - 1, 0, "scheduled_id_idx", true),
         ]);
         let row = u32_str_u32(0, "Bar", 18); // 0 will be ignored.
         let result = datastore.insert_mut_tx(&mut tx, table_id, row);
@@ -2802,6 +4078,7 @@ mod tests {
             index_row(6, 6, 0, "id_idx", true),
             index_row(7, 6, 1, "name_idx", true),
             index_row(8, 6, 2, "age_idx", true),
+            index_row(u32::MAX, u32::MAX - 1, 0, "scheduled_id_idx", true),
         ]);
 
         let row = u32_str_u32(0, "Bar", 18); // 0 will be ignored.
@@ -2847,6 +4124,7 @@ mod tests {
             index_row(5, 1, 0, "idx_ct_columns_table_id", false),
             index_row(6, 6, 0, "id_idx", true),
             index_row(7, 6, 1, "name_idx", true),
+            index_row(u32::MAX, u32::MAX - 1, 0, "scheduled_id_idx", true),
         ]);
         let row = u32_str_u32(0, "Bar", 18); // 0 will be ignored.
         datastore.insert_mut_tx(&mut tx, table_id, row)?;
@@ -2903,6 +4181,247 @@ mod tests {
         Ok(())
     }
 
+    fn pet_table_schema() -> TableDef {
+        TableDef {
+            table_name: "Pet".into(),
+            columns: vec![
+                ColumnDef {
+                    col_name: "name".into(),
+                    col_type: AlgebraicType::String,
+                    is_autoinc: false,
+                },
+                ColumnDef {
+                    col_name: "owner".into(),
+                    col_type: AlgebraicType::U32,
+                    is_autoinc: false,
+                },
+            ],
+            indexes: vec![IndexDef::new("owner_idx".into(), 0.into(), 1.into(), false)],
+            table_type: StTableType::User,
+            table_access: StAccess::Public,
+        }
+    }
+
+    fn pet_owner_fkey(table_id: TableId, ref_table_id: TableId, on_delete: ReferentialAction) -> ForeignKeyDef {
+        ForeignKeyDef {
+            constraint_name: "Pet_owner_fkey".into(),
+            table_id,
+            columns: NonEmpty::new(1.into()),
+            ref_table_id,
+            ref_columns: NonEmpty::new(0.into()),
+            on_delete,
+        }
+    }
+
+    /// Creates the table `Foo` with the row `(1, "Foo", 18)`,
+    /// and the table `Pet` with the row `("Rex", 1)` referencing it.
+    fn setup_foreign_key(on_delete: ReferentialAction) -> ResultTest<(Locking, TableId, TableId)> {
+        let (datastore, mut tx, table_id) = setup_table()?;
+        let pet_id = datastore.create_table_mut_tx(&mut tx, pet_table_schema())?;
+        datastore.create_foreign_key_mut_tx(&mut tx, pet_owner_fkey(pet_id, table_id, on_delete))?;
+        datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Foo", 18))?;
+        datastore.insert_mut_tx(&mut tx, pet_id, product!["Rex", 1u32])?;
+        datastore.commit_mut_tx(tx)?;
+        Ok((datastore, table_id, pet_id))
+    }
+
+    #[test]
+    fn test_foreign_key_insert() -> ResultTest<()> {
+        let (datastore, table_id, pet_id) = setup_foreign_key(ReferentialAction::Restrict)?;
+
+        let mut tx = datastore.begin_mut_tx();
+        datastore.insert_mut_tx(&mut tx, pet_id, product!["Tom", 2u32])?;
+        match datastore.commit_mut_tx(tx) {
+            Err(DBError::Constraint(ConstraintError::ForeignKeyViolation { value, .. })) => {
+                assert_eq!(value, AlgebraicValue::U32(2))
+            }
+            _ => panic!("Expected a foreign key violation error."),
+        }
+
+        // The referenced row can be inserted after the referencing one.
+        let mut tx = datastore.begin_mut_tx();
+        datastore.insert_mut_tx(&mut tx, pet_id, product!["Tom", 2u32])?;
+        datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Bar", 20))?;
+        datastore.commit_mut_tx(tx)?;
+
+        let tx = datastore.begin_mut_tx();
+        #[rustfmt::skip]
+        assert_eq!(all_rows(&datastore, &tx, pet_id), vec![product!["Rex", 1u32], product!["Tom", 2u32]]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_foreign_key_restrict() -> ResultTest<()> {
+        let (datastore, table_id, pet_id) = setup_foreign_key(ReferentialAction::Restrict)?;
+
+        let mut tx = datastore.begin_mut_tx();
+        datastore.delete_by_rel_mut_tx(&mut tx, table_id, [u32_str_u32(1, "Foo", 18)]);
+        match datastore.commit_mut_tx(tx) {
+            Err(DBError::Constraint(ConstraintError::ForeignKeyRestrict { value, .. })) => {
+                assert_eq!(value, AlgebraicValue::U32(1))
+            }
+            _ => panic!("Expected a foreign key violation error."),
+        }
+
+        // Replacing the referenced row by one with the same key is fine.
+        let mut tx = datastore.begin_mut_tx();
+        datastore.delete_by_rel_mut_tx(&mut tx, table_id, [u32_str_u32(1, "Foo", 18)]);
+        datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(1, "Foo", 19))?;
+        datastore.commit_mut_tx(tx)?;
+
+        let tx = datastore.begin_mut_tx();
+        assert_eq!(all_rows(&datastore, &tx, table_id), vec![u32_str_u32(1, "Foo", 19)]);
+        assert_eq!(all_rows(&datastore, &tx, pet_id), vec![product!["Rex", 1u32]]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_foreign_key_cascade() -> ResultTest<()> {
+        let (datastore, table_id, pet_id) = setup_foreign_key(ReferentialAction::Cascade)?;
+
+        let mut tx = datastore.begin_mut_tx();
+        datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Bar", 20))?;
+        datastore.insert_mut_tx(&mut tx, pet_id, product!["Tom", 2u32])?;
+        datastore.insert_mut_tx(&mut tx, pet_id, product!["Felix", 1u32])?;
+        datastore.commit_mut_tx(tx)?;
+
+        let mut tx = datastore.begin_mut_tx();
+        datastore.delete_by_rel_mut_tx(&mut tx, table_id, [u32_str_u32(1, "Foo", 18)]);
+        let tx_data = datastore.commit_mut_tx(tx)?.unwrap();
+        // The deletes of the cascade are part of the transaction.
+        assert_eq!(tx_data.records.len(), 3);
+
+        let tx = datastore.begin_mut_tx();
+        assert_eq!(all_rows(&datastore, &tx, pet_id), vec![product!["Tom", 2u32]]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_foreign_key() -> ResultTest<()> {
+        let (datastore, mut tx, table_id) = setup_table()?;
+        let pet_id = datastore.create_table_mut_tx(
+            &mut tx,
+            TableDef {
+                indexes: vec![],
+                ..pet_table_schema()
+            },
+        )?;
+        datastore.insert_mut_tx(&mut tx, pet_id, product!["Rex", 1u32])?;
+        datastore.commit_mut_tx(tx)?;
+
+        // The referenced column must be unique.
+        let mut tx = datastore.begin_mut_tx();
+        let mut fk = pet_owner_fkey(pet_id, table_id, ReferentialAction::Restrict);
+        fk.ref_columns = NonEmpty::new(2.into());
+        match datastore.create_foreign_key_mut_tx(&mut tx, fk) {
+            Err(DBError::Constraint(ConstraintError::InvalidForeignKey(..))) => (),
+            _ => panic!("Expected an invalid foreign key error."),
+        }
+
+        // The referencing column must be indexed.
+        let fk = pet_owner_fkey(pet_id, table_id, ReferentialAction::Restrict);
+        match datastore.create_foreign_key_mut_tx(&mut tx, fk) {
+            Err(DBError::Constraint(ConstraintError::InvalidForeignKey(..))) => (),
+            _ => panic!("Expected an invalid foreign key error."),
+        }
+        let index = IndexDef::new("owner_idx".into(), pet_id, 1.into(), false);
+        datastore.create_index_mut_tx(&mut tx, index)?;
+        datastore.commit_mut_tx(tx)?;
+        let mut tx = datastore.begin_mut_tx();
+
+        // The rows already in the table are checked too.
+        let fk = pet_owner_fkey(pet_id, table_id, ReferentialAction::Restrict);
+        datastore.create_foreign_key_mut_tx(&mut tx, fk.clone())?;
+        assert!(datastore.commit_mut_tx(tx).is_err());
+
+        let mut tx = datastore.begin_mut_tx();
+        assert!(datastore.foreign_keys_mut_tx(&tx)?.is_empty());
+        datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Foo", 18))?;
+        let constraint_id = datastore.create_foreign_key_mut_tx(&mut tx, fk.clone())?;
+        datastore.commit_mut_tx(tx)?;
+
+        let mut tx = datastore.begin_mut_tx();
+        let fks = datastore.foreign_keys_mut_tx(&tx)?;
+        assert_eq!(fks.len(), 1);
+        assert_eq!(fks[0].constraint_id, constraint_id);
+        assert_eq!(ForeignKeyDef::from(fks[0].clone()), fk);
+        // The key is cached in the schemas of both tables.
+        assert_eq!(datastore.schema_for_table_mut_tx(&tx, pet_id)?.foreign_keys, fks);
+        assert_eq!(datastore.schema_for_table_mut_tx(&tx, table_id)?.foreign_keys, fks);
+
+        // Dropping the referenced table drops the foreign key.
+        datastore.drop_table_mut_tx(&mut tx, table_id)?;
+        assert!(datastore.foreign_keys_mut_tx(&tx)?.is_empty());
+        assert!(datastore.schema_for_table_mut_tx(&tx, pet_id)?.foreign_keys.is_empty());
+        datastore.commit_mut_tx(tx)?;
+
+        let tx = datastore.begin_mut_tx();
+        assert!(!datastore.table_id_exists(&tx, &table_id));
+        assert!(datastore.schema_for_table_mut_tx(&tx, pet_id)?.foreign_keys.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_constraint() -> ResultTest<()> {
+        let (datastore, mut tx, table_id) = setup_table()?;
+        let row = datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Foo", 10))?;
+        let check = |expr: &str| CheckDef {
+            constraint_name: "Foo_check_0".into(),
+            table_id,
+            expr: expr.into(),
+        };
+
+        // The rows already in the table must satisfy the constraint.
+        match datastore.create_check_mut_tx(&mut tx, check("age >= 18")) {
+            Err(DBError::Constraint(ConstraintError::CheckViolation { row: violator, .. })) => {
+                assert_eq!(violator, row)
+            }
+            _ => panic!("Expected a check constraint violation error."),
+        }
+        match datastore.create_check_mut_tx(&mut tx, check("height >= 18")) {
+            Err(DBError::Constraint(ConstraintError::InvalidCheck(..))) => (),
+            _ => panic!("Expected an invalid check constraint error."),
+        }
+        let constraint_id = datastore.create_check_mut_tx(&mut tx, check("age >= 10 AND name <> ''"))?;
+        datastore.commit_mut_tx(tx)?;
+
+        let mut tx = datastore.begin_mut_tx();
+        let checks = datastore.checks_mut_tx(&tx)?;
+        assert_eq!(checks.len(), 1);
+        assert_eq!(checks[0].constraint_id, constraint_id);
+        assert_eq!(CheckDef::from(checks[0].clone()), check("age >= 10 AND name <> ''"));
+        // The predicate is compiled once, into the schema of the table.
+        let schema = datastore.schema_for_table_mut_tx(&tx, table_id)?;
+        assert_eq!(schema.checks.len(), 1);
+        assert_eq!(schema.checks[0].check, checks[0]);
+
+        // Both inserts and updates are checked.
+        assert!(datastore
+            .insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Bar", 9))
+            .is_err());
+        assert!(datastore
+            .insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "", 20))
+            .is_err());
+        datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Bar", 20))?;
+        let row_id = RowId(row.to_data_key());
+        match datastore.update_mut_tx(&mut tx, table_id, row_id, u32_str_u32(1, "Foo", 5)) {
+            Err(DBError::Constraint(ConstraintError::CheckViolation { constraint_name, .. })) => {
+                assert_eq!(constraint_name, "Foo_check_0")
+            }
+            _ => panic!("Expected a check constraint violation error."),
+        }
+        assert!(datastore.update_mut_tx(&mut tx, table_id, row_id, u32_str_u32(1, "Foo", 11))?);
+        let rows = all_rows(&datastore, &tx, table_id);
+        assert_eq!(rows.len(), 2);
+        assert!(rows.contains(&u32_str_u32(1, "Foo", 11)));
+
+        datastore.drop_check_mut_tx(&mut tx, constraint_id)?;
+        datastore.insert_mut_tx(&mut tx, table_id, u32_str_u32(0, "Baz", 9))?;
+        assert!(datastore.checks_mut_tx(&tx)?.is_empty());
+        assert!(datastore.schema_for_table_mut_tx(&tx, table_id)?.checks.is_empty());
+        Ok(())
+    }
+
     // TODO: Add the following tests
     // - Create index with unique constraint and immediately insert a row that violates the constraint before committing.
     // - Create a tx that inserts 2000 rows with an autoinc column
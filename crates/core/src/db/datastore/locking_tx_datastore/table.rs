@@ -1,10 +1,11 @@
 use super::{
-    btree_index::{BTreeIndex, BTreeIndexRangeIter},
+    table_index::{IndexSeekIter, TableIndex},
     RowId,
 };
 use crate::db::datastore::traits::TableSchema;
 use indexmap::IndexMap;
 use nonempty::NonEmpty;
+use spacetimedb_lib::IndexType;
 use spacetimedb_primitives::ColId;
 use spacetimedb_sats::{AlgebraicValue, ProductType, ProductValue};
 use std::{collections::HashMap, ops::RangeBounds};
@@ -12,7 +13,7 @@ use std::{collections::HashMap, ops::RangeBounds};
 pub(crate) struct Table {
     pub(crate) row_type: ProductType,
     pub(crate) schema: TableSchema,
-    pub(crate) indexes: HashMap<NonEmpty<ColId>, BTreeIndex>,
+    pub(crate) indexes: HashMap<NonEmpty<ColId>, TableIndex>,
     pub(crate) rows: IndexMap<RowId, ProductValue>,
 }
 
@@ -26,9 +27,24 @@ impl Table {
         }
     }
 
-    pub(crate) fn insert_index(&mut self, mut index: BTreeIndex) {
+    /// Returns an empty table with the same row type and schema as `self`,
+    /// and an empty index for every index of `self`.
+    pub(crate) fn empty_like(&self) -> Self {
+        Self {
+            row_type: self.row_type.clone(),
+            schema: self.schema.clone(),
+            indexes: self
+                .indexes
+                .iter()
+                .map(|(cols, index)| (cols.clone(), index.empty_like()))
+                .collect(),
+            rows: Default::default(),
+        }
+    }
+
+    pub(crate) fn insert_index(&mut self, mut index: TableIndex) {
         index.build_from_rows(self.scan_rows()).unwrap();
-        self.indexes.insert(index.cols.clone(), index);
+        self.indexes.insert(index.cols().clone(), index);
     }
 
     pub(crate) fn insert(&mut self, row_id: RowId, row: ProductValue) {
@@ -47,6 +63,18 @@ impl Table {
         Some(row)
     }
 
+    /// Replaces the row `row_id` with `row`, stored as `new_row_id`, returning the old row.
+    ///
+    /// Only the indexes whose key differs between the two rows are rewritten.
+    pub(crate) fn update(&mut self, row_id: &RowId, new_row_id: RowId, row: ProductValue) -> Option<ProductValue> {
+        let old_row = self.rows.remove(row_id)?;
+        for index in self.indexes.values_mut() {
+            index.update(&old_row, row_id, &row, new_row_id).unwrap();
+        }
+        self.rows.insert(new_row_id, row);
+        Some(old_row)
+    }
+
     #[tracing::instrument(skip_all)]
     pub(crate) fn get_row(&self, row_id: &RowId) -> Option<&ProductValue> {
         self.rows.get(row_id)
@@ -65,15 +93,30 @@ impl Table {
     }
 
     /// When there's an index for `cols`,
-    /// returns an iterator over the [`BTreeIndex`] that yields all the `RowId`s
+    /// or a btree index on more columns whose leading columns are `cols`,
+    /// returns an iterator over the [`TableIndex`] that yields all the `RowId`s
     /// that match the specified `range` in the indexed column.
     ///
+    /// A hash index on `cols` is only used when `range` is a single value.
+    ///
     /// Matching is defined by `Ord for AlgebraicValue`.
     pub(crate) fn index_seek(
         &self,
         cols: &NonEmpty<ColId>,
         range: &impl RangeBounds<AlgebraicValue>,
-    ) -> Option<BTreeIndexRangeIter<'_>> {
-        self.indexes.get(cols).map(|index| index.seek(range))
+    ) -> Option<IndexSeekIter<'_>> {
+        if let Some(iter) = self.indexes.get(cols).and_then(|index| index.seek(range)) {
+            return Some(iter);
+        }
+        // Prefer the narrowest index, and break ties deterministically.
+        self.indexes
+            .iter()
+            .filter(|(index_cols, index)| {
+                index.index_type() == IndexType::BTree
+                    && index_cols.len() > cols.len()
+                    && index_cols.iter().zip(cols).all(|(a, b)| a == b)
+            })
+            .min_by_key(|(index_cols, _)| (index_cols.len(), *index_cols))
+            .and_then(|(_, index)| index.seek_prefix(cols.len(), range))
     }
 }
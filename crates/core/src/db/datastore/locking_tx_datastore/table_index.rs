@@ -0,0 +1,257 @@
+use super::{
+    btree_index::{BTreeIndex, BTreeIndexRangeIter},
+    hash_index::{HashIndex, HashIndexIter},
+    RowId,
+};
+use crate::{db::datastore::traits::IndexSchema, error::DBError};
+use nonempty::NonEmpty;
+use spacetimedb_lib::IndexType;
+use spacetimedb_primitives::{ColId, IndexId, TableId};
+use spacetimedb_sats::{AlgebraicValue, ProductValue};
+use std::ops::{Bound, RangeBounds};
+
+/// An iterator for the rows that match a value [AlgebraicValue] on a [TableIndex].
+pub enum IndexSeekIter<'a> {
+    BTree(BTreeIndexRangeIter<'a>),
+    Hash(HashIndexIter<'a>),
+}
+
+impl<'a> IndexSeekIter<'a> {
+    /// Advances the iterator, returning the next `RowId` along with its value in the index.
+    fn next_entry(&mut self) -> Option<(&'a AlgebraicValue, &'a RowId)> {
+        match self {
+            Self::BTree(iter) => iter.next_entry(),
+            Self::Hash(iter) => iter.next_entry(),
+        }
+    }
+
+    /// Turns this iterator into one that also yields the value of each `RowId` in the index.
+    pub(crate) fn with_values(self) -> IndexSeekEntries<'a> {
+        IndexSeekEntries { iter: self }
+    }
+}
+
+impl<'a> Iterator for IndexSeekIter<'a> {
+    type Item = &'a RowId;
+
+    #[tracing::instrument(skip_all)]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().map(|(_, row_id)| row_id)
+    }
+}
+
+/// An iterator for the rows that match a value [AlgebraicValue] on a
+/// [TableIndex], along with their values in the index.
+pub struct IndexSeekEntries<'a> {
+    iter: IndexSeekIter<'a>,
+}
+
+impl<'a> Iterator for IndexSeekEntries<'a> {
+    type Item = (&'a AlgebraicValue, &'a RowId);
+
+    #[tracing::instrument(skip_all)]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next_entry()
+    }
+}
+
+/// Returns the only value within `range`, when it is `value..=value`.
+pub(crate) fn as_point(range: &impl RangeBounds<AlgebraicValue>) -> Option<&AlgebraicValue> {
+    match (range.start_bound(), range.end_bound()) {
+        (Bound::Included(start), Bound::Included(end)) if start == end => Some(start),
+        _ => None,
+    }
+}
+
+/// An index of a table, of the kind selected by its [IndexType].
+pub(crate) enum TableIndex {
+    BTree(BTreeIndex),
+    Hash(HashIndex),
+}
+
+impl TableIndex {
+    pub(crate) fn new(
+        index_type: IndexType,
+        index_id: IndexId,
+        table_id: TableId,
+        cols: NonEmpty<ColId>,
+        name: String,
+        is_unique: bool,
+    ) -> Self {
+        match index_type {
+            IndexType::BTree => Self::BTree(BTreeIndex::new(index_id, table_id, cols, name, is_unique)),
+            IndexType::Hash => Self::Hash(HashIndex::new(index_id, table_id, cols, name, is_unique)),
+        }
+    }
+
+    /// Returns an empty index of the same kind and on the same columns as `self`.
+    pub(crate) fn empty_like(&self) -> Self {
+        Self::new(
+            self.index_type(),
+            self.index_id(),
+            self.table_id(),
+            self.cols().clone(),
+            self.name().to_owned(),
+            self.is_unique(),
+        )
+    }
+
+    pub(crate) fn index_type(&self) -> IndexType {
+        match self {
+            Self::BTree(_) => IndexType::BTree,
+            Self::Hash(_) => IndexType::Hash,
+        }
+    }
+
+    pub(crate) fn index_id(&self) -> IndexId {
+        match self {
+            Self::BTree(index) => index.index_id,
+            Self::Hash(index) => index.index_id,
+        }
+    }
+
+    pub(crate) fn table_id(&self) -> TableId {
+        match self {
+            Self::BTree(index) => index.table_id,
+            Self::Hash(index) => index.table_id,
+        }
+    }
+
+    pub(crate) fn cols(&self) -> &NonEmpty<ColId> {
+        match self {
+            Self::BTree(index) => &index.cols,
+            Self::Hash(index) => &index.cols,
+        }
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        match self {
+            Self::BTree(index) => &index.name,
+            Self::Hash(index) => &index.name,
+        }
+    }
+
+    pub(crate) fn is_unique(&self) -> bool {
+        match self {
+            Self::BTree(index) => index.is_unique,
+            Self::Hash(index) => index.is_unique,
+        }
+    }
+
+    pub(crate) fn get_fields(&self, row: &ProductValue) -> Result<AlgebraicValue, DBError> {
+        match self {
+            Self::BTree(index) => index.get_fields(row),
+            Self::Hash(index) => index.get_fields(row),
+        }
+    }
+
+    pub(crate) fn insert(&mut self, row: &ProductValue) -> Result<(), DBError> {
+        match self {
+            Self::BTree(index) => index.insert(row),
+            Self::Hash(index) => index.insert(row),
+        }
+    }
+
+    pub(crate) fn delete(&mut self, col_value: &AlgebraicValue, row_id: &RowId) {
+        match self {
+            Self::BTree(index) => index.delete(col_value, row_id),
+            Self::Hash(index) => index.delete(col_value, row_id),
+        }
+    }
+
+    /// Moves the entry of the row `row_id`, replaced by `new_row` stored as `new_row_id`,
+    /// returning whether its key changed.
+    ///
+    /// Only when it did is the entry removed and inserted again under the new key,
+    /// otherwise it is left under its key and just points to the new row.
+    pub(crate) fn update(
+        &mut self,
+        old_row: &ProductValue,
+        row_id: &RowId,
+        new_row: &ProductValue,
+        new_row_id: RowId,
+    ) -> Result<bool, DBError> {
+        let old_value = self.get_fields(old_row)?;
+        let new_value = self.get_fields(new_row)?;
+        if old_value == new_value {
+            match self {
+                Self::BTree(index) => index.replace_row_id(&old_value, row_id, new_row_id),
+                Self::Hash(index) => index.replace_row_id(&old_value, row_id, new_row_id),
+            }
+            return Ok(false);
+        }
+        self.delete(&old_value, row_id);
+        self.insert(new_row)?;
+        Ok(true)
+    }
+
+    pub(crate) fn violates_unique_constraint(&self, row: &ProductValue) -> bool {
+        match self {
+            Self::BTree(index) => index.violates_unique_constraint(row),
+            Self::Hash(index) => index.violates_unique_constraint(row),
+        }
+    }
+
+    pub(crate) fn get_rows_that_violate_unique_constraint<'a>(
+        &'a self,
+        value: &'a AlgebraicValue,
+    ) -> Option<IndexSeekIter<'a>> {
+        match self {
+            Self::BTree(index) => index
+                .get_rows_that_violate_unique_constraint(value)
+                .map(IndexSeekIter::BTree),
+            Self::Hash(index) => index
+                .get_rows_that_violate_unique_constraint(value)
+                .map(IndexSeekIter::Hash),
+        }
+    }
+
+    /// Returns an iterator over the [TableIndex] that yields all the `RowId`s
+    /// that fall within the specified `range`.
+    ///
+    /// A [HashIndex] can only seek a single value, so this returns `None`
+    /// for any other `range` on it.
+    pub(crate) fn seek<'a>(&'a self, range: &impl RangeBounds<AlgebraicValue>) -> Option<IndexSeekIter<'a>> {
+        match self {
+            Self::BTree(index) => Some(IndexSeekIter::BTree(index.seek(range))),
+            Self::Hash(index) => as_point(range).map(|value| IndexSeekIter::Hash(index.seek(value))),
+        }
+    }
+
+    /// Returns an iterator over the [TableIndex] that yields all the `RowId`s
+    /// where the values of the first `prefix_len` indexed columns fall within `range`.
+    ///
+    /// Only a [BTreeIndex] can seek a prefix of its columns,
+    /// so this returns `None` on a [HashIndex].
+    pub(crate) fn seek_prefix<'a>(
+        &'a self,
+        prefix_len: usize,
+        range: &impl RangeBounds<AlgebraicValue>,
+    ) -> Option<IndexSeekIter<'a>> {
+        match self {
+            Self::BTree(index) => Some(IndexSeekIter::BTree(index.seek_prefix(prefix_len, range))),
+            Self::Hash(_) => None,
+        }
+    }
+
+    /// Construct the [TableIndex] from the rows.
+    pub(crate) fn build_from_rows<'a>(&mut self, rows: impl Iterator<Item = &'a ProductValue>) -> Result<(), DBError> {
+        match self {
+            Self::BTree(index) => index.build_from_rows(rows),
+            Self::Hash(index) => index.build_from_rows(rows),
+        }
+    }
+}
+
+impl From<&TableIndex> for IndexSchema {
+    fn from(x: &TableIndex) -> Self {
+        IndexSchema {
+            index_id: x.index_id(),
+            table_id: x.table_id(),
+            cols: x.cols().clone(),
+            is_unique: x.is_unique(),
+            index_name: x.name().to_owned(),
+            index_type: x.index_type(),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+pub mod check;
 pub mod locking_tx_datastore;
 pub mod system_tables;
 pub mod traits;
@@ -1,11 +1,11 @@
-use super::traits::{ColumnSchema, IndexSchema, SequenceSchema, TableSchema};
+use super::traits::{CheckSchema, ColumnSchema, ForeignKeySchema, IndexSchema, SequenceSchema, TableSchema};
 use crate::db::datastore::traits::ConstraintSchema;
 use crate::error::{DBError, TableError};
 use core::fmt;
 use nonempty::NonEmpty;
 use once_cell::sync::Lazy;
 use spacetimedb_lib::auth::{StAccess, StTableType};
-use spacetimedb_lib::{ColumnIndexAttribute, Hash};
+use spacetimedb_lib::{ColumnIndexAttribute, Hash, IndexType, ReferentialAction};
 use spacetimedb_primitives::{ColId, IndexId, SequenceId, TableId};
 use spacetimedb_sats::{
     impl_deserialize, impl_serialize, product, product_value::InvalidFieldError, AlgebraicType, AlgebraicValue,
@@ -25,6 +25,17 @@ pub(crate) const ST_CONSTRAINTS_ID: TableId = TableId(4);
 /// The static ID of the table that defines the stdb module associated with
 /// the database
 pub(crate) const ST_MODULE_ID: TableId = TableId(5);
+/// The static ID of the table that defines the row-level security policies
+pub(crate) const ST_ROW_POLICIES_ID: TableId = TableId(u32::MAX);
+/// The static ID of the table that holds the scheduled reducer calls
+pub(crate) const ST_SCHEDULED_ID: TableId = TableId(u32::MAX - 1);
+
+/// The ids from here on are reserved for the system tables added after the first release,
+/// along with their indexes, constraints and sequences, which count down from `u32::MAX`.
+///
+/// The ids of the user tables, indexes, constraints and sequences are allocated right after
+/// the ones of the first system tables, so those of existing databases are already taken.
+pub(crate) const ST_RESERVED_IDS_START: u32 = u32::MAX - 1023;
 
 pub(crate) const ST_TABLES_NAME: &str = "st_table";
 pub(crate) const ST_COLUMNS_NAME: &str = "st_columns";
@@ -32,11 +43,14 @@ pub(crate) const ST_SEQUENCES_NAME: &str = "st_sequence";
 pub(crate) const ST_INDEXES_NAME: &str = "st_indexes";
 pub(crate) const ST_CONSTRAINTS_NAME: &str = "st_constraints";
 pub(crate) const ST_MODULE_NAME: &str = "st_module";
+pub(crate) const ST_ROW_POLICIES_NAME: &str = "st_row_policy";
+pub(crate) const ST_SCHEDULED_NAME: &str = "st_scheduled";
 
 pub(crate) const TABLE_ID_SEQUENCE_ID: SequenceId = SequenceId(0);
 pub(crate) const SEQUENCE_ID_SEQUENCE_ID: SequenceId = SequenceId(1);
 pub(crate) const INDEX_ID_SEQUENCE_ID: SequenceId = SequenceId(2);
 pub(crate) const CONSTRAINT_ID_SEQUENCE_ID: SequenceId = SequenceId(3);
+pub(crate) const SCHEDULED_ID_SEQUENCE_ID: SequenceId = SequenceId(u32::MAX);
 
 pub(crate) const ST_TABLE_ID_INDEX_ID: IndexId = IndexId(0);
 pub(crate) const ST_TABLE_NAME_INDEX_ID: IndexId = IndexId(3);
@@ -44,10 +58,11 @@ pub(crate) const ST_INDEX_ID_INDEX_ID: IndexId = IndexId(1);
 pub(crate) const ST_SEQUENCE_ID_INDEX_ID: IndexId = IndexId(2);
 pub(crate) const ST_CONSTRAINT_ID_INDEX_ID: IndexId = IndexId(4);
 pub(crate) const ST_CONSTRAINT_ID_INDEX_HACK: IndexId = IndexId(5);
+pub(crate) const ST_SCHEDULED_ID_INDEX_ID: IndexId = IndexId(u32::MAX);
 pub(crate) struct SystemTables {}
 
 impl SystemTables {
-    pub(crate) fn tables() -> [TableSchema; 6] {
+    pub(crate) fn tables() -> [TableSchema; 8] {
         [
             st_table_schema(),
             st_columns_schema(),
@@ -55,34 +70,44 @@ impl SystemTables {
             st_indexes_schema(),
             st_constraints_schema(),
             st_module_schema(),
+            st_row_policies_schema(),
+            st_scheduled_schema(),
         ]
     }
 
+    /// The system tables of the first release, whose ids start from `0`.
+    ///
+    /// The totals below count only these, as they are where the sequences of ids start,
+    /// which must stay the same for the databases created since.
+    fn first_tables() -> impl Iterator<Item = TableSchema> {
+        Self::tables()
+            .into_iter()
+            .filter(|x| x.table_id.0 < ST_RESERVED_IDS_START)
+    }
+
     pub(crate) fn total_tables() -> usize {
-        Self::tables().len()
+        Self::first_tables().count()
     }
 
     pub(crate) fn total_indexes() -> usize {
-        Self::tables().iter().flat_map(|x| x.indexes.iter()).count()
+        Self::first_tables().flat_map(|x| x.indexes).count()
     }
 
     pub(crate) fn total_constraints_indexes() -> usize {
-        Self::tables()
-            .iter()
-            .flat_map(|x| x.constraints.iter().filter(|x| x.kind != ColumnIndexAttribute::UNSET))
+        Self::first_tables()
+            .flat_map(|x| {
+                x.constraints
+                    .into_iter()
+                    .filter(|x| x.kind != ColumnIndexAttribute::UNSET)
+            })
             .count()
     }
 
     pub(crate) fn total_sequences() -> usize {
-        Self::tables()
-            .iter()
-            .flat_map(|x| x.columns.iter().filter(|x| x.is_autoinc))
+        Self::first_tables()
+            .flat_map(|x| x.columns.into_iter().filter(|x| x.is_autoinc))
             .count()
     }
-
-    pub(crate) fn total_constraints() -> usize {
-        Self::tables().iter().flat_map(|x| x.constraints.iter()).count()
-    }
 }
 
 macro_rules! st_fields_enum {
@@ -139,6 +164,7 @@ st_fields_enum!(enum StIndexFields {
     "cols", Cols = 2,
     "index_name", IndexName = 3,
     "is_unique", IsUnique = 4,
+    "index_type", IndexType = 5,
 });
 
 // WARNING: In order to keep a stable schema, don't change the discriminant of the fields
@@ -164,6 +190,10 @@ st_fields_enum!(enum StConstraintFields {
     "kind", Kind = 2,
     "table_id", TableId = 3,
     "columns", Columns = 4,
+    "ref_table_id", RefTableId = 5,
+    "ref_columns", RefColumns = 6,
+    "on_delete", OnDelete = 7,
+    "check", Check = 8,
 });
 
 // WARNING: In order to keep a stable schema, don't change the discriminant of the fields
@@ -173,6 +203,19 @@ st_fields_enum!(enum StModuleFields {
     "kind", Kind = 1,
     "epoch", Epoch = 2,
 });
+// WARNING: In order to keep a stable schema, don't change the discriminant of the fields
+st_fields_enum!(enum StRowPolicyFields {
+    "table_id", TableId = 0,
+    "policy", Policy = 1,
+});
+// WARNING: In order to keep a stable schema, don't change the discriminant of the fields
+st_fields_enum!(enum StScheduledFields {
+    "scheduled_id", ScheduledId = 0,
+    "reducer", Reducer = 1,
+    "args", Args = 2,
+    "scheduled_at", ScheduledAt = 3,
+    "recurring", Recurring = 4,
+});
 
 /// System Table [ST_TABLES_NAME]
 ///
@@ -190,6 +233,7 @@ pub fn st_table_schema() -> TableSchema {
                 cols: NonEmpty::new(StTableFields::TableId.col_id()),
                 index_name: "table_id_idx".into(),
                 is_unique: true,
+                index_type: IndexType::BTree,
             },
             IndexSchema {
                 index_id: ST_TABLE_NAME_INDEX_ID,
@@ -197,6 +241,7 @@ pub fn st_table_schema() -> TableSchema {
                 cols: NonEmpty::new(StTableFields::TableName.col_id()),
                 index_name: "table_name_idx".into(),
                 is_unique: true,
+                index_type: IndexType::BTree,
             },
         ],
         columns: vec![
@@ -230,6 +275,8 @@ pub fn st_table_schema() -> TableSchema {
             },
         ],
         constraints: vec![],
+        foreign_keys: vec![],
+        checks: vec![],
         table_type: StTableType::System,
         table_access: StAccess::Public,
     }
@@ -294,6 +341,8 @@ pub fn st_columns_schema() -> TableSchema {
             //TODO: Change to multi-columns when PR for it land: StColumnFields::ColId as u32
             columns: vec![StColumnFields::TableId.col_id()],
         }],
+        foreign_keys: vec![],
+        checks: vec![],
         table_type: StTableType::System,
         table_access: StAccess::Public,
     }
@@ -304,9 +353,11 @@ pub static ST_COLUMNS_ROW_TYPE: Lazy<ProductType> =
 
 /// System Table [ST_INDEXES]
 ///
-/// | index_id: IndexId | table_id: TableId | cols: NonEmpty<ColId> | index_name: String | is_unique: bool      |
-/// |---------------|---------------|---------------------|--------------------|----------------------|
-/// | 1             | 1             | [1]                 | "ix_sample"        | 0                    |
+/// | index_id: IndexId | table_id: TableId | cols: NonEmpty<ColId> | index_name: String | is_unique: bool      | index_type: u8 |
+/// |---------------|---------------|---------------------|--------------------|----------------------|----------------|
+/// | 1             | 1             | [1]                 | "ix_sample"        | 0                    | 0              |
+///
+/// `index_type` is [IndexType::to_u8] of the kind of the index.
 pub fn st_indexes_schema() -> TableSchema {
     TableSchema {
         table_id: ST_INDEXES_ID,
@@ -318,6 +369,7 @@ pub fn st_indexes_schema() -> TableSchema {
             cols: NonEmpty::new(0.into()),
             index_name: "index_id_idx".into(),
             is_unique: true,
+            index_type: IndexType::BTree,
         }],
         columns: vec![
             ColumnSchema {
@@ -355,8 +407,17 @@ pub fn st_indexes_schema() -> TableSchema {
                 col_type: AlgebraicType::Bool,
                 is_autoinc: false,
             },
+            ColumnSchema {
+                table_id: ST_INDEXES_ID,
+                col_id: 5.into(),
+                col_name: "index_type".into(),
+                col_type: AlgebraicType::U8,
+                is_autoinc: false,
+            },
         ],
         constraints: vec![],
+        foreign_keys: vec![],
+        checks: vec![],
         table_type: StTableType::System,
         table_access: StAccess::Public,
     }
@@ -381,6 +442,7 @@ pub(crate) fn st_sequences_schema() -> TableSchema {
             cols: NonEmpty::new(0.into()),
             index_name: "sequences_id_idx".into(),
             is_unique: true,
+            index_type: IndexType::BTree,
         }],
         columns: vec![
             ColumnSchema {
@@ -448,6 +510,8 @@ pub(crate) fn st_sequences_schema() -> TableSchema {
             },
         ],
         constraints: vec![],
+        foreign_keys: vec![],
+        checks: vec![],
         table_type: StTableType::System,
         table_access: StAccess::Public,
     }
@@ -461,6 +525,10 @@ pub static ST_SEQUENCE_ROW_TYPE: Lazy<ProductType> =
 /// | constraint_id | constraint_name      | kind | table_id | columns |
 /// |---------------|-------------------- -|-----------|-------|-----------|
 /// | 1             | "unique_customer_id" | 1         | 100   | [1, 4]        |
+///
+/// The columns `ref_table_id`, `ref_columns` and `on_delete` describe the referenced side
+/// of the constraints of kind [ColumnIndexAttribute::FOREIGN_KEY], and are empty for the others.
+/// Likewise, the column `check` holds the `SQL` predicate of the constraints of kind [ColumnIndexAttribute::CHECK].
 pub(crate) fn st_constraints_schema() -> TableSchema {
     TableSchema {
         table_id: ST_CONSTRAINTS_ID,
@@ -472,6 +540,7 @@ pub(crate) fn st_constraints_schema() -> TableSchema {
             cols: NonEmpty::new(0.into()),
             index_name: "constraint_id_idx".into(),
             is_unique: true,
+            index_type: IndexType::BTree,
         }],
         columns: vec![
             ColumnSchema {
@@ -509,8 +578,38 @@ pub(crate) fn st_constraints_schema() -> TableSchema {
                 col_type: AlgebraicType::array(AlgebraicType::U32),
                 is_autoinc: false,
             },
+            ColumnSchema {
+                table_id: ST_CONSTRAINTS_ID,
+                col_id: StConstraintFields::RefTableId.col_id(),
+                col_name: StConstraintFields::RefTableId.col_name(),
+                col_type: AlgebraicType::U32,
+                is_autoinc: false,
+            },
+            ColumnSchema {
+                table_id: ST_CONSTRAINTS_ID,
+                col_id: StConstraintFields::RefColumns.col_id(),
+                col_name: StConstraintFields::RefColumns.col_name(),
+                col_type: AlgebraicType::array(AlgebraicType::U32),
+                is_autoinc: false,
+            },
+            ColumnSchema {
+                table_id: ST_CONSTRAINTS_ID,
+                col_id: StConstraintFields::OnDelete.col_id(),
+                col_name: StConstraintFields::OnDelete.col_name(),
+                col_type: AlgebraicType::U8,
+                is_autoinc: false,
+            },
+            ColumnSchema {
+                table_id: ST_CONSTRAINTS_ID,
+                col_id: StConstraintFields::Check.col_id(),
+                col_name: StConstraintFields::Check.col_name(),
+                col_type: AlgebraicType::String,
+                is_autoinc: false,
+            },
         ],
         constraints: vec![],
+        foreign_keys: vec![],
+        checks: vec![],
         table_type: StTableType::System,
         table_access: StAccess::Public,
     }
@@ -519,6 +618,43 @@ pub(crate) fn st_constraints_schema() -> TableSchema {
 pub static ST_CONSTRAINT_ROW_TYPE: Lazy<ProductType> =
     Lazy::new(|| ProductType::from_iter(st_constraints_schema().columns.iter().map(|c| c.col_type.clone())));
 
+/// The rows of [ST_CONSTRAINTS_NAME] before the columns of foreign keys and check constraints were added.
+static ST_CONSTRAINT_ROW_TYPE_V0: Lazy<ProductType> = Lazy::new(|| ProductType {
+    elements: ST_CONSTRAINT_ROW_TYPE.elements[..StConstraintFields::RefTableId as usize].to_vec(),
+});
+
+/// The rows of [ST_INDEXES_NAME] before the column `index_type` was added, when every index was a btree.
+static ST_INDEX_ROW_TYPE_V0: Lazy<ProductType> = Lazy::new(|| ProductType {
+    elements: ST_INDEX_ROW_TYPE.elements[..StIndexFields::IndexType as usize].to_vec(),
+});
+
+/// Decodes a row of the system table `table_id` written in the layout of an earlier release,
+/// filling in the columns added since with the values they have for such a row.
+///
+/// Returns `None` if `table_id` never had another layout, or if `bytes` doesn't hold exactly one row of it.
+pub(crate) fn decode_legacy_row(table_id: TableId, bytes: &[u8]) -> Option<ProductValue> {
+    let (row_type, added): (&ProductType, Vec<AlgebraicValue>) = match table_id {
+        ST_INDEXES_ID => (&ST_INDEX_ROW_TYPE_V0, vec![IndexType::BTree.to_u8().into()]),
+        ST_CONSTRAINTS_ID => (
+            &ST_CONSTRAINT_ROW_TYPE_V0,
+            vec![
+                TableId(0).into(),
+                ArrayValue::U32(Vec::new()).into(),
+                ReferentialAction::Restrict.to_u8().into(),
+                String::new().into(),
+            ],
+        ),
+        _ => return None,
+    };
+    let bytes = &mut &bytes[..];
+    let mut row = ProductValue::decode(row_type, bytes).ok()?;
+    if !bytes.is_empty() {
+        return None;
+    }
+    row.elements.extend(added);
+    Some(row)
+}
+
 /// System table [ST_MODULE_NAME]
 ///
 /// This table holds exactly one row, describing the latest version of the
@@ -560,6 +696,8 @@ pub(crate) fn st_module_schema() -> TableSchema {
             },
         ],
         constraints: vec![],
+        foreign_keys: vec![],
+        checks: vec![],
         table_type: StTableType::System,
         table_access: StAccess::Public,
     }
@@ -568,10 +706,128 @@ pub(crate) fn st_module_schema() -> TableSchema {
 pub static ST_MODULE_ROW_TYPE: Lazy<ProductType> =
     Lazy::new(|| ProductType::from_iter(st_module_schema().columns.iter().map(|c| c.col_type.clone())));
 
+/// System Table [ST_ROW_POLICIES_NAME]
+///
+/// The row-level security policy of a table, as the `SQL` predicate a row must satisfy
+/// to be visible to an identity other than the owner, who is bound to `:sender`.
+///
+/// | table_id | policy            |
+/// |----------|-------------------|
+/// | 100      | "owner = :sender" |
+pub(crate) fn st_row_policies_schema() -> TableSchema {
+    TableSchema {
+        table_id: ST_ROW_POLICIES_ID,
+        table_name: ST_ROW_POLICIES_NAME.into(),
+        indexes: vec![],
+        columns: vec![
+            ColumnSchema {
+                table_id: ST_ROW_POLICIES_ID,
+                col_id: StRowPolicyFields::TableId.col_id(),
+                col_name: StRowPolicyFields::TableId.col_name(),
+                col_type: AlgebraicType::U32,
+                is_autoinc: false,
+            },
+            ColumnSchema {
+                table_id: ST_ROW_POLICIES_ID,
+                col_id: StRowPolicyFields::Policy.col_id(),
+                col_name: StRowPolicyFields::Policy.col_name(),
+                col_type: AlgebraicType::String,
+                is_autoinc: false,
+            },
+        ],
+        constraints: vec![],
+        foreign_keys: vec![],
+        checks: vec![],
+        table_type: StTableType::System,
+        table_access: StAccess::Public,
+    }
+}
+
+pub static ST_ROW_POLICY_ROW_TYPE: Lazy<ProductType> =
+    Lazy::new(|| ProductType::from_iter(st_row_policies_schema().columns.iter().map(|c| c.col_type.clone())));
+
+/// System Table [ST_SCHEDULED_NAME]
+///
+/// The reducer calls scheduled by the module, with their `BSATN` encoded arguments,
+/// to be made at `scheduled_at`, in microseconds since the unix epoch.
+/// A row is written in the transaction of the reducer that schedules the call,
+/// and deleted once the call is made or cancelled.
+///
+/// The `recurring` rows are the next calls of the schedules declared by the module,
+/// with the `BSATN` encoded [spacetimedb_lib::ScheduleDef] in place of the arguments.
+/// They are moved to the following call once the call is made.
+///
+/// | scheduled_id | reducer      | args      | scheduled_at     | recurring |
+/// |--------------|--------------|-----------|------------------|-----------|
+/// | 1            | "send_email" | [1, 0, 0] | 1697526000000000 | false     |
+pub(crate) fn st_scheduled_schema() -> TableSchema {
+    TableSchema {
+        table_id: ST_SCHEDULED_ID,
+        table_name: ST_SCHEDULED_NAME.into(),
+        indexes: vec![IndexSchema {
+            index_id: ST_SCHEDULED_ID_INDEX_ID,
+            table_id: ST_SCHEDULED_ID,
+            cols: NonEmpty::new(StScheduledFields::ScheduledId.col_id()),
+            index_name: "scheduled_id_idx".into(),
+            is_unique: true,
+            index_type: IndexType::BTree,
+        }],
+        columns: vec![
+            ColumnSchema {
+                table_id: ST_SCHEDULED_ID,
+                col_id: StScheduledFields::ScheduledId.col_id(),
+                col_name: StScheduledFields::ScheduledId.col_name(),
+                col_type: AlgebraicType::U64,
+                is_autoinc: true,
+            },
+            ColumnSchema {
+                table_id: ST_SCHEDULED_ID,
+                col_id: StScheduledFields::Reducer.col_id(),
+                col_name: StScheduledFields::Reducer.col_name(),
+                col_type: AlgebraicType::String,
+                is_autoinc: false,
+            },
+            ColumnSchema {
+                table_id: ST_SCHEDULED_ID,
+                col_id: StScheduledFields::Args.col_id(),
+                col_name: StScheduledFields::Args.col_name(),
+                col_type: AlgebraicType::bytes(),
+                is_autoinc: false,
+            },
+            ColumnSchema {
+                table_id: ST_SCHEDULED_ID,
+                col_id: StScheduledFields::ScheduledAt.col_id(),
+                col_name: StScheduledFields::ScheduledAt.col_name(),
+                col_type: AlgebraicType::U64,
+                is_autoinc: false,
+            },
+            ColumnSchema {
+                table_id: ST_SCHEDULED_ID,
+                col_id: StScheduledFields::Recurring.col_id(),
+                col_name: StScheduledFields::Recurring.col_name(),
+                col_type: AlgebraicType::Bool,
+                is_autoinc: false,
+            },
+        ],
+        constraints: vec![],
+        foreign_keys: vec![],
+        checks: vec![],
+        table_type: StTableType::System,
+        table_access: StAccess::Public,
+    }
+}
+
+pub static ST_SCHEDULED_ROW_TYPE: Lazy<ProductType> =
+    Lazy::new(|| ProductType::from_iter(st_scheduled_schema().columns.iter().map(|c| c.col_type.clone())));
+
 pub(crate) fn table_name_is_system(table_name: &str) -> bool {
     table_name.starts_with("st_")
 }
 
+pub(crate) fn table_id_is_system(table_id: TableId) -> bool {
+    table_id <= ST_MODULE_ID || table_id.0 >= ST_RESERVED_IDS_START
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct StTableRow<Name: AsRef<str>> {
     pub(crate) table_id: TableId,
@@ -702,6 +958,7 @@ pub struct StIndexRow<Name: AsRef<str>> {
     pub(crate) cols: NonEmpty<ColId>,
     pub(crate) index_name: Name,
     pub(crate) is_unique: bool,
+    pub(crate) index_type: IndexType,
 }
 
 impl StIndexRow<&str> {
@@ -712,6 +969,7 @@ impl StIndexRow<&str> {
             cols: self.cols.clone(),
             index_name: self.index_name.to_owned(),
             is_unique: self.is_unique,
+            index_type: self.index_type,
         }
     }
 }
@@ -734,12 +992,20 @@ impl<'a> TryFrom<&'a ProductValue> for StIndexRow<&'a str> {
 
         let index_name = row.field_as_str(StIndexFields::IndexName as usize, None)?;
         let is_unique = row.field_as_bool(StIndexFields::IsUnique as usize, None)?;
+        let index_type = row.field_as_u8(StIndexFields::IndexType as usize, None)?;
+        let index_type = IndexType::from_u8(index_type).ok_or_else(|| TableError::DecodeField {
+            table: ST_INDEXES_NAME.into(),
+            field: StIndexFields::IndexType.col_name(),
+            expect: format!("`{}` or `{}`", IndexType::BTree.to_u8(), IndexType::Hash.to_u8()),
+            found: index_type.to_string(),
+        })?;
         Ok(StIndexRow {
             index_id,
             table_id,
             cols,
             index_name,
             is_unique,
+            index_type,
         })
     }
 }
@@ -751,7 +1017,8 @@ impl From<StIndexRow<String>> for ProductValue {
             x.table_id,
             ArrayValue::from(x.cols.clone().map(|x| x.0)),
             x.index_name,
-            x.is_unique
+            x.is_unique,
+            x.index_type.to_u8()
         ]
     }
 }
@@ -850,6 +1117,18 @@ pub struct StConstraintRow<Name: AsRef<str>> {
     pub(crate) kind: ColumnIndexAttribute,
     pub(crate) table_id: TableId,
     pub(crate) columns: Vec<ColId>,
+    /// The referenced side of a foreign key, `None` for the other kinds of constraint.
+    pub(crate) foreign_key: Option<StForeignKey>,
+    /// The predicate of a check constraint, `None` for the other kinds of constraint.
+    pub(crate) check: Option<Name>,
+}
+
+/// The table and columns referenced by a foreign key, and what happens when a referenced row is deleted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StForeignKey {
+    pub(crate) ref_table_id: TableId,
+    pub(crate) ref_columns: Vec<ColId>,
+    pub(crate) on_delete: ReferentialAction,
 }
 
 impl StConstraintRow<&str> {
@@ -860,8 +1139,42 @@ impl StConstraintRow<&str> {
             kind: self.kind,
             table_id: self.table_id,
             columns: self.columns.clone(),
+            foreign_key: self.foreign_key.clone(),
+            check: self.check.map(str::to_string),
         }
     }
+
+    /// Returns the foreign key described by this row, if it is one.
+    pub fn to_foreign_key(&self) -> Option<ForeignKeySchema> {
+        let foreign_key = self.foreign_key.as_ref()?;
+        Some(ForeignKeySchema {
+            constraint_id: self.constraint_id,
+            constraint_name: self.constraint_name.to_string(),
+            table_id: self.table_id,
+            columns: NonEmpty::from_slice(&self.columns)?,
+            ref_table_id: foreign_key.ref_table_id,
+            ref_columns: NonEmpty::from_slice(&foreign_key.ref_columns)?,
+            on_delete: foreign_key.on_delete,
+        })
+    }
+
+    /// Returns the check constraint described by this row, if it is one.
+    pub fn to_check(&self) -> Option<CheckSchema> {
+        Some(CheckSchema {
+            constraint_id: self.constraint_id,
+            constraint_name: self.constraint_name.to_string(),
+            table_id: self.table_id,
+            expr: self.check?.to_string(),
+        })
+    }
+}
+
+fn to_col_ids(columns: &ArrayValue) -> Vec<ColId> {
+    if let ArrayValue::U32(x) = columns {
+        x.iter().copied().map(ColId).collect()
+    } else {
+        panic!()
+    }
 }
 
 impl<'a> TryFrom<&'a ProductValue> for StConstraintRow<&'a str> {
@@ -869,14 +1182,30 @@ impl<'a> TryFrom<&'a ProductValue> for StConstraintRow<&'a str> {
     fn try_from(row: &'a ProductValue) -> Result<StConstraintRow<&'a str>, DBError> {
         let constraint_id = IndexId(row.field_as_u32(StConstraintFields::ConstraintId as usize, None)?);
         let constraint_name = row.field_as_str(StConstraintFields::ConstraintName as usize, None)?;
-        let kind = row.field_as_u8(StConstraintFields::Kind as usize, None)?;
-        let kind = ColumnIndexAttribute::try_from(kind).expect("Fail to decode ColumnIndexAttribute");
+        let kind = row.field_as_u32(StConstraintFields::Kind as usize, None)?;
+        let kind = u8::try_from(kind)
+            .ok()
+            .and_then(|kind| ColumnIndexAttribute::try_from(kind).ok())
+            .expect("Fail to decode ColumnIndexAttribute");
         let table_id = TableId(row.field_as_u32(StConstraintFields::TableId as usize, None)?);
-        let columns = row.field_as_array(StConstraintFields::Columns as usize, None)?;
-        let columns = if let ArrayValue::U32(x) = columns {
-            x.iter().copied().map(ColId).collect()
+        let columns = to_col_ids(row.field_as_array(StConstraintFields::Columns as usize, None)?);
+        let foreign_key = if kind.is_foreign_key() {
+            let ref_table_id = TableId(row.field_as_u32(StConstraintFields::RefTableId as usize, None)?);
+            let ref_columns = to_col_ids(row.field_as_array(StConstraintFields::RefColumns as usize, None)?);
+            let on_delete = row.field_as_u8(StConstraintFields::OnDelete as usize, None)?;
+            let on_delete = ReferentialAction::from_u8(on_delete).expect("Fail to decode ReferentialAction");
+            Some(StForeignKey {
+                ref_table_id,
+                ref_columns,
+                on_delete,
+            })
         } else {
-            panic!()
+            None
+        };
+        let check = if kind.is_check() {
+            Some(row.field_as_str(StConstraintFields::Check as usize, None)?)
+        } else {
+            None
         };
 
         Ok(StConstraintRow {
@@ -885,18 +1214,29 @@ impl<'a> TryFrom<&'a ProductValue> for StConstraintRow<&'a str> {
             kind,
             table_id,
             columns,
+            foreign_key,
+            check,
         })
     }
 }
 
 impl From<StConstraintRow<String>> for ProductValue {
     fn from(x: StConstraintRow<String>) -> Self {
+        let col_ids = |columns: &[ColId]| ArrayValue::from(columns.iter().copied().map(|x| x.0).collect::<Vec<_>>());
+        let (ref_table_id, ref_columns, on_delete) = match &x.foreign_key {
+            Some(fk) => (fk.ref_table_id, col_ids(&fk.ref_columns), fk.on_delete.to_u8()),
+            None => (TableId(0), col_ids(&[]), ReferentialAction::Restrict.to_u8()),
+        };
         product![
             x.constraint_id,
             x.constraint_name,
-            x.kind.bits(),
+            u32::from(x.kind.bits()),
             x.table_id,
-            ArrayValue::from(x.columns.iter().copied().map(|x| x.0).collect::<Vec<_>>())
+            col_ids(&x.columns),
+            ref_table_id,
+            ref_columns,
+            on_delete,
+            x.check.unwrap_or_default()
         ]
     }
 }
@@ -983,3 +1323,84 @@ impl From<&StModuleRow> for ProductValue {
         ]
     }
 }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StRowPolicyRow<Policy: AsRef<str>> {
+    pub(crate) table_id: TableId,
+    pub(crate) policy: Policy,
+}
+
+impl StRowPolicyRow<&str> {
+    pub fn to_owned(&self) -> StRowPolicyRow<String> {
+        StRowPolicyRow {
+            table_id: self.table_id,
+            policy: self.policy.to_owned(),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a ProductValue> for StRowPolicyRow<&'a str> {
+    type Error = DBError;
+    fn try_from(row: &'a ProductValue) -> Result<StRowPolicyRow<&'a str>, DBError> {
+        let table_id = TableId(row.field_as_u32(StRowPolicyFields::TableId as usize, None)?);
+        let policy = row.field_as_str(StRowPolicyFields::Policy as usize, None)?;
+        Ok(StRowPolicyRow { table_id, policy })
+    }
+}
+
+impl From<StRowPolicyRow<String>> for ProductValue {
+    fn from(x: StRowPolicyRow<String>) -> Self {
+        product![x.table_id, x.policy]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StScheduledRow<Reducer: AsRef<str>> {
+    pub scheduled_id: u64,
+    pub reducer: Reducer,
+    pub args: Vec<u8>,
+    pub scheduled_at: u64,
+    pub recurring: bool,
+}
+
+impl StScheduledRow<&str> {
+    pub fn to_owned(&self) -> StScheduledRow<String> {
+        StScheduledRow {
+            scheduled_id: self.scheduled_id,
+            reducer: self.reducer.to_owned(),
+            args: self.args.clone(),
+            scheduled_at: self.scheduled_at,
+            recurring: self.recurring,
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a ProductValue> for StScheduledRow<&'a str> {
+    type Error = DBError;
+    fn try_from(row: &'a ProductValue) -> Result<StScheduledRow<&'a str>, DBError> {
+        let scheduled_id = row.field_as_u64(StScheduledFields::ScheduledId as usize, None)?;
+        let reducer = row.field_as_str(StScheduledFields::Reducer as usize, None)?;
+        let args = row.field_as_bytes(StScheduledFields::Args as usize, None)?.to_vec();
+        let scheduled_at = row.field_as_u64(StScheduledFields::ScheduledAt as usize, None)?;
+        let recurring = row.field_as_bool(StScheduledFields::Recurring as usize, None)?;
+        Ok(StScheduledRow {
+            scheduled_id,
+            reducer,
+            args,
+            scheduled_at,
+            recurring,
+        })
+    }
+}
+
+impl From<StScheduledRow<String>> for ProductValue {
+    fn from(x: StScheduledRow<String>) -> Self {
+        product![
+            AlgebraicValue::U64(x.scheduled_id),
+            AlgebraicValue::String(x.reducer),
+            AlgebraicValue::Bytes(x.args),
+            AlgebraicValue::U64(x.scheduled_at),
+            AlgebraicValue::Bool(x.recurring),
+        ]
+    }
+}
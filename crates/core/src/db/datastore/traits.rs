@@ -2,14 +2,14 @@ use crate::db::relational_db::ST_TABLES_ID;
 use nonempty::NonEmpty;
 use spacetimedb_lib::auth::{StAccess, StTableType};
 use spacetimedb_lib::relation::{DbTable, FieldName, FieldOnly, Header, TableField};
-use spacetimedb_lib::{ColumnIndexAttribute, DataKey, Hash};
+use spacetimedb_lib::{ColumnIndexAttribute, DataKey, Hash, IndexType, ReferentialAction};
 use spacetimedb_primitives::{ColId, IndexId, SequenceId, TableId};
 use spacetimedb_sats::product_value::InvalidFieldError;
 use spacetimedb_sats::{AlgebraicType, AlgebraicValue, ProductType, ProductTypeElement, ProductValue};
 use spacetimedb_vm::expr::SourceExpr;
 use std::{borrow::Cow, ops::RangeBounds, sync::Arc};
 
-use super::{system_tables::StTableRow, Result};
+use super::{check::CompiledCheck, system_tables::StTableRow, Result};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SequenceSchema {
@@ -44,6 +44,7 @@ pub struct IndexSchema {
     pub(crate) index_name: String,
     pub(crate) is_unique: bool,
     pub(crate) cols: NonEmpty<ColId>,
+    pub(crate) index_type: IndexType,
 }
 
 /// This type is just the [IndexSchema] without the autoinc fields
@@ -53,6 +54,7 @@ pub struct IndexDef {
     pub(crate) cols: NonEmpty<ColId>,
     pub(crate) name: String,
     pub(crate) is_unique: bool,
+    pub(crate) index_type: IndexType,
 }
 
 impl IndexDef {
@@ -62,8 +64,14 @@ impl IndexDef {
             name,
             is_unique,
             table_id,
+            index_type: IndexType::BTree,
         }
     }
+
+    /// Sets the kind of index to build, a [IndexType::BTree] by default.
+    pub fn with_index_type(self, index_type: IndexType) -> Self {
+        Self { index_type, ..self }
+    }
 }
 
 impl From<IndexSchema> for IndexDef {
@@ -73,6 +81,7 @@ impl From<IndexSchema> for IndexDef {
             cols: value.cols,
             name: value.index_name,
             is_unique: value.is_unique,
+            index_type: value.index_type,
         }
     }
 }
@@ -156,6 +165,74 @@ pub struct ConstraintDef {
     pub(crate) columns: Vec<ColId>,
 }
 
+/// A constraint requiring the values of `columns` in the table `table_id`
+/// to be present in the `ref_columns` of the table `ref_table_id`.
+///
+/// It is checked when the transaction commits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignKeySchema {
+    pub(crate) constraint_id: IndexId,
+    pub(crate) constraint_name: String,
+    pub(crate) table_id: TableId,
+    pub(crate) columns: NonEmpty<ColId>,
+    pub(crate) ref_table_id: TableId,
+    pub(crate) ref_columns: NonEmpty<ColId>,
+    pub(crate) on_delete: ReferentialAction,
+}
+
+/// This type is just the [ForeignKeySchema] without the autoinc fields
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignKeyDef {
+    pub(crate) constraint_name: String,
+    pub(crate) table_id: TableId,
+    pub(crate) columns: NonEmpty<ColId>,
+    pub(crate) ref_table_id: TableId,
+    pub(crate) ref_columns: NonEmpty<ColId>,
+    pub(crate) on_delete: ReferentialAction,
+}
+
+impl From<ForeignKeySchema> for ForeignKeyDef {
+    fn from(value: ForeignKeySchema) -> Self {
+        Self {
+            constraint_name: value.constraint_name,
+            table_id: value.table_id,
+            columns: value.columns,
+            ref_table_id: value.ref_table_id,
+            ref_columns: value.ref_columns,
+            on_delete: value.on_delete,
+        }
+    }
+}
+
+/// A constraint requiring every row of the table `table_id` to satisfy the `SQL` predicate `expr`.
+///
+/// It is checked when a row is inserted or updated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckSchema {
+    pub(crate) constraint_id: IndexId,
+    pub(crate) constraint_name: String,
+    pub(crate) table_id: TableId,
+    pub(crate) expr: String,
+}
+
+/// This type is just the [CheckSchema] without the autoinc fields
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckDef {
+    pub(crate) constraint_name: String,
+    pub(crate) table_id: TableId,
+    pub(crate) expr: String,
+}
+
+impl From<CheckSchema> for CheckDef {
+    fn from(value: CheckSchema) -> Self {
+        Self {
+            constraint_name: value.constraint_name,
+            table_id: value.table_id,
+            expr: value.expr,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TableSchema {
     pub table_id: TableId,
@@ -163,6 +240,10 @@ pub struct TableSchema {
     pub columns: Vec<ColumnSchema>,
     pub indexes: Vec<IndexSchema>,
     pub constraints: Vec<ConstraintSchema>,
+    /// The foreign keys of the table, and those referencing it.
+    pub foreign_keys: Vec<ForeignKeySchema>,
+    /// The check constraints of the table.
+    pub checks: Vec<CompiledCheck>,
     pub table_type: StTableType,
     pub table_access: StAccess,
 }
@@ -336,17 +417,23 @@ impl From<TableSchema> for TableDef {
     }
 }
 
-/// Operations in a transaction are either Inserts or Deletes.
-/// Inserts report the byte objects they inserted, to be persisted
+/// Operations in a transaction are either Inserts, Deletes or Updates.
+/// Inserts and Updates report the byte objects they inserted, to be persisted
 /// later in an object store.
 pub enum TxOp {
     Insert(Arc<Vec<u8>>),
     Delete,
+    /// The row replaced the row identified by `old_key`, whose value was `old_row`.
+    Update {
+        bytes: Arc<Vec<u8>>,
+        old_key: DataKey,
+        old_row: ProductValue,
+    },
 }
 
 /// A record of a single operation within a transaction.
 pub struct TxRecord {
-    /// Whether the operation was an insert or a delete.
+    /// Whether the operation was an insert, a delete or an update.
     pub(crate) op: TxOp,
     /// The value of the modified row.
     pub(crate) product_value: ProductValue,
@@ -441,7 +528,21 @@ pub trait MutTxDatastore: TxDatastore + MutTx {
     fn schema_for_table_mut_tx<'tx>(&self, tx: &'tx Self::MutTxId, table_id: TableId) -> Result<Cow<'tx, TableSchema>>;
     fn drop_table_mut_tx(&self, tx: &mut Self::MutTxId, table_id: TableId) -> Result<()>;
     fn rename_table_mut_tx(&self, tx: &mut Self::MutTxId, table_id: TableId, new_name: &str) -> Result<()>;
+    fn rename_index_mut_tx(&self, tx: &mut Self::MutTxId, index_id: IndexId, new_name: &str) -> Result<()>;
+    fn rename_sequence_mut_tx(&self, tx: &mut Self::MutTxId, seq_id: SequenceId, new_name: &str) -> Result<()>;
+    /// Replace the columns of the table identified by `table_id` with `columns`,
+    /// rewriting every stored row with `migrate_row`.
+    ///
+    /// Returns the number of rewritten rows.
+    fn alter_table_columns_mut_tx(
+        &self,
+        tx: &mut Self::MutTxId,
+        table_id: TableId,
+        columns: Vec<ColumnDef>,
+        migrate_row: impl FnMut(ProductValue) -> ProductValue,
+    ) -> Result<u32>;
     fn table_id_exists(&self, tx: &Self::MutTxId, table_id: &TableId) -> bool;
+    fn table_row_count_mut_tx(&self, tx: &Self::MutTxId, table_id: TableId) -> Option<u64>;
     fn table_id_from_name_mut_tx(&self, tx: &Self::MutTxId, table_name: &str) -> Result<Option<TableId>>;
     fn table_name_from_id_mut_tx<'tx>(&self, tx: &'tx Self::MutTxId, table_id: TableId) -> Result<Option<&'tx str>>;
     fn get_all_tables_mut_tx<'tx>(&self, tx: &'tx Self::MutTxId) -> super::Result<Vec<Cow<'tx, TableSchema>>> {
@@ -460,6 +561,18 @@ pub trait MutTxDatastore: TxDatastore + MutTx {
     fn drop_index_mut_tx(&self, tx: &mut Self::MutTxId, index_id: IndexId) -> Result<()>;
     fn index_id_from_name_mut_tx(&self, tx: &Self::MutTxId, index_name: &str) -> super::Result<Option<IndexId>>;
 
+    // Constraints
+    fn create_foreign_key_mut_tx(&self, tx: &mut Self::MutTxId, fk: ForeignKeyDef) -> Result<IndexId>;
+    fn drop_foreign_key_mut_tx(&self, tx: &mut Self::MutTxId, constraint_id: IndexId) -> Result<()>;
+    fn foreign_keys_mut_tx(&self, tx: &Self::MutTxId) -> Result<Vec<ForeignKeySchema>>;
+    fn create_check_mut_tx(&self, tx: &mut Self::MutTxId, check: CheckDef) -> Result<IndexId>;
+    fn drop_check_mut_tx(&self, tx: &mut Self::MutTxId, constraint_id: IndexId) -> Result<()>;
+    fn checks_mut_tx(&self, tx: &Self::MutTxId) -> Result<Vec<CheckSchema>>;
+
+    // Row policies
+    fn set_row_policy_mut_tx(&self, tx: &mut Self::MutTxId, table_id: TableId, policy: Option<String>) -> Result<()>;
+    fn row_policy_mut_tx(&self, tx: &Self::MutTxId, table_id: TableId) -> Result<Option<String>>;
+
     // TODO: Index data
     // - index_scan_mut_tx
     // - index_range_scan_mut_tx
@@ -515,6 +628,20 @@ pub trait MutTxDatastore: TxDatastore + MutTx {
         table_id: TableId,
         row: ProductValue,
     ) -> Result<ProductValue>;
+    /// Replaces the row identified by `row_id` with `row`.
+    ///
+    /// Unlike a delete followed by an insert, no sequence values are
+    /// assigned to `row`, and the change is committed as a single
+    /// [`TxOp::Update`].
+    ///
+    /// Returns `false` if there is no row identified by `row_id`.
+    fn update_mut_tx(
+        &self,
+        tx: &mut Self::MutTxId,
+        table_id: TableId,
+        row_id: Self::RowId,
+        row: ProductValue,
+    ) -> Result<bool>;
 }
 
 /// Describes a programmable [`TxDatastore`].
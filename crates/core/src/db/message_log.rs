@@ -19,6 +19,7 @@ const HEADER_SIZE: usize = 4;
 #[derive(Clone, Copy, Debug)]
 pub struct OpenOptions {
     max_segment_size: u64,
+    initial_offset: u64,
     // TODO(kim): Offset index options
 }
 
@@ -31,6 +32,18 @@ impl OpenOptions {
         self
     }
 
+    /// Set the offset of the first message of a newly created log.
+    ///
+    /// This allows to create a log continuing the history of another log,
+    /// whose older messages are restored from elsewhere, e.g. a snapshot.
+    /// Has no effect if the log already exists.
+    ///
+    /// Default: 0
+    pub fn initial_offset(&mut self, offset: u64) -> &mut Self {
+        self.initial_offset = offset;
+        self
+    }
+
     /// Open the [`MessageLog`] at `path` with the options in self.
     #[tracing::instrument(skip_all)]
     pub fn open(&self, path: impl AsRef<Path>) -> Result<MessageLog, DBError> {
@@ -66,7 +79,10 @@ impl OpenOptions {
         segments.sort_unstable_by_key(|s| s.min_offset);
 
         if segments.is_empty() {
-            segments.push(Segment { min_offset: 0, size: 0 });
+            segments.push(Segment {
+                min_offset: self.initial_offset,
+                size: 0,
+            });
         }
 
         let last_segment = segments.last().unwrap();
@@ -110,6 +126,7 @@ impl Default for OpenOptions {
     fn default() -> Self {
         Self {
             max_segment_size: 1_073_741_824, // 1GiB
+            initial_offset: 0,
         }
     }
 }
@@ -274,12 +291,10 @@ impl MessageLog {
             return Segments::empty();
         }
 
+        // Segments older than the first one may have been removed by
+        // `remove_segments_before`.
         let root = self.get_root();
-        let pos = self
-            .segments
-            .iter()
-            .rposition(|s| s.min_offset <= offset)
-            .expect("a segment with offset 0 must exist");
+        let pos = self.segments.iter().rposition(|s| s.min_offset <= offset).unwrap_or(0);
 
         Segments {
             root,
@@ -287,6 +302,55 @@ impl MessageLog {
         }
     }
 
+    /// Obtains an iterator over all segments containing only messages older
+    /// than `offset`.
+    ///
+    /// The open segment is never yielded. Once the datastore state up to
+    /// `offset` is persisted elsewhere, e.g. in a snapshot, the segments
+    /// yielded by the iterator are no longer needed to restore it, and may be
+    /// archived.
+    pub fn segments_before(&self, offset: u64) -> Segments {
+        Segments {
+            root: self.get_root(),
+            inner: Vec::from(&self.segments[..self.count_segments_before(offset)]).into_iter(),
+        }
+    }
+
+    /// Deletes all segments containing only messages older than `offset`.
+    ///
+    /// This is destructive: the removed messages can no longer be read from
+    /// the log, so the caller must ensure they are persisted elsewhere, e.g. in
+    /// a snapshot.
+    ///
+    /// Returns the number of segments removed and their total size in bytes.
+    pub fn remove_segments_before(&mut self, offset: u64) -> Result<(usize, u64), DBError> {
+        let n = self.count_segments_before(offset);
+        let mut size = 0;
+        for segment in self.segments.drain(..n) {
+            let path = self.root.join(segment.name() + ".log");
+            fs::remove_file(&path).with_context(|| format!("could not remove segment: {}", path.display()))?;
+            size += segment.size;
+        }
+        self.total_size -= size;
+
+        Ok((n, size))
+    }
+
+    /// Whether older segments of the log were removed by
+    /// [`MessageLog::remove_segments_before`].
+    pub fn is_truncated(&self) -> bool {
+        self.segments[0].min_offset > 0
+    }
+
+    fn count_segments_before(&self, offset: u64) -> usize {
+        // A segment only contains messages older than the `min_offset` of the
+        // segment following it.
+        self.segments
+            .windows(2)
+            .take_while(|pair| pair[1].min_offset <= offset)
+            .count()
+    }
+
     fn open_segment(&self) -> &Segment {
         self.segments.last().expect("at least one segment must exist")
     }
@@ -505,6 +569,70 @@ mod tests {
         let segments = message_log.segments_from(10_000).count();
         assert_eq!(3, segments);
 
+        let segments = message_log.segments_before(10_000).count();
+        assert_eq!(0, segments);
+
+        let segments = message_log.segments_before(10_001).count();
+        assert_eq!(1, segments);
+
+        let segments = message_log.segments_before(1_000_000).count();
+        assert_eq!(2, segments);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_segments_before() -> ResultTest<()> {
+        let tmp = TempDir::new("message_log_test")?;
+        let path = tmp.path();
+
+        const MESSAGE: &[u8] = b"fee fi fo fum";
+        const MESSAGES_PER_SEGMENT: usize = 10_000;
+        const SEGMENT_SIZE: usize = MESSAGES_PER_SEGMENT * (MESSAGE.len() + super::HEADER_SIZE);
+        const TOTAL_MESSAGES: usize = (MESSAGES_PER_SEGMENT * 3) - 1;
+
+        let mut message_log = MessageLog::options().max_segment_size(SEGMENT_SIZE as u64).open(path)?;
+        for _ in 0..TOTAL_MESSAGES {
+            message_log.append(MESSAGE)?;
+        }
+        message_log.sync_all()?;
+        assert!(!message_log.is_truncated());
+
+        let (removed, size) = message_log.remove_segments_before(10_001)?;
+        assert_eq!(removed, 1);
+        assert_eq!(size, SEGMENT_SIZE as u64);
+        assert_eq!(
+            message_log.size(),
+            (TOTAL_MESSAGES * (MESSAGE.len() + super::HEADER_SIZE)) as u64 - size
+        );
+        assert!(message_log.is_truncated());
+
+        assert_eq!(message_log.segments_from(0).count(), 2);
+        drop(message_log);
+
+        let message_log = MessageLog::options().max_segment_size(SEGMENT_SIZE as u64).open(path)?;
+        assert!(message_log.is_truncated());
+        assert_eq!(message_log.segments().count(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_initial_offset() -> ResultTest<()> {
+        let tmp = TempDir::new("message_log_test")?;
+        let path = tmp.path();
+
+        let mut message_log = MessageLog::options().initial_offset(42).open(path)?;
+        assert!(message_log.is_truncated());
+        assert_eq!(message_log.open_segment_max_offset, 42);
+        message_log.append(b"fee fi fo fum")?;
+        message_log.sync_all()?;
+        drop(message_log);
+
+        let message_log = MessageLog::open(path)?;
+        assert_eq!(message_log.open_segment_max_offset, 43);
+        assert_eq!(message_log.segments_from(42).next().map(|s| s.offset()), Some(42));
+
         Ok(())
     }
 
@@ -1,18 +1,28 @@
 use super::transaction::Transaction;
 use crate::hash::Hash;
+use crate::host::Timestamp;
 use std::sync::Arc;
 
+/// Set in the flags byte if the commit has a parent.
+const HAS_PARENT: u8 = 1;
+/// Set in the flags byte if the commit records when it was made.
+///
+/// Commits written before timestamps were recorded only ever set [`HAS_PARENT`].
+const HAS_TIMESTAMP: u8 = 2;
+
 // aka "Block" from blockchain, aka RecordBatch, aka TxBatch
 #[derive(Debug)]
 pub struct Commit {
     pub parent_commit_hash: Option<Hash>,
     pub commit_offset: u64,
     pub min_tx_offset: u64,
+    /// When the commit was made, if it was written by a version recording it.
+    pub timestamp: Option<Timestamp>,
     pub transactions: Vec<Arc<Transaction>>,
 }
 
 // TODO: Maybe a transaction buffer hash?
-// commit: <parent_commit_hash(32)><commit_offset(8)><min_tx_offset(8)>[<transaction>...]*
+// commit: <flags(1)>[<parent_commit_hash(32)>]<commit_offset(8)><min_tx_offset(8)>[<timestamp(8)>][<transaction>...]*
 impl Commit {
     pub fn decode(bytes: impl AsRef<[u8]>) -> (Self, usize) {
         let bytes = &mut bytes.as_ref();
@@ -22,6 +32,7 @@ impl Commit {
                     parent_commit_hash: None,
                     commit_offset: 0,
                     min_tx_offset: 0,
+                    timestamp: None,
                     transactions: Vec::new(),
                 },
                 0,
@@ -30,13 +41,14 @@ impl Commit {
 
         let mut read_count = 0;
 
-        let parent_commit_hash = if bytes[read_count] != 0 {
-            read_count += 1;
+        let flags = bytes[read_count];
+        read_count += 1;
+
+        let parent_commit_hash = if flags & HAS_PARENT != 0 {
             let parent_commit_hash = Hash::from_slice(&bytes[read_count..read_count + 32]);
             read_count += 32;
             Some(parent_commit_hash)
         } else {
-            read_count += 1;
             None
         };
 
@@ -50,6 +62,15 @@ impl Commit {
         let min_tx_offset = u64::from_le_bytes(dst);
         read_count += 8;
 
+        let timestamp = if flags & HAS_TIMESTAMP != 0 {
+            let mut dst = [0u8; 8];
+            dst.copy_from_slice(&bytes[read_count..read_count + 8]);
+            read_count += 8;
+            Some(Timestamp(u64::from_le_bytes(dst)))
+        } else {
+            None
+        };
+
         let mut transactions: Vec<Arc<Transaction>> = Vec::new();
         while read_count < bytes.len() {
             let (tx, read) = Transaction::decode(&bytes[read_count..]);
@@ -62,6 +83,7 @@ impl Commit {
                 parent_commit_hash,
                 commit_offset,
                 min_tx_offset,
+                timestamp,
                 transactions,
             },
             read_count,
@@ -84,6 +106,10 @@ impl Commit {
         // 8 for min_tx_offset
         count += 8;
 
+        if self.timestamp.is_some() {
+            count += 8;
+        }
+
         for tx in &self.transactions {
             count += tx.encoded_len();
         }
@@ -94,15 +120,23 @@ impl Commit {
     pub fn encode(&self, bytes: &mut Vec<u8>) {
         bytes.reserve(self.encoded_len());
 
-        if self.parent_commit_hash.is_none() {
-            bytes.push(0);
-        } else {
-            bytes.push(1);
-            bytes.extend(self.parent_commit_hash.unwrap().data);
+        let mut flags = 0;
+        if self.parent_commit_hash.is_some() {
+            flags |= HAS_PARENT;
+        }
+        if self.timestamp.is_some() {
+            flags |= HAS_TIMESTAMP;
+        }
+        bytes.push(flags);
+        if let Some(parent_commit_hash) = self.parent_commit_hash {
+            bytes.extend(parent_commit_hash.data);
         }
 
         bytes.extend(self.commit_offset.to_le_bytes());
         bytes.extend(self.min_tx_offset.to_le_bytes());
+        if let Some(timestamp) = self.timestamp {
+            bytes.extend(timestamp.0.to_le_bytes());
+        }
 
         for tx in &self.transactions {
             tx.encode(bytes);
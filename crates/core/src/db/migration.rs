@@ -0,0 +1,373 @@
+//! Automatic, in-place migrations of table schemas.
+//!
+//! When a module is updated, a stored table can be migrated to the proposed
+//! schema without user intervention if every stored row can be rewritten
+//! losslessly to the new row type. This is the case if, compared to the stored
+//! schema, the proposed schema only
+//!
+//! - appends columns whose type has a default value (see [`default_value`]),
+//! - widens integer columns, e.g. from `u8` to `u32` or from `u16` to `i32`,
+//! - appends variants to sum-typed columns.
+//!
+//! Any other change, e.g. removing, renaming or reordering columns, is
+//! considered incompatible.
+
+use crate::db::datastore::traits::{ColumnDef, TableDef};
+use spacetimedb_sats::{AlgebraicType, AlgebraicValue, BuiltinType, ProductValue, SumType};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MigrationError {
+    #[error("Table type or access of `{0}` changed")]
+    TableKind(String),
+    #[error("Column `{table}.{col}` was removed")]
+    ColumnRemoved { table: String, col: String },
+    #[error("Column `{table}.{old}` was renamed to `{new}` or moved")]
+    ColumnRenamed { table: String, old: String, new: String },
+    #[error("Column `{table}.{col}` changed its auto_inc attribute")]
+    AutoInc { table: String, col: String },
+    #[error("Column `{table}.{col}` can not be migrated from `{from:?}` to `{to:?}`")]
+    ColumnType {
+        table: String,
+        col: String,
+        from: AlgebraicType,
+        to: AlgebraicType,
+    },
+    #[error("Column `{table}.{col}` of type `{ty:?}` was appended, but its type has no default value")]
+    NoDefault {
+        table: String,
+        col: String,
+        ty: AlgebraicType,
+    },
+}
+
+/// How the values of a single column are rewritten by a [`TableMigration`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColumnMigration {
+    /// The column is unchanged.
+    Keep,
+    /// The column is an integer column widened to the type `to`.
+    Widen { to: AlgebraicType },
+    /// The column is a sum with new variants appended.
+    ///
+    /// As the tags of the existing variants are retained, the stored values
+    /// are valid values of the new type.
+    ExtendSum,
+    /// The column is new, stored rows are filled with `default`.
+    Append { default: AlgebraicValue },
+}
+
+/// The plan for migrating the rows of a table to a new schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableMigration {
+    /// One entry per column of the proposed schema.
+    pub columns: Vec<ColumnMigration>,
+}
+
+impl TableMigration {
+    /// Compute the migration from the `known` schema of a table to the
+    /// `proposed` one.
+    ///
+    /// Returns `Ok(None)` if the columns of both schemas are equal, i.e. the
+    /// stored rows don't need to be rewritten. Indexes are not considered.
+    pub fn plan(known: &TableDef, proposed: &TableDef) -> Result<Option<Self>, MigrationError> {
+        let table = || known.table_name.clone();
+
+        if known.table_type != proposed.table_type || known.table_access != proposed.table_access {
+            return Err(MigrationError::TableKind(table()));
+        }
+        if known.columns == proposed.columns {
+            return Ok(None);
+        }
+        if let Some(removed) = known.columns.get(proposed.columns.len()) {
+            return Err(MigrationError::ColumnRemoved {
+                table: table(),
+                col: removed.col_name.clone(),
+            });
+        }
+
+        let mut columns = Vec::with_capacity(proposed.columns.len());
+        for (pos, new) in proposed.columns.iter().enumerate() {
+            let Some(old) = known.columns.get(pos) else {
+                columns.push(Self::plan_appended(&table(), new)?);
+                continue;
+            };
+            if old.col_name != new.col_name {
+                return Err(MigrationError::ColumnRenamed {
+                    table: table(),
+                    old: old.col_name.clone(),
+                    new: new.col_name.clone(),
+                });
+            }
+            if old.is_autoinc != new.is_autoinc {
+                return Err(MigrationError::AutoInc {
+                    table: table(),
+                    col: new.col_name.clone(),
+                });
+            }
+            let column = if old.col_type == new.col_type {
+                ColumnMigration::Keep
+            } else if is_widening(&old.col_type, &new.col_type) {
+                ColumnMigration::Widen {
+                    to: new.col_type.clone(),
+                }
+            } else if is_sum_extension(&old.col_type, &new.col_type) {
+                ColumnMigration::ExtendSum
+            } else {
+                return Err(MigrationError::ColumnType {
+                    table: table(),
+                    col: new.col_name.clone(),
+                    from: old.col_type.clone(),
+                    to: new.col_type.clone(),
+                });
+            };
+            columns.push(column);
+        }
+
+        Ok(Some(Self { columns }))
+    }
+
+    fn plan_appended(table: &str, col: &ColumnDef) -> Result<ColumnMigration, MigrationError> {
+        if col.is_autoinc {
+            return Err(MigrationError::AutoInc {
+                table: table.into(),
+                col: col.col_name.clone(),
+            });
+        }
+        default_value(&col.col_type)
+            .map(|default| ColumnMigration::Append { default })
+            .ok_or_else(|| MigrationError::NoDefault {
+                table: table.into(),
+                col: col.col_name.clone(),
+                ty: col.col_type.clone(),
+            })
+    }
+
+    /// Names of the columns which are appended by this migration.
+    pub fn appended_columns<'a>(&'a self, proposed: &'a TableDef) -> impl Iterator<Item = &'a str> {
+        self.changed_columns(proposed, |col| matches!(col, ColumnMigration::Append { .. }))
+    }
+
+    /// Names of the existing columns whose type is changed by this migration.
+    pub fn altered_columns<'a>(&'a self, proposed: &'a TableDef) -> impl Iterator<Item = &'a str> {
+        self.changed_columns(proposed, |col| {
+            matches!(col, ColumnMigration::Widen { .. } | ColumnMigration::ExtendSum)
+        })
+    }
+
+    fn changed_columns<'a>(
+        &'a self,
+        proposed: &'a TableDef,
+        pred: impl Fn(&ColumnMigration) -> bool + 'a,
+    ) -> impl Iterator<Item = &'a str> {
+        std::iter::zip(&self.columns, &proposed.columns)
+            .filter(move |(col, _)| pred(col))
+            .map(|(_, def)| &*def.col_name)
+    }
+
+    /// Rewrite a row of the known schema to a row of the proposed schema.
+    pub fn migrate_row(&self, row: ProductValue) -> ProductValue {
+        let mut old = row.elements.into_iter();
+        self.columns
+            .iter()
+            .map(|col| match col {
+                ColumnMigration::Keep | ColumnMigration::ExtendSum => {
+                    old.next().expect("row shorter than known schema")
+                }
+                ColumnMigration::Widen { to } => widen(old.next().expect("row shorter than known schema"), to),
+                ColumnMigration::Append { default } => default.clone(),
+            })
+            .collect()
+    }
+}
+
+/// The value stored for an appended column of type `ty` in existing rows.
+///
+/// Options default to `none`, and the builtin scalar types and strings to
+/// their zero value, as in Rust's `Default`. Other types have no default.
+pub fn default_value(ty: &AlgebraicType) -> Option<AlgebraicValue> {
+    Some(match ty {
+        AlgebraicType::Sum(sum) if sum.as_option().is_some() => AlgebraicValue::OptionNone(),
+        AlgebraicType::Builtin(builtin) => match builtin {
+            BuiltinType::Bool => AlgebraicValue::Bool(false),
+            BuiltinType::I8 => AlgebraicValue::I8(0),
+            BuiltinType::U8 => AlgebraicValue::U8(0),
+            BuiltinType::I16 => AlgebraicValue::I16(0),
+            BuiltinType::U16 => AlgebraicValue::U16(0),
+            BuiltinType::I32 => AlgebraicValue::I32(0),
+            BuiltinType::U32 => AlgebraicValue::U32(0),
+            BuiltinType::I64 => AlgebraicValue::I64(0),
+            BuiltinType::U64 => AlgebraicValue::U64(0),
+            BuiltinType::I128 => AlgebraicValue::I128(0),
+            BuiltinType::U128 => AlgebraicValue::U128(0),
+            BuiltinType::F32 => AlgebraicValue::F32(0.0.into()),
+            BuiltinType::F64 => AlgebraicValue::F64(0.0.into()),
+            BuiltinType::String => AlgebraicValue::String(String::new()),
+            BuiltinType::Array(_) | BuiltinType::Map(_) => return None,
+        },
+        _ => return None,
+    })
+}
+
+/// Returns the width in bits and the signedness of an integer type.
+fn int_layout(ty: &AlgebraicType) -> Option<(u8, bool)> {
+    Some(match *ty {
+        AlgebraicType::I8 => (8, true),
+        AlgebraicType::U8 => (8, false),
+        AlgebraicType::I16 => (16, true),
+        AlgebraicType::U16 => (16, false),
+        AlgebraicType::I32 => (32, true),
+        AlgebraicType::U32 => (32, false),
+        AlgebraicType::I64 => (64, true),
+        AlgebraicType::U64 => (64, false),
+        AlgebraicType::I128 => (128, true),
+        AlgebraicType::U128 => (128, false),
+        _ => return None,
+    })
+}
+
+/// Can every value of the integer type `from` be represented by `to`?
+fn is_widening(from: &AlgebraicType, to: &AlgebraicType) -> bool {
+    match (int_layout(from), int_layout(to)) {
+        (Some((from_bits, from_signed)), Some((to_bits, to_signed))) => {
+            to_bits > from_bits && (from_signed == to_signed || to_signed)
+        }
+        _ => false,
+    }
+}
+
+/// Are the variants of `from` a strict prefix of the variants of `to`?
+fn is_sum_extension(from: &AlgebraicType, to: &AlgebraicType) -> bool {
+    match (from, to) {
+        (AlgebraicType::Sum(SumType { variants: from }), AlgebraicType::Sum(SumType { variants: to })) => {
+            from.len() < to.len() && to.starts_with(from)
+        }
+        _ => false,
+    }
+}
+
+fn widen(value: AlgebraicValue, to: &AlgebraicType) -> AlgebraicValue {
+    // `u128` and `i128` are never widened, so every source value fits into an `i128`.
+    let wide: i128 = match value {
+        AlgebraicValue::I8(x) => x.into(),
+        AlgebraicValue::U8(x) => x.into(),
+        AlgebraicValue::I16(x) => x.into(),
+        AlgebraicValue::U16(x) => x.into(),
+        AlgebraicValue::I32(x) => x.into(),
+        AlgebraicValue::U32(x) => x.into(),
+        AlgebraicValue::I64(x) => x.into(),
+        AlgebraicValue::U64(x) => x.into(),
+        value => unreachable!("not a widenable integer: {value:?}"),
+    };
+    match *to {
+        AlgebraicType::I16 => AlgebraicValue::I16(wide as i16),
+        AlgebraicType::U16 => AlgebraicValue::U16(wide as u16),
+        AlgebraicType::I32 => AlgebraicValue::I32(wide as i32),
+        AlgebraicType::U32 => AlgebraicValue::U32(wide as u32),
+        AlgebraicType::I64 => AlgebraicValue::I64(wide as i64),
+        AlgebraicType::U64 => AlgebraicValue::U64(wide as u64),
+        AlgebraicType::I128 => AlgebraicValue::I128(wide),
+        AlgebraicType::U128 => AlgebraicValue::U128(wide as u128),
+        ref ty => unreachable!("not a widened integer type: {ty:?}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spacetimedb_lib::auth::{StAccess, StTableType};
+    use spacetimedb_sats::product;
+
+    fn table(columns: &[(&str, AlgebraicType)]) -> TableDef {
+        TableDef {
+            table_name: "Person".into(),
+            columns: columns
+                .iter()
+                .map(|(name, ty)| ColumnDef {
+                    col_name: (*name).into(),
+                    col_type: ty.clone(),
+                    is_autoinc: false,
+                })
+                .collect(),
+            indexes: vec![],
+            table_type: StTableType::User,
+            table_access: StAccess::Public,
+        }
+    }
+
+    #[test]
+    fn unchanged_columns_need_no_migration() {
+        let known = table(&[("id", AlgebraicType::U32)]);
+        assert_eq!(TableMigration::plan(&known, &known.clone()), Ok(None));
+    }
+
+    #[test]
+    fn append_and_widen() {
+        let known = table(&[("id", AlgebraicType::U32), ("age", AlgebraicType::U8)]);
+        let proposed = table(&[
+            ("id", AlgebraicType::U32),
+            ("age", AlgebraicType::I16),
+            ("nick", AlgebraicType::option(AlgebraicType::String)),
+            ("score", AlgebraicType::U64),
+        ]);
+        let migration = TableMigration::plan(&known, &proposed).unwrap().unwrap();
+        assert_eq!(
+            migration.appended_columns(&proposed).collect::<Vec<_>>(),
+            ["nick", "score"]
+        );
+        assert_eq!(migration.altered_columns(&proposed).collect::<Vec<_>>(), ["age"]);
+
+        let row = migration.migrate_row(product![1u32, 200u8]);
+        assert_eq!(row, product![1u32, 200i16, AlgebraicValue::OptionNone(), 0u64]);
+    }
+
+    #[test]
+    fn extend_sum() {
+        let known = table(&[("kind", AlgebraicType::simple_enum(["a", "b"].into_iter()))]);
+        let proposed = table(&[("kind", AlgebraicType::simple_enum(["a", "b", "c"].into_iter()))]);
+        let migration = TableMigration::plan(&known, &proposed).unwrap().unwrap();
+        assert_eq!(migration.columns, [ColumnMigration::ExtendSum]);
+
+        let row = product![AlgebraicValue::sum(1, AlgebraicValue::unit())];
+        assert_eq!(migration.migrate_row(row.clone()), row);
+    }
+
+    #[test]
+    fn reject_incompatible() {
+        let known = table(&[("id", AlgebraicType::U32), ("name", AlgebraicType::String)]);
+
+        let removed = table(&[("id", AlgebraicType::U32)]);
+        assert!(matches!(
+            TableMigration::plan(&known, &removed),
+            Err(MigrationError::ColumnRemoved { .. })
+        ));
+
+        let reordered = table(&[("name", AlgebraicType::String), ("id", AlgebraicType::U32)]);
+        assert!(matches!(
+            TableMigration::plan(&known, &reordered),
+            Err(MigrationError::ColumnRenamed { .. })
+        ));
+
+        let narrowed = table(&[("id", AlgebraicType::U16), ("name", AlgebraicType::String)]);
+        assert!(matches!(
+            TableMigration::plan(&known, &narrowed),
+            Err(MigrationError::ColumnType { .. })
+        ));
+
+        let signed = table(&[("id", AlgebraicType::I32), ("name", AlgebraicType::String)]);
+        assert!(matches!(
+            TableMigration::plan(&known, &signed),
+            Err(MigrationError::ColumnType { .. })
+        ));
+
+        let no_default = table(&[
+            ("id", AlgebraicType::U32),
+            ("name", AlgebraicType::String),
+            ("tags", AlgebraicType::array(AlgebraicType::String)),
+        ]);
+        assert!(matches!(
+            TableMigration::plan(&known, &no_default),
+            Err(MigrationError::NoDefault { .. })
+        ));
+    }
+}
@@ -4,9 +4,11 @@ pub mod datastore;
 pub mod db_metrics;
 pub mod message_log;
 pub mod messages;
+pub mod migration;
 pub mod ostorage;
 pub mod relational_db;
 mod relational_operators;
+pub mod snapshot;
 
 pub use spacetimedb_lib::{AlgebraicType, AlgebraicValue, ProductType, ProductValue};
 
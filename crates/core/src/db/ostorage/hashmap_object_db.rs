@@ -3,7 +3,7 @@ use crate::hash::{hash_bytes, Hash};
 use hex;
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs::{self, read_dir, File, OpenOptions},
     io::{Read, Write},
     path::{Path, PathBuf},
@@ -186,6 +186,24 @@ impl ObjectDB for HashMapObjectDB {
         }
         Ok(())
     }
+
+    fn retain(&mut self, live: &HashSet<Hash>) -> Result<usize, DBError> {
+        let dead = self
+            .map
+            .keys()
+            .filter(|hash| !live.contains(hash))
+            .copied()
+            .collect::<Vec<_>>();
+        for hash in &dead {
+            let folder = hex::encode(&hash.data[0..1]);
+            let filename = hex::encode(&hash.data[1..]);
+            fs::remove_file(self.root.join(folder).join(filename))?;
+
+            let bytes = self.map.remove(hash).unwrap();
+            self.obj_size -= bytes.len() as u64;
+        }
+        Ok(dead.len())
+    }
 }
 
 fn hex_prefixes() -> Vec<String> {
@@ -271,6 +289,23 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_retain() -> ResultTest<()> {
+        let (mut db, tmp_dir) = setup()?;
+
+        let hash1 = db.add(TEST_DATA1.to_vec());
+        let hash2 = db.add(TEST_DATA2.to_vec());
+
+        assert_eq!(db.retain(&[hash2].into())?, 1);
+        assert!(db.get(hash1).is_none());
+        assert_eq!(db.total_obj_size_bytes(), TEST_DATA2.len() as u64);
+
+        let db = HashMapObjectDB::open(tmp_dir.path())?;
+        assert!(db.get(hash1).is_none());
+        assert_eq!(db.get(hash2).unwrap(), TEST_DATA2.to_vec());
+        Ok(())
+    }
+
     #[test]
     fn test_size() -> ResultTest<()> {
         let (mut db, _tmp_dir) = setup()?;
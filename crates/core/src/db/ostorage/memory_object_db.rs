@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use bytes::Bytes;
 use spacetimedb_lib::{hash::hash_bytes, Hash};
@@ -31,4 +31,10 @@ impl ObjectDB for MemoryObjectDB {
     fn sync_all(&mut self) -> Result<(), crate::error::DBError> {
         Ok(())
     }
+
+    fn retain(&mut self, live: &HashSet<Hash>) -> Result<usize, crate::error::DBError> {
+        let before = self.objects.len();
+        self.objects.retain(|hash, _| live.contains(hash));
+        Ok(before - self.objects.len())
+    }
 }
@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+
 use crate::error::DBError;
 use bytes;
 
@@ -20,4 +22,8 @@ pub trait ObjectDB {
     fn get(&self, hash: Hash) -> Option<bytes::Bytes>;
     fn flush(&mut self) -> Result<(), DBError>;
     fn sync_all(&mut self) -> Result<(), DBError>;
+    /// Remove all objects whose hash is not in `live`.
+    ///
+    /// Returns the number of objects removed.
+    fn retain(&mut self, live: &HashSet<Hash>) -> Result<usize, DBError>;
 }
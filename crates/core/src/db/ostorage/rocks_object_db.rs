@@ -3,6 +3,7 @@ use crate::error::DBError;
 use crate::hash::{hash_bytes, Hash};
 use bytes::Bytes;
 use rocksdb::{ColumnFamilyDescriptor, Options, DB};
+use std::collections::HashSet;
 use std::fs;
 use std::path::Path;
 
@@ -66,6 +67,20 @@ impl ObjectDB for RocksDBObjectDB {
     fn sync_all(&mut self) -> Result<(), DBError> {
         self.flush()
     }
+
+    fn retain(&mut self, live: &HashSet<Hash>) -> Result<usize, DBError> {
+        let cf = self.db.cf_handle(RocksDBObjectDB::OBJECTS_CF).unwrap();
+
+        let mut removed = 0;
+        for entry in self.db.iterator_cf(cf, rocksdb::IteratorMode::Start) {
+            let (key, _) = entry?;
+            if !live.contains(&Hash::from_slice(&key)) {
+                self.db.delete_cf(cf, key)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
 }
 
 #[cfg(test)]
@@ -4,6 +4,7 @@ use crate::hash::{hash_bytes, Hash};
 use bytes::Bytes;
 use sled;
 use sled::Mode::HighThroughput;
+use std::collections::HashSet;
 use std::path::Path;
 
 pub struct SledObjectDB {
@@ -48,6 +49,18 @@ impl ObjectDB for SledObjectDB {
     fn sync_all(&mut self) -> Result<(), DBError> {
         self.flush()
     }
+
+    fn retain(&mut self, live: &HashSet<Hash>) -> Result<usize, DBError> {
+        let mut removed = 0;
+        for key in self.db.iter().keys() {
+            let key = key?;
+            if !live.contains(&Hash::from_slice(&key)) {
+                self.db.remove(key)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
 }
 
 #[cfg(test)]
@@ -113,4 +126,16 @@ mod tests {
 
         assert!(result.is_none());
     }
+
+    #[test]
+    fn test_retain() {
+        let mut db = setup().unwrap();
+
+        let hash1 = db.add(TEST_DATA1.to_vec());
+        let hash2 = db.add(TEST_DATA2.to_vec());
+
+        assert_eq!(db.retain(&[hash2].into()).unwrap(), 1);
+        assert!(db.get(hash1).is_none());
+        assert!(db.get(hash2).is_some());
+    }
 }
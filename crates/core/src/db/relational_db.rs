@@ -1,11 +1,15 @@
-use super::commit_log::{CommitLog, CommitLogView};
+use super::commit_log::{CommitLog, CommitLogView, Compaction};
 use super::datastore::locking_tx_datastore::{DataRef, Iter, IterByColEq, IterByColRange, Locking, MutTxId, RowId};
+use super::datastore::system_tables::{StSequenceRow, ST_SEQUENCES_ID};
 use super::datastore::traits::{
-    DataRow, IndexDef, MutProgrammable, MutTx, MutTxDatastore, Programmable, SequenceDef, TableDef, TableSchema, TxData,
+    CheckDef, CheckSchema, ColumnDef, DataRow, ForeignKeyDef, ForeignKeySchema, IndexDef, MutProgrammable, MutTx,
+    MutTxDatastore, Programmable, SequenceDef, TableDef, TableSchema, TxData,
 };
 use super::message_log::MessageLog;
+use super::migration::TableMigration;
 use super::ostorage::memory_object_db::MemoryObjectDB;
 use super::relational_operators::Relation;
+use super::snapshot::{Snapshot, SnapshotRepository, SNAPSHOT_INTERVAL};
 use crate::address::Address;
 use crate::db::commit_log;
 use crate::db::db_metrics::DB_METRICS;
@@ -14,18 +18,25 @@ use crate::db::ostorage::hashmap_object_db::HashMapObjectDB;
 use crate::db::ostorage::ObjectDB;
 use crate::error::{DBError, DatabaseError, IndexError, TableError};
 use crate::hash::Hash;
+use crate::host::Timestamp;
+use crate::sql::cache::StatementCache;
 use fs2::FileExt;
 use nonempty::NonEmpty;
-use spacetimedb_lib::ColumnIndexAttribute;
-use spacetimedb_lib::{data_key::ToDataKey, PrimaryKey};
+use spacetimedb_lib::buffer::{BufReader, DecodeError};
+use spacetimedb_lib::{data_key::ToDataKey, DataKey, PrimaryKey};
+use spacetimedb_lib::{ColumnIndexAttribute, MIGRATE_TABLE_PREFIX};
 use spacetimedb_primitives::{ColId, IndexId, SequenceId, TableId};
 use spacetimedb_sats::{AlgebraicType, AlgebraicValue, ProductType, ProductValue};
 use std::borrow::Cow;
 use std::fs::{create_dir_all, File};
-use std::ops::RangeBounds;
-use std::path::Path;
+use std::ops::{Bound, RangeBounds};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 
+/// The directory, relative to the root of a database, holding its snapshots.
+const SNAPSHOTS_DIR: &str = "snapshots";
+
 pub const ST_TABLES_NAME: &str = "st_table";
 pub const ST_COLUMNS_NAME: &str = "st_columns";
 pub const ST_SEQUENCES_NAME: &str = "st_sequence";
@@ -43,6 +54,18 @@ pub struct RelationalDB {
     // TODO(cloutiertyler): This should not be public
     pub(crate) inner: Locking,
     commit_log: CommitLog,
+    /// Held while a transaction is committed to the datastore and appended to
+    /// the commit log, so that the datastore state and the log can be
+    /// observed consistently.
+    commit_lock: Arc<Mutex<()>>,
+    snapshots: Option<Arc<SnapshotRepository>>,
+    /// Set while a snapshot taken in the background is in progress,
+    /// see [`Self::spawn_snapshot`].
+    snapshotting: Arc<AtomicBool>,
+    /// The commit offset of the newest snapshot written, which a snapshot is
+    /// taken [`SNAPSHOT_INTERVAL`] commits after.
+    last_snapshot_offset: Arc<AtomicU64>,
+    statements: StatementCache,
     _lock: Arc<File>,
 }
 
@@ -84,18 +107,48 @@ impl RelationalDB {
             .map_err(|err| DatabaseError::DatabasedOpened(root.to_path_buf(), err.into()))?;
 
         let datastore = Locking::bootstrap()?;
-        log::debug!("[{}] Replaying transaction log.", address);
+        let snapshots = message_log
+            .is_some()
+            .then(|| SnapshotRepository::open(root.join(SNAPSHOTS_DIR)).map(Arc::new))
+            .transpose()?;
         let mut segment_index = 0;
         let mut last_logged_percentage = 0;
+        let mut last_snapshot_offset = 0;
         let unwritten_commit = {
             let mut transaction_offset = 0;
             let mut last_commit_offset = None;
             let mut last_hash: Option<Hash> = None;
             if let Some(message_log) = &message_log {
+                // Start from the newest snapshot, if there is one, so that
+                // only the commits made after it need to be replayed.
+                let mut replay_offset = 0;
+                if let Some(snapshot) = snapshots.as_ref().map(|s| s.latest()).transpose()?.flatten() {
+                    log::debug!(
+                        "[{}] Restoring snapshot at commit offset {}.",
+                        address,
+                        snapshot.offset.commit_offset
+                    );
+                    datastore.restore_snapshot(&snapshot)?;
+                    replay_offset = snapshot.offset.commit_offset;
+                    last_snapshot_offset = replay_offset;
+                    transaction_offset = snapshot.offset.tx_offset;
+                    last_commit_offset = replay_offset.checked_sub(1);
+                    last_hash = snapshot.offset.parent_commit_hash;
+                }
+
+                log::debug!("[{}] Replaying transaction log.", address);
                 let message_log = message_log.lock().unwrap();
+                if last_commit_offset.is_none() && message_log.is_truncated() {
+                    return Err(DatabaseError::MissingSnapshot(root.to_path_buf()).into());
+                }
                 let max_offset = message_log.open_segment_max_offset;
-                for commit in commit_log::Iter::from(message_log.segments()) {
+                for commit in commit_log::Iter::from(message_log.segments_from(replay_offset)) {
                     let commit = commit?;
+                    // The first segment may contain commits already covered
+                    // by the snapshot.
+                    if commit.commit_offset < replay_offset {
+                        continue;
+                    }
 
                     segment_index += 1;
                     last_hash = commit.parent_commit_hash;
@@ -148,6 +201,7 @@ impl RelationalDB {
                 parent_commit_hash: last_hash,
                 commit_offset,
                 min_tx_offset: transaction_offset,
+                timestamp: None,
                 transactions: Vec::new(),
             }
         };
@@ -158,6 +212,11 @@ impl RelationalDB {
         let db = Self {
             inner: datastore,
             commit_log,
+            commit_lock: Arc::new(Mutex::new(())),
+            snapshots,
+            snapshotting: Arc::new(AtomicBool::new(false)),
+            last_snapshot_offset: Arc::new(AtomicU64::new(last_snapshot_offset)),
+            statements: StatementCache::default(),
             _lock: Arc::new(lock),
         };
 
@@ -170,6 +229,248 @@ impl RelationalDB {
         CommitLogView::from(&self.commit_log)
     }
 
+    /// Write a snapshot of the committed state of the database to disk.
+    ///
+    /// When the database is reopened, it is restored from the newest snapshot,
+    /// and only the commits made after it are replayed from the message log.
+    /// Message log segments older than the snapshot may then be archived, see
+    /// [`CommitLogView::message_log_segments_before`].
+    ///
+    /// Snapshots are also taken automatically, in the background, once
+    /// [`SNAPSHOT_INTERVAL`] commits were made since the newest one.
+    ///
+    /// Returns the path of the snapshot, or `None` if the database does not
+    /// keep a message log.
+    #[tracing::instrument(skip_all)]
+    pub fn take_snapshot(&self) -> Result<Option<PathBuf>, DBError> {
+        let Some(snapshots) = &self.snapshots else {
+            return Ok(None);
+        };
+        // Beginning a transaction blocks other transactions from committing
+        // while the rows are copied. Taking the commit lock waits for the
+        // last commit to be appended to the log.
+        let tx = self.begin_tx();
+        let commit_lock = self.commit_lock.lock().unwrap();
+        let offset = self.commit_log.snapshot_offset();
+        let rows = self.inner.committed_rows_mut_tx(&tx);
+        drop(commit_lock);
+        self.rollback_tx(tx);
+
+        // Encoding and writing the copy doesn't hold up other transactions.
+        let snapshot = Snapshot::from_rows(offset, rows);
+        log::debug!("Writing snapshot at commit offset {}", offset.commit_offset);
+        let path = snapshots.write(&snapshot)?;
+        self.last_snapshot_offset
+            .fetch_max(offset.commit_offset, Ordering::AcqRel);
+        Ok(Some(path))
+    }
+
+    /// Take a snapshot on a background thread, see [`Self::take_snapshot`],
+    /// unless one is already in progress.
+    ///
+    /// The thread copies the committed rows in a transaction of its own,
+    /// so the transaction which triggered the snapshot doesn't wait for it.
+    /// Other transactions only wait for the copy to be made, not for it to be
+    /// encoded and written.
+    fn spawn_snapshot(&self) {
+        if self.snapshots.is_none() || self.snapshotting.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let db = self.clone();
+        let spawned = std::thread::Builder::new().name("snapshot".into()).spawn(move || {
+            // The commits are durable, so failing to snapshot only means
+            // more of the log needs to be replayed on restart.
+            if let Err(e) = db.take_snapshot() {
+                log::warn!("Failed to take snapshot: {e}");
+            }
+            db.snapshotting.store(false, Ordering::Release);
+        });
+        if let Err(e) = spawned {
+            log::warn!("Failed to spawn snapshot thread: {e}");
+            self.snapshotting.store(false, Ordering::Release);
+        }
+    }
+
+    /// Reclaim the disk space used by the history of the database.
+    ///
+    /// Takes a snapshot, removes the message log segments older than it, and
+    /// removes the objects no longer referenced from either the remaining
+    /// log or the current state of the database from the object DB.
+    ///
+    /// This discards history: the remaining segments are not rewritten, but
+    /// the commits in the removed ones are gone for good. As the log between
+    /// the older snapshots and the new one is removed along with them, a
+    /// [`Self::fork`] can no longer restore commits older than the one right
+    /// before the new snapshot.
+    ///
+    /// Like [`Self::take_snapshot`], this only blocks other transactions while
+    /// the rows of the snapshot are copied. Commits are blocked again while
+    /// the unreferenced objects are removed.
+    ///
+    /// Returns `None` if the database does not keep a message log.
+    #[tracing::instrument(skip_all)]
+    pub fn compact(&self) -> Result<Option<Compaction>, DBError> {
+        let Some(snapshots) = &self.snapshots else {
+            return Ok(None);
+        };
+        let tx = self.begin_tx();
+        let commit_lock = self.commit_lock.lock().unwrap();
+        let offset = self.commit_log.snapshot_offset();
+        let rows = self.inner.committed_rows_mut_tx(&tx);
+        drop(commit_lock);
+        self.rollback_tx(tx);
+
+        let snapshot = Snapshot::from_rows(offset, rows);
+        snapshots.write(&snapshot)?;
+        self.last_snapshot_offset
+            .fetch_max(offset.commit_offset, Ordering::AcqRel);
+        let (segments_removed, segment_bytes_removed) = self.commit_log.remove_segments_before(offset.commit_offset)?;
+
+        // Keep the objects referenced from the state as of the snapshot, and
+        // from the commits after it, so the object DB remains consistent with
+        // the datastore. Also keep those of the commits in the first
+        // remaining segment, which may precede the snapshot.
+        let mut live = snapshot
+            .tables
+            .iter()
+            .flat_map(|table| &table.rows)
+            .filter_map(|row| match DataKey::from_data(row) {
+                DataKey::Hash(hash) => Some(hash),
+                DataKey::Data(_) => None,
+            })
+            .collect();
+        let next = self.commit_log.referenced_objects(0, &mut live)?;
+        // Commits made meanwhile add objects which are not in `live` yet. Once
+        // the commit lock is taken, all of them have been appended to the log.
+        let objects_removed = {
+            let _commit_lock = self.commit_lock.lock().unwrap();
+            self.commit_log.referenced_objects(next, &mut live)?;
+            self.commit_log.retain_objects(&live)?
+        };
+
+        let compaction = Compaction {
+            segments_removed,
+            segment_bytes_removed,
+            objects_removed,
+        };
+        log::info!(
+            "Compacted database: removed {} segments ({} bytes) and {} objects",
+            compaction.segments_removed,
+            compaction.segment_bytes_removed,
+            compaction.objects_removed
+        );
+        Ok(Some(compaction))
+    }
+
+    /// Write the history of this database up to and including the commit at
+    /// `commit_offset` to a new database at `root`, such that opening it
+    /// yields the state this database had right after that commit.
+    ///
+    /// The commits are copied to a new message log at `mlog_path`, and the
+    /// objects they reference to `odb`. If a snapshot taken before the commit
+    /// exists, the new database starts from the newest such snapshot instead,
+    /// and only the commits after it are copied. Note that this does not reach
+    /// back past a [`Self::compact`]: only the snapshot it takes is followed
+    /// by an unbroken log, so the commit right before that snapshot is the
+    /// oldest one which can still be restored.
+    ///
+    /// Fails with [`DatabaseError::CommitOffsetOutOfRange`] if the commit does
+    /// not exist (yet), and with [`DatabaseError::HistoryUnavailable`] if the
+    /// database does not keep a message log, or its history does not reach
+    /// back far enough.
+    #[tracing::instrument(skip(self, root, mlog_path, odb))]
+    pub fn fork(
+        &self,
+        commit_offset: u64,
+        root: impl AsRef<Path>,
+        mlog_path: impl AsRef<Path>,
+        odb: &mut dyn ObjectDB,
+    ) -> Result<(), DBError> {
+        let Some(snapshots) = &self.snapshots else {
+            return Err(DatabaseError::HistoryUnavailable(commit_offset).into());
+        };
+        // Once the commit lock is released, all commits before the unwritten
+        // one have been appended to the log.
+        let end = {
+            let _commit_lock = self.commit_lock.lock().unwrap();
+            self.commit_log.snapshot_offset().commit_offset
+        };
+        if commit_offset >= end {
+            return Err(DatabaseError::CommitOffsetOutOfRange(commit_offset).into());
+        }
+
+        let snapshot = snapshots
+            .offsets()?
+            .into_iter()
+            .rev()
+            .find(|offset| *offset <= commit_offset + 1)
+            .map(|offset| snapshots.read(offset))
+            .transpose()?;
+        let start = snapshot.as_ref().map_or(0, |snapshot| snapshot.offset.commit_offset);
+
+        let mut message_log = MessageLog::options().initial_offset(start).open(mlog_path)?;
+        let log = self.commit_log();
+        let mut next = start;
+        'segments: for segment in log.message_log_segments_from(start) {
+            for message in segment.try_into_iter()? {
+                if next > commit_offset {
+                    break 'segments;
+                }
+                let message = message?;
+                let (commit, _) = Commit::decode(&message);
+                if commit.commit_offset < next {
+                    continue;
+                }
+                // The segments containing `next` were removed.
+                if commit.commit_offset > next {
+                    break 'segments;
+                }
+
+                for object in log.commit_objects(&commit) {
+                    odb.add(object?.to_vec());
+                }
+                message_log.append(&message)?;
+                next += 1;
+            }
+        }
+        if next <= commit_offset {
+            return Err(DatabaseError::HistoryUnavailable(commit_offset).into());
+        }
+
+        odb.sync_all()?;
+        message_log.sync_all()?;
+        if let Some(snapshot) = snapshot {
+            SnapshotRepository::open(root.as_ref().join(SNAPSHOTS_DIR))?.write(&snapshot)?;
+        }
+
+        log::info!("Forked database at commit offset {commit_offset}, starting from commit offset {start}");
+        Ok(())
+    }
+
+    /// Find the offset of the last commit made at or before `timestamp`, to
+    /// [`Self::fork`] the database as of that point in time.
+    ///
+    /// Commits written before commits recorded when they were made are
+    /// considered to be made at or before `timestamp` only if a later commit
+    /// is.
+    ///
+    /// Fails with [`DatabaseError::HistoryUnavailableAt`] if no commit in the
+    /// log is known to be made at or before `timestamp`, e.g. because the
+    /// database does not keep a message log, or it was compacted since.
+    #[tracing::instrument(skip(self))]
+    pub fn commit_offset_at(&self, timestamp: Timestamp) -> Result<u64, DBError> {
+        let mut commit_offset = None;
+        for commit in self.commit_log().iter() {
+            let commit = commit?;
+            match commit.timestamp {
+                Some(made_at) if made_at > timestamp => break,
+                Some(_) => commit_offset = Some(commit.commit_offset),
+                None => {}
+            }
+        }
+        commit_offset.ok_or_else(|| DatabaseError::HistoryUnavailableAt(timestamp).into())
+    }
+
     #[tracing::instrument(skip_all)]
     pub fn pk_for_row(row: &ProductValue) -> PrimaryKey {
         PrimaryKey {
@@ -241,6 +542,59 @@ impl RelationalDB {
         Ok(AlgebraicValue::decode(&schema, &mut &bytes[..])?)
     }
 
+    /// Decode a value of the columns `cols` of the table `table_id` from `bytes`.
+    ///
+    /// The value is encoded as the BSATN-encoded values of each column, in order.
+    /// Like the keys of an index on `cols`, the result is a single value
+    /// when there is only one column, and a product of the column values otherwise.
+    pub fn decode_columns(
+        &self,
+        tx: &MutTxId,
+        table_id: TableId,
+        cols: &NonEmpty<ColId>,
+        bytes: &[u8],
+    ) -> Result<AlgebraicValue, DBError> {
+        let bytes = &mut &bytes[..];
+        let mut values = Vec::with_capacity(cols.len());
+        for col_id in cols.iter() {
+            let schema = self.schema_for_column(tx, table_id, *col_id)?;
+            values.push(AlgebraicValue::decode(&schema, bytes)?);
+        }
+        Ok(if values.len() == 1 {
+            values.pop().unwrap()
+        } else {
+            AlgebraicValue::Product(values.into_iter().collect())
+        })
+    }
+
+    /// Decode a range of values of the column `col_id` of the table `table_id`
+    /// from `bytes`.
+    ///
+    /// The range is encoded as its start bound followed by its end bound.
+    /// Each bound is a tag byte, `0` for inclusive, `1` for exclusive and `2`
+    /// for unbounded, followed by the BSATN-encoded endpoint if there is one.
+    pub fn decode_column_range(
+        &self,
+        tx: &MutTxId,
+        table_id: TableId,
+        col_id: ColId,
+        bytes: &[u8],
+    ) -> Result<(Bound<AlgebraicValue>, Bound<AlgebraicValue>), DBError> {
+        let schema = self.schema_for_column(tx, table_id, col_id)?;
+        let bytes = &mut &bytes[..];
+        let mut decode_bound = || -> Result<_, DecodeError> {
+            Ok(match bytes.get_u8()? {
+                0 => Bound::Included(AlgebraicValue::decode(&schema, bytes)?),
+                1 => Bound::Excluded(AlgebraicValue::decode(&schema, bytes)?),
+                2 => Bound::Unbounded,
+                _ => return Err(DecodeError::InvalidTag),
+            })
+        };
+        let start = decode_bound()?;
+        let end = decode_bound()?;
+        Ok((start, end))
+    }
+
     /// Begin a transaction.
     ///
     /// **Note**: this call **must** be paired with [`Self::rollback_tx`] or
@@ -261,8 +615,16 @@ impl RelationalDB {
     #[tracing::instrument(skip_all)]
     pub fn commit_tx(&self, tx: MutTxId) -> Result<Option<(TxData, Option<usize>)>, DBError> {
         log::trace!("COMMIT TX");
+        let commit_lock = self.commit_lock.lock().unwrap();
         if let Some(tx_data) = self.inner.commit_mut_tx(tx)? {
             let bytes_written = self.commit_log.append_tx(&tx_data, &self.inner)?;
+            // If a snapshot is still in progress, the next commit tries again.
+            let commit_offset = self.commit_log.snapshot_offset().commit_offset;
+            let last_snapshot_offset = self.last_snapshot_offset.load(Ordering::Acquire);
+            if bytes_written.is_some() && commit_offset.saturating_sub(last_snapshot_offset) >= SNAPSHOT_INTERVAL {
+                self.spawn_snapshot();
+            }
+            drop(commit_lock);
             return Ok(Some((tx_data, bytes_written)));
         }
         Ok(None)
@@ -398,6 +760,50 @@ impl RelationalDB {
         self.inner.rename_table_mut_tx(tx, table_id, new_name)
     }
 
+    /// Move the table identified by `table_id` out of the way of a new table
+    /// of the same name, by prefixing the names of the table, its indexes and
+    /// its sequences with [`MIGRATE_TABLE_PREFIX`].
+    ///
+    /// Returns the new name of the table.
+    pub fn stash_table(&self, tx: &mut MutTxId, table_id: TableId) -> Result<String, DBError> {
+        let stashed = |name: &str| format!("{MIGRATE_TABLE_PREFIX}{name}");
+
+        let schema = self.schema_for_table(tx, table_id)?.into_owned();
+        for index in &schema.indexes {
+            self.inner
+                .rename_index_mut_tx(tx, index.index_id, &stashed(&index.index_name))?;
+        }
+
+        const ST_SEQUENCES_TABLE_ID_COL: ColId = ColId(2);
+        let sequences = self
+            .iter_by_col_eq(tx, ST_SEQUENCES_ID, ST_SEQUENCES_TABLE_ID_COL, table_id.into())?
+            .map(|row| StSequenceRow::try_from(row.view()).map(|el| (el.sequence_id, stashed(el.sequence_name))))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (seq_id, seq_name) in sequences {
+            self.inner.rename_sequence_mut_tx(tx, seq_id, &seq_name)?;
+        }
+
+        let table_name = stashed(&schema.table_name);
+        self.rename_table(tx, table_id, &table_name)?;
+        Ok(table_name)
+    }
+
+    /// Migrate the table identified by `table_id` to the new `columns`,
+    /// rewriting every stored row according to `migration`.
+    ///
+    /// Returns the number of migrated rows.
+    #[tracing::instrument(skip(self, tx, columns, migration))]
+    pub fn migrate_table(
+        &self,
+        tx: &mut MutTxId,
+        table_id: TableId,
+        columns: Vec<ColumnDef>,
+        migration: &TableMigration,
+    ) -> Result<u32, DBError> {
+        self.inner
+            .alter_table_columns_mut_tx(tx, table_id, columns, |row| migration.migrate_row(row))
+    }
+
     #[tracing::instrument(skip_all)]
     pub fn table_id_from_name(&self, tx: &MutTxId, table_name: &str) -> Result<Option<TableId>, DBError> {
         self.inner.table_id_from_name_mut_tx(tx, table_name)
@@ -408,6 +814,24 @@ impl RelationalDB {
         self.inner.table_name_from_id_mut_tx(tx, table_id)
     }
 
+    /// Returns the number of rows visible to `tx` in the table,
+    /// or `None` if the table does not exist.
+    #[tracing::instrument(skip_all)]
+    pub fn table_row_count(&self, tx: &MutTxId, table_id: TableId) -> Option<u64> {
+        self.inner.table_row_count_mut_tx(tx, table_id)
+    }
+
+    /// The statements of the `SQL` run against this database, and the plans compiled from them.
+    pub fn statement_cache(&self) -> &StatementCache {
+        &self.statements
+    }
+
+    /// Returns the version of the schema seen by `tx`, which changes whenever a change to the schema is committed,
+    /// or `None` if `tx` itself changes the schema.
+    pub fn schema_version(&self, tx: &MutTxId) -> Option<u64> {
+        self.inner.schema_version(tx)
+    }
+
     #[tracing::instrument(skip_all)]
     pub fn column_attrs(
         &self,
@@ -468,6 +892,63 @@ impl RelationalDB {
         self.inner.drop_index_mut_tx(tx, index_id)
     }
 
+    /// Adds the foreign key `fk` into the `st_constraints` table.
+    ///
+    /// The rows of the table are checked against it when `tx` commits.
+    ///
+    /// Returns the `constraint_id`
+    #[tracing::instrument(skip(self, tx))]
+    pub fn create_foreign_key(&self, tx: &mut MutTxId, fk: ForeignKeyDef) -> Result<IndexId, DBError> {
+        self.inner.create_foreign_key_mut_tx(tx, fk)
+    }
+
+    /// Removes the foreign key identified by `constraint_id`.
+    #[tracing::instrument(skip(self, tx))]
+    pub fn drop_foreign_key(&self, tx: &mut MutTxId, constraint_id: IndexId) -> Result<(), DBError> {
+        self.inner.drop_foreign_key_mut_tx(tx, constraint_id)
+    }
+
+    /// Returns the foreign keys of every table.
+    pub fn foreign_keys(&self, tx: &MutTxId) -> Result<Vec<ForeignKeySchema>, DBError> {
+        self.inner.foreign_keys_mut_tx(tx)
+    }
+
+    /// Adds the check constraint `check` into the `st_constraints` table.
+    ///
+    /// Fails if a row of the table doesn't satisfy it.
+    /// The rows inserted or updated afterwards are checked against it.
+    ///
+    /// Returns the `constraint_id`
+    #[tracing::instrument(skip(self, tx))]
+    pub fn create_check(&self, tx: &mut MutTxId, check: CheckDef) -> Result<IndexId, DBError> {
+        self.inner.create_check_mut_tx(tx, check)
+    }
+
+    /// Removes the check constraint identified by `constraint_id`.
+    #[tracing::instrument(skip(self, tx))]
+    pub fn drop_check(&self, tx: &mut MutTxId, constraint_id: IndexId) -> Result<(), DBError> {
+        self.inner.drop_check_mut_tx(tx, constraint_id)
+    }
+
+    /// Returns the check constraints of every table.
+    pub fn checks(&self, tx: &MutTxId) -> Result<Vec<CheckSchema>, DBError> {
+        self.inner.checks_mut_tx(tx)
+    }
+
+    /// Sets the row-level security policy of the table `table_id`, or removes it when `policy` is `None`.
+    ///
+    /// The `policy` is a `SQL` predicate, like `owner = :sender`,
+    /// restricting the rows visible to the identities other than the owner.
+    #[tracing::instrument(skip(self, tx))]
+    pub fn set_row_policy(&self, tx: &mut MutTxId, table_id: TableId, policy: Option<String>) -> Result<(), DBError> {
+        self.inner.set_row_policy_mut_tx(tx, table_id, policy)
+    }
+
+    /// Returns the row-level security policy of the table `table_id`, if any.
+    pub fn row_policy(&self, tx: &MutTxId, table_id: TableId) -> Result<Option<String>, DBError> {
+        self.inner.row_policy_mut_tx(tx, table_id)
+    }
+
     /// Returns an iterator,
     /// yielding every row in the table identified by `table_id`.
     #[tracing::instrument(skip(self, tx))]
@@ -523,15 +1004,34 @@ impl RelationalDB {
         table_id: TableId,
         row_bytes: &[u8],
     ) -> Result<ProductValue, DBError> {
-        let ty = self.inner.row_type_for_table_mut_tx(tx, table_id)?;
-        let row = ProductValue::decode(&ty, &mut &row_bytes[..])?;
+        let row = self.decode_row(tx, table_id, row_bytes)?;
         self.insert(tx, table_id, row)
     }
 
+    /// Decode a row of the table `table_id` from the BSATN-encoded `row_bytes`.
+    pub fn decode_row(&self, tx: &MutTxId, table_id: TableId, row_bytes: &[u8]) -> Result<ProductValue, DBError> {
+        let ty = self.inner.row_type_for_table_mut_tx(tx, table_id)?;
+        Ok(ProductValue::decode(&ty, &mut &row_bytes[..])?)
+    }
+
     pub fn delete(&self, tx: &mut MutTxId, table_id: TableId, row_ids: impl IntoIterator<Item = RowId>) -> u32 {
         self.inner.delete_mut_tx(tx, table_id, row_ids)
     }
 
+    /// Replaces the row identified by `row_id` with `row`, in place.
+    ///
+    /// Returns `false` if there is no row identified by `row_id`.
+    #[tracing::instrument(skip(self, tx, row))]
+    pub fn update(
+        &self,
+        tx: &mut MutTxId,
+        table_id: TableId,
+        row_id: RowId,
+        row: ProductValue,
+    ) -> Result<bool, DBError> {
+        self.inner.update_mut_tx(tx, table_id, row_id, row)
+    }
+
     #[tracing::instrument(skip_all)]
     pub fn delete_by_rel<R: Relation>(&self, tx: &mut MutTxId, table_id: TableId, relation: R) -> u32 {
         let _guard = DB_METRICS
@@ -643,31 +1143,46 @@ mod tests {
     #![allow(clippy::disallowed_macros)]
 
     use nonempty::NonEmpty;
-    use spacetimedb_primitives::ColId;
+    use spacetimedb_primitives::{ColId, TableId};
+    use std::ops::Bound;
+    use std::path::Path;
     use std::sync::{Arc, Mutex};
+    use std::time::Duration;
 
     use crate::address::Address;
-    use crate::db::datastore::locking_tx_datastore::IterByColEq;
+    use crate::db::datastore::locking_tx_datastore::{DataRef, IterByColEq, MutTxId, RowId};
     use crate::db::datastore::system_tables::StIndexRow;
+    use crate::db::datastore::system_tables::StScheduledRow;
     use crate::db::datastore::system_tables::StSequenceRow;
     use crate::db::datastore::system_tables::StTableRow;
     use crate::db::datastore::system_tables::ST_INDEXES_ID;
+    use crate::db::datastore::system_tables::ST_SCHEDULED_ID;
     use crate::db::datastore::system_tables::ST_SEQUENCES_ID;
+    use crate::db::datastore::traits::CheckDef;
     use crate::db::datastore::traits::ColumnDef;
+    use crate::db::datastore::traits::ForeignKeyDef;
     use crate::db::datastore::traits::IndexDef;
     use crate::db::datastore::traits::TableDef;
+    use crate::db::datastore::traits::TxOp;
     use crate::db::message_log::MessageLog;
+    use crate::db::migration::TableMigration;
+    use crate::db::ostorage::memory_object_db::MemoryObjectDB;
+    use crate::db::ostorage::ObjectDB;
     use crate::db::relational_db::{open_db, ST_TABLES_ID};
 
     use super::RelationalDB;
     use crate::db::relational_db::make_default_ostorage;
     use crate::db::relational_db::tests_utils::make_test_db;
-    use crate::error::{DBError, DatabaseError, IndexError};
+    use crate::error::{ConstraintError, DBError, DatabaseError, IndexError};
+    use crate::host::Timestamp;
     use spacetimedb_lib::auth::StAccess;
     use spacetimedb_lib::auth::StTableType;
     use spacetimedb_lib::error::ResultTest;
-    use spacetimedb_lib::{AlgebraicType, AlgebraicValue, ProductType};
+    use spacetimedb_lib::{
+        bsatn, AlgebraicType, AlgebraicValue, DataKey, IndexType, ProductType, ProductValue, ReferentialAction,
+    };
     use spacetimedb_sats::product;
+    use tempdir::TempDir;
 
     fn column(name: &str, ty: AlgebraicType) -> ColumnDef {
         ColumnDef {
@@ -683,6 +1198,7 @@ mod tests {
             cols: NonEmpty::collect(cols.iter().copied().map(Into::into)).unwrap(),
             name: name.to_string(),
             is_unique: false,
+            index_type: IndexType::BTree,
         }
     }
 
@@ -884,6 +1400,47 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_filter_decoded_range() -> ResultTest<()> {
+        let (stdb, _tmp_dir) = make_test_db()?;
+
+        let mut tx = stdb.begin_tx();
+
+        let mut schema = TableDef::from(ProductType::from([("my_col", AlgebraicType::I32)]));
+        schema.table_name = "MyTable".to_string();
+        let table_id = stdb.create_table(&mut tx, schema)?;
+
+        for i in -2..3 {
+            stdb.insert(&mut tx, table_id, product![AlgebraicValue::I32(i)])?;
+        }
+
+        let filter = |range: &[u8]| -> ResultTest<Vec<i32>> {
+            let range = stdb.decode_column_range(&tx, table_id, ColId(0), range)?;
+            let mut rows = stdb
+                .iter_by_col_range(&tx, table_id, ColId(0), range)?
+                .map(|r| *r.view().elements[0].as_i32().unwrap())
+                .collect::<Vec<i32>>();
+            rows.sort();
+            Ok(rows)
+        };
+        // [-1, 1)
+        let mut range = vec![0];
+        range.extend((-1i32).to_le_bytes());
+        range.push(1);
+        range.extend(1i32.to_le_bytes());
+        assert_eq!(filter(&range)?, vec![-1, 0]);
+        // (0, ..)
+        let mut range = vec![1];
+        range.extend(0i32.to_le_bytes());
+        range.push(2);
+        assert_eq!(filter(&range)?, vec![1, 2]);
+        // Invalid tag.
+        assert!(filter(&[3, 2]).is_err());
+
+        stdb.rollback_tx(tx);
+        Ok(())
+    }
+
     #[test]
     fn test_create_table_rollback() -> ResultTest<()> {
         let (stdb, _tmp_dir) = make_test_db()?;
@@ -930,6 +1487,54 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_drop_table_rollback() -> ResultTest<()> {
+        let (stdb, _tmp_dir) = make_test_db()?;
+
+        let mut tx = stdb.begin_tx();
+        let schema = TableDef {
+            table_name: "MyTable".to_string(),
+            columns: vec![
+                ColumnDef {
+                    col_name: "id".to_string(),
+                    col_type: AlgebraicType::I64,
+                    is_autoinc: true,
+                },
+                ColumnDef {
+                    col_name: "name".to_string(),
+                    col_type: AlgebraicType::String,
+                    is_autoinc: false,
+                },
+            ],
+            indexes: vec![IndexDef::new("MyTable_id_idx".to_string(), 0.into(), 0.into(), true)],
+            table_type: StTableType::User,
+            table_access: StAccess::Public,
+        };
+        let table_id = stdb.create_table(&mut tx, schema)?;
+        stdb.insert(&mut tx, table_id, product![0i64, "Foo"])?;
+        stdb.commit_tx(tx)?;
+
+        let mut tx = stdb.begin_tx();
+        stdb.drop_table(&mut tx, table_id)?;
+        assert_eq!(stdb.table_id_from_name(&tx, "MyTable")?, None);
+        stdb.rollback_tx(tx);
+
+        // The table is back, along with its rows, indexes and sequences.
+        let mut tx = stdb.begin_tx();
+        assert_eq!(stdb.table_id_from_name(&tx, "MyTable")?, Some(table_id));
+        let name_of = |tx: &MutTxId, id: i64| -> ResultTest<Vec<String>> {
+            Ok(stdb
+                .iter_by_col_eq(tx, table_id, ColId(0), AlgebraicValue::I64(id))?
+                .map(|row| row.view().elements[1].as_string().unwrap().clone())
+                .collect())
+        };
+        assert_eq!(name_of(&tx, 1)?, ["Foo"]);
+        stdb.insert(&mut tx, table_id, product![0i64, "Bar"])?;
+        assert_eq!(name_of(&tx, 2)?, ["Bar"]);
+        stdb.commit_tx(tx)?;
+        Ok(())
+    }
+
     #[test]
     fn test_auto_inc() -> ResultTest<()> {
         let (stdb, _tmp_dir) = make_test_db()?;
@@ -1053,69 +1658,642 @@ mod tests {
     }
 
     #[test]
-    fn test_indexed() -> ResultTest<()> {
-        let (stdb, _tmp_dir) = make_test_db()?;
+    fn test_hash_index_reload() -> ResultTest<()> {
+        let (stdb, tmp_dir) = make_test_db()?;
 
         let mut tx = stdb.begin_tx();
-        let schema = TableDef {
-            table_name: "MyTable".to_string(),
-            columns: vec![ColumnDef {
-                col_name: "my_col".to_string(),
-                col_type: AlgebraicType::I64,
-                is_autoinc: false,
-            }],
-            indexes: vec![IndexDef::new(
-                "MyTable_my_col_idx".to_string(),
-                0.into(),
-                0.into(),
-                false,
-            )],
-            table_type: StTableType::User,
-            table_access: StAccess::Public,
-        };
+        let index = index("MyTable_name_idx", &[0]).with_index_type(IndexType::Hash);
+        let schema = table("MyTable", vec![column("name", AlgebraicType::String)], vec![index]);
         let table_id = stdb.create_table(&mut tx, schema)?;
+        for name in ["a", "b", "c"] {
+            stdb.insert(&mut tx, table_id, product![AlgebraicValue::String(name.into())])?;
+        }
+        stdb.commit_tx(tx)?;
+        drop(stdb);
 
-        assert!(
-            stdb.index_id_from_name(&tx, "MyTable_my_col_idx")?.is_some(),
-            "Index not created"
-        );
-
-        stdb.insert(&mut tx, table_id, product![AlgebraicValue::I64(1)])?;
-        stdb.insert(&mut tx, table_id, product![AlgebraicValue::I64(1)])?;
-
-        let mut rows = stdb
-            .iter_by_col_range(&tx, table_id, ColId(0), AlgebraicValue::I64(0)..)?
-            .map(|r| *r.view().elements[0].as_i64().unwrap())
-            .collect::<Vec<i64>>();
-        rows.sort();
+        let stdb = open_db(&tmp_dir, false, true)?;
+        let tx = stdb.begin_tx();
 
-        assert_eq!(rows, vec![1]);
+        let schema = stdb.schema_for_table(&tx, table_id)?;
+        assert_eq!(schema.indexes[0].index_type, IndexType::Hash);
 
+        let value = AlgebraicValue::String("b".into());
+        let IterByColEq::CommittedIndex(iter) = stdb.iter_by_col_eq(&tx, table_id, ColId(0), value.clone())? else {
+            panic!("expected index iterator");
+        };
+        assert_eq!(
+            iter.map(|row| row.view().clone()).collect::<Vec<_>>(),
+            vec![product![value]]
+        );
         Ok(())
     }
 
     #[test]
-    fn test_unique() -> ResultTest<()> {
-        let (stdb, _tmp_dir) = make_test_db()?;
+    fn test_migrate_table_reload() -> ResultTest<()> {
+        let (stdb, tmp_dir) = make_test_db()?;
 
         let mut tx = stdb.begin_tx();
-        let schema = TableDef {
-            table_name: "MyTable".to_string(),
-            columns: vec![ColumnDef {
-                col_name: "my_col".to_string(),
-                col_type: AlgebraicType::I64,
-                is_autoinc: false,
-            }],
-            indexes: vec![IndexDef::new(
-                "MyTable_my_col_idx".to_string(),
-                0.into(),
-                0.into(),
-                true,
-            )],
-            table_type: StTableType::User,
-            table_access: StAccess::Public,
-        };
-        let table_id = stdb.create_table(&mut tx, schema)?;
+        let known = table("MyTable", vec![column("a", AlgebraicType::U32)], vec![]);
+        let table_id = stdb.create_table(&mut tx, known.clone())?;
+        stdb.insert(&mut tx, table_id, product![AlgebraicValue::U32(1)])?;
+        stdb.insert(&mut tx, table_id, product![AlgebraicValue::U32(2)])?;
+        stdb.commit_tx(tx)?;
+
+        let proposed = table(
+            "MyTable",
+            vec![column("a", AlgebraicType::U64), column("b", AlgebraicType::String)],
+            vec![],
+        );
+        let migration = TableMigration::plan(&known, &proposed)?.expect("schema changed");
+
+        let mut tx = stdb.begin_tx();
+        let migrated = stdb.migrate_table(&mut tx, table_id, proposed.columns.clone(), &migration)?;
+        assert_eq!(migrated, 2);
+        stdb.commit_tx(tx)?;
+        drop(stdb);
+
+        let stdb = open_db(&tmp_dir, false, true)?;
+        let mut tx = stdb.begin_tx();
+        stdb.insert(
+            &mut tx,
+            table_id,
+            product![AlgebraicValue::U64(3), AlgebraicValue::String("c".into())],
+        )?;
+
+        let mut rows = stdb.iter(&tx, table_id)?.map(|r| r.view().clone()).collect::<Vec<_>>();
+        rows.sort();
+
+        assert_eq!(
+            rows,
+            vec![
+                product![AlgebraicValue::U64(1), AlgebraicValue::String("".into())],
+                product![AlgebraicValue::U64(2), AlgebraicValue::String("".into())],
+                product![AlgebraicValue::U64(3), AlgebraicValue::String("c".into())],
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_table_extend_sum_reload() -> ResultTest<()> {
+        let (stdb, tmp_dir) = make_test_db()?;
+        let kind = |variants: &[&'static str]| AlgebraicType::simple_enum(variants.iter().copied());
+
+        let mut tx = stdb.begin_tx();
+        let known = table("MyTable", vec![column("kind", kind(&["a", "b"]))], vec![]);
+        let table_id = stdb.create_table(&mut tx, known.clone())?;
+        stdb.insert(
+            &mut tx,
+            table_id,
+            product![AlgebraicValue::sum(0, AlgebraicValue::unit())],
+        )?;
+        stdb.commit_tx(tx)?;
+
+        // No stored row changes, so the migration only writes to `st_columns`.
+        let proposed = table("MyTable", vec![column("kind", kind(&["a", "b", "c"]))], vec![]);
+        let migration = TableMigration::plan(&known, &proposed)?.expect("schema changed");
+        let mut tx = stdb.begin_tx();
+        stdb.migrate_table(&mut tx, table_id, proposed.columns.clone(), &migration)?;
+        stdb.commit_tx(tx)?;
+
+        let mut tx = stdb.begin_tx();
+        stdb.insert(
+            &mut tx,
+            table_id,
+            product![AlgebraicValue::sum(2, AlgebraicValue::unit())],
+        )?;
+        stdb.commit_tx(tx)?;
+        drop(stdb);
+
+        let stdb = open_db(&tmp_dir, false, true)?;
+        let tx = stdb.begin_tx();
+        let mut rows = stdb.iter(&tx, table_id)?.map(|r| r.view().clone()).collect::<Vec<_>>();
+        rows.sort();
+        assert_eq!(
+            rows,
+            vec![
+                product![AlgebraicValue::sum(0, AlgebraicValue::unit())],
+                product![AlgebraicValue::sum(2, AlgebraicValue::unit())],
+            ]
+        );
+        stdb.rollback_tx(tx);
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_empty_table_reload() -> ResultTest<()> {
+        let (stdb, tmp_dir) = make_test_db()?;
+
+        let mut tx = stdb.begin_tx();
+        let known = table("MyTable", vec![column("a", AlgebraicType::U32)], vec![]);
+        let table_id = stdb.create_table(&mut tx, known.clone())?;
+        stdb.commit_tx(tx)?;
+
+        let proposed = table(
+            "MyTable",
+            vec![column("a", AlgebraicType::U32), column("b", AlgebraicType::String)],
+            vec![],
+        );
+        let migration = TableMigration::plan(&known, &proposed)?.expect("schema changed");
+        let mut tx = stdb.begin_tx();
+        let migrated = stdb.migrate_table(&mut tx, table_id, proposed.columns.clone(), &migration)?;
+        assert_eq!(migrated, 0);
+        stdb.commit_tx(tx)?;
+
+        let mut tx = stdb.begin_tx();
+        stdb.insert(
+            &mut tx,
+            table_id,
+            product![AlgebraicValue::U32(1), AlgebraicValue::String("a".into())],
+        )?;
+        stdb.commit_tx(tx)?;
+        drop(stdb);
+
+        let stdb = open_db(&tmp_dir, false, true)?;
+        let tx = stdb.begin_tx();
+        let rows = stdb.iter(&tx, table_id)?.map(|r| r.view().clone()).collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![product![AlgebraicValue::U32(1), AlgebraicValue::String("a".into())]]
+        );
+        stdb.rollback_tx(tx);
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_reload() -> ResultTest<()> {
+        let (stdb, tmp_dir) = make_test_db()?;
+
+        let mut tx = stdb.begin_tx();
+        let schema = TableDef {
+            table_name: "MyTable".to_string(),
+            columns: vec![ColumnDef {
+                col_name: "my_col".to_string(),
+                col_type: AlgebraicType::I64,
+                is_autoinc: true,
+            }],
+            indexes: vec![IndexDef::new(
+                "MyTable_my_col_idx".to_string(),
+                0.into(),
+                0.into(),
+                true,
+            )],
+            table_type: StTableType::User,
+            table_access: StAccess::Public,
+        };
+        let table_id = stdb.create_table(&mut tx, schema)?;
+        stdb.insert(&mut tx, table_id, product![AlgebraicValue::I64(0)])?;
+        stdb.insert(&mut tx, table_id, product![AlgebraicValue::I64(0)])?;
+        stdb.commit_tx(tx)?;
+
+        assert!(stdb.take_snapshot()?.is_some());
+
+        let mut tx = stdb.begin_tx();
+        stdb.insert(&mut tx, table_id, product![AlgebraicValue::I64(0)])?;
+        let row_ids = stdb
+            .iter_by_col_eq(&tx, table_id, ColId(0), AlgebraicValue::I64(1))?
+            .map(|r| RowId(*r.id()))
+            .collect::<Vec<_>>();
+        assert_eq!(stdb.delete(&mut tx, table_id, row_ids), 1);
+        stdb.commit_tx(tx)?;
+        drop(stdb);
+
+        let stdb = open_db(&tmp_dir, false, true)?;
+        assert_eq!(stdb.commit_log().iter().count(), 2);
+        assert_eq!(stdb.commit_log().message_log_segments_before(1).count(), 0);
+
+        let mut tx = stdb.begin_tx();
+        stdb.insert(&mut tx, table_id, product![AlgebraicValue::I64(0)])?;
+        assert!(stdb
+            .insert(&mut tx, table_id, product![AlgebraicValue::I64(2)])
+            .is_err());
+
+        let mut rows = stdb
+            .iter(&tx, table_id)?
+            .map(|r| *r.view().elements[0].as_i64().unwrap())
+            .collect::<Vec<i64>>();
+        rows.sort();
+
+        // The sequence resumes past its last allocation, as after a full replay.
+        assert_eq!(rows[..2], [2, 3]);
+        assert!(rows[2] > 3);
+        stdb.rollback_tx(tx);
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay_system_tables_clear_of_user_tables() -> ResultTest<()> {
+        let (stdb, tmp_dir) = make_test_db()?;
+
+        // The first user tables get the ids they had before `st_row_policy` and `st_scheduled`,
+        // so the commit logs written back then replay the same.
+        let mut tx = stdb.begin_tx();
+        let a = stdb.create_table(&mut tx, table("A", vec![column("a", AlgebraicType::U32)], vec![]))?;
+        let b = stdb.create_table(&mut tx, table("B", vec![column("b", AlgebraicType::U32)], vec![]))?;
+        assert_eq!((a, b), (TableId(6), TableId(7)));
+        stdb.insert(&mut tx, a, product![1u32])?;
+        stdb.insert(&mut tx, b, product![2u32])?;
+        stdb.set_row_policy(&mut tx, b, Some("b = 2".into()))?;
+        let row = StScheduledRow {
+            scheduled_id: 0,
+            reducer: "reducer".to_string(),
+            args: vec![],
+            scheduled_at: 0,
+            recurring: false,
+        };
+        stdb.insert(&mut tx, ST_SCHEDULED_ID, row.into())?;
+        stdb.commit_tx(tx)?;
+        drop(stdb);
+
+        let stdb = open_db(&tmp_dir, false, true)?;
+        let tx = stdb.begin_tx();
+        assert_eq!(stdb.table_name_from_id(&tx, a)?, Some("A"));
+        assert_eq!(stdb.table_name_from_id(&tx, b)?, Some("B"));
+        let rows = |table_id| -> ResultTest<Vec<ProductValue>> {
+            Ok(stdb.iter(&tx, table_id)?.map(|r| r.view().clone()).collect())
+        };
+        assert_eq!(rows(a)?, [product![1u32]]);
+        assert_eq!(rows(b)?, [product![2u32]]);
+        assert_eq!(stdb.row_policy(&tx, b)?.as_deref(), Some("b = 2"));
+        assert_eq!(rows(ST_SCHEDULED_ID)?.len(), 1);
+        stdb.rollback_tx(tx);
+        Ok(())
+    }
+
+    #[test]
+    fn test_foreign_key_after_replay() -> ResultTest<()> {
+        let (stdb, tmp_dir) = make_test_db()?;
+
+        let mut tx = stdb.begin_tx();
+        let mut owner_id = index("owner_id", &[0]);
+        owner_id.is_unique = true;
+        let owner = stdb.create_table(
+            &mut tx,
+            table("Owner", vec![column("id", AlgebraicType::U32)], vec![owner_id]),
+        )?;
+        let pet = stdb.create_table(
+            &mut tx,
+            table(
+                "Pet",
+                vec![column("owner", AlgebraicType::U32)],
+                vec![index("pet_owner", &[0])],
+            ),
+        )?;
+        stdb.insert(&mut tx, owner, product![1u32])?;
+        stdb.insert(&mut tx, pet, product![1u32])?;
+        stdb.commit_tx(tx)?;
+
+        // The key is created after the tables were, so it isn't part of their schemas when they're replayed.
+        let mut tx = stdb.begin_tx();
+        let fk = ForeignKeyDef {
+            constraint_name: "Pet_owner_fkey".into(),
+            table_id: pet,
+            columns: NonEmpty::new(0.into()),
+            ref_table_id: owner,
+            ref_columns: NonEmpty::new(0.into()),
+            on_delete: ReferentialAction::Restrict,
+        };
+        stdb.create_foreign_key(&mut tx, fk)?;
+        stdb.commit_tx(tx)?;
+        drop(stdb);
+
+        let stdb = open_db(&tmp_dir, false, true)?;
+        let mut tx = stdb.begin_tx();
+        stdb.delete_by_rel(&mut tx, owner, [product![1u32]]);
+        assert!(matches!(
+            stdb.commit_tx(tx),
+            Err(DBError::Constraint(ConstraintError::ForeignKeyRestrict { .. }))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_after_replay() -> ResultTest<()> {
+        let (stdb, tmp_dir) = make_test_db()?;
+
+        let mut tx = stdb.begin_tx();
+        let table_id = stdb.create_table(&mut tx, table("MyTable", vec![column("a", AlgebraicType::U32)], vec![]))?;
+        stdb.commit_tx(tx)?;
+        let mut tx = stdb.begin_tx();
+        let check = CheckDef {
+            constraint_name: "MyTable_check_0".into(),
+            table_id,
+            expr: "a < 10".into(),
+        };
+        stdb.create_check(&mut tx, check)?;
+        stdb.commit_tx(tx)?;
+        drop(stdb);
+
+        let stdb = open_db(&tmp_dir, false, true)?;
+        let mut tx = stdb.begin_tx();
+        stdb.insert(&mut tx, table_id, product![1u32])?;
+        assert!(matches!(
+            stdb.insert(&mut tx, table_id, product![10u32]),
+            Err(DBError::Constraint(ConstraintError::CheckViolation { .. }))
+        ));
+        stdb.rollback_tx(tx);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact() -> ResultTest<()> {
+        let tmp_dir = TempDir::new("stdb_test")?;
+        type Odb = Arc<Mutex<Box<dyn ObjectDB + Send>>>;
+        let open = || -> ResultTest<(RelationalDB, Odb)> {
+            let mlog = MessageLog::options()
+                .max_segment_size(512)
+                .open(tmp_dir.path().join("mlog"))?;
+            let odb = Arc::new(Mutex::new(make_default_ostorage(false, tmp_dir.path().join("odb"))?));
+            let stdb = RelationalDB::open(
+                tmp_dir.path(),
+                Some(Arc::new(Mutex::new(mlog))),
+                odb.clone(),
+                Address::zero(),
+                true,
+            )?;
+            Ok((stdb, odb))
+        };
+        let large = |c: char| AlgebraicValue::String(c.to_string().repeat(100));
+        let hash = |c: char| match RelationalDB::pk_for_row(&product![large(c)]).data_key {
+            DataKey::Hash(hash) => hash,
+            DataKey::Data(_) => unreachable!("row is too large to be inlined"),
+        };
+
+        let (stdb, odb) = open()?;
+        let mut tx = stdb.begin_tx();
+        let table_id = stdb.create_table(
+            &mut tx,
+            table("MyTable", vec![column("a", AlgebraicType::String)], vec![]),
+        )?;
+        stdb.insert(&mut tx, table_id, product![large('a')])?;
+        stdb.insert(&mut tx, table_id, product![large('b')])?;
+        stdb.commit_tx(tx)?;
+
+        let mut tx = stdb.begin_tx();
+        let row_ids = stdb
+            .iter_by_col_eq(&tx, table_id, ColId(0), large('a'))?
+            .map(|r| RowId(*r.id()))
+            .collect::<Vec<_>>();
+        assert_eq!(stdb.delete(&mut tx, table_id, row_ids), 1);
+        stdb.commit_tx(tx)?;
+
+        for i in 0..20 {
+            let mut tx = stdb.begin_tx();
+            stdb.insert(&mut tx, table_id, product![AlgebraicValue::String(i.to_string())])?;
+            stdb.commit_tx(tx)?;
+        }
+
+        let compaction = stdb.compact()?.expect("database has a message log");
+        assert!(compaction.segments_removed > 0);
+        assert!(compaction.objects_removed > 0);
+        assert!(stdb.commit_log().iter().count() < 22);
+        assert!(odb.lock().unwrap().get(hash('a')).is_none());
+        assert!(odb.lock().unwrap().get(hash('b')).is_some());
+        drop((stdb, odb));
+
+        let (stdb, _odb) = open()?;
+        let mut tx = stdb.begin_tx();
+        stdb.insert(&mut tx, table_id, product![large('c')])?;
+        let rows = stdb.iter(&tx, table_id)?.count();
+        assert_eq!(rows, 22);
+        assert_eq!(stdb.iter_by_col_eq(&tx, table_id, ColId(0), large('a'))?.count(), 0);
+        stdb.commit_tx(tx)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fork() -> ResultTest<()> {
+        let tmp_dir = TempDir::new("stdb_test")?;
+        let open = |path: &Path| -> ResultTest<RelationalDB> {
+            let mlog = MessageLog::options().max_segment_size(512).open(path.join("mlog"))?;
+            let odb = make_default_ostorage(false, path.join("odb"))?;
+            Ok(RelationalDB::open(
+                path,
+                Some(Arc::new(Mutex::new(mlog))),
+                Arc::new(Mutex::new(odb)),
+                Address::zero(),
+                true,
+            )?)
+        };
+        let fork = |stdb: &RelationalDB, commit_offset: u64, name: &str| -> ResultTest<RelationalDB> {
+            let path = tmp_dir.path().join(name);
+            let mut odb = make_default_ostorage(false, path.join("odb"))?;
+            stdb.fork(commit_offset, &path, path.join("mlog"), &mut *odb)?;
+            drop(odb);
+            open(&path)
+        };
+        let count_rows = |stdb: &RelationalDB, table_id| -> ResultTest<usize> {
+            let tx = stdb.begin_tx();
+            let count = stdb.iter(&tx, table_id)?.count();
+            stdb.rollback_tx(tx);
+            Ok(count)
+        };
+
+        let stdb = open(&tmp_dir.path().join("source"))?;
+        let mut tx = stdb.begin_tx();
+        let table_id = stdb.create_table(
+            &mut tx,
+            table("MyTable", vec![column("a", AlgebraicType::String)], vec![]),
+        )?;
+        stdb.commit_tx(tx)?;
+        // Large enough to be stored in the object DB.
+        let large = AlgebraicValue::String("a".repeat(100));
+        let mut tx = stdb.begin_tx();
+        stdb.insert(&mut tx, table_id, product![large.clone()])?;
+        stdb.commit_tx(tx)?;
+        for i in 2..=10 {
+            let mut tx = stdb.begin_tx();
+            stdb.insert(&mut tx, table_id, product![AlgebraicValue::String(i.to_string())])?;
+            stdb.commit_tx(tx)?;
+        }
+
+        let forked = fork(&stdb, 5, "fork-5")?;
+        assert_eq!(count_rows(&forked, table_id)?, 5);
+        let tx = forked.begin_tx();
+        assert_eq!(forked.iter_by_col_eq(&tx, table_id, ColId(0), large)?.count(), 1);
+        forked.rollback_tx(tx);
+
+        assert!(matches!(
+            stdb.fork(
+                11,
+                tmp_dir.path(),
+                tmp_dir.path().join("fork-11"),
+                &mut MemoryObjectDB::default()
+            ),
+            Err(DBError::Database(DatabaseError::CommitOffsetOutOfRange(11)))
+        ));
+
+        // After compaction, the newest commit can still be restored from the
+        // snapshot, but the oldest one is gone.
+        stdb.compact()?;
+        let forked = fork(&stdb, 10, "fork-10")?;
+        assert_eq!(count_rows(&forked, table_id)?, 10);
+        assert!(matches!(
+            stdb.fork(
+                0,
+                tmp_dir.path(),
+                tmp_dir.path().join("fork-0"),
+                &mut MemoryObjectDB::default()
+            ),
+            Err(DBError::Database(DatabaseError::HistoryUnavailable(0)))
+        ));
+
+        // The fork is a database of its own.
+        let mut tx = forked.begin_tx();
+        forked.insert(&mut tx, table_id, product![AlgebraicValue::String("11".into())])?;
+        forked.commit_tx(tx)?;
+        assert_eq!(count_rows(&forked, table_id)?, 11);
+        assert_eq!(count_rows(&stdb, table_id)?, 10);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_commit_offset_at() -> ResultTest<()> {
+        let tmp_dir = TempDir::new("stdb_test")?;
+        let mlog = MessageLog::open(tmp_dir.path().join("mlog"))?;
+        let odb = make_default_ostorage(false, tmp_dir.path().join("odb"))?;
+        let stdb = RelationalDB::open(
+            tmp_dir.path(),
+            Some(Arc::new(Mutex::new(mlog))),
+            Arc::new(Mutex::new(odb)),
+            Address::zero(),
+            true,
+        )?;
+        // Make sure the commits get distinct timestamps.
+        let now = || {
+            std::thread::sleep(Duration::from_millis(1));
+            let now = Timestamp::now();
+            std::thread::sleep(Duration::from_millis(1));
+            now
+        };
+
+        let before = now();
+        let mut tx = stdb.begin_tx();
+        let table_id = stdb.create_table(&mut tx, table("MyTable", vec![column("a", AlgebraicType::I32)], vec![]))?;
+        stdb.commit_tx(tx)?;
+        let between = now();
+        let mut tx = stdb.begin_tx();
+        stdb.insert(&mut tx, table_id, product![AlgebraicValue::I32(1)])?;
+        stdb.commit_tx(tx)?;
+        let after = now();
+
+        assert!(matches!(
+            stdb.commit_offset_at(before),
+            Err(DBError::Database(DatabaseError::HistoryUnavailableAt(t))) if t == before
+        ));
+        assert_eq!(stdb.commit_offset_at(between)?, 0);
+        assert_eq!(stdb.commit_offset_at(after)?, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stash_table() -> ResultTest<()> {
+        let (stdb, _tmp_dir) = make_test_db()?;
+
+        let schema = TableDef {
+            table_name: "MyTable".to_string(),
+            columns: vec![ColumnDef {
+                col_name: "my_col".to_string(),
+                col_type: AlgebraicType::I64,
+                is_autoinc: true,
+            }],
+            indexes: vec![IndexDef::new(
+                "MyTable_my_col_idx".to_string(),
+                0.into(),
+                0.into(),
+                true,
+            )],
+            table_type: StTableType::User,
+            table_access: StAccess::Public,
+        };
+
+        let mut tx = stdb.begin_tx();
+        let old_table_id = stdb.create_table(&mut tx, schema.clone())?;
+        stdb.insert(&mut tx, old_table_id, product![AlgebraicValue::I64(0)])?;
+
+        let stashed_name = stdb.stash_table(&mut tx, old_table_id)?;
+        assert_eq!(stashed_name, "__old__MyTable");
+        assert_eq!(stdb.table_id_from_name(&tx, &stashed_name)?, Some(old_table_id));
+        assert!(stdb.index_id_from_name(&tx, "__old__MyTable_my_col_idx")?.is_some());
+        assert!(stdb.sequence_id_from_name(&tx, "__old__MyTable_my_col_seq")?.is_some());
+
+        // The names are free for a new table with the same schema.
+        let new_table_id = stdb.create_table(&mut tx, schema)?;
+        assert_ne!(old_table_id, new_table_id);
+        assert_eq!(stdb.table_id_from_name(&tx, "MyTable")?, Some(new_table_id));
+        assert_eq!(stdb.iter(&tx, old_table_id)?.count(), 1);
+        assert_eq!(stdb.iter(&tx, new_table_id)?.count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_indexed() -> ResultTest<()> {
+        let (stdb, _tmp_dir) = make_test_db()?;
+
+        let mut tx = stdb.begin_tx();
+        let schema = TableDef {
+            table_name: "MyTable".to_string(),
+            columns: vec![ColumnDef {
+                col_name: "my_col".to_string(),
+                col_type: AlgebraicType::I64,
+                is_autoinc: false,
+            }],
+            indexes: vec![IndexDef::new(
+                "MyTable_my_col_idx".to_string(),
+                0.into(),
+                0.into(),
+                false,
+            )],
+            table_type: StTableType::User,
+            table_access: StAccess::Public,
+        };
+        let table_id = stdb.create_table(&mut tx, schema)?;
+
+        assert!(
+            stdb.index_id_from_name(&tx, "MyTable_my_col_idx")?.is_some(),
+            "Index not created"
+        );
+
+        stdb.insert(&mut tx, table_id, product![AlgebraicValue::I64(1)])?;
+        stdb.insert(&mut tx, table_id, product![AlgebraicValue::I64(1)])?;
+
+        let mut rows = stdb
+            .iter_by_col_range(&tx, table_id, ColId(0), AlgebraicValue::I64(0)..)?
+            .map(|r| *r.view().elements[0].as_i64().unwrap())
+            .collect::<Vec<i64>>();
+        rows.sort();
+
+        assert_eq!(rows, vec![1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unique() -> ResultTest<()> {
+        let (stdb, _tmp_dir) = make_test_db()?;
+
+        let mut tx = stdb.begin_tx();
+        let schema = TableDef {
+            table_name: "MyTable".to_string(),
+            columns: vec![ColumnDef {
+                col_name: "my_col".to_string(),
+                col_type: AlgebraicType::I64,
+                is_autoinc: false,
+            }],
+            indexes: vec![IndexDef::new(
+                "MyTable_my_col_idx".to_string(),
+                0.into(),
+                0.into(),
+                true,
+            )],
+            table_type: StTableType::User,
+            table_access: StAccess::Public,
+        };
+        let table_id = stdb.create_table(&mut tx, schema)?;
 
         assert!(
             stdb.index_id_from_name(&tx, "MyTable_my_col_idx")?.is_some(),
@@ -1141,6 +2319,96 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_update() -> ResultTest<()> {
+        let (stdb, _tmp_dir) = make_test_db()?;
+
+        let mut tx = stdb.begin_tx();
+        let schema = TableDef {
+            table_name: "MyTable".to_string(),
+            columns: vec![
+                ColumnDef {
+                    col_name: "my_col".to_string(),
+                    col_type: AlgebraicType::I64,
+                    is_autoinc: false,
+                },
+                ColumnDef {
+                    col_name: "my_other_col".to_string(),
+                    col_type: AlgebraicType::I64,
+                    is_autoinc: false,
+                },
+            ],
+            indexes: vec![IndexDef::new(
+                "MyTable_my_col_idx".to_string(),
+                0.into(),
+                0.into(),
+                true,
+            )],
+            table_type: StTableType::User,
+            table_access: StAccess::Public,
+        };
+        let table_id = stdb.create_table(&mut tx, schema)?;
+        stdb.insert(
+            &mut tx,
+            table_id,
+            product![AlgebraicValue::I64(1), AlgebraicValue::I64(10)],
+        )?;
+        stdb.insert(
+            &mut tx,
+            table_id,
+            product![AlgebraicValue::I64(2), AlgebraicValue::I64(20)],
+        )?;
+        stdb.commit_tx(tx)?;
+
+        let row_id_of = |tx: &MutTxId, value: i64| {
+            stdb.iter_by_col_eq(tx, table_id, ColId(0), AlgebraicValue::I64(value))
+                .unwrap()
+                .map(|row| RowId(*row.id()))
+                .next()
+                .unwrap()
+        };
+
+        let mut tx = stdb.begin_tx();
+        let row_id = row_id_of(&tx, 1);
+        assert!(stdb.update(
+            &mut tx,
+            table_id,
+            row_id,
+            product![AlgebraicValue::I64(1), AlgebraicValue::I64(11)]
+        )?);
+        let (tx_data, _) = stdb.commit_tx(tx)?.unwrap();
+        assert_eq!(tx_data.records.len(), 1);
+        let record = &tx_data.records[0];
+        assert!(matches!(record.op, TxOp::Update { .. }));
+        assert_eq!(
+            record.product_value,
+            product![AlgebraicValue::I64(1), AlgebraicValue::I64(11)]
+        );
+
+        // Updating a row to collide with another on a unique column fails
+        // and leaves the table as it was.
+        let mut tx = stdb.begin_tx();
+        let row_id = row_id_of(&tx, 1);
+        assert!(stdb
+            .update(
+                &mut tx,
+                table_id,
+                row_id,
+                product![AlgebraicValue::I64(2), AlgebraicValue::I64(11)]
+            )
+            .is_err());
+        let mut rows = stdb
+            .iter(&tx, table_id)?
+            .map(|r| *r.view().elements[1].as_i64().unwrap())
+            .collect::<Vec<i64>>();
+        rows.sort();
+        assert_eq!(rows, vec![11, 20]);
+        let (tx_data, _) = stdb.commit_tx(tx)?.unwrap();
+        assert!(tx_data.records.is_empty());
+
+        Ok(())
+    }
+
     #[test]
     fn test_identity() -> ResultTest<()> {
         let (stdb, _tmp_dir) = make_test_db()?;
@@ -1351,6 +2619,62 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_multi_column_index_prefix() -> ResultTest<()> {
+        let (stdb, _tmp_dir) = make_test_db()?;
+
+        let columns = vec![
+            column("a", AlgebraicType::U64),
+            column("b", AlgebraicType::U64),
+            column("c", AlgebraicType::U64),
+        ];
+
+        let indexes = vec![index("0", &[0, 1])];
+        let schema = table("t", columns, indexes);
+
+        let mut tx = stdb.begin_tx();
+        let table_id = stdb.create_table(&mut tx, schema)?;
+
+        for (a, b) in [(0, 0), (0, 1), (1, 2), (2, 0)] {
+            stdb.insert(
+                &mut tx,
+                table_id,
+                product![AlgebraicValue::U64(a), AlgebraicValue::U64(b), AlgebraicValue::U64(0)],
+            )?;
+        }
+
+        fn a_of<'a>(rows: impl Iterator<Item = DataRef<'a>>) -> Vec<u64> {
+            rows.map(|row| *row.view().elements[0].as_u64().unwrap()).collect()
+        }
+
+        // Seek on the leading column of the index.
+        let IterByColEq::Index(iter) = stdb.iter_by_col_eq(&tx, table_id, ColId(0), AlgebraicValue::U64(0))? else {
+            panic!("expected index iterator");
+        };
+        assert_eq!(a_of(iter), vec![0, 0]);
+
+        // Range seeks on the leading column of the index.
+        let rows = stdb.iter_by_col_range(
+            &tx,
+            table_id,
+            ColId(0),
+            (Bound::Excluded(AlgebraicValue::U64(0)), Bound::Unbounded),
+        )?;
+        assert_eq!(a_of(rows), vec![1, 2]);
+        let rows = stdb.iter_by_col_range(&tx, table_id, ColId(0), ..=AlgebraicValue::U64(1))?;
+        assert_eq!(a_of(rows), vec![0, 0, 1]);
+
+        // Decode a value of both columns of the index.
+        let mut bytes = Vec::new();
+        bsatn::to_writer(&mut bytes, &(0u64, 1u64)).unwrap();
+        let cols = NonEmpty::collect(vec![0.into(), 1.into()]).unwrap();
+        let value = stdb.decode_columns(&tx, table_id, &cols, &bytes)?;
+        assert_eq!(value, product![AlgebraicValue::U64(0), AlgebraicValue::U64(1)].into());
+        assert_eq!(stdb.iter_by_col_eq(&tx, table_id, cols, value)?.count(), 1);
+
+        Ok(())
+    }
+
     // #[test]
     // fn test_rename_column() -> ResultTest<()> {
     //     let (mut stdb, _tmp_dir) = make_test_db()?;
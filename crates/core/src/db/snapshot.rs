@@ -0,0 +1,320 @@
+//! Snapshots of the committed state of the datastore.
+//!
+//! A snapshot holds every row of every table, including the system tables,
+//! as of a position in the commit log. Restoring a snapshot and replaying
+//! only the commits after that position yields the same state as replaying
+//! the whole commit log.
+//!
+//! Snapshots are stored as individual files named after the commit offset
+//! they cover, alongside the message log of a database.
+
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context};
+use spacetimedb_lib::buffer::{BufReader, BufWriter};
+use spacetimedb_lib::hash::{hash_bytes, Hash};
+use spacetimedb_primitives::TableId;
+use spacetimedb_sats::ProductValue;
+
+use crate::error::DBError;
+
+/// Take a snapshot every this many commits.
+pub const SNAPSHOT_INTERVAL: u64 = 10_000;
+
+/// The number of snapshots to keep around when creating a new one.
+const SNAPSHOTS_TO_RETAIN: usize = 2;
+
+const MAGIC: &[u8; 8] = b"STDBSNAP";
+const VERSION: u8 = 1;
+const EXTENSION: &str = "snapshot";
+
+/// The position in the commit log a snapshot was taken at.
+///
+/// The fields mirror the header of the next [`Commit`] to be written, so a
+/// database restored from a snapshot can resume writing the commit log.
+///
+/// A snapshot reflects exactly the commits before `commit_offset`, as no
+/// transaction can commit while it is being taken.
+///
+/// [`Commit`]: crate::db::messages::commit::Commit
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SnapshotOffset {
+    /// The offset of the first commit not covered by the snapshot.
+    pub commit_offset: u64,
+    /// The offset of the first transaction not covered by the snapshot.
+    pub tx_offset: u64,
+    /// The hash of the last commit covered by the snapshot, if any.
+    pub parent_commit_hash: Option<Hash>,
+}
+
+/// The rows of a single table in a [`Snapshot`].
+pub struct SnapshotTable {
+    pub table_id: TableId,
+    /// The BSATN-encoded rows of the table.
+    pub rows: Vec<Vec<u8>>,
+}
+
+/// The committed state of a database as of [`SnapshotOffset`].
+pub struct Snapshot {
+    pub offset: SnapshotOffset,
+    /// The tables of the database, in ascending order of their ids.
+    ///
+    /// As the system tables come first, restoring the tables in order
+    /// ensures the schema of each user table is known when restoring it.
+    pub tables: Vec<SnapshotTable>,
+}
+
+impl Snapshot {
+    /// Encode the rows of each table in `tables` into a snapshot at `offset`.
+    pub fn from_rows(offset: SnapshotOffset, tables: Vec<(TableId, Vec<ProductValue>)>) -> Self {
+        let mut tables = tables
+            .into_iter()
+            .map(|(table_id, rows)| SnapshotTable {
+                table_id,
+                rows: rows
+                    .iter()
+                    .map(|row| {
+                        let mut bytes = Vec::new();
+                        row.encode(&mut bytes);
+                        bytes
+                    })
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+        tables.sort_unstable_by_key(|table| table.table_id);
+
+        Self { offset, tables }
+    }
+}
+
+// snapshot: <magic(8)><version(1)><commit_offset(8)><tx_offset(8)><parent_commit_hash(1|33)>
+//           <n_tables(4)>[<table_id(4)><n_rows(4)>[<row_len(4)><row>...]*...]*<hash(32)>
+impl Snapshot {
+    pub fn encode(&self, bytes: &mut Vec<u8>) {
+        bytes.put_slice(MAGIC);
+        bytes.put_u8(VERSION);
+        bytes.put_u64(self.offset.commit_offset);
+        bytes.put_u64(self.offset.tx_offset);
+        match self.offset.parent_commit_hash {
+            None => bytes.put_u8(0),
+            Some(hash) => {
+                bytes.put_u8(1);
+                bytes.put_slice(&hash.data);
+            }
+        }
+
+        bytes.put_u32(self.tables.len() as u32);
+        for table in &self.tables {
+            bytes.put_u32(table.table_id.0);
+            bytes.put_u32(table.rows.len() as u32);
+            for row in &table.rows {
+                bytes.put_u32(row.len() as u32);
+                bytes.put_slice(row);
+            }
+        }
+
+        let hash = hash_bytes(&bytes);
+        bytes.put_slice(&hash.data);
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, DBError> {
+        let (mut body, hash) = bytes
+            .len()
+            .checked_sub(32)
+            .map(|at| bytes.split_at(at))
+            .ok_or_else(|| anyhow!("snapshot is truncated"))?;
+        if hash_bytes(body) != Hash::from_slice(hash) {
+            return Err(anyhow!("snapshot checksum mismatch").into());
+        }
+
+        let body = &mut body;
+        if body.get_slice(MAGIC.len())? != MAGIC {
+            return Err(anyhow!("not a snapshot").into());
+        }
+        let version = body.get_u8()?;
+        if version != VERSION {
+            return Err(anyhow!("unsupported snapshot version: {version}").into());
+        }
+
+        let commit_offset = body.get_u64()?;
+        let tx_offset = body.get_u64()?;
+        let parent_commit_hash = match body.get_u8()? {
+            0 => None,
+            _ => Some(Hash::from_slice(body.get_slice(32)?)),
+        };
+
+        let n_tables = body.get_u32()?;
+        let mut tables = Vec::with_capacity(n_tables as usize);
+        for _ in 0..n_tables {
+            let table_id = TableId(body.get_u32()?);
+            let n_rows = body.get_u32()?;
+            let mut rows = Vec::with_capacity(n_rows as usize);
+            for _ in 0..n_rows {
+                let len = body.get_u32()?;
+                rows.push(body.get_slice(len as usize)?.to_vec());
+            }
+            tables.push(SnapshotTable { table_id, rows });
+        }
+
+        Ok(Self {
+            offset: SnapshotOffset {
+                commit_offset,
+                tx_offset,
+                parent_commit_hash,
+            },
+            tables,
+        })
+    }
+}
+
+/// The on-disk collection of [`Snapshot`]s of a database.
+#[derive(Debug)]
+pub struct SnapshotRepository {
+    root: PathBuf,
+}
+
+impl SnapshotRepository {
+    /// Open the repository at `root`, creating the directory if necessary.
+    pub fn open(root: impl AsRef<Path>) -> Result<Self, DBError> {
+        let root = root.as_ref();
+        fs::create_dir_all(root).with_context(|| format!("could not create snapshot directory: {}", root.display()))?;
+        Ok(Self { root: root.to_owned() })
+    }
+
+    /// The commit offsets of all snapshots in the repository, in ascending order.
+    pub fn offsets(&self) -> Result<Vec<u64>, DBError> {
+        let mut offsets = Vec::new();
+        for entry in fs::read_dir(&self.root)? {
+            let path = entry?.path();
+            if path.extension().map_or(true, |ext| ext != EXTENSION) {
+                continue;
+            }
+            let offset = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<u64>().ok())
+                .ok_or_else(|| anyhow!("unexpected snapshot file: {}", path.display()))?;
+            offsets.push(offset);
+        }
+        offsets.sort_unstable();
+        Ok(offsets)
+    }
+
+    /// The commit offset of the newest snapshot, if any.
+    pub fn latest_offset(&self) -> Result<Option<u64>, DBError> {
+        self.offsets().map(|offsets| offsets.last().copied())
+    }
+
+    /// Read the newest snapshot, if any.
+    pub fn latest(&self) -> Result<Option<Snapshot>, DBError> {
+        self.latest_offset()?.map(|offset| self.read(offset)).transpose()
+    }
+
+    /// Read the snapshot taken at `commit_offset`.
+    pub fn read(&self, commit_offset: u64) -> Result<Snapshot, DBError> {
+        let path = self.path(commit_offset);
+        let bytes = fs::read(&path).with_context(|| format!("could not read snapshot: {}", path.display()))?;
+        Snapshot::decode(&bytes)
+    }
+
+    /// Write `snapshot` to the repository, and remove all but the newest
+    /// snapshots.
+    ///
+    /// The snapshot is written to a temporary file first, which is then
+    /// renamed, so that a crash never leaves behind a partial snapshot.
+    ///
+    /// Returns the path of the new snapshot.
+    pub fn write(&self, snapshot: &Snapshot) -> Result<PathBuf, DBError> {
+        let mut bytes = Vec::new();
+        snapshot.encode(&mut bytes);
+
+        let path = self.path(snapshot.offset.commit_offset);
+        let tmp_path = path.with_extension("tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &path)?;
+
+        let offsets = self.offsets()?;
+        for offset in &offsets[..offsets.len().saturating_sub(SNAPSHOTS_TO_RETAIN)] {
+            if let Err(e) = fs::remove_file(self.path(*offset)) {
+                if e.kind() != io::ErrorKind::NotFound {
+                    return Err(e.into());
+                }
+            }
+        }
+
+        Ok(path)
+    }
+
+    fn path(&self, commit_offset: u64) -> PathBuf {
+        self.root
+            .join(format!("{commit_offset:0>20}"))
+            .with_extension(EXTENSION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    fn snapshot(commit_offset: u64) -> Snapshot {
+        Snapshot {
+            offset: SnapshotOffset {
+                commit_offset,
+                tx_offset: commit_offset * 2,
+                parent_commit_hash: Some(hash_bytes(b"parent")),
+            },
+            tables: vec![
+                SnapshotTable {
+                    table_id: TableId(0),
+                    rows: vec![vec![1, 2, 3], vec![]],
+                },
+                SnapshotTable {
+                    table_id: TableId(7),
+                    rows: vec![vec![42; 100]],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_encode_decode() -> Result<(), DBError> {
+        let snapshot = snapshot(5);
+        let mut bytes = Vec::new();
+        snapshot.encode(&mut bytes);
+
+        let decoded = Snapshot::decode(&bytes)?;
+        assert_eq!(decoded.offset, snapshot.offset);
+        assert_eq!(decoded.tables.len(), 2);
+        assert_eq!(decoded.tables[1].table_id, TableId(7));
+        assert_eq!(decoded.tables[1].rows, snapshot.tables[1].rows);
+
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert!(Snapshot::decode(&bytes).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_repository_retains_newest() -> Result<(), DBError> {
+        let tmp_dir = TempDir::new("snapshots")?;
+        let repo = SnapshotRepository::open(tmp_dir.path())?;
+        assert!(repo.latest()?.is_none());
+
+        for offset in [10, 20, 30] {
+            repo.write(&snapshot(offset))?;
+        }
+
+        assert_eq!(repo.offsets()?, vec![20, 30]);
+        assert_eq!(repo.latest()?.map(|s| s.offset.commit_offset), Some(30));
+
+        Ok(())
+    }
+}
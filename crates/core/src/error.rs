@@ -1,5 +1,7 @@
 use crate::client::ClientActorId;
 use crate::db::datastore::traits::IndexDef;
+use crate::host::scheduler::ScheduleError;
+use crate::host::Timestamp;
 use hex::FromHexError;
 use spacetimedb_lib::buffer::DecodeError;
 use spacetimedb_lib::error::{LibError, RelationError};
@@ -70,6 +72,55 @@ pub enum IndexError {
     },
     #[error("Attempt to define a index with more than 1 auto_inc column: Table: {0:?}, Columns: {1:?}")]
     OneAutoInc(TableId, Vec<String>),
+    #[error("Index '{0}' is a hash index, which can only look up a single value, not scan a range")]
+    HashIndexRange(String),
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ConstraintError {
+    #[error("Constraint not found: {0:?}")]
+    NotFound(IndexId),
+    #[error("Constraint with name `{0}` already exists.")]
+    Exists(String),
+    #[error("Invalid foreign key '{0}': {1}")]
+    InvalidForeignKey(String, String),
+    #[error("Foreign key violation '{}' in table '{}': value {} not found in table '{}'", constraint_name, table_name, value.to_satn(), ref_table_name)]
+    ForeignKeyViolation {
+        constraint_name: String,
+        table_name: String,
+        ref_table_name: String,
+        value: AlgebraicValue,
+    },
+    #[error("Foreign key violation '{}': value {} deleted from table '{}' is still referenced by table '{}'", constraint_name, value.to_satn(), ref_table_name, table_name)]
+    ForeignKeyRestrict {
+        constraint_name: String,
+        table_name: String,
+        ref_table_name: String,
+        value: AlgebraicValue,
+    },
+    #[error("Invalid check constraint '{0}': {1}")]
+    InvalidCheck(String, String),
+    #[error("Check constraint violation '{}' in table '{}': row {} does not satisfy `{}`", constraint_name, table_name, row.to_satn(), expr)]
+    CheckViolation {
+        constraint_name: String,
+        table_name: String,
+        expr: String,
+        row: ProductValue,
+    },
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum SessionError {
+    #[error("A transaction is already open.")]
+    AlreadyOpen,
+    #[error("No transaction is open.")]
+    NotOpen,
+    #[error("`BEGIN`, `COMMIT` and `ROLLBACK` must be sent on their own.")]
+    MixedTxControl,
+    #[error("The transaction was rolled back because {0}. Send `ROLLBACK` to end it.")]
+    Aborted(String),
+    #[error("A transaction is open in the session of this connection. Send `COMMIT` or `ROLLBACK` to end it first.")]
+    Busy,
 }
 
 #[derive(Error, Debug, PartialEq, Eq)]
@@ -86,6 +137,8 @@ pub enum SubscriptionError {
     Empty,
     #[error("Queries with side effects not allowed: {0:?}")]
     SideEffect(Crud),
+    #[error("Unsupported in subscriptions: {0}")]
+    Unsupported(String),
 }
 
 #[derive(Error, Debug)]
@@ -105,6 +158,8 @@ pub enum PlanError {
     },
     #[error("Ambiguous field: `{field}`. Also found in {found:?}")]
     AmbiguousField { field: String, found: Vec<FieldName> },
+    #[error("Invalid parameter `{param}`: {error}")]
+    Param { param: String, error: String },
     #[error("Plan error: `{0}`")]
     Unstructured(String),
     #[error("Internal DBError: `{0}`")]
@@ -121,6 +176,14 @@ pub enum DatabaseError {
     NotFound(u64),
     #[error("Database is already opened. Path:`{0}`. Error:{1}")]
     DatabasedOpened(PathBuf, anyhow::Error),
+    #[error("Message log of database at `{0}` is compacted, but no snapshot was found")]
+    MissingSnapshot(PathBuf),
+    #[error("Commit offset {0} is beyond the end of the commit log")]
+    CommitOffsetOutOfRange(u64),
+    #[error("History of the database is not available at commit offset {0}")]
+    HistoryUnavailable(u64),
+    #[error("History of the database is not available at {0:?}")]
+    HistoryUnavailableAt(Timestamp),
 }
 
 #[derive(Error, Debug)]
@@ -135,6 +198,8 @@ pub enum DBError {
     Sequence2(#[from] crate::db::datastore::locking_tx_datastore::SequenceError),
     #[error("IndexError: {0}")]
     Index(#[from] IndexError),
+    #[error("ConstraintError: {0}")]
+    Constraint(#[from] ConstraintError),
     #[error("IOError: {0}.")]
     IoError(#[from] std::io::Error),
     #[error("ParseIntError: {0}.")]
@@ -167,6 +232,8 @@ pub enum DBError {
     },
     #[error("SqlError: {error}, executing: `{sql}`")]
     Plan { sql: String, error: PlanError },
+    #[error("SessionError: {0}")]
+    Session(#[from] SessionError),
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -224,6 +291,8 @@ pub enum NodesError {
     PrimaryKeyNotFound(PrimaryKey),
     #[error("row with column of given value not found")]
     ColumnValueNotFound,
+    #[error("more than one row with column of given value found")]
+    ColumnValueNotUnique,
     #[error("range of rows not found")]
     RangeNotFound,
     #[error("column is out of bounds")]
@@ -238,6 +307,17 @@ pub enum NodesError {
     Internal(#[source] Box<DBError>),
     #[error("invalid index type: {0}")]
     BadIndexType(u8),
+    #[error("requested delay is too long")]
+    ScheduleDelayTooLong,
+}
+
+impl From<ScheduleError> for NodesError {
+    fn from(e: ScheduleError) -> Self {
+        match e {
+            ScheduleError::DelayTooLong(_) => Self::ScheduleDelayTooLong,
+            ScheduleError::Db(e) => e.into(),
+        }
+    }
 }
 
 impl From<DBError> for NodesError {
@@ -0,0 +1,239 @@
+//! Cron expressions for the recurring schedules of reducers.
+//!
+//! An expression has six fields, `second minute hour day-of-month month day-of-week`,
+//! and is evaluated in UTC, e.g. `0 */5 * * * *` matches every five minutes.
+//! Each field is `*`, a value, a range `a-b`, any of these followed by a step `/n`,
+//! or a comma separated list of them. Day-of-week counts from Sunday, as `0` or `7`.
+//! As in cron, when both day fields are restricted, i.e. don't start with `*`, a day matching either of them matches.
+use std::str::FromStr;
+
+use super::Timestamp;
+
+const MICROS_PER_SEC: u64 = 1_000_000;
+const SECS_PER_DAY: u64 = 86_400;
+
+/// How many years ahead [CronSchedule::next_after] looks for a match.
+/// Eight years always contain a February 29th.
+const SEARCH_YEARS: u64 = 8;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum CronError {
+    #[error("expected 6 fields (second minute hour day-of-month month day-of-week), found {0}")]
+    FieldCount(usize),
+    #[error("invalid {field} field {value:?}")]
+    InvalidField { field: &'static str, value: String },
+    #[error("the expression never matches")]
+    NeverMatches,
+}
+
+/// The values a field of a [CronSchedule] matches, as a bit set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Field(u64);
+
+impl Field {
+    fn contains(self, value: u32) -> bool {
+        self.0 & (1 << value) != 0
+    }
+
+    fn parse(name: &'static str, min: u32, max: u32, text: &str) -> Result<Self, CronError> {
+        let invalid = || CronError::InvalidField {
+            field: name,
+            value: text.to_owned(),
+        };
+        let value = |s: &str| {
+            s.parse::<u32>()
+                .ok()
+                .filter(|v| (min..=max).contains(v))
+                .ok_or_else(invalid)
+        };
+        let mut bits = 0;
+        for part in text.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (
+                    range,
+                    Some(step.parse::<usize>().ok().filter(|&s| s > 0).ok_or_else(invalid)?),
+                ),
+                None => (part, None),
+            };
+            let (start, end) = match (range, range.split_once('-')) {
+                ("*" | "?", _) => (min, max),
+                (_, Some((start, end))) => (value(start)?, value(end)?),
+                // `a/n` steps from `a` to the end of the field.
+                (_, None) if step.is_some() => (value(range)?, max),
+                (_, None) => (value(range)?, value(range)?),
+            };
+            if start > end {
+                return Err(invalid());
+            }
+            for v in (start..=end).step_by(step.unwrap_or(1)) {
+                bits |= 1 << v;
+            }
+        }
+        Ok(Self(bits))
+    }
+}
+
+/// A parsed cron expression, matching whole seconds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    seconds: Field,
+    minutes: Field,
+    hours: Field,
+    days_of_month: Field,
+    months: Field,
+    days_of_week: Field,
+    /// Whether both day fields are restricted, so that a day matching either of them matches.
+    either_day: bool,
+}
+
+impl FromStr for CronSchedule {
+    type Err = CronError;
+
+    fn from_str(s: &str) -> Result<Self, CronError> {
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+        let [second, minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return Err(CronError::FieldCount(fields.len()));
+        };
+        let mut days_of_week = Field::parse("day-of-week", 0, 7, day_of_week)?;
+        if days_of_week.contains(7) {
+            days_of_week.0 |= 1;
+        }
+        let restricted = |field: &str| !field.starts_with(['*', '?']);
+        let schedule = Self {
+            seconds: Field::parse("second", 0, 59, second)?,
+            minutes: Field::parse("minute", 0, 59, minute)?,
+            hours: Field::parse("hour", 0, 23, hour)?,
+            days_of_month: Field::parse("day-of-month", 1, 31, day_of_month)?,
+            months: Field::parse("month", 1, 12, month)?,
+            days_of_week,
+            either_day: restricted(day_of_month) && restricted(day_of_week),
+        };
+        // A day that doesn't exist, e.g. `0 0 0 30 2 *`, is never reached.
+        if schedule.next_after(Timestamp(0)).is_none() {
+            return Err(CronError::NeverMatches);
+        }
+        Ok(schedule)
+    }
+}
+
+impl CronSchedule {
+    /// Returns the first time matching the expression strictly after `after`.
+    pub fn next_after(&self, after: Timestamp) -> Option<Timestamp> {
+        let mut secs = after.0 / MICROS_PER_SEC + 1;
+        let last_year = civil_from_days(secs / SECS_PER_DAY).0 + SEARCH_YEARS;
+        loop {
+            let days = secs / SECS_PER_DAY;
+            let (year, month, day) = civil_from_days(days);
+            if year > last_year {
+                return None;
+            }
+            let time = secs % SECS_PER_DAY;
+            secs = if !self.months.contains(month) {
+                let (year, month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+                days_from_civil(year, month, 1) * SECS_PER_DAY
+            } else if !self.matches_day(day, ((days + 4) % 7) as u32) {
+                (days + 1) * SECS_PER_DAY
+            } else if !self.hours.contains((time / 3600) as u32) {
+                (secs / 3600 + 1) * 3600
+            } else if !self.minutes.contains((time / 60 % 60) as u32) {
+                (secs / 60 + 1) * 60
+            } else if !self.seconds.contains((time % 60) as u32) {
+                secs + 1
+            } else {
+                return Some(Timestamp(secs * MICROS_PER_SEC));
+            };
+        }
+    }
+
+    /// Returns whether the day `day` of a month, which is the `weekday`th day of its week, matches.
+    fn matches_day(&self, day: u32, weekday: u32) -> bool {
+        let day_of_month = self.days_of_month.contains(day);
+        let day_of_week = self.days_of_week.contains(weekday);
+        if self.either_day {
+            day_of_month || day_of_week
+        } else {
+            day_of_month && day_of_week
+        }
+    }
+}
+
+/// Returns the `(year, month, day)` of the date `days` days after the UNIX epoch.
+fn civil_from_days(days: u64) -> (u64, u32, u32) {
+    // See http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Returns how many days after the UNIX epoch the date `year-month-day` is.
+fn days_from_civil(year: u64, month: u32, day: u32) -> u64 {
+    // See http://howardhinnant.github.io/date_algorithms.html#days_from_civil.
+    let year = year - u64::from(month <= 2);
+    let era = year / 400;
+    let yoe = year % 400;
+    let mp = u64::from((month + 9) % 12);
+    let doy = (153 * mp + 2) / 5 + u64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the [Timestamp] of `year-month-day hour:minute:second` in UTC.
+    fn at(year: u64, month: u32, day: u32, hour: u64, minute: u64, second: u64) -> Timestamp {
+        let secs = days_from_civil(year, month, day) * SECS_PER_DAY + hour * 3600 + minute * 60 + second;
+        Timestamp(secs * MICROS_PER_SEC)
+    }
+
+    fn next(cron: &str, after: Timestamp) -> Option<Timestamp> {
+        cron.parse::<CronSchedule>().unwrap().next_after(after)
+    }
+
+    #[test]
+    fn test_next_after() {
+        assert_eq!(civil_from_days(days_from_civil(2024, 2, 29)), (2024, 2, 29));
+
+        let start = at(2023, 12, 31, 23, 57, 30);
+        assert_eq!(next("0 */5 * * * *", start), Some(at(2024, 1, 1, 0, 0, 0)));
+        assert_eq!(next("30 57 23 * * *", start), Some(at(2024, 1, 1, 23, 57, 30)));
+        assert_eq!(next("0 0 12 1-7 * 1", start), Some(at(2024, 1, 1, 12, 0, 0)));
+        // Both day fields are restricted, so either of them matches: this is Friday the 5th.
+        assert_eq!(next("0 0 0 13 * 5", start), Some(at(2024, 1, 5, 0, 0, 0)));
+        assert_eq!(next("0 0 0 * * 7", start), Some(at(2024, 1, 7, 0, 0, 0)));
+        // A stepped `*` doesn't restrict its day field, so both of them match: this is Monday the 26th.
+        assert_eq!(
+            next("0 0 0 */5 * 1", at(2024, 1, 1, 0, 0, 0)),
+            Some(at(2024, 2, 26, 0, 0, 0))
+        );
+        assert_eq!(next("0 0 0 29 2 *", start), Some(at(2024, 2, 29, 0, 0, 0)));
+        assert_eq!(next("10/20 0 0 * 3,6 *", start), Some(at(2024, 3, 1, 0, 0, 10)));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let parse = |cron: &str| cron.parse::<CronSchedule>().unwrap_err();
+        assert_eq!(parse("0 */5 * * *"), CronError::FieldCount(5));
+        assert!(matches!(
+            parse("0 60 * * * *"),
+            CronError::InvalidField { field: "minute", .. }
+        ));
+        assert!(matches!(
+            parse("0 0 5-1 * * *"),
+            CronError::InvalidField { field: "hour", .. }
+        ));
+        assert!(matches!(
+            parse("*/0 * * * * *"),
+            CronError::InvalidField { field: "second", .. }
+        ));
+        assert_eq!(parse("0 0 0 30 2 *"), CronError::NeverMatches);
+    }
+}
@@ -227,10 +227,11 @@ impl HostController {
         &self,
         fence: u128,
         module_host_context: ModuleHostContext,
+        drop_orphaned_tables: bool,
     ) -> Result<UpdateOutcome, anyhow::Error> {
         let module_host = self.spawn_module_host(module_host_context).await?;
         // TODO: see init_module_host
-        let update_result = module_host.update_database(fence).await?;
+        let update_result = module_host.update_database(fence, drop_orphaned_tables).await?;
 
         Ok(UpdateOutcome {
             module_host,
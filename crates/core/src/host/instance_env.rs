@@ -1,6 +1,6 @@
 use nonempty::NonEmpty;
 use parking_lot::{Mutex, MutexGuard};
-use spacetimedb_lib::{bsatn, ProductValue};
+use spacetimedb_lib::{bsatn, IndexType, ProductValue};
 use std::num::NonZeroU32;
 use std::ops::DerefMut;
 use std::sync::Arc;
@@ -12,7 +12,7 @@ use crate::db::datastore::traits::IndexDef;
 use crate::error::{IndexError, NodesError};
 use crate::util::ResultInspectExt;
 
-use super::scheduler::{ScheduleError, ScheduledReducerId, Scheduler};
+use super::scheduler::{ScheduledReducerId, Scheduler};
 use super::timestamp::Timestamp;
 use crate::vm::DbProgram;
 use spacetimedb_lib::filter::CmpArgs;
@@ -91,18 +91,15 @@ impl InstanceEnv {
     }
 
     #[tracing::instrument(skip_all, fields(reducer=reducer))]
-    pub fn schedule(
-        &self,
-        reducer: String,
-        args: Vec<u8>,
-        time: Timestamp,
-    ) -> Result<ScheduledReducerId, ScheduleError> {
-        self.scheduler.schedule(reducer, args, time)
+    pub fn schedule(&self, reducer: String, args: Vec<u8>, time: Timestamp) -> Result<ScheduledReducerId, NodesError> {
+        let tx = &mut *self.get_tx()?;
+        Ok(self.scheduler.schedule(tx, reducer, args, time)?)
     }
 
     #[tracing::instrument(skip_all)]
-    pub fn cancel_reducer(&self, id: ScheduledReducerId) {
-        self.scheduler.cancel(id)
+    pub fn cancel_reducer(&self, id: ScheduledReducerId) -> Result<(), NodesError> {
+        let tx = &mut *self.get_tx()?;
+        Ok(self.scheduler.cancel(tx, id)?)
     }
 
     fn get_tx(&self) -> Result<impl DerefMut<Target = MutTxId> + '_, GetTxError> {
@@ -164,6 +161,44 @@ impl InstanceEnv {
         NonZeroU32::new(count).ok_or(NodesError::ColumnValueNotFound)
     }
 
+    /// Replaces the row in the table identified by `table_id`
+    /// where the column identified by `col_id` equates to `value`
+    /// with `row`, in place.
+    ///
+    /// Returns an error if no row matches, more than one row matches,
+    /// or if the column wasn't found.
+    #[tracing::instrument(skip(self, value, row))]
+    pub fn update_by_col_eq(
+        &self,
+        table_id: TableId,
+        col_id: ColId,
+        value: &[u8],
+        row: &[u8],
+    ) -> Result<(), NodesError> {
+        let stdb = &*self.dbic.relational_db;
+        let tx = &mut *self.get_tx()?;
+
+        // Interpret the `value` using the schema of the column,
+        // and the `row` using the schema of the table.
+        let eq_value = stdb.decode_column(tx, table_id, col_id, value)?;
+        let row = stdb.decode_row(tx, table_id, row)?;
+
+        // Find the row in the table where the column data equates to `value`.
+        let mut rows = stdb
+            .iter_by_col_eq(tx, table_id, col_id, eq_value)?
+            .map(|x| RowId(*x.id()));
+        let row_id = rows.next().ok_or(NodesError::ColumnValueNotFound)?;
+        if rows.next().is_some() {
+            return Err(NodesError::ColumnValueNotUnique);
+        }
+
+        if stdb.update(tx, table_id, row_id, row)? {
+            Ok(())
+        } else {
+            Err(NodesError::ColumnValueNotFound)
+        }
+    }
+
     /// Returns the `table_id` associated with the given `table_name`.
     ///
     /// Errors with `TableNotFound` if the table does not exist.
@@ -184,13 +219,9 @@ impl InstanceEnv {
     /// on a product of the given columns in `col_ids`,
     /// in the table identified by `table_id`.
     ///
-    /// Currently only single-column-indices are supported.
-    /// That is, `col_ids.len() == 1`, or the call will panic.
-    ///
-    /// Another limitation is on the `index_type`.
-    /// Only `btree` indices are supported as of now, i.e., `index_type == 0`.
-    /// When `index_type == 1` is passed, the call will happen
-    /// and on `index_type > 1`, an error is returned.
+    /// The `index_type` is that of [IndexType::to_u8],
+    /// i.e., `0` for a `btree` index and `1` for a `hash` index,
+    /// and on any other value, an error is returned.
     #[tracing::instrument(skip_all)]
     pub fn create_index(
         &self,
@@ -202,15 +233,7 @@ impl InstanceEnv {
         let stdb = &*self.dbic.relational_db;
         let tx = &mut *self.get_tx()?;
 
-        // TODO(george) This check should probably move towards src/db/index, but right
-        // now the API is pretty hardwired towards btrees.
-        //
-        // TODO(george) Dedup the constant here.
-        match index_type {
-            0 => (),
-            1 => todo!("Hash indexes not yet supported"),
-            _ => return Err(NodesError::BadIndexType(index_type)),
-        };
+        let index_type = IndexType::from_u8(index_type).ok_or(NodesError::BadIndexType(index_type))?;
 
         let cols = NonEmpty::from_slice(&col_ids)
             .expect("Attempt to create an index with zero columns")
@@ -223,6 +246,7 @@ impl InstanceEnv {
             cols,
             name: index_name,
             is_unique,
+            index_type,
         };
 
         stdb.create_index(tx, index)?;
@@ -255,6 +279,58 @@ impl InstanceEnv {
         Ok(bytes)
     }
 
+    /// Finds all rows in the table identified by `table_id`
+    /// where the column identified by `col_id` lies within the encoded `range`.
+    ///
+    /// These rows are returned concatenated with each row bsatn encoded.
+    ///
+    /// See [`RelationalDB::decode_column_range`] for the encoding of `range`.
+    #[tracing::instrument(skip_all)]
+    pub fn iter_by_col_range(&self, table_id: TableId, col_id: ColId, range: &[u8]) -> Result<Vec<u8>, NodesError> {
+        let stdb = &*self.dbic.relational_db;
+        let tx = &mut *self.get_tx()?;
+
+        // Interpret the bounds of `range` using the schema of the column.
+        let range = stdb.decode_column_range(tx, table_id, col_id, range)?;
+
+        // Find all rows in the table where the column data lies within `range`.
+        // Concatenate and return these rows using bsatn encoding.
+        let results = stdb.iter_by_col_range(tx, table_id, col_id, range)?;
+        let mut bytes = Vec::new();
+        for result in results {
+            bsatn::to_writer(&mut bytes, result.view()).unwrap();
+        }
+        Ok(bytes)
+    }
+
+    /// Finds all rows in the table identified by `table_id`
+    /// where the columns identified by `col_ids` match to `value`.
+    ///
+    /// These rows are returned concatenated with each row bsatn encoded.
+    ///
+    /// See [`RelationalDB::decode_columns`] for the encoding of `value`.
+    #[tracing::instrument(skip_all)]
+    pub fn iter_by_cols_eq(&self, table_id: TableId, col_ids: &[u8], value: &[u8]) -> Result<Vec<u8>, NodesError> {
+        let stdb = &*self.dbic.relational_db;
+        let tx = &mut *self.get_tx()?;
+
+        let cols = NonEmpty::from_slice(col_ids)
+            .ok_or(NodesError::BadColumn)?
+            .map(ColId::from);
+
+        // Interpret the `value` using the schema of the columns.
+        let value = stdb.decode_columns(tx, table_id, &cols, value)?;
+
+        // Find all rows in the table where the column data matches `value`.
+        // Concatenate and return these rows using bsatn encoding.
+        let results = stdb.iter_by_col_eq(tx, table_id, cols, value)?;
+        let mut bytes = Vec::new();
+        for result in results {
+            bsatn::to_writer(&mut bytes, result.view()).unwrap();
+        }
+        Ok(bytes)
+    }
+
     #[tracing::instrument(skip_all)]
     pub fn iter_chunks(&self, table_id: TableId) -> Result<Vec<Box<[u8]>>, NodesError> {
         let mut chunked_writer = ChunkedWriter::default();
@@ -9,9 +9,10 @@ use spacetimedb_lib::{bsatn, Hash, Identity};
 use spacetimedb_lib::{ProductValue, ReducerDef};
 use spacetimedb_sats::WithTypespace;
 
+mod cron;
 mod host_controller;
 pub(crate) mod module_host;
-pub use module_host::{UpdateDatabaseError, UpdateDatabaseResult, UpdateDatabaseSuccess};
+pub use module_host::{MigratedTable, UpdateDatabaseError, UpdateDatabaseResult, UpdateDatabaseSuccess};
 pub mod scheduler;
 mod wasmer;
 
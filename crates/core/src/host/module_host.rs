@@ -1,4 +1,5 @@
 use super::host_controller::HostThreadpool;
+use super::scheduler::ScheduledReducerId;
 use super::{ArgsTuple, EnergyDiff, InvalidReducerArguments, ReducerArgs, ReducerCallResult, Timestamp};
 use crate::client::ClientConnectionSender;
 use crate::database_logger::LogLevel;
@@ -9,6 +10,8 @@ use crate::hash::Hash;
 use crate::identity::Identity;
 use crate::json::client_api::{SubscriptionUpdateJson, TableRowOperationJson, TableUpdateJson};
 use crate::protobuf::client_api::{table_row_operation, SubscriptionUpdate, TableRowOperation, TableUpdate};
+use crate::sql::params::SqlParams;
+use crate::sql::session::SqlSession;
 use crate::subscription::module_subscription_actor::ModuleSubscriptionManager;
 use crate::util::lending_pool::{Closed, LendingPool, LentResource, PoolClosed};
 use crate::util::notify_once::NotifyOnce;
@@ -17,14 +20,14 @@ use base64::{engine::general_purpose::STANDARD as BASE_64_STD, Engine as _};
 use futures::{Future, FutureExt};
 use indexmap::IndexMap;
 use spacetimedb_lib::relation::MemTable;
-use spacetimedb_lib::{Address, ReducerDef, TableDef};
+use spacetimedb_lib::{Address, ReducerDef, ScheduleDef, TableDef};
 use spacetimedb_primitives::TableId;
 use spacetimedb_sats::{ProductValue, Typespace, WithTypespace};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::sync::{Arc, Weak};
 use std::time::Duration;
-use tokio::sync::oneshot;
+use tokio::sync::{oneshot, OwnedMutexGuard};
 
 #[derive(Debug, Default, Clone)]
 pub struct DatabaseUpdate {
@@ -32,6 +35,21 @@ pub struct DatabaseUpdate {
 }
 
 impl DatabaseUpdate {
+    /// Returns this update with every update operation replaced by the deletion of the old row
+    /// followed by the insertion of the new row, for the clients that don't understand update operations.
+    pub fn split_updates(&self) -> DatabaseUpdate {
+        let tables = self
+            .tables
+            .iter()
+            .map(|table| DatabaseTableUpdate {
+                table_id: table.table_id,
+                table_name: table.table_name.clone(),
+                ops: table.split_updates().collect(),
+            })
+            .collect();
+        DatabaseUpdate { tables }
+    }
+
     pub fn is_empty(&self) -> bool {
         if self.tables.len() == 0 {
             return true;
@@ -44,11 +62,6 @@ impl DatabaseUpdate {
         //TODO: This should be wrapped with .auto_commit
         let tx = stdb.begin_tx();
         for record in tx_data.records.iter() {
-            let op = match record.op {
-                TxOp::Delete => 0,
-                TxOp::Insert(_) => 1,
-            };
-
             let vec = if let Some(vec) = map.get_mut(&record.table_id) {
                 vec
             } else {
@@ -56,12 +69,19 @@ impl DatabaseUpdate {
                 map.get_mut(&record.table_id).unwrap()
             };
 
+            let (op, old) = match &record.op {
+                TxOp::Delete => (0, None),
+                TxOp::Insert(_) => (1, None),
+                TxOp::Update { old_key, old_row, .. } => (2, Some((old_key.to_bytes(), old_row.clone()))),
+            };
+
             let (row, row_pk) = (record.product_value.clone(), record.key.to_bytes());
 
             vec.push(TableOp {
                 op_type: op,
                 row_pk,
                 row,
+                old,
             });
         }
 
@@ -100,14 +120,24 @@ impl DatabaseUpdate {
                         .map(|op| {
                             let mut row_bytes = Vec::new();
                             op.row.encode(&mut row_bytes);
+                            let (old_row_pk, old_row) = match op.old {
+                                Some((old_row_pk, old_row)) => {
+                                    let mut old_row_bytes = Vec::new();
+                                    old_row.encode(&mut old_row_bytes);
+                                    (old_row_pk, old_row_bytes)
+                                }
+                                None => (Vec::new(), Vec::new()),
+                            };
                             TableRowOperation {
-                                op: if op.op_type == 1 {
-                                    table_row_operation::OperationType::Insert.into()
-                                } else {
-                                    table_row_operation::OperationType::Delete.into()
+                                op: match op.op_type {
+                                    1 => table_row_operation::OperationType::Insert.into(),
+                                    2 => table_row_operation::OperationType::Update.into(),
+                                    _ => table_row_operation::OperationType::Delete.into(),
                                 },
                                 row_pk: op.row_pk,
                                 row: row_bytes,
+                                old_row_pk,
+                                old_row,
                             }
                         })
                         .collect(),
@@ -131,14 +161,22 @@ impl DatabaseUpdate {
                         .into_iter()
                         .map(|op| {
                             let row_pk = BASE_64_STD.encode(&op.row_pk);
+                            let (old_row_pk, old_row) = match op.old {
+                                Some((old_row_pk, old_row)) => {
+                                    (Some(BASE_64_STD.encode(old_row_pk)), Some(old_row.elements))
+                                }
+                                None => (None, None),
+                            };
                             TableRowOperationJson {
-                                op: if op.op_type == 1 {
-                                    "insert".into()
-                                } else {
-                                    "delete".into()
+                                op: match op.op_type {
+                                    1 => "insert".into(),
+                                    2 => "update".into(),
+                                    _ => "delete".into(),
                                 },
                                 row_pk,
                                 row: op.row.elements,
+                                old_row_pk,
+                                old_row,
                             }
                         })
                         .collect(),
@@ -155,11 +193,90 @@ pub struct DatabaseTableUpdate {
     pub ops: Vec<TableOp>,
 }
 
+impl DatabaseTableUpdate {
+    /// Returns the operations of this update with every update operation
+    /// replaced by the deletion of the old row followed by the insertion of the new row.
+    pub fn split_updates(&self) -> impl Iterator<Item = TableOp> + '_ {
+        self.ops.iter().flat_map(TableOp::split_update)
+    }
+
+    /// Merges the deletion of a row and the insertion of the row that replaced it,
+    /// as recorded by the update operations in `source`, back into a single update operation.
+    ///
+    /// The `ops` are assumed to be the result of evaluating a query over
+    /// [`DatabaseTableUpdate::split_updates`] of `source`.
+    /// If only one half of an update is present in `ops`,
+    /// i.e. the row moved in or out of the query's result, it is kept as a plain delete or insert.
+    pub fn merge_updates(source: &DatabaseTableUpdate, ops: Vec<TableOp>) -> Vec<TableOp> {
+        // The primary key of the new row of each update, by the primary key of the old row.
+        let replaced_by: HashMap<&[u8], &[u8]> = source
+            .ops
+            .iter()
+            .filter_map(|op| op.old.as_ref().map(|(old_row_pk, _)| (&old_row_pk[..], &op.row_pk[..])))
+            .collect();
+        if replaced_by.is_empty() {
+            return ops;
+        }
+
+        let inserted: HashSet<Vec<u8>> = ops
+            .iter()
+            .filter(|op| op.op_type == 1)
+            .map(|op| op.row_pk.clone())
+            .collect();
+        let (merged, mut ops): (Vec<_>, Vec<_>) = ops.into_iter().partition(|op| {
+            op.op_type == 0
+                && replaced_by
+                    .get(&op.row_pk[..])
+                    .map_or(false, |row_pk| inserted.contains(*row_pk))
+        });
+        let mut old_rows: HashMap<&[u8], (Vec<u8>, ProductValue)> = merged
+            .into_iter()
+            .map(|op| (replaced_by[&op.row_pk[..]], (op.row_pk, op.row)))
+            .collect();
+        for op in ops.iter_mut().filter(|op| op.op_type == 1) {
+            if let Some(old) = old_rows.remove(&op.row_pk[..]) {
+                op.op_type = 2;
+                op.old = Some(old);
+            }
+        }
+        ops
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TableOp {
+    /// `0` for a delete, `1` for an insert and `2` for an update.
     pub op_type: u8,
     pub row_pk: Vec<u8>,
     pub row: ProductValue,
+    /// For an update, the primary key and value of the row replaced by `row`.
+    pub old: Option<(Vec<u8>, ProductValue)>,
+}
+
+impl TableOp {
+    /// Returns an update as the deletion of the old row followed by the insertion of the new row,
+    /// and any other operation as is.
+    pub fn split_update(&self) -> impl Iterator<Item = TableOp> {
+        let (delete, op_type) = match &self.old {
+            Some((row_pk, row)) => {
+                let delete = TableOp {
+                    op_type: 0,
+                    row_pk: row_pk.clone(),
+                    row: row.clone(),
+                    old: None,
+                };
+                (Some(delete), 1)
+            }
+            None => (None, self.op_type),
+        };
+        let op = TableOp {
+            op_type,
+            row_pk: self.row_pk.clone(),
+            row: self.row.clone(),
+            old: None,
+        };
+        delete.into_iter().chain(std::iter::once(op))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -202,6 +319,8 @@ pub struct ModuleInfo {
     pub module_hash: Hash,
     pub typespace: Typespace,
     pub reducers: IndexMap<String, ReducerDef>,
+    /// The recurring schedules declared by the module, by the name of their reducer.
+    pub schedules: IndexMap<String, ScheduleDef>,
     pub catalog: HashMap<String, EntityDef>,
     pub log_tx: tokio::sync::broadcast::Sender<bytes::Bytes>,
     pub subscription: ModuleSubscriptionManager,
@@ -219,6 +338,14 @@ pub trait Module: Send + Sync + 'static {
         &self,
         caller_identity: Identity,
         query: String,
+        params: SqlParams,
+    ) -> Result<Vec<spacetimedb_lib::relation::MemTable>, DBError>;
+    fn sql_session_query(
+        &self,
+        caller_identity: Identity,
+        session: &mut SqlSession,
+        query: String,
+        params: SqlParams,
     ) -> Result<Vec<spacetimedb_lib::relation::MemTable>, DBError>;
     fn clear_table(&self, table_name: String) -> Result<(), anyhow::Error>;
 
@@ -238,7 +365,12 @@ pub trait ModuleInstance: Send + 'static {
 
     fn init_database(&mut self, fence: u128, args: ArgsTuple) -> anyhow::Result<ReducerCallResult>;
 
-    fn update_database(&mut self, fence: u128) -> anyhow::Result<UpdateDatabaseResult>;
+    /// Update the database to the schema of this module.
+    ///
+    /// Tables which are no longer part of the module are dropped once the
+    /// `__migrate__` reducer ran. Unless `drop_orphaned_tables` is set,
+    /// the update is rejected if any of them still holds rows.
+    fn update_database(&mut self, fence: u128, drop_orphaned_tables: bool) -> anyhow::Result<UpdateDatabaseResult>;
 
     fn call_reducer(
         &mut self,
@@ -248,6 +380,16 @@ pub trait ModuleInstance: Send + 'static {
         reducer_id: usize,
         args: ArgsTuple,
     ) -> ReducerCallResult;
+
+    /// Make the scheduled call `id` to the reducer `reducer_id`,
+    /// removing it from `st_scheduled` in the transaction of the reducer.
+    fn call_scheduled_reducer(
+        &mut self,
+        id: ScheduledReducerId,
+        caller_identity: Identity,
+        reducer_id: usize,
+        args: ArgsTuple,
+    ) -> ReducerCallResult;
 }
 
 // TODO: figure out how we want to handle traps. maybe it should just not return to the LendingPool and
@@ -274,8 +416,8 @@ impl<T: Module> ModuleInstance for AutoReplacingModuleInstance<T> {
         self.check_trap();
         ret
     }
-    fn update_database(&mut self, fence: u128) -> anyhow::Result<UpdateDatabaseResult> {
-        let ret = self.inst.update_database(fence);
+    fn update_database(&mut self, fence: u128, drop_orphaned_tables: bool) -> anyhow::Result<UpdateDatabaseResult> {
+        let ret = self.inst.update_database(fence, drop_orphaned_tables);
         self.check_trap();
         ret
     }
@@ -293,6 +435,17 @@ impl<T: Module> ModuleInstance for AutoReplacingModuleInstance<T> {
         self.check_trap();
         ret
     }
+    fn call_scheduled_reducer(
+        &mut self,
+        id: ScheduledReducerId,
+        caller_identity: Identity,
+        reducer_id: usize,
+        args: ArgsTuple,
+    ) -> ReducerCallResult {
+        let ret = self.inst.call_scheduled_reducer(id, caller_identity, reducer_id, args);
+        self.check_trap();
+        ret
+    }
 }
 
 #[derive(Clone)]
@@ -318,6 +471,14 @@ trait DynModuleHost: Send + Sync + 'static {
         &self,
         caller_identity: Identity,
         query: String,
+        params: SqlParams,
+    ) -> Result<Vec<spacetimedb_lib::relation::MemTable>, DBError>;
+    fn sql_session_query(
+        &self,
+        caller_identity: Identity,
+        session: &mut SqlSession,
+        query: String,
+        params: SqlParams,
     ) -> Result<Vec<spacetimedb_lib::relation::MemTable>, DBError>;
     fn clear_table(&self, table_name: String) -> Result<(), anyhow::Error>;
     fn start(&self);
@@ -388,8 +549,19 @@ impl<T: Module> DynModuleHost for HostControllerActor<T> {
         &self,
         caller_identity: Identity,
         query: String,
+        params: SqlParams,
     ) -> Result<Vec<spacetimedb_lib::relation::MemTable>, DBError> {
-        self.module.one_off_query(caller_identity, query)
+        self.module.one_off_query(caller_identity, query, params)
+    }
+
+    fn sql_session_query(
+        &self,
+        caller_identity: Identity,
+        session: &mut SqlSession,
+        query: String,
+        params: SqlParams,
+    ) -> Result<Vec<spacetimedb_lib::relation::MemTable>, DBError> {
+        self.module.sql_session_query(caller_identity, session, query, params)
     }
 
     fn clear_table(&self, table_name: String) -> Result<(), anyhow::Error> {
@@ -421,17 +593,26 @@ pub struct UpdateDatabaseSuccess {
     /// Outcome of calling the module's __update__ reducer, `None` if none is
     /// defined.
     pub update_result: Option<ReducerCallResult>,
-    /// Outcome of calling the module's pending __migrate__ reducers, empty if
-    /// none are defined or pending.
-    ///
-    /// Currently always empty, as __migrate__ is not yet supported.
+    /// Outcome of calling the module's __migrate__ reducer, empty if no
+    /// tables with incompatible schema changes had to be migrated.
     pub migrate_results: Vec<ReducerCallResult>,
+    /// Tables whose schema was migrated automatically, empty if all existing
+    /// tables were compatible as-is.
+    pub migrated_tables: Vec<MigratedTable>,
 }
 
+/// Summary of the automatic migration of a table performed by
+/// `update_database`, which is reported back to the publisher.
+pub use spacetimedb_lib::name::MigratedTable;
+
 #[derive(thiserror::Error, Debug)]
 pub enum UpdateDatabaseError {
     #[error("incompatible schema changes for: {tables:?}")]
     IncompatibleSchema { tables: Vec<String> },
+    #[error("migrate reducer failed: {0}")]
+    MigrateFailed(String),
+    #[error("tables no longer part of the module still hold rows: {tables:?}; publish with `--drop-orphaned-tables` to drop them")]
+    OrphanedTables { tables: Vec<String> },
     #[error(transparent)]
     Database(#[from] DBError),
 }
@@ -582,6 +763,30 @@ impl ModuleHost {
         res
     }
 
+    /// Make the scheduled call `id` to `reducer_name` on behalf of the module.
+    ///
+    /// The call is removed from `st_scheduled` in the transaction of the reducer,
+    /// so it's committed along with the writes of the reducer, or not at all.
+    pub async fn call_scheduled_reducer(
+        &self,
+        id: ScheduledReducerId,
+        reducer_name: &str,
+        args: ReducerArgs,
+    ) -> Result<ReducerCallResult, ReducerCallError> {
+        let (reducer_id, _, schema) = self
+            .info
+            .reducers
+            .get_full(reducer_name)
+            .ok_or(ReducerCallError::NoSuchReducer)?;
+
+        let args = args.into_tuple(self.info.typespace.with_type(schema))?;
+        let caller_identity = self.info.identity;
+
+        self.call(move |inst| inst.call_scheduled_reducer(id, caller_identity, reducer_id, args))
+            .await
+            .map_err(Into::into)
+    }
+
     pub fn catalog(&self) -> Catalog {
         Catalog(self.info.clone())
     }
@@ -600,8 +805,12 @@ impl ModuleHost {
             .map_err(InitDatabaseError::Other)
     }
 
-    pub async fn update_database(&self, fence: u128) -> Result<UpdateDatabaseResult, anyhow::Error> {
-        self.call(move |inst| inst.update_database(fence))
+    pub async fn update_database(
+        &self,
+        fence: u128,
+        drop_orphaned_tables: bool,
+    ) -> Result<UpdateDatabaseResult, anyhow::Error> {
+        self.call(move |inst| inst.update_database(fence, drop_orphaned_tables))
             .await?
             .map_err(Into::into)
     }
@@ -622,11 +831,37 @@ impl ModuleHost {
         &self,
         caller_identity: Identity,
         query: String,
+        params: SqlParams,
     ) -> Result<Vec<MemTable>, anyhow::Error> {
-        let result = self.inner.one_off_query(caller_identity, query)?;
+        // The query waits for the transaction in progress, if there's one.
+        let inner = self.inner.clone();
+        let result = tokio::task::spawn_blocking(move || inner.one_off_query(caller_identity, query, params)).await??;
         Ok(result)
     }
 
+    /// Runs `query` in the `SQL` session of a connection of `caller_identity`,
+    /// and hands the session back along with its result.
+    pub async fn sql_session_query(
+        &self,
+        caller_identity: Identity,
+        mut session: OwnedMutexGuard<SqlSession>,
+        query: String,
+        params: SqlParams,
+    ) -> (OwnedMutexGuard<SqlSession>, Result<Vec<MemTable>, anyhow::Error>) {
+        // Like `one_off_query`, a statement can wait for the transaction in progress,
+        // and `BEGIN` holds the transaction of the database until the session ends it.
+        let inner = self.inner.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let result = inner.sql_session_query(caller_identity, &mut session, query, params);
+            (session, result.map_err(Into::into))
+        })
+        .await;
+        match result {
+            Ok((session, result)) => (session, result),
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        }
+    }
+
     /// FIXME(jgilles): this is a temporary workaround for deleting not currently being supported
     /// for tables without primary keys. It is only used in the benchmarks.
     /// Note: this doesn't drop the table, it just clears it!
@@ -687,6 +922,10 @@ impl Catalog {
         let schema = self.get(name)?;
         Some(schema.with(schema.ty().as_reducer()?))
     }
+    /// Returns the recurring schedule of the reducer `name`, if it has one.
+    pub fn get_schedule(&self, name: &str) -> Option<&ScheduleDef> {
+        self.0.schedules.get(name)
+    }
     pub fn get_table(&self, name: &str) -> Option<WithTypespace<'_, TableDef>> {
         let schema = self.get(name)?;
         Some(schema.with(schema.ty().as_table()?))
@@ -1,15 +1,19 @@
-use std::path::Path;
+use std::collections::HashSet;
+use std::sync::Arc;
 
 use futures::StreamExt;
-use rustc_hash::FxHashMap;
-use sled::transaction::{ConflictableTransactionError::Abort as TxAbort, TransactionError};
-use spacetimedb_lib::bsatn;
-use spacetimedb_lib::bsatn::ser::BsatnError;
+use indexmap::IndexMap;
+use spacetimedb_lib::{bsatn, AlgebraicType, CatchUp, ReducerDef, ScheduleDef, ScheduleKind};
 use tokio::sync::mpsc;
-use tokio_util::time::{delay_queue, DelayQueue};
+use tokio_util::time::DelayQueue;
 
+use super::cron::{CronError, CronSchedule};
 use super::module_host::WeakModuleHost;
-use super::{ModuleHost, ReducerArgs, ReducerCallError, Timestamp};
+use super::{ModuleHost, ReducerArgs, ReducerCallError, ReducerCallResult, ReducerOutcome, Timestamp};
+use crate::db::datastore::locking_tx_datastore::{MutTxId, RowId};
+use crate::db::datastore::system_tables::{StScheduledFields, StScheduledRow, ST_SCHEDULED_ID};
+use crate::db::relational_db::RelationalDB;
+use crate::error::DBError;
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash)]
 pub struct ScheduledReducerId(pub u64);
@@ -21,55 +25,51 @@ enum MsgOrExit<T> {
 
 enum SchedulerMessage {
     Schedule { id: ScheduledReducerId, at: Timestamp },
-    Cancel { id: ScheduledReducerId },
-}
-
-#[derive(spacetimedb_sats::ser::Serialize, spacetimedb_sats::de::Deserialize)]
-struct ScheduledReducer {
-    at: Timestamp,
-    reducer: String,
-    bsatn_args: Vec<u8>,
 }
 
+/// Schedules the reducer calls of a module.
+///
+/// The calls are stored in the system table `st_scheduled`,
+/// in the transaction of the reducer that schedules or cancels them,
+/// so they are undone along with it, and restored from the commit log on restart.
+///
+/// So is the next call of each recurring schedule declared by the module, see [ScheduleDef].
+///
+/// The row of a call scheduled by a reducer is deleted in the transaction of the called reducer,
+/// so once that committed, the call isn't made again after a restart.
+/// The next call of a recurring schedule is stored in a transaction of its own,
+/// after the current one committed, so a restart in between makes the current call again:
+/// recurring calls are delivered at least once.
 #[derive(Clone)]
 pub struct Scheduler {
     tx: mpsc::UnboundedSender<MsgOrExit<SchedulerMessage>>,
-    db: sled::Db,
+    db: Arc<RelationalDB>,
 }
 
 pub struct SchedulerStarter {
+    tx: mpsc::WeakUnboundedSender<MsgOrExit<SchedulerMessage>>,
     rx: mpsc::UnboundedReceiver<MsgOrExit<SchedulerMessage>>,
-    db: sled::Db,
+    db: Arc<RelationalDB>,
 }
 
 impl Scheduler {
-    pub fn dummy(dummy_path: &Path) -> Self {
+    pub fn dummy(db: Arc<RelationalDB>) -> Self {
         let (tx, _) = mpsc::unbounded_channel();
-        let db = sled::open(dummy_path).unwrap();
         Self { tx, db }
     }
 
-    pub fn open(scheduler_db_path: impl AsRef<Path>) -> anyhow::Result<(Self, SchedulerStarter)> {
-        let db = sled::Config::default()
-            .path(scheduler_db_path)
-            .flush_every_ms(Some(50))
-            .mode(sled::Mode::HighThroughput)
-            .open()?;
-
-        Ok(Self::from_db(db))
-    }
-
-    fn from_db(db: sled::Db) -> (Self, SchedulerStarter) {
+    pub fn open(db: Arc<RelationalDB>) -> (Self, SchedulerStarter) {
         let (tx, rx) = mpsc::unbounded_channel();
-        (Scheduler { tx, db: db.clone() }, SchedulerStarter { rx, db })
+        let starter = SchedulerStarter {
+            tx: tx.downgrade(),
+            rx,
+            db: db.clone(),
+        };
+        (Scheduler { tx, db }, starter)
     }
 
     pub fn new_with_same_db(&self) -> (Self, SchedulerStarter) {
-        Self::from_db(self.db.clone())
-    }
-
-    pub fn clear(&self) {
-        self.db.clear().unwrap()
+        Self::open(self.db.clone())
     }
 }
 
@@ -79,19 +79,19 @@ impl SchedulerStarter {
     pub fn start(self, module_host: &ModuleHost) -> anyhow::Result<()> {
         let mut queue = DelayQueue::new();
 
-        for entry in self.db.iter() {
-            let (k, v) = entry?;
-            let get_u64 = |b: &[u8]| u64::from_le_bytes(b.try_into().unwrap());
-            let id = ScheduledReducerId(get_u64(&k));
-            let at = Timestamp(get_u64(&v[..8]));
-            queue.insert(id, at.to_duration_from_now());
+        let schedules = &module_host.info().schedules;
+        let scheduled = self
+            .db
+            .with_auto_commit(|tx| load_scheduled(&self.db, tx, schedules, Timestamp::now()))?;
+        for (id, at) in scheduled {
+            queue.insert(id, queue_delay(at));
         }
 
         tokio::spawn(
             SchedulerActor {
+                tx: self.tx,
                 rx: self.rx,
                 queue,
-                key_map: FxHashMap::default(),
                 db: self.db,
                 module_host: module_host.downgrade(),
             }
@@ -132,18 +132,33 @@ const MAX_SCHEDULE_DELAY: std::time::Duration = std::time::Duration::from_millis
     (1 << (6 * 6)) - 1,
 );
 
+/// The longest delay after which a call is queued.
+///
+/// The next call of a cron schedule may be years away, like `0 0 0 29 2 *`,
+/// beyond what `DelayQueue` supports.
+/// Such a call is queued at this limit instead, and queued again from there until it's due.
+/// Half of [MAX_SCHEDULE_DELAY] leaves room for the window of `DelayQueue` to slide.
+const MAX_QUEUE_DELAY: std::time::Duration = std::time::Duration::from_millis(((1 << (6 * 6)) - 1) / 2);
+
+/// Returns the delay after which to queue a call due at `at`, see [MAX_QUEUE_DELAY].
+fn queue_delay(at: Timestamp) -> std::time::Duration {
+    at.to_duration_from_now().min(MAX_QUEUE_DELAY)
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum ScheduleError {
     #[error("Unable to schedule with long delay at {0:?}")]
     DelayTooLong(Timestamp),
 
-    #[error("Unable to generate a ScheduledReducerId: {0:?}")]
-    IdTransactionError(#[from] TransactionError<BsatnError>),
+    #[error("Unable to store the scheduled reducer: {0}")]
+    Db(#[from] DBError),
 }
 
 impl Scheduler {
+    /// Schedules a call to `reducer` at `at`, in the transaction `tx` of the calling reducer.
     pub fn schedule(
         &self,
+        tx: &mut MutTxId,
         reducer: String,
         bsatn_args: Vec<u8>,
         at: Timestamp,
@@ -175,41 +190,34 @@ impl Scheduler {
             return Err(ScheduleError::DelayTooLong(at));
         }
 
-        let reducer = ScheduledReducer {
-            at,
+        let row = StScheduledRow {
+            // Generated by the sequence of `scheduled_id`.
+            scheduled_id: 0,
             reducer,
-            bsatn_args,
+            args: bsatn_args,
+            scheduled_at: at.0,
+            recurring: false,
         };
+        let row = self.db.insert(tx, ST_SCHEDULED_ID, row.into())?;
+        let id = ScheduledReducerId(StScheduledRow::try_from(&row)?.scheduled_id);
 
-        let id = self.db.transaction(|tx| {
-            let id = tx.generate_id()?;
-            let reducer = bsatn::to_vec(&reducer).map_err(TxAbort)?;
-            tx.insert(&id.to_le_bytes(), reducer)?;
-            Ok(ScheduledReducerId(id))
-        })?;
-
+        // The call is queued before `tx` commits, and when it's due, it's only made
+        // if its row is still in `st_scheduled`, so a rolled back schedule is never called.
         // if the actor has exited, it's fine to ignore; it means that the host actor calling
         // schedule will exit soon as well, and it'll be scheduled to run when the module host restarts
         let _ = self.tx.send(MsgOrExit::Msg(SchedulerMessage::Schedule { id, at }));
         Ok(id)
     }
 
-    pub fn cancel(&self, id: ScheduledReducerId) {
-        let res = self.db.transaction(|tx| {
-            tx.remove(&id.0.to_le_bytes())?;
-            Ok(())
-        });
-        match res {
-            Ok(()) => {
-                // if it's exited it's not gonna run it :) see also the comment in schedule()
-                let _ = self.tx.send(MsgOrExit::Msg(SchedulerMessage::Cancel { id }));
-            }
-            // we could return an error here, but that would give them information that
-            // there exists a scheduled reducer with this id. like returning a HTTP 400
-            // instead of a 404
-            Err(TransactionError::Abort(())) => {}
-            Err(TransactionError::Storage(e)) => panic!("sled error: {e:?}"),
-        }
+    /// Cancels the scheduled call `id`, in the transaction `tx` of the calling reducer.
+    ///
+    /// The call stays queued, but it won't be made once its row is deleted from `st_scheduled`.
+    pub fn cancel(&self, tx: &mut MutTxId, id: ScheduledReducerId) -> Result<(), ScheduleError> {
+        // we don't return an error if there's no such call, as that would give them information that
+        // there exists a scheduled reducer with this id. like returning a HTTP 400
+        // instead of a 404
+        delete_scheduled(&self.db, tx, id)?;
+        Ok(())
     }
 
     pub fn close(&self) {
@@ -217,11 +225,250 @@ impl Scheduler {
     }
 }
 
+#[derive(thiserror::Error, Debug)]
+pub enum InvalidSchedule {
+    #[error("no such reducer")]
+    NoSuchReducer,
+    #[error("the reducer is scheduled more than once")]
+    Duplicate,
+    #[error("the reducer must take no arguments, or the `Timestamp` of the call")]
+    Arguments,
+    #[error("the interval must be longer than zero")]
+    ZeroInterval,
+    #[error("the interval must be shorter than {0:?}")]
+    IntervalTooLong(std::time::Duration),
+    #[error("invalid cron expression: {0}")]
+    Cron(#[from] CronError),
+}
+
+/// Checks that `schedule` can call one of the `reducers` of the module.
+pub fn validate_schedule(
+    schedule: &ScheduleDef,
+    reducers: &IndexMap<String, ReducerDef>,
+) -> Result<(), InvalidSchedule> {
+    let reducer = reducers.get(&schedule.reducer).ok_or(InvalidSchedule::NoSuchReducer)?;
+    match &reducer.args[..] {
+        [] => {}
+        [arg] if arg.algebraic_type == AlgebraicType::U64 => {}
+        _ => return Err(InvalidSchedule::Arguments),
+    }
+    Recurrence::new(schedule).map(drop)
+}
+
+/// The times at which a [ScheduleDef] calls its reducer.
+struct Recurrence {
+    kind: RecurrenceKind,
+    catch_up: CatchUp,
+}
+
+enum RecurrenceKind {
+    Cron(CronSchedule),
+    FixedRate(u64),
+    FixedDelay(u64),
+}
+
+impl Recurrence {
+    fn new(schedule: &ScheduleDef) -> Result<Self, InvalidSchedule> {
+        let kind = match &schedule.kind {
+            ScheduleKind::Cron(cron) => RecurrenceKind::Cron(cron.parse()?),
+            ScheduleKind::FixedRate(0) | ScheduleKind::FixedDelay(0) => return Err(InvalidSchedule::ZeroInterval),
+            ScheduleKind::FixedRate(micros) | ScheduleKind::FixedDelay(micros)
+                if u128::from(*micros) >= MAX_SCHEDULE_DELAY.as_micros() =>
+            {
+                return Err(InvalidSchedule::IntervalTooLong(MAX_SCHEDULE_DELAY))
+            }
+            ScheduleKind::FixedRate(micros) => RecurrenceKind::FixedRate(*micros),
+            ScheduleKind::FixedDelay(micros) => RecurrenceKind::FixedDelay(*micros),
+        };
+        Ok(Self {
+            kind,
+            catch_up: schedule.catch_up,
+        })
+    }
+
+    /// Returns the first call of a schedule declared at `now`.
+    ///
+    /// Here and below, there's no call when its time can't be represented.
+    fn first(&self, now: Timestamp) -> Option<Timestamp> {
+        match &self.kind {
+            RecurrenceKind::Cron(cron) => cron.next_after(now),
+            RecurrenceKind::FixedRate(micros) | RecurrenceKind::FixedDelay(micros) => {
+                now.0.checked_add(*micros).map(Timestamp)
+            }
+        }
+    }
+
+    /// Returns when to make the call that was due at `due`, now that the scheduler starts at `now`.
+    ///
+    /// Unless the calls missed while the database was down are skipped, it's made right away.
+    fn resume(&self, due: Timestamp, now: Timestamp) -> Option<Timestamp> {
+        if due > now || self.catch_up != CatchUp::Skip {
+            Some(due)
+        } else {
+            self.skip_missed(due, now)
+        }
+    }
+
+    /// Returns the call following the one due at `due`, which ended at `now`.
+    ///
+    /// The calls already due by then are only made one after the other with [CatchUp::All].
+    fn next(&self, due: Timestamp, now: Timestamp) -> Option<Timestamp> {
+        let next = match &self.kind {
+            RecurrenceKind::Cron(cron) => cron.next_after(due)?,
+            RecurrenceKind::FixedRate(micros) => Timestamp(due.0.checked_add(*micros)?),
+            RecurrenceKind::FixedDelay(micros) => Timestamp(now.0.checked_add(*micros)?),
+        };
+        if next > now || self.catch_up == CatchUp::All {
+            Some(next)
+        } else {
+            self.skip_missed(due, now)
+        }
+    }
+
+    /// Returns the first call after `now`, skipping those due since `due`.
+    fn skip_missed(&self, due: Timestamp, now: Timestamp) -> Option<Timestamp> {
+        match &self.kind {
+            RecurrenceKind::Cron(cron) => cron.next_after(now),
+            RecurrenceKind::FixedRate(micros) => {
+                let missed = now.0.saturating_sub(due.0) / micros + 1;
+                missed.checked_mul(*micros)?.checked_add(due.0).map(Timestamp)
+            }
+            RecurrenceKind::FixedDelay(micros) => now.0.checked_add(*micros).map(Timestamp),
+        }
+    }
+}
+
+/// Returns the calls in `st_scheduled` to queue when the scheduler starts at `now`,
+/// after bringing their recurring calls in line with the `schedules` of the module.
+///
+/// The calls of the schedules that the module no longer declares as they are stored,
+/// e.g. after an update of the module, are replaced by the first call of the new schedules.
+fn load_scheduled(
+    db: &RelationalDB,
+    tx: &mut MutTxId,
+    schedules: &IndexMap<String, ScheduleDef>,
+    now: Timestamp,
+) -> Result<Vec<(ScheduledReducerId, Timestamp)>, DBError> {
+    let rows = db
+        .iter(tx, ST_SCHEDULED_ID)?
+        .map(|row| Ok(StScheduledRow::try_from(row.view())?.to_owned()))
+        .collect::<Result<Vec<_>, DBError>>()?;
+
+    let mut queued = Vec::new();
+    let mut resumed = HashSet::new();
+    for row in rows {
+        let id = ScheduledReducerId(row.scheduled_id);
+        if !row.recurring {
+            queued.push((id, Timestamp(row.scheduled_at)));
+            continue;
+        }
+        delete_scheduled(db, tx, id)?;
+        let Some(schedule) = schedules.get(&row.reducer) else {
+            continue;
+        };
+        let Ok(recurrence) = Recurrence::new(schedule) else {
+            continue;
+        };
+        if bsatn::to_vec(schedule).unwrap() != row.args || !resumed.insert(row.reducer.clone()) {
+            continue;
+        }
+        if let Some(at) = recurrence.resume(Timestamp(row.scheduled_at), now) {
+            let row = StScheduledRow {
+                scheduled_at: at.0,
+                ..row
+            };
+            db.insert(tx, ST_SCHEDULED_ID, row.into())?;
+            queued.push((id, at));
+        }
+    }
+
+    for (reducer, schedule) in schedules {
+        let Ok(recurrence) = Recurrence::new(schedule) else {
+            continue;
+        };
+        if resumed.contains(reducer) {
+            continue;
+        }
+        if let Some(at) = recurrence.first(now) {
+            let row = StScheduledRow {
+                // Generated by the sequence of `scheduled_id`.
+                scheduled_id: 0,
+                reducer: reducer.clone(),
+                args: bsatn::to_vec(schedule).unwrap(),
+                scheduled_at: at.0,
+                recurring: true,
+            };
+            let row = db.insert(tx, ST_SCHEDULED_ID, row.into())?;
+            queued.push((ScheduledReducerId(StScheduledRow::try_from(&row)?.scheduled_id), at));
+        }
+    }
+    Ok(queued)
+}
+
+/// Moves the recurring call `scheduled`, which was just made, to the following call of its schedule.
+fn reschedule(
+    db: &RelationalDB,
+    tx: &mut MutTxId,
+    scheduled: StScheduledRow<String>,
+    now: Timestamp,
+) -> Result<Option<Timestamp>, DBError> {
+    let id = ScheduledReducerId(scheduled.scheduled_id);
+    // The row may have been replaced by an update of the module while the call was made.
+    if find_scheduled(db, tx, id)?.as_ref() != Some(&scheduled) {
+        return Ok(None);
+    }
+    delete_scheduled(db, tx, id)?;
+    let next = bsatn::from_slice::<ScheduleDef>(&scheduled.args)
+        .ok()
+        .and_then(|schedule| Recurrence::new(&schedule).ok())
+        .and_then(|recurrence| recurrence.next(Timestamp(scheduled.scheduled_at), now));
+    if let Some(at) = next {
+        let row = StScheduledRow {
+            scheduled_at: at.0,
+            ..scheduled
+        };
+        db.insert(tx, ST_SCHEDULED_ID, row.into())?;
+    }
+    Ok(next)
+}
+
+/// Returns the row of the scheduled call `id`, if it's still in `st_scheduled`.
+fn find_scheduled(
+    db: &RelationalDB,
+    tx: &MutTxId,
+    id: ScheduledReducerId,
+) -> Result<Option<StScheduledRow<String>>, DBError> {
+    db.iter_by_col_eq(
+        tx,
+        ST_SCHEDULED_ID,
+        StScheduledFields::ScheduledId.col_id(),
+        id.0.into(),
+    )?
+    .next()
+    .map(|row| Ok(StScheduledRow::try_from(row.view())?.to_owned()))
+    .transpose()
+}
+
+/// Deletes the row of the scheduled call `id` from `st_scheduled`.
+pub(crate) fn delete_scheduled(db: &RelationalDB, tx: &mut MutTxId, id: ScheduledReducerId) -> Result<(), DBError> {
+    let row_ids = db
+        .iter_by_col_eq(
+            tx,
+            ST_SCHEDULED_ID,
+            StScheduledFields::ScheduledId.col_id(),
+            id.0.into(),
+        )?
+        .map(|row| RowId(*row.id()))
+        .collect::<Vec<_>>();
+    db.delete(tx, ST_SCHEDULED_ID, row_ids);
+    Ok(())
+}
+
 struct SchedulerActor {
+    tx: mpsc::WeakUnboundedSender<MsgOrExit<SchedulerMessage>>,
     rx: mpsc::UnboundedReceiver<MsgOrExit<SchedulerMessage>>,
     queue: DelayQueue<ScheduledReducerId>,
-    key_map: FxHashMap<ScheduledReducerId, delay_queue::Key>,
-    db: sled::Db,
+    db: Arc<RelationalDB>,
     module_host: WeakModuleHost,
 }
 
@@ -237,7 +484,6 @@ impl SchedulerActor {
                 },
                 Some(scheduled) = self.queue.next() => {
                     let id = scheduled.into_inner();
-                    self.key_map.remove(&id);
                     self.handle_queued(id).await;
                 }
             }
@@ -247,13 +493,7 @@ impl SchedulerActor {
     fn handle_message(&mut self, msg: SchedulerMessage) {
         match msg {
             SchedulerMessage::Schedule { id, at } => {
-                let key = self.queue.insert(id, at.to_duration_from_now());
-                self.key_map.insert(id, key);
-            }
-            SchedulerMessage::Cancel { id } => {
-                if let Some(key) = self.key_map.remove(&id) {
-                    self.queue.remove(&key);
-                }
+                self.queue.insert(id, queue_delay(at));
             }
         }
     }
@@ -262,30 +502,104 @@ impl SchedulerActor {
         let Some(module_host) = self.module_host.upgrade() else {
             return;
         };
-        let Some(scheduled) = self.db.get(id.0.to_le_bytes()).unwrap() else {
-            return;
-        };
-        let scheduled: ScheduledReducer = bsatn::from_slice(&scheduled).unwrap();
         let db = self.db.clone();
+        let tx = self.tx.clone();
         tokio::spawn(async move {
+            // This waits for the transaction that scheduled the call, if it's still running.
+            let lookup = tokio::task::spawn_blocking({
+                let db = db.clone();
+                move || db.with_read_only(|tx| find_scheduled(&db, tx, id))
+            });
+            let scheduled = match lookup.await {
+                Ok(Ok(Some(scheduled))) => scheduled,
+                // The call was cancelled, or scheduled by a transaction that was rolled back.
+                Ok(Ok(None)) => return,
+                Ok(Err(e)) => {
+                    log::error!("reading scheduled reducer failed: {e:#}");
+                    return;
+                }
+                Err(e) => {
+                    log::error!("reading scheduled reducer panicked: {e:#}");
+                    return;
+                }
+            };
+            // The call was queued at the limit of the queue, see `MAX_QUEUE_DELAY`,
+            // or by the scheduler of a previous version of the module,
+            // before the row was moved to a later call.
+            if Timestamp(scheduled.scheduled_at) > Timestamp::now() {
+                let _ = tx.upgrade().map(|tx| {
+                    tx.send(MsgOrExit::Msg(SchedulerMessage::Schedule {
+                        id,
+                        at: Timestamp(scheduled.scheduled_at),
+                    }))
+                });
+                return;
+            }
             let info = module_host.info();
             let identity = info.identity;
+            let args = if scheduled.recurring {
+                // Recurring calls pass the time they were due to the reducers taking a `Timestamp`.
+                match info.reducers.get(&scheduled.reducer) {
+                    Some(reducer) if reducer.args.len() == 1 => bsatn::to_vec(&scheduled.scheduled_at).unwrap(),
+                    _ => Vec::new(),
+                }
+            } else {
+                scheduled.args.clone()
+            };
             // TODO: pass a logical "now" timestamp to this reducer call, but there's some
             //       intricacies to get right (how much drift to tolerate? what kind of tokio::time::MissedTickBehavior do we want?)
-            let res = module_host
-                .call_reducer(
-                    identity,
-                    // Scheduled reducers take `None` as the caller address.
-                    None,
-                    None,
-                    &scheduled.reducer,
-                    ReducerArgs::Bsatn(scheduled.bsatn_args.into()),
-                )
-                .await;
-            if !matches!(res, Err(ReducerCallError::NoSuchModule(_))) {
+            let res = if scheduled.recurring {
+                module_host
+                    .call_reducer(
+                        identity,
+                        // Scheduled reducers take `None` as the caller address.
+                        None,
+                        None,
+                        &scheduled.reducer,
+                        ReducerArgs::Bsatn(args.into()),
+                    )
+                    .await
+            } else {
+                module_host
+                    .call_scheduled_reducer(id, &scheduled.reducer, ReducerArgs::Bsatn(args.into()))
+                    .await
+            };
+            if scheduled.recurring && !matches!(res, Err(ReducerCallError::NoSuchModule(_))) {
+                // Failed calls are not retried, the schedule just goes on.
+                let next = tokio::task::spawn_blocking({
+                    let db = db.clone();
+                    move || db.with_auto_commit(|tx| reschedule(&db, tx, scheduled, Timestamp::now()))
+                });
+                match next.await {
+                    Ok(Ok(Some(at))) => {
+                        let _ = tx
+                            .upgrade()
+                            .map(|tx| tx.send(MsgOrExit::Msg(SchedulerMessage::Schedule { id, at })));
+                    }
+                    Ok(Ok(None)) => {}
+                    Ok(Err(e)) => log::error!("rescheduling recurring reducer failed: {e:#}"),
+                    Err(e) => log::error!("rescheduling recurring reducer panicked: {e:#}"),
+                }
+            } else if !matches!(
+                res,
+                Ok(ReducerCallResult {
+                    outcome: ReducerOutcome::Committed,
+                    ..
+                }) | Err(ReducerCallError::NoSuchModule(_))
+            ) {
+                // A committed call was removed from `st_scheduled` by the transaction of the reducer.
+                // A failed one is not retried, so it's removed on its own.
                 // if we didn't actually call the reducer because the module exited, leave
-                // the ScheduledReducer in the database for when the module restarts
-                let _ = db.remove(id.0.to_le_bytes());
+                // the scheduled call in `st_scheduled` for when the module restarts
+                let delete = tokio::task::spawn_blocking({
+                    let db = db.clone();
+                    move || db.with_auto_commit(|tx| delete_scheduled(&db, tx, id))
+                });
+                match delete.await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => log::error!("removing scheduled reducer failed: {e:#}"),
+                    Err(e) => log::error!("removing scheduled reducer panicked: {e:#}"),
+                }
             }
             match res {
                 Ok(_) => {}
@@ -294,3 +608,203 @@ impl SchedulerActor {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::relational_db::open_db;
+    use crate::db::relational_db::tests_utils::make_test_db;
+    use spacetimedb_lib::error::ResultTest;
+
+    fn all_scheduled(db: &RelationalDB) -> ResultTest<Vec<StScheduledRow<String>>> {
+        Ok(db.with_read_only(|tx| {
+            db.iter(tx, ST_SCHEDULED_ID)?
+                .map(|row| Ok(StScheduledRow::try_from(row.view())?.to_owned()))
+                .collect::<Result<Vec<_>, DBError>>()
+        })?)
+    }
+
+    #[test]
+    fn test_schedule_in_tx() -> ResultTest<()> {
+        let (db, tmp_dir) = make_test_db()?;
+        let (scheduler, starter) = Scheduler::open(Arc::new(db));
+        let db = scheduler.db.clone();
+        let at = Timestamp::now();
+
+        // A call scheduled by a rolled back transaction is dropped along with it.
+        let mut tx = db.begin_tx();
+        scheduler.schedule(&mut tx, "rolled_back".into(), vec![], at)?;
+        db.rollback_tx(tx);
+        assert_eq!(all_scheduled(&db)?, vec![]);
+
+        let mut tx = db.begin_tx();
+        let id = scheduler.schedule(&mut tx, "reducer".into(), vec![1, 2], at)?;
+        let cancelled = scheduler.schedule(&mut tx, "cancelled".into(), vec![], at)?;
+        scheduler.cancel(&mut tx, cancelled)?;
+        db.commit_tx(tx)?;
+
+        // So is the cancellation of a rolled back transaction.
+        let mut tx = db.begin_tx();
+        scheduler.cancel(&mut tx, id)?;
+        db.rollback_tx(tx);
+
+        let expected = vec![StScheduledRow {
+            scheduled_id: id.0,
+            reducer: "reducer".to_string(),
+            args: vec![1, 2],
+            scheduled_at: at.0,
+            recurring: false,
+        }];
+        assert_eq!(all_scheduled(&db)?, expected);
+
+        // The calls are replayed from the commit log, and their ids are never reused.
+        drop((scheduler, starter, db));
+        let (scheduler, _starter) = Scheduler::open(Arc::new(open_db(&tmp_dir, false, true)?));
+        let db = scheduler.db.clone();
+        assert_eq!(all_scheduled(&db)?, expected);
+
+        let mut tx = db.begin_tx();
+        let next = scheduler.schedule(&mut tx, "reducer".into(), vec![], at)?;
+        db.commit_tx(tx)?;
+        assert!(next.0 > cancelled.0);
+
+        // A call can't be scheduled beyond what the queue supports.
+        let mut tx = db.begin_tx();
+        let too_late = Timestamp::from_systemtime(std::time::SystemTime::now() + MAX_SCHEDULE_DELAY * 2);
+        let result = scheduler.schedule(&mut tx, "reducer".into(), vec![], too_late);
+        assert!(matches!(result, Err(ScheduleError::DelayTooLong(_))));
+        db.rollback_tx(tx);
+
+        Ok(())
+    }
+
+    fn schedule(kind: ScheduleKind, catch_up: CatchUp) -> IndexMap<String, ScheduleDef> {
+        let schedule = ScheduleDef {
+            reducer: "tick".into(),
+            kind,
+            catch_up,
+        };
+        [(schedule.reducer.clone(), schedule)].into_iter().collect()
+    }
+
+    fn load(db: &RelationalDB, schedules: &IndexMap<String, ScheduleDef>, now: u64) -> ResultTest<Vec<u64>> {
+        let queued = db.with_auto_commit(|tx| load_scheduled(db, tx, schedules, Timestamp(now)))?;
+        Ok(queued.into_iter().map(|(_, at)| at.0).collect())
+    }
+
+    #[test]
+    fn test_recurrence() -> ResultTest<()> {
+        let rate = |catch_up| Recurrence::new(&schedule(ScheduleKind::FixedRate(10), catch_up)["tick"]);
+        let delay = Recurrence::new(&schedule(ScheduleKind::FixedDelay(10), CatchUp::All)["tick"])?;
+
+        // A call that ends late is followed by the next one due, or the next one after it ends.
+        assert_eq!(
+            rate(CatchUp::Skip)?.next(Timestamp(100), Timestamp(103)),
+            Some(Timestamp(110))
+        );
+        assert_eq!(
+            rate(CatchUp::Skip)?.next(Timestamp(100), Timestamp(125)),
+            Some(Timestamp(130))
+        );
+        assert_eq!(
+            rate(CatchUp::All)?.next(Timestamp(100), Timestamp(125)),
+            Some(Timestamp(110))
+        );
+        assert_eq!(delay.next(Timestamp(100), Timestamp(125)), Some(Timestamp(135)));
+
+        // The calls missed while the database was down are made according to the catch up policy.
+        assert_eq!(
+            rate(CatchUp::Skip)?.resume(Timestamp(100), Timestamp(125)),
+            Some(Timestamp(130))
+        );
+        assert_eq!(
+            rate(CatchUp::Once)?.resume(Timestamp(100), Timestamp(125)),
+            Some(Timestamp(100))
+        );
+        assert_eq!(
+            rate(CatchUp::Once)?.next(Timestamp(100), Timestamp(126)),
+            Some(Timestamp(130))
+        );
+
+        let cron = ScheduleKind::Cron("0 */5 * * * *".into());
+        let cron = Recurrence::new(&schedule(cron, CatchUp::Skip)["tick"])?;
+        assert_eq!(cron.first(Timestamp(1)), Some(Timestamp(300_000_000)));
+        assert!(Recurrence::new(&schedule(ScheduleKind::FixedRate(0), CatchUp::Skip)["tick"]).is_err());
+        assert!(Recurrence::new(&schedule(ScheduleKind::Cron("* * *".into()), CatchUp::Skip)["tick"]).is_err());
+
+        // The interval must fit in the queue.
+        let micros = MAX_SCHEDULE_DELAY.as_micros() as u64;
+        let too_long = Recurrence::new(&schedule(ScheduleKind::FixedDelay(micros), CatchUp::Skip)["tick"]);
+        assert!(matches!(too_long, Err(InvalidSchedule::IntervalTooLong(_))));
+
+        // There's no call after the last representable time.
+        let rate = rate(CatchUp::All)?;
+        assert_eq!(rate.first(Timestamp(u64::MAX - 5)), None);
+        assert_eq!(rate.next(Timestamp(u64::MAX - 5), Timestamp(u64::MAX - 5)), None);
+        let rate = Recurrence::new(&schedule(ScheduleKind::FixedRate(10), CatchUp::Skip)["tick"])?;
+        assert_eq!(rate.resume(Timestamp(u64::MAX - 25), Timestamp(u64::MAX - 1)), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_queue_far_off_call() -> ResultTest<()> {
+        // After the 29th of February 2028, the next one is 4 years away.
+        let cron = ScheduleKind::Cron("0 0 0 29 2 *".into());
+        let leap_day = Recurrence::new(&schedule(cron, CatchUp::Skip)["tick"])?;
+        let at = leap_day.first(Timestamp(1_835_481_600_000_000)).unwrap();
+        assert_eq!(at, Timestamp(1_961_625_600_000_000));
+
+        // It's queued at the limit of the queue, instead of panicking.
+        assert_eq!(queue_delay(at), MAX_QUEUE_DELAY);
+        let mut queue = DelayQueue::new();
+        queue.insert(ScheduledReducerId(0), queue_delay(at));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_recurring() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let (scheduler, _starter) = Scheduler::open(Arc::new(db));
+        let db = scheduler.db.clone();
+        let mut tx = db.begin_tx();
+        scheduler.schedule(&mut tx, "once".into(), vec![], Timestamp(5))?;
+        db.commit_tx(tx)?;
+
+        // The first call of a new schedule is stored along with the calls scheduled by reducers.
+        let skip = schedule(ScheduleKind::FixedRate(10), CatchUp::Skip);
+        assert_eq!(load(&db, &skip, 100)?, vec![5, 110]);
+        let recurring = |db: &RelationalDB| -> ResultTest<_> {
+            Ok(all_scheduled(db)?
+                .into_iter()
+                .filter(|row| row.recurring)
+                .collect::<Vec<_>>())
+        };
+        let [row] = &recurring(&db)?[..] else { panic!() };
+        assert_eq!((row.reducer.as_str(), row.scheduled_at), ("tick", 110));
+
+        // After the call is made, the row moves to the next one.
+        let next = db.with_auto_commit(|tx| reschedule(&db, tx, row.clone(), Timestamp(112)))?;
+        assert_eq!(next, Some(Timestamp(120)));
+        assert_eq!(load(&db, &skip, 115)?, vec![5, 120]);
+
+        // The calls missed while the database was down are skipped.
+        assert_eq!(load(&db, &skip, 145)?, vec![5, 150]);
+
+        // A schedule changed by an update of the module starts over.
+        let once = schedule(ScheduleKind::FixedRate(10), CatchUp::Once);
+        assert_eq!(load(&db, &once, 175)?, vec![5, 185]);
+
+        // With `CatchUp::Once`, the missed calls are made once, right away.
+        assert_eq!(load(&db, &once, 200)?, vec![5, 185]);
+
+        let slower = schedule(ScheduleKind::FixedRate(50), CatchUp::Once);
+        assert_eq!(load(&db, &slower, 200)?, vec![5, 250]);
+        assert_eq!(recurring(&db)?.len(), 1);
+
+        // A removed schedule is dropped.
+        assert_eq!(load(&db, &IndexMap::new(), 200)?, vec![5]);
+        assert_eq!(recurring(&db)?, vec![]);
+
+        Ok(())
+    }
+}
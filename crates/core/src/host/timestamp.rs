@@ -2,7 +2,7 @@ use std::time::{Duration, SystemTime};
 
 use spacetimedb_sats::{impl_deserialize, impl_serialize};
 
-#[derive(Copy, Clone, PartialEq, Eq, Debug, serde::Serialize)]
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, serde::Serialize)]
 #[serde(transparent)]
 #[repr(transparent)]
 pub struct Timestamp(pub u64);
@@ -18,6 +18,8 @@ pub const SETUP_DUNDER: &str = "__setup__";
 pub const INIT_DUNDER: &str = "__init__";
 /// the reducer with this name is invoked when updating the database
 pub const UPDATE_DUNDER: &str = "__update__";
+/// the reducer with this name migrates the rows of tables with incompatible schema changes when updating the database
+pub const MIGRATE_DUNDER: &str = "__migrate__";
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[allow(unused)]
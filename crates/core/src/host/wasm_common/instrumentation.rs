@@ -89,11 +89,14 @@ pub enum Call {
     GetTableId,
     Insert,
     IterByColEq,
+    IterByColRange,
+    IterByColsEq,
     IterDrop,
     IterNext,
     IterStart,
     IterStartFiltered,
     ScheduleReducer,
+    UpdateByColEq,
 }
 
 #[derive(Debug)]
@@ -3,16 +3,21 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use crate::db::datastore::locking_tx_datastore::MutTxId;
-use crate::db::datastore::traits::{ColumnDef, IndexDef, TableDef};
-use crate::host::scheduler::Scheduler;
+use crate::db::datastore::traits::{CheckDef, ColumnDef, ForeignKeyDef, IndexDef, TableDef};
+use crate::db::migration::TableMigration;
+use crate::host::scheduler::{delete_scheduled, validate_schedule, InvalidSchedule, ScheduledReducerId, Scheduler};
 use crate::sql;
-use anyhow::{anyhow, Context};
+use crate::sql::params::SqlParams;
+use crate::sql::policy::compile_policy;
+use crate::sql::session::SqlSession;
+use anyhow::Context;
 use bytes::Bytes;
+use indexmap::IndexMap;
+use nonempty::NonEmpty;
 use spacetimedb_lib::buffer::DecodeError;
 use spacetimedb_lib::identity::AuthCtx;
-use spacetimedb_lib::{bsatn, Address, IndexType, ModuleDef};
-use spacetimedb_primitives::IndexId;
-use spacetimedb_vm::expr::CrudExpr;
+use spacetimedb_lib::{Address, MiscModuleExport, ModuleDef};
+use spacetimedb_primitives::{ColId, IndexId, TableId};
 
 use crate::client::ClientConnectionSender;
 use crate::database_instance_context::DatabaseInstanceContext;
@@ -20,7 +25,7 @@ use crate::database_logger::{DatabaseLogger, LogLevel, Record};
 use crate::hash::Hash;
 use crate::host::instance_env::InstanceEnv;
 use crate::host::module_host::{
-    DatabaseUpdate, EventStatus, Module, ModuleEvent, ModuleFunctionCall, ModuleInfo, ModuleInstance,
+    DatabaseUpdate, EventStatus, MigratedTable, Module, ModuleEvent, ModuleFunctionCall, ModuleInfo, ModuleInstance,
     UpdateDatabaseError, UpdateDatabaseResult, UpdateDatabaseSuccess,
 };
 use crate::host::{
@@ -113,6 +118,12 @@ pub enum InitializationError {
     Instantiation(anyhow::Error),
     #[error("error getting module description: {0}")]
     Describe(#[from] DescribeError),
+    #[error("invalid schedule for reducer {reducer:?}: {err}")]
+    Schedule {
+        reducer: String,
+        #[source]
+        err: InvalidSchedule,
+    },
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -157,19 +168,35 @@ impl<T: WasmModule> WasmModuleHostActor<T> {
         )?;
 
         let desc = instance.extract_descriptions()?;
-        let desc = bsatn::from_slice(&desc).map_err(DescribeError::Decode)?;
+        let desc = ModuleDef::decode(&desc).map_err(DescribeError::Decode)?;
         let ModuleDef {
             typespace,
             tables,
             reducers,
-            misc_exports: _,
+            misc_exports,
         } = desc;
         let catalog = itertools::chain(
             tables.into_iter().map(|x| (x.name.clone(), EntityDef::Table(x))),
             reducers.iter().map(|x| (x.name.clone(), EntityDef::Reducer(x.clone()))),
         )
         .collect();
-        let reducers = reducers.into_iter().map(|x| (x.name.clone(), x)).collect();
+        let reducers: IndexMap<_, _> = reducers.into_iter().map(|x| (x.name.clone(), x)).collect();
+
+        let mut schedules = IndexMap::new();
+        for export in misc_exports {
+            let MiscModuleExport::Schedule(schedule) = export else {
+                continue;
+            };
+            let invalid = |err| InitializationError::Schedule {
+                reducer: schedule.reducer.clone(),
+                err,
+            };
+            validate_schedule(&schedule, &reducers).map_err(invalid)?;
+            if schedules.contains_key(&schedule.reducer) {
+                return Err(invalid(InvalidSchedule::Duplicate));
+            }
+            schedules.insert(schedule.reducer.clone(), schedule);
+        }
 
         let info = Arc::new(ModuleInfo {
             identity: database_instance_context.identity,
@@ -177,6 +204,7 @@ impl<T: WasmModule> WasmModuleHostActor<T> {
             module_hash,
             typespace,
             reducers,
+            schedules,
             catalog,
             log_tx,
             subscription,
@@ -257,6 +285,7 @@ impl<T: WasmModule> Module for WasmModuleHostActor<T> {
         &self,
         caller_identity: Identity,
         query: String,
+        params: SqlParams,
     ) -> Result<Vec<spacetimedb_lib::relation::MemTable>, DBError> {
         let db = &self.worker_database_instance.relational_db;
         let auth = AuthCtx::new(self.worker_database_instance.identity, caller_identity);
@@ -264,21 +293,23 @@ impl<T: WasmModule> Module for WasmModuleHostActor<T> {
 
         db.with_read_only(|tx| {
             log::debug!("One-off query: {query}");
-
-            let compiled = sql::compiler::compile_sql(db, tx, &query)?
-                .into_iter()
-                .map(|expr| {
-                    if matches!(expr, CrudExpr::Query { .. }) {
-                        Ok(expr)
-                    } else {
-                        Err(anyhow!("One-off queries are not allowed to modify the database"))
-                    }
-                })
-                .collect::<Result<_, _>>()?;
-            sql::execute::execute_sql(db, tx, compiled, auth)
+            sql::execute::run_read_only(db, tx, &query, &params, auth)
         })
     }
 
+    fn sql_session_query(
+        &self,
+        caller_identity: Identity,
+        session: &mut SqlSession,
+        query: String,
+        params: SqlParams,
+    ) -> Result<Vec<spacetimedb_lib::relation::MemTable>, DBError> {
+        let db = &self.worker_database_instance.relational_db;
+        let auth = AuthCtx::new(self.worker_database_instance.identity, caller_identity);
+        log::debug!("SQL session query: {query}");
+        session.execute(db, &query, &params, auth)
+    }
+
     fn clear_table(&self, table_name: String) -> Result<(), anyhow::Error> {
         let db = &*self.worker_database_instance.relational_db;
         db.with_auto_commit(|tx| {
@@ -373,6 +404,13 @@ impl<T: WasmInstance> ModuleInstance for WasmModuleInstance<T> {
                     e
                 })?;
         }
+        tx = stdb
+            .with_auto_rollback(tx, |tx| {
+                self.update_foreign_keys(tx)?;
+                self.update_checks(tx)?;
+                self.update_row_policies(tx)
+            })
+            .map(|(tx, ())| tx)?;
 
         // Set the module hash. Morally, this should be done _after_ calling
         // the `init` reducer, but that consumes our transaction context.
@@ -408,45 +446,151 @@ impl<T: WasmInstance> ModuleInstance for WasmModuleInstance<T> {
     }
 
     #[tracing::instrument(skip_all)]
-    fn update_database(&mut self, fence: u128) -> Result<UpdateDatabaseResult, anyhow::Error> {
-        let stdb = &*self.database_instance_context().relational_db;
+    fn update_database(
+        &mut self,
+        fence: u128,
+        drop_orphaned_tables: bool,
+    ) -> Result<UpdateDatabaseResult, anyhow::Error> {
+        let stdb = self.database_instance_context().relational_db.clone();
         let mut tx = stdb.begin_tx();
 
         let (tx0, updates) = stdb.with_auto_rollback::<_, _, anyhow::Error>(tx, |tx| self.schema_updates(tx))?;
         tx = tx0;
-        if updates.tainted_tables.is_empty() {
-            tx = stdb
-                .with_auto_rollback::<_, _, DBError>(tx, |tx| {
-                    for (name, schema) in updates.new_tables {
-                        self.system_logger().info(&format!("Creating table `{}`", name));
-                        stdb.create_table(tx, schema)
-                            .with_context(|| format!("failed to create table {}", name))?;
-                    }
-
-                    for index_id in updates.indexes_to_drop {
-                        self.system_logger()
-                            .info(&format!("Dropping index with id {}", index_id.0));
-                        stdb.drop_index(tx, index_id)?;
-                    }
-
-                    for index_def in updates.indexes_to_create {
-                        self.system_logger()
-                            .info(&format!("Creating index `{}`", index_def.name));
-                        stdb.create_index(tx, index_def)?;
-                    }
 
-                    Ok(())
-                })
-                .map(|(tx, ())| tx)?;
-        } else {
+        // Tables with incompatible schema changes can only be dealt with by
+        // the module itself.
+        let migrate_reducer = self.info.reducers.get_index_of(MIGRATE_DUNDER);
+        if !updates.tainted_tables.is_empty() && migrate_reducer.is_none() {
             stdb.rollback_tx(tx);
             self.system_logger()
                 .error("Module update rejected due to schema mismatch");
             return Ok(Err(UpdateDatabaseError::IncompatibleSchema {
-                tables: updates.tainted_tables,
+                tables: updates.tainted_tables.into_iter().map(|t| t.table_name).collect(),
             }));
         }
 
+        let (migrated_tables, stashed_tables);
+        (tx, (migrated_tables, stashed_tables)) = stdb.with_auto_rollback::<_, _, DBError>(tx, |tx| {
+            // Move tables with incompatible schema changes out of the way,
+            // so that the `migrate` reducer can read their rows while
+            // writing to the tables created from the proposed schema.
+            let mut stashed_tables = Vec::with_capacity(updates.tainted_tables.len());
+            let mut new_tables = updates.new_tables;
+            for tainted in updates.tainted_tables {
+                let stashed_name = stdb
+                    .stash_table(tx, tainted.table_id)
+                    .with_context(|| format!("failed to stash table {}", tainted.table_name))?;
+                self.system_logger().info(&format!(
+                    "Stashing table `{}` as `{}`",
+                    tainted.table_name, stashed_name
+                ));
+                let orphaned = tainted.proposed.is_none();
+                if let Some(schema) = tainted.proposed {
+                    new_tables.insert(tainted.table_name.clone(), schema);
+                }
+                stashed_tables.push(StashedTable {
+                    table_id: tainted.table_id,
+                    table_name: tainted.table_name,
+                    stashed_name,
+                    orphaned,
+                });
+            }
+
+            for (name, schema) in new_tables {
+                self.system_logger().info(&format!("Creating table `{}`", name));
+                stdb.create_table(tx, schema)
+                    .with_context(|| format!("failed to create table {}", name))?;
+            }
+
+            // Drop indexes before migrating tables,
+            // so that the migrated rows are not indexed needlessly.
+            for index_id in updates.indexes_to_drop {
+                self.system_logger()
+                    .info(&format!("Dropping index with id {}", index_id.0));
+                stdb.drop_index(tx, index_id)?;
+            }
+
+            let mut migrated_tables = Vec::with_capacity(updates.tables_to_migrate.len());
+            for (table_id, schema, migration) in updates.tables_to_migrate {
+                self.system_logger()
+                    .info(&format!("Migrating table `{}`", schema.table_name));
+                let rows_migrated = stdb
+                    .migrate_table(tx, table_id, schema.columns.clone(), &migration)
+                    .with_context(|| format!("failed to migrate table {}", schema.table_name))?;
+                migrated_tables.push(MigratedTable {
+                    added_columns: migration.appended_columns(&schema).map(Into::into).collect(),
+                    altered_columns: migration.altered_columns(&schema).map(Into::into).collect(),
+                    table_name: schema.table_name,
+                    rows_migrated,
+                });
+            }
+
+            for index_def in updates.indexes_to_create {
+                self.system_logger()
+                    .info(&format!("Creating index `{}`", index_def.name));
+                stdb.create_index(tx, index_def)?;
+            }
+
+            self.update_foreign_keys(tx)?;
+            self.update_checks(tx)?;
+            self.update_row_policies(tx)?;
+
+            Ok((migrated_tables, stashed_tables))
+        })?;
+
+        let mut migrate_results = Vec::new();
+        if let Some(reducer_id) = migrate_reducer.filter(|_| !stashed_tables.is_empty()) {
+            self.system_logger().info("Invoking `migrate` reducer");
+            let rcr;
+            (tx, rcr) = self.call_migrate_reducer(tx, reducer_id);
+            match &rcr.outcome {
+                ReducerOutcome::Committed => migrate_results.push(rcr),
+                _ => {
+                    stdb.rollback_tx(tx);
+                    let err = rcr.outcome.into_result().unwrap_err();
+                    self.system_logger()
+                        .error(&format!("Module update rejected: `migrate` reducer failed: {err}"));
+                    return Ok(Err(UpdateDatabaseError::MigrateFailed(err.to_string())));
+                }
+            }
+        }
+
+        // Orphaned tables are dropped along with their rows, so unless told
+        // otherwise, only drop those the `migrate` reducer has emptied.
+        if !drop_orphaned_tables {
+            let mut non_empty = Vec::new();
+            for stashed in stashed_tables.iter().filter(|stashed| stashed.orphaned) {
+                match stdb.iter(&tx, stashed.table_id).map(|mut rows| rows.next().is_some()) {
+                    Ok(false) => {}
+                    Ok(true) => non_empty.push(stashed.table_name.clone()),
+                    Err(e) => {
+                        stdb.rollback_tx(tx);
+                        return Err(e.into());
+                    }
+                }
+            }
+            if !non_empty.is_empty() {
+                stdb.rollback_tx(tx);
+                self.system_logger().error(&format!(
+                    "Module update rejected: orphaned tables still hold rows: {non_empty:?}"
+                ));
+                return Ok(Err(UpdateDatabaseError::OrphanedTables { tables: non_empty }));
+            }
+        }
+
+        // The stashed tables are dropped in the same transaction, so that
+        // they are either gone along with the old schema or not at all.
+        tx = stdb
+            .with_auto_rollback::<_, _, DBError>(tx, |tx| {
+                for stashed in &stashed_tables {
+                    self.system_logger()
+                        .info(&format!("Dropping table `{}`", stashed.stashed_name));
+                    stdb.drop_table(tx, stashed.table_id)?;
+                }
+                Ok(())
+            })
+            .map(|(tx, ())| tx)?;
+
         // Update the module hash. Morally, this should be done _after_ calling
         // the `update` reducer, but that consumes our transaction context.
         tx = stdb
@@ -483,7 +627,8 @@ impl<T: WasmInstance> ModuleInstance for WasmModuleInstance<T> {
 
         Ok(Ok(UpdateDatabaseSuccess {
             update_result,
-            migrate_results: vec![],
+            migrate_results,
+            migrated_tables,
         }))
     }
 
@@ -498,6 +643,30 @@ impl<T: WasmInstance> ModuleInstance for WasmModuleInstance<T> {
     ) -> ReducerCallResult {
         self.call_reducer_internal(None, caller_identity, caller_address, client, reducer_id, args)
     }
+
+    #[tracing::instrument(skip_all)]
+    fn call_scheduled_reducer(
+        &mut self,
+        id: ScheduledReducerId,
+        caller_identity: Identity,
+        reducer_id: usize,
+        args: ArgsTuple,
+    ) -> ReducerCallResult {
+        let stdb = &*self.database_instance_context().relational_db;
+        let mut tx = stdb.begin_tx();
+        // Removing the call along with the writes of the reducer makes sure
+        // it's not made again after a restart once the reducer committed.
+        if let Err(e) = delete_scheduled(stdb, &mut tx, id) {
+            stdb.rollback_tx(tx);
+            return ReducerCallResult {
+                outcome: ReducerOutcome::Failed(format!("removing scheduled reducer failed: {e}")),
+                energy_used: EnergyDiff::ZERO,
+                execution_duration: Duration::ZERO,
+            };
+        }
+        // Scheduled reducers take `None` as the caller address.
+        self.call_reducer_internal(Some(tx), caller_identity, None, None, reducer_id, args)
+    }
 }
 
 impl<T: WasmInstance> WasmModuleInstance<T> {
@@ -570,6 +739,40 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
         }
     }
 
+    /// Call the module's `migrate` reducer within the update transaction `tx`.
+    ///
+    /// Unlike [`Self::call_reducer_internal`], the transaction is handed back
+    /// rather than committed or rolled back, and no [`ModuleEvent`] is
+    /// broadcast, as the reducer's writes are part of the database update.
+    fn call_migrate_reducer(&mut self, tx: MutTxId, reducer_id: usize) -> (MutTxId, ReducerCallResult) {
+        let start_instant = Instant::now();
+
+        let caller_identity = self.database_instance_context().identity;
+        let caller_address = self.database_instance_context().publisher_address;
+        let mut args = ArgsTuple::default();
+        let (tx, result, energy) = self.execute_in_tx(
+            tx,
+            ReducerOp {
+                id: reducer_id,
+                sender_identity: &caller_identity,
+                sender_address: &caller_address.unwrap_or(Address::__dummy()),
+                timestamp: Timestamp::now(),
+                arg_bytes: args.get_bsatn().clone(),
+            },
+        );
+
+        let outcome = match &result {
+            Ok(()) => ReducerOutcome::Committed,
+            Err(status) => ReducerOutcome::from(status),
+        };
+        let rcr = ReducerCallResult {
+            outcome,
+            energy_used: energy.used,
+            execution_duration: start_instant.elapsed(),
+        };
+        (tx, rcr)
+    }
+
     /// Execute a reducer.
     ///
     /// If `Some` [`MutTxId`] is supplied, the reducer is called within the
@@ -586,6 +789,52 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
     /// The method also performs various measurements and records energy usage.
     #[tracing::instrument(skip_all)]
     fn execute(&mut self, tx: Option<MutTxId>, op: ReducerOp<'_>) -> (EventStatus, EnergyStats) {
+        let address = self.database_instance_context().address;
+        let reducer_id = op.id;
+
+        let tx = tx.unwrap_or_else(|| self.database_instance_context().relational_db.begin_tx());
+        let (tx, result, energy) = self.execute_in_tx(tx, op);
+
+        let stdb = &*self.database_instance_context().relational_db;
+        let status = match result {
+            Err(status) => {
+                stdb.rollback_tx(tx);
+                status
+            }
+            Ok(()) => match stdb.commit_tx(tx) {
+                // The writes of the reducer violate a constraint checked at commit.
+                Err(e) => {
+                    log::info!("reducer commit failed: {e}");
+                    EventStatus::Failed(e.to_string())
+                }
+                Ok(Some((tx_data, bytes_written))) => {
+                    // TODO(cloutiertyler): This tracking doesn't really belong here if we want to write transactions to disk
+                    // in batches. This is because it's possible for a tiny reducer call to trigger a whole commit to be written to disk.
+                    // We should track the commit sizes instead internally to the CommitLog probably.
+                    if let Some(bytes_written) = bytes_written {
+                        let func_ident = &*self.info.reducers[reducer_id].name;
+                        WORKER_METRICS
+                            .reducer_write_size
+                            .with_label_values(&address, func_ident)
+                            .observe(bytes_written as f64);
+                    }
+                    EventStatus::Committed(DatabaseUpdate::from_writes(stdb, &tx_data))
+                }
+                Ok(None) => todo!("Write skew, you need to implement retries my man, T-dawg."),
+            },
+        };
+        (status, energy)
+    }
+
+    /// Execute a reducer within the transaction `tx`, without committing or
+    /// rolling it back.
+    ///
+    /// The transaction is handed back along with the outcome of the call,
+    /// which is the [`EventStatus`] to report if the reducer failed.
+    /// It is up to the caller to roll back the transaction in that case.
+    ///
+    /// The method also performs various measurements and records energy usage.
+    fn execute_in_tx(&mut self, tx: MutTxId, op: ReducerOp<'_>) -> (MutTxId, Result<(), EventStatus>, EnergyStats) {
         let address = self.database_instance_context().address;
         let func_ident = &*self.info.reducers[op.id].name;
         WORKER_METRICS
@@ -602,8 +851,6 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
 
         let budget = self.energy_monitor.reducer_budget(&energy_fingerprint);
 
-        let tx = tx.unwrap_or_else(|| self.database_instance_context().relational_db.begin_tx());
-
         let tx_slot = self.instance.instance_env().tx.clone();
         let (tx, result) = tx_slot.set(tx, || {
             self.instance.call_reducer(
@@ -653,47 +900,29 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
             .with_label_values(&address, func_ident)
             .observe(timings.total_duration.as_secs_f64());
 
-        let stdb = &*self.database_instance_context().relational_db;
-        let status = match call_result {
+        let result = match call_result {
             Err(err) => {
-                stdb.rollback_tx(tx);
-
                 T::log_traceback("reducer", func_ident, &err);
 
                 // discard this instance
                 self.trapped = true;
 
                 if energy.remaining == EnergyQuanta::ZERO {
-                    EventStatus::OutOfEnergy
+                    Err(EventStatus::OutOfEnergy)
                 } else {
-                    EventStatus::Failed("The Wasm instance encountered a fatal error.".into())
+                    Err(EventStatus::Failed(
+                        "The Wasm instance encountered a fatal error.".into(),
+                    ))
                 }
             }
             Ok(Err(errmsg)) => {
-                stdb.rollback_tx(tx);
-
                 log::info!("reducer returned error: {errmsg}");
 
-                EventStatus::Failed(errmsg.into())
-            }
-            Ok(Ok(())) => {
-                if let Some((tx_data, bytes_written)) = stdb.commit_tx(tx).unwrap() {
-                    // TODO(cloutiertyler): This tracking doesn't really belong here if we want to write transactions to disk
-                    // in batches. This is because it's possible for a tiny reducer call to trigger a whole commit to be written to disk.
-                    // We should track the commit sizes instead internally to the CommitLog probably.
-                    if let Some(bytes_written) = bytes_written {
-                        WORKER_METRICS
-                            .reducer_write_size
-                            .with_label_values(&address, func_ident)
-                            .observe(bytes_written as f64);
-                    }
-                    EventStatus::Committed(DatabaseUpdate::from_writes(stdb, &tx_data))
-                } else {
-                    todo!("Write skew, you need to implement retries my man, T-dawg.");
-                }
+                Err(EventStatus::Failed(errmsg.into()))
             }
+            Ok(Ok(())) => Ok(()),
         };
-        (status, energy)
+        (tx, result, energy)
     }
 
     // Helpers - NOT API
@@ -725,7 +954,7 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
             let mut index_for_column = None;
             for index in table.indexes.iter() {
                 let [index_col_id] = *index.col_ids else {
-                    //Ignore multi-column indexes
+                    // Multi-column indexes are handled below.
                     continue;
                 };
                 if index_col_id as usize != col_id {
@@ -739,17 +968,13 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
             // If there's an index defined for this column already, use it
             // making sure that it is unique if the column has a unique constraint
             if let Some(index) = index_for_column {
-                match index.ty {
-                    IndexType::BTree => {}
-                    // TODO
-                    IndexType::Hash => anyhow::bail!("hash indexes not yet supported"),
-                }
                 let index = IndexDef::new(
                     index.name.clone(),
                     0.into(), // Will be ignored
                     col_id.into(),
                     col_attr.is_unique(),
-                );
+                )
+                .with_index_type(index.ty);
                 indexes.push(index);
             } else if col_attr.is_unique() {
                 // If you didn't find an index, but the column is unique then create a unique btree index
@@ -761,9 +986,32 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
                     true,
                 );
                 indexes.push(index);
+            } else if table.foreign_keys.iter().any(|fk| fk.col_id as usize == col_id) {
+                // The rows referencing a deleted row are looked up with an index.
+                let index = IndexDef::new(
+                    format!("{}_{}_fkey", table.name, col.col_name),
+                    0.into(), // Will be ignored
+                    col_id.into(),
+                    false,
+                );
+                indexes.push(index);
             }
         }
 
+        for index in table.indexes.iter().filter(|index| index.col_ids.len() > 1) {
+            let cols = NonEmpty::collect(index.col_ids.iter().map(|&col_id| ColId::from(col_id))).unwrap();
+            if let Some(col_id) = cols.iter().find(|col_id| col_id.idx() >= columns.len()) {
+                anyhow::bail!("index `{}` refers to invalid column id {}", index.name, col_id);
+            }
+            indexes.push(IndexDef {
+                table_id: 0.into(), // Will be ignored
+                cols,
+                name: index.name.clone(),
+                is_unique: false,
+                index_type: index.ty,
+            });
+        }
+
         Ok(TableDef {
             table_name: table.name.clone(),
             columns,
@@ -773,6 +1021,136 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
         })
     }
 
+    /// Creates the foreign keys declared by the module that don't exist yet,
+    /// and drops those it no longer declares.
+    ///
+    /// Must be called once the tables of the module have been created.
+    fn update_foreign_keys(&self, tx: &mut MutTxId) -> anyhow::Result<()> {
+        let stdb = &*self.database_instance_context().relational_db;
+
+        let mut proposed = Vec::new();
+        for table in self.info.catalog.values().filter_map(EntityDef::as_table) {
+            for fk in &table.foreign_keys {
+                let table_id = stdb
+                    .table_id_from_name(tx, &table.name)?
+                    .with_context(|| format!("table `{}` not found", table.name))?;
+                let ref_table_id = stdb
+                    .table_id_from_name(tx, &fk.table)?
+                    .with_context(|| format!("foreign key `{}` references unknown table `{}`", fk.name, fk.table))?;
+                let ref_col_id = stdb
+                    .schema_for_table(tx, ref_table_id)?
+                    .get_column_by_name(&fk.column)
+                    .map(|col| col.col_id)
+                    .with_context(|| {
+                        format!(
+                            "foreign key `{}` references unknown column `{}.{}`",
+                            fk.name, fk.table, fk.column
+                        )
+                    })?;
+                proposed.push(ForeignKeyDef {
+                    constraint_name: fk.name.clone(),
+                    table_id,
+                    columns: NonEmpty::new(ColId::from(fk.col_id)),
+                    ref_table_id,
+                    ref_columns: NonEmpty::new(ref_col_id),
+                    on_delete: fk.on_delete,
+                });
+            }
+        }
+
+        for known in stdb.foreign_keys(tx)? {
+            let constraint_id = known.constraint_id;
+            let known = ForeignKeyDef::from(known);
+            if let Some(pos) = proposed.iter().position(|fk| *fk == known) {
+                proposed.swap_remove(pos);
+            } else {
+                self.system_logger()
+                    .info(&format!("Dropping foreign key `{}`", known.constraint_name));
+                stdb.drop_foreign_key(tx, constraint_id)?;
+            }
+        }
+        for fk in proposed {
+            self.system_logger()
+                .info(&format!("Creating foreign key `{}`", fk.constraint_name));
+            let name = fk.constraint_name.clone();
+            stdb.create_foreign_key(tx, fk)
+                .with_context(|| format!("failed to create foreign key {name}"))?;
+        }
+        Ok(())
+    }
+
+    /// Creates the check constraints declared by the module that don't exist yet,
+    /// and drops those it no longer declares.
+    ///
+    /// Must be called once the tables of the module have been created.
+    fn update_checks(&self, tx: &mut MutTxId) -> anyhow::Result<()> {
+        let stdb = &*self.database_instance_context().relational_db;
+
+        let mut proposed = Vec::new();
+        for table in self.info.catalog.values().filter_map(EntityDef::as_table) {
+            for check in &table.checks {
+                let table_id = stdb
+                    .table_id_from_name(tx, &table.name)?
+                    .with_context(|| format!("table `{}` not found", table.name))?;
+                proposed.push(CheckDef {
+                    constraint_name: check.name.clone(),
+                    table_id,
+                    expr: check.expr.clone(),
+                });
+            }
+        }
+
+        for known in stdb.checks(tx)? {
+            let constraint_id = known.constraint_id;
+            let known = CheckDef::from(known);
+            if let Some(pos) = proposed.iter().position(|check| *check == known) {
+                proposed.swap_remove(pos);
+            } else {
+                self.system_logger()
+                    .info(&format!("Dropping check constraint `{}`", known.constraint_name));
+                stdb.drop_check(tx, constraint_id)?;
+            }
+        }
+        for check in proposed {
+            self.system_logger()
+                .info(&format!("Creating check constraint `{}`", check.constraint_name));
+            let name = check.constraint_name.clone();
+            stdb.create_check(tx, check)
+                .with_context(|| format!("failed to create check constraint {name}"))?;
+        }
+        Ok(())
+    }
+
+    /// Stores the row-level security policies declared by the module,
+    /// once checked against the schema of their table.
+    ///
+    /// Must be called once the tables of the module have been created.
+    fn update_row_policies(&self, tx: &mut MutTxId) -> anyhow::Result<()> {
+        let stdb = &*self.database_instance_context().relational_db;
+        let owner = self.database_instance_context().identity;
+
+        for table in self.info.catalog.values().filter_map(EntityDef::as_table) {
+            let table_id = stdb
+                .table_id_from_name(tx, &table.name)?
+                .with_context(|| format!("table `{}` not found", table.name))?;
+            if let Some(policy) = &table.row_policy {
+                let schema = stdb.schema_for_table(tx, table_id)?;
+                compile_policy(&schema, policy, owner)?;
+            }
+            if stdb.row_policy(tx, table_id)? != table.row_policy {
+                let action = if table.row_policy.is_some() {
+                    "Setting"
+                } else {
+                    "Removing"
+                };
+                self.system_logger()
+                    .info(&format!("{action} row-level security policy of `{}`", table.name));
+                stdb.set_row_policy(tx, table_id, table.row_policy.clone())?;
+            }
+        }
+        Ok(())
+    }
+
     fn system_logger(&self) -> SystemLogger {
         let inner = self.database_instance_context().logger.lock().unwrap();
         SystemLogger { inner }
@@ -782,26 +1160,8 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
     fn schema_updates(&self, tx: &MutTxId) -> anyhow::Result<SchemaUpdates> {
         let stdb = &*self.database_instance_context().relational_db;
 
-        // Until we know how to migrate schemas, we only accept `TableDef`s for
-        // existing tables which are equal sans their indexes.
-        struct Equiv<'a>(&'a TableDef);
-        impl PartialEq for Equiv<'_> {
-            fn eq(&self, other: &Self) -> bool {
-                let TableDef {
-                    table_name,
-                    columns,
-                    indexes: _,
-                    table_type,
-                    table_access,
-                } = &self.0;
-                table_name == &other.0.table_name
-                    && table_type == &other.0.table_type
-                    && table_access == &other.0.table_access
-                    && columns == &other.0.columns
-            }
-        }
-
         let mut new_tables = HashMap::new();
+        let mut tables_to_migrate = Vec::new();
         let mut tainted_tables = Vec::new();
         let mut indexes_to_create = Vec::new();
         let mut indexes_to_drop = Vec::new();
@@ -817,41 +1177,51 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
             if let Some(known_schema) = known_tables.remove(&table.name) {
                 let table_id = known_schema.table_id;
                 let known_schema_def = TableDef::from(&*known_schema);
-                // If the schemas differ acc. to [Equiv], the update should be
-                // rejected.
-                if Equiv(&known_schema_def) != Equiv(&proposed_schema_def) {
-                    self.system_logger()
-                        .warn(&format!("stored and proposed schema of `{}` differ", table.name));
-                    tainted_tables.push(table.name.to_owned());
-                } else {
-                    // The schema is unchanged, but maybe the indexes are.
-                    let mut known_indexes = known_schema
-                        .indexes
-                        .iter()
-                        .map(|idx| (&idx.index_name, idx))
-                        .collect::<BTreeMap<_, _>>();
-
-                    for mut index_def in proposed_schema_def.indexes {
-                        // This is zero in the proposed schema, as the table id
-                        // is not known at proposal time.
-                        index_def.table_id = table_id;
-
-                        match known_indexes.remove(&index_def.name) {
-                            None => indexes_to_create.push(index_def),
-                            Some(known_index) => {
-                                let known_id = known_index.index_id;
-                                let known_index_def = IndexDef::from(known_index.clone());
-                                if known_index_def != index_def {
-                                    indexes_to_drop.push(known_id);
-                                    indexes_to_create.push(index_def);
+                // If the columns can't be migrated automatically, the update
+                // should be rejected.
+                match TableMigration::plan(&known_schema_def, &proposed_schema_def) {
+                    Err(e) => {
+                        self.system_logger()
+                            .warn(&format!("stored and proposed schema of `{}` differ: {e}", table.name));
+                        tainted_tables.push(TaintedTable {
+                            table_id,
+                            table_name: table.name.to_owned(),
+                            proposed: Some(proposed_schema_def),
+                        });
+                    }
+                    Ok(migration) => {
+                        if let Some(migration) = migration {
+                            tables_to_migrate.push((table_id, proposed_schema_def.clone(), migration));
+                        }
+                        // Maybe the indexes changed, too.
+                        let mut known_indexes = known_schema
+                            .indexes
+                            .iter()
+                            .map(|idx| (&idx.index_name, idx))
+                            .collect::<BTreeMap<_, _>>();
+
+                        for mut index_def in proposed_schema_def.indexes {
+                            // This is zero in the proposed schema, as the table id
+                            // is not known at proposal time.
+                            index_def.table_id = table_id;
+
+                            match known_indexes.remove(&index_def.name) {
+                                None => indexes_to_create.push(index_def),
+                                Some(known_index) => {
+                                    let known_id = known_index.index_id;
+                                    let known_index_def = IndexDef::from(known_index.clone());
+                                    if known_index_def != index_def {
+                                        indexes_to_drop.push(known_id);
+                                        indexes_to_create.push(index_def);
+                                    }
                                 }
                             }
                         }
-                    }
 
-                    // Indexes not in the proposed schema shall be dropped.
-                    for index in known_indexes.into_values() {
-                        indexes_to_drop.push(index.index_id);
+                        // Indexes not in the proposed schema shall be dropped.
+                        for index in known_indexes.into_values() {
+                            indexes_to_drop.push(index.index_id);
+                        }
                     }
                 }
             } else {
@@ -860,16 +1230,21 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
         }
         // We may at some point decide to drop orphaned tables automatically,
         // but for now it's an incompatible schema change
-        for orphan in known_tables.into_keys() {
+        for (orphan, schema) in known_tables {
             if !orphan.starts_with("st_") {
                 self.system_logger()
                     .warn(format!("Orphaned table: {}", orphan).as_str());
-                tainted_tables.push(orphan);
+                tainted_tables.push(TaintedTable {
+                    table_id: schema.table_id,
+                    table_name: orphan,
+                    proposed: None,
+                });
             }
         }
 
         Ok(SchemaUpdates {
             new_tables,
+            tables_to_migrate,
             tainted_tables,
             indexes_to_drop,
             indexes_to_create,
@@ -877,11 +1252,23 @@ impl<T: WasmInstance> WasmModuleInstance<T> {
     }
 }
 
+/// A table moved out of the way of the proposed schema by
+/// [`crate::db::relational_db::RelationalDB::stash_table`].
+struct StashedTable {
+    table_id: TableId,
+    table_name: String,
+    stashed_name: String,
+    /// Whether the table is no longer part of the module.
+    orphaned: bool,
+}
+
 struct SchemaUpdates {
     /// Tables to create.
     new_tables: HashMap<String, TableDef>,
-    /// Names of tables with incompatible schema updates.
-    tainted_tables: Vec<String>,
+    /// Tables whose stored rows must be migrated to the proposed schema.
+    tables_to_migrate: Vec<(TableId, TableDef, TableMigration)>,
+    /// Tables with incompatible schema updates.
+    tainted_tables: Vec<TaintedTable>,
     /// Indexes to drop.
     ///
     /// Should be processed _before_ `indexes_to_create`, as we might be
@@ -893,6 +1280,15 @@ struct SchemaUpdates {
     indexes_to_create: Vec<IndexDef>,
 }
 
+/// A stored table whose schema is incompatible with the proposed schema.
+struct TaintedTable {
+    table_id: TableId,
+    table_name: String,
+    /// The proposed schema of the table,
+    /// `None` if the table is no longer defined by the module.
+    proposed: Option<TableDef>,
+}
+
 #[derive(Debug)]
 struct ReducerOp<'a> {
     id: usize,
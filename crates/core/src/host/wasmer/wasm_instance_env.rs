@@ -3,7 +3,7 @@
 use std::time::Instant;
 
 use crate::database_logger::{BacktraceFrame, BacktraceProvider, ModuleBacktrace, Record};
-use crate::host::scheduler::{ScheduleError, ScheduledReducerId};
+use crate::host::scheduler::ScheduledReducerId;
 use crate::host::timestamp::Timestamp;
 use crate::host::wasm_common::instrumentation;
 use crate::host::wasm_common::module_host_actor::ExecutionTimings;
@@ -248,6 +248,9 @@ impl WasmInstanceEnv {
     /// A generated schedule id is assigned to the reducer.
     /// This id is written to the pointer `out`.
     ///
+    /// The call is stored in `st_scheduled` by the transaction of the calling reducer,
+    /// so it's never made if that reducer fails.
+    ///
     /// Returns an error if
     /// - the `time` delay exceeds `64^6 - 1` milliseconds from now
     /// - `name` does not point to valid UTF-8
@@ -275,16 +278,7 @@ impl WasmInstanceEnv {
             // Noa: This would be nice but I think the eventual goal/desire is to switch to wasmtime,
             //      which doesn't allow user types to impl ValueType.
             //      Probably the correct API choice, but makes things a bit less ergonomic sometimes.
-            let ScheduledReducerId(id) = caller
-                .data()
-                .instance_env
-                .schedule(name, args, Timestamp(time))
-                .map_err(|e| match e {
-                    ScheduleError::DelayTooLong(_) => RuntimeError::new("requested delay is too long"),
-                    ScheduleError::IdTransactionError(_) => {
-                        RuntimeError::new("transaction to acquire ScheduleReducerId failed")
-                    }
-                })?;
+            let ScheduledReducerId(id) = caller.data().instance_env.schedule(name, args, Timestamp(time))?;
             Ok(id)
         })
         .map(|_| ())
@@ -293,10 +287,20 @@ impl WasmInstanceEnv {
     /// Unschedule a reducer using the same `id` generated as when it was scheduled.
     ///
     /// This assumes that the reducer hasn't already been executed.
+    ///
+    /// The cancellation is part of the transaction of the calling reducer,
+    /// so it's undone if that reducer fails.
     #[tracing::instrument(skip_all)]
-    pub fn cancel_reducer(caller: FunctionEnvMut<'_, Self>, id: u64) {
+    pub fn cancel_reducer(caller: FunctionEnvMut<'_, Self>, id: u64) -> RtResult<()> {
+        let mut result = Ok(());
         Self::cvt_noret(caller, Call::CancelReducer, |caller, _mem| {
-            caller.data().instance_env.cancel_reducer(ScheduledReducerId(id))
+            result = caller.data().instance_env.cancel_reducer(ScheduledReducerId(id))
+        });
+        result.map_err(|err| {
+            RuntimeError::user(Box::new(AbiRuntimeError {
+                func: "cancel_reducer",
+                err,
+            }))
         })
     }
 
@@ -435,6 +439,50 @@ impl WasmInstanceEnv {
         })
     }
 
+    /// Replaces the row in the table identified by `table_id`
+    /// where the column identified by `col_id` matches the byte string,
+    /// in WASM memory, pointed to at by `value`,
+    /// with the BSATN-encoded row, in WASM memory, pointed to at by `row`.
+    ///
+    /// Matching is defined by BSATN-decoding `value` to an `AlgebraicValue`
+    /// according to the column's schema and then `Ord for AlgebraicValue`.
+    ///
+    /// The row is updated in place: auto-incrementing columns of `row` are kept as they are,
+    /// and subscribers are informed of a single update rather than a delete and an insert.
+    ///
+    /// Returns an error if
+    /// - a table with the provided `table_id` doesn't exist
+    /// - no row matches `value`
+    /// - `col_id` does not identify a column of the table,
+    /// - `(value, value_len)` doesn't decode from BSATN to an `AlgebraicValue`
+    ///   according to the `AlgebraicType` that the table's schema specifies for `col_id`.
+    /// - `(row, row_len)` doesn't decode from BSATN to a `ProductValue`
+    ///   according to the `ProductType` that the table's schema specifies.
+    /// - there were unique constraint violations
+    /// - `value + value_len` or `row + row_len` overflow a 64-bit integer
+    ///
+    /// Traps if more than one row matches `value`.
+    #[tracing::instrument(skip_all)]
+    pub fn update_by_col_eq(
+        caller: FunctionEnvMut<'_, Self>,
+        table_id: u32,
+        col_id: u32,
+        value: WasmPtr<u8>,
+        value_len: u32,
+        row: WasmPtr<u8>,
+        row_len: u32,
+    ) -> RtResult<u16> {
+        Self::cvt(caller, "update_by_col_eq", Call::UpdateByColEq, |caller, mem| {
+            let value = mem.read_bytes(&caller, value, value_len)?;
+            let row = mem.read_bytes(&caller, row, row_len)?;
+            caller
+                .data()
+                .instance_env
+                .update_by_col_eq(table_id.into(), col_id.into(), &value, &row)?;
+            Ok(())
+        })
+    }
+
     /// Queries the `table_id` associated with the given (table) `name`
     /// where `name` points to a UTF-8 slice in WASM memory of `name_len` bytes.
     ///
@@ -468,16 +516,18 @@ impl WasmInstanceEnv {
     /// Here `index_name` points to a UTF-8 slice in WASM memory
     /// and `col_ids` points to a byte slice in WASM memory with each element being a column.
     ///
-    /// Currently only single-column-indices are supported
-    /// and they may only be of the btree index type.
+    /// The `index_type` is `0` for a btree index and `1` for a hash index.
+    /// A hash index only supports seeking a single value, not a range of values.
     ///
-    /// Returns an error if
-    /// - a table with the provided `table_id` doesn't exist
+    /// Returns an error if a table with the provided `table_id` doesn't exist.
+    ///
+    /// Traps if
+    /// - `index_type > 1`
     /// - the slice `(index_name, index_name_len)` is not valid UTF-8
     /// - `index_name + index_name_len` or `col_ids + col_len` overflow a 64-bit integer
-    /// - `index_type > 1`
+    /// - a column of `col_ids` doesn't exist in the table
     ///
-    /// Panics if `index_type == 1` or `col_ids.len() != 1`.
+    /// Panics if `col_ids.len() == 0`.
     #[tracing::instrument(skip_all)]
     pub fn create_index(
         caller: FunctionEnvMut<'_, Self>,
@@ -545,6 +595,101 @@ impl WasmInstanceEnv {
         })
     }
 
+    /// Finds all rows in the table identified by `table_id`,
+    /// where the row has a column, identified by `col_id`,
+    /// with a value within the range, in WASM memory, pointed to at by `range`.
+    ///
+    /// The range is encoded as its start bound followed by its end bound.
+    /// Each bound is a tag byte, `0` for inclusive, `1` for exclusive and `2` for unbounded,
+    /// followed by the BSATN-encoded endpoint unless the bound is unbounded.
+    /// The endpoints are decoded to an `AlgebraicValue` according to the column's schema
+    /// and compared with `Ord for AlgebraicValue`.
+    ///
+    /// The rows found are BSATN-encoded and then concatenated.
+    /// The resulting byte string from the concatenation is written
+    /// to a fresh buffer with the buffer's identifier written to the WASM pointer `out`.
+    ///
+    /// Returns an error if
+    /// - a table with the provided `table_id` doesn't exist
+    /// - `col_id` does not identify a column of the table,
+    /// - `(range, range_len)` cannot be decoded to a range of `AlgebraicValue`s
+    ///   typed at the `AlgebraicType` of the column,
+    /// - `range + range_len` overflows a 64-bit integer
+    #[tracing::instrument(skip_all)]
+    pub fn iter_by_col_range(
+        caller: FunctionEnvMut<'_, Self>,
+        table_id: u32,
+        col_id: u32,
+        range: WasmPtr<u8>,
+        range_len: u32,
+        out: WasmPtr<BufferIdx>,
+    ) -> RtResult<u16> {
+        Self::cvt_ret(
+            caller,
+            "iter_by_col_range",
+            Call::IterByColRange,
+            out,
+            |mut caller, mem| {
+                // Read the encoded range from WASM memory.
+                let range = mem.read_bytes(&caller, range, range_len)?;
+
+                // Find the relevant rows.
+                let data = caller
+                    .data()
+                    .instance_env
+                    .iter_by_col_range(table_id.into(), col_id.into(), &range)?;
+
+                // Insert the encoded + concatenated rows into a new buffer and return its id.
+                Ok(caller.data_mut().buffers.insert(data.into()))
+            },
+        )
+    }
+
+    /// Finds all rows in the table identified by `table_id`,
+    /// where the row has columns, identified by the byte slice `(col_ids, col_len)`,
+    /// with data matching the byte string, in WASM memory, pointed to at by `val`.
+    ///
+    /// The byte string is the concatenation of the BSATN-encoded value of each column.
+    /// Matching is defined by decoding these to `AlgebraicValue`s
+    /// according to the columns' schemas and then `Ord for AlgebraicValue`.
+    /// An index whose leading columns are `col_ids` is used for the lookup, if there is one.
+    ///
+    /// The rows found are BSATN-encoded and then concatenated.
+    /// The resulting byte string from the concatenation is written
+    /// to a fresh buffer with the buffer's identifier written to the WASM pointer `out`.
+    ///
+    /// Returns an error if
+    /// - a table with the provided `table_id` doesn't exist
+    /// - `(col_ids, col_len)` is empty or an element does not identify a column of the table,
+    /// - `(val, val_len)` cannot be decoded to a sequence of `AlgebraicValue`s
+    ///   typed at the `AlgebraicType`s of the columns,
+    /// - `col_ids + col_len` or `val + val_len` overflow a 64-bit integer
+    #[tracing::instrument(skip_all)]
+    pub fn iter_by_cols_eq(
+        caller: FunctionEnvMut<'_, Self>,
+        table_id: u32,
+        col_ids: WasmPtr<u8>,
+        col_len: u32,
+        val: WasmPtr<u8>,
+        val_len: u32,
+        out: WasmPtr<BufferIdx>,
+    ) -> RtResult<u16> {
+        Self::cvt_ret(caller, "iter_by_cols_eq", Call::IterByColsEq, out, |mut caller, mem| {
+            // Read the column ids and the test value from WASM memory.
+            let cols = mem.read_bytes(&caller, col_ids, col_len)?;
+            let value = mem.read_bytes(&caller, val, val_len)?;
+
+            // Find the relevant rows.
+            let data = caller
+                .data()
+                .instance_env
+                .iter_by_cols_eq(table_id.into(), &cols, &value)?;
+
+            // Insert the encoded + concatenated rows into a new buffer and return its id.
+            Ok(caller.data_mut().buffers.insert(data.into()))
+        })
+    }
+
     /// Start iteration on each row, as bytes, of a table identified by `table_id`.
     ///
     /// The iterator is registered in the host environment
@@ -46,7 +46,7 @@ impl WasmerModule {
         WasmerModule { module, engine }
     }
 
-    pub const IMPLEMENTED_ABI: abi::VersionTuple = abi::VersionTuple::new(7, 0);
+    pub const IMPLEMENTED_ABI: abi::VersionTuple = abi::VersionTuple::new(7, 1);
 
     fn imports(&self, store: &mut Store, env: &FunctionEnv<WasmInstanceEnv>) -> Imports {
         #[allow(clippy::assertions_on_constants)]
@@ -110,6 +110,11 @@ impl WasmerModule {
                 "_buffer_alloc" => Function::new_typed_with_env(store, env, WasmInstanceEnv::buffer_alloc),
                 "_span_start" => Function::new_typed_with_env(store, env, WasmInstanceEnv::span_start),
                 "_span_end" => Function::new_typed_with_env(store, env, WasmInstanceEnv::span_end),
+            },
+            "spacetime_7.1" => {
+                "_iter_by_col_range" => Function::new_typed_with_env(store, env, WasmInstanceEnv::iter_by_col_range),
+                "_iter_by_cols_eq" => Function::new_typed_with_env(store, env, WasmInstanceEnv::iter_by_cols_eq),
+                "_update_by_col_eq" => Function::new_typed_with_env(store, env, WasmInstanceEnv::update_by_col_eq),
             }
         }
     }
@@ -72,6 +72,13 @@ pub struct TableRowOperationJson {
     pub row_pk: String,
     #[serde_as(as = "Vec<Sats>")]
     pub row: Vec<AlgebraicValue>,
+    /// For an `"update"`, the primary key of the row replaced by `row`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_row_pk: Option<String>,
+    /// For an `"update"`, the row replaced by `row`.
+    #[serde_as(as = "Option<Vec<Sats>>")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old_row: Option<Vec<AlgebraicValue>>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -1,15 +1,16 @@
 use spacetimedb_lib::auth::{StAccess, StTableType};
-use spacetimedb_lib::error::RelationError;
 use spacetimedb_lib::table::{ColumnDef, ProductTypeMeta};
 use spacetimedb_lib::ColumnIndexAttribute;
 use spacetimedb_sats::{AlgebraicType, AlgebraicValue, ProductTypeElement};
 use sqlparser::ast::{
     Assignment, BinaryOperator, ColumnDef as SqlColumnDef, ColumnOption, DataType, ExactNumberInfo, Expr as SqlExpr,
-    GeneratedAs, HiveDistributionStyle, Ident, JoinConstraint, JoinOperator, ObjectName, ObjectType, Query, Select,
-    SelectItem, SetExpr, Statement, TableFactor, TableWithJoins, Value, Values,
+    Function, FunctionArg, FunctionArgExpr, GeneratedAs, HiveDistributionStyle, Ident, JoinConstraint, JoinOperator,
+    ObjectName, ObjectType, Offset, OrderByExpr, Query, Select, SelectItem, SetExpr, Statement, TableFactor,
+    TableWithJoins, Value, Values,
 };
 use sqlparser::dialect::PostgreSqlDialect;
 use sqlparser::parser::Parser;
+use sqlparser::tokenizer::Token;
 use std::borrow::Cow;
 use std::collections::HashMap;
 
@@ -17,14 +18,15 @@ use crate::db::datastore::locking_tx_datastore::MutTxId;
 use crate::db::datastore::traits::{MutTxDatastore, TableSchema};
 use crate::db::relational_db::RelationalDB;
 use crate::error::{DBError, PlanError};
+use crate::sql::params::SqlParams;
 use spacetimedb_lib::relation::{extract_table_field, FieldExpr, FieldName};
 use spacetimedb_vm::errors::ErrorVm;
-use spacetimedb_vm::expr::{ColumnOp, DbType, Expr};
+use spacetimedb_vm::expr::{AggregateExpr, AggregateFn, ColumnOp, DbType, Expr, Limit, SortKey};
 use spacetimedb_vm::operator::{OpCmp, OpLogic, OpQuery};
 use spacetimedb_vm::ops::parse::parse;
 
 /// Simplify to detect features of the syntax we don't support yet
-/// Because we use [PostgreSqlDialect] in the compiler step it already protect against features
+/// Because we use [sqlparser::dialect::PostgreSqlDialect] in the compiler step it already protect against features
 /// that are not in the standard SQL-92 but still need to check for completeness
 trait Unsupported {
     fn unsupported(&self) -> bool;
@@ -89,16 +91,27 @@ impl Table {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Column {
     /// Any expression, not followed by `[ AS ] alias`
     UnnamedExpr(Expr),
+    /// An aggregate function like `COUNT(*)`, optionally followed by `[ AS ] alias`
+    Aggregate(AggregateExpr),
     /// An qualified `table.*`
     QualifiedWildcard { table: String },
     /// An unqualified `SELECT *`
     Wildcard,
 }
 
+/// The `GROUP BY field1, field2... HAVING expr` clause,
+/// along with every aggregate function computed for each group.
+#[derive(Debug, Clone)]
+pub struct GroupBy {
+    pub fields: Vec<FieldName>,
+    pub aggregates: Vec<AggregateExpr>,
+    pub having: Option<Selection>,
+}
+
 /// The list of expressions for `SELECT expr1, expr2...` determining what data to extract.
 #[derive(Debug, Clone)]
 pub struct Selection {
@@ -112,6 +125,7 @@ impl Selection {
     }
 }
 
+#[derive(Clone)]
 pub struct OnExpr {
     pub op: OpCmp,
     pub lhs: FieldName,
@@ -119,6 +133,7 @@ pub struct OnExpr {
 }
 
 /// The `JOIN [INNER] ON join_expr OpCmp join_expr` clause
+#[derive(Clone)]
 pub enum Join {
     Inner { rhs: TableSchema, on: OnExpr },
 }
@@ -130,6 +145,7 @@ pub struct FromField {
 }
 
 /// The list of tables in `... FROM table1 [JOIN table2] ...`
+#[derive(Clone)]
 pub struct From {
     pub root: TableSchema,
     pub join: Option<Vec<Join>>,
@@ -140,20 +156,9 @@ impl From {
         Self { root, join: None }
     }
 
+    /// Joins `rhs` on `on`, whose `lhs` is a field of the tables joined so far and whose `rhs` is a field of `rhs`.
     pub fn with_inner_join(self, rhs: TableSchema, on: OnExpr) -> Self {
         let mut x = self;
-
-        // Check if the field are inverted:
-        // FROM t1 JOIN t2 ON t2.id = t1.id
-        let on = if on.rhs.table() == x.root.table_name && x.root.get_column_by_field(&on.rhs).is_some() {
-            OnExpr {
-                op: on.op.reverse(),
-                lhs: on.rhs,
-                rhs: on.lhs,
-            }
-        } else {
-            on
-        };
         if let Some(joins) = &mut x.join {
             joins.push(Join::Inner { rhs, on })
         } else {
@@ -179,48 +184,77 @@ impl From {
 
     /// Returns all the fields matching `f` as a `Vec<FromField>`,
     /// including the ones inside the joins.
-    pub fn find_field(&self, f: &str) -> Result<Vec<FromField>, RelationError> {
-        let field = extract_table_field(f)?;
-        let fields = self.iter_tables().filter_map(|t| {
+    pub fn find_field(&self, f: &str) -> Result<Vec<FromField>, PlanError> {
+        find_field(&self.iter_tables().collect::<Vec<_>>(), f)
+    }
+
+    /// Checks if the field `named` matches exactly once in all the tables
+    /// including the ones inside the joins
+    pub fn resolve_field(&self, named: &str) -> Result<FromField, PlanError> {
+        resolve_field(&self.iter_tables().collect::<Vec<_>>(), named)
+    }
+}
+
+/// Returns all the fields matching `f` in `tables`.
+///
+/// A field qualified by a table name only matches the columns of that table,
+/// and fails if it is not one of `tables`.
+fn find_field(tables: &[&TableSchema], f: &str) -> Result<Vec<FromField>, PlanError> {
+    let field = extract_table_field(f)?;
+    let tables = match field.table {
+        Some(table) => {
+            let found: Vec<_> = tables.iter().filter(|t| t.table_name == table).collect();
+            if found.is_empty() {
+                return Err(PlanError::UnknownTable { table: table.into() });
+            }
+            found
+        }
+        None => tables.iter().collect(),
+    };
+
+    Ok(tables
+        .into_iter()
+        .filter_map(|t| {
             let f = t.normalize_field(&field);
             t.get_column_by_field(&f).map(|column| FromField {
                 field: f,
                 column: column.into(),
             })
-        });
-
-        Ok(fields.collect())
-    }
+        })
+        .collect())
+}
 
-    /// Checks if the field `named` matches exactly once in all the tables
-    /// including the ones inside the joins
-    pub fn resolve_field(&self, named: &str) -> Result<FromField, PlanError> {
-        let fields = self.find_field(named)?;
+/// Checks if the field `named` matches exactly once in `tables`.
+fn resolve_field(tables: &[&TableSchema], named: &str) -> Result<FromField, PlanError> {
+    let fields = find_field(tables, named)?;
 
-        match fields.len() {
-            0 => {
-                let field = extract_table_field(named)?;
+    match fields.len() {
+        0 => {
+            let field = extract_table_field(named)?;
 
-                Err(PlanError::UnknownField {
-                    field: FieldName::named(field.table.unwrap_or("?"), field.field),
-                    tables: self.table_names(),
-                })
-            }
-            1 => Ok(fields[0].clone()),
-            _ => Err(PlanError::AmbiguousField {
-                field: named.into(),
-                found: fields.iter().map(|x| x.field.clone()).collect(),
-            }),
+            Err(PlanError::UnknownField {
+                field: FieldName::named(field.table.unwrap_or("?"), field.field),
+                tables: tables.iter().map(|x| x.table_name.clone()).collect(),
+            })
         }
+        1 => Ok(fields[0].clone()),
+        _ => Err(PlanError::AmbiguousField {
+            field: named.into(),
+            found: fields.iter().map(|x| x.field.clone()).collect(),
+        }),
     }
 }
 
 /// Defines the portions of the `SQL` standard that we support.
+#[derive(Clone)]
 pub enum SqlAst {
     Select {
         from: From,
         project: Vec<Column>,
         selection: Option<Selection>,
+        group_by: Option<GroupBy>,
+        order_by: Vec<SortKey>,
+        limit: Option<Limit>,
     },
     Insert {
         table: TableSchema,
@@ -247,6 +281,9 @@ pub enum SqlAst {
         kind: DbType,
         table_access: StAccess,
     },
+    Explain {
+        statement: Box<SqlAst>,
+    },
 }
 
 fn extract_field(table: &From, of: &SqlExpr) -> Result<Option<ProductTypeElement>, PlanError> {
@@ -289,33 +326,63 @@ fn infer_number(field: Option<&ProductTypeElement>, value: &str, is_long: bool)
 }
 
 /// Compiles a [SqlExpr] expression into a [ColumnOp]
-fn compile_expr_value(table: &From, field: Option<&ProductTypeElement>, of: SqlExpr) -> Result<ColumnOp, PlanError> {
+fn compile_expr_value(
+    table: &From,
+    params: &SqlParams,
+    field: Option<&ProductTypeElement>,
+    of: SqlExpr,
+) -> Result<ColumnOp, PlanError> {
     Ok(ColumnOp::Field(match of {
         SqlExpr::Identifier(name) => FieldExpr::Name(table.resolve_field(&name.value)?.field),
         SqlExpr::CompoundIdentifier(ident) => {
             let col_name = compound_ident(&ident);
             table.resolve_field(&col_name)?.field.into()
         }
-        SqlExpr::Value(x) => FieldExpr::Value(match x {
-            Value::Number(value, is_long) => infer_number(field, &value, is_long)?,
-            Value::SingleQuotedString(s) => AlgebraicValue::String(s),
-            Value::DoubleQuotedString(s) => AlgebraicValue::String(s),
-            Value::Boolean(x) => AlgebraicValue::Bool(x),
-            Value::Null => AlgebraicValue::OptionNone(),
-            x => {
-                return Err(PlanError::Unsupported {
-                    feature: format!("Unsupported value: {x}."),
+        SqlExpr::Value(Value::Placeholder(name)) => match field {
+            Some(f) => params.field(&name, &f.algebraic_type)?,
+            None => {
+                return Err(PlanError::Param {
+                    param: name,
+                    error: "Its type can't be inferred, it must be compared or assigned to a field.".into(),
                 })
             }
-        }),
+        },
+        SqlExpr::Value(Value::Null) => FieldExpr::Value(AlgebraicValue::OptionNone()),
+        SqlExpr::Value(x) => {
+            // A value compared to an option, like the `MIN` of a column, is `some` value of the inner type.
+            let inner = field.and_then(|f| match &f.algebraic_type {
+                AlgebraicType::Sum(ty) => ty.as_option().map(|ty| ProductTypeElement::new(ty.clone(), None)),
+                _ => None,
+            });
+            let value = match x {
+                Value::Number(value, is_long) => infer_number(inner.as_ref().or(field), &value, is_long)?,
+                Value::SingleQuotedString(s) => AlgebraicValue::String(s),
+                Value::DoubleQuotedString(s) => AlgebraicValue::String(s),
+                Value::Boolean(x) => AlgebraicValue::Bool(x),
+                x => {
+                    return Err(PlanError::Unsupported {
+                        feature: format!("Unsupported value: {x}."),
+                    })
+                }
+            };
+            FieldExpr::Value(match inner {
+                Some(_) => AlgebraicValue::OptionSome(value),
+                None => value,
+            })
+        }
         SqlExpr::BinaryOp { left, op, right } => {
-            let (op, lhs, rhs) = compile_bin_op(table, op, left, right)?;
+            let (op, lhs, rhs) = compile_bin_op(table, params, op, left, right)?;
 
             return Ok(ColumnOp::new(op, lhs, rhs));
         }
         SqlExpr::Nested(x) => {
-            return compile_expr_value(table, field, *x);
+            return compile_expr_value(table, params, field, *x);
         }
+        x @ (SqlExpr::InList { .. }
+        | SqlExpr::Between { .. }
+        | SqlExpr::Like { .. }
+        | SqlExpr::IsNull(_)
+        | SqlExpr::IsNotNull(_)) => return compile_predicate(table, params, x),
         x => {
             return Err(PlanError::Unsupported {
                 feature: format!("Unsupported expression: {x}"),
@@ -324,8 +391,115 @@ fn compile_expr_value(table: &From, field: Option<&ProductTypeElement>, of: SqlE
     }))
 }
 
-fn compile_expr_field(table: &From, field: Option<&ProductTypeElement>, of: SqlExpr) -> Result<FieldExpr, PlanError> {
-    match compile_expr_value(table, field, of)? {
+/// Compiles the left side of a predicate like `field IN (...)`, that must be a field,
+/// returning it along with its type.
+fn compile_predicate_field(table: &From, expr: SqlExpr) -> Result<(ColumnOp, ProductTypeElement), PlanError> {
+    match extract_field(table, &expr)? {
+        Some(field) => Ok((compile_expr_value(table, &SqlParams::None, None, expr)?, field)),
+        None => Err(PlanError::Unsupported {
+            feature: format!("The left side of the predicate must be a field, found: {expr}."),
+        }),
+    }
+}
+
+/// Compiles `field IS NULL` (when `op` is `=`) or `field IS NOT NULL` (when `op` is `!=`),
+/// for a field of an option type.
+fn compile_is_null(op: OpCmp, table: &From, expr: SqlExpr) -> Result<ColumnOp, PlanError> {
+    let (lhs, field) = compile_predicate_field(table, expr)?;
+    match &field.algebraic_type {
+        AlgebraicType::Sum(ty) if ty.as_option().is_some() => {}
+        _ => {
+            return Err(PlanError::Unsupported {
+                feature: format!("IS NULL on the non-optional field `{lhs}`."),
+            })
+        }
+    }
+
+    Ok(ColumnOp::new(op.into(), lhs, AlgebraicValue::OptionNone().into()))
+}
+
+/// Compiles the predicates `IN (...)`, `BETWEEN`, `LIKE` & `IS [NOT] NULL` into comparisons, like:
+///
+/// `field IN (1, 2)` => `field = 1 OR field = 2`
+///
+/// `field BETWEEN 1 AND 2` => `field >= 1 AND field <= 2`
+///
+/// `field IS NULL` => `field = NULL`
+fn compile_predicate(table: &From, params: &SqlParams, of: SqlExpr) -> Result<ColumnOp, PlanError> {
+    let cmp = |op: OpCmp, lhs: &ColumnOp, rhs: ColumnOp| ColumnOp::new(op.into(), lhs.clone(), rhs);
+    let logic = |op: OpLogic, lhs: ColumnOp, rhs: ColumnOp| ColumnOp::new(op.into(), lhs, rhs);
+
+    match of {
+        SqlExpr::InList { expr, list, negated } => {
+            let (lhs, field) = compile_predicate_field(table, *expr)?;
+            let (op, join) = if negated {
+                (OpCmp::NotEq, OpLogic::And)
+            } else {
+                (OpCmp::Eq, OpLogic::Or)
+            };
+
+            let mut values = list.into_iter();
+            let Some(first) = values.next() else {
+                return Err(PlanError::Unsupported {
+                    feature: "Empty list in IN.".into(),
+                });
+            };
+            let mut op_in = cmp(op, &lhs, compile_expr_value(table, params, Some(&field), first)?);
+            for x in values {
+                let rhs = cmp(op, &lhs, compile_expr_value(table, params, Some(&field), x)?);
+                op_in = logic(join, op_in, rhs);
+            }
+            Ok(op_in)
+        }
+        SqlExpr::Between {
+            expr,
+            negated,
+            low,
+            high,
+        } => {
+            let (lhs, field) = compile_predicate_field(table, *expr)?;
+            let low = compile_expr_value(table, params, Some(&field), *low)?;
+            let high = compile_expr_value(table, params, Some(&field), *high)?;
+
+            Ok(if negated {
+                logic(OpLogic::Or, cmp(OpCmp::Lt, &lhs, low), cmp(OpCmp::Gt, &lhs, high))
+            } else {
+                logic(OpLogic::And, cmp(OpCmp::GtEq, &lhs, low), cmp(OpCmp::LtEq, &lhs, high))
+            })
+        }
+        SqlExpr::Like {
+            negated,
+            expr,
+            pattern,
+            escape_char,
+        } => {
+            unsupported!("LIKE", escape_char);
+
+            let (lhs, field) = compile_predicate_field(table, *expr)?;
+            if field.algebraic_type != AlgebraicType::String {
+                return Err(PlanError::Unsupported {
+                    feature: format!("LIKE on the non-string field `{lhs}`."),
+                });
+            }
+            let pattern = compile_expr_value(table, params, Some(&field), *pattern)?;
+
+            Ok(cmp(if negated { OpCmp::NotLike } else { OpCmp::Like }, &lhs, pattern))
+        }
+        SqlExpr::IsNull(expr) => compile_is_null(OpCmp::Eq, table, *expr),
+        SqlExpr::IsNotNull(expr) => compile_is_null(OpCmp::NotEq, table, *expr),
+        x => Err(PlanError::Unsupported {
+            feature: format!("Unsupported predicate: {x}."),
+        }),
+    }
+}
+
+fn compile_expr_field(
+    table: &From,
+    params: &SqlParams,
+    field: Option<&ProductTypeElement>,
+    of: SqlExpr,
+) -> Result<FieldExpr, PlanError> {
+    match compile_expr_value(table, params, field, of)? {
         ColumnOp::Field(field) => Ok(field),
         x => Err(PlanError::Unsupported {
             feature: format!("Complex expression {x} on insert..."),
@@ -352,14 +526,9 @@ fn compile_table_factor(table: TableFactor) -> Result<Table, PlanError> {
     }
 }
 
-/// Compiles a binary operation like `field > 1`
-fn compile_bin_op(
-    table: &From,
-    op: BinaryOperator,
-    lhs: Box<sqlparser::ast::Expr>,
-    rhs: Box<sqlparser::ast::Expr>,
-) -> Result<(OpQuery, ColumnOp, ColumnOp), PlanError> {
-    let op: OpQuery = match op {
+/// Compiles a binary operator like `>`
+fn compile_bin_operator(op: BinaryOperator) -> Result<OpQuery, PlanError> {
+    Ok(match op {
         BinaryOperator::Gt => OpCmp::Gt.into(),
         BinaryOperator::Lt => OpCmp::Lt.into(),
         BinaryOperator::GtEq => OpCmp::GtEq.into(),
@@ -373,26 +542,44 @@ fn compile_bin_op(
                 feature: format!("BinaryOperator not supported in WHERE: {x}."),
             })
         }
-    };
+    })
+}
+
+/// Compiles a binary operation like `field > 1`
+fn compile_bin_op(
+    table: &From,
+    params: &SqlParams,
+    op: BinaryOperator,
+    lhs: Box<sqlparser::ast::Expr>,
+    rhs: Box<sqlparser::ast::Expr>,
+) -> Result<(OpQuery, ColumnOp, ColumnOp), PlanError> {
+    let op = compile_bin_operator(op)?;
 
     let field_lhs = extract_field(table, &lhs)?;
     let field_rhs = extract_field(table, &rhs)?;
     // This inversion is for inferring the type of the right side, like in `inventory.id = 1`,
     // so `1` get the type of `inventory.id`
-    let lhs = compile_expr_value(table, field_rhs.as_ref(), *lhs)?;
-    let rhs = compile_expr_value(table, field_lhs.as_ref(), *rhs)?;
+    let lhs = compile_expr_value(table, params, field_rhs.as_ref(), *lhs)?;
+    let rhs = compile_expr_value(table, params, field_lhs.as_ref(), *rhs)?;
 
     Ok((op, lhs, rhs))
 }
 
-fn _compile_where(table: &From, filter: SqlExpr) -> Result<Option<Selection>, PlanError> {
+fn _compile_where(table: &From, params: &SqlParams, filter: SqlExpr) -> Result<Option<Selection>, PlanError> {
     match filter {
         SqlExpr::BinaryOp { left, op, right } => {
-            let (op, lhs, rhs) = compile_bin_op(table, op, left, right)?;
+            let (op, lhs, rhs) = compile_bin_op(table, params, op, left, right)?;
 
             Ok(Some(Selection::with_cmp(op, lhs, rhs)))
         }
-        SqlExpr::Nested(x) => _compile_where(table, *x),
+        SqlExpr::Nested(x) => _compile_where(table, params, *x),
+        x @ (SqlExpr::InList { .. }
+        | SqlExpr::Between { .. }
+        | SqlExpr::Like { .. }
+        | SqlExpr::IsNull(_)
+        | SqlExpr::IsNotNull(_)) => Ok(Some(Selection {
+            clause: compile_predicate(table, params, x)?,
+        })),
         x => Err(PlanError::Unsupported {
             feature: format!("Unsupported in WHERE: {x}."),
         }),
@@ -400,14 +587,41 @@ fn _compile_where(table: &From, filter: SqlExpr) -> Result<Option<Selection>, Pl
 }
 
 /// Compiles the `WHERE` clause
-fn compile_where(table: &From, filter: Option<SqlExpr>) -> Result<Option<Selection>, PlanError> {
+pub(crate) fn compile_where(
+    table: &From,
+    params: &SqlParams,
+    filter: Option<SqlExpr>,
+) -> Result<Option<Selection>, PlanError> {
     if let Some(filter) = filter {
-        _compile_where(table, filter)
+        _compile_where(table, params, filter)
     } else {
         Ok(None)
     }
 }
 
+/// Compiles a standalone predicate over the columns of `table`, like `amount >= 0`.
+pub(crate) fn compile_standalone_where(
+    table: &TableSchema,
+    params: &SqlParams,
+    sql: &str,
+) -> Result<Selection, PlanError> {
+    let mut parser = Parser::new(&PostgreSqlDialect {})
+        .try_with_sql(sql)
+        .map_err(|e| PlanError::Unstructured(e.to_string()))?;
+    let expr = parser
+        .parse_expr()
+        .map_err(|e| PlanError::Unstructured(e.to_string()))?;
+    let next = parser.peek_token().token;
+    if next != Token::EOF {
+        return Err(PlanError::Unstructured(format!(
+            "Unexpected `{next}` after the predicate."
+        )));
+    }
+
+    compile_where(&From::new(table.clone()), params, Some(expr))?
+        .ok_or_else(|| PlanError::Unstructured("The predicate is empty.".into()))
+}
+
 /// Retrieves the [TableSchema] for the [Table]
 ///
 /// Fails if the table `name` and/or `table_id` is not found
@@ -449,34 +663,8 @@ fn compile_from(db: &RelationalDB, tx: &MutTxId, from: &[TableWithJoins]) -> Res
 
                 match constraint {
                     JoinConstraint::On(x) => {
-                        let expr = compile_expr_value(&base, None, x.clone())?;
-                        match expr {
-                            ColumnOp::Field(_) => {}
-                            ColumnOp::Cmp { op, lhs, rhs } => {
-                                let op = match op {
-                                    OpQuery::Cmp(op) => op,
-                                    OpQuery::Logic(op) => {
-                                        return Err(PlanError::Unsupported {
-                                            feature: format!("Can't use operator {op} on JOIN clause"),
-                                        });
-                                    }
-                                };
-                                let (lhs, rhs) = match (*lhs, *rhs) {
-                                    (ColumnOp::Field(FieldExpr::Name(lhs)), ColumnOp::Field(FieldExpr::Name(rhs))) => {
-                                        (lhs, rhs)
-                                    }
-                                    (lhs, rhs) => {
-                                        return Err(PlanError::Unsupported {
-                                            feature: format!(
-                                                "Can't compare non-field expressions {lhs} and {rhs} in JOIN clause"
-                                            ),
-                                        });
-                                    }
-                                };
-
-                                base = base.with_inner_join(join, OnExpr { op, lhs, rhs })
-                            }
-                        }
+                        let on = compile_join_on(&base, &join, x.clone())?;
+                        base = base.with_inner_join(join, on)
                     }
                     x => {
                         return Err(PlanError::Unsupported {
@@ -496,6 +684,62 @@ fn compile_from(db: &RelationalDB, tx: &MutTxId, from: &[TableWithJoins]) -> Res
     Ok(base)
 }
 
+/// Compiles the `ON lhs OpCmp rhs` clause joining the table `joined` to the tables of `base`.
+///
+/// One side must be a field of `joined` and the other one a field of the tables joined so far,
+/// in any order.
+fn compile_join_on(base: &From, joined: &TableSchema, expr: SqlExpr) -> Result<OnExpr, PlanError> {
+    let (left, op, right) = match expr {
+        SqlExpr::Nested(x) => return compile_join_on(base, joined, *x),
+        SqlExpr::BinaryOp { left, op, right } => (left, op, right),
+        x => {
+            return Err(PlanError::Unsupported {
+                feature: format!(
+                    "JOIN constrain {x} is not valid, can be only on the form Table.Field [Cmp] Table.Field"
+                ),
+            });
+        }
+    };
+    let op = match compile_bin_operator(op)? {
+        OpQuery::Cmp(op) => op,
+        OpQuery::Logic(op) => {
+            return Err(PlanError::Unsupported {
+                feature: format!("Can't use operator {op} on JOIN clause"),
+            });
+        }
+    };
+
+    let tables: Vec<_> = base.iter_tables().chain([joined]).collect();
+    let field = |expr: &SqlExpr| -> Result<FieldName, PlanError> {
+        match expr {
+            SqlExpr::Identifier(name) => Ok(resolve_field(&tables, &name.value)?.field),
+            SqlExpr::CompoundIdentifier(ident) => Ok(resolve_field(&tables, &compound_ident(ident))?.field),
+            _ => Err(PlanError::Unsupported {
+                feature: format!("Can't compare non-field expressions {left} and {right} in JOIN clause"),
+            }),
+        }
+    };
+    let (lhs, rhs) = (field(&left)?, field(&right)?);
+
+    // Check if the field are inverted:
+    // FROM t1 JOIN t2 ON t2.id = t1.id
+    let is_joined = |f: &FieldName| f.table() == joined.table_name;
+    match (is_joined(&lhs), is_joined(&rhs)) {
+        (false, true) => Ok(OnExpr { op, lhs, rhs }),
+        (true, false) => Ok(OnExpr {
+            op: op.reverse(),
+            lhs: rhs,
+            rhs: lhs,
+        }),
+        _ => Err(PlanError::Unsupported {
+            feature: format!(
+                "JOIN clause `{left} {op} {right}` must compare a field of `{}` with a field of the tables joined before it",
+                joined.table_name
+            ),
+        }),
+    }
+}
+
 fn compound_ident(ident: &[Ident]) -> String {
     ident.iter().map(ToString::to_string).collect::<Vec<_>>().join(".")
 }
@@ -514,12 +758,15 @@ fn compile_select_item(from: &From, select_item: SelectItem) -> Result<Column, P
                 Ok(Column::UnnamedExpr(Expr::Ident(col_name)))
             }
             sqlparser::ast::Expr::Value(_) => {
-                let value = compile_expr_value(from, None, expr)?;
+                let value = compile_expr_value(from, &SqlParams::None, None, expr)?;
                 match value {
                     ColumnOp::Field(value) => match value {
                         FieldExpr::Name(_) => Err(PlanError::Unsupported {
                             feature: "Should not be an identifier in Expr::Value".to_string(),
                         }),
+                        FieldExpr::Param { .. } => Err(PlanError::Unsupported {
+                            feature: "Should not be a parameter in Expr::Value".to_string(),
+                        }),
                         FieldExpr::Value(x) => Ok(Column::UnnamedExpr(Expr::Value(x))),
                     },
                     x => Err(PlanError::Unsupported {
@@ -528,10 +775,15 @@ fn compile_select_item(from: &From, select_item: SelectItem) -> Result<Column, P
                 }
             }
             sqlparser::ast::Expr::Nested(x) => compile_select_item(from, SelectItem::UnnamedExpr(*x)),
+            sqlparser::ast::Expr::Function(f) => Ok(Column::Aggregate(compile_aggregate(from, f, None)?.0)),
             _ => Err(PlanError::Unsupported {
                 feature: "Only columns names & scalars are supported.".into(),
             }),
         },
+        SelectItem::ExprWithAlias {
+            expr: sqlparser::ast::Expr::Function(f),
+            alias,
+        } => Ok(Column::Aggregate(compile_aggregate(from, f, Some(alias))?.0)),
         SelectItem::ExprWithAlias { expr: _, alias: _ } => Err(PlanError::Unsupported {
             feature: "ExprWithAlias".into(),
         }),
@@ -542,8 +794,287 @@ fn compile_select_item(from: &From, select_item: SelectItem) -> Result<Column, P
     }
 }
 
+/// Compiles an aggregate function like `SUM(field)`,
+/// returning it along with the type of its result.
+///
+/// The resulting column is named `alias`, or after the call itself, like `sum(field)`.
+fn compile_aggregate(
+    from: &From,
+    f: Function,
+    alias: Option<Ident>,
+) -> Result<(AggregateExpr, AlgebraicType), PlanError> {
+    unsupported!("Function", f.over, f.distinct, f.special, f.order_by);
+
+    let func = match f.name.to_string().to_lowercase().as_str() {
+        "count" => AggregateFn::Count,
+        "sum" => AggregateFn::Sum,
+        "min" => AggregateFn::Min,
+        "max" => AggregateFn::Max,
+        "avg" => AggregateFn::Avg,
+        _ => {
+            return Err(PlanError::Unsupported {
+                feature: format!("Unsupported function: {}.", f.name),
+            })
+        }
+    };
+
+    let arg = match &f.args[..] {
+        [FunctionArg::Unnamed(arg)] => arg,
+        _ => {
+            return Err(PlanError::Unsupported {
+                feature: format!("`{func}` takes exactly one argument."),
+            })
+        }
+    };
+    let field = match arg {
+        FunctionArgExpr::Wildcard if func == AggregateFn::Count => None,
+        FunctionArgExpr::Expr(SqlExpr::Identifier(ident)) => Some(from.resolve_field(&ident.value)?),
+        FunctionArgExpr::Expr(SqlExpr::CompoundIdentifier(ident)) => Some(from.resolve_field(&compound_ident(ident))?),
+        x => {
+            return Err(PlanError::Unsupported {
+                feature: format!("Unsupported argument for `{func}`: {x}."),
+            })
+        }
+    };
+
+    let ty = match &field {
+        Some(field) => func
+            .result_type(&field.column.column.algebraic_type)
+            .ok_or_else(|| PlanError::Unsupported {
+                feature: format!("`{func}` over the non-numeric field `{}`.", field.field),
+            })?,
+        None => AlgebraicType::U64,
+    };
+
+    let name = alias.map_or_else(|| format!("{func}({arg})"), |alias| alias.value);
+    let aggregate = AggregateExpr {
+        func,
+        field: field.map(|x| x.field),
+        name: FieldName::named(&from.root.table_name, &name),
+    };
+    Ok((aggregate, ty))
+}
+
+/// Returns the type of an operand of the `HAVING` clause, if it is a field or an aggregate function.
+fn having_field(from: &From, of: &SqlExpr) -> Result<Option<ProductTypeElement>, PlanError> {
+    match of {
+        SqlExpr::Function(f) => {
+            let (_, ty) = compile_aggregate(from, f.clone(), None)?;
+            Ok(Some(ProductTypeElement::new(ty, None)))
+        }
+        x => extract_field(from, x),
+    }
+}
+
+/// Compiles the `HAVING` clause, that can only refer to the `group_by` fields and to aggregate functions.
+///
+/// The aggregate functions missing from `aggregates` are added to it.
+fn compile_having(
+    from: &From,
+    params: &SqlParams,
+    group_by: &[FieldName],
+    aggregates: &mut Vec<AggregateExpr>,
+    field: Option<&ProductTypeElement>,
+    of: SqlExpr,
+) -> Result<ColumnOp, PlanError> {
+    match of {
+        SqlExpr::BinaryOp { left, op, right } => {
+            let op = compile_bin_operator(op)?;
+            // Infer the type of each side from the other, like in `compile_bin_op`,
+            // so `1` gets the type `U64` in `COUNT(*) > 1`
+            let field_lhs = having_field(from, &left)?;
+            let field_rhs = having_field(from, &right)?;
+            let lhs = compile_having(from, params, group_by, aggregates, field_rhs.as_ref(), *left)?;
+            let rhs = compile_having(from, params, group_by, aggregates, field_lhs.as_ref(), *right)?;
+
+            Ok(ColumnOp::new(op, lhs, rhs))
+        }
+        SqlExpr::Nested(x) => compile_having(from, params, group_by, aggregates, field, *x),
+        SqlExpr::Function(f) => {
+            let (aggregate, _) = compile_aggregate(from, f, None)?;
+            let name = aggregate.name.clone();
+            if !aggregates.contains(&aggregate) {
+                aggregates.push(aggregate);
+            }
+            Ok(ColumnOp::Field(FieldExpr::Name(name)))
+        }
+        SqlExpr::Identifier(_) | SqlExpr::CompoundIdentifier(_) => {
+            let op = compile_expr_value(from, params, field, of)?;
+            match &op {
+                ColumnOp::Field(FieldExpr::Name(name)) if !group_by.contains(name) => Err(PlanError::Unsupported {
+                    feature: format!("Field `{name}` in HAVING must appear in the GROUP BY clause."),
+                }),
+                _ => Ok(op),
+            }
+        }
+        SqlExpr::Value(_) => compile_expr_value(from, params, field, of),
+        x => Err(PlanError::Unsupported {
+            feature: format!("Unsupported in HAVING: {x}."),
+        }),
+    }
+}
+
+/// Compiles the `GROUP BY` & `HAVING` clauses,
+/// returning `None` when the query doesn't use them nor any aggregate function.
+fn compile_group_by(
+    from: &From,
+    params: &SqlParams,
+    project: &[Column],
+    group_by: Vec<SqlExpr>,
+    having: Option<SqlExpr>,
+) -> Result<Option<GroupBy>, PlanError> {
+    let mut aggregates = Vec::new();
+    for col in project {
+        if let Column::Aggregate(x) = col {
+            if !aggregates.contains(x) {
+                aggregates.push(x.clone());
+            }
+        }
+    }
+    if group_by.is_empty() && having.is_none() && aggregates.is_empty() {
+        return Ok(None);
+    }
+
+    let mut fields = Vec::with_capacity(group_by.len());
+    for x in group_by {
+        let field = match x {
+            SqlExpr::Identifier(ident) => from.resolve_field(&ident.value)?,
+            SqlExpr::CompoundIdentifier(ident) => from.resolve_field(&compound_ident(&ident))?,
+            x => {
+                return Err(PlanError::Unsupported {
+                    feature: format!("Unsupported in GROUP BY: {x}."),
+                })
+            }
+        };
+        fields.push(field.field);
+    }
+
+    // Each group yields a single row, so only its fields can be selected.
+    for col in project {
+        match col {
+            Column::UnnamedExpr(Expr::Ident(name)) => {
+                let field = from.resolve_field(name)?.field;
+                if !fields.contains(&field) {
+                    return Err(PlanError::Unsupported {
+                        feature: format!(
+                            "Field `{field}` must appear in the GROUP BY clause or be used in an aggregate function."
+                        ),
+                    });
+                }
+            }
+            Column::UnnamedExpr(_) | Column::Aggregate(_) => {}
+            Column::QualifiedWildcard { .. } | Column::Wildcard => {
+                return Err(PlanError::Unsupported {
+                    feature: "Wildcards can't be selected along with GROUP BY or aggregate functions.".into(),
+                })
+            }
+        }
+    }
+
+    let having = having
+        .map(|x| compile_having(from, params, &fields, &mut aggregates, None, x))
+        .transpose()?
+        .map(|clause| Selection { clause });
+
+    Ok(Some(GroupBy {
+        fields,
+        aggregates,
+        having,
+    }))
+}
+
+/// Compiles the `ORDER BY` clause.
+///
+/// When the rows are grouped, it can only refer to the `group_by` fields and to aggregate functions,
+/// by their call or their alias. The aggregate functions missing from `group_by` are added to it.
+fn compile_order_by(
+    from: &From,
+    mut group_by: Option<&mut GroupBy>,
+    order_by: Vec<OrderByExpr>,
+) -> Result<Vec<SortKey>, PlanError> {
+    let mut keys = Vec::with_capacity(order_by.len());
+    for x in order_by {
+        unsupported!("ORDER BY", x.nulls_first);
+
+        let name = match x.expr {
+            SqlExpr::Identifier(ident) => ident.value,
+            SqlExpr::CompoundIdentifier(ident) => compound_ident(&ident),
+            SqlExpr::Function(f) if group_by.is_some() => {
+                let (aggregate, _) = compile_aggregate(from, f, None)?;
+                let name = aggregate.name.to_string();
+                let aggregates = &mut group_by.as_deref_mut().unwrap().aggregates;
+                if !aggregates.contains(&aggregate) {
+                    aggregates.push(aggregate);
+                }
+                name
+            }
+            x => {
+                return Err(PlanError::Unsupported {
+                    feature: format!("Unsupported in ORDER BY: {x}."),
+                })
+            }
+        };
+
+        let field = match group_by.as_deref() {
+            Some(group_by) => {
+                let aggregate = group_by
+                    .aggregates
+                    .iter()
+                    .find(|x| x.name.field_name() == Some(name.as_str()) || x.name.to_string() == name);
+                match aggregate {
+                    Some(aggregate) => aggregate.name.clone(),
+                    None => {
+                        let field = from.resolve_field(&name)?.field;
+                        if !group_by.fields.contains(&field) {
+                            return Err(PlanError::Unsupported {
+                                feature: format!("Field `{field}` in ORDER BY must appear in the GROUP BY clause or be used in an aggregate function."),
+                            });
+                        }
+                        field
+                    }
+                }
+            }
+            None => from.resolve_field(&name)?.field,
+        };
+
+        keys.push(SortKey {
+            field,
+            asc: x.asc.unwrap_or(true),
+        });
+    }
+    Ok(keys)
+}
+
+/// Compiles the `LIMIT` & `OFFSET` clauses
+fn compile_limit(limit: Option<SqlExpr>, offset: Option<Offset>) -> Result<Option<Limit>, PlanError> {
+    let rows = |of: SqlExpr| match of {
+        SqlExpr::Value(Value::Number(x, _)) => x.parse::<usize>().map_err(|_| PlanError::Unsupported {
+            feature: format!("Invalid number of rows: {x}."),
+        }),
+        x => Err(PlanError::Unsupported {
+            feature: format!("Unsupported number of rows: {x}."),
+        }),
+    };
+
+    if limit.is_none() && offset.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(Limit {
+        offset: offset.map(|x| rows(x.value)).transpose()?.unwrap_or(0),
+        limit: limit.map(rows).transpose()?,
+    }))
+}
+
 /// Compiles the `SELECT ...` clause
-fn compile_select(db: &RelationalDB, tx: &MutTxId, select: Select) -> Result<SqlAst, PlanError> {
+fn compile_select(
+    db: &RelationalDB,
+    tx: &MutTxId,
+    params: &SqlParams,
+    select: Select,
+    order_by: Vec<OrderByExpr>,
+    limit: Option<SqlExpr>,
+    offset: Option<Offset>,
+) -> Result<SqlAst, PlanError> {
     let from = compile_from(db, tx, &select.from)?;
     // SELECT ...
     let mut project = Vec::new();
@@ -552,26 +1083,24 @@ fn compile_select(db: &RelationalDB, tx: &MutTxId, select: Select) -> Result<Sql
         project.push(col);
     }
 
-    let selection = compile_where(&from, select.selection)?;
+    let selection = compile_where(&from, params, select.selection)?;
+    let mut group_by = compile_group_by(&from, params, &project, select.group_by, select.having)?;
+    let order_by = compile_order_by(&from, group_by.as_mut(), order_by)?;
+    let limit = compile_limit(limit, offset)?;
 
     Ok(SqlAst::Select {
         from,
         project,
         selection,
+        group_by,
+        order_by,
+        limit,
     })
 }
 
 /// Compiles any `query` clause (currently only `SELECT...`)
-fn compile_query(db: &RelationalDB, tx: &MutTxId, query: Query) -> Result<SqlAst, PlanError> {
-    unsupported!(
-        "SELECT",
-        query.order_by,
-        query.fetch,
-        query.limit,
-        query.offset,
-        query.locks,
-        query.with
-    );
+fn compile_query(db: &RelationalDB, tx: &MutTxId, params: &SqlParams, query: Query) -> Result<SqlAst, PlanError> {
+    unsupported!("SELECT", query.fetch, query.locks, query.with);
 
     match *query.body {
         SetExpr::Select(select) => {
@@ -581,12 +1110,10 @@ fn compile_query(db: &RelationalDB, tx: &MutTxId, query: Query) -> Result<SqlAst
                 select.top,
                 select.into,
                 select.lateral_views,
-                select.group_by,
-                select.having,
                 select.sort_by
             );
 
-            compile_select(db, tx, *select)
+            compile_select(db, tx, params, *select, query.order_by, query.limit, query.offset)
         }
         SetExpr::Query(_) => Err(PlanError::Unsupported {
             feature: "Query".into(),
@@ -618,6 +1145,7 @@ fn compile_query(db: &RelationalDB, tx: &MutTxId, query: Query) -> Result<SqlAst
 fn compile_insert(
     db: &RelationalDB,
     tx: &MutTxId,
+    params: &SqlParams,
     table_name: ObjectName,
     columns: Vec<Ident>,
     data: &Values,
@@ -637,7 +1165,7 @@ fn compile_insert(
         let mut row = Vec::with_capacity(x.len());
         for (pos, v) in x.iter().enumerate() {
             let field = table.root.get_column(pos).map(ProductTypeElement::from);
-            row.push(compile_expr_field(&table, field.as_ref(), v.clone())?);
+            row.push(compile_expr_field(&table, params, field.as_ref(), v.clone())?);
         }
 
         values.push(row);
@@ -653,12 +1181,13 @@ fn compile_insert(
 fn compile_update(
     db: &RelationalDB,
     tx: &MutTxId,
+    params: &SqlParams,
     table: Table,
     assignments: Vec<Assignment>,
     selection: Option<SqlExpr>,
 ) -> Result<SqlAst, PlanError> {
     let table = From::new(find_table(db, tx, table)?.into_owned());
-    let selection = compile_where(&table, selection)?;
+    let selection = compile_where(&table, params, selection)?;
 
     let mut x = HashMap::with_capacity(assignments.len());
 
@@ -666,7 +1195,7 @@ fn compile_update(
         let name: String = col.id.iter().map(|x| x.to_string()).collect();
 
         let field = table.root.get_column_by_name(&name).map(ProductTypeElement::from);
-        let value = compile_expr_field(&table, field.as_ref(), col.value)?;
+        let value = compile_expr_field(&table, params, field.as_ref(), col.value)?;
         x.insert(FieldName::named(&table.root.table_name, &name), value);
     }
 
@@ -681,11 +1210,12 @@ fn compile_update(
 fn compile_delete(
     db: &RelationalDB,
     tx: &MutTxId,
+    params: &SqlParams,
     table: Table,
     selection: Option<SqlExpr>,
 ) -> Result<SqlAst, PlanError> {
     let table = From::new(find_table(db, tx, table)?.into_owned());
-    let selection = compile_where(&table, selection)?;
+    let selection = compile_where(&table, params, selection)?;
 
     Ok(SqlAst::Delete {
         table: table.root,
@@ -849,9 +1379,14 @@ fn compile_drop(name: &ObjectName, kind: ObjectType) -> Result<SqlAst, PlanError
 }
 
 /// Compiles a `SQL` clause
-fn compile_statement(db: &RelationalDB, tx: &MutTxId, statement: Statement) -> Result<SqlAst, PlanError> {
+fn compile_statement(
+    db: &RelationalDB,
+    tx: &MutTxId,
+    params: &SqlParams,
+    statement: Statement,
+) -> Result<SqlAst, PlanError> {
     match statement {
-        Statement::Query(query) => Ok(compile_query(db, tx, *query)?),
+        Statement::Query(query) => Ok(compile_query(db, tx, params, *query)?),
         Statement::Insert {
             or,
             into,
@@ -885,7 +1420,7 @@ fn compile_statement(db: &RelationalDB, tx: &MutTxId, statement: Statement) -> R
                     }
                 };
 
-                return compile_insert(db, tx, table_name, columns, values);
+                return compile_insert(db, tx, params, table_name, columns, values);
             };
 
             Err(PlanError::Unsupported {
@@ -902,7 +1437,7 @@ fn compile_statement(db: &RelationalDB, tx: &MutTxId, statement: Statement) -> R
             unsupported!("UPDATE", from, returning);
 
             let table_name = compile_table_factor(table.relation)?;
-            compile_update(db, tx, table_name, assignments, selection)
+            compile_update(db, tx, params, table_name, assignments, selection)
         }
         Statement::Delete {
             tables,
@@ -918,7 +1453,7 @@ fn compile_statement(db: &RelationalDB, tx: &MutTxId, statement: Statement) -> R
 
             let table = from.first().unwrap().clone();
             let table_name = compile_table_factor(table.relation)?;
-            compile_delete(db, tx, table_name, selection)
+            compile_delete(db, tx, params, table_name, selection)
         }
         Statement::CreateTable {
             transient,
@@ -1010,23 +1545,103 @@ fn compile_statement(db: &RelationalDB, tx: &MutTxId, statement: Statement) -> R
             };
             compile_drop(name, object_type)
         }
+        Statement::Explain {
+            describe_alias: _,
+            analyze,
+            verbose,
+            statement,
+            format,
+        } => {
+            unsupported!("EXPLAIN", analyze, verbose, format);
+
+            match *statement {
+                Statement::Query(query) => Ok(SqlAst::Explain {
+                    statement: Box::new(compile_query(db, tx, params, *query)?),
+                }),
+                x => Err(PlanError::Unsupported {
+                    feature: format!("EXPLAIN of {x}"),
+                }),
+            }
+        }
         x => Err(PlanError::Unsupported {
             feature: format!("Syntax {x}"),
         }),
     }
 }
 
-/// Compiles a `sql` string into a `Vec<SqlAst>` using a SQL parser with [PostgreSqlDialect]
-pub(crate) fn compile_to_ast(db: &RelationalDB, tx: &MutTxId, sql_text: &str) -> Result<Vec<SqlAst>, DBError> {
-    let dialect = PostgreSqlDialect {};
-    let ast = Parser::parse_sql(&dialect, sql_text).map_err(|error| DBError::SqlParser {
-        sql: sql_text.to_string(),
-        error,
-    })?;
+/// Replaces the placeholders left in `ast` by compiling it with [SqlParams::Unbound]
+/// with the values of `params`.
+pub(crate) fn bind_params(ast: &mut SqlAst, params: &SqlParams) -> Result<(), PlanError> {
+    fn bind_field(field: &mut FieldExpr, params: &SqlParams) -> Result<(), PlanError> {
+        if let FieldExpr::Param { name, ty } = field {
+            *field = FieldExpr::Value(params.decode(name, ty)?);
+        }
+        Ok(())
+    }
+    fn bind_op(op: &mut ColumnOp, params: &SqlParams) -> Result<(), PlanError> {
+        match op {
+            ColumnOp::Field(field) => bind_field(field, params),
+            ColumnOp::Cmp { lhs, rhs, .. } => {
+                bind_op(lhs, params)?;
+                bind_op(rhs, params)
+            }
+        }
+    }
+    fn bind_selection(selection: &mut Option<Selection>, params: &SqlParams) -> Result<(), PlanError> {
+        selection
+            .as_mut()
+            .map_or(Ok(()), |selection| bind_op(&mut selection.clause, params))
+    }
+
+    match ast {
+        SqlAst::Select {
+            selection, group_by, ..
+        } => {
+            bind_selection(selection, params)?;
+            if let Some(group_by) = group_by {
+                bind_selection(&mut group_by.having, params)?;
+            }
+            Ok(())
+        }
+        SqlAst::Insert { values, .. } => values
+            .iter_mut()
+            .flatten()
+            .try_for_each(|field| bind_field(field, params)),
+        SqlAst::Update {
+            assignments, selection, ..
+        } => {
+            for field in assignments.values_mut() {
+                bind_field(field, params)?;
+            }
+            bind_selection(selection, params)
+        }
+        SqlAst::Delete { selection, .. } => bind_selection(selection, params),
+        SqlAst::Explain { statement } => bind_params(statement, params),
+        SqlAst::CreateTable { .. } | SqlAst::Drop { .. } => Ok(()),
+    }
+}
+
+/// Compiles a `sql` string into a `Vec<SqlAst>` using a SQL parser with [sqlparser::dialect::PostgreSqlDialect],
+/// binding its `$n` placeholders to the values of `params`.
+///
+/// The parsing of `sql_text` is cached in the [crate::sql::cache::StatementCache] of `db`.
+pub(crate) fn compile_to_ast(
+    db: &RelationalDB,
+    tx: &MutTxId,
+    sql_text: &str,
+    params: &SqlParams,
+) -> Result<Vec<SqlAst>, DBError> {
+    let ast = db
+        .statement_cache()
+        .parse(sql_text)
+        .map_err(|error| DBError::SqlParser {
+            sql: sql_text.to_string(),
+            error,
+        })?;
 
     let mut results = Vec::new();
-    for statement in ast {
-        let plan_result = compile_statement(db, tx, statement);
+    for statement in ast.iter().cloned() {
+        let plan_result = compile_statement(db, tx, params, statement);
         let query = match plan_result {
             Ok(plan) => plan,
             Err(error) => {
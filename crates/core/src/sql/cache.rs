@@ -0,0 +1,126 @@
+//! Caches the parsing and the compilation of the `SQL` sent to a database.
+use std::sync::{Arc, Mutex};
+
+use hashlink::LruCache;
+use spacetimedb_lib::Identity;
+use sqlparser::ast::Statement;
+use sqlparser::dialect::PostgreSqlDialect;
+use sqlparser::parser::{Parser, ParserError};
+
+use crate::error::DBError;
+use crate::sql::ast::SqlAst;
+
+/// The number of distinct texts, and of distinct plans, kept before the least recently used is evicted.
+const MAX_ENTRIES: usize = 1024;
+
+/// What the plan of a `SQL` text depends on, besides the schema.
+///
+/// The plan leaves the placeholders of the text as slots, so it doesn't depend on the values
+/// bound to them.
+#[derive(PartialEq, Eq, Hash)]
+struct PlanKey {
+    sql_text: String,
+    /// The caller whose row-level security policies restrict the rows read,
+    /// or `None` if there's no such restriction, like for the owner of the database.
+    caller: Option<Identity>,
+}
+
+struct Cache {
+    statements: LruCache<String, Arc<Vec<Statement>>>,
+    plans: LruCache<PlanKey, Vec<SqlAst>>,
+    /// The version of the schema the `plans` were compiled against.
+    schema_version: u64,
+}
+
+/// The statements parsed from each `SQL` text, and the plans compiled from them,
+/// so the same prepared statement is parsed and compiled once no matter the parameters
+/// it is executed with.
+///
+/// A plan depends on the schema at the time it is compiled,
+/// so the plans are all dropped whenever a change to the schema is committed.
+#[derive(Clone)]
+pub struct StatementCache {
+    cache: Arc<Mutex<Cache>>,
+}
+
+impl Default for StatementCache {
+    fn default() -> Self {
+        Self {
+            cache: Arc::new(Mutex::new(Cache {
+                statements: LruCache::new(MAX_ENTRIES),
+                plans: LruCache::new(MAX_ENTRIES),
+                schema_version: 0,
+            })),
+        }
+    }
+}
+
+impl StatementCache {
+    /// Returns the statements of `sql_text`, parsing them with [PostgreSqlDialect] if they aren't cached.
+    pub fn parse(&self, sql_text: &str) -> Result<Arc<Vec<Statement>>, ParserError> {
+        if let Some(statements) = self.cache.lock().unwrap().statements.get(sql_text) {
+            return Ok(statements.clone());
+        }
+
+        let statements = Arc::new(Parser::parse_sql(&PostgreSqlDialect {}, sql_text)?);
+
+        let mut cache = self.cache.lock().unwrap();
+        cache.statements.insert(sql_text.to_string(), statements.clone());
+        Ok(statements)
+    }
+
+    /// Returns the plan of `sql_text` run by `caller`, calling `compile` if it isn't cached.
+    ///
+    /// The plan has its placeholders left as slots, see [crate::sql::params::SqlParams::Unbound].
+    ///
+    /// `schema_version` is the version of the schema seen by the transaction compiling the plan,
+    /// see [crate::db::relational_db::RelationalDB::schema_version],
+    /// or `None` if it changes the schema, in which case the plan is neither looked up nor cached.
+    pub fn compile(
+        &self,
+        schema_version: Option<u64>,
+        sql_text: &str,
+        caller: Option<Identity>,
+        compile: impl FnOnce() -> Result<Vec<SqlAst>, DBError>,
+    ) -> Result<Vec<SqlAst>, DBError> {
+        let Some(schema_version) = schema_version else {
+            return compile();
+        };
+        let key = PlanKey {
+            sql_text: sql_text.to_string(),
+            caller,
+        };
+        {
+            let mut cache = self.cache.lock().unwrap();
+            if cache.schema_version != schema_version {
+                cache.plans.clear();
+                cache.schema_version = schema_version;
+            }
+            if let Some(plan) = cache.plans.get(&key) {
+                return Ok(plan.clone());
+            }
+        }
+
+        let plan = compile()?;
+
+        let mut cache = self.cache.lock().unwrap();
+        if cache.schema_version == schema_version {
+            cache.plans.insert(key, plan.clone());
+        }
+        Ok(plan)
+    }
+
+    /// The number of distinct texts whose statements are cached.
+    pub fn len(&self) -> usize {
+        self.cache.lock().unwrap().statements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of plans cached for the current schema.
+    pub fn plans_len(&self) -> usize {
+        self.cache.lock().unwrap().plans.len()
+    }
+}
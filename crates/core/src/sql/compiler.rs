@@ -1,32 +1,91 @@
 use nonempty::NonEmpty;
 use std::collections::HashMap;
+use std::ops::Bound;
 use tracing::info;
 
 use crate::db::datastore::locking_tx_datastore::MutTxId;
 use crate::db::datastore::traits::{IndexSchema, TableSchema};
 use crate::db::relational_db::RelationalDB;
 use crate::error::{DBError, PlanError};
-use crate::sql::ast::{compile_to_ast, Column, From, Join, Selection, SqlAst};
+use crate::sql::ast::{bind_params, compile_to_ast, Column, From, GroupBy, Join, Selection, SqlAst};
+use crate::sql::params::SqlParams;
+use crate::sql::policy::apply_row_policies;
 use spacetimedb_lib::auth::{StAccess, StTableType};
+use spacetimedb_lib::identity::AuthCtx;
 use spacetimedb_lib::operator::OpQuery;
 use spacetimedb_lib::relation::{self, DbTable, FieldExpr, FieldName, Header};
 use spacetimedb_lib::table::ProductTypeMeta;
+use spacetimedb_lib::IndexType;
 use spacetimedb_primitives::ColId;
 use spacetimedb_sats::{AlgebraicValue, ProductType};
 use spacetimedb_vm::dsl::{db_table, db_table_raw, query};
-use spacetimedb_vm::expr::{ColumnOp, CrudExpr, DbType, Expr, IndexJoin, JoinExpr, Query, QueryExpr, SourceExpr};
-use spacetimedb_vm::operator::OpCmp;
+use spacetimedb_vm::expr::{
+    ColumnOp, CrudExpr, DbType, Expr, IndexJoin, IndexScan, JoinExpr, Limit, Query, QueryExpr, SortKey, SourceExpr,
+};
+use spacetimedb_vm::operator::{OpCmp, OpLogic};
 
 /// Compile the `SQL` expression into a `ast`
 #[tracing::instrument(skip_all)]
 pub fn compile_sql(db: &RelationalDB, tx: &MutTxId, sql_text: &str) -> Result<Vec<CrudExpr>, DBError> {
+    compile_sql_with_params(db, tx, sql_text, &SqlParams::None)
+}
+
+/// Compile the `SQL` expression into a `ast`, binding its `$n` placeholders to `params`.
+///
+/// Each value of `params` is decoded as the type of the field it is compared or assigned to.
+#[tracing::instrument(skip_all)]
+pub fn compile_sql_with_params(
+    db: &RelationalDB,
+    tx: &MutTxId,
+    sql_text: &str,
+    params: &SqlParams,
+) -> Result<Vec<CrudExpr>, DBError> {
     info!(sql = sql_text);
-    let ast = compile_to_ast(db, tx, sql_text)?;
+    let schema_version = db.schema_version(tx);
+    let ast = db.statement_cache().compile(schema_version, sql_text, None, || {
+        compile_to_ast(db, tx, sql_text, &SqlParams::Unbound)
+    })?;
+    compile_statements(sql_text, ast, params)
+}
+
+/// Compile the `SQL` expression into a `ast` run by the caller of `auth`,
+/// binding its `$n` placeholders to `params`.
+///
+/// Unless the caller is the owner of the database, the rows of the tables are restricted
+/// to the ones visible to the caller according to their row-level security policies.
+#[tracing::instrument(skip_all)]
+pub fn compile_sql_with_auth(
+    db: &RelationalDB,
+    tx: &MutTxId,
+    sql_text: &str,
+    params: &SqlParams,
+    auth: &AuthCtx,
+) -> Result<Vec<CrudExpr>, DBError> {
+    info!(sql = sql_text);
+    let schema_version = db.schema_version(tx);
+    // The row-level security policies only restrict the rows read by other callers than the owner.
+    let caller = (auth.caller != auth.owner).then_some(auth.caller);
+    let ast = db.statement_cache().compile(schema_version, sql_text, caller, || {
+        let mut ast = compile_to_ast(db, tx, sql_text, &SqlParams::Unbound)?;
+        for sql in &mut ast {
+            apply_row_policies(db, tx, auth, sql).map_err(|error| DBError::Plan {
+                sql: sql_text.to_string(),
+                error,
+            })?;
+        }
+        Ok(ast)
+    })?;
+    compile_statements(sql_text, ast, params)
+}
 
+/// Binds the placeholders of each statement of `ast` to `params` and picks the indexes to use,
+/// which may depend on the values bound.
+fn compile_statements(sql_text: &str, ast: Vec<SqlAst>, params: &SqlParams) -> Result<Vec<CrudExpr>, DBError> {
     let mut results = Vec::with_capacity(ast.len());
 
-    for sql in ast {
-        results.push(compile_statement(sql).map_err(|error| DBError::Plan {
+    for mut sql in ast {
+        let result = bind_params(&mut sql, params).and_then(|()| compile_statement(sql));
+        results.push(result.map_err(|error| DBError::Plan {
             sql: sql_text.to_string(),
             error,
         })?);
@@ -92,6 +151,12 @@ fn compile_where(mut q: QueryExpr, table: &From, filter: Selection) -> Result<Qu
                     q = q.with_index_eq(schema.into(), col_id, value);
                     continue 'outer;
                 }
+                // found sargable `IN` condition for one of the table schemas,
+                // answered with an index seek for each of its values
+                Some(IndexArgument::In { col_id, values }) => {
+                    q = q.with_index_in(schema.into(), col_id, values);
+                    continue 'outer;
+                }
                 // found sargable range condition for one of the table schemas
                 Some(IndexArgument::LowerBound {
                     col_id,
@@ -126,6 +191,11 @@ pub enum IndexArgument {
         col_id: ColId,
         value: AlgebraicValue,
     },
+    /// Equality with any of several values, like in `a IN (1, 5)`.
+    In {
+        col_id: ColId,
+        values: Vec<AlgebraicValue>,
+    },
     LowerBound {
         col_id: ColId,
         value: AlgebraicValue,
@@ -165,6 +235,11 @@ fn is_sargable(table: &TableSchema, op: &ColumnOp) -> Option<IndexArgument> {
 
         assert_eq!(index.cols.len(), 1, "No yet supported multi-column indexes");
 
+        // a hash index only answers equality
+        if index.index_type == IndexType::Hash && *op != OpCmp::Eq {
+            return None;
+        }
+
         match op {
             OpCmp::Eq => Some(IndexArgument::Eq {
                 col_id: index.cols.head,
@@ -194,15 +269,78 @@ fn is_sargable(table: &TableSchema, op: &ColumnOp) -> Option<IndexArgument> {
                 value: value.clone(),
                 inclusive: true,
             }),
-            OpCmp::NotEq => None,
+            OpCmp::NotEq | OpCmp::Like | OpCmp::NotLike => None,
         }
     } else {
-        None
+        is_sargable_in(table, op)
     }
 }
 
+/// Is `op` a disjunction of equalities of an indexed field, like `a = 1 OR a = 5`,
+/// as an `IN` is compiled.
+fn is_sargable_in(table: &TableSchema, op: &ColumnOp) -> Option<IndexArgument> {
+    fn collect<'a>(op: &'a ColumnOp, values: &mut Vec<(&'a FieldName, &'a AlgebraicValue)>) -> Option<()> {
+        match op {
+            ColumnOp::Cmp {
+                op: OpQuery::Logic(OpLogic::Or),
+                lhs,
+                rhs,
+            } => {
+                collect(lhs, values)?;
+                collect(rhs, values)
+            }
+            ColumnOp::Cmp {
+                op: OpQuery::Cmp(OpCmp::Eq),
+                lhs,
+                rhs,
+            } => match (&**lhs, &**rhs) {
+                (ColumnOp::Field(FieldExpr::Name(name)), ColumnOp::Field(FieldExpr::Value(value))) => {
+                    values.push((name, value));
+                    Some(())
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    if !matches!(
+        op,
+        ColumnOp::Cmp {
+            op: OpQuery::Logic(OpLogic::Or),
+            ..
+        }
+    ) {
+        return None;
+    }
+    let mut values = Vec::new();
+    collect(op, &mut values)?;
+
+    let name = values[0].0;
+    if values.iter().any(|(x, _)| *x != name) {
+        return None;
+    }
+    let column = table.get_column_by_field(name)?;
+    let index = table
+        .indexes
+        .iter()
+        .find(|index| index.cols == NonEmpty::new(column.col_id))?;
+
+    Some(IndexArgument::In {
+        col_id: index.cols.head,
+        values: values.into_iter().map(|(_, x)| x.clone()).collect(),
+    })
+}
+
 /// Compiles a `SELECT ...` clause
-fn compile_select(table: From, project: Vec<Column>, selection: Option<Selection>) -> Result<QueryExpr, PlanError> {
+fn compile_select(
+    table: From,
+    project: Vec<Column>,
+    selection: Option<Selection>,
+    group_by: Option<GroupBy>,
+    order_by: Vec<SortKey>,
+    limit: Option<Limit>,
+) -> Result<QueryExpr, PlanError> {
     let mut not_found = Vec::with_capacity(project.len());
     let mut col_ids = Vec::new();
     let mut qualified_wildcards = Vec::new();
@@ -214,6 +352,7 @@ fn compile_select(table: From, project: Vec<Column>, selection: Option<Selection
                 Err(PlanError::UnknownField { field, tables: _ }) => not_found.push(field),
                 Err(err) => return Err(err),
             },
+            Column::Aggregate(x) => col_ids.push(x.name.into()),
             Column::QualifiedWildcard { table: name } => {
                 if let Some(t) = table.iter_tables().find(|x| x.table_name == name) {
                     for c in t.columns.iter() {
@@ -235,6 +374,20 @@ fn compile_select(table: From, project: Vec<Column>, selection: Option<Selection
         });
     }
 
+    // Index joins only yield the rows of the root table, so they can only be sorted by its fields.
+    let sorted_by_root = order_by.iter().all(|key| key.field.table() == table.root.table_name);
+
+    // A projection of only `root.*` over a chain of joins may be evaluated with index joins.
+    if group_by.is_none()
+        && sorted_by_root
+        && qualified_wildcards == [table.root.table_id]
+        && col_ids.len() == table.root.columns.len()
+    {
+        if let Some(q) = try_index_join_chain(&table, selection.as_ref())? {
+            return Ok(compile_order_by_and_limit(q, &table.root, &order_by, limit));
+        }
+    }
+
     let mut q = query(db_table_raw(
         ProductType::from(&table.root),
         table.root.table_name.clone(),
@@ -261,6 +414,20 @@ fn compile_select(table: From, project: Vec<Column>, selection: Option<Selection
     if let Some(filter) = selection {
         q = compile_where(q, &table, filter)?;
     }
+    // The rows are grouped after being filtered, and the groups before being projected.
+    if let Some(GroupBy {
+        fields,
+        aggregates,
+        having,
+    }) = group_by
+    {
+        q = q.with_aggregate(&fields, &aggregates);
+        if let Some(having) = having {
+            q = q.with_select(having.clause);
+        }
+        q = q.with_project(&col_ids, None);
+        return Ok(compile_order_by_and_limit(q, &table.root, &order_by, limit));
+    }
     // It is important to project at the end.
     // This is so joins and filters see fields that are not projected.
     // It is also important to identify a wildcard project of the form `table.*`.
@@ -271,9 +438,82 @@ fn compile_select(table: From, project: Vec<Column>, selection: Option<Selection
         None
     };
     q = q.with_project(&col_ids, qualified_wildcard);
-    q = try_index_join(q, &table);
+    if sorted_by_root {
+        q = try_index_join(q, &table);
+    }
 
-    Ok(q)
+    Ok(compile_order_by_and_limit(q, &table.root, &order_by, limit))
+}
+
+/// Sorts and limits the rows of `q` before they are projected,
+/// as the projection may drop the fields they are sorted by.
+fn compile_order_by_and_limit(
+    mut q: QueryExpr,
+    root: &TableSchema,
+    order_by: &[SortKey],
+    limit: Option<Limit>,
+) -> QueryExpr {
+    let project = match q.query.last() {
+        Some(Query::Project(..)) => q.query.pop(),
+        _ => None,
+    };
+    if !order_by.is_empty() && !try_index_order(&mut q, root, order_by) {
+        q = q.with_sort(order_by);
+    }
+    if let Some(limit) = limit {
+        q = q.with_limit(limit);
+    }
+    q.query.extend(project);
+    q
+}
+
+// Try to avoid sorting rows that are already in order.
+// This is the case when they are sorted in ascending order by a single field of the root table,
+// which has a btree index on that field, and they only come from an index scan on that field,
+// possibly followed by filters.
+// If there is no index scan yet, one over the whole table is added.
+//
+// Ex. SELECT * FROM Table WHERE Table.x > 5 ORDER BY Table.id
+// where `Table` has an index defined on `id`.
+fn try_index_order(q: &mut QueryExpr, root: &TableSchema, order_by: &[SortKey]) -> bool {
+    let [SortKey { field, asc: true }] = order_by else {
+        return false;
+    };
+    if field.table() != root.table_name || q.source.get_db_table().map(|t| t.table_id) != Some(root.table_id) {
+        return false;
+    }
+    let Some(IndexSchema {
+        cols: NonEmpty { head: col_id, tail },
+        index_type: IndexType::BTree,
+        ..
+    }) = root.get_index_by_field(field)
+    else {
+        return false;
+    };
+    if !tail.is_empty() {
+        return false;
+    }
+
+    let (scan, rest) = match q.query.split_first() {
+        Some((Query::IndexScan(scan), rest)) => (Some(scan.col_id), rest),
+        _ => (None, &q.query[..]),
+    };
+    if !rest.iter().all(|op| matches!(op, Query::Select(_))) {
+        return false;
+    }
+    match scan {
+        Some(scan) => scan == *col_id,
+        None => {
+            let scan = IndexScan {
+                table: root.into(),
+                col_id: *col_id,
+                lower_bound: Bound::Unbounded,
+                upper_bound: Bound::Unbounded,
+            };
+            q.query.insert(0, Query::IndexScan(scan));
+            true
+        }
+    }
 }
 
 // Try to turn an applicable join into an index join.
@@ -346,6 +586,119 @@ fn try_index_join(mut query: QueryExpr, table: &From) -> QueryExpr {
     }
 }
 
+// Try to turn a chain of (at least 2) joins into nested index joins.
+// An applicable chain is one where each table joins the table before it,
+// and every table but the last has an index on the field joining it to the next,
+// so that each table can be probed with the rows of the next one.
+// Every condition of the `WHERE` clause must refer to a single table,
+// and at least one of them to a table other than the root.
+//
+// Ex. SELECT A.* FROM A JOIN B ON A.id = B.a_id JOIN C ON B.id = C.b_id WHERE C.x = 1
+// where `A` and `B` have an index defined on `id`.
+//
+// The result is the root table joined via an index with the probe side `B`,
+// which is in turn joined via an index with the probe side `C`.
+fn try_index_join_chain(table: &From, selection: Option<&Selection>) -> Result<Option<QueryExpr>, PlanError> {
+    let Some(joins) = table.join.as_ref().filter(|joins| joins.len() >= 2) else {
+        return Ok(None);
+    };
+
+    let has_field = |schema: &TableSchema, field: &FieldName| {
+        field.table() == schema.table_name && schema.get_column_by_field(field).is_some()
+    };
+
+    let mut tables = vec![&table.root];
+    // The fields joining each table to the next, as `(field of the table, field of the next)`.
+    let mut links = Vec::with_capacity(joins.len());
+    for Join::Inner { rhs, on } in joins {
+        let prev = tables[tables.len() - 1];
+        if on.op != OpCmp::Eq {
+            return Ok(None);
+        }
+        let link = if has_field(prev, &on.lhs) && has_field(rhs, &on.rhs) {
+            (on.lhs.clone(), on.rhs.clone())
+        } else if has_field(prev, &on.rhs) && has_field(rhs, &on.lhs) {
+            (on.rhs.clone(), on.lhs.clone())
+        } else {
+            return Ok(None);
+        };
+        if prev.get_index_by_field(&link.0).is_none() {
+            return Ok(None);
+        }
+        tables.push(rhs);
+        links.push(link);
+    }
+
+    // Assign every condition of the `WHERE` clause to the table it refers to.
+    let mut filters = vec![vec![]; tables.len()];
+    for op in selection
+        .map(|filter| filter.clause.clone().to_vec())
+        .unwrap_or_default()
+    {
+        let mut fields = Vec::new();
+        collect_fields(&op, &mut fields);
+        let mut positions = fields
+            .iter()
+            .map(|field| tables.iter().position(|schema| has_field(schema, field)));
+        let Some(Some(pos)) = positions.next() else {
+            return Ok(None);
+        };
+        if !positions.all(|other| other == Some(pos)) {
+            return Ok(None);
+        }
+        filters[pos].push(op);
+    }
+    if filters[1..].iter().all(Vec::is_empty) {
+        return Ok(None);
+    }
+
+    // Build the chain from the last table back to the root.
+    let mut probe: Option<(QueryExpr, FieldName)> = None;
+    for (pos, (schema, filters)) in tables.into_iter().zip(filters).enumerate().rev() {
+        let mut q = query(db_table_raw(
+            ProductType::from(schema),
+            schema.table_name.clone(),
+            schema.table_id,
+            schema.table_type,
+            schema.table_access,
+        ));
+        if let Some((probe_side, probe_field)) = probe.take() {
+            let (index_field, _) = &links[pos];
+            let index = schema.get_index_by_field(index_field).unwrap();
+            q.query.push(Query::IndexJoin(IndexJoin {
+                probe_side,
+                probe_field,
+                index_header: schema.into(),
+                index_table: schema.table_id,
+                index_col: index.cols.head,
+            }));
+        }
+        // Filters following an index join are compiled to selects.
+        let from = From::new(schema.clone());
+        for clause in filters {
+            q = compile_where(q, &from, Selection { clause })?;
+        }
+        if pos > 0 {
+            probe = Some((q, links[pos - 1].1.clone()));
+        } else {
+            return Ok(Some(q));
+        }
+    }
+    unreachable!("the chain of joins has a root")
+}
+
+/// Collects the fields referred to by `op` into `fields`.
+fn collect_fields<'a>(op: &'a ColumnOp, fields: &mut Vec<&'a FieldName>) {
+    match op {
+        ColumnOp::Field(FieldExpr::Name(field)) => fields.push(field),
+        ColumnOp::Field(FieldExpr::Value(_) | FieldExpr::Param { .. }) => {}
+        ColumnOp::Cmp { lhs, rhs, .. } => {
+            collect_fields(lhs, fields);
+            collect_fields(rhs, fields);
+        }
+    }
+}
+
 /// Builds the schema description [DbTable] from the [TableSchema] and their list of columns
 fn compile_columns(table: &TableSchema, columns: Vec<FieldName>) -> DbTable {
     let mut new = Vec::with_capacity(columns.len());
@@ -454,7 +807,10 @@ fn compile_statement(statement: SqlAst) -> Result<CrudExpr, PlanError> {
             from,
             project,
             selection,
-        } => CrudExpr::Query(compile_select(from, project, selection)?),
+            group_by,
+            order_by,
+            limit,
+        } => CrudExpr::Query(compile_select(from, project, selection, group_by, order_by, limit)?),
         SqlAst::Insert { table, columns, values } => compile_insert(table, columns, values)?,
         SqlAst::Update {
             table,
@@ -473,6 +829,14 @@ fn compile_statement(statement: SqlAst) -> Result<CrudExpr, PlanError> {
             kind,
             table_access,
         } => compile_drop(name, kind, table_access)?,
+        SqlAst::Explain { statement } => match compile_statement(*statement)? {
+            CrudExpr::Query(query) => CrudExpr::Explain { query },
+            _ => {
+                return Err(PlanError::Unsupported {
+                    feature: "EXPLAIN of statements other than SELECT".into(),
+                })
+            }
+        },
     };
 
     Ok(q)
@@ -490,7 +854,7 @@ mod tests {
     };
     use spacetimedb_primitives::TableId;
     use spacetimedb_sats::AlgebraicType;
-    use spacetimedb_vm::expr::{IndexScan, JoinExpr, Query};
+    use spacetimedb_vm::expr::{Aggregate, AggregateExpr, AggregateFn, IndexScan, IndexSeek, JoinExpr, Query};
 
     use crate::db::{
         datastore::traits::{ColumnDef, IndexDef, TableDef},
@@ -562,6 +926,206 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn compile_group_by() -> ResultTest<()> {
+        let (db, _) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        // Create table [test] with index on [a]
+        let schema = &[("a", AlgebraicType::U64), ("b", AlgebraicType::I32)];
+        let indexes = &[(0, "a")];
+        create_table(&db, &mut tx, "test", schema, indexes)?;
+
+        // Compile query
+        let sql = "select b, sum(a) from test where a = 1 group by b having count(*) > 1";
+        let CrudExpr::Query(QueryExpr {
+            source: _,
+            query: mut ops,
+        }) = compile_sql(&db, &tx, sql)?.remove(0)
+        else {
+            panic!("Expected QueryExpr");
+        };
+
+        assert_eq!(4, ops.len());
+
+        // Assert the rows are filtered using the index before being grouped
+        let Query::IndexScan(_) = ops.remove(0) else {
+            panic!("Expected IndexScan");
+        };
+
+        // Assert the aggregates of the projection and of the filter are computed for each group
+        let Query::Aggregate(Aggregate { group_by, aggregates }) = ops.remove(0) else {
+            panic!("Expected Aggregate");
+        };
+        assert_eq!(group_by, vec![FieldName::named("test", "b")]);
+        assert_eq!(
+            aggregates,
+            vec![
+                AggregateExpr {
+                    func: AggregateFn::Sum,
+                    field: Some(FieldName::named("test", "a")),
+                    name: FieldName::named("test", "sum(a)"),
+                },
+                AggregateExpr {
+                    func: AggregateFn::Count,
+                    field: None,
+                    name: FieldName::named("test", "count(*)"),
+                },
+            ]
+        );
+
+        // Assert the groups are filtered by comparing with a value of the type of the aggregate
+        let Query::Select(ColumnOp::Cmp { op: _, lhs: _, rhs }) = ops.remove(0) else {
+            panic!("Expected Select");
+        };
+        assert_eq!(*rhs, ColumnOp::Field(FieldExpr::Value(AlgebraicValue::U64(1))));
+
+        // Assert only the selected columns are returned
+        let Query::Project(cols, None) = ops.remove(0) else {
+            panic!("Expected Project");
+        };
+        assert_eq!(
+            cols,
+            vec![
+                FieldExpr::Name(FieldName::named("test", "b")),
+                FieldExpr::Name(FieldName::named("test", "sum(a)")),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn compile_order_by_index() -> ResultTest<()> {
+        let (db, _) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        // Create table [test] with index on [a]
+        let schema = &[("a", AlgebraicType::U64), ("b", AlgebraicType::U64)];
+        let indexes = &[(0, "a")];
+        create_table(&db, &mut tx, "test", schema, indexes)?;
+
+        let compile = |sql: &str| {
+            let CrudExpr::Query(QueryExpr { source: _, query: ops }) = compile_sql(&db, &tx, sql).unwrap().remove(0)
+            else {
+                panic!("Expected QueryExpr");
+            };
+            ops
+        };
+
+        // Assert the index is scanned in order instead of sorting the rows
+        let mut ops = compile("select b from test where b > 1 order by a limit 2");
+        assert_eq!(4, ops.len());
+        let Query::IndexScan(IndexScan {
+            table: _,
+            col_id,
+            lower_bound: Bound::Unbounded,
+            upper_bound: Bound::Unbounded,
+        }) = ops.remove(0)
+        else {
+            panic!("Expected IndexScan");
+        };
+        assert_eq!(col_id, 0.into());
+        let Query::Select(_) = ops.remove(0) else {
+            panic!("Expected Select");
+        };
+        let Query::Limit(limit) = ops.remove(0) else {
+            panic!("Expected Limit");
+        };
+        assert_eq!(
+            limit,
+            Limit {
+                offset: 0,
+                limit: Some(2)
+            }
+        );
+
+        // Assert a range scan on the sort key is reused
+        let ops = compile("select * from test where a > 1 order by a");
+        assert!(matches!(ops[..], [Query::IndexScan(_)]), "{ops:?}");
+
+        // Assert the rows are sorted when no index matches the order
+        for sql in [
+            "select * from test order by b limit 2",
+            "select * from test order by a desc limit 2",
+            "select * from test order by a, b limit 2",
+        ] {
+            let mut ops = compile(sql);
+            let Query::Sort(sort) = ops.remove(0) else {
+                panic!("Expected Sort for `{sql}`");
+            };
+            assert_eq!(sort.top, Some(2), "{sql}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn compile_in_between_index() -> ResultTest<()> {
+        let (db, _) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        // Create table [test] with index on [a]
+        let schema = &[("a", AlgebraicType::U64), ("b", AlgebraicType::U64)];
+        let indexes = &[(0, "a")];
+        create_table(&db, &mut tx, "test", schema, indexes)?;
+
+        let compile = |sql: &str| {
+            let CrudExpr::Query(QueryExpr { source: _, query: ops }) = compile_sql(&db, &tx, sql).unwrap().remove(0)
+            else {
+                panic!("Expected QueryExpr");
+            };
+            ops
+        };
+        let range = |ops: &[Query]| match ops.first() {
+            Some(Query::IndexScan(IndexScan {
+                col_id,
+                lower_bound: Bound::Included(lower),
+                upper_bound: Bound::Included(upper),
+                ..
+            })) if *col_id == 0.into() => Some((lower.clone(), upper.clone())),
+            _ => None,
+        };
+
+        let seek = |ops: &[Query]| match ops {
+            [Query::IndexSeek(IndexSeek { col_id, values, .. })] if *col_id == 0.into() => Some(values.clone()),
+            _ => None,
+        };
+        let u64s = |values: &[u64]| values.iter().copied().map(AlgebraicValue::U64).collect::<Vec<_>>();
+
+        // Assert `IN` seeks each of its values once, rather than scanning the range they span
+        let ops = compile("select * from test where a in (5, 1, 3, 1)");
+        assert_eq!(seek(&ops), Some(u64s(&[1, 3, 5])), "{ops:?}");
+        let ops = compile("select * from test where a in (1, 1000000)");
+        assert_eq!(seek(&ops), Some(u64s(&[1, 1000000])), "{ops:?}");
+
+        // Assert `IN` of a single value is an index seek
+        let ops = compile("select * from test where a in (2)");
+        assert_eq!(ops.len(), 1, "{ops:?}");
+        assert_eq!(range(&ops), Some((AlgebraicValue::U64(2), AlgebraicValue::U64(2))));
+
+        // Assert `BETWEEN` is a range scan
+        let ops = compile("select * from test where a between 2 and 4");
+        assert_eq!(ops.len(), 1, "{ops:?}");
+        assert_eq!(range(&ops), Some((AlgebraicValue::U64(2), AlgebraicValue::U64(4))));
+
+        // Assert an inverted `BETWEEN` seeks nothing
+        let ops = compile("select * from test where a between 4 and 2");
+        assert_eq!(seek(&ops), Some(vec![]), "{ops:?}");
+        let ops = compile("select * from test where a >= 3 and a < 3");
+        assert_eq!(seek(&ops), Some(vec![]), "{ops:?}");
+
+        // Assert the negated predicates and the ones on a field without an index are filters
+        for sql in [
+            "select * from test where a not in (1, 2)",
+            "select * from test where a not between 2 and 4",
+            "select * from test where b in (1, 2)",
+            "select * from test where a = 1 or b = 2",
+        ] {
+            let ops = compile(sql);
+            assert!(matches!(ops[..], [Query::Select(_)]), "{sql}: {ops:?}");
+        }
+        Ok(())
+    }
+
     #[test]
     fn compile_index_eq() -> ResultTest<()> {
         let (db, _) = make_test_db()?;
@@ -734,6 +1298,47 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn compile_hash_index_range() -> ResultTest<()> {
+        let (db, _) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        // Create table [test] with a hash index on [b]
+        let table_id = create_table(&db, &mut tx, "test", &[("b", AlgebraicType::U64)], &[])?;
+        let index = IndexDef::new("b".into(), table_id, ColId(0), false).with_index_type(IndexType::Hash);
+        db.create_index(&mut tx, index)?;
+
+        let compile = |sql| -> ResultTest<Vec<Query>> {
+            let CrudExpr::Query(QueryExpr { source: _, query: ops }) = compile_sql(&db, &tx, sql)?.remove(0) else {
+                panic!("Expected QueryExpr");
+            };
+            Ok(ops)
+        };
+
+        // Assert index scan for equality
+        let ops = compile("select * from test where b = 2")?;
+        assert!(matches!(&ops[..], [Query::IndexScan(_)]));
+
+        // Assert no index scan for ranges, which a hash index can't answer
+        let ops = compile("select * from test where b > 2")?;
+        assert!(matches!(&ops[..], [Query::Select(_)]));
+        let ops = compile("select * from test where b between 2 and 3")?;
+        assert!(matches!(&ops[..], [Query::Select(_)]));
+
+        // Assert index seeks for each of several values
+        let ops = compile("select * from test where b = 2 or b = 3")?;
+        assert!(matches!(&ops[..], [Query::IndexSeek(_)]));
+        let ops = compile("select * from test where b in (2, 3)")?;
+        assert!(matches!(&ops[..], [Query::IndexSeek(_)]));
+
+        // Assert the rows are sorted, as a hash index doesn't keep them in order
+        let ops = compile("select * from test order by b")?;
+        assert!(matches!(&ops[..], [Query::Sort(_)]));
+        let ops = compile("select * from test where b = 2 order by b")?;
+        assert!(matches!(&ops[..], [Query::IndexScan(_), Query::Sort(_)]));
+        Ok(())
+    }
+
     #[test]
     fn compile_index_range_closed() -> ResultTest<()> {
         let (db, _) = make_test_db()?;
@@ -1237,4 +1842,161 @@ mod tests {
         };
         Ok(())
     }
+
+    #[test]
+    fn compile_index_join_chain() -> ResultTest<()> {
+        let (db, _) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        // Create table [lhs] with index on [b]
+        let schema = &[("a", AlgebraicType::U64), ("b", AlgebraicType::U64)];
+        let indexes = &[(1, "b")];
+        let lhs_id = create_table(&db, &mut tx, "lhs", schema, indexes)?;
+
+        // Create table [mid] with index on [c]
+        let schema = &[("b", AlgebraicType::U64), ("c", AlgebraicType::U64)];
+        let indexes = &[(1, "c")];
+        let mid_id = create_table(&db, &mut tx, "mid", schema, indexes)?;
+
+        // Create table [rhs] with no indexes
+        let schema = &[("c", AlgebraicType::U64), ("d", AlgebraicType::U64)];
+        let rhs_id = create_table(&db, &mut tx, "rhs", schema, &[])?;
+
+        // Should generate a chain of index joins since there are indexes on `lhs.b` and `mid.c`.
+        // Should push each condition into the probe side of its table.
+        let sql =
+            "select lhs.* from lhs join mid on lhs.b = mid.b join rhs on mid.c = rhs.c where rhs.d = 3 and mid.b > 2";
+        let exp = compile_sql(&db, &tx, sql)?.remove(0);
+
+        let CrudExpr::Query(QueryExpr {
+            source: SourceExpr::DbTable(DbTable { table_id, .. }),
+            query,
+            ..
+        }) = exp
+        else {
+            panic!("unexpected result from compilation: {:?}", exp);
+        };
+
+        assert_eq!(table_id, lhs_id);
+        assert_eq!(query.len(), 1);
+
+        let Query::IndexJoin(IndexJoin {
+            probe_side:
+                QueryExpr {
+                    source: SourceExpr::DbTable(DbTable { table_id, .. }),
+                    query: ref mid,
+                },
+            ref probe_field,
+            index_table,
+            index_col,
+            ..
+        }) = query[0]
+        else {
+            panic!("unexpected operator {:#?}", query[0]);
+        };
+
+        assert_eq!(table_id, mid_id);
+        assert_eq!(index_table, lhs_id);
+        assert_eq!(index_col, 1.into());
+        assert_eq!(probe_field, &FieldName::named("mid", "b"));
+
+        // The probe side [mid] is itself an index join, followed by its selection
+        assert_eq!(2, mid.len());
+
+        let Query::IndexJoin(IndexJoin {
+            probe_side:
+                QueryExpr {
+                    source: SourceExpr::DbTable(DbTable { table_id, .. }),
+                    query: ref rhs,
+                },
+            ref probe_field,
+            index_table,
+            index_col,
+            ..
+        }) = mid[0]
+        else {
+            panic!("unexpected operator {:#?}", mid[0]);
+        };
+
+        assert_eq!(table_id, rhs_id);
+        assert_eq!(index_table, mid_id);
+        assert_eq!(index_col, 1.into());
+        assert_eq!(probe_field, &FieldName::named("rhs", "c"));
+
+        let Query::Select(_) = mid[1] else {
+            panic!("unexpected operator {:#?}", mid[1]);
+        };
+
+        // The probe side [rhs] is a selection
+        assert_eq!(1, rhs.len());
+
+        let Query::Select(_) = rhs[0] else {
+            panic!("unexpected operator {:#?}", rhs[0]);
+        };
+
+        // Without an index on `mid.b`, it's a plain join
+        let sql = "select lhs.* from lhs join mid on lhs.b = mid.b join rhs on mid.b = rhs.c where rhs.d = 3";
+        let exp = compile_sql(&db, &tx, sql)?.remove(0);
+
+        let CrudExpr::Query(QueryExpr { query, .. }) = exp else {
+            panic!("unexpected result from compilation: {:?}", exp);
+        };
+
+        assert!(query.iter().any(|op| matches!(op, Query::JoinInner(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn compile_join_on_joined_tables() -> ResultTest<()> {
+        let (db, _) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        let schema = &[("a", AlgebraicType::U64), ("b", AlgebraicType::U64)];
+        create_table(&db, &mut tx, "lhs", schema, &[])?;
+        let schema = &[("b", AlgebraicType::U64), ("c", AlgebraicType::U64)];
+        create_table(&db, &mut tx, "mid", schema, &[])?;
+        let schema = &[("c", AlgebraicType::U64), ("d", AlgebraicType::U64)];
+        create_table(&db, &mut tx, "rhs", schema, &[])?;
+
+        // The fields of the joined table can come first
+        let sql = "select lhs.* from lhs join mid on mid.b = lhs.b";
+        let CrudExpr::Query(QueryExpr { query, .. }) = compile_sql(&db, &tx, sql)?.remove(0) else {
+            panic!("unexpected result from compilation");
+        };
+        let [Query::JoinInner(JoinExpr {
+            ref col_lhs,
+            ref col_rhs,
+            ..
+        })] = query[..]
+        else {
+            panic!("unexpected operators {:#?}", query);
+        };
+        assert_eq!(col_lhs, &FieldName::named("lhs", "b"));
+        assert_eq!(col_rhs, &FieldName::named("mid", "b"));
+
+        // A qualified field of a table that isn't in `FROM` doesn't fall back to a column of the same name
+        let err = compile_sql(&db, &tx, "select * from lhs where x.a = 1").unwrap_err();
+        assert!(
+            matches!(&err, DBError::Plan { error: PlanError::UnknownTable { table }, .. } if table == "x"),
+            "{err}"
+        );
+        let err = compile_sql(&db, &tx, "select * from lhs join mid on x.b = mid.b").unwrap_err();
+        assert!(
+            matches!(&err, DBError::Plan { error: PlanError::UnknownTable { table }, .. } if table == "x"),
+            "{err}"
+        );
+
+        // The `ON` clause can't refer to a table joined after it
+        let sql = "select lhs.* from lhs join mid on lhs.b = rhs.c join rhs on mid.c = rhs.c";
+        let err = compile_sql(&db, &tx, sql).unwrap_err();
+        assert!(
+            matches!(&err, DBError::Plan { error: PlanError::UnknownTable { table }, .. } if table == "rhs"),
+            "{err}"
+        );
+
+        // Nor compare two fields of the same side
+        let sql = "select lhs.* from lhs join mid on mid.b = mid.c";
+        assert!(compile_sql(&db, &tx, sql).is_err());
+        Ok(())
+    }
 }
@@ -1,15 +1,21 @@
+use anyhow::anyhow;
+use spacetimedb_lib::auth::StAccess;
+use spacetimedb_lib::error::AuthError;
 use spacetimedb_lib::identity::AuthCtx;
 use spacetimedb_lib::relation::MemTable;
 use spacetimedb_sats::{ProductType, ProductValue};
+use spacetimedb_vm::errors::ErrorVm;
 use spacetimedb_vm::eval::run_ast;
-use spacetimedb_vm::expr::{CodeResult, CrudExpr, Expr};
+use spacetimedb_vm::expr::{CodeResult, CrudExpr, Expr, QueryExpr};
 use tracing::info;
 
-use crate::database_instance_context_controller::DatabaseInstanceContextController;
+use crate::database_instance_context::DatabaseInstanceContext;
 use crate::db::datastore::locking_tx_datastore::MutTxId;
 use crate::db::relational_db::RelationalDB;
-use crate::error::{DBError, DatabaseError};
-use crate::sql::compiler::compile_sql;
+use crate::error::DBError;
+use crate::sql::compiler::compile_sql_with_auth;
+use crate::sql::explain::explain;
+use crate::sql::params::SqlParams;
 use crate::vm::DbProgram;
 
 pub struct StmtResult {
@@ -20,22 +26,21 @@ pub struct StmtResult {
 // TODO(cloutiertyler): we could do this the swift parsing way in which
 // we always generate a plan, but it may contain errors
 
-/// Run a `SQL` query/statement in the specified `database_instance_id`.
+/// Run a `SQL` query/statement in the database of `database_instance_context`,
+/// with its `$n` placeholders bound to `params`.
+///
+/// This waits for the transaction in progress, if there's one, so async callers should run it
+/// on a blocking thread.
 #[tracing::instrument(skip_all)]
 pub fn execute(
-    db_inst_ctx_controller: &DatabaseInstanceContextController,
-    database_instance_id: u64,
+    database_instance_context: &DatabaseInstanceContext,
     sql_text: String,
+    params: SqlParams,
     auth: AuthCtx,
 ) -> Result<Vec<MemTable>, DBError> {
     info!(sql = sql_text);
-    if let Some((database_instance_context, _)) = db_inst_ctx_controller.get(database_instance_id) {
-        database_instance_context
-            .relational_db
-            .with_auto_commit(|tx| run(&database_instance_context.relational_db, tx, &sql_text, auth))
-    } else {
-        Err(DatabaseError::NotFound(database_instance_id).into())
-    }
+    let db = &database_instance_context.relational_db;
+    db.with_auto_commit(|tx| run_with_params(db, tx, &sql_text, &params, auth))
 }
 
 fn collect_result(result: &mut Vec<MemTable>, r: CodeResult) -> Result<(), DBError> {
@@ -70,6 +75,8 @@ pub fn execute_single_sql(
 }
 
 /// Run the compiled `SQL` expression inside the `vm` created by [DbProgram]
+///
+/// `EXPLAIN` statements are answered with their plan, see [explain].
 #[tracing::instrument(skip_all)]
 pub fn execute_sql(
     db: &RelationalDB,
@@ -77,26 +84,95 @@ pub fn execute_sql(
     ast: Vec<CrudExpr>,
     auth: AuthCtx,
 ) -> Result<Vec<MemTable>, DBError> {
-    let total = ast.len();
+    let mut result = Vec::with_capacity(ast.len());
+    let mut block = Vec::new();
+    for x in ast {
+        match x {
+            CrudExpr::Explain { query } => {
+                run_block(db, tx, std::mem::take(&mut block), auth, &mut result)?;
+                check_explain_auth(&query, auth)?;
+                result.push(explain(db, tx, &query));
+            }
+            x => block.push(Expr::Crud(Box::new(x))),
+        }
+    }
+    run_block(db, tx, block, auth, &mut result)?;
+    Ok(result)
+}
 
+fn run_block(
+    db: &RelationalDB,
+    tx: &mut MutTxId,
+    block: Vec<Expr>,
+    auth: AuthCtx,
+    result: &mut Vec<MemTable>,
+) -> Result<(), DBError> {
+    if block.is_empty() {
+        return Ok(());
+    }
     let p = &mut DbProgram::new(db, tx, auth);
-    let q = Expr::Block(ast.into_iter().map(|x| Expr::Crud(Box::new(x))).collect());
+    collect_result(result, run_ast(p, Expr::Block(block)).into())
+}
 
-    let mut result = Vec::with_capacity(total);
-    collect_result(&mut result, run_ast(p, q).into())?;
-    Ok(result)
+/// The plan reveals the tables of the query, so it requires the same access as running it.
+fn check_explain_auth(query: &QueryExpr, auth: AuthCtx) -> Result<(), ErrorVm> {
+    if auth.owner == auth.caller {
+        return Ok(());
+    }
+    for table in query.sources() {
+        if table.table_access() == StAccess::Private {
+            return Err(AuthError::TablePrivate {
+                named: table.table_name().to_owned(),
+            }
+            .into());
+        }
+    }
+    Ok(())
 }
 
 /// Run the `SQL` string using the `auth` credentials
 #[tracing::instrument(skip_all)]
 pub fn run(db: &RelationalDB, tx: &mut MutTxId, sql_text: &str, auth: AuthCtx) -> Result<Vec<MemTable>, DBError> {
-    let ast = compile_sql(db, tx, sql_text)?;
+    run_with_params(db, tx, sql_text, &SqlParams::None, auth)
+}
+
+/// Run the `SQL` string using the `auth` credentials, with its `$n` placeholders bound to `params`
+#[tracing::instrument(skip_all)]
+pub fn run_with_params(
+    db: &RelationalDB,
+    tx: &mut MutTxId,
+    sql_text: &str,
+    params: &SqlParams,
+    auth: AuthCtx,
+) -> Result<Vec<MemTable>, DBError> {
+    let ast = compile_sql_with_auth(db, tx, sql_text, params, &auth)?;
+    execute_sql(db, tx, ast, auth)
+}
+
+/// Run the `SQL` string using the `auth` credentials, with its `$n` placeholders bound to `params`,
+/// refusing any statement that would modify the database.
+#[tracing::instrument(skip_all)]
+pub fn run_read_only(
+    db: &RelationalDB,
+    tx: &mut MutTxId,
+    sql_text: &str,
+    params: &SqlParams,
+    auth: AuthCtx,
+) -> Result<Vec<MemTable>, DBError> {
+    let ast = compile_sql_with_auth(db, tx, sql_text, params, &auth)?;
+    if !ast
+        .iter()
+        .all(|expr| matches!(expr, CrudExpr::Query { .. } | CrudExpr::Explain { .. }))
+    {
+        return Err(anyhow!("One-off queries are not allowed to modify the database").into());
+    }
     execute_sql(db, tx, ast, auth)
 }
 
 #[cfg(test)]
 pub(crate) mod tests {
     use super::*;
+    use crate::db::datastore::traits::{CheckDef, IndexDef};
     use crate::db::relational_db::tests_utils::make_test_db;
     use crate::db::relational_db::{ST_TABLES_ID, ST_TABLES_NAME};
     use crate::vm::tests::create_table_with_rows;
@@ -104,7 +180,8 @@ pub(crate) mod tests {
     use spacetimedb_lib::auth::{StAccess, StTableType};
     use spacetimedb_lib::error::ResultTest;
     use spacetimedb_lib::relation::{Header, RelValue};
-    use spacetimedb_sats::{product, AlgebraicType, ProductType};
+    use spacetimedb_lib::{bsatn, Identity};
+    use spacetimedb_sats::{product, AlgebraicType, AlgebraicValue, ProductType};
     use spacetimedb_vm::dsl::{mem_table, scalar};
     use spacetimedb_vm::eval::create_game_data;
     use tempdir::TempDir;
@@ -425,6 +502,427 @@ pub(crate) mod tests {
         Ok(())
     }
 
+    fn create_scores(db: &RelationalDB) -> ResultTest<()> {
+        let mut tx = db.begin_tx();
+        let head = ProductType::from([
+            ("id", AlgebraicType::U64),
+            ("zone", AlgebraicType::I32),
+            ("score", AlgebraicType::I32),
+        ]);
+        let rows = [
+            product!(1u64, 1, 10),
+            product!(2u64, 1, 20),
+            product!(3u64, 2, 30),
+            product!(4u64, 2, 40),
+            product!(5u64, 3, 50),
+        ];
+        create_table_with_rows(db, &mut tx, "player", head, &rows)?;
+        db.commit_tx(tx)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_aggregates() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        create_scores(&db)?;
+        let mut tx = db.begin_tx();
+
+        let result = &run_for_testing(
+            &db,
+            &mut tx,
+            "SELECT COUNT(*), SUM(score), MIN(score), MAX(score), AVG(score) FROM player",
+        )?[0];
+
+        let head = ProductType::from([
+            ("count(*)", AlgebraicType::U64),
+            ("sum(score)", AlgebraicType::I64),
+            ("min(score)", AlgebraicType::option(AlgebraicType::I32)),
+            ("max(score)", AlgebraicType::option(AlgebraicType::I32)),
+            ("avg(score)", AlgebraicType::option(AlgebraicType::F64)),
+        ]);
+        let some = AlgebraicValue::OptionSome;
+        let input = mem_table(
+            head,
+            [product!(
+                5u64,
+                150i64,
+                some(10.into()),
+                some(50.into()),
+                some(30.0f64.into())
+            )],
+        );
+        assert_eq!(
+            input.as_without_table_name(),
+            result.as_without_table_name(),
+            "Aggregates"
+        );
+
+        // Counting no rows yields a single row...
+        let result = &run_for_testing(&db, &mut tx, "SELECT COUNT(*) AS total FROM player WHERE zone = 4")?[0];
+        let input = mem_table(ProductType::from([("total", AlgebraicType::U64)]), [product!(0u64)]);
+        assert_eq!(
+            input.as_without_table_name(),
+            result.as_without_table_name(),
+            "Empty count"
+        );
+
+        // ...with `NULL` for the aggregates undefined over no rows, like the minimum
+        let result = &run_for_testing(
+            &db,
+            &mut tx,
+            "SELECT COUNT(*), MIN(score), MAX(score), AVG(score) FROM player WHERE zone = 4",
+        )?[0];
+        let head = ProductType::from([
+            ("count(*)", AlgebraicType::U64),
+            ("min(score)", AlgebraicType::option(AlgebraicType::I32)),
+            ("max(score)", AlgebraicType::option(AlgebraicType::I32)),
+            ("avg(score)", AlgebraicType::option(AlgebraicType::F64)),
+        ]);
+        let null = AlgebraicValue::OptionNone;
+        let input = mem_table(head, [product!(0u64, null(), null(), null())]);
+        assert_eq!(
+            input.as_without_table_name(),
+            result.as_without_table_name(),
+            "Empty aggregates"
+        );
+
+        // Only numbers can be summed
+        assert!(run_for_testing(&db, &mut tx, "SELECT SUM(table_name) FROM st_table").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_by() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        create_scores(&db)?;
+        let mut tx = db.begin_tx();
+
+        let result = &run_for_testing(
+            &db,
+            &mut tx,
+            "SELECT zone, COUNT(*) AS players, SUM(score) FROM player WHERE id > 1 GROUP BY zone",
+        )?[0];
+
+        let head = ProductType::from([
+            ("zone", AlgebraicType::I32),
+            ("players", AlgebraicType::U64),
+            ("sum(score)", AlgebraicType::I64),
+        ]);
+        let rows = [
+            product!(1, 1u64, 20i64),
+            product!(2, 2u64, 70i64),
+            product!(3, 1u64, 50i64),
+        ];
+        let input = mem_table(head.clone(), rows);
+        assert_eq!(
+            input.as_without_table_name(),
+            result.as_without_table_name(),
+            "Group by"
+        );
+
+        // Filter the groups, including by an aggregate that is not selected
+        let result = &run_for_testing(
+            &db,
+            &mut tx,
+            "SELECT zone, COUNT(*) AS players, SUM(score) FROM player GROUP BY zone HAVING MAX(score) >= 20 AND zone < 3",
+        )?[0];
+
+        let rows = [product!(1, 2u64, 30i64), product!(2, 2u64, 70i64)];
+        let input = mem_table(head, rows);
+        assert_eq!(input.as_without_table_name(), result.as_without_table_name(), "Having");
+
+        // Each group yields a single row, so only the grouped fields can be selected
+        assert!(run_for_testing(&db, &mut tx, "SELECT id, COUNT(*) FROM player GROUP BY zone").is_err());
+        assert!(run_for_testing(&db, &mut tx, "SELECT * FROM player GROUP BY zone").is_err());
+        assert!(run_for_testing(&db, &mut tx, "SELECT zone FROM player GROUP BY zone HAVING id > 1").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_order_by_limit() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        create_scores(&db)?;
+        let mut tx = db.begin_tx();
+
+        let ids = |tx: &mut MutTxId, sql: &str| -> ResultTest<Vec<ProductValue>> {
+            let result = run_for_testing(&db, tx, sql)?.remove(0);
+            Ok(result.data.into_iter().map(|row| row.data).collect())
+        };
+
+        // Sorting by a field that is not selected
+        assert_eq!(
+            ids(&mut tx, "SELECT id FROM player ORDER BY score DESC LIMIT 2")?,
+            vec![product!(5u64), product!(4u64)],
+            "Top scores"
+        );
+        assert_eq!(
+            ids(&mut tx, "SELECT id FROM player ORDER BY zone DESC, id LIMIT 2 OFFSET 1")?,
+            vec![product!(3u64), product!(4u64)],
+            "Offset"
+        );
+        assert_eq!(
+            ids(
+                &mut tx,
+                "SELECT id FROM player WHERE zone < 3 ORDER BY player.zone, score DESC"
+            )?,
+            vec![product!(2u64), product!(1u64), product!(4u64), product!(3u64)],
+            "Filter and sort"
+        );
+        assert!(ids(&mut tx, "SELECT id FROM player LIMIT 0")?.is_empty());
+        assert!(ids(&mut tx, "SELECT id FROM player OFFSET 5")?.is_empty());
+
+        // Groups are sorted by aggregates, named or not
+        let result = &run_for_testing(
+            &db,
+            &mut tx,
+            "SELECT zone, COUNT(*) AS players FROM player GROUP BY zone ORDER BY players, SUM(score) DESC LIMIT 2",
+        )?[0];
+        let head = ProductType::from([("zone", AlgebraicType::I32), ("players", AlgebraicType::U64)]);
+        let input = mem_table(head, [product!(3, 1u64), product!(2, 2u64)]);
+        assert_eq!(
+            input.as_without_table_name(),
+            result.as_without_table_name(),
+            "Sorted groups"
+        );
+
+        assert!(run_for_testing(&db, &mut tx, "SELECT zone FROM player GROUP BY zone ORDER BY score").is_err());
+        assert!(run_for_testing(&db, &mut tx, "SELECT * FROM player ORDER BY COUNT(*)").is_err());
+        assert!(run_for_testing(&db, &mut tx, "SELECT * FROM player LIMIT -1").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_explain() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        create_scores(&db)?;
+        let mut tx = db.begin_tx();
+
+        let plan = |tx: &mut MutTxId, sql: &str| -> ResultTest<Vec<String>> {
+            let result = run_for_testing(&db, tx, sql)?.remove(0);
+            Ok(result
+                .data
+                .into_iter()
+                .map(|row| row.data.elements[0].as_string().unwrap().clone())
+                .collect())
+        };
+
+        assert_eq!(
+            plan(
+                &mut tx,
+                "EXPLAIN SELECT id FROM player WHERE zone > 1 ORDER BY score DESC LIMIT 2"
+            )?,
+            vec![
+                "Project: player.id  (rows=2)",
+                "  -> Limit: 2 offset 0  (rows=2)",
+                "      -> Sort: player.score desc top 2  (rows=2)",
+                "          -> Filter: player.zone > 1  (rows=2)",
+                "              -> Seq Scan on player  (rows=5)",
+            ]
+        );
+
+        let lines = plan(&mut tx, "EXPLAIN SELECT * FROM st_table WHERE table_id = 0")?;
+        assert!(
+            lines
+                .iter()
+                .any(|line| line
+                    .contains("Index Scan on st_table using st_table.table_id: st_table.table_id = 0  (rows=1)")),
+            "{lines:?}"
+        );
+        let lines = plan(&mut tx, "EXPLAIN SELECT * FROM st_table WHERE table_id IN (2, 0)")?;
+        assert!(
+            lines.iter().any(
+                |line| line.contains("Index Seek on st_table using st_table.table_id: st_table.table_id in (0, 2)")
+            ),
+            "{lines:?}"
+        );
+
+        // The plan of a query is returned in the place of its result
+        let result = run_for_testing(&db, &mut tx, "EXPLAIN SELECT * FROM player; SELECT * FROM player")?;
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].data.len(), 1);
+        assert_eq!(result[1].data.len(), 5);
+
+        assert!(run_for_testing(&db, &mut tx, "EXPLAIN DELETE FROM player").is_err());
+        assert!(run_for_testing(&db, &mut tx, "EXPLAIN ANALYZE SELECT * FROM player").is_err());
+
+        // Explaining a query requires the access needed to run it
+        run_for_testing(&db, &mut tx, "CREATE TABLE _secret (id BIGINT UNSIGNED)")?;
+        let auth = AuthCtx::new(Identity::__dummy(), Identity::from_byte_array([1u8; 32]));
+        assert!(run(&db, &mut tx, "EXPLAIN SELECT * FROM _secret", auth).is_err());
+        assert!(run_for_testing(&db, &mut tx, "EXPLAIN SELECT * FROM _secret").is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_predicates() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+        let head = ProductType::from([
+            ("id", AlgebraicType::U64),
+            ("name", AlgebraicType::String),
+            ("nickname", AlgebraicType::option(AlgebraicType::String)),
+        ]);
+        let nick = |x: &str| AlgebraicValue::OptionSome(AlgebraicValue::String(x.into()));
+        let rows = [
+            product!(1u64, "Alice", nick("Al")),
+            product!(2u64, "Bob", AlgebraicValue::OptionNone()),
+            product!(3u64, "Carol", nick("Caz")),
+            product!(4u64, "alfred", AlgebraicValue::OptionNone()),
+        ];
+        create_table_with_rows(&db, &mut tx, "people", head, &rows)?;
+
+        let ids = |tx: &mut MutTxId, filter: &str| -> ResultTest<Vec<u64>> {
+            let sql = format!("SELECT id FROM people WHERE {filter}");
+            let result = run_for_testing(&db, tx, &sql)?.remove(0);
+            let mut ids = result
+                .data
+                .into_iter()
+                .map(|row| *row.data.elements[0].as_u64().unwrap())
+                .collect::<Vec<_>>();
+            ids.sort();
+            Ok(ids)
+        };
+
+        assert_eq!(ids(&mut tx, "id IN (1, 3)")?, [1, 3]);
+        assert_eq!(ids(&mut tx, "id NOT IN (1, 3)")?, [2, 4]);
+        assert_eq!(ids(&mut tx, "id BETWEEN 2 AND 3")?, [2, 3]);
+        assert_eq!(ids(&mut tx, "id NOT BETWEEN 2 AND 3")?, [1, 4]);
+        assert_eq!(ids(&mut tx, "name LIKE 'A%'")?, [1]);
+        assert_eq!(ids(&mut tx, "name LIKE '_ob'")?, [2]);
+        assert_eq!(ids(&mut tx, "name NOT LIKE '%o%'")?, [1, 4]);
+        assert_eq!(ids(&mut tx, "nickname IS NULL")?, [2, 4]);
+        assert_eq!(ids(&mut tx, "nickname IS NOT NULL AND id > 1")?, [3]);
+        assert_eq!(ids(&mut tx, "(id IN (1, 2) OR name LIKE 'C%')")?, [1, 2, 3]);
+
+        let result = run_with_params(
+            &db,
+            &mut tx,
+            "SELECT id FROM people WHERE id IN ($1, $2) AND name LIKE $3",
+            &SqlParams::Json(serde_json::from_str(r#"[2, 4, "B%"]"#)?),
+            AuthCtx::for_testing(),
+        )?;
+        assert_eq!(result[0].data.len(), 1, "Bound params");
+
+        // On an indexed field, like `st_table.table_id`
+        let table_ids = |tx: &mut MutTxId, filter: &str| -> ResultTest<Vec<u32>> {
+            let sql = format!("SELECT table_id FROM st_table WHERE {filter}");
+            let result = run_for_testing(&db, tx, &sql)?.remove(0);
+            let mut ids = result
+                .data
+                .into_iter()
+                .map(|row| *row.data.elements[0].as_u32().unwrap())
+                .collect::<Vec<_>>();
+            ids.sort();
+            Ok(ids)
+        };
+        assert_eq!(table_ids(&mut tx, "table_id IN (2, 0, 2, 1000000)")?, [0, 2]);
+        assert_eq!(table_ids(&mut tx, "table_id BETWEEN 1 AND 2")?, [1, 2]);
+        assert!(table_ids(&mut tx, "table_id BETWEEN 2 AND 1")?.is_empty());
+
+        assert!(ids(&mut tx, "id LIKE 'A%'").is_err(), "LIKE on a number");
+        assert!(ids(&mut tx, "name IS NULL").is_err(), "IS NULL on a non-optional field");
+        assert!(ids(&mut tx, "1 IN (id)").is_err(), "IN on a value");
+        assert!(ids(&mut tx, "name LIKE 'a!%' ESCAPE '!'").is_err(), "Custom escape");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_params() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        create_scores(&db)?;
+        let mut tx = db.begin_tx();
+
+        let ids = |tx: &mut MutTxId, sql: &str, params: SqlParams| -> ResultTest<Vec<ProductValue>> {
+            let result = run_with_params(&db, tx, sql, &params, AuthCtx::for_testing())?.remove(0);
+            Ok(result.data.into_iter().map(|row| row.data).collect())
+        };
+        let json = |values: &str| SqlParams::Json(serde_json::from_str(values).unwrap());
+
+        let sql = "SELECT id FROM player WHERE zone = $1 AND score > $2";
+        assert_eq!(ids(&mut tx, sql, json("[2, 30]"))?, vec![product!(4u64)], "JSON params");
+        let bsatn = SqlParams::Bsatn(vec![bsatn::to_vec(&1i32).unwrap(), bsatn::to_vec(&10i32).unwrap()]);
+        assert_eq!(ids(&mut tx, sql, bsatn)?, vec![product!(2u64)], "BSATN params");
+
+        // The values are never parsed as SQL
+        run_with_params(
+            &db,
+            &mut tx,
+            "INSERT INTO player (id, zone, score) VALUES ($1, $2, $3)",
+            &json("[6, 4, 60]"),
+            AuthCtx::for_testing(),
+        )?;
+        assert_eq!(
+            ids(&mut tx, "SELECT id FROM player WHERE score = $1", json("[60]"))?,
+            vec![product!(6u64)],
+            "Inserted with params"
+        );
+
+        // The statement is parsed once for all the values it is run with
+        assert_eq!(db.statement_cache().len(), 3);
+
+        assert!(ids(&mut tx, sql, json("[2]")).is_err(), "Missing param");
+        assert!(ids(&mut tx, sql, json(r#"["2", 30]"#)).is_err(), "Mismatched type");
+        assert!(ids(&mut tx, sql, json("[4294967296, 30]")).is_err(), "Out of range");
+        assert!(ids(&mut tx, sql, SqlParams::Bsatn(vec![vec![1], vec![]])).is_err());
+        assert!(ids(&mut tx, "SELECT id FROM player WHERE $1 = $2", json("[1, 1]")).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_cache() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        create_scores(&db)?;
+
+        let plan = |tx: &mut MutTxId| -> ResultTest<String> {
+            let result = run_for_testing(&db, tx, "EXPLAIN SELECT id FROM player WHERE zone = 2")?.remove(0);
+            let lines = result
+                .data
+                .into_iter()
+                .map(|row| row.data.elements[0].as_string().unwrap().clone());
+            Ok(lines.collect::<Vec<_>>().join("\n"))
+        };
+
+        // The plan is compiled once for all the runs of the statement
+        let mut tx = db.begin_tx();
+        assert!(plan(&mut tx)?.contains("Seq Scan on player"));
+        assert!(plan(&mut tx)?.contains("Seq Scan on player"));
+        assert_eq!(db.statement_cache().plans_len(), 1);
+        db.commit_tx(tx)?;
+
+        // A transaction changing the schema compiles its own plans
+        let mut tx = db.begin_tx();
+        let table_id = db.table_id_from_name(&tx, "player")?.unwrap();
+        db.create_index(&mut tx, IndexDef::new("zone_idx".into(), table_id, 1.into(), false))?;
+        assert!(plan(&mut tx)?.contains("Index Scan on player"));
+        assert_eq!(db.statement_cache().plans_len(), 1);
+        db.commit_tx(tx)?;
+
+        // And the plans compiled against the previous schema are dropped once it commits
+        let mut tx = db.begin_tx();
+        assert!(plan(&mut tx)?.contains("Index Scan on player"));
+        assert_eq!(db.statement_cache().plans_len(), 1);
+
+        // A plan is compiled once no matter the values bound to its placeholders
+        let ids = |tx: &mut MutTxId, zone: i32| -> ResultTest<Vec<ProductValue>> {
+            let sql = "SELECT id FROM player WHERE zone = $1";
+            let params = SqlParams::Json(vec![zone.into()]);
+            let result = run_with_params(&db, tx, sql, &params, AuthCtx::for_testing())?.remove(0);
+            Ok(result.data.into_iter().map(|row| row.data).collect())
+        };
+        assert_eq!(ids(&mut tx, 1)?, vec![product!(1u64), product!(2u64)]);
+        assert_eq!(ids(&mut tx, 3)?, vec![product!(5u64)]);
+        assert_eq!(db.statement_cache().plans_len(), 2);
+        db.rollback_tx(tx);
+
+        Ok(())
+    }
+
     #[test]
     fn test_insert() -> ResultTest<()> {
         let (db, mut input, _tmp_dir) = create_data(1)?;
@@ -671,6 +1169,104 @@ pub(crate) mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_row_policy() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+        let identity_ty = AlgebraicType::product([("__identity_bytes", AlgebraicType::bytes())]);
+        let head = ProductType::from([("id", AlgebraicType::U64), ("owner", identity_ty)]);
+        let (alice, bob) = (
+            Identity::from_byte_array([1u8; 32]),
+            Identity::from_byte_array([2u8; 32]),
+        );
+        let owner = |x: Identity| AlgebraicValue::product(vec![AlgebraicValue::Bytes(x.to_vec())]);
+        let rows = [
+            product!(1u64, owner(alice)),
+            product!(2u64, owner(bob)),
+            product!(3u64, owner(alice)),
+        ];
+        let table_id = create_table_with_rows(&db, &mut tx, "message", head, &rows)?;
+        db.set_row_policy(&mut tx, table_id, Some("owner = :sender".into()))?;
+
+        let ids = |tx: &mut MutTxId, sql: &str, caller: Identity| -> ResultTest<Vec<u64>> {
+            let result = run(&db, tx, sql, AuthCtx::new(Identity::__dummy(), caller))?.remove(0);
+            let mut ids = result
+                .data
+                .into_iter()
+                .map(|row| *row.data.elements[0].as_u64().unwrap())
+                .collect::<Vec<_>>();
+            ids.sort();
+            Ok(ids)
+        };
+
+        assert_eq!(ids(&mut tx, "SELECT id FROM message", alice)?, [1, 3]);
+        assert_eq!(ids(&mut tx, "SELECT id FROM message WHERE id > 1", bob)?, [2]);
+        assert_eq!(
+            ids(&mut tx, "SELECT id FROM message", Identity::__dummy())?,
+            [1, 2, 3],
+            "Owner"
+        );
+
+        // As do one-off queries by anyone but the owner.
+        let auth = AuthCtx::new(Identity::__dummy(), bob);
+        let result = run_read_only(&db, &mut tx, "SELECT id FROM message", &SqlParams::None, auth)?.remove(0);
+        assert_eq!(result.data.len(), 1);
+        assert_eq!(result.data[0].data.elements[0], AlgebraicValue::U64(2));
+        let sql = "DELETE FROM message";
+        assert!(run_read_only(&db, &mut tx, sql, &SqlParams::None, auth).is_err());
+
+        // The policy shows up in the plan
+        let auth = AuthCtx::new(Identity::__dummy(), bob);
+        let plan = run(&db, &mut tx, "EXPLAIN SELECT id FROM message", auth)?.remove(0);
+        let lines = plan
+            .data
+            .into_iter()
+            .map(|row| row.data.elements[0].as_string().unwrap().clone())
+            .collect::<Vec<_>>();
+        assert!(
+            lines.iter().any(|line| line.contains("Filter: message.owner == ")),
+            "{lines:?}"
+        );
+
+        db.set_row_policy(&mut tx, table_id, Some("id = :sender".into()))?;
+        assert!(ids(&mut tx, "SELECT id FROM message", alice).is_err(), "Invalid policy");
+
+        db.set_row_policy(&mut tx, table_id, None)?;
+        assert_eq!(ids(&mut tx, "SELECT id FROM message", bob)?, [1, 2, 3], "No policy");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_constraint() -> ResultTest<()> {
+        let (db, _input, _tmp_dir) = create_data(1)?;
+        let mut tx = db.begin_tx();
+        let table_id = db.table_id_from_name(&tx, "inventory")?.unwrap();
+        let check = CheckDef {
+            constraint_name: "inventory_check_0".into(),
+            table_id,
+            expr: "inventory_id < 10".into(),
+        };
+        db.create_check(&mut tx, check)?;
+
+        let sql = "INSERT INTO inventory (inventory_id, name) VALUES (2, 'x')";
+        run_for_testing(&db, &mut tx, sql)?;
+        let sql = "INSERT INTO inventory (inventory_id, name) VALUES (10, 'y')";
+        assert!(run_for_testing(&db, &mut tx, sql).is_err());
+        db.commit_tx(tx)?;
+
+        let mut tx = db.begin_tx();
+        let sql = "UPDATE inventory SET inventory_id = 20 WHERE inventory_id = 2";
+        assert!(run_for_testing(&db, &mut tx, sql).is_err());
+        db.rollback_tx(tx);
+
+        let mut tx = db.begin_tx();
+        let result = run_for_testing(&db, &mut tx, "SELECT inventory_id FROM inventory")?;
+        assert_eq!(result[0].data.len(), 2);
+
+        Ok(())
+    }
+
     #[test]
     fn test_big_sql() -> ResultTest<()> {
         let (db, _input, _tmp_dir) = create_data(1)?;
@@ -0,0 +1,286 @@
+//! Renders the plan chosen for a [QueryExpr], for `EXPLAIN`.
+use std::ops::Bound;
+
+use spacetimedb_lib::operator::{OpCmp, OpLogic, OpQuery};
+use spacetimedb_lib::relation::{FieldExpr, MemTable};
+use spacetimedb_sats::{product, AlgebraicType, AlgebraicValue, ProductType};
+use spacetimedb_vm::dsl::mem_table;
+use spacetimedb_vm::expr::{ColumnOp, IndexScan, IndexSeek, JoinExpr, Query, QueryExpr, SourceExpr};
+
+use crate::db::datastore::locking_tx_datastore::MutTxId;
+use crate::db::relational_db::RelationalDB;
+
+// The selectivities used to estimate how many rows pass a predicate,
+// in absence of statistics about the values of the columns.
+// They are the defaults used by PostgreSQL.
+const EQ_SELECTIVITY: f64 = 0.005;
+const RANGE_SELECTIVITY: f64 = 1.0 / 3.0;
+const LIKE_SELECTIVITY: f64 = 0.005;
+const DEFAULT_SELECTIVITY: f64 = 0.5;
+/// The estimated number of input rows per group of a `GROUP BY`.
+const ROWS_PER_GROUP: u64 = 10;
+
+/// A step of the plan, with the estimated number of rows it yields.
+struct PlanNode {
+    label: String,
+    rows: u64,
+    children: Vec<PlanNode>,
+}
+
+impl PlanNode {
+    fn leaf(label: String, rows: u64) -> Self {
+        Self {
+            label,
+            rows,
+            children: Vec::new(),
+        }
+    }
+
+    fn wrap(self, label: String, rows: u64) -> Self {
+        Self {
+            label,
+            rows,
+            children: vec![self],
+        }
+    }
+
+    fn render(&self, depth: usize, lines: &mut Vec<String>) {
+        let indent = if depth == 0 {
+            String::new()
+        } else {
+            format!("{}-> ", " ".repeat(4 * depth - 2))
+        };
+        lines.push(format!("{indent}{}  (rows={})", self.label, self.rows));
+        for child in &self.children {
+            child.render(depth + 1, lines);
+        }
+    }
+}
+
+/// Describes how `query` is evaluated as a table with a line per step of the plan,
+/// each one indented under the step that consumes its rows.
+///
+/// The number of rows of each step is estimated from the sizes of the tables at `tx`.
+pub fn explain(db: &RelationalDB, tx: &MutTxId, query: &QueryExpr) -> MemTable {
+    let mut lines = Vec::new();
+    plan(db, tx, query).render(0, &mut lines);
+
+    let head = ProductType::from([("plan", AlgebraicType::String)]);
+    mem_table(head, lines.into_iter().map(|line| product!(line)))
+}
+
+/// Mirrors the evaluation of `query` by [crate::vm::build_query].
+fn plan(db: &RelationalDB, tx: &MutTxId, query: &QueryExpr) -> PlanNode {
+    let (mut node, db_table) = match &query.source {
+        SourceExpr::DbTable(table) => {
+            let rows = db.table_row_count(tx, table.table_id).unwrap_or_default();
+            (
+                PlanNode::leaf(format!("Seq Scan on {}", table.head.table_name), rows),
+                true,
+            )
+        }
+        SourceExpr::MemTable(table) => {
+            let rows = table.data.len() as u64;
+            (PlanNode::leaf(format!("Values {}", table.head.table_name), rows), false)
+        }
+    };
+
+    for op in &query.query {
+        node = match op {
+            // The index replaces the scan of the table
+            Query::IndexScan(scan) if db_table => {
+                let col = &scan.table.head.fields[scan.col_id.idx()].field;
+                let mut label = format!("Index Scan on {} using {col}", scan.table.head.table_name);
+                if let Some(cond) = index_cond(scan) {
+                    label = format!("{label}: {cond}");
+                }
+                let rows = estimate(node.rows, index_selectivity(scan));
+                PlanNode::leaf(label, rows)
+            }
+            Query::IndexScan(scan) => {
+                let cmp: ColumnOp = scan.clone().into();
+                let rows = estimate(node.rows, selectivity(&cmp));
+                node.wrap(format!("Filter: {cmp}"), rows)
+            }
+            // The index is looked up once per value, instead of scanning the table
+            Query::IndexSeek(seek) if db_table => {
+                let col = &seek.table.head.fields[seek.col_id.idx()].field;
+                let label = format!(
+                    "Index Seek on {} using {col}: {}",
+                    seek.table.head.table_name,
+                    index_seek_cond(seek)
+                );
+                let rows = match seek.values.len() {
+                    0 => 0,
+                    n => estimate(node.rows, EQ_SELECTIVITY * n as f64),
+                };
+                PlanNode::leaf(label, rows)
+            }
+            Query::IndexSeek(seek) => {
+                let cmp: ColumnOp = seek.clone().into();
+                let rows = estimate(node.rows, selectivity(&cmp));
+                node.wrap(format!("Filter: {cmp}"), rows)
+            }
+            // The rows of the probe side are looked up in the index, instead of scanning the table
+            Query::IndexJoin(join) if db_table => {
+                let probe = plan(db, tx, &join.probe_side);
+                let col = &join.index_header.fields[join.index_col.idx()].field;
+                let label = format!(
+                    "Index Semi Join on {} using {col} = {}",
+                    join.index_header.table_name, join.probe_field
+                );
+                let rows = probe.rows.min(node.rows);
+                probe.wrap(label, rows)
+            }
+            Query::IndexJoin(join) => {
+                let join: JoinExpr = join.clone().into();
+                let rhs = plan(db, tx, &join.rhs);
+                let label = format!("Hash Semi Join: {} = {}", join.col_lhs, join.col_rhs);
+                let rows = node.rows.min(rhs.rows);
+                PlanNode {
+                    label,
+                    rows,
+                    children: vec![node, rhs],
+                }
+            }
+            Query::JoinInner(join) => {
+                let rhs = plan(db, tx, &join.rhs);
+                let label = format!("Hash Join: {} = {}", join.col_lhs, join.col_rhs);
+                // Assumes the join is on a key of one of the sides
+                let rows = node.rows.max(rhs.rows);
+                PlanNode {
+                    label,
+                    rows,
+                    children: vec![node, rhs],
+                }
+            }
+            Query::Select(cmp) => {
+                let rows = estimate(node.rows, selectivity(cmp));
+                node.wrap(format!("Filter: {cmp}"), rows)
+            }
+            Query::Project(cols, _) if cols.is_empty() => node,
+            Query::Project(cols, _) => {
+                let cols = cols.iter().map(ToString::to_string).collect::<Vec<_>>();
+                let rows = node.rows;
+                node.wrap(format!("Project: {}", cols.join(", ")), rows)
+            }
+            Query::Aggregate(aggregate) => {
+                let funcs = aggregate
+                    .aggregates
+                    .iter()
+                    .map(|x| match &x.field {
+                        Some(field) => format!("{}({field})", x.func),
+                        None => format!("{}(*)", x.func),
+                    })
+                    .collect::<Vec<_>>();
+                let mut label = format!("Aggregate: {}", funcs.join(", "));
+                let rows = if aggregate.group_by.is_empty() {
+                    1
+                } else {
+                    let fields = aggregate.group_by.iter().map(ToString::to_string).collect::<Vec<_>>();
+                    label = format!("{label} group by {}", fields.join(", "));
+                    (node.rows + ROWS_PER_GROUP - 1) / ROWS_PER_GROUP
+                };
+                node.wrap(label, rows)
+            }
+            Query::Sort(sort) => {
+                let keys = sort
+                    .keys
+                    .iter()
+                    .map(|key| format!("{} {}", key.field, if key.asc { "asc" } else { "desc" }))
+                    .collect::<Vec<_>>();
+                let mut label = format!("Sort: {}", keys.join(", "));
+                let mut rows = node.rows;
+                if let Some(top) = sort.top {
+                    label = format!("{label} top {top}");
+                    rows = rows.min(top as u64);
+                }
+                node.wrap(label, rows)
+            }
+            Query::Limit(limit) => {
+                let mut label = "Limit:".to_string();
+                let mut rows = node.rows.saturating_sub(limit.offset as u64);
+                if let Some(n) = limit.limit {
+                    label = format!("{label} {n}");
+                    rows = rows.min(n as u64);
+                }
+                node.wrap(format!("{label} offset {}", limit.offset), rows)
+            }
+        };
+    }
+    node
+}
+
+fn estimate(rows: u64, selectivity: f64) -> u64 {
+    if rows == 0 {
+        return 0;
+    }
+    ((rows as f64 * selectivity).ceil() as u64).clamp(1, rows)
+}
+
+fn selectivity(cmp: &ColumnOp) -> f64 {
+    match cmp {
+        ColumnOp::Field(_) => DEFAULT_SELECTIVITY,
+        ColumnOp::Cmp { op, lhs, rhs } => match op {
+            OpQuery::Cmp(OpCmp::Eq) => EQ_SELECTIVITY,
+            OpQuery::Cmp(OpCmp::NotEq) => 1.0 - EQ_SELECTIVITY,
+            OpQuery::Cmp(OpCmp::Lt | OpCmp::LtEq | OpCmp::Gt | OpCmp::GtEq) => RANGE_SELECTIVITY,
+            OpQuery::Cmp(OpCmp::Like) => LIKE_SELECTIVITY,
+            OpQuery::Cmp(OpCmp::NotLike) => 1.0 - LIKE_SELECTIVITY,
+            OpQuery::Logic(OpLogic::And) => selectivity(lhs) * selectivity(rhs),
+            OpQuery::Logic(OpLogic::Or) => {
+                let (lhs, rhs) = (selectivity(lhs), selectivity(rhs));
+                lhs + rhs - lhs * rhs
+            }
+        },
+    }
+}
+
+fn index_selectivity(scan: &IndexScan) -> f64 {
+    match (&scan.lower_bound, &scan.upper_bound) {
+        (Bound::Unbounded, Bound::Unbounded) => 1.0,
+        (Bound::Included(lower), Bound::Included(upper)) if lower == upper => EQ_SELECTIVITY,
+        (Bound::Unbounded, _) | (_, Bound::Unbounded) => RANGE_SELECTIVITY,
+        _ => RANGE_SELECTIVITY * RANGE_SELECTIVITY,
+    }
+}
+
+/// Describes the values of an [IndexSeek].
+fn index_seek_cond(seek: &IndexSeek) -> String {
+    let col = &seek.table.head.fields[seek.col_id.idx()].field;
+    if seek.values.is_empty() {
+        return "no rows".into();
+    }
+    let values = seek
+        .values
+        .iter()
+        .map(|value| FieldExpr::Value(value.clone()).to_string())
+        .collect::<Vec<_>>();
+    format!("{col} in ({})", values.join(", "))
+}
+
+/// Describes the range of values of an [IndexScan], or `None` if it scans the whole index.
+fn index_cond(scan: &IndexScan) -> Option<String> {
+    let col = &scan.table.head.fields[scan.col_id.idx()].field;
+    let value = |value: &AlgebraicValue| FieldExpr::Value(value.clone()).to_string();
+    if let (Bound::Included(lower), Bound::Included(upper)) = (&scan.lower_bound, &scan.upper_bound) {
+        if lower == upper {
+            return Some(format!("{col} = {}", value(lower)));
+        }
+    }
+
+    let lower = match &scan.lower_bound {
+        Bound::Included(x) => Some(format!("{col} >= {}", value(x))),
+        Bound::Excluded(x) => Some(format!("{col} > {}", value(x))),
+        Bound::Unbounded => None,
+    };
+    let upper = match &scan.upper_bound {
+        Bound::Included(x) => Some(format!("{col} <= {}", value(x))),
+        Bound::Excluded(x) => Some(format!("{col} < {}", value(x))),
+        Bound::Unbounded => None,
+    };
+    match (lower, upper) {
+        (Some(lower), Some(upper)) => Some(format!("{lower} and {upper}")),
+        (lower, upper) => lower.or(upper),
+    }
+}
@@ -1,3 +1,8 @@
 pub mod ast;
+pub mod cache;
 pub mod compiler;
 pub mod execute;
+pub mod explain;
+pub mod params;
+pub mod policy;
+pub mod session;
@@ -0,0 +1,112 @@
+//! The values bound to the `$1`-style placeholders of a prepared statement.
+use serde::de::DeserializeSeed;
+use spacetimedb_lib::de::serde::SeedWrapper;
+use spacetimedb_lib::relation::FieldExpr;
+use spacetimedb_lib::{bsatn, Identity};
+use spacetimedb_sats::{AlgebraicType, AlgebraicValue, Typespace, WithTypespace};
+
+use crate::error::PlanError;
+
+/// The parameters of a `SQL` statement, where `$n` refers to the `n`-th value (starting at `1`).
+///
+/// The values are untyped until compiling the statement,
+/// where each one is decoded as the type of the column it is compared or assigned to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum SqlParams {
+    #[default]
+    None,
+    Json(Vec<serde_json::Value>),
+    /// Each value encoded with BSATN.
+    Bsatn(Vec<Vec<u8>>),
+    /// The only parameter of a row-level security policy, `:sender`, bound to the identity of the caller.
+    Sender(Identity),
+    /// The values aren't known yet, so the placeholders are left in the plan as [FieldExpr::Param],
+    /// to be bound by [crate::sql::ast::bind_params] before it runs.
+    Unbound,
+}
+
+/// The name of the parameter bound to the caller in a row-level security policy.
+pub const SENDER_PARAM: &str = ":sender";
+
+impl SqlParams {
+    /// Returns the value of the placeholder `name`, like `$1`, decoded as the type `ty`,
+    /// or a slot for it when the values are [SqlParams::Unbound].
+    pub(crate) fn field(&self, name: &str, ty: &AlgebraicType) -> Result<FieldExpr, PlanError> {
+        match self {
+            Self::Unbound => {
+                Self::position(name)?;
+                Ok(FieldExpr::Param {
+                    name: name.to_string(),
+                    ty: ty.clone(),
+                })
+            }
+            _ => self.decode(name, ty).map(FieldExpr::Value),
+        }
+    }
+
+    /// Returns the position, starting at `1`, of the positional placeholder `name`, like `$1`.
+    fn position(name: &str) -> Result<usize, PlanError> {
+        name.strip_prefix('$')
+            .and_then(|x| x.parse::<usize>().ok())
+            .filter(|x| *x > 0)
+            .ok_or_else(|| PlanError::Param {
+                param: name.to_string(),
+                error: "Only positional parameters like `$1` are supported.".into(),
+            })
+    }
+
+    /// Decodes the value of the placeholder `name`, like `$1`, as the type `ty`.
+    pub(crate) fn decode(&self, name: &str, ty: &AlgebraicType) -> Result<AlgebraicValue, PlanError> {
+        let err = |error: String| PlanError::Param {
+            param: name.to_string(),
+            error,
+        };
+        let pos = || Self::position(name);
+
+        let missing = || err(format!("Missing value, {} parameter(s) were given.", self.len()));
+        match self {
+            Self::None | Self::Unbound => {
+                pos()?;
+                Err(missing())
+            }
+            Self::Json(values) => {
+                let value = values.get(pos()? - 1).ok_or_else(missing)?;
+                let typespace = Typespace::new(Vec::new());
+                SeedWrapper(WithTypespace::new(&typespace, ty))
+                    .deserialize(value)
+                    .map_err(|e| err(e.to_string()))
+            }
+            Self::Bsatn(values) => {
+                let value = values.get(pos()? - 1).ok_or_else(missing)?;
+                AlgebraicValue::decode(ty, &mut &value[..]).map_err(|e| err(e.to_string()))
+            }
+            Self::Sender(sender) => {
+                if name != SENDER_PARAM {
+                    return Err(err(format!(
+                        "Only `{SENDER_PARAM}` is supported in a row-level security policy."
+                    )));
+                }
+                // The whole identity must be consumed, so it is only compared to an `Identity`.
+                let bytes = bsatn::to_vec(sender).map_err(|e| err(e.to_string()))?;
+                let mut bytes = &bytes[..];
+                AlgebraicValue::decode(ty, &mut bytes)
+                    .ok()
+                    .filter(|_| bytes.is_empty())
+                    .ok_or_else(|| err("It must be compared to a field of type `Identity`.".into()))
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::None | Self::Unbound => 0,
+            Self::Json(values) => values.len(),
+            Self::Bsatn(values) => values.len(),
+            Self::Sender(_) => 1,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -0,0 +1,74 @@
+//! Row-level security: the `SQL` predicate, like `owner = :sender`, that the rows of a table must satisfy
+//! to be visible to an identity other than the owner of the database.
+use spacetimedb_lib::identity::AuthCtx;
+use spacetimedb_lib::Identity;
+use spacetimedb_vm::expr::ColumnOp;
+use spacetimedb_vm::operator::OpLogic;
+
+use crate::db::datastore::locking_tx_datastore::MutTxId;
+use crate::db::datastore::traits::TableSchema;
+use crate::db::relational_db::RelationalDB;
+use crate::error::PlanError;
+use crate::sql::ast::{compile_standalone_where, Selection, SqlAst};
+use crate::sql::params::SqlParams;
+
+/// Compiles the row-level security `policy` of `table` into the [Selection] of the rows visible to `sender`.
+pub fn compile_policy(table: &TableSchema, policy: &str, sender: Identity) -> Result<Selection, PlanError> {
+    compile_standalone_where(table, &SqlParams::Sender(sender), policy).map_err(|e| {
+        PlanError::Unstructured(format!(
+            "Invalid row-level security policy of `{}`: {e}",
+            table.table_name
+        ))
+    })
+}
+
+/// Returns the [Selection] of the rows of `table` visible to the caller of `auth`,
+/// or `None` if every row is.
+///
+/// The owner of the database is not restricted by the policies.
+pub fn row_policy(
+    db: &RelationalDB,
+    tx: &MutTxId,
+    auth: &AuthCtx,
+    table: &TableSchema,
+) -> Result<Option<Selection>, PlanError> {
+    if auth.owner == auth.caller {
+        return Ok(None);
+    }
+    db.row_policy(tx, table.table_id)?
+        .map(|policy| compile_policy(table, &policy, auth.caller))
+        .transpose()
+}
+
+/// Restricts the rows read by `ast` to the ones visible to the caller of `auth`,
+/// by adding the row-level security policies of its tables to its `WHERE` clause.
+///
+/// Mutations are left untouched: only the owner, who sees every row, can run them.
+pub(crate) fn apply_row_policies(
+    db: &RelationalDB,
+    tx: &MutTxId,
+    auth: &AuthCtx,
+    ast: &mut SqlAst,
+) -> Result<(), PlanError> {
+    let restrict = |table: &TableSchema, selection: &mut Option<Selection>| -> Result<(), PlanError> {
+        if let Some(policy) = row_policy(db, tx, auth, table)? {
+            *selection = Some(match selection.take() {
+                Some(x) => Selection {
+                    clause: ColumnOp::new(OpLogic::And.into(), x.clause, policy.clause),
+                },
+                None => policy,
+            });
+        }
+        Ok(())
+    };
+
+    match ast {
+        SqlAst::Select { from, selection, .. } => from.iter_tables().try_for_each(|t| restrict(t, selection)),
+        SqlAst::Explain { statement } => apply_row_policies(db, tx, auth, statement),
+        SqlAst::Insert { .. }
+        | SqlAst::Update { .. }
+        | SqlAst::Delete { .. }
+        | SqlAst::CreateTable { .. }
+        | SqlAst::Drop { .. } => Ok(()),
+    }
+}
@@ -0,0 +1,289 @@
+//! Transactional `SQL` sessions: the owner of the database can `BEGIN` a transaction on a connection,
+//! run statements in it over several messages, inspect their effects, and then `COMMIT` or `ROLLBACK` it.
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use spacetimedb_lib::error::AuthError;
+use spacetimedb_lib::identity::AuthCtx;
+use spacetimedb_lib::relation::MemTable;
+use spacetimedb_vm::errors::ErrorVm;
+use sqlparser::ast::Statement;
+
+use crate::db::datastore::locking_tx_datastore::MutTxId;
+use crate::db::relational_db::RelationalDB;
+use crate::error::{DBError, SessionError};
+use crate::sql::execute::run_with_params;
+use crate::sql::params::SqlParams;
+
+/// How long the transaction of a [SqlSession] can stay open before it is rolled back.
+///
+/// While it is open, every other transaction of the database waits for it,
+/// so it is kept short: a session is meant for a few statements typed by hand, not for batch jobs.
+pub const SESSION_TX_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A statement that starts or ends the transaction of a [SqlSession].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TxControl {
+    Begin,
+    Commit,
+    Rollback,
+}
+
+impl TxControl {
+    fn from_statement(statement: &Statement) -> Option<Self> {
+        match statement {
+            Statement::StartTransaction { .. } => Some(Self::Begin),
+            Statement::Commit { .. } => Some(Self::Commit),
+            Statement::Rollback { .. } => Some(Self::Rollback),
+            _ => None,
+        }
+    }
+}
+
+enum SessionState {
+    /// Each batch of statements runs in its own transaction.
+    AutoCommit,
+    /// The statements run in `tx`, until it is committed, rolled back or `deadline` passes.
+    Open {
+        db: Arc<RelationalDB>,
+        tx: MutTxId,
+        deadline: Instant,
+    },
+    /// The transaction was rolled back for the given reason,
+    /// and statements are rejected until the client acknowledges it with `ROLLBACK`.
+    Aborted(String),
+}
+
+/// The `SQL` session of a connection.
+///
+/// Outside of a transaction, each batch of statements is committed on its own, just like with
+/// [crate::sql::execute::execute]. `BEGIN` opens a transaction that the next batches run in,
+/// until `COMMIT` or `ROLLBACK`, which must each be sent on their own.
+///
+/// When a statement of the transaction fails, or it stays open longer than [SESSION_TX_TIMEOUT],
+/// the transaction is rolled back, and every statement but `ROLLBACK` fails until then,
+/// so a repair is never half applied by the statements that follow.
+/// While the transaction is open, the connection rejects every message but the statements of its session,
+/// as they would otherwise wait for the transaction that only a later message of the connection can end.
+///
+/// Only the owner of the database can use a session.
+pub struct SqlSession {
+    state: SessionState,
+}
+
+impl Default for SqlSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SqlSession {
+    pub fn new() -> Self {
+        Self {
+            state: SessionState::AutoCommit,
+        }
+    }
+
+    /// Returns when the open transaction of the session will be rolled back, if there's one.
+    pub fn deadline(&self) -> Option<Instant> {
+        match &self.state {
+            SessionState::Open { deadline, .. } => Some(*deadline),
+            _ => None,
+        }
+    }
+
+    /// Runs the `SQL` batch `sql_text` in the session, with its `$n` placeholders bound to `params`.
+    pub fn execute(
+        &mut self,
+        db: &Arc<RelationalDB>,
+        sql_text: &str,
+        params: &SqlParams,
+        auth: AuthCtx,
+    ) -> Result<Vec<MemTable>, DBError> {
+        if auth.owner != auth.caller {
+            return Err(ErrorVm::from(AuthError::OwnerRequired).into());
+        }
+        self.expire(Instant::now());
+
+        let statements = db
+            .statement_cache()
+            .parse(sql_text)
+            .map_err(|error| DBError::SqlParser {
+                sql: sql_text.to_string(),
+                error,
+            })?;
+        let control = statements
+            .iter()
+            .filter_map(TxControl::from_statement)
+            .collect::<Vec<_>>();
+        match (&control[..], statements.len()) {
+            ([], _) => self.run(db, sql_text, params, auth),
+            ([control], 1) => self.control(db, *control).map(|()| Vec::new()),
+            _ => Err(SessionError::MixedTxControl.into()),
+        }
+    }
+
+    /// Rolls back the open transaction of the session if its deadline is past `now`.
+    pub fn expire(&mut self, now: Instant) {
+        if self.deadline().map_or(false, |deadline| deadline <= now) {
+            self.abort(format!("it was open for longer than {SESSION_TX_TIMEOUT:?}"));
+        }
+    }
+
+    fn run(
+        &mut self,
+        db: &RelationalDB,
+        sql_text: &str,
+        params: &SqlParams,
+        auth: AuthCtx,
+    ) -> Result<Vec<MemTable>, DBError> {
+        let result = match &mut self.state {
+            SessionState::AutoCommit => {
+                return db.with_auto_commit(|tx| run_with_params(db, tx, sql_text, params, auth));
+            }
+            SessionState::Open { tx, .. } => run_with_params(db, tx, sql_text, params, auth),
+            SessionState::Aborted(reason) => return Err(SessionError::Aborted(reason.clone()).into()),
+        };
+        if let Err(err) = &result {
+            self.abort(format!("a statement failed: {err}"));
+        }
+        result
+    }
+
+    fn control(&mut self, db: &Arc<RelationalDB>, control: TxControl) -> Result<(), DBError> {
+        match (std::mem::replace(&mut self.state, SessionState::AutoCommit), control) {
+            (SessionState::AutoCommit, TxControl::Begin) => {
+                self.state = SessionState::Open {
+                    db: db.clone(),
+                    tx: db.begin_tx(),
+                    deadline: Instant::now() + SESSION_TX_TIMEOUT,
+                };
+                Ok(())
+            }
+            (SessionState::AutoCommit, TxControl::Commit | TxControl::Rollback) => Err(SessionError::NotOpen.into()),
+            (state @ SessionState::Open { .. }, TxControl::Begin) => {
+                self.state = state;
+                Err(SessionError::AlreadyOpen.into())
+            }
+            (SessionState::Open { db, tx, .. }, TxControl::Commit) => db.commit_tx(tx).map(drop),
+            (SessionState::Open { db, tx, .. }, TxControl::Rollback) => {
+                db.rollback_tx(tx);
+                Ok(())
+            }
+            (SessionState::Aborted(_), TxControl::Rollback) => Ok(()),
+            (SessionState::Aborted(reason), TxControl::Begin | TxControl::Commit) => {
+                self.state = SessionState::Aborted(reason.clone());
+                Err(SessionError::Aborted(reason).into())
+            }
+        }
+    }
+
+    /// Rolls back the open transaction of the session, if there's one, because of `reason`.
+    fn abort(&mut self, reason: String) {
+        if let SessionState::Open { db, tx, .. } = std::mem::replace(&mut self.state, SessionState::Aborted(reason)) {
+            db.rollback_tx(tx);
+        }
+    }
+}
+
+impl Drop for SqlSession {
+    /// Rolls back the transaction left open by a closed connection.
+    fn drop(&mut self) {
+        if let SessionState::Open { db, tx, .. } = std::mem::replace(&mut self.state, SessionState::AutoCommit) {
+            db.rollback_tx(tx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::relational_db::tests_utils::make_test_db;
+    use crate::vm::tests::create_table_with_rows;
+    use spacetimedb_lib::error::ResultTest;
+    use spacetimedb_lib::Identity;
+    use spacetimedb_sats::{product, AlgebraicType, ProductType};
+
+    fn count(session: &mut SqlSession, db: &Arc<RelationalDB>) -> ResultTest<usize> {
+        let result = session.execute(db, "SELECT * FROM inventory", &SqlParams::None, AuthCtx::for_testing())?;
+        Ok(result[0].data.len())
+    }
+
+    fn run(session: &mut SqlSession, db: &Arc<RelationalDB>, sql: &str) -> Result<Vec<MemTable>, DBError> {
+        session.execute(db, sql, &SqlParams::None, AuthCtx::for_testing())
+    }
+
+    #[test]
+    fn test_session() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let db = Arc::new(db);
+        let mut tx = db.begin_tx();
+        let head = ProductType::from([("inventory_id", AlgebraicType::U64), ("name", AlgebraicType::String)]);
+        create_table_with_rows(&db, &mut tx, "inventory", head, &[product!(1u64, "health1")])?;
+        db.commit_tx(tx)?;
+
+        let mut session = SqlSession::new();
+        let insert = "INSERT INTO inventory (inventory_id, name) VALUES (2, 'health2')";
+
+        // The statements of a rolled back transaction are undone.
+        run(&mut session, &db, "BEGIN")?;
+        run(&mut session, &db, insert)?;
+        assert_eq!(count(&mut session, &db)?, 2);
+        run(&mut session, &db, "ROLLBACK")?;
+        assert_eq!(count(&mut session, &db)?, 1);
+
+        run(&mut session, &db, "BEGIN")?;
+        assert!(matches!(
+            run(&mut session, &db, "BEGIN"),
+            Err(DBError::Session(SessionError::AlreadyOpen))
+        ));
+        run(&mut session, &db, insert)?;
+        run(&mut session, &db, "COMMIT")?;
+        assert_eq!(count(&mut session, &db)?, 2);
+        assert!(matches!(
+            run(&mut session, &db, "COMMIT"),
+            Err(DBError::Session(SessionError::NotOpen))
+        ));
+        assert!(matches!(
+            run(&mut session, &db, &format!("BEGIN; {insert}")),
+            Err(DBError::Session(SessionError::MixedTxControl))
+        ));
+
+        // A failed statement rolls back the transaction, until the client acknowledges it.
+        run(&mut session, &db, "BEGIN")?;
+        run(&mut session, &db, "DELETE FROM inventory")?;
+        assert!(run(&mut session, &db, "SELECT * FROM unknown").is_err());
+        assert!(matches!(
+            run(&mut session, &db, "COMMIT"),
+            Err(DBError::Session(SessionError::Aborted(_)))
+        ));
+        run(&mut session, &db, "ROLLBACK")?;
+        assert_eq!(count(&mut session, &db)?, 2);
+
+        // So does the timeout, which releases the database to other transactions.
+        run(&mut session, &db, "BEGIN")?;
+        run(&mut session, &db, "DELETE FROM inventory")?;
+        session.expire(session.deadline().unwrap());
+        db.rollback_tx(db.begin_tx());
+        assert!(matches!(
+            run(&mut session, &db, "SELECT * FROM inventory"),
+            Err(DBError::Session(SessionError::Aborted(_)))
+        ));
+        run(&mut session, &db, "ROLLBACK")?;
+        assert_eq!(count(&mut session, &db)?, 2);
+
+        // Only the owner can use a session.
+        let auth = AuthCtx::new(Identity::__dummy(), Identity::from_byte_array([1; 32]));
+        let result = session.execute(&db, "BEGIN", &SqlParams::None, auth);
+        assert!(matches!(result, Err(DBError::Vm(_))));
+        assert!(session.deadline().is_none());
+
+        // A transaction left open by a closed connection is rolled back.
+        run(&mut session, &db, "BEGIN")?;
+        run(&mut session, &db, "DELETE FROM inventory")?;
+        drop(session);
+        assert_eq!(count(&mut SqlSession::new(), &db)?, 2);
+
+        Ok(())
+    }
+}
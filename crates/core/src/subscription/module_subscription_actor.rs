@@ -7,6 +7,7 @@ use super::{
 use crate::db::datastore::locking_tx_datastore::MutTxId;
 use crate::host::module_host::{EventStatus, ModuleEvent};
 use crate::protobuf::client_api::Subscribe;
+use crate::sql::params::SqlParams;
 use crate::{
     client::{
         messages::{CachedMessage, SubscriptionUpdateMessage, TransactionUpdateMessage},
@@ -25,6 +26,7 @@ enum ModuleSubscriptionCommand {
     AddSubscriber {
         sender: ClientConnectionSender,
         subscription: Subscribe,
+        params: SqlParams,
     },
     RemoveSubscriber {
         client_id: ClientActorId,
@@ -70,9 +72,20 @@ impl ModuleSubscriptionManager {
         (Self { tx }, SubscriptionEventSender { commit_event_tx })
     }
 
-    pub fn add_subscriber(&self, sender: ClientConnectionSender, subscription: Subscribe) -> Result<(), NoSuchModule> {
+    /// Subscribes the client of `sender` to the queries of `subscription`,
+    /// with their `$n` placeholders bound to `params`.
+    pub fn add_subscriber(
+        &self,
+        sender: ClientConnectionSender,
+        subscription: Subscribe,
+        params: SqlParams,
+    ) -> Result<(), NoSuchModule> {
         self.tx
-            .send(ModuleSubscriptionCommand::AddSubscriber { sender, subscription })
+            .send(ModuleSubscriptionCommand::AddSubscriber {
+                sender,
+                subscription,
+                params,
+            })
             .map_err(|_| NoSuchModule)
     }
 
@@ -126,9 +139,11 @@ impl ModuleSubscriptionActor {
 
     async fn handle_message(&mut self, command: Command) -> Result<(), DBError> {
         match command {
-            Command::Subscription(ModuleSubscriptionCommand::AddSubscriber { sender, subscription }) => {
-                self.add_subscription(sender, subscription).await?
-            }
+            Command::Subscription(ModuleSubscriptionCommand::AddSubscriber {
+                sender,
+                subscription,
+                params,
+            }) => self.add_subscription(sender, subscription, params).await?,
             Command::Subscription(ModuleSubscriptionCommand::RemoveSubscriber { client_id }) => {
                 self.remove_subscriber(client_id)
             }
@@ -141,6 +156,7 @@ impl ModuleSubscriptionActor {
         &mut self,
         sender: ClientConnectionSender,
         subscription: Subscribe,
+        params: SqlParams,
         tx: &mut MutTxId,
     ) -> Result<(), DBError> {
         self.remove_subscriber(sender.id);
@@ -148,7 +164,7 @@ impl ModuleSubscriptionActor {
 
         let mut queries = QuerySet::new();
         for sql in subscription.query_strings {
-            let qset = compile_read_only_query(&self.relational_db, tx, &auth, &sql)?;
+            let qset = compile_read_only_query(&self.relational_db, tx, &auth, &sql, &params)?;
             queries.extend(qset);
         }
 
@@ -180,13 +196,23 @@ impl ModuleSubscriptionActor {
         &mut self,
         sender: ClientConnectionSender,
         subscription: Subscribe,
+        params: SqlParams,
     ) -> Result<(), DBError> {
         //Split logic to properly handle `Error` + `Tx`
-        let mut tx = self.relational_db.begin_tx();
-        let result = self._add_subscription(sender, subscription, &mut tx).await;
+        let mut tx = self.begin_tx().await;
+        let result = self._add_subscription(sender, subscription, params, &mut tx).await;
         self.relational_db.finish_tx(tx, result)
     }
 
+    /// Begins a transaction, waiting for the one in progress, if there's one,
+    /// on a blocking thread rather than on the worker running this actor.
+    async fn begin_tx(&self) -> MutTxId {
+        let db = self.relational_db.clone();
+        tokio::task::spawn_blocking(move || db.begin_tx())
+            .await
+            .expect("beginning a transaction panicked")
+    }
+
     fn remove_subscriber(&mut self, client_id: ClientActorId) {
         self.subscriptions.retain_mut(|sub| {
             sub.remove_subscriber(client_id);
@@ -208,17 +234,27 @@ impl ModuleSubscriptionActor {
                 continue;
             }
 
-            let message = TransactionUpdateMessage {
-                event: &mut event,
-                database_update: incr,
-            };
-            let mut message = CachedMessage::new(message);
-
-            for subscriber in subscription.subscribers() {
-                // rustc realllly doesn't like subscriber.send_message(message) here for weird
-                // lifetime reasons, even though it would be sound
-                let message = message.serialize(subscriber.protocol);
-                futures.push(subscriber.send(message).map(drop))
+            // Only the clients that asked for them are sent update operations,
+            // as the others would take them for deletions.
+            let (with_update_ops, without_update_ops): (Vec<_>, Vec<_>) =
+                subscription.subscribers().iter().partition(|s| s.update_ops);
+            let split = (!without_update_ops.is_empty()).then(|| incr.split_updates());
+            for (subscribers, database_update) in [(with_update_ops, Some(incr)), (without_update_ops, split)] {
+                let Some(database_update) = database_update.filter(|_| !subscribers.is_empty()) else {
+                    continue;
+                };
+                let message = TransactionUpdateMessage {
+                    event: &mut event,
+                    database_update,
+                };
+                let mut message = CachedMessage::new(message);
+
+                for subscriber in subscribers {
+                    // rustc realllly doesn't like subscriber.send_message(message) here for weird
+                    // lifetime reasons, even though it would be sound
+                    let message = message.serialize(subscriber.protocol);
+                    futures.push(subscriber.send(message).map(drop))
+                }
             }
         }
 
@@ -229,7 +265,7 @@ impl ModuleSubscriptionActor {
 
     async fn broadcast_commit_event(&mut self, event: ModuleEvent) -> Result<(), DBError> {
         //Split logic to properly handle `Error` + `Tx`
-        let mut tx = self.relational_db.begin_tx();
+        let mut tx = self.begin_tx().await;
         let result = self._broadcast_commit_event(event, &mut tx).await;
         self.relational_db.finish_tx(tx, result)
     }
@@ -2,8 +2,9 @@ use crate::db::datastore::locking_tx_datastore::MutTxId;
 use crate::db::relational_db::RelationalDB;
 use crate::error::{DBError, SubscriptionError};
 use crate::host::module_host::DatabaseTableUpdate;
-use crate::sql::compiler::compile_sql;
+use crate::sql::compiler::compile_sql_with_auth;
 use crate::sql::execute::execute_single_sql;
+use crate::sql::params::SqlParams;
 use crate::subscription::subscription::QuerySet;
 use spacetimedb_lib::identity::AuthCtx;
 use spacetimedb_lib::relation::{Column, FieldName, MemTable, RelValue};
@@ -25,6 +26,11 @@ pub const OP_TYPE_FIELD_NAME: &str = "__op_type";
 ///
 /// To be able to reify the `op_type` of the individual operations in the update,
 /// each virtual row is extended with a column [`OP_TYPE_FIELD_NAME`].
+///
+/// An update operation contributes two virtual rows:
+/// its old row as a delete and its new row as an insert,
+/// so that each is matched against the query on its own.
+/// See [`DatabaseTableUpdate::merge_updates`] for turning them back into an update.
 #[tracing::instrument(skip_all)]
 pub fn to_mem_table(of: QueryExpr, data: &DatabaseTableUpdate) -> QueryExpr {
     let mut q = of;
@@ -37,8 +43,8 @@ pub fn to_mem_table(of: QueryExpr, data: &DatabaseTableUpdate) -> QueryExpr {
     let mut t = MemTable::new(head.clone(), table_access, vec![]);
 
     if let Some(pos) = t.head.find_pos_by_name(OP_TYPE_FIELD_NAME) {
-        t.data.extend(data.ops.iter().map(|row| {
-            let mut new = row.row.clone();
+        t.data.extend(data.split_updates().map(|row| {
+            let mut new = row.row;
             new.elements[pos] = row.op_type.into();
             let mut bytes: &[u8] = row.row_pk.as_ref();
             RelValue::new(new, Some(DataKey::decode(&mut bytes).unwrap()))
@@ -48,8 +54,8 @@ pub fn to_mem_table(of: QueryExpr, data: &DatabaseTableUpdate) -> QueryExpr {
             FieldName::named(&t.head.table_name, OP_TYPE_FIELD_NAME),
             AlgebraicType::U8,
         ));
-        for row in &data.ops {
-            let mut new = row.row.clone();
+        for row in data.split_updates() {
+            let mut new = row.row;
             new.elements.push(row.op_type.into());
             let mut bytes: &[u8] = row.row_pk.as_ref();
             t.data
@@ -89,12 +95,17 @@ pub(crate) fn run_query(
 /// ```
 ///
 /// WARNING: [`SUBSCRIBE_TO_ALL_QUERY`] is only valid for repeated calls as long there is not change on database schema, and the clients must `unsubscribe` before modifying it.
-#[tracing::instrument(skip(relational_db, auth, tx))]
+///
+/// The `$n` placeholders of `input` are bound to the values of `params`.
+///
+/// For a caller other than the owner, the rows of each table are restricted by its row-level security policy.
+#[tracing::instrument(skip(relational_db, auth, tx, params))]
 pub fn compile_read_only_query(
     relational_db: &RelationalDB,
     tx: &MutTxId,
     auth: &AuthCtx,
     input: &str,
+    params: &SqlParams,
 ) -> Result<QuerySet, DBError> {
     let input = input.trim();
     if input.is_empty() {
@@ -105,7 +116,7 @@ pub fn compile_read_only_query(
         return QuerySet::get_all(relational_db, tx, auth);
     }
 
-    let compiled = compile_sql(relational_db, tx, input)?;
+    let compiled = compile_sql_with_auth(relational_db, tx, input, params, auth)?;
     let mut queries = Vec::with_capacity(compiled.len());
     for q in compiled {
         match q {
@@ -119,6 +130,7 @@ pub fn compile_read_only_query(
                 return Err(SubscriptionError::SideEffect(Crud::Create(DbType::Table)).into())
             }
             CrudExpr::Drop { kind, .. } => return Err(SubscriptionError::SideEffect(Crud::Drop(kind)).into()),
+            CrudExpr::Explain { .. } => return Err(SubscriptionError::Unsupported("EXPLAIN".into()).into()),
         }
     }
 
@@ -134,9 +146,9 @@ pub fn compile_read_only_query(
 pub enum Supported {
     /// A scan or [`QueryExpr::Select`] of a single table.
     Scan,
-    /// A semijoin of two tables, restricted to [`QueryExpr::IndexJoin`]s.
+    /// A semijoin of two or more tables, restricted to a chain of [`QueryExpr::IndexJoin`]s.
     ///
-    /// See [`crate::sql::compiler::try_index_join`].
+    /// See [`crate::sql::compiler::try_index_join`] and [`crate::sql::compiler::try_index_join_chain`].
     Semijoin,
 }
 
@@ -144,23 +156,40 @@ pub enum Supported {
 /// evaluation is not currently supported for the expression.
 pub fn classify(expr: &QueryExpr) -> Option<Supported> {
     use expr::Query::*;
-    if expr.query.len() == 1 && matches!(expr.query[0], IndexJoin(_)) {
+    if is_index_join_chain(expr) {
         return Some(Supported::Semijoin);
     }
     for op in &expr.query {
-        if let JoinInner(_) = op {
+        if let JoinInner(_) | IndexJoin(_) | Aggregate(_) | Sort(_) | Limit(_) = op {
             return None;
         }
     }
     Some(Supported::Scan)
 }
 
+/// Is `expr` an [`expr::Query::IndexJoin`], optionally followed by selections,
+/// whose probe side is either free of joins or again such an expression?
+fn is_index_join_chain(expr: &QueryExpr) -> bool {
+    use expr::Query::*;
+    let Some((IndexJoin(join), rest)) = expr.query.split_first() else {
+        return false;
+    };
+    rest.iter().all(|op| matches!(op, Select(_)))
+        && (join
+            .probe_side
+            .query
+            .iter()
+            .all(|op| !matches!(op, JoinInner(_) | IndexJoin(_)))
+            || is_index_join_chain(&join.probe_side))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::db::datastore::traits::{ColumnDef, IndexDef, TableDef, TableSchema};
     use crate::db::relational_db::tests_utils::make_test_db;
     use crate::host::module_host::{DatabaseTableUpdate, DatabaseUpdate, TableOp};
+    use crate::sql::compiler::compile_sql;
     use crate::sql::execute::run;
     use crate::subscription::subscription::QuerySet;
     use crate::vm::tests::create_table_with_rows;
@@ -171,7 +200,7 @@ mod tests {
     use spacetimedb_lib::relation::FieldName;
     use spacetimedb_lib::Identity;
     use spacetimedb_primitives::{ColId, TableId};
-    use spacetimedb_sats::{product, ProductType, ProductValue};
+    use spacetimedb_sats::{product, AlgebraicValue, ProductType, ProductValue};
     use spacetimedb_vm::dsl::{db_table, mem_table, scalar};
     use spacetimedb_vm::operator::OpCmp;
 
@@ -220,6 +249,7 @@ mod tests {
                 op_type: 1,
                 row,
                 row_pk,
+                old: None,
             }],
         }
     }
@@ -233,6 +263,7 @@ mod tests {
                 op_type: 0,
                 row,
                 row_pk,
+                old: None,
             }],
         }
     }
@@ -262,6 +293,7 @@ mod tests {
             op_type: 1,
             row_pk: row.to_data_key().to_bytes(),
             row: row.clone(),
+            old: None,
         };
 
         let data = DatabaseTableUpdate {
@@ -414,6 +446,7 @@ mod tests {
             op_type: 0,
             row_pk: id1.clone(),
             row: row.clone(),
+            old: None,
         };
 
         let update = DatabaseTableUpdate {
@@ -462,6 +495,7 @@ mod tests {
                 op_type: 0,
                 row_pk,
                 row,
+                old: None,
             })
         }
 
@@ -489,6 +523,87 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_eval_incr_for_update() -> ResultTest<()> {
+        let (db, _) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        let schema = &[("a", AlgebraicType::U64), ("b", AlgebraicType::U64)];
+        let table_id = create_table(&db, &mut tx, "test", schema, &[])?;
+
+        let sql = "select * from test where b = 3";
+        let mut exp = compile_sql(&db, &tx, sql)?;
+
+        let Some(CrudExpr::Query(query)) = exp.pop() else {
+            panic!("unexpected query {:#?}", exp[0]);
+        };
+
+        let query = QuerySet::try_from(query)?;
+
+        let update_op = |old: ProductValue, new: ProductValue| TableOp {
+            op_type: 2,
+            row_pk: new.to_data_key().to_bytes(),
+            row: new,
+            old: Some((old.to_data_key().to_bytes(), old)),
+        };
+        let update = DatabaseUpdate {
+            tables: vec![DatabaseTableUpdate {
+                table_id,
+                table_name: "test".into(),
+                ops: vec![
+                    // Both rows match: an update.
+                    update_op(product!(1u64, 3u64), product!(2u64, 3u64)),
+                    // Only the old row matches: a delete.
+                    update_op(product!(3u64, 3u64), product!(3u64, 4u64)),
+                    // Only the new row matches: an insert.
+                    update_op(product!(4u64, 4u64), product!(4u64, 3u64)),
+                    // Neither row matches.
+                    update_op(product!(5u64, 5u64), product!(6u64, 5u64)),
+                ],
+            }],
+        };
+
+        let result = query.eval_incr(&db, &mut tx, &update, AuthCtx::for_testing())?;
+
+        assert_eq!(result.tables.len(), 1);
+
+        let mut ops = result.tables[0]
+            .ops
+            .iter()
+            .map(|op| (op.op_type, op.row.clone(), op.old.as_ref().map(|(_, row)| row.clone())))
+            .collect::<Vec<_>>();
+        ops.sort_by_key(|(op_type, ..)| *op_type);
+        assert_eq!(
+            ops,
+            vec![
+                (0, product!(3u64, 3u64), None),
+                (1, product!(4u64, 3u64), None),
+                (2, product!(2u64, 3u64), Some(product!(1u64, 3u64))),
+            ]
+        );
+
+        // Clients that don't understand update operations get the deletion of the old row
+        // and the insertion of the new one instead.
+        let split = result.split_updates();
+        assert!(split.tables[0].ops.iter().all(|op| op.old.is_none()));
+        let mut ops = split.tables[0]
+            .ops
+            .iter()
+            .map(|op| (op.op_type, op.row.clone()))
+            .collect::<Vec<_>>();
+        ops.sort();
+        assert_eq!(
+            ops,
+            vec![
+                (0, product!(1u64, 3u64)),
+                (0, product!(3u64, 3u64)),
+                (1, product!(2u64, 3u64)),
+                (1, product!(4u64, 3u64)),
+            ]
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_eval_incr_for_index_join() -> ResultTest<()> {
         let (db, _) = make_test_db()?;
@@ -726,6 +841,184 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_eval_incr_for_index_join_chain() -> ResultTest<()> {
+        let (db, _) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        // Create table [item] with index on [item_id]
+        let schema = &[("item_id", AlgebraicType::I32), ("weight", AlgebraicType::I32)];
+        let indexes = &[(0, "item_id")];
+        let item_id = create_table(&db, &mut tx, "item", schema, indexes)?;
+
+        // Create table [inventory] with index on [owner_id]
+        let schema = &[
+            ("inventory_id", AlgebraicType::I32),
+            ("owner_id", AlgebraicType::I32),
+            ("item_id", AlgebraicType::I32),
+        ];
+        let indexes = &[(1, "owner_id")];
+        let inventory_id = create_table(&db, &mut tx, "inventory", schema, indexes)?;
+
+        // Create table [player] with no indexes
+        let schema = &[("owner_id", AlgebraicType::I32), ("zone", AlgebraicType::I32)];
+        let player_id = create_table(&db, &mut tx, "player", schema, &[])?;
+
+        for i in 0..3 {
+            db.insert(&mut tx, player_id, product!(i, i % 2))?;
+        }
+        for i in 0..6 {
+            db.insert(&mut tx, inventory_id, product!(i, i % 3, i))?;
+            db.insert(&mut tx, item_id, product!(i, i * 10))?;
+        }
+
+        // Should be answered using a chain of index semijoins
+        let sql = "select item.* from item \
+            join inventory on item.item_id = inventory.item_id \
+            join player on inventory.owner_id = player.owner_id \
+            where player.zone = 1";
+        let mut exp = compile_sql(&db, &tx, sql)?;
+
+        let Some(CrudExpr::Query(query)) = exp.pop() else {
+            panic!("unexpected query {:#?}", exp[0]);
+        };
+
+        let query = QuerySet::try_from(query)?;
+        assert_eq!(query.iter().next().unwrap().kind(), Supported::Semijoin);
+
+        let eval = |tx: &mut MutTxId, tables: Vec<DatabaseTableUpdate>| -> ResultTest<Vec<(u8, ProductValue)>> {
+            let update = DatabaseUpdate { tables };
+            let result = query.eval_incr(&db, tx, &update, AuthCtx::for_testing())?;
+            let mut ops = result
+                .tables
+                .into_iter()
+                .inspect(|table| assert_eq!(table.table_id, item_id))
+                .flat_map(|table| table.ops)
+                .map(|op| (op.op_type, op.row))
+                .collect::<Vec<_>>();
+            ops.sort();
+            Ok(ops)
+        };
+
+        // Case 1: A player moves into the zone
+        {
+            let r1 = product!(2, 0);
+            let r2 = product!(2, 1);
+
+            delete_row(&db, &mut tx, player_id, r1.clone());
+            insert_row(&db, &mut tx, player_id, r2.clone())?;
+
+            let updates = vec![
+                delete_op(player_id, "player", r1.clone()),
+                insert_op(player_id, "player", r2.clone()),
+            ];
+
+            // The items of the player are inserted
+            let result = eval(&mut tx, updates)?;
+            assert_eq!(result, vec![(1, product!(2, 20)), (1, product!(5, 50))]);
+
+            // Clean up tx
+            insert_row(&db, &mut tx, player_id, r1.clone())?;
+            delete_row(&db, &mut tx, player_id, r2.clone());
+        }
+
+        // Case 2: An item changes hands from a player inside the zone to one outside of it
+        {
+            let r1 = product!(1, 1, 1);
+            let r2 = product!(1, 0, 1);
+
+            delete_row(&db, &mut tx, inventory_id, r1.clone());
+            insert_row(&db, &mut tx, inventory_id, r2.clone())?;
+
+            let updates = vec![
+                delete_op(inventory_id, "inventory", r1.clone()),
+                insert_op(inventory_id, "inventory", r2.clone()),
+            ];
+
+            // The item is deleted
+            let result = eval(&mut tx, updates)?;
+            assert_eq!(result, vec![(0, product!(1, 10))]);
+
+            // Clean up tx
+            insert_row(&db, &mut tx, inventory_id, r1.clone())?;
+            delete_row(&db, &mut tx, inventory_id, r2.clone());
+        }
+
+        // Case 3: An item changes hands between two players outside the zone
+        {
+            let r1 = product!(3, 0, 3);
+            let r2 = product!(3, 2, 3);
+
+            delete_row(&db, &mut tx, inventory_id, r1.clone());
+            insert_row(&db, &mut tx, inventory_id, r2.clone())?;
+
+            let updates = vec![
+                delete_op(inventory_id, "inventory", r1.clone()),
+                insert_op(inventory_id, "inventory", r2.clone()),
+            ];
+
+            // No updates to report
+            let result = eval(&mut tx, updates)?;
+            assert_eq!(result, vec![]);
+
+            // Clean up tx
+            insert_row(&db, &mut tx, inventory_id, r1.clone())?;
+            delete_row(&db, &mut tx, inventory_id, r2.clone());
+        }
+
+        // Case 4: A new item is given to a player inside the zone
+        {
+            let item_row = product!(6, 60);
+            let inventory_row = product!(6, 1, 6);
+
+            insert_row(&db, &mut tx, item_id, item_row.clone())?;
+            insert_row(&db, &mut tx, inventory_id, inventory_row.clone())?;
+
+            let updates = vec![
+                insert_op(item_id, "item", item_row.clone()),
+                insert_op(inventory_id, "inventory", inventory_row.clone()),
+            ];
+
+            // The item is inserted
+            let result = eval(&mut tx, updates)?;
+            assert_eq!(result, vec![(1, product!(6, 60))]);
+
+            // Clean up tx
+            delete_row(&db, &mut tx, item_id, item_row.clone());
+            delete_row(&db, &mut tx, inventory_id, inventory_row.clone());
+        }
+
+        // Case 5: A player inside the zone is updated to be outside of it
+        {
+            let r1 = product!(1, 1);
+            let r2 = product!(1, 0);
+
+            delete_row(&db, &mut tx, player_id, r1.clone());
+            insert_row(&db, &mut tx, player_id, r2.clone())?;
+
+            let updates = vec![DatabaseTableUpdate {
+                table_id: player_id,
+                table_name: "player".into(),
+                ops: vec![TableOp {
+                    op_type: 2,
+                    row_pk: r2.to_data_key().to_bytes(),
+                    row: r2.clone(),
+                    old: Some((r1.to_data_key().to_bytes(), r1.clone())),
+                }],
+            }];
+
+            // The items of the player are deleted
+            let result = eval(&mut tx, updates)?;
+            assert_eq!(result, vec![(0, product!(1, 10)), (0, product!(4, 40))]);
+
+            // Clean up tx
+            insert_row(&db, &mut tx, player_id, r1.clone())?;
+            delete_row(&db, &mut tx, player_id, r2.clone());
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_subscribe() -> ResultTest<()> {
         let (db, _tmp_dir) = make_test_db()?;
@@ -774,12 +1067,14 @@ mod tests {
             op_type: 0,
             row_pk: row.to_data_key().to_bytes(),
             row: row.clone(),
+            old: None,
         };
 
         let row2 = TableOp {
             op_type: 1,
             row_pk: row.to_data_key().to_bytes(),
             row: row.clone(),
+            old: None,
         };
 
         let data = DatabaseTableUpdate {
@@ -851,12 +1146,14 @@ mod tests {
             op_type: 0,
             row_pk: row.to_data_key().to_bytes(),
             row: row.clone(),
+            old: None,
         };
 
         let row2 = TableOp {
             op_type: 1,
             row_pk: row.to_data_key().to_bytes(),
             row: row.clone(),
+            old: None,
         };
 
         let data = DatabaseTableUpdate {
@@ -872,6 +1169,39 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_subscribe_predicates() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        let (schema, _, _, _) = make_inv(&db, &mut tx, StAccess::Public)?;
+
+        let sql = "SELECT * FROM inventory WHERE inventory_id IN (1, 3) AND name LIKE 'he%'";
+        let s = compile_read_only_query(&db, &tx, &AuthCtx::for_testing(), sql, &SqlParams::None)?;
+        check_query_eval(&db, &mut tx, &s, 1, &[product!(1u64, "health")])?;
+
+        let insert = |row: ProductValue| TableOp {
+            op_type: 1,
+            row_pk: row.to_data_key().to_bytes(),
+            row,
+            old: None,
+        };
+        let data = DatabaseTableUpdate {
+            table_id: schema.table_id,
+            table_name: "inventory".to_string(),
+            ops: vec![
+                insert(product!(3u64, "heal")),
+                insert(product!(3u64, "armor")),
+                insert(product!(4u64, "heal")),
+            ],
+        };
+        let update = DatabaseUpdate { tables: vec![data] };
+
+        check_query_incr(&db, &mut tx, &s, &update, 1, &[product!(3u64, "heal")])?;
+
+        Ok(())
+    }
+
     #[test]
     fn test_subscribe_sql() -> ResultTest<()> {
         let (db, _tmp_dir) = make_test_db()?;
@@ -917,7 +1247,7 @@ mod tests {
             AND MobileEntityState.location_x < 192000 \
             AND MobileEntityState.location_z > 96000 \
             AND MobileEntityState.location_z < 192000";
-        let qset = compile_read_only_query(&db, &tx, &AuthCtx::for_testing(), sql_query)?;
+        let qset = compile_read_only_query(&db, &tx, &AuthCtx::for_testing(), sql_query, &SqlParams::None)?;
 
         for q in qset {
             let result = run_query(&db, &mut tx, q.as_expr(), AuthCtx::for_testing())?;
@@ -937,19 +1267,27 @@ mod tests {
         let row_1 = product!(1u64, "health");
         let row_2 = product!(2u64, "jhon doe");
 
-        let s = compile_read_only_query(&db, &tx, &AuthCtx::for_testing(), SUBSCRIBE_TO_ALL_QUERY)?;
+        let s = compile_read_only_query(
+            &db,
+            &tx,
+            &AuthCtx::for_testing(),
+            SUBSCRIBE_TO_ALL_QUERY,
+            &SqlParams::None,
+        )?;
         check_query_eval(&db, &mut tx, &s, 2, &[row_1.clone(), row_2.clone()])?;
 
         let row1 = TableOp {
             op_type: 0,
             row_pk: row_1.to_data_key().to_bytes(),
             row: row_1,
+            old: None,
         };
 
         let row2 = TableOp {
             op_type: 1,
             row_pk: row_2.to_data_key().to_bytes(),
             row: row_2,
+            old: None,
         };
 
         let data1 = DatabaseTableUpdate {
@@ -975,6 +1313,70 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_subscribe_with_params() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        make_inv(&db, &mut tx, StAccess::Public)?;
+        run(
+            &db,
+            &mut tx,
+            "INSERT INTO inventory (inventory_id, name) VALUES (3, 'other')",
+            AuthCtx::for_testing(),
+        )?;
+
+        let auth = AuthCtx::for_testing();
+        let by_name = SqlParams::Json(vec![serde_json::json!("health")]);
+        let sql = "SELECT * FROM inventory WHERE name = $1";
+        let s1 = compile_read_only_query(&db, &tx, &auth, sql, &by_name)?;
+        check_query_eval(&db, &mut tx, &s1, 1, &[product!(1u64, "health")])?;
+
+        let by_id = SqlParams::Bsatn(vec![spacetimedb_lib::bsatn::to_vec(&3u64).unwrap()]);
+        let sql = "SELECT * FROM inventory WHERE inventory_id = $1";
+        let s2 = compile_read_only_query(&db, &tx, &auth, sql, &by_id)?;
+        check_query_eval(&db, &mut tx, &s2, 1, &[product!(3u64, "other")])?;
+
+        // The bound values are part of the queries, so subscriptions with other values are not shared
+        let s3 = compile_read_only_query(&db, &tx, &auth, sql, &SqlParams::Json(vec![serde_json::json!(1)]))?;
+        assert!(s2 != s3);
+
+        assert!(compile_read_only_query(&db, &tx, &auth, sql, &SqlParams::None).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_subscribe_with_row_policy() -> ResultTest<()> {
+        let (db, _tmp_dir) = make_test_db()?;
+        let mut tx = db.begin_tx();
+
+        let identity_ty = AlgebraicType::product([("__identity_bytes", AlgebraicType::bytes())]);
+        let head = ProductType::from([("id", AlgebraicType::U64), ("owner", identity_ty)]);
+        let (alice, bob) = (
+            Identity::from_byte_array([1u8; 32]),
+            Identity::from_byte_array([2u8; 32]),
+        );
+        let owner = |x: Identity| AlgebraicValue::product(vec![AlgebraicValue::Bytes(x.to_vec())]);
+        let rows = [product!(1u64, owner(alice)), product!(2u64, owner(bob))];
+        let table_id = create_table_with_rows(&db, &mut tx, "message", head, &rows)?;
+        db.set_row_policy(&mut tx, table_id, Some("owner = :sender".into()))?;
+
+        for sql in ["SELECT * FROM message", SUBSCRIBE_TO_ALL_QUERY] {
+            let auth = AuthCtx::new(Identity::__dummy(), bob);
+            let s = compile_read_only_query(&db, &tx, &auth, sql, &SqlParams::None)?;
+            let result = get_result(s.eval(&db, &mut tx, auth)?);
+            assert_eq!(result, [rows[1].clone()], "Visible to `bob` with `{sql}`");
+
+            let auth = AuthCtx::new(Identity::__dummy(), Identity::__dummy());
+            let s = compile_read_only_query(&db, &tx, &auth, sql, &SqlParams::None)?;
+            let result = get_result(s.eval(&db, &mut tx, auth)?);
+            assert_eq!(result, rows, "Visible to the owner with `{sql}`");
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_classify() -> ResultTest<()> {
         let (db, _tmp_dir) = make_test_db()?;
@@ -1006,14 +1408,22 @@ mod tests {
             "SELECT * FROM lhs WHERE id > 5",
         ];
         for scan in scans {
-            let expr = compile_read_only_query(&db, &tx, &auth, scan)?.pop_first().unwrap();
+            let expr = compile_read_only_query(&db, &tx, &auth, scan, &SqlParams::None)?
+                .pop_first()
+                .unwrap();
             assert_eq!(expr.kind(), Supported::Scan, "{scan}\n{expr:#?}");
         }
 
         // Only index semijoins are supported
-        let joins = ["SELECT lhs.* FROM lhs JOIN rhs ON lhs.id = rhs.id WHERE rhs.y < 10"];
+        let joins = [
+            "SELECT lhs.* FROM lhs JOIN rhs ON lhs.id = rhs.id WHERE rhs.y < 10",
+            "SELECT lhs.* FROM lhs JOIN rhs ON lhs.id = rhs.id JOIN plain ON rhs.id = plain.id WHERE rhs.y < 10",
+            "SELECT lhs.* FROM lhs JOIN rhs ON lhs.id = rhs.id JOIN plain ON rhs.id = plain.id WHERE plain.id > 5",
+        ];
         for join in joins {
-            let expr = compile_read_only_query(&db, &tx, &auth, join)?.pop_first().unwrap();
+            let expr = compile_read_only_query(&db, &tx, &auth, join, &SqlParams::None)?
+                .pop_first()
+                .unwrap();
             assert_eq!(expr.kind(), Supported::Semijoin, "{join}\n{expr:#?}");
         }
 
@@ -1022,9 +1432,14 @@ mod tests {
             "SELECT lhs.* FROM lhs JOIN rhs ON lhs.id = rhs.id",
             "SELECT * FROM lhs JOIN rhs ON lhs.id = rhs.id",
             "SELECT * FROM lhs JOIN rhs ON lhs.id = rhs.id WHERE lhs.x < 10",
+            "SELECT lhs.* FROM lhs JOIN plain ON lhs.id = plain.id JOIN rhs ON plain.id = rhs.id WHERE rhs.y < 10",
+            "SELECT lhs.* FROM lhs JOIN rhs ON lhs.id = rhs.id JOIN plain ON rhs.id = plain.id WHERE lhs.x < 10",
         ];
         for join in joins {
-            assert!(compile_read_only_query(&db, &tx, &auth, join).is_err(), "{join}");
+            assert!(
+                compile_read_only_query(&db, &tx, &auth, join, &SqlParams::None).is_err(),
+                "{join}"
+            );
         }
 
         Ok(())
@@ -36,6 +36,7 @@ use std::ops::Deref;
 
 use crate::db::datastore::locking_tx_datastore::MutTxId;
 use crate::error::DBError;
+use crate::sql::policy::row_policy;
 use crate::subscription::query::{run_query, OP_TYPE_FIELD_NAME};
 use crate::{
     client::{ClientActorId, ClientConnectionSender},
@@ -171,7 +172,8 @@ impl QuerySet {
 
     /// Queries all the [`StTableType::User`] tables *right now*
     /// and turns them into [`QueryExpr`],
-    /// the moral equivalent of `SELECT * FROM table`.
+    /// the moral equivalent of `SELECT * FROM table`,
+    /// restricted by the row-level security policy of the table.
     pub(crate) fn get_all(relational_db: &RelationalDB, tx: &MutTxId, auth: &AuthCtx) -> Result<Self, DBError> {
         let tables = relational_db.get_all_tables(tx)?;
         let same_owner = auth.owner == auth.caller;
@@ -179,11 +181,21 @@ impl QuerySet {
             .iter()
             .map(Deref::deref)
             .filter(|t| t.table_type == StTableType::User && (same_owner || t.table_access == StAccess::Public))
-            .map(|src| SupportedQuery {
-                kind: query::Supported::Scan,
-                expr: QueryExpr::new(src),
+            .map(|src| {
+                let mut expr = QueryExpr::new(src);
+                let policy = row_policy(relational_db, tx, auth, src).map_err(|error| DBError::Plan {
+                    sql: query::SUBSCRIBE_TO_ALL_QUERY.into(),
+                    error,
+                })?;
+                if let Some(policy) = policy {
+                    expr = expr.with_select(policy.clause);
+                }
+                Ok(SupportedQuery {
+                    kind: query::Supported::Scan,
+                    expr,
+                })
             })
-            .collect();
+            .collect::<Result<_, DBError>>()?;
 
         Ok(Self(exprs))
     }
@@ -233,8 +245,8 @@ impl QuerySet {
 
                 Semijoin => {
                     if let Some(plan) = IncrementalJoin::new(expr, database_update.tables.iter())? {
-                        let table_id = plan.lhs.table.table_id;
-                        let header = &plan.lhs.table.head;
+                        let table_id = plan.table().table_id;
+                        let header = &plan.table().head;
 
                         // Get the TableOps for this table
                         let (_, table_row_operations) = table_ops
@@ -253,6 +265,11 @@ impl QuerySet {
             }
         }
         for (table_id, (table_name, ops)) in table_ops.into_iter().filter(|(_, (_, ops))| !ops.is_empty()) {
+            // Report the rows replaced by an update, and still matched by a query, as an update.
+            let ops = match database_update.tables.iter().find(|t| t.table_id == table_id) {
+                Some(source) => DatabaseTableUpdate::merge_updates(source, ops),
+                None => ops,
+            };
             output.tables.push(DatabaseTableUpdate {
                 table_id,
                 table_name,
@@ -302,6 +319,7 @@ impl QuerySet {
                             op_type: 1, // Insert
                             row_pk,
                             row,
+                            old: None,
                         });
                     }
                 }
@@ -334,6 +352,7 @@ impl From<Op> for TableOp {
             op_type: op.op_type,
             row_pk: op.row_pk.to_bytes(),
             row: op.row,
+            old: None,
         }
     }
 }
@@ -389,10 +408,15 @@ fn eval_incremental(
 }
 
 /// Helper for evaluating a [`query::Supported::Semijoin`].
+///
+/// The join is a chain of [`IndexJoin`]s:
+/// the source table of the expression is joined with a probe side,
+/// whose source table may in turn be joined with a probe side, and so on.
 struct IncrementalJoin<'a> {
     expr: &'a QueryExpr,
-    lhs: JoinSide<'a>,
-    rhs: JoinSide<'a>,
+    /// The tables of the chain, starting with the source table of `expr`,
+    /// whose rows are the result of the join.
+    sides: Vec<JoinSide<'a>>,
 }
 
 /// One side of an [`IncrementalJoin`].
@@ -406,8 +430,10 @@ struct JoinSide<'a> {
 
 impl JoinSide<'_> {
     /// Return a [`DatabaseTableUpdate`] consisting of only insert operations.
+    ///
+    /// The new row of an update is included as an insert.
     pub fn inserts(&self) -> DatabaseTableUpdate {
-        let ops = self.updates.ops.iter().filter(|op| op.op_type == 1).cloned().collect();
+        let ops = self.updates.split_updates().filter(|op| op.op_type == 1).collect();
         DatabaseTableUpdate {
             table_id: self.updates.table_id,
             table_name: self.updates.table_name.clone(),
@@ -416,8 +442,10 @@ impl JoinSide<'_> {
     }
 
     /// Return a [`DatabaseTableUpdate`] with only delete operations.
+    ///
+    /// The old row of an update is included as a delete.
     pub fn deletes(&self) -> DatabaseTableUpdate {
-        let ops = self.updates.ops.iter().filter(|op| op.op_type == 0).cloned().collect();
+        let ops = self.updates.split_updates().filter(|op| op.op_type == 0).collect();
         DatabaseTableUpdate {
             table_id: self.updates.table_id,
             table_name: self.updates.table_name.clone(),
@@ -434,85 +462,89 @@ impl<'a> IncrementalJoin<'a> {
     /// [`query::Supported::Semijoin`] already. The supplied updates are assumed
     /// to be the full set of updates from a single transaction.
     ///
-    /// If neither side of the join is modified by any of the updates, `None` is
+    /// If no side of the join is modified by any of the updates, `None` is
     /// returned. Otherwise, `Some` [`IncrementalJoin`] is returned with the
-    /// updates partitioned into the respective [`JoinSide`].
+    /// updates partitioned into the respective [`JoinSide`]s.
     ///
     /// An error is returned if the expression is not well-formed.
     pub fn new(
         expr: &'a QueryExpr,
         updates: impl Iterator<Item = &'a DatabaseTableUpdate>,
     ) -> anyhow::Result<Option<Self>> {
-        let mut lhs = expr
-            .source
-            .get_db_table()
-            .map(|table| JoinSide {
+        let mut sides = Vec::new();
+        let mut next = Some(expr);
+        while let Some(expr) = next {
+            let table = expr
+                .source
+                .get_db_table()
+                .context("expression without physical source table")?;
+            sides.push(JoinSide {
                 table,
                 updates: DatabaseTableUpdate {
                     table_id: table.table_id,
                     table_name: table.head.table_name.clone(),
                     ops: vec![],
                 },
-            })
-            .context("expression without physical source table")?;
-        let mut rhs = expr
-            .query
-            .iter()
-            .find_map(|op| match op {
-                expr::Query::IndexJoin(IndexJoin { probe_side: rhs, .. }) => {
-                    rhs.source.get_db_table().map(|table| JoinSide {
-                        table,
-                        updates: DatabaseTableUpdate {
-                            table_id: table.table_id,
-                            table_name: table.head.table_name.clone(),
-                            ops: vec![],
-                        },
-                    })
-                }
+            });
+            next = expr.query.iter().find_map(|op| match op {
+                expr::Query::IndexJoin(IndexJoin { probe_side, .. }) => Some(probe_side),
                 _ => None,
-            })
-            .context("rhs table not found")?;
+            });
+        }
+        anyhow::ensure!(sides.len() > 1, "rhs table not found");
 
         for update in updates {
-            if update.table_id == lhs.table.table_id {
-                lhs.updates.ops.extend(update.ops.iter().cloned());
-            } else if update.table_id == rhs.table.table_id {
-                rhs.updates.ops.extend(update.ops.iter().cloned());
+            // A table may appear on more than one side.
+            for side in sides.iter_mut().filter(|side| side.table.table_id == update.table_id) {
+                side.updates.ops.extend(update.ops.iter().cloned());
             }
         }
 
-        if lhs.updates.ops.is_empty() && rhs.updates.ops.is_empty() {
+        if sides.iter().all(|side| side.updates.ops.is_empty()) {
             Ok(None)
         } else {
-            Ok(Some(Self { expr, lhs, rhs }))
+            Ok(Some(Self { expr, sides }))
         }
     }
 
+    /// The table whose rows are the result of the join.
+    fn table(&self) -> &DbTable {
+        self.sides[0].table
+    }
+
     /// Evaluate this [`IncrementalJoin`].
     ///
     /// The following assumptions are made for the incremental evaluation to be
     /// correct without maintaining a materialized view:
     ///
-    /// * The join is a primary foreign key semijoin, i.e. one row from the
-    ///   right table joins with at most one row from the left table.
-    /// * The rows in the [`DatabaseTableUpdate`]s on either side of the join
+    /// * Each join is a primary foreign key semijoin, i.e. one row from a
+    ///   probe side joins with at most one row from the table it probes.
+    /// * The rows in the [`DatabaseTableUpdate`]s on every side of the join
     ///   are already committed to the underlying "physical" tables.
     /// * We maintain set semantics, i.e. no two rows with the same
     ///   [`PrimaryKey`] can appear in the result.
     ///
-    /// Based on this, we evaluate the join as:
+    /// Based on this, we evaluate the join of the tables `T1`, ..., `Tn` as:
     ///
     /// ```text
-    ///     let inserts = {A+ join B} U {A join B+}
-    ///     let deletes = {A- join B} U {A join B-} U {A- join B-}
+    ///     let inserts = U { T1 join ... join Ti+ join ... join Tn | 1 <= i <= n }
+    ///     let deletes = U { T1' join ... join Tn' | Ti' = Ti- for some, and Tj' = Tj for all other, 1 <= i, j <= n }
     ///
     ///     (deletes \ inserts) || (inserts \ deletes)
     /// ```
     ///
+    /// That is, an insert into any one of the tables may add rows to the result,
+    /// while a delete from any non-empty subset of the tables may remove rows from it.
+    /// For two tables `A` and `B`, this is:
+    ///
+    /// ```text
+    ///     let inserts = {A+ join B} U {A join B+}
+    ///     let deletes = {A- join B} U {A join B-} U {A- join B-}
+    /// ```
+    ///
     /// Where:
     ///
-    /// * `A`:  Committed table to the LHS of the join.
-    /// * `B`:  Committed table to the RHS of the join.
+    /// * `Ti`: Committed table at position `i` of the chain of joins.
     /// * `+`:  Virtual table of only the insert operations against the annotated table.
     /// * `-`:  Virtual table of only the delete operations against the annotated table.
     /// * `U`:  Set union.
@@ -526,57 +558,34 @@ impl<'a> IncrementalJoin<'a> {
         tx: &mut MutTxId,
         auth: &AuthCtx,
     ) -> Result<impl Iterator<Item = Op>, DBError> {
-        let mut inserts = {
-            let lhs_virt = query::to_mem_table(self.expr.clone(), &self.lhs.inserts());
-            let rhs_virt = self.to_mem_table_rhs(self.rhs.inserts());
-
-            // {A+ join B}
-            let a = eval_incremental(db, tx, auth, &lhs_virt)?;
-            // {A join B+}
-            let b = run_query(db, tx, &rhs_virt, *auth)?
-                .into_iter()
-                .filter(|result| !result.data.is_empty())
-                .flat_map(|result| {
-                    result.data.into_iter().map(move |row| {
-                        Op {
-                            op_type: 1, // Insert
-                            row_pk: pk_for_row(&row),
-                            row: row.data,
-                        }
-                    })
-                });
-            // {A+ join B} U {A join B+}
-            let mut set = a.map(|op| (op.row_pk, op)).collect::<HashMap<PrimaryKey, Op>>();
-            set.extend(b.map(|op| (op.row_pk, op)));
-            set
-        };
-        let mut deletes = {
-            let lhs_virt = query::to_mem_table(self.expr.clone(), &self.lhs.deletes());
-            let rhs_virt = self.to_mem_table_rhs(self.rhs.deletes());
-
-            // {A- join B}
-            let a = eval_incremental(db, tx, auth, &lhs_virt)?;
-            // {A join B-}
-            let b = run_query(db, tx, &rhs_virt, *auth)?
-                .into_iter()
-                .filter(|result| !result.data.is_empty())
-                .flat_map(|result| {
-                    result.data.into_iter().map(move |row| {
-                        Op {
-                            op_type: 0, // Delete
-                            row_pk: pk_for_row(&row),
-                            row: row.data,
-                        }
-                    })
-                });
-            // {A- join B-}
-            let c = eval_incremental(db, tx, auth, &query::to_mem_table(rhs_virt, &self.lhs.deletes()))?;
-            // {A- join B} U {A join B-} U {A- join B-}
-            let mut set = a.map(|op| (op.row_pk, op)).collect::<HashMap<PrimaryKey, Op>>();
-            set.extend(b.map(|op| (op.row_pk, op)));
-            set.extend(c.map(|op| (op.row_pk, op)));
-            set
-        };
+        let inserts_by_side = self.sides.iter().map(JoinSide::inserts).collect::<Vec<_>>();
+        let deletes_by_side = self.sides.iter().map(JoinSide::deletes).collect::<Vec<_>>();
+
+        let mut inserts = HashMap::new();
+        for (pos, virt) in inserts_by_side.iter().enumerate() {
+            if virt.ops.is_empty() {
+                continue;
+            }
+            let mut virts = vec![None; self.sides.len()];
+            virts[pos] = Some(virt);
+            // {T1 join ... join Ti+ join ... join Tn}
+            self.eval_with(db, tx, auth, &virts, 1, &mut inserts)?;
+        }
+
+        let mut deletes = HashMap::new();
+        // Every non-empty subset of the sides, as a bit set.
+        for subset in 1..1usize << self.sides.len() {
+            let virts = deletes_by_side
+                .iter()
+                .enumerate()
+                .map(|(pos, virt)| (subset & (1 << pos) != 0).then_some(virt))
+                .collect::<Vec<_>>();
+            if virts.iter().flatten().any(|virt| virt.ops.is_empty()) {
+                continue;
+            }
+            // {T1' join ... join Tn'}
+            self.eval_with(db, tx, auth, &virts, 0, &mut deletes)?;
+        }
 
         let symmetric_difference = inserts
             .keys()
@@ -591,30 +600,60 @@ impl<'a> IncrementalJoin<'a> {
         Ok(deletes.into_values().chain(inserts.into_values()))
     }
 
-    /// Replace the RHS of the join with a virtual [`MemTable`] of the operations
-    /// in [`DatabaseTableUpdate`].
-    fn to_mem_table_rhs(&self, updates: DatabaseTableUpdate) -> QueryExpr {
-        fn as_rel_value(
-            TableOp {
-                op_type: _,
-                row_pk,
-                row,
-            }: &TableOp,
-        ) -> RelValue {
+    /// Evaluate the join with the table at each position of the chain
+    /// replaced by the virtual table in `virts` at that position, if any,
+    /// and collect the resulting rows as [`Op`]s of type `op_type` into `set`.
+    fn eval_with(
+        &self,
+        db: &RelationalDB,
+        tx: &mut MutTxId,
+        auth: &AuthCtx,
+        virts: &[Option<&DatabaseTableUpdate>],
+        op_type: u8,
+        set: &mut HashMap<PrimaryKey, Op>,
+    ) -> Result<(), DBError> {
+        let expr = Self::to_mem_tables(self.expr, virts);
+        for result in run_query(db, tx, &expr, *auth)? {
+            set.extend(result.data.into_iter().map(|row| {
+                let row_pk = pk_for_row(&row);
+                (
+                    row_pk,
+                    Op {
+                        op_type,
+                        row_pk,
+                        row: row.data,
+                    },
+                )
+            }));
+        }
+        Ok(())
+    }
+
+    /// Replace the source of `expr`, and of each of its nested probe sides,
+    /// with a virtual [`MemTable`] of the operations in the [`DatabaseTableUpdate`]
+    /// at the corresponding position in `virts`, if any.
+    fn to_mem_tables(expr: &QueryExpr, virts: &[Option<&DatabaseTableUpdate>]) -> QueryExpr {
+        fn as_rel_value(TableOp { row_pk, row, .. }: &TableOp) -> RelValue {
             let mut bytes: &[u8] = row_pk.as_ref();
             RelValue::new(row.clone(), Some(DataKey::decode(&mut bytes).unwrap()))
         }
 
-        let mut q = self.expr.clone();
+        let mut q = expr.clone();
+        let Some((virt, virts)) = virts.split_first() else {
+            return q;
+        };
+        if let Some(updates) = virt {
+            let table = q.source.get_db_table().unwrap();
+            let virt = MemTable::new(
+                table.head.clone(),
+                table.table_access,
+                updates.ops.iter().map(as_rel_value).collect::<Vec<_>>(),
+            );
+            q.source = SourceExpr::MemTable(virt);
+        }
         for op in q.query.iter_mut() {
-            if let expr::Query::IndexJoin(IndexJoin { probe_side: rhs, .. }) = op {
-                let virt = MemTable::new(
-                    self.rhs.table.head.clone(),
-                    self.rhs.table.table_access,
-                    updates.ops.iter().map(as_rel_value).collect::<Vec<_>>(),
-                );
-                rhs.source = SourceExpr::MemTable(virt);
-
+            if let expr::Query::IndexJoin(IndexJoin { probe_side, .. }) = op {
+                *probe_side = Self::to_mem_tables(probe_side, virts);
                 break;
             }
         }
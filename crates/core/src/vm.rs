@@ -47,6 +47,15 @@ pub fn build_query<'a>(
                 let iter = result.select(move |row| cmp.compare(row, &header));
                 Box::new(iter)
             }
+            Query::IndexSeek(IndexSeek { table, col_id, values }) if db_table => {
+                Box::new(IndexSeekValues::new(stdb, tx, table, col_id, values))
+            }
+            Query::IndexSeek(index_seek) => {
+                let header = result.head().clone();
+                let cmp: ColumnOp = index_seek.into();
+                let iter = result.select(move |row| cmp.compare(row, &header));
+                Box::new(iter)
+            }
             Query::IndexJoin(join) if db_table => Box::new(IndexSemiJoin::new(
                 stdb,
                 tx,
@@ -79,6 +88,18 @@ pub fn build_query<'a>(
                 let iter = join_inner(stdb, tx, result, join, false)?;
                 Box::new(iter)
             }
+            Query::Aggregate(Aggregate { group_by, aggregates }) => {
+                let iter = result.group_by(&group_by, &aggregates)?;
+                Box::new(iter)
+            }
+            Query::Sort(Sort { keys, top }) => {
+                let iter = result.sort(&keys, top)?;
+                Box::new(iter)
+            }
+            Query::Limit(Limit { offset, limit }) => {
+                let iter = result.limit(offset, limit);
+                Box::new(iter)
+            }
         }
     }
     Ok(result)
@@ -157,6 +178,67 @@ fn iter_by_col_range<'a>(
     Ok(Box::new(IndexCursor::new(table, iter)?) as Box<IterRows<'_>>)
 }
 
+// An index seek operator that returns the rows with any of several values in an indexed column.
+pub struct IndexSeekValues<'a> {
+    // The table on which the index is defined.
+    pub table: DbTable,
+    // The column id for which the index is defined.
+    pub col_id: ColId,
+    // The values still to be looked up in the index.
+    pub values: std::vec::IntoIter<AlgebraicValue>,
+    // An iterator for the rows with the current value.
+    pub index_iter: Option<IterByColEq<'a>>,
+    // A reference to the database.
+    pub db: &'a RelationalDB,
+    // A reference to the current transaction.
+    pub tx: &'a MutTxId,
+}
+
+impl<'a> IndexSeekValues<'a> {
+    pub fn new(
+        db: &'a RelationalDB,
+        tx: &'a MutTxId,
+        table: DbTable,
+        col_id: ColId,
+        values: Vec<AlgebraicValue>,
+    ) -> Self {
+        IndexSeekValues {
+            table,
+            col_id,
+            values: values.into_iter(),
+            index_iter: None,
+            db,
+            tx,
+        }
+    }
+}
+
+impl<'a> RelOps for IndexSeekValues<'a> {
+    fn head(&self) -> &Header {
+        &self.table.head
+    }
+
+    fn row_count(&self) -> RowCount {
+        RowCount::unknown()
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn next(&mut self) -> Result<Option<RelValue>, ErrorVm> {
+        loop {
+            // Return a row from the current index iterator, if not exhausted.
+            if let Some(row) = self.index_iter.as_mut().and_then(|iter| iter.next()) {
+                return Ok(Some(row.to_rel_value()));
+            }
+            // Otherwise seek the next value.
+            let Some(value) = self.values.next() else {
+                return Ok(None);
+            };
+            let table_id = self.table.table_id;
+            self.index_iter = Some(self.db.iter_by_col_eq(self.tx, table_id, self.col_id, value)?);
+        }
+    }
+}
+
 // An index join operator that returns matching rows from the index side.
 pub struct IndexSemiJoin<'a, Rhs: RelOps> {
     // An iterator for the probe side.
@@ -518,6 +600,7 @@ pub(crate) mod tests {
     use nonempty::NonEmpty;
     use spacetimedb_lib::error::ResultTest;
     use spacetimedb_lib::relation::{DbTable, FieldName};
+    use spacetimedb_lib::IndexType;
     use spacetimedb_primitives::TableId;
     use spacetimedb_sats::{product, AlgebraicType, ProductType, ProductValue};
     use spacetimedb_vm::dsl::*;
@@ -721,6 +804,7 @@ pub(crate) mod tests {
                 table_id,
                 cols: NonEmpty::new(0.into()),
                 is_unique: true,
+                index_type: IndexType::BTree,
             }
             .into(),
             q,
@@ -126,4 +126,6 @@ pub enum RelationError {
     TypeInference(FieldName, TypeError),
     #[error("Field declaration only support `table.field` or `field`. It gets instead `{0}`")]
     FieldPathInvalid(String),
+    #[error("Parameter `{0}` was not bound to a value")]
+    UnboundParam(String),
 }
@@ -43,6 +43,13 @@ pub use spacetimedb_sats as sats;
 
 pub const MODULE_ABI_MAJOR_VERSION: u16 = 7;
 
+/// The prefix given to the name of a table with an incompatible schema change
+/// while the module's `migrate` reducer runs.
+///
+/// The table's rows can be read from within the reducer under the prefixed name;
+/// the table is dropped once the reducer returns.
+pub const MIGRATE_TABLE_PREFIX: &str = "__old__";
+
 // if it ends up we need more fields in the future, we can split one of them in two
 #[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
 pub struct VersionTuple {
@@ -92,6 +99,7 @@ impl std::fmt::Display for VersionTuple {
 extern crate self as spacetimedb_lib;
 
 //WARNING: Change this structure(or any of their members) is an ABI change.
+// The layouts described by earlier modules are decoded by `ModuleDef::decode`.
 #[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord, de::Deserialize, ser::Serialize)]
 pub struct TableDef {
     pub name: String,
@@ -101,6 +109,11 @@ pub struct TableDef {
     pub indexes: Vec<IndexDef>,
     pub table_type: StTableType,
     pub table_access: StAccess,
+    pub foreign_keys: Vec<ForeignKeyDef>,
+    pub checks: Vec<CheckDef>,
+    /// The `SQL` predicate restricting the rows visible to the identities other than the owner,
+    /// where `:sender` is bound to the identity of the client.
+    pub row_policy: Option<String>,
 }
 
 #[derive(Debug, Clone, de::Deserialize, ser::Serialize)]
@@ -180,10 +193,82 @@ pub struct ModuleDef {
     pub misc_exports: Vec<MiscModuleExport>,
 }
 
+impl ModuleDef {
+    /// Decodes the `ModuleDef` described by a module.
+    ///
+    /// Modules built before [`TableDef`] had `foreign_keys`, `checks` and `row_policy`
+    /// describe their tables without them, in which case their tables have none of them.
+    pub fn decode(bytes: &[u8]) -> Result<Self, buffer::DecodeError> {
+        let decode_exact = |bytes: &mut &[u8]| {
+            let def = bsatn::from_reader::<Self>(bytes)?;
+            if !bytes.is_empty() {
+                return Err(buffer::DecodeError::Other(format!("{} trailing bytes", bytes.len())));
+            }
+            Ok(def)
+        };
+        decode_exact(&mut &*bytes).or_else(|err| {
+            let bytes = &mut &*bytes;
+            let Ok(def) = bsatn::from_reader::<ModuleDefV0>(bytes) else {
+                return Err(err);
+            };
+            if !bytes.is_empty() {
+                return Err(err);
+            }
+            Ok(def.into())
+        })
+    }
+}
+
+/// The layout of [`ModuleDef`] before [`TableDef`] had `foreign_keys`, `checks` and `row_policy`.
+#[derive(de::Deserialize)]
+struct ModuleDefV0 {
+    typespace: sats::Typespace,
+    tables: Vec<TableDefV0>,
+    reducers: Vec<ReducerDef>,
+    misc_exports: Vec<MiscModuleExport>,
+}
+
+#[derive(de::Deserialize)]
+struct TableDefV0 {
+    name: String,
+    data: sats::AlgebraicTypeRef,
+    column_attrs: Vec<ColumnIndexAttribute>,
+    indexes: Vec<IndexDef>,
+    table_type: StTableType,
+    table_access: StAccess,
+}
+
+impl From<ModuleDefV0> for ModuleDef {
+    fn from(def: ModuleDefV0) -> Self {
+        let tables = def
+            .tables
+            .into_iter()
+            .map(|table| TableDef {
+                name: table.name,
+                data: table.data,
+                column_attrs: table.column_attrs,
+                indexes: table.indexes,
+                table_type: table.table_type,
+                table_access: table.table_access,
+                foreign_keys: Vec::new(),
+                checks: Vec::new(),
+                row_policy: None,
+            })
+            .collect();
+        ModuleDef {
+            typespace: def.typespace,
+            tables,
+            reducers: def.reducers,
+            misc_exports: def.misc_exports,
+        }
+    }
+}
+
 // an enum to keep it extensible without breaking abi
 #[derive(Debug, Clone, de::Deserialize, ser::Serialize)]
 pub enum MiscModuleExport {
     TypeAlias(TypeAlias),
+    Schedule(ScheduleDef),
 }
 
 #[derive(Debug, Clone, de::Deserialize, ser::Serialize)]
@@ -205,6 +290,99 @@ pub enum IndexType {
     Hash,
 }
 
+impl IndexType {
+    pub const fn to_u8(self) -> u8 {
+        match self {
+            Self::BTree => 0,
+            Self::Hash => 1,
+        }
+    }
+
+    pub const fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0 => Some(Self::BTree),
+            1 => Some(Self::Hash),
+            _ => None,
+        }
+    }
+}
+
+/// A column whose values must be present in the column `column` of the table `table`.
+#[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord, de::Deserialize, ser::Serialize)]
+pub struct ForeignKeyDef {
+    pub name: String,
+    pub col_id: u8,
+    pub table: String,
+    pub column: String,
+    pub on_delete: ReferentialAction,
+}
+
+/// A `SQL` predicate, like `amount >= 0`, that every row of the table must satisfy.
+#[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord, de::Deserialize, ser::Serialize)]
+pub struct CheckDef {
+    pub name: String,
+    pub expr: String,
+}
+
+/// A reducer that the host calls on a recurring schedule.
+///
+/// Each call is delivered at least once: the host restarting right after a call committed
+/// makes that call again.
+#[derive(Debug, Clone, Eq, PartialEq, de::Deserialize, ser::Serialize)]
+pub struct ScheduleDef {
+    pub reducer: String,
+    pub kind: ScheduleKind,
+    pub catch_up: CatchUp,
+}
+
+/// When a scheduled reducer is called.
+#[derive(Debug, Clone, Eq, PartialEq, de::Deserialize, ser::Serialize)]
+pub enum ScheduleKind {
+    /// At the times matching a cron expression, like `0 */5 * * * *`.
+    Cron(String),
+    /// Every so many microseconds, however long each call takes.
+    FixedRate(u64),
+    /// So many microseconds after each call ends.
+    FixedDelay(u64),
+}
+
+/// Which of the calls of a scheduled reducer missed while the database was down are made.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, de::Deserialize, ser::Serialize)]
+pub enum CatchUp {
+    /// None of them.
+    Skip,
+    /// A single call, in place of all of them.
+    Once,
+    /// Every one of them, one after the other.
+    All,
+}
+
+/// What happens to the rows referencing a row that is deleted.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, PartialOrd, Ord, de::Deserialize, ser::Serialize)]
+pub enum ReferentialAction {
+    /// The transaction fails.
+    Restrict,
+    /// The referencing rows are deleted too.
+    Cascade,
+}
+
+impl ReferentialAction {
+    pub const fn to_u8(self) -> u8 {
+        match self {
+            Self::Restrict => 0,
+            Self::Cascade => 1,
+        }
+    }
+
+    pub const fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0 => Some(Self::Restrict),
+            1 => Some(Self::Cascade),
+            _ => None,
+        }
+    }
+}
+
 // NOTE: Duplicated in `crates/bindings-macro/src/lib.rs`
 bitflags::bitflags! {
     #[derive(Debug, Default, Clone, Copy, Eq, PartialEq, PartialOrd, Ord)]
@@ -222,6 +400,10 @@ bitflags::bitflags! {
         const PRIMARY_KEY = Self::UNIQUE.bits() | 0b1000;
         /// PrimaryKey + AutoInc
         const PRIMARY_KEY_AUTO = Self::PRIMARY_KEY.bits() | Self::AUTO_INC.bits();
+        /// References the column of another table
+        const FOREIGN_KEY = 0b1_0000;
+        /// Restricts the rows to those satisfying a predicate
+        const CHECK = 0b10_0000;
     }
 }
 
@@ -238,6 +420,12 @@ impl ColumnIndexAttribute {
     pub const fn is_primary(self) -> bool {
         self.contains(Self::PRIMARY_KEY)
     }
+    pub const fn is_foreign_key(self) -> bool {
+        self.contains(Self::FOREIGN_KEY)
+    }
+    pub const fn is_check(self) -> bool {
+        self.contains(Self::CHECK)
+    }
 }
 
 impl TryFrom<u8> for ColumnIndexAttribute {
@@ -260,3 +448,74 @@ impl ser::Serialize for ColumnIndexAttribute {
         serializer.serialize_u8(self.bits())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// [`TableDef`] as described by the modules built before it had foreign keys, checks and row policies.
+    #[derive(ser::Serialize)]
+    struct LegacyTableDef {
+        name: String,
+        data: sats::AlgebraicTypeRef,
+        column_attrs: Vec<ColumnIndexAttribute>,
+        indexes: Vec<IndexDef>,
+        table_type: StTableType,
+        table_access: StAccess,
+    }
+
+    #[derive(ser::Serialize)]
+    struct LegacyModuleDef {
+        typespace: sats::Typespace,
+        tables: Vec<LegacyTableDef>,
+        reducers: Vec<ReducerDef>,
+        misc_exports: Vec<MiscModuleExport>,
+    }
+
+    fn table(name: &str) -> LegacyTableDef {
+        LegacyTableDef {
+            name: name.into(),
+            data: sats::AlgebraicTypeRef(0),
+            column_attrs: vec![ColumnIndexAttribute::UNIQUE],
+            indexes: vec![IndexDef {
+                name: format!("{name}_id"),
+                ty: IndexType::BTree,
+                col_ids: vec![0],
+            }],
+            table_type: StTableType::User,
+            table_access: StAccess::Public,
+        }
+    }
+
+    #[test]
+    fn test_decode_legacy_module_def() {
+        let legacy = LegacyModuleDef {
+            typespace: sats::Typespace::new(vec![AlgebraicType::product([AlgebraicType::U32])]),
+            tables: vec![table("a"), table("b")],
+            reducers: vec![ReducerDef {
+                name: "r".into(),
+                args: vec![],
+            }],
+            misc_exports: vec![],
+        };
+        let bytes = bsatn::to_vec(&legacy).unwrap();
+
+        let def = ModuleDef::decode(&bytes).unwrap();
+        assert_eq!(def.tables.len(), 2);
+        for (table, legacy) in def.tables.iter().zip(&legacy.tables) {
+            assert_eq!(table.name, legacy.name);
+            assert_eq!(table.column_attrs, legacy.column_attrs);
+            assert_eq!(table.indexes, legacy.indexes);
+            assert!(table.foreign_keys.is_empty());
+            assert!(table.checks.is_empty());
+            assert_eq!(table.row_policy, None);
+        }
+        assert_eq!(def.reducers.len(), 1);
+
+        // The current layout still decodes as is.
+        let mut current = def;
+        current.tables[1].row_policy = Some("id = 1".into());
+        let def = ModuleDef::decode(&bsatn::to_vec(&current).unwrap()).unwrap();
+        assert_eq!(def.tables, current.tables);
+    }
+}
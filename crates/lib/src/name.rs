@@ -50,6 +50,19 @@ pub enum PublishOp {
     Updated,
 }
 
+/// Summary of the automatic migration of a table by the update of a database.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MigratedTable {
+    pub table_name: String,
+    /// The columns appended to the table.
+    pub added_columns: Vec<String>,
+    /// The existing columns whose type was widened or extended.
+    pub altered_columns: Vec<String>,
+    /// The number of stored rows which were rewritten.
+    pub rows_migrated: u32,
+}
+
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PublishResult {
@@ -67,6 +80,10 @@ pub enum PublishResult {
         /// or not.
         address: Address,
         op: PublishOp,
+        /// The tables whose schema was migrated automatically by an update,
+        /// empty if all existing tables were compatible as-is.
+        #[cfg_attr(feature = "serde", serde(default))]
+        migrated_tables: Vec<MigratedTable>,
     },
 
     // TODO: below variants are obsolete with control db module
@@ -16,6 +16,10 @@ pub enum OpCmp {
     LtEq,
     Gt,
     GtEq,
+    /// `LIKE`, matching a `String` against a pattern where `%` matches any sequence of characters
+    /// and `_` any single character.
+    Like,
+    NotLike,
 }
 
 impl From<OpCmp> for &str {
@@ -27,16 +31,22 @@ impl From<OpCmp> for &str {
             OpCmp::LtEq => "std::cmp::le",
             OpCmp::Gt => "std::cmp::gt",
             OpCmp::GtEq => "std::cmp::ge",
+            OpCmp::Like => "std::cmp::like",
+            OpCmp::NotLike => "std::cmp::not_like",
         }
     }
 }
 
 impl OpCmp {
     /// Reverse the order of the `cmp`, to helps in reducing the cases on evaluation, ie:
+    ///
+    /// `LIKE` is not reversible, as only its right side is a pattern, so it is returned as is.
     pub fn reverse(self) -> Self {
         match self {
             OpCmp::Eq => self,
             OpCmp::NotEq => self,
+            OpCmp::Like => self,
+            OpCmp::NotLike => self,
             OpCmp::Lt => OpCmp::Gt,
             OpCmp::LtEq => OpCmp::GtEq,
             OpCmp::Gt => OpCmp::Lt,
@@ -121,6 +131,8 @@ impl fmt::Display for OpCmp {
             OpCmp::LtEq => "<=",
             OpCmp::Gt => ">",
             OpCmp::GtEq => ">=",
+            OpCmp::Like => "like",
+            OpCmp::NotLike => "not like",
         };
         write!(f, "{x}")
     }
@@ -188,3 +200,77 @@ impl fmt::Display for OpQuery {
         }
     }
 }
+
+/// Returns whether `text` matches the `LIKE` `pattern`,
+/// where `%` matches any sequence of characters, `_` any single character,
+/// and `\` escapes the following character.
+pub fn like(text: &str, pattern: &str) -> bool {
+    enum Token {
+        AnySeq,
+        AnyChar,
+        Char(char),
+    }
+
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '%' => Token::AnySeq,
+            '_' => Token::AnyChar,
+            '\\' => Token::Char(chars.next().unwrap_or('\\')),
+            c => Token::Char(c),
+        });
+    }
+    let text = text.chars().collect::<Vec<_>>();
+
+    // Matches greedily, backtracking to the last `%` on a mismatch
+    let (mut t, mut p) = (0, 0);
+    let mut last_any_seq = None;
+    while t < text.len() {
+        match tokens.get(p) {
+            Some(Token::AnySeq) => {
+                last_any_seq = Some((p + 1, t));
+                p += 1;
+            }
+            Some(Token::AnyChar) => {
+                t += 1;
+                p += 1;
+            }
+            Some(Token::Char(c)) if *c == text[t] => {
+                t += 1;
+                p += 1;
+            }
+            _ => match last_any_seq {
+                Some((after, skipped)) => {
+                    last_any_seq = Some((after, skipped + 1));
+                    p = after;
+                    t = skipped + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    tokens[p..].iter().all(|x| matches!(x, Token::AnySeq))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::like;
+
+    #[test]
+    fn test_like() {
+        assert!(like("health", "health"));
+        assert!(like("health", "he%"));
+        assert!(like("health", "%th"));
+        assert!(like("health", "%al%"));
+        assert!(like("health", "h_alth"));
+        assert!(like("", "%"));
+        assert!(like("100%", "100\\%"));
+
+        assert!(!like("health", "he"));
+        assert!(!like("health", "_ealt"));
+        assert!(!like("1000", "100\\%"));
+        assert!(!like("Health", "health"));
+        assert!(!like(&"a".repeat(100), &format!("{}b", "%a".repeat(50))));
+    }
+}
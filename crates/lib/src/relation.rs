@@ -120,6 +120,12 @@ impl FieldName {
 pub enum FieldExpr {
     Name(FieldName),
     Value(AlgebraicValue),
+    /// A placeholder of a prepared statement, like `$1`, to be replaced by a value of type `ty`
+    /// before the statement is run.
+    Param {
+        name: String,
+        ty: AlgebraicType,
+    },
 }
 
 impl fmt::Display for FieldName {
@@ -146,6 +152,9 @@ impl fmt::Display for FieldExpr {
                 let ts = Typespace::new(vec![]);
                 write!(f, "{}", WithTypespace::new(&ts, &ty).with_value(x).to_satn())
             }
+            FieldExpr::Param { name, .. } => {
+                write!(f, "{name}")
+            }
         }
     }
 }
@@ -288,6 +297,15 @@ impl Header {
                         col.type_of(),
                     ));
                 }
+                FieldExpr::Param { ty, .. } => {
+                    p.push(Column::new(
+                        FieldName::Pos {
+                            table: self.table_name.clone(),
+                            field: pos,
+                        },
+                        ty,
+                    ));
+                }
             }
         }
 
@@ -429,6 +447,7 @@ impl<'a> RelValueRef<'a> {
                     .ok_or_else(|| RelationError::FieldNotFoundAtPos(pos, col.clone()))?
             }
             FieldExpr::Value(x) => x,
+            FieldExpr::Param { name, .. } => return Err(RelationError::UnboundParam(name.clone())),
         };
 
         Ok(val)
@@ -449,6 +468,7 @@ impl<'a> RelValueRef<'a> {
                 FieldExpr::Value(col) => {
                     elements.push(col.clone());
                 }
+                FieldExpr::Param { name, .. } => return Err(RelationError::UnboundParam(name.clone())),
             }
         }
 
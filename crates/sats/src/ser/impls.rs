@@ -45,6 +45,25 @@ macro_rules! impl_prim {
 
 impl_serialize!([] (), (self, ser) => ser.serialize_seq_product(0)?.end());
 
+macro_rules! impl_tuple {
+    ($(($($ty:ident $idx:tt),+))*) => {
+        $(impl_serialize!([$($ty: Serialize),+] ($($ty,)+), (self, ser) => {
+            let mut prod = ser.serialize_seq_product([$(stringify!($ty)),+].len())?;
+            $(prod.serialize_element(&self.$idx)?;)+
+            prod.end()
+        });)*
+    };
+}
+
+impl_tuple! {
+    (A 0)
+    (A 0, B 1)
+    (A 0, B 1, C 2)
+    (A 0, B 1, C 2, D 3)
+    (A 0, B 1, C 2, D 3, E 4)
+    (A 0, B 1, C 2, D 3, E 4, F 5)
+}
+
 impl_prim! {
     (bool, serialize_bool) /*(u8, serialize_u8)*/ (u16, serialize_u16)
     (u32, serialize_u32) (u64, serialize_u64) (u128, serialize_u128) (i8, serialize_i8)
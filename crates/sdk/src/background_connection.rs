@@ -3,22 +3,191 @@ use crate::callbacks::{
 };
 use crate::client_api_messages;
 use crate::client_cache::{ClientCache, ClientCacheView, RowCallbackReminders};
-use crate::global_connection::CLIENT_CACHE;
+use crate::db_connection::ConnectionId;
 use crate::identity::Credentials;
 use crate::reducer::{AnyReducerEvent, Reducer};
 use crate::spacetime_module::SpacetimeModule;
-use crate::websocket::DbConnection;
+use crate::websocket::WsConnection;
 use anyhow::{Context, Result};
 use futures::stream::StreamExt;
 use futures_channel::mpsc;
 use spacetimedb_sats::bsatn;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::runtime::{self, Builder, Runtime};
+use tokio::sync::Notify;
 use tokio::task::JoinHandle;
 
 /// A thread-safe mutable place that can be shared by multiple referents.
 type SharedCell<T> = Arc<Mutex<T>>;
 
+type SendChan = mpsc::UnboundedSender<client_api_messages::Message>;
+
+/// How a `BackgroundDbConnection` re-establishes its WebSocket after the server drops it.
+///
+/// Reconnection is opt-in, via [`crate::set_reconnect_policy`]
+/// or [`crate::DbConnection::set_reconnect_policy`].
+/// Each attempt reuses the connection's `Credentials` and `Address`,
+/// then replays the most recent `subscribe` query set.
+/// The resulting `SubscriptionUpdate` is reconciled against the `ClientCache`,
+/// so insert and delete callbacks fire only for rows which changed while disconnected.
+///
+/// On-connect and on-subscription-applied callbacks run again after each successful reconnect.
+/// On-disconnect callbacks run only once the connection ends for good:
+/// upon calling `disconnect`, or after `max_attempts` consecutive failed attempts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    /// The delay before the first reconnect attempt.
+    pub initial_delay: Duration,
+
+    /// The delay doubles after each failed attempt, up to `max_delay`.
+    pub max_delay: Duration,
+
+    /// Give up after this many consecutive failed attempts.
+    /// `None` to retry forever.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// The delay before the zero-indexed `attempt`.
+    fn delay(&self, attempt: u32) -> Duration {
+        self.initial_delay
+            .checked_mul(1 << attempt.min(31))
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+}
+
+/// Set by `disconnect` to tell the `receiver_loop`
+/// that the connection ended on purpose, and should not be re-established.
+#[derive(Default)]
+struct DisconnectSignal {
+    requested: AtomicBool,
+    notify: Notify,
+}
+
+impl DisconnectSignal {
+    fn request(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+        // `notify_one` stores a permit if the `receiver_loop` is not currently waiting,
+        // so a request made between its checks is not lost.
+        self.notify.notify_one();
+    }
+
+    fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+}
+
+/// Everything the `receiver_loop` needs to re-establish a dropped connection.
+struct Reconnector {
+    handle: runtime::Handle,
+    uri: http::Uri,
+    db_name: String,
+    policy: SharedCell<Option<ReconnectPolicy>>,
+    send_chan: SharedCell<Option<SendChan>>,
+    subscribed_queries: SharedCell<Option<Vec<String>>>,
+    disconnect: Arc<DisconnectSignal>,
+}
+
+impl Reconnector {
+    /// Re-establish the connection according to the current `ReconnectPolicy`,
+    /// replay the most recent subscription, and return the new incoming message stream.
+    ///
+    /// Return `None` if reconnection is disabled, `disconnect` was called,
+    /// or the policy's attempts are exhausted.
+    async fn reconnect(
+        &self,
+        credentials: &Mutex<CredentialStore>,
+    ) -> Option<mpsc::UnboundedReceiver<client_api_messages::Message>> {
+        // Messages sent before we reconnect should fail, rather than vanish into the closed socket.
+        self.send_chan.lock().expect("SendChan Mutex is poisoned").take();
+
+        let policy = self.policy.lock().expect("ReconnectPolicy Mutex is poisoned").clone()?;
+        let (credentials, client_address) = {
+            let lock = credentials.lock().expect("CredentialStore Mutex is poisoned");
+            (lock.credentials(), lock.address()?)
+        };
+
+        let mut attempt = 0;
+        loop {
+            if policy.max_attempts.map_or(false, |max| attempt >= max) {
+                log::warn!(
+                    "Giving up on reconnecting to {:?} after {} attempts",
+                    self.db_name,
+                    attempt
+                );
+                return None;
+            }
+            if self.disconnect.is_requested() {
+                return None;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(policy.delay(attempt)) => (),
+                _ = self.disconnect.notify.notified() => return None,
+            }
+
+            log::info!("Reconnecting to {:?}, attempt {}", self.db_name, attempt + 1);
+            let connection = match WsConnection::connect(
+                self.uri.clone(),
+                &self.db_name,
+                credentials.as_ref(),
+                client_address,
+            )
+            .await
+            {
+                Ok(connection) => connection,
+                Err(e) => {
+                    log::warn!("Failed to reconnect to {:?}: {:?}", self.db_name, e);
+                    attempt += 1;
+                    continue;
+                }
+            };
+            // The message loop ends on its own once the socket closes,
+            // which the `receiver_loop` observes as the end of `recv_chan`.
+            let (_, recv_chan, send_chan) = connection.spawn_message_loop(&self.handle);
+
+            // Hold the query lock across replaying and publishing the new `send_chan`,
+            // so a concurrent `subscribe` is ordered entirely before or after the replay.
+            let queries = self.subscribed_queries.lock().expect("Queries Mutex is poisoned");
+            if let Some(queries) = &*queries {
+                if let Err(e) = send_chan.unbounded_send(subscribe_message(queries.clone())) {
+                    log::warn!("Failed to replay subscription after reconnecting: {:?}", e);
+                }
+            }
+            let mut send_chan_lock = self.send_chan.lock().expect("SendChan Mutex is poisoned");
+            if self.disconnect.is_requested() {
+                // `disconnect` was called while we were connecting.
+                // Dropping `send_chan` closes the new socket.
+                return None;
+            }
+            *send_chan_lock = Some(send_chan);
+            return Some(recv_chan);
+        }
+    }
+}
+
+fn subscribe_message(queries: Vec<String>) -> client_api_messages::Message {
+    client_api_messages::Message {
+        r#type: Some(client_api_messages::message::Type::Subscribe(
+            client_api_messages::Subscribe {
+                query_strings: queries,
+                params: Vec::new(),
+            },
+        )),
+    }
+}
+
 pub struct BackgroundDbConnection {
     /// `Some` if not within the context of an outer runtime. The `Runtime` must
     /// then live as long as `Self`.
@@ -26,8 +195,23 @@ pub struct BackgroundDbConnection {
     runtime: Option<Runtime>,
 
     handle: runtime::Handle,
+
+    /// Distinguishes this connection's `ClientCache` states from those of other connections
+    /// when resolving `global_connection::CURRENT_STATE`.
+    pub(crate) id: ConnectionId,
+
+    /// None if not connected.
+    /// Shared with the `receiver_loop`, which replaces it upon reconnecting.
+    send_chan: SharedCell<Option<SendChan>>,
+
+    /// The queries most recently passed to `subscribe`, to be replayed upon reconnecting.
+    subscribed_queries: SharedCell<Option<Vec<String>>>,
+
+    /// None to leave dropped connections closed.
+    reconnect_policy: SharedCell<Option<ReconnectPolicy>>,
+
     /// None if not yet connected.
-    send_chan: Option<mpsc::UnboundedSender<client_api_messages::Message>>,
+    disconnect_signal: Option<Arc<DisconnectSignal>>,
     #[allow(unused)]
     /// None if not yet connected.
     websocket_loop_handle: Option<JoinHandle<()>>,
@@ -39,7 +223,7 @@ pub struct BackgroundDbConnection {
 
     /// The most recent state of the `ClientCache`, kept in a shared cell
     /// so that the `receiver_loop` can update it, and non-callback table accesses
-    /// can observe it via `global_connection::current_or_global_state`.
+    /// can observe it via `DbConnection::current_or_latest_state`.
     ///
     /// If you expand these type aliases, you get `Arc<Mutex<Arc<ClientCache>>>`,
     /// which looks somewhat strange. The type aliases are intended to make clear
@@ -54,6 +238,8 @@ pub struct BackgroundDbConnection {
     /// and without changes to the state invalidating or altering the snapshot.
     ///
     /// None if not yet connected.
+    /// Will not be reset on disconnect,
+    /// so that clients can continue accessing the last cache state after their connection ends.
     pub(crate) client_cache: SharedCell<Option<ClientCacheView>>,
 
     pub(crate) db_callbacks: SharedCell<DbCallbacks>,
@@ -189,6 +375,11 @@ fn process_transaction_update(
 // This function's future will be run in the background with `Runtime::spawn`, so the
 // future must be `'static`. As a result, it must own (shared pointers to) the
 // `ClientCache`, `ReducerCallbacks` and `Credentials`, rather than references.
+//
+// When `recv` ends because the WebSocket closed, the `reconnector` may replace it
+// with the stream of a fresh connection, in which case the loop continues
+// with the same `ClientCache` and callbacks.
+#[allow(clippy::too_many_arguments)]
 async fn receiver_loop(
     mut recv: mpsc::UnboundedReceiver<client_api_messages::Message>,
     client_cache: SharedCell<Option<ClientCacheView>>,
@@ -197,6 +388,39 @@ async fn receiver_loop(
     credentials: SharedCell<CredentialStore>,
     subscription_callbacks: SharedCell<SubscriptionAppliedCallbacks>,
     disconnect_callbacks: SharedCell<DisconnectCallbacks>,
+    reconnector: Reconnector,
+) {
+    loop {
+        receive_messages(
+            &mut recv,
+            &client_cache,
+            &db_callbacks,
+            &reducer_callbacks,
+            &credentials,
+            &subscription_callbacks,
+        )
+        .await;
+        match reconnector.reconnect(&credentials).await {
+            Some(new_recv) => recv = new_recv,
+            None => break,
+        }
+    }
+    let final_state = client_cache.lock().expect("ClientCache Mutex is poisoned");
+    let final_state = ClientCacheView::clone(final_state.as_ref().unwrap());
+    disconnect_callbacks
+        .lock()
+        .expect("DisconnectCallbacks Mutex is poisoned")
+        .handle_disconnect(final_state);
+}
+
+/// Handle messages from `recv` until the WebSocket which feeds it closes.
+async fn receive_messages(
+    recv: &mut mpsc::UnboundedReceiver<client_api_messages::Message>,
+    client_cache: &Mutex<Option<ClientCacheView>>,
+    db_callbacks: &Mutex<DbCallbacks>,
+    reducer_callbacks: &Mutex<ReducerCallbacks>,
+    credentials: &Mutex<CredentialStore>,
+    subscription_callbacks: &Mutex<SubscriptionAppliedCallbacks>,
 ) {
     while let Some(msg) = recv.next().await {
         match msg {
@@ -206,7 +430,7 @@ async fn receiver_loop(
             } => {
                 log::info!("Message SubscriptionUpdate");
                 let mut callback_reminders = RowCallbackReminders::new_for_subscription_update(&update);
-                let new_state = update_client_cache(&client_cache, |client_cache| {
+                let new_state = update_client_cache(client_cache, |client_cache| {
                     process_subscription_update_for_new_subscribed_set(update, client_cache, &mut callback_reminders);
                 });
 
@@ -223,7 +447,7 @@ async fn receiver_loop(
             } => {
                 log::info!("Message TransactionUpdate");
 
-                process_transaction_update(transaction_update, &client_cache, &db_callbacks, &reducer_callbacks);
+                process_transaction_update(transaction_update, client_cache, db_callbacks, reducer_callbacks);
             }
             client_api_messages::Message {
                 r#type: Some(client_api_messages::message::Type::IdentityToken(ident)),
@@ -236,12 +460,6 @@ async fn receiver_loop(
             other => log::info!("Unknown message: {:?}", other),
         }
     }
-    let final_state = client_cache.lock().expect("ClientCache Mutex is poisoned");
-    let final_state = ClientCacheView::clone(final_state.as_ref().unwrap());
-    disconnect_callbacks
-        .lock()
-        .expect("DisconnectCallbacks Mutex is poisoned")
-        .handle_disconnect(final_state);
 }
 
 impl BackgroundDbConnection {
@@ -260,11 +478,15 @@ impl BackgroundDbConnection {
         Ok(BackgroundDbConnection {
             runtime,
             handle,
-            send_chan: None,
+            id: ConnectionId::next(),
+            send_chan: Arc::new(Mutex::new(None)),
+            subscribed_queries: Arc::new(Mutex::new(None)),
+            reconnect_policy: Arc::new(Mutex::new(None)),
+            disconnect_signal: None,
             websocket_loop_handle: None,
             recv_handle: None,
             credentials,
-            client_cache: Arc::clone(&CLIENT_CACHE),
+            client_cache: Arc::new(Mutex::new(None)),
             db_callbacks,
             reducer_callbacks,
             subscription_callbacks,
@@ -276,6 +498,7 @@ impl BackgroundDbConnection {
         &self,
         recv: mpsc::UnboundedReceiver<client_api_messages::Message>,
         client_cache: SharedCell<Option<ClientCacheView>>,
+        reconnector: Reconnector,
     ) -> JoinHandle<()> {
         self.handle.spawn(receiver_loop(
             recv,
@@ -285,6 +508,7 @@ impl BackgroundDbConnection {
             self.credentials.clone(),
             self.subscription_callbacks.clone(),
             self.disconnect_callbacks.clone(),
+            reconnector,
         ))
     }
 
@@ -317,30 +541,48 @@ impl BackgroundDbConnection {
             let mut lock = self.credentials.lock().expect("CredentialStore Mutex is poisoned");
             lock.get_or_init_address()
         };
+        // Keep the parsed URI around in case we need to reconnect.
+        let spacetimedb_uri: http::Uri = spacetimedb_uri.try_into()?;
         // `block_in_place` is required here, as tokio won't allow us to call
         // `block_on` if it would block the current thread of an outer runtime
         let connection = tokio::task::block_in_place(|| {
-            self.handle.block_on(DbConnection::connect(
-                spacetimedb_uri,
+            self.handle.block_on(WsConnection::connect(
+                spacetimedb_uri.clone(),
                 db_name,
                 credentials.as_ref(),
                 client_address,
             ))
         })?;
 
-        let client_cache = Arc::new(ClientCache::new(module.clone()));
+        let client_cache = Arc::new(ClientCache::new(module.clone(), self.id));
 
         {
-            // Set the global `CLIENT_CACHE` to our newly-constructed cache.
+            // Replace this connection's cache with our newly-constructed one.
             // Do this inside a short scope to avoid holding the lock unnecessarily.
             let mut client_cache_lock = self.client_cache.lock().expect("ClientCache mutex is poisoned");
             *client_cache_lock = Some(client_cache);
         }
 
+        // A fresh connection has no subscriptions to replay.
+        *self.subscribed_queries.lock().expect("Queries Mutex is poisoned") = None;
+        *self.send_chan.lock().expect("SendChan Mutex is poisoned") = None;
+
+        let disconnect_signal = Arc::new(DisconnectSignal::default());
+        let reconnector = Reconnector {
+            handle: self.handle.clone(),
+            uri: spacetimedb_uri,
+            db_name: db_name.to_owned(),
+            policy: self.reconnect_policy.clone(),
+            send_chan: self.send_chan.clone(),
+            subscribed_queries: self.subscribed_queries.clone(),
+            disconnect: disconnect_signal.clone(),
+        };
+
         let (websocket_loop_handle, recv_chan, send_chan) = connection.spawn_message_loop(&self.handle);
-        let recv_handle = self.spawn_receiver(recv_chan, self.client_cache.clone());
+        *self.send_chan.lock().expect("SendChan Mutex is poisoned") = Some(send_chan);
+        let recv_handle = self.spawn_receiver(recv_chan, self.client_cache.clone(), reconnector);
 
-        self.send_chan = Some(send_chan);
+        self.disconnect_signal = Some(disconnect_signal);
         self.websocket_loop_handle = Some(websocket_loop_handle);
         self.recv_handle = Some(recv_handle);
 
@@ -357,7 +599,11 @@ impl BackgroundDbConnection {
     }
 
     pub fn disconnect(&mut self) {
-        self.send_chan = None;
+        if let Some(signal) = self.disconnect_signal.take() {
+            signal.request();
+        }
+        // Dropping the `send_chan` closes the WebSocket.
+        self.send_chan.lock().expect("SendChan Mutex is poisoned").take();
         if let Some(h) = self.websocket_loop_handle.take() {
             let _ = self.handle.block_on(h);
         }
@@ -366,8 +612,25 @@ impl BackgroundDbConnection {
         }
     }
 
+    /// Close the WebSocket without ending the connection,
+    /// as if the server had dropped it, so the `ReconnectPolicy` decides what happens next.
+    pub(crate) fn drop_websocket(&self) {
+        // Dropping the `send_chan` closes the WebSocket.
+        self.send_chan.lock().expect("SendChan Mutex is poisoned").take();
+    }
+
+    /// Set the policy for re-establishing this connection after the server drops it,
+    /// or `None` to leave dropped connections closed.
+    ///
+    /// Takes effect the next time the connection drops, even if already connected.
+    pub(crate) fn set_reconnect_policy(&self, policy: Option<ReconnectPolicy>) {
+        *self.reconnect_policy.lock().expect("ReconnectPolicy Mutex is poisoned") = policy;
+    }
+
     fn send_message(&self, message: client_api_messages::Message) -> Result<()> {
         self.send_chan
+            .lock()
+            .expect("SendChan Mutex is poisoned")
             .as_ref()
             .context("Cannot send message before connecting")?
             .unbounded_send(message)
@@ -379,12 +642,13 @@ impl BackgroundDbConnection {
     }
 
     pub(crate) fn subscribe_owned(&self, queries: Vec<String>) -> Result<()> {
-        self.send_message(client_api_messages::Message {
-            r#type: Some(client_api_messages::message::Type::Subscribe(
-                client_api_messages::Subscribe { query_strings: queries },
-            )),
-        })
-        .with_context(|| "Subscribing to new queries")
+        // Hold the lock while sending, so that a concurrent reconnect
+        // replays either the previous queries before this message, or these queries.
+        let mut subscribed_queries = self.subscribed_queries.lock().expect("Queries Mutex is poisoned");
+        self.send_message(subscribe_message(queries.clone()))
+            .with_context(|| "Subscribing to new queries")?;
+        *subscribed_queries = Some(queries);
+        Ok(())
     }
 
     pub(crate) fn invoke_reducer<R: Reducer>(&self, reducer: R) -> Result<()> {
@@ -399,3 +663,81 @@ impl BackgroundDbConnection {
         .with_context(|| format!("Invoking reducer {}", R::REDUCER_NAME))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(max_attempts: Option<u32>) -> ReconnectPolicy {
+        ReconnectPolicy {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            max_attempts,
+        }
+    }
+
+    #[test]
+    fn test_delay_doubles_up_to_max_delay() {
+        let policy = policy(None);
+        let delays = (0..6).map(|attempt| policy.delay(attempt)).collect::<Vec<_>>();
+        assert_eq!(
+            delays,
+            [100, 200, 400, 800, 1000, 1000].map(Duration::from_millis).to_vec()
+        );
+    }
+
+    #[test]
+    fn test_delay_of_large_attempts() {
+        let policy = policy(None);
+        for attempt in [31, 32, 64, u32::MAX] {
+            assert_eq!(policy.delay(attempt), policy.max_delay, "attempt {attempt}");
+        }
+
+        // The delay saturates at `max_delay`, rather than overflowing.
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_secs(u64::MAX / 2),
+            max_delay: Duration::MAX,
+            max_attempts: None,
+        };
+        assert_eq!(policy.delay(0), Duration::from_secs(u64::MAX / 2));
+        assert_eq!(policy.delay(2), Duration::MAX);
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_gives_up_after_max_attempts() {
+        // Nothing listens on the port, so every attempt fails.
+        let reconnector = Reconnector {
+            handle: runtime::Handle::current(),
+            uri: "http://127.0.0.1:1".parse().unwrap(),
+            db_name: "test".into(),
+            policy: Arc::new(Mutex::new(Some(ReconnectPolicy {
+                initial_delay: Duration::from_millis(1),
+                max_delay: Duration::from_millis(1),
+                max_attempts: Some(3),
+            }))),
+            send_chan: Default::default(),
+            subscribed_queries: Default::default(),
+            disconnect: Default::default(),
+        };
+        let mut credentials = CredentialStore::without_credentials(&runtime::Handle::current());
+        credentials.get_or_init_address();
+        let credentials = Mutex::new(credentials);
+
+        let started = tokio::time::Instant::now();
+        assert!(reconnector.reconnect(&credentials).await.is_none());
+        assert!(started.elapsed() >= Duration::from_millis(3));
+
+        // A requested disconnect cuts the delay before the next attempt short.
+        *reconnector.policy.lock().unwrap() = Some(ReconnectPolicy {
+            initial_delay: Duration::from_secs(3600),
+            ..ReconnectPolicy::default()
+        });
+        reconnector.disconnect.request();
+        let reconnect = tokio::time::timeout(Duration::from_secs(5), reconnector.reconnect(&credentials));
+        assert!(reconnect.await.unwrap().is_none());
+
+        // With no policy, the connection is not re-established at all.
+        *reconnector.policy.lock().unwrap() = None;
+        assert!(reconnector.reconnect(&credentials).await.is_none());
+    }
+}
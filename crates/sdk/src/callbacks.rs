@@ -8,7 +8,7 @@
 //! so we define a `CallbackId` type which uniquely identifies a registered callback,
 //! and can be used to remove it.
 //!
-//! Callbacks may access the global `CONNECTION`, individual `TableCache`s,
+//! Callbacks may access any `DbConnection`, individual `TableCache`s,
 //! or register or remove other callbacks. This means that the event source,
 //! e.g. a `TableCache`, cannot hold its callbacks directly; doing so would require
 //! a `Mutex` or `RwLock` and cause deadlocks when the callbacks attempted to re-acquire it.
@@ -1,5 +1,6 @@
 use crate::callbacks::DbCallbacks;
 use crate::client_api_messages;
+use crate::db_connection::ConnectionId;
 use crate::reducer::AnyReducerEvent;
 use crate::spacetime_module::SpacetimeModule;
 use crate::table::{TableType, TableWithPrimaryKey};
@@ -55,6 +56,13 @@ fn op_is_insert(op: i32) -> bool {
     (client_api_messages::table_row_operation::OperationType::Insert as i32) == op
 }
 
+/// Is `op` the `Update` operation?
+///
+/// `op` will be the `op` field of a `client_api_messages::TableRowOperation`.
+fn op_is_update(op: i32) -> bool {
+    (client_api_messages::table_row_operation::OperationType::Update as i32) == op
+}
+
 impl<T: TableType> TableCache<T> {
     /// Returns the number of rows resident in the client cache for this `TableType`,
     /// i.e. the number of subscribed rows.
@@ -100,12 +108,33 @@ impl<T: TableType> TableCache<T> {
     /// Decode an instance of `T`, i.e. a row, from the `row` field of the `row_op`, and
     /// dispatch on the `op` field of the `row_op` to determine the appropriate action:
     /// `self.delete` or `self.insert`.
+    ///
+    /// Without a primary key, an `Update` is treated as a `Delete` of the `old_row`
+    /// followed by an `Insert` of the `row`.
     fn handle_row_update(
         &mut self,
         callbacks: &mut Vec<RowCallback<T>>,
         row_op: client_api_messages::TableRowOperation,
     ) {
-        let client_api_messages::TableRowOperation { op, row_pk, row } = row_op;
+        let client_api_messages::TableRowOperation {
+            mut op,
+            row_pk,
+            row,
+            old_row_pk,
+            old_row,
+        } = row_op;
+        if op_is_update(op) {
+            self.handle_row_update(
+                callbacks,
+                client_api_messages::TableRowOperation {
+                    op: client_api_messages::table_row_operation::OperationType::Delete as i32,
+                    row_pk: old_row_pk,
+                    row: old_row,
+                    ..Default::default()
+                },
+            );
+            op = client_api_messages::table_row_operation::OperationType::Insert as i32;
+        }
         match bsatn::from_slice(&row) {
             Err(e) => {
                 log::error!(
@@ -217,7 +246,7 @@ impl<T: TableType> TableCache<T> {
         }
 
         for row_op in new_subs.table_row_operations.into_iter() {
-            let client_api_messages::TableRowOperation { op, row_pk, row } = row_op;
+            let client_api_messages::TableRowOperation { op, row_pk, row, .. } = row_op;
 
             if !op_is_insert(op) {
                 log::error!(
@@ -291,6 +320,8 @@ impl<T: TableWithPrimaryKey> TableCache<T> {
     /// Generate a diff from the `TableRowOperation`s in the `table_update` in order to
     /// merge `delete` and `insert` operations into `update`s, then perform the operations
     /// specified in the diff and invoke callbacks as appropriate.
+    ///
+    /// `update` operations sent by the database are used as they are.
     fn handle_table_update_with_primary_key(
         &mut self,
         callbacks: &mut Vec<RowCallback<T>>,
@@ -344,12 +375,21 @@ impl<T: TableWithPrimaryKey> TableCache<T> {
                     );
                     DiffEntry::Delete(left_hash, left)
                 }
-                (DiffEntry::Update { .. }, _) => unreachable!(),
+                (u @ DiffEntry::Update { .. }, Some(_)) => {
+                    log::warn!("Received a `TableRowOperation` for a row which already has an `Update` within one `TableUpdate`");
+                    u
+                }
             }
         }
 
         fn parse_diff_entry<T: TableWithPrimaryKey>(
-            client_api_messages::TableRowOperation { op, row_pk, row }: client_api_messages::TableRowOperation,
+            client_api_messages::TableRowOperation {
+                op,
+                row_pk,
+                row,
+                old_row_pk,
+                old_row,
+            }: client_api_messages::TableRowOperation,
         ) -> Option<DiffEntry<T>> {
             match bsatn::from_slice(&row) {
                 Err(e) => {
@@ -361,7 +401,27 @@ impl<T: TableWithPrimaryKey> TableCache<T> {
                     None
                 }
                 Ok(row) => {
-                    if op_is_delete(op) {
+                    if op_is_update(op) {
+                        match bsatn::from_slice(&old_row) {
+                            Err(e) => {
+                                log::error!(
+                                    "Error while deserializing old row from `TableRowOperation`: {:?}. Row is {:?}",
+                                    e,
+                                    old_row
+                                );
+                                None
+                            }
+                            Ok(old) => {
+                                log::trace!("Got update event for {:?} row {:?} to {:?}", T::TABLE_NAME, old, row,);
+                                Some(DiffEntry::Update {
+                                    old_hash: old_row_pk,
+                                    old,
+                                    new_hash: row_pk,
+                                    new: row,
+                                })
+                            }
+                        }
+                    } else if op_is_delete(op) {
                         log::trace!("Got delete event for {:?} row {:?}", T::TABLE_NAME, row,);
                         Some(DiffEntry::Delete(row_pk, row))
                     } else if op_is_insert(op) {
@@ -526,6 +586,9 @@ pub struct ClientCache {
     /// which handle dispatching on table names
     /// to select appropriate type parameters for various methods.
     module: Arc<dyn SpacetimeModule>,
+
+    /// The connection which owns this cache.
+    connection: ConnectionId,
 }
 
 impl ClientCache {
@@ -583,13 +646,19 @@ impl ClientCache {
         table_cache.reinitialize_for_new_subscribed_set(table_callbacks, new_subs);
     }
 
-    pub(crate) fn new(module: Arc<dyn SpacetimeModule>) -> ClientCache {
+    pub(crate) fn new(module: Arc<dyn SpacetimeModule>, connection: ConnectionId) -> ClientCache {
         ClientCache {
             tables: Map::new(),
             module,
+            connection,
         }
     }
 
+    /// The connection which owns this cache.
+    pub(crate) fn connection(&self) -> ConnectionId {
+        self.connection
+    }
+
     /// Invoke the autogenerated `handle_table_update` function
     /// to dispatch on the table name in `table_update`,
     /// and invoke `ClientCache::find_table` with an apprpriate type arg.
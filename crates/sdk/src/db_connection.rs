@@ -0,0 +1,431 @@
+use crate::background_connection::{BackgroundDbConnection, ReconnectPolicy};
+use crate::callbacks::{
+    CredentialStore, DbCallbacks, DisconnectCallbacks, ReducerCallbacks, SubscriptionAppliedCallbacks, TableCallbacks,
+};
+use crate::client_cache::{ClientCacheView, TableCache};
+use crate::global_connection::try_current_state;
+use crate::identity::{ConnectCallbackId, Credentials, Identity, Token};
+use crate::reducer::{Reducer, ReducerCallbackId, Status};
+use crate::spacetime_module::SpacetimeModule;
+use crate::table::{DeleteCallbackId, InsertCallbackId, TableIter, TableType, TableWithPrimaryKey, UpdateCallbackId};
+use crate::{Address, DisconnectCallbackId, SubscriptionCallbackId};
+use anyhow::{anyhow, Result};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// Uniquely identifies a `BackgroundDbConnection` within this process.
+///
+/// Each `ClientCache` records the connection which owns it,
+/// so that a callback running on behalf of one connection
+/// does not observe its `CURRENT_STATE` when reading from another connection.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) struct ConnectionId(usize);
+
+impl ConnectionId {
+    pub(crate) fn next() -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        ConnectionId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// A handle on a connection to a SpacetimeDB database.
+///
+/// Each `DbConnection` owns its own WebSocket, client cache, credentials and callbacks,
+/// so a single process may hold several of them at once,
+/// whether to several databases or to the same database as several identities.
+///
+/// `DbConnection` is cheap to clone; clones refer to the same connection.
+///
+/// The free functions in this crate, along with the static methods of
+/// [`TableType`], [`TableWithPrimaryKey`] and [`Reducer`],
+/// operate on a process-wide default `DbConnection`,
+/// which is connected by the autogenerated `connect` function.
+/// To connect a `DbConnection` of your own,
+/// use the autogenerated `connect_with` function.
+#[derive(Clone)]
+pub struct DbConnection {
+    inner: Arc<RwLock<BackgroundDbConnection>>,
+}
+
+impl DbConnection {
+    /// Construct a new, not-yet-connected `DbConnection`.
+    ///
+    /// Callbacks may be registered on the returned `DbConnection` before connecting it.
+    pub fn new() -> Result<Self> {
+        Ok(DbConnection {
+            inner: Arc::new(RwLock::new(BackgroundDbConnection::unconnected()?)),
+        })
+    }
+
+    /// Invoke `f` with this connection's `BackgroundDbConnection` locked for writing.
+    ///
+    /// Calls to this function are generated by the Spacetime CLI.
+    /// Users should not call this function directly.
+    #[doc(hidden)]
+    pub fn with_connection_mut<Res>(&self, f: impl FnOnce(&mut BackgroundDbConnection) -> Res) -> Res {
+        let mut connection = self.inner.write().expect("DbConnection RwLock is poisoned");
+        f(&mut connection)
+    }
+
+    pub(crate) fn with_connection<Res>(&self, f: impl FnOnce(&BackgroundDbConnection) -> Res) -> Res {
+        let connection = self.inner.read().expect("DbConnection RwLock is poisoned");
+        f(&connection)
+    }
+
+    pub(crate) fn with_reducer_callbacks<Res>(&self, f: impl FnOnce(&mut ReducerCallbacks) -> Res) -> Res {
+        self.with_connection(|connection| {
+            let mut callbacks = connection
+                .reducer_callbacks
+                .lock()
+                .expect("ReducerCallbacks Mutex is poisoned");
+            f(&mut callbacks)
+        })
+    }
+
+    pub(crate) fn with_credential_store<Res>(&self, f: impl FnOnce(&mut CredentialStore) -> Res) -> Res {
+        self.with_connection(|connection| {
+            let mut credentials = connection
+                .credentials
+                .lock()
+                .expect("CredentialStore Mutex is poisoned");
+            f(&mut credentials)
+        })
+    }
+
+    pub(crate) fn with_db_callbacks<Res>(&self, f: impl FnOnce(&mut DbCallbacks) -> Res) -> Res {
+        self.with_connection(|connection| {
+            let mut db_callbacks = connection.db_callbacks.lock().expect("DbCallbacks Mutex is poisoned");
+            f(&mut db_callbacks)
+        })
+    }
+
+    pub(crate) fn with_subscription_callbacks<Res>(
+        &self,
+        f: impl FnOnce(&mut SubscriptionAppliedCallbacks) -> Res,
+    ) -> Res {
+        self.with_connection(|connection| {
+            let mut subscription_callbacks = connection
+                .subscription_callbacks
+                .lock()
+                .expect("SubscriptionAppliedCallbacks Mutex is poisoned");
+            f(&mut subscription_callbacks)
+        })
+    }
+
+    pub(crate) fn with_disconnect_callbacks<Res>(&self, f: impl FnOnce(&mut DisconnectCallbacks) -> Res) -> Res {
+        self.with_connection(|connection| {
+            let mut dc_callbacks = connection
+                .disconnect_callbacks
+                .lock()
+                .expect("DisconnectCallbacks Mutex is poisoned");
+            f(&mut dc_callbacks)
+        })
+    }
+
+    /// If we're in a callback running on behalf of this connection,
+    /// return the state which caused that callback.
+    /// Otherwise, return this connection's most recent client cache state.
+    /// Return an error if this connection has never connected.
+    pub(crate) fn current_or_latest_state(&self) -> Result<ClientCacheView> {
+        self.with_connection(|connection| {
+            try_current_state(connection.id)
+                .or_else(|| Option::clone(&connection.client_cache.lock().expect("ClientCache Mutex is poisoned")))
+                .ok_or(anyhow!("Cannot access ClientCache before connecting"))
+        })
+    }
+
+    /// Connect to a database named `db_name` accessible over the internet at the URI `spacetimedb_uri`.
+    ///
+    /// If `credentials` are supplied, they will be passed to the new connection to
+    /// identify and authenticate the user. Otherwise, a set of `Credentials` will be
+    /// generated by the server.
+    ///
+    /// Prefer the `connect_with` function generated by the SpacetimeDB CLI,
+    /// which supplies the `module` automatically.
+    pub fn connect<IntoUri>(
+        &self,
+        spacetimedb_uri: IntoUri,
+        db_name: &str,
+        credentials: Option<Credentials>,
+        module: Arc<dyn SpacetimeModule>,
+    ) -> Result<()>
+    where
+        IntoUri: TryInto<http::Uri>,
+        <IntoUri as TryInto<http::Uri>>::Error: std::error::Error + Send + Sync + 'static,
+    {
+        self.with_connection_mut(|connection| connection.connect(spacetimedb_uri, db_name, credentials, module))
+    }
+
+    /// Set the policy for re-establishing this connection after the server drops it,
+    /// or `None` to leave dropped connections closed, which is the default.
+    ///
+    /// See [`ReconnectPolicy`].
+    pub fn set_reconnect_policy(&self, policy: Option<ReconnectPolicy>) {
+        self.with_connection(|connection| connection.set_reconnect_policy(policy));
+    }
+
+    /// Close this connection's WebSocket as if the server had dropped it,
+    /// leaving it to the [`ReconnectPolicy`] to re-establish the connection.
+    ///
+    /// Used to test reconnection.
+    /// Users should not call this function directly.
+    #[doc(hidden)]
+    pub fn drop_websocket(&self) {
+        self.with_connection(|connection| connection.drop_websocket());
+    }
+
+    /// Gracefully close this connection's WebSocket.
+    ///
+    /// If the connection is not active, this operation does nothing.
+    pub fn disconnect(&self) {
+        self.with_connection_mut(|connection| connection.disconnect());
+    }
+
+    /// Subscribe to a set of queries on this connection.
+    ///
+    /// See [`crate::subscribe`].
+    pub fn subscribe(&self, queries: &[&str]) -> Result<()> {
+        self.with_connection(|connection| connection.subscribe(queries))
+    }
+
+    /// Subscribe to a set of queries on this connection.
+    ///
+    /// See [`crate::subscribe_owned`].
+    pub fn subscribe_owned(&self, queries: Vec<String>) -> Result<()> {
+        self.with_connection(|connection| connection.subscribe_owned(queries))
+    }
+
+    /// Register a callback to be invoked upon this connection's subscription being applied.
+    ///
+    /// See [`crate::on_subscription_applied`].
+    pub fn on_subscription_applied(&self, callback: impl FnMut() + Send + 'static) -> SubscriptionCallbackId {
+        let id =
+            self.with_subscription_callbacks(|sub_callbacks| sub_callbacks.register_on_subscription_applied(callback));
+        SubscriptionCallbackId { id }
+    }
+
+    /// Register a callback to be invoked once upon this connection's subscription being applied.
+    ///
+    /// See [`crate::once_on_subscription_applied`].
+    pub fn once_on_subscription_applied(&self, callback: impl FnOnce() + Send + 'static) -> SubscriptionCallbackId {
+        let id = self.with_subscription_callbacks(|sub_callbacks| {
+            sub_callbacks.register_on_subscription_applied_oneshot(callback)
+        });
+        SubscriptionCallbackId { id }
+    }
+
+    /// Unregister a previously-registered [`DbConnection::on_subscription_applied`] callback.
+    pub fn remove_on_subscription_applied(&self, id: SubscriptionCallbackId) {
+        self.with_subscription_callbacks(|sub_callbacks| sub_callbacks.unregister_on_subscription_applied(id.id));
+    }
+
+    /// Register a callback to be invoked when this connection ends.
+    ///
+    /// See [`crate::on_disconnect`].
+    pub fn on_disconnect(&self, callback: impl FnMut() + Send + 'static) -> DisconnectCallbackId {
+        let id = self.with_disconnect_callbacks(|dc_callbacks| dc_callbacks.register_on_disconnect(callback));
+        DisconnectCallbackId { id }
+    }
+
+    /// Register a callback to be invoked once when this connection ends.
+    ///
+    /// See [`crate::once_on_disconnect`].
+    pub fn once_on_disconnect(&self, callback: impl FnOnce() + Send + 'static) -> DisconnectCallbackId {
+        let id = self.with_disconnect_callbacks(|dc_callbacks| dc_callbacks.register_on_disconnect_oneshot(callback));
+        DisconnectCallbackId { id }
+    }
+
+    /// Unregister a previously-registered [`DbConnection::on_disconnect`] callback.
+    pub fn remove_on_disconnect(&self, id: DisconnectCallbackId) {
+        self.with_disconnect_callbacks(|dc_callbacks| dc_callbacks.unregister_on_disconnect(id.id));
+    }
+
+    /// Register a callback to be invoked upon this connection authenticating with the database.
+    ///
+    /// See [`crate::identity::on_connect`].
+    pub fn on_connect(&self, callback: impl FnMut(&Credentials, Address) + Send + 'static) -> ConnectCallbackId {
+        let id = self.with_credential_store(|cred_store| cred_store.register_on_connect(callback));
+        ConnectCallbackId { id }
+    }
+
+    /// Register a callback to be invoked once upon this connection authenticating with the database.
+    ///
+    /// See [`crate::identity::once_on_connect`].
+    pub fn once_on_connect(&self, callback: impl FnOnce(&Credentials, Address) + Send + 'static) -> ConnectCallbackId {
+        let id = self.with_credential_store(|cred_store| cred_store.register_on_connect_oneshot(callback));
+        ConnectCallbackId { id }
+    }
+
+    /// Unregister a previously-registered [`DbConnection::on_connect`] callback.
+    pub fn remove_on_connect(&self, id: ConnectCallbackId) {
+        self.with_credential_store(|cred_store| cred_store.unregister_on_connect(id.id));
+    }
+
+    /// Read this connection's public `Identity`.
+    ///
+    /// See [`crate::identity::identity`].
+    pub fn identity(&self) -> Result<Identity> {
+        self.with_credential_store(|cred_store| cred_store.identity().ok_or(anyhow!("Identity not yet received")))
+    }
+
+    /// Read this connection's private `Token`.
+    ///
+    /// See [`crate::identity::token`].
+    pub fn token(&self) -> Result<Token> {
+        self.with_credential_store(|cred_store| cred_store.token().ok_or(anyhow!("Token not yet received")))
+    }
+
+    /// Read this connection's `Credentials`.
+    ///
+    /// See [`crate::identity::credentials`].
+    pub fn credentials(&self) -> Result<Credentials> {
+        self.with_credential_store(|cred_store| cred_store.credentials().ok_or(anyhow!("Credentials not yet received")))
+    }
+
+    /// Read this connection's `Address`.
+    ///
+    /// Returns an error if this connection has not yet connected.
+    pub fn address(&self) -> Result<Address> {
+        self.with_credential_store(|cred_store| cred_store.address().ok_or(anyhow!("Address not yet generated")))
+    }
+
+    #[doc(hidden)]
+    /// Designate a file to store this connection's `Address`.
+    ///
+    /// See [`crate::identity::use_saved_address`].
+    pub fn use_saved_address(&self, path: &str) -> Result<Address> {
+        self.with_credential_store(|cred_store| cred_store.use_saved_address(path))
+    }
+
+    fn try_with_table<T: TableType, Res>(&self, f: impl FnOnce(&TableCache<T>) -> Res) -> Result<Res> {
+        self.current_or_latest_state()?
+            .get_table::<T>()
+            .map(f)
+            .ok_or_else(|| anyhow!("TableCache does not exist"))
+    }
+
+    fn with_table_callbacks<T: TableType, Res>(&self, f: impl FnOnce(&mut TableCallbacks<T>) -> Res) -> Res {
+        self.with_db_callbacks(|db_callbacks| f(db_callbacks.find_table::<T>()))
+    }
+
+    /// Return the number of subscribed rows in the table `T`,
+    /// or 0 if this connection has never connected.
+    pub fn count<T: TableType>(&self) -> usize {
+        self.try_with_table::<T, _>(|table_cache| table_cache.count_subscribed_rows())
+            .unwrap_or(0)
+    }
+
+    /// Iterate over all the subscribed rows in the table `T`.
+    ///
+    /// See [`TableType::iter`].
+    pub fn iter<T: TableType>(&self) -> TableIter<T> {
+        TableIter {
+            iter: self
+                .try_with_table::<T, _>(|table_cache| table_cache.values())
+                .unwrap_or_else(|_| Vec::new())
+                .into_iter(),
+        }
+    }
+
+    /// Iterate over the subscribed rows in the table `T` for which `predicate` returns `true`.
+    ///
+    /// See [`TableType::filter`].
+    pub fn filter<T: TableType>(&self, predicate: impl FnMut(&T) -> bool) -> TableIter<T> {
+        TableIter {
+            iter: self
+                .try_with_table::<T, _>(|table_cache| table_cache.filter(predicate))
+                .unwrap_or_else(|_| Vec::new())
+                .into_iter(),
+        }
+    }
+
+    /// Locate a subscribed row in the table `T` for which `predicate` returns `true`, if one exists.
+    ///
+    /// See [`TableType::find`].
+    pub fn find<T: TableType>(&self, predicate: impl FnMut(&T) -> bool) -> Option<T> {
+        self.try_with_table::<T, _>(|table_cache| table_cache.find(predicate))
+            .unwrap_or(None)
+    }
+
+    /// Register an `on_insert` callback for the table `T` on this connection.
+    ///
+    /// See [`TableType::on_insert`].
+    pub fn on_insert<T: TableType>(
+        &self,
+        callback: impl FnMut(&T, Option<&T::ReducerEvent>) + Send + 'static,
+    ) -> InsertCallbackId<T> {
+        let id = self.with_table_callbacks::<T, _>(|table_callbacks| table_callbacks.register_on_insert(callback));
+        InsertCallbackId { id }
+    }
+
+    /// Unregister a previously-registered [`DbConnection::on_insert`] callback.
+    pub fn remove_on_insert<T: TableType>(&self, id: InsertCallbackId<T>) {
+        self.with_table_callbacks::<T, _>(|table_callbacks| table_callbacks.unregister_on_insert(id.id));
+    }
+
+    /// Register an `on_delete` callback for the table `T` on this connection.
+    ///
+    /// See [`TableType::on_delete`].
+    pub fn on_delete<T: TableType>(
+        &self,
+        callback: impl FnMut(&T, Option<&T::ReducerEvent>) + Send + 'static,
+    ) -> DeleteCallbackId<T> {
+        let id = self.with_table_callbacks::<T, _>(|table_callbacks| table_callbacks.register_on_delete(callback));
+        DeleteCallbackId { id }
+    }
+
+    /// Unregister a previously-registered [`DbConnection::on_delete`] callback.
+    pub fn remove_on_delete<T: TableType>(&self, id: DeleteCallbackId<T>) {
+        self.with_table_callbacks::<T, _>(|table_callbacks| table_callbacks.unregister_on_delete(id.id));
+    }
+
+    /// Register an `on_update` callback for the table `T` on this connection.
+    ///
+    /// See [`TableWithPrimaryKey::on_update`].
+    pub fn on_update<T: TableWithPrimaryKey>(
+        &self,
+        callback: impl FnMut(&T, &T, Option<&T::ReducerEvent>) + Send + 'static,
+    ) -> UpdateCallbackId<T> {
+        let id = self.with_table_callbacks::<T, _>(|table_callbacks| table_callbacks.register_on_update(callback));
+        UpdateCallbackId { id }
+    }
+
+    /// Unregister a previously-registered [`DbConnection::on_update`] callback.
+    pub fn remove_on_update<T: TableWithPrimaryKey>(&self, id: UpdateCallbackId<T>) {
+        self.with_table_callbacks::<T, _>(|table_callbacks| table_callbacks.unregister_on_update(id.id));
+    }
+
+    /// Request that the remote database invoke `reducer` on behalf of this connection.
+    ///
+    /// See [`Reducer::invoke`].
+    pub fn invoke<R: Reducer>(&self, reducer: R) -> Result<()> {
+        self.with_connection(|connection| connection.invoke_reducer(reducer))
+    }
+
+    /// Register a callback to run after the reducer `R` runs, as observed by this connection.
+    ///
+    /// See [`Reducer::on_reducer`].
+    pub fn on_reducer<R: Reducer>(
+        &self,
+        callback: impl FnMut(&Identity, Option<Address>, &Status, &R) + Send + 'static,
+    ) -> ReducerCallbackId<R> {
+        let id = self.with_reducer_callbacks(|callbacks| callbacks.register_on_reducer::<R>(callback));
+        ReducerCallbackId { id }
+    }
+
+    /// Register a callback to run once after the reducer `R` runs, as observed by this connection.
+    ///
+    /// See [`Reducer::once_on_reducer`].
+    pub fn once_on_reducer<R: Reducer>(
+        &self,
+        callback: impl FnOnce(&Identity, Option<Address>, &Status, &R) + Send + 'static,
+    ) -> ReducerCallbackId<R> {
+        let id = self.with_reducer_callbacks(|callbacks| callbacks.register_on_reducer_oneshot::<R>(callback));
+        ReducerCallbackId { id }
+    }
+
+    /// Unregister a previously-registered [`DbConnection::on_reducer`] callback.
+    pub fn remove_on_reducer<R: Reducer>(&self, id: ReducerCallbackId<R>) {
+        self.with_reducer_callbacks(|callbacks| callbacks.unregister_on_reducer::<R>(id.id));
+    }
+}
@@ -1,114 +1,37 @@
 use crate::background_connection::BackgroundDbConnection;
-use crate::callbacks::{
-    CredentialStore, DbCallbacks, DisconnectCallbacks, ReducerCallbacks, SubscriptionAppliedCallbacks,
-};
 use crate::client_cache::{ClientCache, ClientCacheView};
-use anyhow::{anyhow, Result};
-use lazy_static::lazy_static;
+use crate::db_connection::{ConnectionId, DbConnection};
 use std::{
     cell::{Ref, RefCell},
     marker::PhantomData,
-    sync::{Arc, Mutex, RwLock},
+    sync::Arc,
 };
 
 lazy_static::lazy_static! {
-    /// The global connection.
+    /// The default connection.
+    ///
+    /// The free functions in this crate, and the static methods of `TableType` and `Reducer`,
+    /// operate on this connection.
     ///
-    /// Must be initialized by calling `BackgroundDbConnection::connect`
+    /// Must be connected by calling `BackgroundDbConnection::connect`
     /// before doing anything interesting.
     ///
     /// The autogenerated `module_bindings::connect` free functions accomplish this.
-    pub(crate) static ref CONNECTION: RwLock<BackgroundDbConnection> = RwLock::new(
-        BackgroundDbConnection::unconnected().expect("Could not create BackgroundDbConnection"),
-    );
-}
-
-lazy_static! {
-    /// The global most-recent client cache state.
-    ///
-    /// Attempts to access the client cache will first attempt to use the thread-local `CURRENT_STATE`,
-    /// then fall back to this global.
-    ///
-    /// The connection in `CONNECTION` also holds an Arc-copy of this Mutex.
-    ///
-    /// Initially `None`. Will be initialized upon calling `BackgroundDbConnection::connect`.
-    /// Will not be de-initialized on disconnect,
-    /// so that clients can continue accessing the last cache state after their connection ends.
-    pub(crate) static ref CLIENT_CACHE: Arc<Mutex<Option<ClientCacheView>>> = Arc::new(Mutex::new(None));
+    pub(crate) static ref CONNECTION: DbConnection = DbConnection::new()
+        .expect("Could not create default DbConnection");
 }
 
-/// Invoke `f` with `CONNECTION` locked.
+/// Invoke `f` with the default connection locked.
 ///
 /// Calls to this function are generated in the `connect` function in `mod.rs` generated
 /// by the Spacetime CLI. Users should not call this function directly.
 pub fn with_connection_mut<Res>(f: impl FnOnce(&mut BackgroundDbConnection) -> Res) -> Res {
-    let mut connection = CONNECTION.write().expect("CONNECTION RwLock is poisoned");
-    f(&mut connection)
-}
-
-/// If currently connected, invoke `f` with `CONNECTION` locked. If not connected, return an error.
-pub(crate) fn with_connection<Res>(f: impl FnOnce(&BackgroundDbConnection) -> Res) -> Res {
-    let connection = CONNECTION.read().expect("CONNECTION RwLock is poisoned");
-    f(&connection)
-}
-
-/// If `CURRENT_STATE` is bound in this thread, invoke `f` with the current state.
-/// Otherwise, invoke `f` with `CONNECTION`'s client cache.
-/// Return an error if not connected.
-pub(crate) fn try_with_client_cache<Res>(f: impl FnOnce(&ClientCache) -> Res) -> Result<Res> {
-    let state = current_or_global_state()?;
-    Ok(f(&state))
-}
-
-/// If currently connected, invoke `f` with the current connection's `ReducerCallbacks`. If not
-/// connected, return an error.
-pub(crate) fn with_reducer_callbacks<Res>(f: impl FnOnce(&mut ReducerCallbacks) -> Res) -> Res {
-    with_connection(|connection| {
-        let mut callbacks = connection
-            .reducer_callbacks
-            .lock()
-            .expect("ReducerCallbacks Mutex is poisoned");
-        f(&mut callbacks)
-    })
-}
-
-/// If currently connected, invoke `f` with the current connection's `CredentialStore`. If not
-/// connected, return an error.
-pub(crate) fn with_credential_store<Res>(f: impl FnOnce(&mut CredentialStore) -> Res) -> Res {
-    with_connection(|connection| {
-        let mut credentials = connection
-            .credentials
-            .lock()
-            .expect("CredentialStore Mutex is poisoned");
-        f(&mut credentials)
-    })
-}
-
-pub(crate) fn with_db_callbacks<Res>(f: impl FnOnce(&mut DbCallbacks) -> Res) -> Res {
-    with_connection(|connection| {
-        let mut db_callbacks = connection.db_callbacks.lock().expect("DbCallbacks Mutex is poisoned");
-        f(&mut db_callbacks)
-    })
+    CONNECTION.with_connection_mut(f)
 }
 
-pub(crate) fn with_subscription_callbacks<Res>(f: impl FnOnce(&mut SubscriptionAppliedCallbacks) -> Res) -> Res {
-    with_connection(|connection| {
-        let mut subscription_callbacks = connection
-            .subscription_callbacks
-            .lock()
-            .expect("SubscriptionAppliedCallbacks Mutex is poisoned");
-        f(&mut subscription_callbacks)
-    })
-}
-
-pub(crate) fn with_disconnect_callbacks<Res>(f: impl FnOnce(&mut DisconnectCallbacks) -> Res) -> Res {
-    with_connection(|connection| {
-        let mut dc_callbacks = connection
-            .disconnect_callbacks
-            .lock()
-            .expect("DisconnectCallbacks Mutex is poisoned");
-        f(&mut dc_callbacks)
-    })
+/// The default connection, on which the free functions in this crate operate.
+pub(crate) fn default_connection() -> &'static DbConnection {
+    &CONNECTION
 }
 
 thread_local! {
@@ -117,29 +40,26 @@ thread_local! {
     /// While inside a callback, this will be bound by a `CurrentStateGuard`,
     /// and accesses to the client cache state (e.g. by `TableType::iter`)
     /// will inspect the `CURRENT_STATE`, rather than the most-recent state
-    /// in `global_connection::CONNECTION`.
+    /// of the connection being read.
     pub(crate) static CURRENT_STATE: RefCell<Option<ClientCacheView>> = RefCell::new(None);
 }
 
-/// If `CURRENT_STATE` is bound,
+/// If `CURRENT_STATE` is bound to a state of the `connection`,
 /// i.e. we're in a `CurrentStateGuard` frame,
-/// i.e. we're in a callback,
+/// i.e. we're in a callback running on behalf of `connection`,
 /// extract and return the `CURRENT_STATE`.
-pub(crate) fn try_current_state() -> Option<ClientCacheView> {
-    CURRENT_STATE.with(|current_state| current_state.borrow().as_ref().map(Arc::clone))
-}
-
-/// If `CURRENT_STATE` is bound,
-/// i.e. we're in a `CurrentStateGuard` frame,
-/// i.e. we're in a callback,
-/// extract and return the `CURRENT_STATE`.
-/// If `CURRENT_STATE` is unbound, i.e. we're not in a callback,
-/// attempt to extract the most recent client cache state from `CLIENT_CACHE`.
-/// Return an error if both `CURRENT_STATE` and `CLIENT_CACHE` are unbound.
-pub(crate) fn current_or_global_state() -> Result<ClientCacheView> {
-    try_current_state()
-        .or_else(|| Option::clone(&CLIENT_CACHE.lock().expect("ClientCache Mutex is poisoned")))
-        .ok_or(anyhow!("Cannot access ClientCache before connecting"))
+///
+/// States of other connections are ignored,
+/// so that a callback on one connection reading from another
+/// observes the other connection's most recent state.
+pub(crate) fn try_current_state(connection: ConnectionId) -> Option<ClientCacheView> {
+    CURRENT_STATE.with(|current_state| {
+        current_state
+            .borrow()
+            .as_ref()
+            .filter(|state| state.connection() == connection)
+            .map(Arc::clone)
+    })
 }
 
 /// An RAII-style guard for a binding of `CURRENT_STATE`.
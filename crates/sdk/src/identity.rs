@@ -1,6 +1,6 @@
 use crate::callbacks::CallbackId;
-use crate::global_connection::with_credential_store;
-use anyhow::{anyhow, Context, Result};
+use crate::global_connection::default_connection;
+use anyhow::{Context, Result};
 use spacetimedb_lib::de::Deserialize;
 use spacetimedb_lib::ser::Serialize;
 use spacetimedb_lib::Address;
@@ -76,7 +76,7 @@ pub struct Credentials {
 
 #[derive(Copy, Clone)]
 pub struct ConnectCallbackId {
-    id: CallbackId<(Credentials, Address)>,
+    pub(crate) id: CallbackId<(Credentials, Address)>,
 }
 
 /// Register a callback to be invoked upon authentication with the database.
@@ -104,8 +104,7 @@ pub struct ConnectCallbackId {
 /// The returned `ConnectCallbackId` can be passed to `remove_on_connect` to unregister
 /// the callback.
 pub fn on_connect(callback: impl FnMut(&Credentials, Address) + Send + 'static) -> ConnectCallbackId {
-    let id = with_credential_store(|cred_store| cred_store.register_on_connect(callback));
-    ConnectCallbackId { id }
+    default_connection().on_connect(callback)
 }
 
 /// Register a callback to be invoked once upon authentication with the database.
@@ -127,8 +126,7 @@ pub fn on_connect(callback: impl FnMut(&Credentials, Address) + Send + 'static)
 /// The returned `ConnectCallbackId` can be passed to `remove_on_connect` to unregister
 /// the callback.
 pub fn once_on_connect(callback: impl FnOnce(&Credentials, Address) + Send + 'static) -> ConnectCallbackId {
-    let id = with_credential_store(|cred_store| cred_store.register_on_connect_oneshot(callback));
-    ConnectCallbackId { id }
+    default_connection().once_on_connect(callback)
 }
 
 /// Unregister a previously-registered `on_connect` callback.
@@ -136,7 +134,7 @@ pub fn once_on_connect(callback: impl FnOnce(&Credentials, Address) + Send + 'st
 /// If `id` does not refer to a currently-registered callback, this operation does
 /// nothing.
 pub fn remove_on_connect(id: ConnectCallbackId) {
-    with_credential_store(|cred_store| cred_store.unregister_on_connect(id.id));
+    default_connection().remove_on_connect(id);
 }
 
 /// Read the current connection's public `Identity`.
@@ -145,7 +143,7 @@ pub fn remove_on_connect(id: ConnectCallbackId) {
 /// - `connect` has not yet been called.
 /// - We connected anonymously, and we have not yet received our credentials.
 pub fn identity() -> Result<Identity> {
-    with_credential_store(|cred_store| cred_store.identity().ok_or(anyhow!("Identity not yet received")))
+    default_connection().identity()
 }
 
 /// Read the current connection's private `Token`.
@@ -154,7 +152,7 @@ pub fn identity() -> Result<Identity> {
 /// - `connect` has not yet been called.
 /// - We connected anonymously, and we have not yet received our credentials.
 pub fn token() -> Result<Token> {
-    with_credential_store(|cred_store| cred_store.token().ok_or(anyhow!("Token not yet received")))
+    default_connection().token()
 }
 
 /// Read the current connection's `Credentials`,
@@ -164,14 +162,14 @@ pub fn token() -> Result<Token> {
 /// - `connect` has not yet been called.
 /// - We connected anonymously, and we have not yet received our credentials.
 pub fn credentials() -> Result<Credentials> {
-    with_credential_store(|cred_store| cred_store.credentials().ok_or(anyhow!("Credentials not yet received")))
+    default_connection().credentials()
 }
 
 /// Read the current connection's `Address`.
 ///
 /// Returns an error if `connect` has not yet been called.
 pub fn address() -> Result<Address> {
-    with_credential_store(|cred_store| cred_store.address().ok_or(anyhow!("Address not yet generated")))
+    default_connection().address()
 }
 
 const CREDS_FILE: &str = "credentials";
@@ -225,5 +223,5 @@ pub fn save_credentials(dirname: &str, creds: &Credentials) -> Result<()> {
 ///
 /// If the file at `path` exists, it will be treated as a BSATN-encoded `Address`
 pub fn use_saved_address(path: &str) -> Result<Address> {
-    with_credential_store(|cred_store| cred_store.use_saved_address(path))
+    default_connection().use_saved_address(path)
 }
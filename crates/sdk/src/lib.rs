@@ -7,7 +7,11 @@ pub mod identity;
 pub mod reducer;
 pub mod table;
 use callbacks::CallbackId;
-use global_connection::{with_connection, with_connection_mut, with_disconnect_callbacks, with_subscription_callbacks};
+use global_connection::default_connection;
+
+mod db_connection;
+pub use background_connection::ReconnectPolicy;
+pub use db_connection::DbConnection;
 
 // Any `#[doc(hidden)]` modules are public because code generated by the CLI's codegen
 // references them, but users should not.
@@ -62,7 +66,7 @@ pub use spacetimedb_sats as sats;
 /// with the autogenerated `connect` function.
 /// In that case, the queries are not registered.
 pub fn subscribe(queries: &[&str]) -> anyhow::Result<()> {
-    with_connection(|conn| conn.subscribe(queries))
+    default_connection().subscribe(queries)
 }
 
 /// Subscribe to a set of queries,
@@ -80,7 +84,7 @@ pub fn subscribe(queries: &[&str]) -> anyhow::Result<()> {
 /// with the autogenerated `connect` function.
 /// In that case, the queries are not registered.
 pub fn subscribe_owned(queries: Vec<String>) -> anyhow::Result<()> {
-    with_connection(|conn| conn.subscribe_owned(queries))
+    default_connection().subscribe_owned(queries)
 }
 
 #[derive(Copy, Clone)]
@@ -96,8 +100,7 @@ pub struct SubscriptionCallbackId {
 /// The returned `SubscriptionCallbackId` can be passed to `remove_on_subscription_applied`
 /// to unregister the callback.
 pub fn on_subscription_applied(callback: impl FnMut() + Send + 'static) -> SubscriptionCallbackId {
-    let id = with_subscription_callbacks(|sub_callbacks| sub_callbacks.register_on_subscription_applied(callback));
-    SubscriptionCallbackId { id }
+    default_connection().on_subscription_applied(callback)
 }
 
 /// Register a callback to be invoked once upon a subscription's matching rows becoming available.
@@ -110,9 +113,7 @@ pub fn on_subscription_applied(callback: impl FnMut() + Send + 'static) -> Subsc
 /// The returned `SubscriptionCallbackId` can be passed to `remove_on_subscription_applied`
 /// to unregister the callback.
 pub fn once_on_subscription_applied(callback: impl FnOnce() + Send + 'static) -> SubscriptionCallbackId {
-    let id =
-        with_subscription_callbacks(|sub_callbacks| sub_callbacks.register_on_subscription_applied_oneshot(callback));
-    SubscriptionCallbackId { id }
+    default_connection().once_on_subscription_applied(callback)
 }
 
 /// Unregister a previously-registered [`on_subscription_applied`] callback.
@@ -120,14 +121,28 @@ pub fn once_on_subscription_applied(callback: impl FnOnce() + Send + 'static) ->
 /// If `id` does not refer to a currently-registered callback, this operation does
 /// nothing.
 pub fn remove_on_subscription_applied(id: SubscriptionCallbackId) {
-    with_subscription_callbacks(|sub_callbacks| sub_callbacks.unregister_on_subscription_applied(id.id));
+    default_connection().remove_on_subscription_applied(id);
+}
+
+/// Set the policy for re-establishing the connection after the server drops it,
+/// or `None` to leave dropped connections closed, which is the default.
+///
+/// Reconnecting reuses the connection's `Credentials` and `Address`,
+/// and replays the queries most recently passed to [`subscribe`] or [`subscribe_owned`].
+/// Rows which changed while disconnected fire `on_insert` and `on_delete` callbacks as usual;
+/// rows which did not change fire none.
+///
+/// The policy may be set before or after connecting,
+/// and takes effect the next time the connection drops.
+pub fn set_reconnect_policy(policy: Option<ReconnectPolicy>) {
+    default_connection().set_reconnect_policy(policy);
 }
 
 /// Gracefully close the current WebSocket connection.
 ///
 /// If there is no active connection, this operation does nothing.
 pub fn disconnect() {
-    with_connection_mut(|x| x.disconnect());
+    default_connection().disconnect();
 }
 
 #[derive(Copy, Clone)]
@@ -139,26 +154,28 @@ pub struct DisconnectCallbackId {
 ///
 /// The callback will be invoked after a connection closes,
 /// either because of a call to [`disconnect`] or because the server closed the connection.
+/// If a [`ReconnectPolicy`] is set, a connection closed by the server
+/// ends only once reconnecting gives up.
 ///
 /// The returned `DisconnectCallbackId` can be passed to `remove_on_disconnect`
 /// to unregister the callback.
 pub fn on_disconnect(callback: impl FnMut() + Send + 'static) -> DisconnectCallbackId {
-    let id = with_disconnect_callbacks(|dc_callbacks| dc_callbacks.register_on_disconnect(callback));
-    DisconnectCallbackId { id }
+    default_connection().on_disconnect(callback)
 }
 
 /// Register a callback to be invoked when a connection ends.
 ///
 /// The callback will be invoked after a connection closes,
 /// either because of a call to [`disconnect`] or because the server closed the connection.
+/// If a [`ReconnectPolicy`] is set, a connection closed by the server
+/// ends only once reconnecting gives up.
 ///
 /// The callback will be unregistered after running.
 ///
 /// The returned `DisconnectCallbackId` can be passed to `remove_on_disconnect`
 /// to unregister the callback.
 pub fn once_on_disconnect(callback: impl FnOnce() + Send + 'static) -> DisconnectCallbackId {
-    let id = with_disconnect_callbacks(|dc_callbacks| dc_callbacks.register_on_disconnect_oneshot(callback));
-    DisconnectCallbackId { id }
+    default_connection().once_on_disconnect(callback)
 }
 
 /// Unregister a previously-registered [`on_disconnect`] callback.
@@ -166,5 +183,5 @@ pub fn once_on_disconnect(callback: impl FnOnce() + Send + 'static) -> Disconnec
 /// If `id` does not refer to a currently-registered callback, this operation does
 /// nothing.
 pub fn remove_on_disconnect(id: DisconnectCallbackId) {
-    with_disconnect_callbacks(|dc_callbacks| dc_callbacks.unregister_on_disconnect(id.id));
+    default_connection().remove_on_disconnect(id);
 }
@@ -1,5 +1,5 @@
 use crate::callbacks::CallbackId;
-use crate::global_connection::{with_connection, with_reducer_callbacks};
+use crate::global_connection::default_connection;
 use crate::identity::Identity;
 use crate::Address;
 use anyhow::Result;
@@ -15,7 +15,7 @@ pub enum Status {
 
 #[derive(Copy, Clone)]
 pub struct ReducerCallbackId<R> {
-    id: CallbackId<(Identity, Option<Address>, Status, R)>,
+    pub(crate) id: CallbackId<(Identity, Option<Address>, Status, R)>,
 }
 
 // Any bound so these can be keys in an `AnyMap` to store callbacks.
@@ -23,11 +23,15 @@ pub struct ReducerCallbackId<R> {
 ///
 /// Types which implement `Reducer` autogenerated by the SpacetimeDB CLI's
 /// `generate` command. Users should not `impl Reducer`.
+///
+/// The methods of `Reducer` operate on the default connection.
+/// To invoke or observe a reducer through another connection,
+/// use the corresponding methods of [`crate::DbConnection`].
 pub trait Reducer: DeserializeOwned + Serialize + Any + Send + Sync + Clone {
     const REDUCER_NAME: &'static str;
 
     fn invoke(self) -> Result<()> {
-        with_connection(|conn| conn.invoke_reducer(self))
+        default_connection().invoke(self)
     }
 
     /// Register a callback to run after the reducer runs.
@@ -43,8 +47,7 @@ pub trait Reducer: DeserializeOwned + Serialize + Any + Send + Sync + Clone {
     fn on_reducer(
         callback: impl FnMut(&Identity, Option<Address>, &Status, &Self) + Send + 'static,
     ) -> ReducerCallbackId<Self> {
-        let id = with_reducer_callbacks(|callbacks| callbacks.register_on_reducer::<Self>(callback));
-        ReducerCallbackId { id }
+        default_connection().on_reducer::<Self>(callback)
     }
 
     /// Register a callback to run once after the reducer runs.
@@ -55,8 +58,7 @@ pub trait Reducer: DeserializeOwned + Serialize + Any + Send + Sync + Clone {
     fn once_on_reducer(
         callback: impl FnOnce(&Identity, Option<Address>, &Status, &Self) + Send + 'static,
     ) -> ReducerCallbackId<Self> {
-        let id = with_reducer_callbacks(|callbacks| callbacks.register_on_reducer_oneshot::<Self>(callback));
-        ReducerCallbackId { id }
+        default_connection().once_on_reducer::<Self>(callback)
     }
 
     /// Unregister a previously-registered `on_reducer` callback.
@@ -64,7 +66,7 @@ pub trait Reducer: DeserializeOwned + Serialize + Any + Send + Sync + Clone {
     /// If `id` does not refer to a currently-registered callback, this operation will do
     /// nothing.
     fn remove_on_reducer(id: ReducerCallbackId<Self>) {
-        with_reducer_callbacks(|callbacks| callbacks.unregister_on_reducer::<Self>(id.id));
+        default_connection().remove_on_reducer::<Self>(id);
     }
 }
 
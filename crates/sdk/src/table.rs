@@ -1,8 +1,6 @@
-use crate::callbacks::{CallbackId, TableCallbacks};
-use crate::client_cache::TableCache;
-use crate::global_connection::{try_with_client_cache, with_db_callbacks};
+use crate::callbacks::CallbackId;
+use crate::global_connection::default_connection;
 use crate::reducer::AnyReducerEvent;
-use anyhow::{anyhow, Result};
 use spacetimedb_sats::{de::DeserializeOwned, ser::Serialize};
 use std::{any::Any, sync::Arc};
 
@@ -12,7 +10,7 @@ use std::{any::Any, sync::Arc};
 /// `TableType::remove_on_insert` to remove the callback.
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct InsertCallbackId<T: TableType> {
-    id: CallbackId<(T, Option<Arc<AnyReducerEvent>>)>,
+    pub(crate) id: CallbackId<(T, Option<Arc<AnyReducerEvent>>)>,
 }
 
 /// A unique identifier for an `on_delete` callback registered with a table.
@@ -21,7 +19,7 @@ pub struct InsertCallbackId<T: TableType> {
 /// `TableType::remove_on_delete` to remove the callback.
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct DeleteCallbackId<T: TableType> {
-    id: CallbackId<(T, Option<Arc<AnyReducerEvent>>)>,
+    pub(crate) id: CallbackId<(T, Option<Arc<AnyReducerEvent>>)>,
 }
 
 /// An iterator over all of the rows in `Table`.
@@ -30,7 +28,7 @@ pub struct DeleteCallbackId<T: TableType> {
 pub struct TableIter<Table> {
     // The specific iterator type here should remain opaque to users, so that we can
     // change the implementation of `TableCache`.
-    iter: std::vec::IntoIter<Table>,
+    pub(crate) iter: std::vec::IntoIter<Table>,
 }
 
 impl<Table> Iterator for TableIter<Table> {
@@ -45,29 +43,15 @@ impl<Table> Iterator for TableIter<Table> {
     }
 }
 
-/// Because `Result::flatten` is unstable as of Rust 1.70.
-fn flatten_result<T>(res: Result<Result<T>>) -> Result<T> {
-    res.and_then(|x| x)
-}
-
-fn try_with_table<T: TableType, Res>(f: impl FnOnce(&TableCache<T>) -> Res) -> Result<Res> {
-    flatten_result(try_with_client_cache(|client_cache| {
-        client_cache
-            .get_table::<T>()
-            .map(f)
-            .ok_or_else(|| anyhow!("TableCache does not exist"))
-    }))
-}
-
-fn with_callbacks<T: TableType, Res>(f: impl FnOnce(&mut TableCallbacks<T>) -> Res) -> Res {
-    with_db_callbacks(|db_callbacks| f(db_callbacks.find_table::<T>()))
-}
-
 // Any bound so these can go into an `AnyMap` in the `ClientCache`.
 /// A type representing rows in a table in the database.
 ///
 /// Types which implement `TableType` are autogenerated by the SpacetimeDB CLI's
 /// `generate` command. Users should not `impl TableType`.
+///
+/// The methods of `TableType` operate on the default connection.
+/// To read or observe a table through another connection,
+/// use the corresponding methods of [`crate::DbConnection`].
 pub trait TableType: DeserializeOwned + Serialize + Any + Send + Sync + Clone + std::fmt::Debug {
     const TABLE_NAME: &'static str;
 
@@ -95,7 +79,7 @@ pub trait TableType: DeserializeOwned + Serialize + Any + Send + Sync + Clone +
     ///
     /// This method acquires a global lock.
     fn count() -> usize {
-        try_with_table::<Self, _>(|table_cache| table_cache.count_subscribed_rows()).unwrap_or(0)
+        default_connection().count::<Self>()
     }
 
     /// Iterate over all the subscribed rows in the table.
@@ -106,11 +90,7 @@ pub trait TableType: DeserializeOwned + Serialize + Any + Send + Sync + Clone +
     /// iterated over. `TableType::filter` allocates significantly less, so prefer it when
     /// possible.
     fn iter() -> TableIter<Self> {
-        TableIter {
-            iter: try_with_table::<Self, _>(|table_cache| table_cache.values())
-                .unwrap_or_else(|_| Vec::new())
-                .into_iter(),
-        }
+        default_connection().iter::<Self>()
     }
 
     /// Iterate over the subscribed rows in the table for which `predicate` returns `true`.
@@ -124,11 +104,7 @@ pub trait TableType: DeserializeOwned + Serialize + Any + Send + Sync + Clone +
     /// This method must heap-allocate enough memory to hold all of the matching rows, but
     /// does not allocate space for subscribed rows which do not match the `predicate`.
     fn filter(predicate: impl FnMut(&Self) -> bool) -> TableIter<Self> {
-        TableIter {
-            iter: try_with_table::<Self, _>(|table_cache| table_cache.filter(predicate))
-                .unwrap_or_else(|_| Vec::new())
-                .into_iter(),
-        }
+        default_connection().filter::<Self>(predicate)
     }
 
     /// Locate a subscribed row for which `predicate` returns `true`, if one exists.
@@ -139,7 +115,7 @@ pub trait TableType: DeserializeOwned + Serialize + Any + Send + Sync + Clone +
     /// choice may not be stable across different calls to `find` with the same
     /// `predicate`.
     fn find(predicate: impl FnMut(&Self) -> bool) -> Option<Self> {
-        try_with_table::<Self, _>(|table_cache| table_cache.find(predicate)).unwrap_or(None)
+        default_connection().find::<Self>(predicate)
     }
 
     /// Register an `on_insert` callback for when a row is newly inserted into the
@@ -150,8 +126,7 @@ pub trait TableType: DeserializeOwned + Serialize + Any + Send + Sync + Clone +
     /// The returned `InsertCallbackId` can be passed to `remove_on_insert` to remove the
     /// callback.
     fn on_insert(callback: impl FnMut(&Self, Option<&Self::ReducerEvent>) + Send + 'static) -> InsertCallbackId<Self> {
-        let id = with_callbacks::<Self, _>(|table_callbacks| table_callbacks.register_on_insert(callback));
-        InsertCallbackId { id }
+        default_connection().on_insert::<Self>(callback)
     }
 
     /// Unregister a previously-registered `on_insert` callback.
@@ -159,7 +134,7 @@ pub trait TableType: DeserializeOwned + Serialize + Any + Send + Sync + Clone +
     /// If `id` does not refer to a currently-registered callback, this operation does
     /// nothing.
     fn remove_on_insert(id: InsertCallbackId<Self>) {
-        with_callbacks::<Self, _>(|table_callbacks| table_callbacks.unregister_on_insert(id.id));
+        default_connection().remove_on_insert::<Self>(id);
     }
 
     /// Register an `on_delete` callback for when a row is removed from the database.
@@ -170,8 +145,7 @@ pub trait TableType: DeserializeOwned + Serialize + Any + Send + Sync + Clone +
     /// The returned `DeleteCallbackId` can be passed to `remove_on_delete` to remove the
     /// callback.
     fn on_delete(callback: impl FnMut(&Self, Option<&Self::ReducerEvent>) + Send + 'static) -> DeleteCallbackId<Self> {
-        let id = with_callbacks::<Self, _>(|table_callbacks| table_callbacks.register_on_delete(callback));
-        DeleteCallbackId { id }
+        default_connection().on_delete::<Self>(callback)
     }
 
     /// Unregister a previously-registered `on_delete` callback.
@@ -179,7 +153,7 @@ pub trait TableType: DeserializeOwned + Serialize + Any + Send + Sync + Clone +
     /// If `id` does not refer to a currently-registered callback, this operation does
     /// nothing.
     fn remove_on_delete(id: DeleteCallbackId<Self>) {
-        with_callbacks::<Self, _>(|table_callbacks| table_callbacks.unregister_on_delete(id.id));
+        default_connection().remove_on_delete::<Self>(id);
     }
 }
 
@@ -189,7 +163,7 @@ pub trait TableType: DeserializeOwned + Serialize + Any + Send + Sync + Clone +
 /// `TableWithPrimaryKey::remove_on_update` to remove the callback.
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct UpdateCallbackId<T: TableWithPrimaryKey> {
-    id: CallbackId<(T, T, Option<Arc<AnyReducerEvent>>)>,
+    pub(crate) id: CallbackId<(T, T, Option<Arc<AnyReducerEvent>>)>,
 }
 
 /// A `TableType` with a column annotated `#[primarykey]`, allowing `on_update` callbacks.
@@ -209,8 +183,7 @@ pub trait TableWithPrimaryKey: TableType {
     fn on_update(
         callback: impl FnMut(&Self, &Self, Option<&Self::ReducerEvent>) + Send + 'static,
     ) -> UpdateCallbackId<Self> {
-        let id = with_callbacks::<Self, _>(|table_callbacks| table_callbacks.register_on_update(callback));
-        UpdateCallbackId { id }
+        default_connection().on_update::<Self>(callback)
     }
 
     /// Unregister a previously-registered `on_update` callback.
@@ -218,6 +191,6 @@ pub trait TableWithPrimaryKey: TableType {
     /// If `id` does not refer to a currently-registered callback, this operation does
     /// nothing.
     fn remove_on_update(id: UpdateCallbackId<Self>) {
-        with_callbacks::<Self, _>(|table_callbacks| table_callbacks.unregister_on_update(id.id));
+        default_connection().remove_on_update::<Self>(id);
     }
 }
@@ -15,7 +15,7 @@ use tokio_tungstenite::{
     MaybeTlsStream, WebSocketStream,
 };
 
-pub(crate) struct DbConnection {
+pub(crate) struct WsConnection {
     sock: WebSocketStream<MaybeTlsStream<TcpStream>>,
 }
 
@@ -56,6 +56,8 @@ where
     path.push_str(db_name);
     path.push_str("?client_address=");
     path.push_str(&client_address.to_hex());
+    // The client cache applies update operations as such.
+    path.push_str("&update_ops=true");
     parts.path_and_query = Some(path.parse()?);
     Ok(Uri::try_from(parts)?)
 }
@@ -122,7 +124,7 @@ fn request_insert_auth_header(req: &mut http::Request<()>, credentials: Option<&
     };
 }
 
-impl DbConnection {
+impl WsConnection {
     pub(crate) async fn connect<Host>(
         host: Host,
         db_name: &str,
@@ -147,7 +149,7 @@ impl DbConnection {
             false,
         )
         .await?;
-        Ok(DbConnection { sock })
+        Ok(WsConnection { sock })
     }
 
     pub(crate) fn parse_response(bytes: &[u8]) -> Result<Message> {
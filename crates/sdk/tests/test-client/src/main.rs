@@ -5,8 +5,11 @@ use spacetimedb_sdk::{
     reducer::Status,
     subscribe,
     table::TableType,
+    DbConnection, ReconnectPolicy,
 };
 
+use std::time::Duration;
+
 #[allow(clippy::too_many_arguments)]
 #[allow(clippy::large_enum_variant)]
 mod module_bindings;
@@ -92,6 +95,8 @@ fn main() {
 
         "reconnect_same_address" => exec_reconnect_same_address(),
 
+        "multiple_connections" => exec_multiple_connections(),
+
         _ => panic!("Unknown test: {}", test),
     }
 }
@@ -1283,10 +1288,118 @@ fn exec_resubscribe() {
     test_counter.wait_for_all();
 }
 
-/// Once we determine appropriate semantics for in-process re-connecting,
-/// this test will verify it.
+/// This tests that, under a `ReconnectPolicy`, a connection whose WebSocket drops
+/// reconnects and replays its subscription,
+/// that only the rows which changed while it was disconnected fire `on_insert`,
+/// and that `on_disconnect` runs only once reconnecting gives up.
 fn exec_reconnect() {
-    todo!()
+    let test_counter = TestCounter::new();
+    let name = db_name_or_panic();
+
+    let conn = DbConnection::new().expect("Failed to create DbConnection");
+    let writer = DbConnection::new().expect("Failed to create DbConnection");
+
+    // Leave time for the `writer` to insert a row before reconnecting.
+    conn.set_reconnect_policy(Some(ReconnectPolicy {
+        initial_delay: Duration::from_secs(2),
+        max_delay: Duration::from_secs(2),
+        max_attempts: Some(3),
+    }));
+
+    // The `writer` subscribes too, as only subscribers are sent the events of committed reducers.
+    for (conn, label) in [(&conn, "conn"), (&writer, "writer")] {
+        let conn_result = test_counter.add_test(format!("{}-connect", label));
+        let sub_result = test_counter.add_test(format!("{}-subscribe", label));
+        let sub_applied_result = test_counter.add_test(format!("{}-on_subscription_applied", label));
+
+        let dup = conn.clone();
+        conn.once_on_subscription_applied(move || {
+            let run_checks = || {
+                assert_eq_or_bail!(0, dup.count::<OneU8>());
+                Ok(())
+            };
+            sub_applied_result(run_checks());
+        });
+        let dup = conn.clone();
+        conn.once_on_connect(move |_, _| sub_result(dup.subscribe(&["SELECT * FROM OneU8"])));
+
+        conn_result(connect_with(conn, LOCALHOST, &name, None));
+    }
+
+    test_counter.wait_for_all();
+
+    let test_counter = TestCounter::new();
+    let mut insert_result = Some(test_counter.add_test("on_insert-before-drop"));
+    let insert_id = conn.on_insert::<OneU8>(move |row, _| {
+        let run_checks = || {
+            assert_eq_or_bail!(1, row.n);
+            Ok(())
+        };
+        (insert_result.take().unwrap())(run_checks());
+    });
+    writer
+        .invoke(InsertOneU8Args { n: 1 })
+        .expect("Failed to invoke reducer");
+
+    test_counter.wait_for_all();
+    conn.remove_on_insert(insert_id);
+
+    let test_counter = TestCounter::new();
+    let reconnect_result = test_counter.add_test("reconnect");
+    let resubscribe_result = test_counter.add_test("resubscribe");
+    let mut insert_result = Some(test_counter.add_test("on_insert-while-disconnected"));
+    let writer_reducer_result = test_counter.add_test("writer-on_reducer");
+
+    conn.once_on_connect(move |_, _| reconnect_result(Ok(())));
+    let dup = conn.clone();
+    conn.once_on_subscription_applied(move || {
+        let run_checks = || {
+            assert_eq_or_bail!(2, dup.count::<OneU8>());
+            Ok(())
+        };
+        resubscribe_result(run_checks());
+    });
+    // The row inserted before the WebSocket dropped did not change,
+    // so it must not fire `on_insert` again.
+    conn.on_insert::<OneU8>(move |row, _| {
+        let run_checks = || {
+            assert_eq_or_bail!(2, row.n);
+            Ok(())
+        };
+        (insert_result.take().expect("on_insert fired more than once"))(run_checks());
+    });
+    let reconnecting_id = conn.on_disconnect(|| panic!("on_disconnect fired while reconnecting"));
+    writer.once_on_reducer::<InsertOneU8Args>(move |_, _, status, _| {
+        let run_checks = || {
+            if !matches!(status, Status::Committed) {
+                anyhow::bail!("Unexpected status. Expected Committed but found {:?}", status);
+            }
+            Ok(())
+        };
+        writer_reducer_result(run_checks());
+    });
+
+    conn.drop_websocket();
+    writer
+        .invoke(InsertOneU8Args { n: 2 })
+        .expect("Failed to invoke reducer");
+
+    test_counter.wait_for_all();
+    conn.remove_on_disconnect(reconnecting_id);
+
+    let test_counter = TestCounter::new();
+    let disconnect_result = test_counter.add_test("on_disconnect-after-giving-up");
+
+    conn.set_reconnect_policy(Some(ReconnectPolicy {
+        max_attempts: Some(0),
+        ..ReconnectPolicy::default()
+    }));
+    conn.once_on_disconnect(move || disconnect_result(Ok(())));
+    conn.drop_websocket();
+
+    test_counter.wait_for_all();
+
+    writer.disconnect();
 }
 
 /// Part of the `reauth` test, this connects to Spacetime to get new credentials,
@@ -1385,3 +1498,87 @@ fn exec_reconnect_same_address() {
 
     test_counter.wait_for_all();
 }
+
+/// This tests that two `DbConnection`s in one process
+/// get distinct identities and client caches,
+/// and that each observes the other's changes only through its own subscription.
+fn exec_multiple_connections() {
+    let test_counter = TestCounter::new();
+    let name = db_name_or_panic();
+
+    let writer = DbConnection::new().expect("Failed to create DbConnection");
+    let reader = DbConnection::new().expect("Failed to create DbConnection");
+
+    for (conn, label) in [(&writer, "writer"), (&reader, "reader")] {
+        let conn_result = test_counter.add_test(format!("{}-connect", label));
+        let sub_result = test_counter.add_test(format!("{}-subscribe", label));
+        let sub_applied_result = test_counter.add_test(format!("{}-on_subscription_applied", label));
+
+        let dup = conn.clone();
+        conn.once_on_subscription_applied(move || {
+            let run_checks = || {
+                assert_eq_or_bail!(0, dup.count::<OneU8>());
+                Ok(())
+            };
+            sub_applied_result(run_checks());
+        });
+
+        let dup = conn.clone();
+        conn.once_on_connect(move |_, _| sub_result(dup.subscribe(SUBSCRIBE_ALL)));
+
+        conn_result(connect_with(conn, LOCALHOST, &name, None));
+    }
+
+    test_counter.wait_for_all();
+
+    let test_counter = TestCounter::new();
+    let distinct_result = test_counter.add_test("distinct-identities");
+    let mut reader_insert_result = Some(test_counter.add_test("reader-on_insert"));
+    let writer_reducer_result = test_counter.add_test("writer-on_reducer");
+
+    distinct_result((|| {
+        if writer.identity()? == reader.identity()? {
+            anyhow::bail!("Expected distinct identities for two anonymous connections");
+        }
+        if writer.address()? == reader.address()? {
+            anyhow::bail!("Expected distinct addresses for two connections");
+        }
+        Ok(())
+    })());
+
+    let value = 42;
+
+    let writer_identity = writer.identity().unwrap();
+    let dup = reader.clone();
+    reader.on_insert::<OneU8>(move |row, _| {
+        let run_checks = || {
+            assert_eq_or_bail!(value, row.n);
+            assert_eq_or_bail!(1, dup.count::<OneU8>());
+            // The default connection was never connected, so it must not see the row.
+            assert_eq_or_bail!(0, OneU8::count());
+            Ok(())
+        };
+        (reader_insert_result.take().unwrap())(run_checks());
+    });
+
+    writer.once_on_reducer::<InsertOneU8Args>(move |caller_id, _, status, args| {
+        let run_checks = || {
+            assert_eq_or_bail!(writer_identity, *caller_id);
+            assert_eq_or_bail!(value, args.n);
+            if !matches!(status, Status::Committed) {
+                anyhow::bail!("Unexpected status. Expected Committed but found {:?}", status);
+            }
+            Ok(())
+        };
+        writer_reducer_result(run_checks());
+    });
+
+    writer
+        .invoke(InsertOneU8Args { n: value })
+        .expect("Failed to invoke reducer");
+
+    test_counter.wait_for_all();
+
+    writer.disconnect();
+    reader.disconnect();
+}
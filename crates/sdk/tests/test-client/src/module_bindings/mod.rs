@@ -1137,3 +1137,25 @@ where
         Ok(())
     })
 }
+
+/// Connect to a database named `db_name` accessible over the internet at the URI `spacetimedb_uri`.
+///
+/// If `credentials` are supplied, they will be passed to the new connection to
+/// identify and authenticate the user. Otherwise, a set of `Credentials` will be
+/// generated by the server.
+///
+/// Unlike `connect`, which uses the default connection,
+/// this connects the caller-owned `connection`.
+#[allow(unused)]
+pub fn connect_with<IntoUri>(
+    connection: &spacetimedb_sdk::DbConnection,
+    spacetimedb_uri: IntoUri,
+    db_name: &str,
+    credentials: Option<Credentials>,
+) -> Result<()>
+where
+    IntoUri: TryInto<spacetimedb_sdk::http::Uri>,
+    <IntoUri as TryInto<spacetimedb_sdk::http::Uri>>::Error: std::error::Error + Send + Sync + 'static,
+{
+    connection.connect(spacetimedb_uri, db_name, credentials, Arc::new(Module))
+}
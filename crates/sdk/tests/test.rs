@@ -107,11 +107,21 @@ fn reauth() {
     make_test("reauth_part_2").run();
 }
 
+#[test]
+fn reconnect() {
+    make_test("reconnect").run();
+}
+
 #[test]
 fn reconnect_same_address() {
     make_test("reconnect_same_address").run();
 }
 
+#[test]
+fn multiple_connections() {
+    make_test("multiple_connections").run();
+}
+
 #[test]
 fn connect_disconnect_callbacks() {
     Test::builder()
@@ -19,6 +19,7 @@ use spacetimedb::control_db::{self, ControlDb};
 use spacetimedb::database_instance_context::DatabaseInstanceContext;
 use spacetimedb::database_instance_context_controller::DatabaseInstanceContextController;
 use spacetimedb::db::{db_metrics::DB_METRICS, Config};
+use spacetimedb::hash::Hash;
 use spacetimedb::host::EnergyQuanta;
 use spacetimedb::host::UpdateDatabaseResult;
 use spacetimedb::host::UpdateOutcome;
@@ -285,6 +286,26 @@ impl spacetimedb_client_api::ControlStateWriteAccess for StandaloneEnv {
     ) -> spacetimedb::control_db::Result<Option<UpdateDatabaseResult>> {
         let existing_db = self.control_db.get_database_by_address(&spec.address)?;
         let program_bytes_address = self.object_db.insert_object(spec.program_bytes)?;
+
+        if let Some(restore_from) = spec.restore_from {
+            if existing_db.is_some() {
+                return Err(anyhow!(
+                    "Cannot restore into existing database `{}`",
+                    spec.address.to_abbreviated_hex()
+                )
+                .into());
+            }
+            let database = Database {
+                id: 0,
+                address: spec.address,
+                identity: *identity,
+                host_type: HostType::Wasmer,
+                num_replicas: spec.num_replicas,
+                program_bytes_address,
+                publisher_address,
+            };
+            return Ok(self.restore_database(identity, database, restore_from).await?);
+        }
         let mut database = match existing_db.as_ref() {
             Some(existing) => Database {
                 address: spec.address,
@@ -329,7 +350,7 @@ impl spacetimedb_client_api::ControlStateWriteAccess for StandaloneEnv {
                 .control_db
                 .get_leader_database_instance_by_database(database_id)
                 .ok_or_else(|| anyhow!("Not found: leader instance for database {database_id}"))?;
-            Ok(self.update_database_instance(leader).await?)
+            Ok(self.update_database_instance(leader, spec.drop_orphaned_tables).await?)
         } else {
             Ok(None)
         }
@@ -425,9 +446,11 @@ impl StandaloneEnv {
     async fn update_database_instance(
         &self,
         database_instance: DatabaseInstance,
+        drop_orphaned_tables: bool,
     ) -> Result<Option<UpdateDatabaseResult>, anyhow::Error> {
         self.control_db.update_database_instance(database_instance.clone())?;
-        self.on_update_database_instance(&database_instance).await
+        self.on_update_database_instance(&database_instance, drop_orphaned_tables)
+            .await
     }
 
     async fn delete_database_instance(&self, database_instance_id: u64) -> Result<(), anyhow::Error> {
@@ -466,6 +489,94 @@ impl StandaloneEnv {
         Ok(())
     }
 
+    /// Create `database`, with the state of the database `restore_from.address`
+    /// as of the commit `restore_from.target`.
+    ///
+    /// The restored database runs the module recorded in its state. If the
+    /// module requested via `database.program_bytes_address` is a different
+    /// one, the database is subsequently updated to it.
+    async fn restore_database(
+        &self,
+        identity: &Identity,
+        mut database: Database,
+        restore_from: spacetimedb_client_api::RestorePoint,
+    ) -> Result<Option<UpdateDatabaseResult>, anyhow::Error> {
+        let source = self
+            .control_db
+            .get_database_by_address(&restore_from.address)?
+            .ok_or_else(|| anyhow!("Not found: database {}", restore_from.address.to_abbreviated_hex()))?;
+        if &source.identity != identity {
+            return Err(anyhow!(
+                "Permission denied: `{}` does not own database `{}`",
+                identity.to_hex(),
+                restore_from.address.to_abbreviated_hex()
+            ));
+        }
+        let source_instance = self
+            .control_db
+            .get_leader_database_instance_by_database(source.id)
+            .ok_or_else(|| anyhow!("Not found: leader instance for database {}", source.id))?;
+        let source_ctx = self
+            .load_module_host_context(source.clone(), source_instance.id)
+            .await?;
+
+        let requested_program = database.program_bytes_address;
+        database.host_type = source.host_type;
+        database.id = self.control_db.insert_database(database.clone())?;
+        let mut instance = DatabaseInstance {
+            id: 0,
+            database_id: database.id,
+            node_id: 0,
+            leader: true,
+        };
+        instance.id = self.control_db.insert_database_instance(instance.clone())?;
+
+        // `spawn_blocking` because we're accessing the filesystem
+        let res = tokio::task::spawn_blocking({
+            let database = database.clone();
+            let config = self.config;
+            let instance_id = instance.id;
+            move || {
+                let source = &source_ctx.dbic.relational_db;
+                let commit_offset = match restore_from.target {
+                    spacetimedb_client_api::RestoreTarget::CommitOffset(commit_offset) => commit_offset,
+                    spacetimedb_client_api::RestoreTarget::Timestamp(timestamp) => {
+                        source.commit_offset_at(timestamp)?
+                    }
+                };
+                DatabaseInstanceContext::fork(
+                    config,
+                    &database,
+                    instance_id,
+                    stdb_path("worker_node/database_instances"),
+                    source,
+                    commit_offset,
+                )
+            }
+        })
+        .await?;
+        if let Err(e) = res {
+            self.control_db.delete_database_instance(instance.id)?;
+            self.control_db.delete_database(database.id)?;
+            return Err(e.into());
+        }
+
+        // Open the restored database to find out which module it ran.
+        let ctx = self.load_module_host_context(database.clone(), instance.id).await?;
+        if let Some(hash) = program_hash(&ctx).await? {
+            database.program_bytes_address = hash;
+            self.control_db.update_database(database.clone())?;
+        }
+        self.on_insert_database_instance(&instance).await?;
+
+        if database.program_bytes_address != requested_program {
+            database.program_bytes_address = requested_program;
+            self.control_db.update_database(database)?;
+            return self.update_database_instance(instance, false).await;
+        }
+        Ok(None)
+    }
+
     async fn schedule_replicas(&self, database_id: u64, num_replicas: u32) -> Result<(), anyhow::Error> {
         // Just scheduling a bunch of replicas to the only machine
         for i in 0..num_replicas {
@@ -504,45 +615,33 @@ impl StandaloneEnv {
                 )
             })?;
         let ctx = self.load_module_host_context(database.clone(), instance.id).await?;
-        let stdb = &ctx.dbic.relational_db;
-        let tx = stdb.begin_tx();
-        match stdb.program_hash(&tx) {
-            Err(e) => {
-                stdb.rollback_tx(tx);
-                Err(e.into())
-            }
-
-            Ok(maybe_hash) => {
-                // Release tx due to locking semantics and acquire a control db
-                // lock instead.
-                stdb.commit_tx(tx)?;
-                let lock = self.lock_database_instance_for_update(instance.id)?;
-
-                if let Some(hash) = maybe_hash {
-                    ensure!(
-                        hash == database.program_bytes_address,
-                        "database already initialized with module {} (requested: {})",
-                        hash,
-                        database.program_bytes_address,
-                    );
-                    if !self.host_controller.has_module_host(&ctx) {
-                        log::info!("Re-spawing database (module: {})", hash);
-                        self.host_controller.spawn_module_host(ctx).await?;
-                    } else {
-                        log::info!("Database already initialized with module {}", hash);
-                    }
-                } else {
-                    self.host_controller.init_module_host(lock.token() as u128, ctx).await?;
-                }
-
-                Ok(())
+        let maybe_hash = program_hash(&ctx).await?;
+        let lock = self.lock_database_instance_for_update(instance.id)?;
+
+        if let Some(hash) = maybe_hash {
+            ensure!(
+                hash == database.program_bytes_address,
+                "database already initialized with module {} (requested: {})",
+                hash,
+                database.program_bytes_address,
+            );
+            if !self.host_controller.has_module_host(&ctx) {
+                log::info!("Re-spawing database (module: {})", hash);
+                self.host_controller.spawn_module_host(ctx).await?;
+            } else {
+                log::info!("Database already initialized with module {}", hash);
             }
+        } else {
+            self.host_controller.init_module_host(lock.token() as u128, ctx).await?;
         }
+
+        Ok(())
     }
 
     async fn on_update_database_instance(
         &self,
         instance: &DatabaseInstance,
+        drop_orphaned_tables: bool,
     ) -> Result<Option<UpdateDatabaseResult>, anyhow::Error> {
         let database = self
             .control_db
@@ -556,46 +655,32 @@ impl StandaloneEnv {
             })?;
 
         let ctx = self.load_module_host_context(database.clone(), instance.id).await?;
-        let stdb = &ctx.dbic.relational_db;
-        let tx = stdb.begin_tx();
-
-        match stdb.program_hash(&tx) {
-            Err(e) => {
-                stdb.rollback_tx(tx);
-                Err(e.into())
+        let maybe_hash = program_hash(&ctx).await?;
+        let lock = self.lock_database_instance_for_update(instance.id)?;
+
+        match maybe_hash {
+            None => {
+                log::warn!(
+                    "Update requested on non-initialized database, initializing with module {}",
+                    database.program_bytes_address
+                );
+                self.host_controller.init_module_host(lock.token() as u128, ctx).await?;
+                Ok(None)
             }
-
-            Ok(maybe_hash) => {
-                // Release tx due to locking semantics and acquire a control db
-                // lock instead.
-                stdb.commit_tx(tx)?;
-                let lock = self.lock_database_instance_for_update(instance.id)?;
-
-                match maybe_hash {
-                    None => {
-                        log::warn!(
-                            "Update requested on non-initialized database, initializing with module {}",
-                            database.program_bytes_address
-                        );
-                        self.host_controller.init_module_host(lock.token() as u128, ctx).await?;
-                        Ok(None)
-                    }
-                    Some(hash) if hash == database.program_bytes_address => {
-                        log::info!("Database up-to-date with module {}", hash);
-                        Ok(None)
-                    }
-                    Some(hash) => {
-                        log::info!("Updating database from {} to {}", hash, database.program_bytes_address);
-                        let UpdateOutcome {
-                            module_host: _,
-                            update_result,
-                        } = self
-                            .host_controller
-                            .update_module_host(lock.token() as u128, ctx)
-                            .await?;
-                        Ok(Some(update_result))
-                    }
-                }
+            Some(hash) if hash == database.program_bytes_address => {
+                log::info!("Database up-to-date with module {}", hash);
+                Ok(None)
+            }
+            Some(hash) => {
+                log::info!("Updating database from {} to {}", hash, database.program_bytes_address);
+                let UpdateOutcome {
+                    module_host: _,
+                    update_result,
+                } = self
+                    .host_controller
+                    .update_module_host(lock.token() as u128, ctx, drop_orphaned_tables)
+                    .await?;
+                Ok(Some(update_result))
             }
         }
     }
@@ -610,14 +695,11 @@ impl StandaloneEnv {
         // database instances which have been deleted. This will just drop
         // them from memory, but will not remove them from disk.  We need
         // some kind of database lifecycle manager long term.
-        let (_, scheduler) = self.db_inst_ctx_controller.remove(instance_id).unzip();
+        self.db_inst_ctx_controller.remove(instance_id);
         self.host_controller
             .delete_module_host(lock.token() as u128, instance_id)
             .await
             .unwrap();
-        if let Some(scheduler) = scheduler {
-            scheduler.clear();
-        }
 
         Ok(())
     }
@@ -647,11 +729,11 @@ impl StandaloneEnv {
                 // `spawn_blocking` because we're accessing the filesystem
                 let (dbic, (scheduler, scheduler_starter)) = tokio::task::spawn_blocking({
                     let database = database.clone();
-                    let path = root_db_path.clone();
+                    let path = root_db_path;
                     let config = self.config;
                     move || -> anyhow::Result<_> {
                         let dbic = DatabaseInstanceContext::from_database(config, &database, instance_id, path);
-                        let sched = Scheduler::open(dbic.scheduler_db_path(root_db_path))?;
+                        let sched = Scheduler::open(dbic.relational_db.clone());
                         Ok((dbic, sched))
                     }
                 })
@@ -673,6 +755,15 @@ impl StandaloneEnv {
     }
 }
 
+/// Reads the hash of the module the database of `ctx` runs.
+///
+/// This waits for the transaction in progress, if there's one, so it runs on a blocking thread.
+async fn program_hash(ctx: &ModuleHostContext) -> anyhow::Result<Option<Hash>> {
+    let stdb = ctx.dbic.relational_db.clone();
+    let hash = tokio::task::spawn_blocking(move || stdb.with_read_only(|tx| stdb.program_hash(tx))).await??;
+    Ok(hash)
+}
+
 pub async fn exec_subcommand(cmd: &str, args: &ArgMatches) -> Result<(), anyhow::Error> {
     match cmd {
         "start" => start::exec(args).await,
@@ -146,6 +146,8 @@ impl CompiledModule {
                 address: db_address,
                 program_bytes,
                 num_replicas: 1,
+                restore_from: None,
+                drop_orphaned_tables: false,
             },
         )
         .await
@@ -35,6 +35,8 @@ pub enum ErrorVm {
     Auth(#[from] AuthError),
     #[error("Unsupported: {0}")]
     Unsupported(String),
+    #[error("Arithmetic overflow computing {0}")]
+    Overflow(String),
     #[error("{0}")]
     Other(#[from] anyhow::Error),
 }
@@ -128,6 +130,7 @@ impl From<ErrorVm> for ErrorLang {
             ErrorVm::Other(err) => ErrorLang::new(ErrorKind::Db, Some(&err.to_string())),
             ErrorVm::Rel(err) => ErrorLang::new(ErrorKind::Db, Some(&err.to_string())),
             ErrorVm::Unsupported(err) => ErrorLang::new(ErrorKind::Compiler, Some(&err)),
+            ErrorVm::Overflow(_) => ErrorLang::new(ErrorKind::OutOfBounds, Some(&err.to_string())),
             ErrorVm::Lang(err) => err,
             ErrorVm::Auth(err) => ErrorLang::new(ErrorKind::Unauthorized, Some(&err.to_string())),
         }
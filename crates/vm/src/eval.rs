@@ -7,11 +7,11 @@ use spacetimedb_sats::{product, ProductType, ProductValue};
 
 use crate::dsl::{bin_op, call_fn, if_, mem_table, scalar, var};
 use crate::errors::{ErrorKind, ErrorLang, ErrorType, ErrorVm};
+use crate::expr::{Aggregate, Function, Limit, Query, Sort};
 use crate::expr::{
     Code, CrudCode, CrudExpr, CrudExprOpt, Expr, ExprOpt, FunctionOpt, QueryCode, QueryExpr, QueryExprOpt, SourceExpr,
     SourceExprOpt, TyExpr,
 };
-use crate::expr::{Function, Query};
 use crate::functions::{Args, Param};
 use crate::operator::*;
 use crate::program::ProgramVm;
@@ -95,6 +95,9 @@ fn build_typed<P: ProgramVm>(p: &mut P, node: Expr) -> ExprOpt {
                             FieldExpr::Value(x) => {
                                 row.push(x);
                             }
+                            FieldExpr::Param { name, .. } => {
+                                unreachable!("Unbound parameter in insert: {}", name)
+                            }
                         }
                     }
                     rows.push(ProductValue::new(&row))
@@ -132,6 +135,10 @@ fn build_typed<P: ProgramVm>(p: &mut P, node: Expr) -> ExprOpt {
                 kind,
                 table_access,
             })),
+            CrudExpr::Explain { .. } => ExprOpt::Halt(ErrorLang::new(
+                ErrorKind::Compiler,
+                Some("EXPLAIN is not supported by the vm"),
+            )),
         },
         x => {
             todo!("{:?}", x)
@@ -408,6 +415,9 @@ pub fn build_query(mut result: Box<IterRows>, query: Vec<Query>) -> Result<Box<I
             Query::IndexScan(_) => {
                 panic!("index scans unsupported on memory tables")
             }
+            Query::IndexSeek(_) => {
+                panic!("index seeks unsupported on memory tables")
+            }
             Query::IndexJoin(_) => {
                 panic!("index joins unsupported on memory tables")
             }
@@ -425,6 +435,18 @@ pub fn build_query(mut result: Box<IterRows>, query: Vec<Query>) -> Result<Box<I
                     Box::new(iter)
                 }
             }
+            Query::Aggregate(Aggregate { group_by, aggregates }) => {
+                let iter = result.group_by(&group_by, &aggregates)?;
+                Box::new(iter)
+            }
+            Query::Sort(Sort { keys, top }) => {
+                let iter = result.sort(&keys, top)?;
+                Box::new(iter)
+            }
+            Query::Limit(Limit { offset, limit }) => {
+                let iter = result.limit(offset, limit);
+                Box::new(iter)
+            }
             Query::JoinInner(q) => {
                 //Pick the smaller set to be at the left
                 let col_lhs = FieldExpr::Name(q.col_lhs);
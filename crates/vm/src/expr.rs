@@ -19,6 +19,7 @@ use std::ops::Bound;
 use crate::errors::{ErrorKind, ErrorLang, ErrorType, ErrorVm};
 use crate::functions::{FunDef, Param};
 use crate::operator::{Op, OpCmp, OpLogic, OpQuery};
+use crate::ops::shared::like;
 use crate::types::Ty;
 
 /// A `index` into the list of [Fun]
@@ -140,6 +141,8 @@ impl ColumnOp {
                     OpCmp::LtEq => lhs <= rhs,
                     OpCmp::Gt => lhs > rhs,
                     OpCmp::GtEq => lhs >= rhs,
+                    OpCmp::Like => like(&lhs, &rhs),
+                    OpCmp::NotLike => !like(&lhs, &rhs),
                 })
             }
             OpQuery::Logic(op) => {
@@ -255,7 +258,8 @@ impl From<IndexScan> for ColumnOp {
                 lhs: field.into(),
                 rhs: value.into(),
             },
-            (Bound::Unbounded, Bound::Unbounded) => unreachable!(),
+            // A scan of the whole index, used for its order, doesn't filter any rows
+            (Bound::Unbounded, Bound::Unbounded) => ColumnOp::Field(FieldExpr::Value(AlgebraicValue::Bool(true))),
             (lower_bound, upper_bound) => {
                 let lhs = IndexScan {
                     table: table.clone(),
@@ -279,6 +283,28 @@ impl From<IndexScan> for ColumnOp {
     }
 }
 
+impl From<IndexSeek> for ColumnOp {
+    fn from(value: IndexSeek) -> Self {
+        let field = value.table.head.fields[value.col_id.idx()].field.clone();
+        // field = value OR field = value ...
+        value
+            .values
+            .into_iter()
+            .map(|value| ColumnOp::Cmp {
+                op: OpQuery::Cmp(OpCmp::Eq),
+                lhs: field.clone().into(),
+                rhs: value.into(),
+            })
+            .reduce(|lhs, rhs| ColumnOp::Cmp {
+                op: OpQuery::Logic(OpLogic::Or),
+                lhs: lhs.into(),
+                rhs: rhs.into(),
+            })
+            // Seeking no values finds no rows
+            .unwrap_or(ColumnOp::Field(FieldExpr::Value(AlgebraicValue::Bool(false))))
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord, From)]
 pub enum SourceExpr {
     MemTable(MemTable),
@@ -383,6 +409,105 @@ impl JoinExpr {
     }
 }
 
+/// The aggregate functions of `SELECT`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash)]
+pub enum AggregateFn {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+impl AggregateFn {
+    /// Returns the type of the result of the function applied to values of type `ty`,
+    /// or `None` if the function can't be applied to them.
+    ///
+    /// `SUM` widens integers to 64 bits (keeping their signedness) and floats to `F64`.
+    /// `MIN`, `MAX` and `AVG` are options, as they are `NULL` (`none`) over no rows.
+    pub fn result_type(&self, ty: &AlgebraicType) -> Option<AlgebraicType> {
+        let is_signed = matches!(
+            ty,
+            &AlgebraicType::I8 | &AlgebraicType::I16 | &AlgebraicType::I32 | &AlgebraicType::I64
+        );
+        let is_unsigned = matches!(
+            ty,
+            &AlgebraicType::U8 | &AlgebraicType::U16 | &AlgebraicType::U32 | &AlgebraicType::U64
+        );
+        let is_float = matches!(ty, &AlgebraicType::F32 | &AlgebraicType::F64);
+        let is_wide = matches!(ty, &AlgebraicType::I128 | &AlgebraicType::U128);
+
+        match self {
+            AggregateFn::Count => Some(AlgebraicType::U64),
+            AggregateFn::Min | AggregateFn::Max => Some(AlgebraicType::option(ty.clone())),
+            AggregateFn::Sum if is_signed => Some(AlgebraicType::I64),
+            AggregateFn::Sum if is_unsigned => Some(AlgebraicType::U64),
+            AggregateFn::Sum if is_float => Some(AlgebraicType::F64),
+            AggregateFn::Sum if is_wide => Some(ty.clone()),
+            AggregateFn::Avg if is_signed || is_unsigned || is_float || is_wide => {
+                Some(AlgebraicType::option(AlgebraicType::F64))
+            }
+            AggregateFn::Sum | AggregateFn::Avg => None,
+        }
+    }
+}
+
+impl fmt::Display for AggregateFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AggregateFn::Count => "count",
+            AggregateFn::Sum => "sum",
+            AggregateFn::Min => "min",
+            AggregateFn::Max => "max",
+            AggregateFn::Avg => "avg",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// An aggregate function over a column, or over the rows themselves for `COUNT(*)`.
+#[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord)]
+pub struct AggregateExpr {
+    pub func: AggregateFn,
+    /// The aggregated column, `None` for `COUNT(*)`.
+    pub field: Option<FieldName>,
+    /// The name of the resulting column.
+    pub name: FieldName,
+}
+
+/// A column to sort the rows by, in ascending or descending order.
+#[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord)]
+pub struct SortKey {
+    pub field: FieldName,
+    pub asc: bool,
+}
+
+// A descriptor for a sort.
+// If `top` is set, only that many of the first rows are needed,
+// which avoids keeping all the rows in memory.
+#[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord)]
+pub struct Sort {
+    pub keys: Vec<SortKey>,
+    pub top: Option<usize>,
+}
+
+// A descriptor for skipping the first `offset` rows, and then returning at most `limit` rows.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord)]
+pub struct Limit {
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+// A descriptor for an aggregation.
+// The rows are grouped by the `group_by` columns, and each group yields one row
+// with the `group_by` columns followed by the results of the `aggregates`.
+// Without `group_by` columns, all the rows form a single group.
+#[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord)]
+pub struct Aggregate {
+    pub group_by: Vec<FieldName>,
+    pub aggregates: Vec<AggregateExpr>,
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord)]
 pub enum DbType {
     Table,
@@ -425,6 +550,12 @@ pub enum CrudExpr {
         kind: DbType,
         table_access: StAccess,
     },
+    /// Describes how `query` would be evaluated, without running it.
+    ///
+    /// The plan depends on the state of the database, so it is rendered by the database and not by the `vm`.
+    Explain {
+        query: QueryExpr,
+    },
 }
 
 // impl AuthAccess for CrudExpr {
@@ -453,6 +584,19 @@ pub struct IndexScan {
     pub upper_bound: Bound<AlgebraicValue>,
 }
 
+impl IndexScan {
+    /// Is the range of the scan empty, like in `a >= 4 AND a <= 2`?
+    pub fn is_empty(&self) -> bool {
+        match (&self.lower_bound, &self.upper_bound) {
+            (Bound::Included(lower), Bound::Included(upper)) => lower > upper,
+            (Bound::Included(lower) | Bound::Excluded(lower), Bound::Included(upper) | Bound::Excluded(upper)) => {
+                lower >= upper
+            }
+            _ => false,
+        }
+    }
+}
+
 impl PartialOrd for IndexScan {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
@@ -510,11 +654,23 @@ impl Ord for IndexScan {
     }
 }
 
+/// Fetches the rows whose value of `col_id` is any of `values`, with an index seek for each.
+///
+/// No rows are fetched if `values` is empty.
+#[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord)]
+pub struct IndexSeek {
+    pub table: DbTable,
+    pub col_id: ColId,
+    pub values: Vec<AlgebraicValue>,
+}
+
 // An individual operation in a query.
 #[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord, From)]
 pub enum Query {
     // Fetching rows via an index.
     IndexScan(IndexScan),
+    // Fetching rows with any of several values via an index.
+    IndexSeek(IndexSeek),
     // Joining rows via an index.
     // Equivalent to Index Nested Loop Join.
     IndexJoin(IndexJoin),
@@ -530,6 +686,12 @@ pub enum Query {
     // Equivalent to a Nested Loop Join.
     // Its operands my use indexes but the join itself does not.
     JoinInner(JoinExpr),
+    // Groups rows and computes aggregate functions over each group.
+    Aggregate(Aggregate),
+    // Sorts the rows by a set of columns.
+    Sort(Sort),
+    // Skips and truncates the rows.
+    Limit(Limit),
 }
 
 impl Query {
@@ -538,8 +700,11 @@ impl Query {
     /// Sources are yielded from left to right. Duplicates are not filtered out.
     pub fn sources(&self) -> QuerySources {
         match self {
-            Self::Select(..) | Self::Project(..) => QuerySources::None,
+            Self::Select(..) | Self::Project(..) | Self::Aggregate(..) | Self::Sort(..) | Self::Limit(..) => {
+                QuerySources::None
+            }
             Self::IndexScan(scan) => QuerySources::One(Some(scan.table.clone().into())),
+            Self::IndexSeek(seek) => QuerySources::One(Some(seek.table.clone().into())),
             Self::IndexJoin(join) => QuerySources::Expr(join.probe_side.sources()),
             Self::JoinInner(join) => QuerySources::Expr(join.rhs.sources()),
         }
@@ -680,6 +845,63 @@ impl QueryExpr {
         }
     }
 
+    // Generate an index seek for each of several values, like in `a IN (1, 5)`,
+    // if this is the first operator. Otherwise generate a select.
+    // TODO: Replace these methods with a proper query optimization pass.
+    pub fn with_index_in(mut self, table: DbTable, col_id: ColId, mut values: Vec<AlgebraicValue>) -> Self {
+        // Seek each value once, in order.
+        values.sort();
+        values.dedup();
+        let seek = IndexSeek { table, col_id, values };
+        // if this is the first operator in the list, generate index seeks
+        let Some(query) = self.query.pop() else {
+            self.query.push(Query::IndexSeek(seek));
+            return self;
+        };
+        match query {
+            // try to push below join's lhs
+            Query::JoinInner(JoinExpr {
+                rhs:
+                    QueryExpr {
+                        source:
+                            SourceExpr::DbTable(DbTable {
+                                table_id: rhs_table_id, ..
+                            }),
+                        ..
+                    },
+                ..
+            }) if seek.table.table_id != rhs_table_id => {
+                self = self.with_index_in(seek.table, seek.col_id, seek.values);
+                self.query.push(query);
+                self
+            }
+            // try to push below join's rhs
+            Query::JoinInner(JoinExpr { rhs, col_lhs, col_rhs }) => {
+                self.query.push(Query::JoinInner(JoinExpr {
+                    rhs: rhs.with_index_in(seek.table, seek.col_id, seek.values),
+                    col_lhs,
+                    col_rhs,
+                }));
+                self
+            }
+            // merge with a preceding select
+            Query::Select(filter) => {
+                self.query.push(Query::Select(ColumnOp::Cmp {
+                    op: OpQuery::Logic(OpLogic::And),
+                    lhs: filter.into(),
+                    rhs: ColumnOp::from(seek).into(),
+                }));
+                self
+            }
+            // else generate a new select
+            query => {
+                self.query.push(query);
+                self.query.push(Query::Select(seek.into()));
+                self
+            }
+        }
+    }
+
     // Generate an index scan for a range predicate or try merging with a previous index scan.
     // Otherwise generate a select.
     // TODO: Replace these methods with a proper query optimization pass.
@@ -733,7 +955,7 @@ impl QueryExpr {
                 upper_bound: Bound::Included(upper),
                 ..
             }) if col_id == lhs_col_id => {
-                self.query.push(Query::IndexScan(IndexScan {
+                self.query.push(Self::index_scan(IndexScan {
                     table,
                     col_id,
                     lower_bound: Self::bound(value, inclusive),
@@ -748,7 +970,7 @@ impl QueryExpr {
                 upper_bound: Bound::Excluded(upper),
                 ..
             }) if col_id == lhs_col_id => {
-                self.query.push(Query::IndexScan(IndexScan {
+                self.query.push(Self::index_scan(IndexScan {
                     table,
                     col_id,
                     lower_bound: Self::bound(value, inclusive),
@@ -841,7 +1063,7 @@ impl QueryExpr {
                 upper_bound: Bound::Unbounded,
                 ..
             }) if col_id == lhs_col_id => {
-                self.query.push(Query::IndexScan(IndexScan {
+                self.query.push(Self::index_scan(IndexScan {
                     table,
                     col_id,
                     lower_bound: Bound::Included(lower),
@@ -856,7 +1078,7 @@ impl QueryExpr {
                 upper_bound: Bound::Unbounded,
                 ..
             }) if col_id == lhs_col_id => {
-                self.query.push(Query::IndexScan(IndexScan {
+                self.query.push(Self::index_scan(IndexScan {
                     table,
                     col_id,
                     lower_bound: Bound::Excluded(lower),
@@ -973,12 +1195,57 @@ impl QueryExpr {
         x
     }
 
+    // Appends an aggregation to the query operator pipeline.
+    pub fn with_aggregate(self, group_by: &[FieldName], aggregates: &[AggregateExpr]) -> Self {
+        let mut x = self;
+        x.query.push(Query::Aggregate(Aggregate {
+            group_by: group_by.into(),
+            aggregates: aggregates.into(),
+        }));
+        x
+    }
+
+    // Appends a sort to the query operator pipeline.
+    pub fn with_sort(self, keys: &[SortKey]) -> Self {
+        let mut x = self;
+        x.query.push(Query::Sort(Sort {
+            keys: keys.into(),
+            top: None,
+        }));
+        x
+    }
+
+    // Appends a limit to the query operator pipeline.
+    // If it follows a sort, the sort only keeps the rows that are within the limit.
+    pub fn with_limit(self, limit: Limit) -> Self {
+        let mut x = self;
+        if let (Some(Query::Sort(sort)), Some(n)) = (x.query.last_mut(), limit.limit) {
+            sort.top = Some(limit.offset.saturating_add(n));
+        }
+        x.query.push(Query::Limit(limit));
+        x
+    }
+
     pub fn with_join_inner(self, with: impl Into<QueryExpr>, lhs: FieldName, rhs: FieldName) -> Self {
         let mut x = self;
         x.query.push(Query::JoinInner(JoinExpr::new(with.into(), lhs, rhs)));
         x
     }
 
+    // An index scan, or a seek of no values if the range of the scan is empty,
+    // like in `a BETWEEN 4 AND 2`, so that no rows are fetched.
+    fn index_scan(scan: IndexScan) -> Query {
+        if scan.is_empty() {
+            Query::IndexSeek(IndexSeek {
+                table: scan.table,
+                col_id: scan.col_id,
+                values: vec![],
+            })
+        } else {
+            Query::IndexScan(scan)
+        }
+    }
+
     fn bound(value: AlgebraicValue, inclusive: bool) -> Bound<AlgebraicValue> {
         if inclusive {
             Bound::Included(value)
@@ -1168,6 +1435,9 @@ impl fmt::Display for Query {
             Query::IndexScan(op) => {
                 write!(f, "index_scan {:?}", op)
             }
+            Query::IndexSeek(op) => {
+                write!(f, "index_seek {:?}", op)
+            }
             Query::IndexJoin(op) => {
                 write!(f, "index_join {:?}", op)
             }
@@ -1190,6 +1460,48 @@ impl fmt::Display for Query {
             Query::JoinInner(q) => {
                 write!(f, "&inner {:?} ON {} = {}", q.rhs, q.col_lhs, q.col_rhs)
             }
+            Query::Sort(q) => {
+                write!(f, "sort")?;
+                for (pos, x) in q.keys.iter().enumerate() {
+                    write!(f, " {} {}", x.field, if x.asc { "asc" } else { "desc" })?;
+                    if pos + 1 < q.keys.len() {
+                        write!(f, ",")?;
+                    }
+                }
+                if let Some(top) = q.top {
+                    write!(f, " top {top}")?;
+                }
+                Ok(())
+            }
+            Query::Limit(q) => {
+                write!(f, "limit")?;
+                if let Some(limit) = q.limit {
+                    write!(f, " {limit}")?;
+                }
+                write!(f, " offset {}", q.offset)
+            }
+            Query::Aggregate(q) => {
+                write!(f, "aggregate")?;
+                for (pos, x) in q.aggregates.iter().enumerate() {
+                    match &x.field {
+                        Some(field) => write!(f, " {}({field})", x.func)?,
+                        None => write!(f, " {}(*)", x.func)?,
+                    }
+                    if pos + 1 < q.aggregates.len() {
+                        write!(f, ",")?;
+                    }
+                }
+                if !q.group_by.is_empty() {
+                    write!(f, " group by ")?;
+                }
+                for (pos, x) in q.group_by.iter().enumerate() {
+                    write!(f, "{x}")?;
+                    if pos + 1 < q.group_by.len() {
+                        write!(f, ", ")?;
+                    }
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -1482,11 +1794,16 @@ mod tests {
         // information
         [
             Query::IndexScan(IndexScan {
-                table: db_table,
+                table: db_table.clone(),
                 col_id: 42.into(),
                 lower_bound: Bound::Included(22.into()),
                 upper_bound: Bound::Unbounded,
             }),
+            Query::IndexSeek(IndexSeek {
+                table: db_table,
+                col_id: 42.into(),
+                values: vec![22.into()],
+            }),
             Query::IndexJoin(IndexJoin {
                 probe_side: mem_table.clone().into(),
                 probe_field: FieldName::Name {
@@ -2,7 +2,7 @@ use spacetimedb_sats::algebraic_value::AlgebraicValue;
 
 use crate::expr::Code;
 use crate::functions::Args;
-use crate::ops::shared::to_bool;
+use crate::ops::shared::{self, to_bool};
 use crate::program::ProgramRef;
 
 fn _bool_op<F>(args: Args<'_>, f: F) -> Code
@@ -55,6 +55,14 @@ pub(crate) fn greater_than(_p: ProgramRef<'_>, args: Args<'_>) -> Code {
     _cmp_op(args, |a, b| a >= b)
 }
 
+pub(crate) fn like(_p: ProgramRef<'_>, args: Args<'_>) -> Code {
+    _cmp_op(args, shared::like)
+}
+
+pub(crate) fn not_like(_p: ProgramRef<'_>, args: Args<'_>) -> Code {
+    _cmp_op(args, |a, b| !shared::like(a, b))
+}
+
 pub(crate) fn and(__p: ProgramRef<'_>, args: Args<'_>) -> Code {
     _bool_op(args, |a, b| a && b)
 }
@@ -11,3 +11,11 @@ where
 pub(crate) fn to_bool(of: &AlgebraicValue) -> Option<bool> {
     of.as_bool().copied()
 }
+
+/// Returns whether `of` is a `String` matching the `LIKE` `pattern`.
+pub(crate) fn like(of: &AlgebraicValue, pattern: &AlgebraicValue) -> bool {
+    match (of.as_string(), pattern.as_string()) {
+        (Some(text), Some(pattern)) => crate::operator::like(text, pattern),
+        _ => false,
+    }
+}
@@ -45,6 +45,14 @@ pub trait ProgramVm {
             OpCmp::GtEq.into(),
             env.functions.add(OpCmp::GtEq, Box::new(logic::greater_than)),
         );
+        ops.insert(
+            OpCmp::Like.into(),
+            env.functions.add(OpCmp::Like, Box::new(logic::like)),
+        );
+        ops.insert(
+            OpCmp::NotLike.into(),
+            env.functions.add(OpCmp::NotLike, Box::new(logic::not_like)),
+        );
         ops.insert(
             OpUnary::Not.into(),
             env.functions.add(OpUnary::Not, Box::new(logic::not)),
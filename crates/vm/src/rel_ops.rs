@@ -1,7 +1,12 @@
 use crate::errors::ErrorVm;
-use spacetimedb_lib::relation::{FieldExpr, Header, RelValue, RelValueRef, RowCount};
+use crate::expr::{AggregateExpr, AggregateFn, SortKey};
+use spacetimedb_lib::error::RelationError;
+use spacetimedb_lib::relation::{Column, FieldExpr, FieldName, Header, RelValue, RelValueRef, RowCount};
+use spacetimedb_sats::algebraic_value::AlgebraicValue;
 use spacetimedb_sats::product_value::ProductValue;
-use std::collections::HashMap;
+use spacetimedb_sats::AlgebraicType;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
 
 pub(crate) trait ResultExt<T> {
     fn unpack_fold(self) -> Result<T, ErrorVm>;
@@ -100,6 +105,106 @@ pub trait RelOps {
         Ok(JoinInner::new(head, self, with, key_lhs, key_rhs, predicate, project))
     }
 
+    /// Creates an `Iterator` that groups the rows by the values of the `group_by` columns,
+    /// yielding a row per group with those columns followed by the results of the `aggregates`.
+    ///
+    /// Without `group_by` columns, all the rows form a single group, which is yielded even if empty.
+    /// `MIN`, `MAX` and `AVG` are then `NULL` (`none`), see [AggregateFn::result_type].
+    ///
+    /// The groups are yielded in the order of their `group_by` values.
+    ///
+    /// Note:
+    ///
+    /// It is the equivalent of a `GROUP BY` clause on SQL.
+    #[inline]
+    fn group_by(self, group_by: &[FieldName], aggregates: &[AggregateExpr]) -> Result<GroupBy<Self>, ErrorVm>
+    where
+        Self: Sized,
+    {
+        let head = self.head();
+        let column_pos = |field: &FieldName| {
+            head.column_pos(field)
+                .ok_or_else(|| RelationError::FieldNotFound(head.clone(), field.clone()))
+        };
+
+        let mut fields = Vec::with_capacity(group_by.len() + aggregates.len());
+        let mut keys = Vec::with_capacity(group_by.len());
+        for field in group_by {
+            let pos = column_pos(field)?;
+            fields.push(head.fields[pos].clone());
+            keys.push(pos);
+        }
+
+        let mut funcs = Vec::with_capacity(aggregates.len());
+        for x in aggregates {
+            let (pos, ty) = match &x.field {
+                Some(field) => {
+                    let pos = column_pos(field)?;
+                    (Some(pos), &head.fields[pos].algebraic_type)
+                }
+                None => (None, &AlgebraicType::U64),
+            };
+            let ty = x.func.result_type(ty).ok_or_else(|| {
+                ErrorVm::Unsupported(format!("Can't compute `{}` over a column of type {ty:?}", x.func))
+            })?;
+            fields.push(Column::new(x.name.clone(), ty.clone()));
+            funcs.push((x.func, pos, ty));
+        }
+
+        let head = Header::new(head.table_name.clone(), fields);
+        Ok(GroupBy::new(self, head, keys, funcs))
+    }
+
+    /// Creates an `Iterator` that yields the rows ordered by the `keys` columns,
+    /// keeping the relative order of the rows that compare equal.
+    ///
+    /// If `top` is set, only that many of the first rows are yielded,
+    /// and only those are kept in memory while sorting.
+    ///
+    /// Note:
+    ///
+    /// It is the equivalent of a `ORDER BY` clause on SQL.
+    #[inline]
+    fn sort(self, keys: &[SortKey], top: Option<usize>) -> Result<Sort<Self>, ErrorVm>
+    where
+        Self: Sized,
+    {
+        let head = self.head();
+        let mut cols = Vec::with_capacity(keys.len());
+        for key in keys {
+            let pos = head
+                .column_pos(&key.field)
+                .ok_or_else(|| RelationError::FieldNotFound(head.clone(), key.field.clone()))?;
+            cols.push((pos, key.asc));
+        }
+
+        let head = head.clone();
+        Ok(Sort::new(self, head, cols, top))
+    }
+
+    /// Creates an `Iterator` that skips the first `offset` rows, and then yields at most `limit` rows.
+    ///
+    /// Note:
+    ///
+    /// It is the equivalent of the `LIMIT` & `OFFSET` clauses on SQL.
+    #[inline]
+    fn limit(self, offset: usize, limit: Option<usize>) -> Limit<Self>
+    where
+        Self: Sized,
+    {
+        let head = self.head().clone();
+        let count = self.row_count();
+        let max = match (count.max, limit) {
+            (Some(max), Some(limit)) => Some(max.saturating_sub(offset).min(limit)),
+            (max, limit) => max.map(|max| max.saturating_sub(offset)).or(limit),
+        };
+        let count = RowCount {
+            min: count.min.saturating_sub(offset).min(limit.unwrap_or(usize::MAX)),
+            max,
+        };
+        Limit::new(self, count, head, offset, limit)
+    }
+
     /// Utility to collect the results into a [Vec]
     #[inline]
     fn collect_vec(mut self) -> Result<Vec<RelValue>, ErrorVm>
@@ -315,3 +420,365 @@ where
         }
     }
 }
+
+/// The running state of an [AggregateFn] over a group of rows.
+#[derive(Clone, Debug)]
+enum Accumulator {
+    Count(u64),
+    SumSigned(i128),
+    SumUnsigned(u128),
+    SumFloat(f64),
+    Min(Option<AlgebraicValue>),
+    Max(Option<AlgebraicValue>),
+    Avg { sum: f64, count: u64 },
+}
+
+impl Accumulator {
+    fn new(func: AggregateFn, ty: &AlgebraicType) -> Self {
+        match func {
+            AggregateFn::Count => Self::Count(0),
+            AggregateFn::Sum if ty == &AlgebraicType::F64 => Self::SumFloat(0.0),
+            AggregateFn::Sum if ty == &AlgebraicType::U64 || ty == &AlgebraicType::U128 => Self::SumUnsigned(0),
+            AggregateFn::Sum => Self::SumSigned(0),
+            AggregateFn::Min => Self::Min(None),
+            AggregateFn::Max => Self::Max(None),
+            AggregateFn::Avg => Self::Avg { sum: 0.0, count: 0 },
+        }
+    }
+
+    fn add(&mut self, value: Option<&AlgebraicValue>) -> Result<(), ErrorVm> {
+        let overflow = || ErrorVm::Overflow("SUM".into());
+        match (self, value) {
+            (Self::Count(count), _) => *count += 1,
+            (Self::SumSigned(sum), Some(x)) => *sum = sum.checked_add(as_i128(x)).ok_or_else(overflow)?,
+            (Self::SumUnsigned(sum), Some(x)) => *sum = sum.checked_add(as_u128(x)).ok_or_else(overflow)?,
+            (Self::SumFloat(sum), Some(x)) => *sum += as_f64(x),
+            (Self::Min(min), Some(x)) => {
+                if min.as_ref().map_or(true, |min| x < min) {
+                    *min = Some(x.clone());
+                }
+            }
+            (Self::Max(max), Some(x)) => {
+                if max.as_ref().map_or(true, |max| x > max) {
+                    *max = Some(x.clone());
+                }
+            }
+            (Self::Avg { sum, count }, Some(x)) => {
+                *sum += as_f64(x);
+                *count += 1;
+            }
+            (_, None) => unreachable!("Only `COUNT(*)` is computed without a column"),
+        }
+        Ok(())
+    }
+
+    /// Returns the result of the aggregate as a value of type `ty`,
+    /// which is `none` when it is not defined, e.g. the `MIN` of no rows.
+    fn finish(self, ty: &AlgebraicType) -> Result<AlgebraicValue, ErrorVm> {
+        let overflow = || ErrorVm::Overflow("SUM".into());
+        Ok(match self {
+            Self::Count(count) => count.into(),
+            Self::SumSigned(sum) if ty == &AlgebraicType::I64 => i64::try_from(sum).map_err(|_| overflow())?.into(),
+            Self::SumSigned(sum) => sum.into(),
+            Self::SumUnsigned(sum) if ty == &AlgebraicType::U64 => u64::try_from(sum).map_err(|_| overflow())?.into(),
+            Self::SumUnsigned(sum) => sum.into(),
+            Self::SumFloat(sum) => sum.into(),
+            Self::Min(x) | Self::Max(x) => x.map_or_else(AlgebraicValue::OptionNone, AlgebraicValue::OptionSome),
+            Self::Avg { count: 0, .. } => AlgebraicValue::OptionNone(),
+            Self::Avg { sum, count } => AlgebraicValue::OptionSome((sum / count as f64).into()),
+        })
+    }
+}
+
+fn as_i128(x: &AlgebraicValue) -> i128 {
+    match *x {
+        AlgebraicValue::I8(x) => x.into(),
+        AlgebraicValue::I16(x) => x.into(),
+        AlgebraicValue::I32(x) => x.into(),
+        AlgebraicValue::I64(x) => x.into(),
+        AlgebraicValue::I128(x) => x,
+        ref x => unreachable!("Not a signed integer: {x:?}"),
+    }
+}
+
+fn as_u128(x: &AlgebraicValue) -> u128 {
+    match *x {
+        AlgebraicValue::U8(x) => x.into(),
+        AlgebraicValue::U16(x) => x.into(),
+        AlgebraicValue::U32(x) => x.into(),
+        AlgebraicValue::U64(x) => x.into(),
+        AlgebraicValue::U128(x) => x,
+        ref x => unreachable!("Not an unsigned integer: {x:?}"),
+    }
+}
+
+fn as_f64(x: &AlgebraicValue) -> f64 {
+    match *x {
+        AlgebraicValue::F32(x) => x.into_inner().into(),
+        AlgebraicValue::F64(x) => x.into_inner(),
+        AlgebraicValue::U8(_) | AlgebraicValue::U16(_) | AlgebraicValue::U32(_) | AlgebraicValue::U64(_) => {
+            as_u128(x) as f64
+        }
+        AlgebraicValue::U128(x) => x as f64,
+        _ => as_i128(x) as f64,
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GroupBy<I> {
+    pub(crate) head: Header,
+    pub(crate) iter: I,
+    keys: Vec<usize>,
+    aggregates: Vec<(AggregateFn, Option<usize>, AlgebraicType)>,
+    groups: Option<std::vec::IntoIter<ProductValue>>,
+}
+
+impl<I> GroupBy<I> {
+    pub fn new(
+        iter: I,
+        head: Header,
+        keys: Vec<usize>,
+        aggregates: Vec<(AggregateFn, Option<usize>, AlgebraicType)>,
+    ) -> GroupBy<I> {
+        GroupBy {
+            head,
+            iter,
+            keys,
+            aggregates,
+            groups: None,
+        }
+    }
+}
+
+impl<I: RelOps> GroupBy<I> {
+    fn fill(&mut self) -> Result<Vec<ProductValue>, ErrorVm> {
+        let init = || {
+            self.aggregates
+                .iter()
+                .map(|(func, _, ty)| Accumulator::new(*func, ty))
+                .collect::<Vec<_>>()
+        };
+
+        let mut groups = BTreeMap::new();
+        if self.keys.is_empty() {
+            groups.insert(ProductValue::new(&[]), init());
+        }
+        while let Some(row) = self.iter.next()? {
+            let key: ProductValue = self.keys.iter().map(|pos| row.data.elements[*pos].clone()).collect();
+            let group = groups.entry(key).or_insert_with(init);
+            for (acc, (_, pos, _)) in group.iter_mut().zip(&self.aggregates) {
+                acc.add(pos.map(|pos| &row.data.elements[pos]))?;
+            }
+        }
+
+        let mut rows = Vec::with_capacity(groups.len());
+        for (key, group) in groups {
+            let mut row = key.elements;
+            for (acc, (_, _, ty)) in group.into_iter().zip(&self.aggregates) {
+                row.push(acc.finish(ty)?);
+            }
+            rows.push(row.into_iter().collect());
+        }
+        Ok(rows)
+    }
+}
+
+impl<I: RelOps> RelOps for GroupBy<I> {
+    fn head(&self) -> &Header {
+        &self.head
+    }
+
+    fn row_count(&self) -> RowCount {
+        RowCount::unknown()
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn next(&mut self) -> Result<Option<RelValue>, ErrorVm> {
+        if self.groups.is_none() {
+            self.groups = Some(self.fill()?.into_iter());
+        }
+        Ok(self
+            .groups
+            .as_mut()
+            .and_then(|x| x.next())
+            .map(|x| RelValue::new(x, None)))
+    }
+}
+
+/// The value of a sort key, ordered according to the direction of the sort.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+enum SortValue {
+    Asc(AlgebraicValue),
+    Desc(Reverse<AlgebraicValue>),
+}
+
+/// A row being sorted, ordered by its sort keys and then by its position in the input.
+#[derive(Clone, Debug)]
+struct SortRow {
+    keys: Vec<SortValue>,
+    pos: usize,
+    row: RelValue,
+}
+
+impl PartialEq for SortRow {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for SortRow {}
+
+impl PartialOrd for SortRow {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SortRow {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (&self.keys, self.pos).cmp(&(&other.keys, other.pos))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Sort<I> {
+    pub(crate) head: Header,
+    pub(crate) iter: I,
+    cols: Vec<(usize, bool)>,
+    top: Option<usize>,
+    rows: Option<std::vec::IntoIter<SortRow>>,
+}
+
+impl<I> Sort<I> {
+    pub fn new(iter: I, head: Header, cols: Vec<(usize, bool)>, top: Option<usize>) -> Sort<I> {
+        Sort {
+            head,
+            iter,
+            cols,
+            top,
+            rows: None,
+        }
+    }
+}
+
+impl<I: RelOps> Sort<I> {
+    fn fill(&mut self) -> Result<Vec<SortRow>, ErrorVm> {
+        let estimate = self.iter.row_count().min;
+        let mut pos = 0;
+        let mut next = || -> Result<Option<SortRow>, ErrorVm> {
+            let Some(row) = self.iter.next()? else {
+                return Ok(None);
+            };
+            let keys = self
+                .cols
+                .iter()
+                .map(|&(col, asc)| {
+                    let value = row.data.elements[col].clone();
+                    if asc {
+                        SortValue::Asc(value)
+                    } else {
+                        SortValue::Desc(Reverse(value))
+                    }
+                })
+                .collect();
+            pos += 1;
+            Ok(Some(SortRow { keys, pos, row }))
+        };
+
+        let Some(top) = self.top else {
+            let mut rows = Vec::with_capacity(estimate);
+            while let Some(row) = next()? {
+                rows.push(row);
+            }
+            rows.sort();
+            return Ok(rows);
+        };
+
+        // Keep the `top` smallest rows, evicting the largest one when there are too many.
+        let mut heap = BinaryHeap::with_capacity(top.saturating_add(1).min(1024));
+        while let Some(row) = next()? {
+            if top == 0 {
+                continue;
+            }
+            heap.push(row);
+            if heap.len() > top {
+                heap.pop();
+            }
+        }
+        Ok(heap.into_sorted_vec())
+    }
+}
+
+impl<I: RelOps> RelOps for Sort<I> {
+    fn head(&self) -> &Header {
+        &self.head
+    }
+
+    fn row_count(&self) -> RowCount {
+        let count = self.iter.row_count();
+        match self.top {
+            Some(top) => RowCount {
+                min: count.min.min(top),
+                max: Some(count.max.map_or(top, |max| max.min(top))),
+            },
+            None => count,
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn next(&mut self) -> Result<Option<RelValue>, ErrorVm> {
+        if self.rows.is_none() {
+            self.rows = Some(self.fill()?.into_iter());
+        }
+        Ok(self.rows.as_mut().and_then(|x| x.next()).map(|x| x.row))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Limit<I> {
+    pub(crate) head: Header,
+    pub(crate) count: RowCount,
+    pub(crate) iter: I,
+    offset: usize,
+    limit: Option<usize>,
+}
+
+impl<I> Limit<I> {
+    pub fn new(iter: I, count: RowCount, head: Header, offset: usize, limit: Option<usize>) -> Limit<I> {
+        Limit {
+            head,
+            count,
+            iter,
+            offset,
+            limit,
+        }
+    }
+}
+
+impl<I: RelOps> RelOps for Limit<I> {
+    fn head(&self) -> &Header {
+        &self.head
+    }
+
+    fn row_count(&self) -> RowCount {
+        self.count
+    }
+
+    #[tracing::instrument(skip_all)]
+    fn next(&mut self) -> Result<Option<RelValue>, ErrorVm> {
+        while self.offset > 0 {
+            if self.iter.next()?.is_none() {
+                return Ok(None);
+            }
+            self.offset -= 1;
+        }
+        match &mut self.limit {
+            Some(0) => Ok(None),
+            Some(limit) => {
+                *limit -= 1;
+                self.iter.next()
+            }
+            None => self.iter.next(),
+        }
+    }
+}
@@ -65,6 +65,16 @@ pub fn repeating_test(ctx: ReducerContext, prev_time: Timestamp) {
     log::trace!("Timestamp: {:?}, Delta time: {:?}", ctx.timestamp, delta_time);
 }
 
+#[spacetimedb(reducer, cron = "0 */5 * * * *", catch_up = once)]
+pub fn cron_test(ctx: ReducerContext, slot: Timestamp) {
+    log::trace!("Timestamp: {:?}, Slot: {:?}", ctx.timestamp, slot);
+}
+
+#[spacetimedb(reducer, rate = 10s)]
+pub fn rate_test() {
+    log::trace!("Fixed rate call");
+}
+
 #[spacetimedb(reducer)]
 pub fn test(ctx: ReducerContext, arg: TestAlias, arg2: TestB, arg3: TestC) -> anyhow::Result<()> {
     log::info!("BEGIN");
@@ -110,6 +120,10 @@ pub fn test(ctx: ReducerContext, arg: TestAlias, arg2: TestB, arg3: TestC) -> an
 
     log::info!("Row count filtered by condition: {:?}", other_row_count);
 
+    let range_row_count = TestA::filter_by_x_range(arg.x + 2..arg.x + 5).count();
+
+    log::info!("Row count filtered by range: {:?}", range_row_count);
+
     log::info!("END");
     Ok(())
 }
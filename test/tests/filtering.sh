@@ -156,6 +156,41 @@ fn find_indexed_people(surname: String) {
     }
 }
 
+#[spacetimedb(reducer)]
+fn find_indexed_people_in_range(min_id: i32, max_id: i32) {
+    for person in IndexedPerson::filter_by_id_range(min_id..=max_id) {
+        println!("RANGE FOUND: id {}: {}, {}", person.id, person.surname, person.given_name);
+    }
+}
+
+// Ensure that multi-column indices can be used for lookups on all and on leading columns.
+#[spacetimedb(table)]
+#[spacetimedb(index(btree, name = "message_owner_sent", owner, sent))]
+struct Message {
+    owner: u32,
+    sent: u64,
+    text: String,
+}
+
+#[spacetimedb(reducer)]
+fn insert_message(owner: u32, sent: u64, text: String) {
+    Message::insert(Message { owner, sent, text });
+}
+
+#[spacetimedb(reducer)]
+fn find_messages_by_owner_and_sent(owner: u32, sent: u64) {
+    for message in Message::filter_by_owner_and_sent(&owner, &sent) {
+        println!("COMPOSITE FOUND: owner {}: {}", message.owner, message.text);
+    }
+}
+
+#[spacetimedb(reducer)]
+fn find_messages_by_owner(owner: u32) {
+    for message in Message::filter_by_owner(&owner) {
+        println!("PREFIX FOUND: owner {}: {}", message.owner, message.text);
+    }
+}
+
 EOF
 
 run_test cargo run publish -S -d --project-path "$PROJECT_PATH" --clear-database
@@ -252,6 +287,27 @@ run_test cargo run logs "$IDENT" 100
 [ 1 == "$(grep -c 'INDEXED FOUND: id 1: Bond, Hydrogen' "$TEST_OUT")" ]
 [ 0 == "$(grep -c 'INDEXED FOUND: id 100: Bond, Whiskey' "$TEST_OUT")" ]
 
+# Find people within a range of ids
+run_test cargo run call "$IDENT" find_indexed_people_in_range 5 79
+run_test cargo run logs "$IDENT" 100
+[ 1 == "$(grep -c 'RANGE FOUND: id 7: Bond, James' "$TEST_OUT")" ]
+[ 1 == "$(grep -c 'RANGE FOUND: id 79: Bond, Gold' "$TEST_OUT")" ]
+[ 0 == "$(grep -c 'RANGE FOUND: id 1: Bond, Hydrogen' "$TEST_OUT")" ]
+
+# Find messages by all and by leading columns of a multi-column index
+run_test cargo run call "$IDENT" insert_message 1 10 hello
+run_test cargo run call "$IDENT" insert_message 1 20 again
+run_test cargo run call "$IDENT" insert_message 2 10 other
+run_test cargo run call "$IDENT" find_messages_by_owner_and_sent 1 20
+run_test cargo run logs "$IDENT" 100
+[ 1 == "$(grep -c 'COMPOSITE FOUND: owner 1: again' "$TEST_OUT")" ]
+[ 0 == "$(grep -c 'COMPOSITE FOUND: owner 1: hello' "$TEST_OUT")" ]
+run_test cargo run call "$IDENT" find_messages_by_owner 1
+run_test cargo run logs "$IDENT" 100
+[ 1 == "$(grep -c 'PREFIX FOUND: owner 1: hello' "$TEST_OUT")" ]
+[ 1 == "$(grep -c 'PREFIX FOUND: owner 1: again' "$TEST_OUT")" ]
+[ 0 == "$(grep -c 'PREFIX FOUND: owner 2: other' "$TEST_OUT")" ]
+
 # Non-unique version; does not work yet, see db_delete codegen in SpacetimeDB\crates\bindings-macro\src\lib.rs
 # run_test cargo run call "$IDENT" insert_nonunique_person 101 Fee
 # run_test cargo run call "$IDENT" insert_nonunique_person 102 "Fi"